@@ -0,0 +1,20 @@
+-- Offline request queue for the BetCode CLI.
+--
+-- Requests made while the daemon (or the relay in front of it) is
+-- unreachable, replayed in id order the next time the CLI connects to the
+-- same target.
+
+CREATE TABLE IF NOT EXISTS queued_requests (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    -- Daemon address or relay machine the request is meant for
+    target TEXT NOT NULL,
+    session_id TEXT NOT NULL,
+    -- JSON-encoded request
+    payload TEXT NOT NULL,
+    idempotency_key TEXT NOT NULL UNIQUE,
+    attempts INTEGER NOT NULL DEFAULT 0,
+    last_error TEXT,
+    created_at INTEGER NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_queued_requests_target ON queued_requests(target, id);
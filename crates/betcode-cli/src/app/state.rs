@@ -3,6 +3,7 @@
 use std::collections::VecDeque;
 
 use crate::commands::cache::CommandCache;
+use crate::connection::ConnectionState;
 use crate::tui::fingerprint_panel::FingerprintPrompt;
 use betcode_proto::v1::AgentEvent;
 
@@ -197,6 +198,12 @@ pub struct App {
     pub detail_panel: DetailPanelState,
     /// Connection type displayed in the status panel ("local" or "relay").
     pub connection_type: String,
+    /// Requests waiting in the offline queue, as one-line summaries.
+    pub queued_requests: Vec<String>,
+    /// State of the conversation stream, shown in the header while it is down.
+    pub connection_state: ConnectionState,
+    /// Highest event sequence number seen, to resume from after a reconnect.
+    pub last_sequence: u64,
     /// Structured tracking of tool call lifecycle.
     pub tool_calls: Vec<ToolCallEntry>,
     /// Current spinner animation tick (incremented by the ticker).
@@ -255,6 +262,9 @@ impl App {
             show_status_panel: false,
             detail_panel: DetailPanelState::default(),
             connection_type: "local".to_string(),
+            queued_requests: Vec::new(),
+            connection_state: ConnectionState::Connected,
+            last_sequence: 0,
             tool_calls: Vec::new(),
             spinner_tick: 0,
             command_cache: CommandCache::new(),
@@ -491,6 +501,23 @@ impl App {
         });
     }
 
+    /// Record an event's sequence number, returning `false` if the event was
+    /// already seen.
+    ///
+    /// After a reconnect the session's missed events are fetched with
+    /// `ResumeSession` while the new stream already delivers live ones, so
+    /// the two can overlap. Events without a sequence number are always new.
+    pub const fn track_sequence(&mut self, sequence: u64) -> bool {
+        if sequence == 0 {
+            return true;
+        }
+        if sequence <= self.last_sequence {
+            return false;
+        }
+        self.last_sequence = sequence;
+        true
+    }
+
     /// Process an incoming agent event.
     #[allow(clippy::too_many_lines)]
     pub fn handle_event(&mut self, event: AgentEvent) {
@@ -899,7 +926,7 @@ impl Default for App {
 }
 
 /// Convert a `prost_types::Struct` to `serde_json::Value`.
-fn struct_to_json(s: betcode_proto::prost_types::Struct) -> serde_json::Value {
+pub fn struct_to_json(s: betcode_proto::prost_types::Struct) -> serde_json::Value {
     use betcode_proto::prost_types::value::Kind;
     fn value_to_json(v: betcode_proto::prost_types::Value) -> serde_json::Value {
         match v.kind {
@@ -1900,4 +1927,16 @@ mod tests {
             Some("subagent")
         );
     }
+
+    #[test]
+    fn track_sequence_drops_seen_events() {
+        let mut app = App::new();
+        assert!(app.track_sequence(1));
+        assert!(app.track_sequence(3));
+        assert!(!app.track_sequence(3));
+        assert!(!app.track_sequence(2));
+        assert!(app.track_sequence(0));
+        assert!(app.track_sequence(4));
+        assert_eq!(app.last_sequence, 4);
+    }
 }
@@ -1,16 +1,54 @@
-//! Auth subcommands: login, logout, status.
+//! Auth subcommands: register, login (password or SSO), logout, status.
 //!
 //! User-facing output uses writeln! to stdout (this is a CLI binary, not debug output).
 
 use std::io::{self, Write};
+use std::time::Duration;
 
 use tonic::transport::Channel;
+use tonic::{Response, Status};
 
+use betcode_proto::rate_limit::retry_after;
 use betcode_proto::v1::auth_service_client::AuthServiceClient;
 use betcode_proto::v1::{LoginRequest, RefreshTokenRequest, RegisterRequest, RevokeTokenRequest};
 
 use crate::config::{AuthConfig, CliConfig};
 use crate::relay::relay_channel;
+use crate::sso_login;
+
+/// Longest relay retry hint an auth command waits out before retrying.
+const MAX_RETRY_WAIT: Duration = Duration::from_secs(10);
+
+/// Run an auth RPC, retrying once if the relay throttles it with a retry
+/// hint of at most [`MAX_RETRY_WAIT`].
+pub(crate) async fn call_with_retry<T, F, Fut>(mut call: F) -> Result<T, Status>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<Response<T>, Status>>,
+{
+    let result = match call().await {
+        Err(status) if retry_after(&status).is_some_and(|wait| wait <= MAX_RETRY_WAIT) => {
+            let wait = retry_after(&status).unwrap_or_default();
+            let _ = writeln!(
+                io::stderr(),
+                "Relay is rate limiting requests; retrying in {}s",
+                wait.as_secs()
+            );
+            tokio::time::sleep(wait).await;
+            call().await
+        }
+        result => result,
+    };
+    result.map(Response::into_inner)
+}
+
+/// A failed auth RPC's message, with the relay's retry hint if it gave one.
+pub(crate) fn status_message(status: &Status) -> String {
+    retry_after(status).map_or_else(
+        || status.message().to_string(),
+        |wait| format!("{} (try again in {}s)", status.message(), wait.as_secs()),
+    )
+}
 
 /// Auth subcommand actions.
 #[derive(clap::Subcommand, Debug)]
@@ -30,11 +68,29 @@ pub enum AuthAction {
     /// Log in to a relay server.
     Login {
         /// Username.
-        #[arg(short, long)]
-        username: String,
+        #[arg(
+            short,
+            long,
+            required_unless_present_any = ["sso", "device"],
+            conflicts_with_all = ["sso", "device"]
+        )]
+        username: Option<String>,
         /// Password (or set `BETCODE_PASSWORD` env var).
-        #[arg(short, long, env = "BETCODE_PASSWORD")]
-        password: String,
+        #[arg(
+            short,
+            long,
+            env = "BETCODE_PASSWORD",
+            required_unless_present_any = ["sso", "device"]
+        )]
+        password: Option<String>,
+        /// Log in through the relay's identity provider (SSO).
+        #[arg(long)]
+        sso: bool,
+        /// Approve the SSO login on another device instead of opening a
+        /// browser; implies `--sso`. The default over SSH or without a
+        /// display.
+        #[arg(long)]
+        device: bool,
     },
     /// Log out and revoke tokens.
     Logout,
@@ -50,7 +106,19 @@ pub async fn run(action: AuthAction, config: &mut CliConfig) -> anyhow::Result<(
             password,
             email,
         } => register(config, &username, &password, &email).await,
-        AuthAction::Login { username, password } => login(config, &username, &password).await,
+        AuthAction::Login { sso, device, .. } if sso || device => {
+            sso_login(config, device || sso_login::is_headless()).await
+        }
+        AuthAction::Login {
+            username, password, ..
+        } => {
+            login(
+                config,
+                &username.unwrap_or_default(),
+                &password.unwrap_or_default(),
+            )
+            .await
+        }
         AuthAction::Logout => logout(config).await,
         AuthAction::Status => status(config).await,
     }
@@ -84,12 +152,21 @@ pub async fn ensure_valid_token(config: &mut CliConfig) -> anyhow::Result<()> {
         .await
         .map_err(|e| anyhow::anyhow!("Cannot reach relay: {e}"))?;
 
-    let mut client = AuthServiceClient::new(channel);
-    let resp = client
-        .refresh_token(RefreshTokenRequest { refresh_token })
-        .await
-        .map_err(|e| anyhow::anyhow!("Token refresh failed (re-login required): {}", e.message()))?
-        .into_inner();
+    let client = AuthServiceClient::new(channel);
+    let resp = call_with_retry(|| {
+        let mut client = client.clone();
+        let request = RefreshTokenRequest {
+            refresh_token: refresh_token.clone(),
+        };
+        async move { client.refresh_token(request).await }
+    })
+    .await
+    .map_err(|e| {
+        anyhow::anyhow!(
+            "Token refresh failed (re-login required): {}",
+            status_message(&e)
+        )
+    })?;
 
     let auth_mut = config
         .auth
@@ -145,16 +222,19 @@ async fn register(
     password: &str,
     email: &str,
 ) -> anyhow::Result<()> {
-    let mut client = auth_client(config).await?;
-    let resp = client
-        .register(RegisterRequest {
-            username: username.into(),
-            password: password.into(),
-            email: email.into(),
-        })
-        .await
-        .map_err(|e| anyhow::anyhow!("Registration failed: {}", e.message()))?
-        .into_inner();
+    let client = auth_client(config).await?;
+    let request = RegisterRequest {
+        username: username.into(),
+        password: password.into(),
+        email: email.into(),
+    };
+    let resp = call_with_retry(|| {
+        let mut client = client.clone();
+        let request = request.clone();
+        async move { client.register(request).await }
+    })
+    .await
+    .map_err(|e| anyhow::anyhow!("Registration failed: {}", status_message(&e)))?;
 
     finish_auth(
         config,
@@ -169,15 +249,18 @@ async fn register(
 }
 
 async fn login(config: &mut CliConfig, username: &str, password: &str) -> anyhow::Result<()> {
-    let mut client = auth_client(config).await?;
-    let resp = client
-        .login(LoginRequest {
-            username: username.into(),
-            password: password.into(),
-        })
-        .await
-        .map_err(|e| anyhow::anyhow!("Login failed: {}", e.message()))?
-        .into_inner();
+    let client = auth_client(config).await?;
+    let request = LoginRequest {
+        username: username.into(),
+        password: password.into(),
+    };
+    let resp = call_with_retry(|| {
+        let mut client = client.clone();
+        let request = request.clone();
+        async move { client.login(request).await }
+    })
+    .await
+    .map_err(|e| anyhow::anyhow!("Login failed: {}", status_message(&e)))?;
 
     finish_auth(
         config,
@@ -191,6 +274,23 @@ async fn login(config: &mut CliConfig, username: &str, password: &str) -> anyhow
     )
 }
 
+async fn sso_login(config: &mut CliConfig, device: bool) -> anyhow::Result<()> {
+    let client = auth_client(config).await?;
+    let resp = sso_login::login(&client, device).await?;
+
+    let username = resp.username;
+    finish_auth(
+        config,
+        &username,
+        AuthResponse {
+            user_id: resp.user_id,
+            access_token: resp.access_token,
+            refresh_token: resp.refresh_token,
+        },
+        "Logged in",
+    )
+}
+
 async fn logout(config: &mut CliConfig) -> anyhow::Result<()> {
     if let (Some(auth), Some(relay_url)) = (&config.auth, &config.relay_url)
         && let Ok(channel) = relay_channel(relay_url, config.relay_custom_ca_cert.as_deref()).await
@@ -318,6 +418,75 @@ mod tests {
         );
     }
 
+    #[tokio::test(start_paused = true)]
+    async fn short_retry_hint_is_waited_out_once() {
+        let calls = std::cell::Cell::new(0);
+        let result = call_with_retry(|| {
+            calls.set(calls.get() + 1);
+            let attempt = calls.get();
+            async move {
+                if attempt == 1 {
+                    Err(betcode_proto::rate_limit::resource_exhausted(
+                        "slow down",
+                        Duration::from_secs(2),
+                    ))
+                } else {
+                    Ok(Response::new(attempt))
+                }
+            }
+        })
+        .await;
+        assert_eq!(result.unwrap(), 2);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn long_retry_hint_is_reported_not_waited() {
+        let calls = std::cell::Cell::new(0);
+        let err = call_with_retry(|| {
+            calls.set(calls.get() + 1);
+            async {
+                Err::<Response<()>, _>(betcode_proto::rate_limit::resource_exhausted(
+                    "Account temporarily locked",
+                    Duration::from_secs(90),
+                ))
+            }
+        })
+        .await
+        .unwrap_err();
+        assert_eq!(calls.get(), 1);
+        assert_eq!(
+            status_message(&err),
+            "Account temporarily locked (try again in 90s)"
+        );
+    }
+
+    #[derive(clap::Parser, Debug)]
+    struct AuthCli {
+        #[command(subcommand)]
+        action: AuthAction,
+    }
+
+    #[test]
+    fn sso_login_needs_no_credentials() {
+        use clap::Parser;
+
+        let cli = AuthCli::try_parse_from(["auth", "login", "--sso", "--device"]).unwrap();
+        assert!(matches!(
+            cli.action,
+            AuthAction::Login {
+                sso: true,
+                device: true,
+                username: None,
+                ..
+            }
+        ));
+        assert!(AuthCli::try_parse_from(["auth", "login", "--sso", "-u", "alice"]).is_err());
+        assert!(
+            AuthCli::try_parse_from(["auth", "login", "--device", "-u", "a", "-p", "b"]).is_err()
+        );
+        assert!(AuthCli::try_parse_from(["auth", "login", "-p", "secret"]).is_err());
+    }
+
     #[test]
     fn status_shows_not_logged_in() {
         let config = CliConfig::default();
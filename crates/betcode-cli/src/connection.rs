@@ -12,21 +12,26 @@ use tracing::{error, info, warn};
 use betcode_proto::v1::{
     AddPluginRequest, AddPluginResponse, AgentEvent, AgentRequest, CancelSubagentRequest,
     CancelSubagentResponse, CancelTurnRequest, CancelTurnResponse, CompactSessionRequest,
-    CompactSessionResponse, CreateWorktreeRequest, DeleteSessionRequest, DeleteSessionResponse,
-    DisablePluginRequest, DisablePluginResponse, EnablePluginRequest, EnablePluginResponse,
-    ExecuteServiceCommandRequest, GetCommandRegistryResponse, GetIssueRequest, GetIssueResponse,
-    GetMergeRequestRequest, GetMergeRequestResponse, GetPipelineRequest, GetPipelineResponse,
-    GetPluginStatusRequest, GetPluginStatusResponse, GetRepoRequest, GetWorktreeRequest,
-    GitRepoDetail, KeyExchangeRequest, ListAgentsRequest, ListAgentsResponse, ListIssuesRequest,
-    ListIssuesResponse, ListMergeRequestsRequest, ListMergeRequestsResponse, ListPathRequest,
-    ListPathResponse, ListPipelinesRequest, ListPipelinesResponse, ListPluginsRequest,
-    ListPluginsResponse, ListReposRequest, ListReposResponse, ListSessionsRequest,
-    ListSessionsResponse, ListSubagentsRequest, ListSubagentsResponse, ListWorktreesRequest,
-    ListWorktreesResponse, RegisterRepoRequest, RemovePluginRequest, RemovePluginResponse,
-    RemoveWorktreeRequest, RemoveWorktreeResponse, RenameSessionRequest, RenameSessionResponse,
-    ResumeSessionRequest, ScanReposRequest, ServiceCommandOutput, SpawnSubagentRequest,
-    SpawnSubagentResponse, SubagentEvent, UnregisterRepoRequest, UnregisterRepoResponse,
-    UpdateRepoRequest, WatchSubagentRequest, WorktreeDetail,
+    CompactSessionResponse, CreateOrchestrationRequest, CreateOrchestrationResponse,
+    CreateWorktreeRequest, DeleteSessionRequest, DeleteSessionResponse, DisablePluginRequest,
+    DisablePluginResponse, EnablePluginRequest, EnablePluginResponse, ExecuteServiceCommandRequest,
+    GcWorktreesRequest, GcWorktreesResponse, GetCommandRegistryResponse, GetIssueRequest,
+    GetIssueResponse, GetMergeRequestRequest, GetMergeRequestResponse, GetPipelineRequest,
+    GetPipelineResponse, GetPluginStatusRequest, GetPluginStatusResponse, GetRepoRequest,
+    GetWorktreeRequest, GetWorktreeStatusRequest, GitRepoDetail, KeyExchangeRequest,
+    ListAgentsRequest, ListAgentsResponse, ListIssuesRequest, ListIssuesResponse,
+    ListMergeRequestsRequest, ListMergeRequestsResponse, ListPathRequest, ListPathResponse,
+    ListPipelinesRequest, ListPipelinesResponse, ListPluginsRequest, ListPluginsResponse,
+    ListReposRequest, ListReposResponse, ListSessionsRequest, ListSessionsResponse,
+    ListSubagentsRequest, ListSubagentsResponse, ListWorktreesRequest, ListWorktreesResponse,
+    OrchestrationEvent, OrchestrationStep, OrchestrationStrategy, PermissionDecision,
+    RegisterRepoRequest, RemovePluginRequest, RemovePluginResponse, RemoveWorktreeRequest,
+    RemoveWorktreeResponse, RenameSessionRequest, RenameSessionResponse, ResumeSessionRequest,
+    RunWorktreeSetupRequest, SandboxPolicy, ScanReposRequest, ServiceCommandOutput,
+    SpawnSubagentRequest, SpawnSubagentResponse, SubagentEvent, SubagentPermissionResponse,
+    SubagentPermissionResult, SyncStrategy, SyncWorktreeRequest, SyncWorktreeResponse,
+    UnregisterRepoRequest, UnregisterRepoResponse, UpdateRepoRequest, WatchOrchestrationRequest,
+    WatchSubagentRequest, WorktreeDetail, WorktreeSetupEvent, WorktreeStatus,
     agent_service_client::AgentServiceClient, command_service_client::CommandServiceClient,
     git_lab_service_client::GitLabServiceClient, git_repo_service_client::GitRepoServiceClient,
     subagent_service_client::SubagentServiceClient, worktree_service_client::WorktreeServiceClient,
@@ -312,7 +317,10 @@ impl DaemonConnection {
         let response = client
             .exchange_keys(request)
             .await
-            .map_err(|e| ConnectionError::RpcFailed(format!("Key exchange failed: {e}")))?;
+            .map_err(|e| match rpc_error(&e) {
+                unreachable @ ConnectionError::Unavailable(_) => unreachable,
+                _ => ConnectionError::RpcFailed(format!("Key exchange failed: {e}")),
+            })?;
 
         let resp = response.into_inner();
         let session = state
@@ -441,6 +449,16 @@ impl DaemonConnection {
         self.config.machine_id.as_deref()
     }
 
+    /// Get the connection configuration.
+    pub const fn config(&self) -> &ConnectionConfig {
+        &self.config
+    }
+
+    /// Get the configured daemon (or relay) address.
+    pub fn addr(&self) -> &str {
+        &self.config.addr
+    }
+
     /// Get the auth token for relay connections.
     pub const fn auth_token(&self) -> Option<&String> {
         self.config.auth_token.as_ref()
@@ -520,10 +538,7 @@ impl DaemonConnection {
         // Call the bidirectional streaming RPC
         let mut request = tonic::Request::new(request_stream);
         apply_relay_meta(&mut request, &auth_token, &machine_id);
-        let response = client
-            .converse(request)
-            .await
-            .map_err(|e| ConnectionError::RpcFailed(e.to_string()))?;
+        let response = client.converse(request).await.map_err(|e| rpc_error(&e))?;
 
         let mut event_stream = response.into_inner();
 
@@ -713,7 +728,7 @@ impl DaemonConnection {
         let response = client
             .rename_session(request)
             .await
-            .map_err(|e| ConnectionError::RpcFailed(e.to_string()))?;
+            .map_err(|e| rpc_error(&e))?;
 
         Ok(response.into_inner())
     }
@@ -734,7 +749,7 @@ impl DaemonConnection {
         let response = client
             .delete_session(request)
             .await
-            .map_err(|e| ConnectionError::RpcFailed(e.to_string()))?;
+            .map_err(|e| rpc_error(&e))?;
 
         Ok(response.into_inner())
     }
@@ -755,7 +770,7 @@ impl DaemonConnection {
         let response = client
             .compact_session(request)
             .await
-            .map_err(|e| ConnectionError::RpcFailed(e.to_string()))?;
+            .map_err(|e| rpc_error(&e))?;
 
         Ok(response.into_inner())
     }
@@ -771,6 +786,7 @@ impl DaemonConnection {
         repo_id: &str,
         branch: &str,
         setup_script: Option<&str>,
+        defer_setup: bool,
     ) -> Result<WorktreeDetail, ConnectionError> {
         let auth_token = self.config.auth_token.clone();
         let machine_id = self.config.machine_id.clone();
@@ -784,6 +800,7 @@ impl DaemonConnection {
             repo_id: repo_id.to_string(),
             branch: branch.to_string(),
             setup_script: setup_script.unwrap_or_default().to_string(),
+            defer_setup,
         });
         apply_relay_meta(&mut request, &auth_token, &machine_id);
         let response = client
@@ -859,6 +876,112 @@ impl DaemonConnection {
         Ok(response.into_inner())
     }
 
+    /// Get git status (dirty files, ahead/behind) of a worktree.
+    pub async fn get_worktree_status(
+        &mut self,
+        id: &str,
+    ) -> Result<WorktreeStatus, ConnectionError> {
+        let auth_token = self.config.auth_token.clone();
+        let machine_id = self.config.machine_id.clone();
+        let client = self
+            .worktree_client
+            .as_mut()
+            .ok_or(ConnectionError::NotConnected)?;
+
+        let mut request = tonic::Request::new(GetWorktreeStatusRequest { id: id.to_string() });
+        apply_relay_meta(&mut request, &auth_token, &machine_id);
+        let response = client
+            .get_worktree_status(request)
+            .await
+            .map_err(|e| ConnectionError::RpcFailed(e.to_string()))?;
+
+        Ok(response.into_inner())
+    }
+
+    /// Rebase or merge a worktree onto its base branch.
+    pub async fn sync_worktree(
+        &mut self,
+        id: &str,
+        strategy: SyncStrategy,
+        base_ref: Option<&str>,
+    ) -> Result<SyncWorktreeResponse, ConnectionError> {
+        let auth_token = self.config.auth_token.clone();
+        let machine_id = self.config.machine_id.clone();
+        let client = self
+            .worktree_client
+            .as_mut()
+            .ok_or(ConnectionError::NotConnected)?;
+
+        let mut request = tonic::Request::new(SyncWorktreeRequest {
+            id: id.to_string(),
+            strategy: strategy.into(),
+            base_ref: base_ref.unwrap_or_default().to_string(),
+        });
+        apply_relay_meta(&mut request, &auth_token, &machine_id);
+        let response = client
+            .sync_worktree(request)
+            .await
+            .map_err(|e| ConnectionError::RpcFailed(e.to_string()))?;
+
+        Ok(response.into_inner())
+    }
+
+    /// Garbage-collect merged, idle or missing worktrees.
+    pub async fn gc_worktrees(
+        &mut self,
+        repo_id: Option<&str>,
+        max_idle_secs: Option<u64>,
+        dry_run: bool,
+    ) -> Result<GcWorktreesResponse, ConnectionError> {
+        let auth_token = self.config.auth_token.clone();
+        let machine_id = self.config.machine_id.clone();
+        let client = self
+            .worktree_client
+            .as_mut()
+            .ok_or(ConnectionError::NotConnected)?;
+
+        let mut request = tonic::Request::new(GcWorktreesRequest {
+            repo_id: repo_id.unwrap_or_default().to_string(),
+            dry_run,
+            max_idle_secs: max_idle_secs.unwrap_or_default(),
+        });
+        apply_relay_meta(&mut request, &auth_token, &machine_id);
+        let response = client
+            .gc_worktrees(request)
+            .await
+            .map_err(|e| ConnectionError::RpcFailed(e.to_string()))?;
+
+        Ok(response.into_inner())
+    }
+
+    /// Run (or resume) a worktree's setup pipeline, streaming progress events.
+    pub async fn run_worktree_setup(
+        &mut self,
+        id: &str,
+        retry: bool,
+        from_step: Option<&str>,
+    ) -> Result<tonic::Streaming<WorktreeSetupEvent>, ConnectionError> {
+        let auth_token = self.config.auth_token.clone();
+        let machine_id = self.config.machine_id.clone();
+        let client = self
+            .worktree_client
+            .as_mut()
+            .ok_or(ConnectionError::NotConnected)?;
+
+        let mut request = tonic::Request::new(RunWorktreeSetupRequest {
+            id: id.to_string(),
+            retry,
+            from_step: from_step.unwrap_or_default().to_string(),
+        });
+        apply_relay_meta(&mut request, &auth_token, &machine_id);
+        let response = client
+            .run_worktree_setup(request)
+            .await
+            .map_err(|e| ConnectionError::RpcFailed(e.to_string()))?;
+
+        Ok(response.into_inner())
+    }
+
     // =========================================================================
     // Git repo operations
     // =========================================================================
@@ -979,6 +1102,7 @@ impl DaemonConnection {
         custom_path: Option<&str>,
         setup_script: Option<&str>,
         auto_gitignore: Option<bool>,
+        sandbox: Option<SandboxPolicy>,
     ) -> Result<GitRepoDetail, ConnectionError> {
         let auth_token = self.config.auth_token.clone();
         let machine_id = self.config.machine_id.clone();
@@ -995,6 +1119,7 @@ impl DaemonConnection {
             custom_path: custom_path.map(String::from),
             setup_script: setup_script.map(String::from),
             auto_gitignore,
+            sandbox,
         });
         apply_relay_meta(&mut request, &auth_token, &machine_id);
         let response = client
@@ -1559,6 +1684,88 @@ impl DaemonConnection {
         Ok(response.into_inner())
     }
 
+    /// Answer a permission request a subagent is waiting on.
+    pub async fn respond_to_subagent_permission(
+        &mut self,
+        subagent_id: &str,
+        request_id: &str,
+        decision: PermissionDecision,
+    ) -> Result<SubagentPermissionResult, ConnectionError> {
+        let auth_token = self.config.auth_token.clone();
+        let machine_id = self.config.machine_id.clone();
+        let client = self
+            .subagent_client
+            .as_mut()
+            .ok_or(ConnectionError::NotConnected)?;
+
+        let mut request = tonic::Request::new(SubagentPermissionResponse {
+            subagent_id: subagent_id.to_string(),
+            request_id: request_id.to_string(),
+            decision: decision.into(),
+        });
+        apply_relay_meta(&mut request, &auth_token, &machine_id);
+        let response = client
+            .respond_to_subagent_permission(request)
+            .await
+            .map_err(|e| ConnectionError::RpcFailed(e.to_string()))?;
+
+        Ok(response.into_inner())
+    }
+
+    /// Submit an orchestration of steps under a parent session.
+    pub async fn create_orchestration(
+        &mut self,
+        parent_session_id: &str,
+        steps: Vec<OrchestrationStep>,
+        strategy: OrchestrationStrategy,
+    ) -> Result<CreateOrchestrationResponse, ConnectionError> {
+        let auth_token = self.config.auth_token.clone();
+        let machine_id = self.config.machine_id.clone();
+        let client = self
+            .subagent_client
+            .as_mut()
+            .ok_or(ConnectionError::NotConnected)?;
+
+        let mut request = tonic::Request::new(CreateOrchestrationRequest {
+            parent_session_id: parent_session_id.to_string(),
+            steps,
+            strategy: strategy.into(),
+            // Plans carry their review settings on the reviewed step
+            max_review_iterations: 0,
+        });
+        apply_relay_meta(&mut request, &auth_token, &machine_id);
+        let response = client
+            .create_orchestration(request)
+            .await
+            .map_err(|e| ConnectionError::RpcFailed(e.to_string()))?;
+
+        Ok(response.into_inner())
+    }
+
+    /// Watch an orchestration's progress events.
+    pub async fn watch_orchestration(
+        &mut self,
+        orchestration_id: &str,
+    ) -> Result<tonic::Streaming<OrchestrationEvent>, ConnectionError> {
+        let auth_token = self.config.auth_token.clone();
+        let machine_id = self.config.machine_id.clone();
+        let client = self
+            .subagent_client
+            .as_mut()
+            .ok_or(ConnectionError::NotConnected)?;
+
+        let mut request = tonic::Request::new(WatchOrchestrationRequest {
+            orchestration_id: orchestration_id.to_string(),
+        });
+        apply_relay_meta(&mut request, &auth_token, &machine_id);
+        let response = client
+            .watch_orchestration(request)
+            .await
+            .map_err(|e| ConnectionError::RpcFailed(e.to_string()))?;
+
+        Ok(response.into_inner())
+    }
+
     // =========================================================================
     // Connection state
     // =========================================================================
@@ -1589,6 +1796,15 @@ pub enum ConnectionError {
     #[error("RPC call failed: {0}")]
     RpcFailed(String),
 
+    #[error("Daemon unavailable: {0}")]
+    Unavailable(String),
+
+    #[error("Rate limited, retry in {}s: {message}", retry_after.as_secs())]
+    RateLimited {
+        message: String,
+        retry_after: std::time::Duration,
+    },
+
     #[error("Key exchange required: relay connections require E2E encryption")]
     KeyExchangeRequired,
 
@@ -1601,6 +1817,47 @@ pub enum ConnectionError {
     FingerprintMismatch { expected: String, actual: String },
 }
 
+impl ConnectionError {
+    /// Whether the daemon (or the relay in front of it) could not be reached,
+    /// as opposed to it rejecting the request. Such requests can be queued
+    /// and retried later.
+    pub const fn is_unreachable(&self) -> bool {
+        matches!(
+            self,
+            Self::ConnectFailed(_) | Self::NotConnected | Self::Unavailable(_)
+        )
+    }
+
+    /// How long the relay asked to wait before retrying, if it throttled
+    /// the request.
+    pub const fn retry_after(&self) -> Option<std::time::Duration> {
+        match self {
+            Self::RateLimited { retry_after, .. } => Some(*retry_after),
+            _ => None,
+        }
+    }
+}
+
+/// Map a failed RPC to a `ConnectionError`.
+///
+/// `UNAVAILABLE` means the daemon is offline, unless the relay reports it has
+/// buffered the request itself, in which case retrying would apply it twice.
+/// `RESOURCE_EXHAUSTED` with a retry hint means the relay throttled it.
+pub(crate) fn rpc_error(status: &tonic::Status) -> ConnectionError {
+    match betcode_proto::rate_limit::retry_after(status) {
+        Some(retry_after) => ConnectionError::RateLimited {
+            message: status.message().to_string(),
+            retry_after,
+        },
+        None if status.code() == tonic::Code::Unavailable
+            && !status.message().contains("request buffered") =>
+        {
+            ConnectionError::Unavailable(status.message().to_string())
+        }
+        None => ConnectionError::RpcFailed(status.to_string()),
+    }
+}
+
 /// Encrypt an `AgentRequest` by serializing it, encrypting the bytes, and
 /// wrapping in a new `AgentRequest` with the `Encrypted` oneof variant.
 pub(crate) fn encrypt_agent_request(
@@ -1734,6 +1991,36 @@ mod tests {
         assert!(req.metadata().get("x-machine-id").is_none());
     }
 
+    #[test]
+    fn unavailable_status_is_unreachable_unless_buffered() {
+        let err = rpc_error(&tonic::Status::unavailable("Machine offline: m1"));
+        assert!(matches!(err, ConnectionError::Unavailable(_)));
+        assert!(err.is_unreachable());
+
+        let err = rpc_error(&tonic::Status::unavailable(
+            "Machine offline, request buffered: m1",
+        ));
+        assert!(!err.is_unreachable());
+        assert!(!rpc_error(&tonic::Status::not_found("no session")).is_unreachable());
+        assert!(ConnectionError::ConnectFailed("refused".into()).is_unreachable());
+    }
+
+    #[test]
+    fn throttled_status_carries_retry_hint() {
+        let status = betcode_proto::rate_limit::resource_exhausted(
+            "Rate limit exceeded for machine m1",
+            std::time::Duration::from_secs(3),
+        );
+        let err = rpc_error(&status);
+        assert!(matches!(err, ConnectionError::RateLimited { .. }));
+        assert_eq!(err.retry_after(), Some(std::time::Duration::from_secs(3)));
+        assert!(!err.is_unreachable());
+
+        // Without a hint it is an ordinary failure
+        let err = rpc_error(&tonic::Status::resource_exhausted("pool full"));
+        assert!(matches!(err, ConnectionError::RpcFailed(_)));
+    }
+
     #[test]
     fn new_connection_has_no_crypto() {
         let conn = DaemonConnection::new(ConnectionConfig::default());
@@ -1803,6 +2090,7 @@ mod tests {
                     content: "hello".into(),
                     attachments: vec![],
                     agent_id: String::new(),
+                    idempotency_key: String::new(),
                 },
             )),
         };
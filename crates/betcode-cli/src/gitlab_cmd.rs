@@ -34,7 +34,8 @@ pub enum GitLabAction {
 pub enum MrAction {
     /// List merge requests.
     List {
-        /// GitLab project path (e.g., "group/project").
+        /// Project path (e.g., "group/project"), "host/owner/repo", or a
+        /// registered repo ID or name (forge picked from its origin remote).
         project: String,
         /// Filter by state: opened, closed, merged, locked.
         #[arg(short, long)]
@@ -45,7 +46,7 @@ pub enum MrAction {
     },
     /// Get a single merge request.
     Get {
-        /// GitLab project path.
+        /// Project path or registered repo.
         project: String,
         /// Merge request IID.
         iid: u64,
@@ -56,7 +57,7 @@ pub enum MrAction {
 pub enum PipelineAction {
     /// List pipelines.
     List {
-        /// GitLab project path.
+        /// Project path or registered repo.
         project: String,
         /// Filter by status: running, success, failed, etc.
         #[arg(short, long)]
@@ -67,7 +68,7 @@ pub enum PipelineAction {
     },
     /// Get a single pipeline.
     Get {
-        /// GitLab project path.
+        /// Project path or registered repo.
         project: String,
         /// Pipeline ID.
         id: u64,
@@ -78,7 +79,7 @@ pub enum PipelineAction {
 pub enum IssueAction {
     /// List issues.
     List {
-        /// GitLab project path.
+        /// Project path or registered repo.
         project: String,
         /// Filter by state: opened, closed.
         #[arg(short, long)]
@@ -89,7 +90,7 @@ pub enum IssueAction {
     },
     /// Get a single issue.
     Get {
-        /// GitLab project path.
+        /// Project path or registered repo.
         project: String,
         /// Issue IID.
         iid: u64,
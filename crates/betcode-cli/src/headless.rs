@@ -1,17 +1,73 @@
 //! Headless (non-interactive) mode.
 //!
-//! Sends a prompt to the daemon and streams the response to stdout.
+//! Sends a prompt to the daemon and streams the response to stdout, either as
+//! plain text or, for scripting, as JSON (see [`OutputFormat`]). The outcome
+//! is reported as an [`ExitStatus`] whose code the binary exits with.
 
+use std::time::Instant;
+
+use serde_json::json;
 use tokio::sync::mpsc;
 use tracing::{error, info};
 
 use betcode_proto::v1::agent_event::Event;
 use betcode_proto::v1::{
-    AgentEvent, AgentRequest, PermissionDecision, PermissionResponse, UserMessage,
+    AgentEvent, AgentRequest, AgentStatus, PermissionDecision, PermissionResponse, SessionInfo,
+    TodoStatus, TodoUpdate, UserMessage, UserQuestion,
 };
 
+use crate::app::struct_to_json;
 use crate::connection::{ConnectionError, DaemonConnection};
 
+/// How headless mode writes the response to stdout.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum OutputFormat {
+    /// Assistant text on stdout, tool calls and usage on stderr.
+    #[default]
+    Text,
+    /// A single JSON object on completion: the summary plus every event.
+    Json,
+    /// Newline-delimited JSON: one object per event as it arrives, then the
+    /// summary.
+    StreamJson,
+}
+
+/// How a headless run ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExitStatus {
+    /// The turn completed.
+    Success,
+    /// The agent reported a fatal error.
+    AgentError,
+    /// The daemon could not be reached or the stream broke.
+    ConnectionFailure,
+    /// The turn completed, but at least one tool call was denied.
+    PermissionDenied,
+}
+
+impl ExitStatus {
+    /// Process exit code: 0 success, 1 agent error, 2 connection failure,
+    /// 3 permission denied.
+    pub const fn code(self) -> i32 {
+        match self {
+            Self::Success => 0,
+            Self::AgentError => 1,
+            Self::ConnectionFailure => 2,
+            Self::PermissionDenied => 3,
+        }
+    }
+
+    /// Name used in JSON output.
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Success => "success",
+            Self::AgentError => "agent_error",
+            Self::ConnectionFailure => "connection_failure",
+            Self::PermissionDenied => "permission_denied",
+        }
+    }
+}
+
 /// Headless mode configuration.
 #[derive(Debug, Clone)]
 pub struct HeadlessConfig {
@@ -25,29 +81,58 @@ pub struct HeadlessConfig {
     pub model: Option<String>,
     /// Auto-accept all permissions.
     pub auto_accept: bool,
+    /// How to write the response.
+    pub output_format: OutputFormat,
 }
 
 /// Run headless mode.
-#[allow(clippy::too_many_lines, clippy::print_stderr)]
-pub async fn run(conn: &mut DaemonConnection, config: HeadlessConfig) -> Result<(), HeadlessError> {
+///
+/// In the JSON formats the summary object is written even when the run
+/// fails, so scripts always get a result line.
+pub async fn run(
+    conn: &mut DaemonConnection,
+    config: HeadlessConfig,
+) -> Result<ExitStatus, HeadlessError> {
     // Generate session ID if not provided
     let session_id = config
         .session_id
+        .clone()
         .unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
 
+    let mut reporter = Reporter::new(config.output_format, session_id.clone());
+    let result = run_turn(conn, config, &session_id, &mut reporter).await;
+    let status = match &result {
+        Ok(()) => reporter.status(),
+        Err(e) => e.exit_status(),
+    };
+    let error = result.as_ref().err().map(ToString::to_string);
+    reporter.finish(status, error.as_deref());
+    result.map(|()| status)
+}
+
+/// Write the summary for a headless run that failed before [`run`] got
+/// going, e.g. because the daemon could not be reached. Text mode writes
+/// nothing; the caller reports the error on stderr.
+pub fn report_failure(
+    format: OutputFormat,
+    session_id: &str,
+    status: ExitStatus,
+    error: &dyn std::fmt::Display,
+) {
+    Reporter::new(format, session_id.to_string()).finish(status, Some(&error.to_string()));
+}
+
+/// Send the prompt and process events until the turn completes.
+#[allow(clippy::print_stderr)]
+async fn run_turn(
+    conn: &mut DaemonConnection,
+    config: HeadlessConfig,
+    session_id: &str,
+    reporter: &mut Reporter,
+) -> Result<(), HeadlessError> {
     // Load and display history if continuing an existing session
-    match conn.resume_session(&session_id, 0).await {
-        Ok(events) if !events.is_empty() => {
-            eprintln!(
-                "[Resuming session {} ({} events)]",
-                &session_id[..8.min(session_id.len())],
-                events.len()
-            );
-            for event in &events {
-                print_history_event(event);
-            }
-            eprintln!("[--- End of history ---]");
-        }
+    match conn.resume_session(session_id, 0).await {
+        Ok(events) if !events.is_empty() => reporter.history(session_id, &events),
         Ok(_) => {} // No history, new session
         Err(e) => {
             // Non-fatal: session may be new
@@ -66,7 +151,7 @@ pub async fn run(conn: &mut DaemonConnection, config: HeadlessConfig) -> Result<
     // Send start conversation
     request_tx
         .send(crate::connection::start_conversation_request(
-            session_id.clone(),
+            session_id.to_string(),
             config.working_directory,
             config.model.unwrap_or_default(),
         ))
@@ -81,6 +166,7 @@ pub async fn run(conn: &mut DaemonConnection, config: HeadlessConfig) -> Result<
                     content: config.prompt,
                     attachments: Vec::new(),
                     agent_id: String::new(),
+                    idempotency_key: String::new(),
                 },
             )),
         })
@@ -95,9 +181,10 @@ pub async fn run(conn: &mut DaemonConnection, config: HeadlessConfig) -> Result<
             match result {
                 Ok(event) => {
                     let done =
-                        process_headless_event(event, &request_tx, config.auto_accept).await?;
+                        process_headless_event(event, &request_tx, config.auto_accept, reporter)
+                            .await?;
                     if done {
-                        break;
+                        return Ok(());
                     }
                 }
                 Err(e) => {
@@ -106,7 +193,7 @@ pub async fn run(conn: &mut DaemonConnection, config: HeadlessConfig) -> Result<
                 }
             }
         }
-        Ok(())
+        Err(HeadlessError::StreamClosed)
     }
     .await;
 
@@ -115,13 +202,174 @@ pub async fn run(conn: &mut DaemonConnection, config: HeadlessConfig) -> Result<
 }
 
 /// Process a single event in headless mode. Returns true if done.
-#[allow(clippy::print_stdout, clippy::print_stderr)]
 async fn process_headless_event(
     event: AgentEvent,
     request_tx: &mpsc::Sender<AgentRequest>,
     auto_accept: bool,
+    reporter: &mut Reporter,
 ) -> Result<bool, HeadlessError> {
+    let decision = if let Some(Event::PermissionRequest(perm)) = &event.event {
+        let answer = if auto_accept {
+            PermissionDecision::AllowOnce
+        } else {
+            PermissionDecision::Deny
+        };
+        request_tx
+            .send(AgentRequest {
+                request: Some(betcode_proto::v1::agent_request::Request::Permission(
+                    PermissionResponse {
+                        request_id: perm.request_id.clone(),
+                        decision: answer.into(),
+                        updated_input: None,
+                        message: String::new(),
+                        idempotency_key: String::new(),
+                    },
+                )),
+            })
+            .await
+            .map_err(|_| HeadlessError::StreamClosed)?;
+        Some(answer)
+    } else {
+        None
+    };
+
+    reporter.event(&event, decision);
+
     match event.event {
+        Some(Event::Error(err)) if err.is_fatal => Err(HeadlessError::FatalError(err.message)),
+        Some(Event::TurnComplete(_)) => Ok(true),
+        Some(Event::SessionInfo(info)) => {
+            info!(session_id = %info.session_id, model = %info.model, "Session started");
+            Ok(false)
+        }
+        _ => Ok(false),
+    }
+}
+
+/// Writes events in the configured format and accumulates the summary.
+struct Reporter {
+    format: OutputFormat,
+    session_id: String,
+    started: Instant,
+    /// Assistant text of this turn.
+    text: String,
+    /// Event objects, collected for [`OutputFormat::Json`].
+    events: Vec<serde_json::Value>,
+    input_tokens: u64,
+    output_tokens: u64,
+    cost_usd: f64,
+    /// Tools whose permission requests were denied.
+    denied: Vec<String>,
+}
+
+impl Reporter {
+    fn new(format: OutputFormat, session_id: String) -> Self {
+        Self {
+            format,
+            session_id,
+            started: Instant::now(),
+            text: String::new(),
+            events: Vec::new(),
+            input_tokens: 0,
+            output_tokens: 0,
+            cost_usd: 0.0,
+            denied: Vec::new(),
+        }
+    }
+
+    /// Status of a turn that completed.
+    const fn status(&self) -> ExitStatus {
+        if self.denied.is_empty() {
+            ExitStatus::Success
+        } else {
+            ExitStatus::PermissionDenied
+        }
+    }
+
+    /// Show the session's earlier history. Only text mode does; the JSON
+    /// formats describe the new turn alone.
+    #[allow(clippy::print_stderr)]
+    fn history(&self, session_id: &str, events: &[AgentEvent]) {
+        if self.format != OutputFormat::Text {
+            return;
+        }
+        eprintln!(
+            "[Resuming session {} ({} events)]",
+            &session_id[..8.min(session_id.len())],
+            events.len()
+        );
+        for event in events {
+            print_history_event(event);
+        }
+        eprintln!("[--- End of history ---]");
+    }
+
+    /// Record and write one event of the turn. `decision` is the answer
+    /// sent for a permission request.
+    #[allow(clippy::print_stdout)]
+    fn event(&mut self, event: &AgentEvent, decision: Option<PermissionDecision>) {
+        match &event.event {
+            Some(Event::TextDelta(delta)) => self.text.push_str(&delta.text),
+            Some(Event::Usage(usage)) => {
+                self.input_tokens += u64::from(usage.input_tokens);
+                self.output_tokens += u64::from(usage.output_tokens);
+                self.cost_usd += usage.cost_usd;
+            }
+            Some(Event::PermissionRequest(perm)) if decision == Some(PermissionDecision::Deny) => {
+                self.denied.push(perm.tool_name.clone());
+            }
+            _ => {}
+        }
+
+        match self.format {
+            OutputFormat::Text => print_text_event(event, decision),
+            OutputFormat::Json => {
+                if let Some(value) = event_json(event, decision) {
+                    self.events.push(value);
+                }
+            }
+            OutputFormat::StreamJson => {
+                if let Some(value) = event_json(event, decision) {
+                    println!("{value}");
+                }
+            }
+        }
+    }
+
+    /// Write the summary object (JSON formats only).
+    #[allow(clippy::print_stdout)]
+    fn finish(self, status: ExitStatus, error: Option<&str>) {
+        let events = match self.format {
+            OutputFormat::Text => return,
+            OutputFormat::Json => Some(self.events),
+            OutputFormat::StreamJson => None,
+        };
+        let mut summary = json!({
+            "type": "result",
+            "session_id": self.session_id,
+            "exit_status": status.as_str(),
+            "exit_code": status.code(),
+            "result": self.text,
+            "cost_usd": self.cost_usd,
+            "usage": {
+                "input_tokens": self.input_tokens,
+                "output_tokens": self.output_tokens,
+            },
+            "duration_ms": u64::try_from(self.started.elapsed().as_millis()).unwrap_or(u64::MAX),
+            "permission_denials": self.denied,
+            "error": error,
+        });
+        if let Some(events) = events {
+            summary["events"] = serde_json::Value::Array(events);
+        }
+        println!("{summary}");
+    }
+}
+
+/// Print an event of the current turn in text mode.
+#[allow(clippy::print_stdout, clippy::print_stderr)]
+fn print_text_event(event: &AgentEvent, decision: Option<PermissionDecision>) {
+    match &event.event {
         Some(Event::TextDelta(delta)) => {
             print!("{}", delta.text);
         }
@@ -134,59 +382,26 @@ async fn process_headless_event(
             }
         }
         Some(Event::PermissionRequest(perm)) => {
-            if auto_accept {
+            if decision == Some(PermissionDecision::AllowOnce) {
                 eprintln!(
                     "[Auto-accepting: {} - {}]",
                     perm.tool_name, perm.description
                 );
-                request_tx
-                    .send(AgentRequest {
-                        request: Some(betcode_proto::v1::agent_request::Request::Permission(
-                            PermissionResponse {
-                                request_id: perm.request_id,
-                                decision: PermissionDecision::AllowOnce.into(),
-                                updated_input: None,
-                                message: String::new(),
-                            },
-                        )),
-                    })
-                    .await
-                    .map_err(|_| HeadlessError::StreamClosed)?;
             } else {
                 eprintln!(
                     "[Permission denied (use --yes to auto-accept): {} - {}]",
                     perm.tool_name, perm.description
                 );
-                request_tx
-                    .send(AgentRequest {
-                        request: Some(betcode_proto::v1::agent_request::Request::Permission(
-                            PermissionResponse {
-                                request_id: perm.request_id,
-                                decision: PermissionDecision::Deny.into(),
-                                updated_input: None,
-                                message: String::new(),
-                            },
-                        )),
-                    })
-                    .await
-                    .map_err(|_| HeadlessError::StreamClosed)?;
             }
         }
         Some(Event::Error(err)) => {
             eprintln!("[Error: {} - {}]", err.code, err.message);
-            if err.is_fatal {
-                return Err(HeadlessError::FatalError(err.message));
-            }
         }
         Some(Event::TurnComplete(_)) => {
             // Final newline after streamed text output
             use std::io::Write;
             let _ = std::io::stdout().flush();
             eprintln!();
-            return Ok(true);
-        }
-        Some(Event::SessionInfo(info)) => {
-            info!(session_id = %info.session_id, model = %info.model, "Session started");
         }
         Some(Event::Usage(usage)) => {
             eprintln!(
@@ -196,8 +411,149 @@ async fn process_headless_event(
         }
         _ => {}
     }
+}
+
+/// JSON object for an event of the current turn, tagged with `type`.
+///
+/// Returns `None` for events that carry nothing for a script (an encrypted
+/// envelope the connection could not open, or an empty event).
+fn event_json(
+    event: &AgentEvent,
+    decision: Option<PermissionDecision>,
+) -> Option<serde_json::Value> {
+    let mut value = match event.event.as_ref()? {
+        Event::TextDelta(delta) => json!({
+            "type": "text_delta",
+            "text": delta.text,
+            "is_complete": delta.is_complete,
+        }),
+        Event::ToolCallStart(tool) => json!({
+            "type": "tool_call_start",
+            "tool_id": tool.tool_id,
+            "tool_name": tool.tool_name,
+            "description": tool.description,
+            "input": tool.input.clone().map(struct_to_json),
+        }),
+        Event::ToolCallResult(result) => json!({
+            "type": "tool_call_result",
+            "tool_id": result.tool_id,
+            "output": result.output,
+            "is_error": result.is_error,
+            "duration_ms": result.duration_ms,
+        }),
+        Event::PermissionRequest(perm) => json!({
+            "type": "permission_request",
+            "request_id": perm.request_id,
+            "tool_name": perm.tool_name,
+            "description": perm.description,
+            "input": perm.input.clone().map(struct_to_json),
+            "decision": decision.map(|d| enum_name(d.as_str_name(), "PERMISSION_DECISION_")),
+        }),
+        Event::UserQuestion(question) => user_question_json(question),
+        Event::TodoUpdate(update) => todo_update_json(update),
+        Event::StatusChange(change) => json!({
+            "type": "status_change",
+            "status": AgentStatus::try_from(change.status)
+                .map_or_else(|_| "unspecified".to_string(), |s| enum_name(s.as_str_name(), "AGENT_STATUS_")),
+            "message": change.message,
+        }),
+        Event::SessionInfo(info) => session_info_json(info),
+        Event::Error(err) => json!({
+            "type": "error",
+            "code": err.code,
+            "message": err.message,
+            "is_fatal": err.is_fatal,
+            "details": err.details,
+        }),
+        Event::Usage(usage) => json!({
+            "type": "usage",
+            "input_tokens": usage.input_tokens,
+            "output_tokens": usage.output_tokens,
+            "cache_read_tokens": usage.cache_read_tokens,
+            "cache_creation_tokens": usage.cache_creation_tokens,
+            "cost_usd": usage.cost_usd,
+            "model": usage.model,
+            "duration_ms": usage.duration_ms,
+        }),
+        Event::PlanMode(plan) => json!({
+            "type": "plan_mode",
+            "active": plan.active,
+            "plan": plan.plan,
+        }),
+        Event::TurnComplete(complete) => json!({
+            "type": "turn_complete",
+            "stop_reason": complete.stop_reason,
+        }),
+        Event::UserInput(input) => json!({
+            "type": "user_input",
+            "content": input.content,
+        }),
+        Event::Encrypted(_) | Event::RequestApplied(_) => return None,
+    };
+    value["sequence"] = json!(event.sequence);
+    if !event.parent_tool_use_id.is_empty() {
+        value["parent_tool_use_id"] = json!(event.parent_tool_use_id);
+    }
+    Some(value)
+}
+
+/// JSON object for a question Claude asks the user.
+fn user_question_json(question: &UserQuestion) -> serde_json::Value {
+    json!({
+        "type": "user_question",
+        "question_id": question.question_id,
+        "question": question.question,
+        "options": question
+            .options
+            .iter()
+            .map(|o| json!({
+                "label": o.label,
+                "description": o.description,
+                "value": o.value,
+            }))
+            .collect::<Vec<_>>(),
+        "multi_select": question.multi_select,
+    })
+}
 
-    Ok(false)
+/// JSON object for an update of Claude's todo list.
+fn todo_update_json(update: &TodoUpdate) -> serde_json::Value {
+    json!({
+        "type": "todo_update",
+        "items": update
+            .items
+            .iter()
+            .map(|item| json!({
+                "id": item.id,
+                "content": item.content,
+                "status": TodoStatus::try_from(item.status)
+                    .map_or_else(|_| "unspecified".to_string(), |s| enum_name(s.as_str_name(), "TODO_STATUS_")),
+                "active_form": item.active_form,
+            }))
+            .collect::<Vec<_>>(),
+    })
+}
+
+/// JSON object for the session a turn runs in.
+fn session_info_json(info: &SessionInfo) -> serde_json::Value {
+    json!({
+        "type": "session_info",
+        "session_id": info.session_id,
+        "model": info.model,
+        "working_directory": info.working_directory,
+        "worktree_id": info.worktree_id,
+        "message_count": info.message_count,
+        "is_resumed": info.is_resumed,
+        "is_compacted": info.is_compacted,
+        "context_usage_percent": info.context_usage_percent,
+    })
+}
+
+/// `"AGENT_STATUS_THINKING"` with prefix `"AGENT_STATUS_"` becomes `"thinking"`.
+fn enum_name(name: &str, prefix: &str) -> String {
+    name.strip_prefix(prefix)
+        .unwrap_or(name)
+        .to_ascii_lowercase()
 }
 
 /// Print a historical event to stderr for headless context display.
@@ -246,9 +602,31 @@ pub enum HeadlessError {
     FatalError(String),
 }
 
+impl HeadlessError {
+    /// How a run that failed with this error ended.
+    pub const fn exit_status(&self) -> ExitStatus {
+        match self {
+            Self::Connection(_) | Self::StreamClosed | Self::StreamError(_) => {
+                ExitStatus::ConnectionFailure
+            }
+            Self::FatalError(_) => ExitStatus::AgentError,
+        }
+    }
+}
+
 #[cfg(test)]
+#[allow(clippy::panic, clippy::expect_used, clippy::unwrap_used)]
 mod tests {
     use super::*;
+    use betcode_proto::v1::{PermissionRequest, StatusChange, TextDelta, UsageReport};
+
+    fn event(sequence: u64, event: Event) -> AgentEvent {
+        AgentEvent {
+            sequence,
+            event: Some(event),
+            ..Default::default()
+        }
+    }
 
     #[test]
     fn headless_config_defaults() {
@@ -258,8 +636,97 @@ mod tests {
             working_directory: "/tmp".to_string(),
             model: None,
             auto_accept: false,
+            output_format: OutputFormat::default(),
         };
         assert!(!config.auto_accept);
         assert!(config.session_id.is_none());
+        assert_eq!(config.output_format, OutputFormat::Text);
+    }
+
+    #[test]
+    fn exit_codes_are_distinct() {
+        let statuses = [
+            ExitStatus::Success,
+            ExitStatus::AgentError,
+            ExitStatus::ConnectionFailure,
+            ExitStatus::PermissionDenied,
+        ];
+        let codes: Vec<i32> = statuses.iter().map(|s| s.code()).collect();
+        assert_eq!(codes, vec![0, 1, 2, 3]);
+        assert_eq!(
+            HeadlessError::StreamClosed.exit_status(),
+            ExitStatus::ConnectionFailure
+        );
+        assert_eq!(
+            HeadlessError::FatalError("boom".into()).exit_status(),
+            ExitStatus::AgentError
+        );
+    }
+
+    #[test]
+    fn event_json_tags_type_and_sequence() {
+        let value = event_json(
+            &event(
+                7,
+                Event::TextDelta(TextDelta {
+                    text: "hi".into(),
+                    is_complete: false,
+                }),
+            ),
+            None,
+        )
+        .unwrap();
+        assert_eq!(value["type"], "text_delta");
+        assert_eq!(value["text"], "hi");
+        assert_eq!(value["sequence"], 7);
+        assert!(value.get("parent_tool_use_id").is_none());
+
+        let value = event_json(
+            &event(
+                8,
+                Event::StatusChange(StatusChange {
+                    status: AgentStatus::Thinking.into(),
+                    message: String::new(),
+                }),
+            ),
+            None,
+        )
+        .unwrap();
+        assert_eq!(value["status"], "thinking");
+    }
+
+    #[test]
+    fn denied_permissions_set_exit_status() {
+        let mut reporter = Reporter::new(OutputFormat::Json, "s1".into());
+        reporter.event(
+            &event(
+                1,
+                Event::Usage(UsageReport {
+                    input_tokens: 10,
+                    output_tokens: 5,
+                    cost_usd: 0.25,
+                    ..Default::default()
+                }),
+            ),
+            None,
+        );
+        assert_eq!(reporter.status(), ExitStatus::Success);
+
+        reporter.event(
+            &event(
+                2,
+                Event::PermissionRequest(PermissionRequest {
+                    request_id: "r1".into(),
+                    tool_name: "Bash".into(),
+                    ..Default::default()
+                }),
+            ),
+            Some(PermissionDecision::Deny),
+        );
+        assert_eq!(reporter.status(), ExitStatus::PermissionDenied);
+        assert_eq!(reporter.denied, vec!["Bash".to_string()]);
+        assert_eq!(reporter.events[1]["decision"], "deny");
+        assert_eq!(reporter.input_tokens, 10);
+        assert!((reporter.cost_usd - 0.25).abs() < f64::EPSILON);
     }
 }
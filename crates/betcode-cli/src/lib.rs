@@ -14,9 +14,13 @@ pub mod gitlab_cmd;
 pub mod gitlab_fmt;
 pub mod headless;
 pub mod machine_cmd;
+pub mod offline_queue;
+pub mod orchestrate_cmd;
+pub mod orchestrate_plan;
 pub mod relay;
 pub mod repo_cmd;
 pub mod session_cmd;
+pub mod sso_login;
 pub mod subagent_cmd;
 pub mod tui;
 pub mod ui;
@@ -1,4 +1,5 @@
-//! Machine subcommands: list, switch, status.
+//! Machine subcommands: list, switch, status, the relay's offline queue,
+//! and Wake-on-LAN.
 //!
 //! User-facing output uses writeln! to stdout (this is a CLI binary, not debug output).
 
@@ -9,7 +10,9 @@ use tonic::transport::Channel;
 
 use betcode_proto::v1::machine_service_client::MachineServiceClient;
 use betcode_proto::v1::{
-    GetMachineRequest, ListMachinesRequest, MachineStatus, RegisterMachineRequest,
+    CancelBufferedRequestRequest, GetMachineRequest, ListBufferedRequestsRequest,
+    ListMachinesRequest, MachineStatus, RegisterMachineRequest, SetMachineWakeRequest,
+    WakeMachineRequest, WakeOutcome,
 };
 
 use crate::auth_cmd;
@@ -37,6 +40,51 @@ pub enum MachineAction {
     },
     /// Show active machine and its status.
     Status,
+    /// Show requests the relay is holding for an offline machine.
+    Queue {
+        /// Machine ID (defaults to the active machine).
+        #[arg(long)]
+        machine: Option<String>,
+        /// Also show requests already delivered on reconnect.
+        #[arg(long)]
+        all: bool,
+    },
+    /// Retract a request the relay has not delivered yet.
+    Cancel {
+        /// Request ID, as shown by `betcode machine queue`.
+        request_id: String,
+        /// Machine ID (defaults to the active machine).
+        #[arg(long)]
+        machine: Option<String>,
+    },
+    /// Configure how the relay wakes a machine that sleeps.
+    SetWake {
+        /// MAC address of the machine's network interface.
+        #[arg(long, required_unless_present = "off", requires = "via")]
+        mac: Option<String>,
+        /// Always-on machine on the same LAN that sends the wake packet
+        /// (its daemon must run with --allow-wake).
+        #[arg(long)]
+        via: Option<String>,
+        /// Broadcast address of the LAN (default 255.255.255.255).
+        #[arg(long)]
+        broadcast: Option<String>,
+        /// UDP port for the wake packet (default 9).
+        #[arg(long)]
+        port: Option<u16>,
+        /// Remove the wake configuration.
+        #[arg(long, conflicts_with_all = ["mac", "via", "broadcast", "port"])]
+        off: bool,
+        /// Machine ID (defaults to the active machine).
+        #[arg(long)]
+        machine: Option<String>,
+    },
+    /// Wake an offline machine now.
+    Wake {
+        /// Machine ID (defaults to the active machine).
+        #[arg(long)]
+        machine: Option<String>,
+    },
 }
 
 /// Execute a machine subcommand.
@@ -49,9 +97,41 @@ pub async fn run(action: MachineAction, config: &mut CliConfig) -> anyhow::Resul
         MachineAction::List => list(config).await,
         MachineAction::Switch { machine_id } => switch(config, &machine_id).await,
         MachineAction::Status => status(config).await,
+        MachineAction::Queue { machine, all } => queue(config, machine, all).await,
+        MachineAction::Cancel {
+            request_id,
+            machine,
+        } => cancel(config, machine, &request_id).await,
+        MachineAction::SetWake {
+            mac,
+            via,
+            broadcast,
+            port,
+            off: _,
+            machine,
+        } => {
+            let request = SetMachineWakeRequest {
+                machine_id: target_machine(config, machine)?,
+                mac: mac.unwrap_or_default(),
+                waker_machine_id: via.unwrap_or_default(),
+                broadcast: broadcast.unwrap_or_default(),
+                port: port.map_or(0, u32::from),
+            };
+            set_wake(config, request).await
+        }
+        MachineAction::Wake { machine } => wake(config, machine).await,
     }
 }
 
+/// The explicitly given machine, or the active one.
+fn target_machine(config: &CliConfig, machine: Option<String>) -> anyhow::Result<String> {
+    machine
+        .or_else(|| config.active_machine.clone())
+        .ok_or_else(|| {
+            anyhow::anyhow!("No active machine. Use --machine or `betcode machine switch`")
+        })
+}
+
 fn make_authed_request<T>(inner: T, config: &CliConfig) -> anyhow::Result<Request<T>> {
     let auth = config
         .auth
@@ -167,6 +247,125 @@ async fn switch(config: &mut CliConfig, machine_id: &str) -> anyhow::Result<()>
     Ok(())
 }
 
+async fn queue(config: &CliConfig, machine: Option<String>, all: bool) -> anyhow::Result<()> {
+    let machine_id = target_machine(config, machine)?;
+    let channel = connect_relay(config).await?;
+    let mut client = MachineServiceClient::new(channel);
+    let request = make_authed_request(
+        ListBufferedRequestsRequest {
+            machine_id: machine_id.clone(),
+            include_delivered: all,
+        },
+        config,
+    )?;
+    let resp = client.list_buffered_requests(request).await?.into_inner();
+    let mut out = io::stdout();
+    if resp.requests.is_empty() {
+        writeln!(out, "Nothing queued for {machine_id}")?;
+        return Ok(());
+    }
+    writeln!(
+        out,
+        "{:<36} {:<40} {:>8} {:<10}",
+        "REQUEST", "METHOD", "PRIORITY", "STATE"
+    )?;
+    for r in &resp.requests {
+        let state = if r.delivered_at.is_some() {
+            "delivered"
+        } else {
+            "queued"
+        };
+        writeln!(
+            out,
+            "{:<36} {:<40} {:>8} {:<10}",
+            r.request_id, r.method, r.priority, state
+        )?;
+    }
+    Ok(())
+}
+
+async fn cancel(
+    config: &CliConfig,
+    machine: Option<String>,
+    request_id: &str,
+) -> anyhow::Result<()> {
+    let machine_id = target_machine(config, machine)?;
+    let channel = connect_relay(config).await?;
+    let mut client = MachineServiceClient::new(channel);
+    let request = make_authed_request(
+        CancelBufferedRequestRequest {
+            machine_id,
+            request_id: request_id.to_string(),
+        },
+        config,
+    )?;
+    let resp = client.cancel_buffered_request(request).await?.into_inner();
+    let mut out = io::stdout();
+    if resp.cancelled {
+        writeln!(out, "Cancelled {request_id}")?;
+    } else {
+        writeln!(
+            out,
+            "{request_id} is not queued (already delivered or expired)"
+        )?;
+    }
+    Ok(())
+}
+
+async fn set_wake(config: &CliConfig, request: SetMachineWakeRequest) -> anyhow::Result<()> {
+    let machine_id = request.machine_id.clone();
+    let enabled = !request.mac.is_empty();
+    let channel = connect_relay(config).await?;
+    let mut client = MachineServiceClient::new(channel);
+    let request = make_authed_request(request, config)?;
+    let machine = client
+        .set_machine_wake(request)
+        .await?
+        .into_inner()
+        .machine
+        .unwrap_or_default();
+    let mut out = io::stdout();
+    if enabled {
+        writeln!(
+            out,
+            "{machine_id} will be woken via {} (MAC {}, broadcast {}:{})",
+            machine.metadata.get("wake_via").map_or("?", String::as_str),
+            machine.metadata.get("wake_mac").map_or("?", String::as_str),
+            machine
+                .metadata
+                .get("wake_broadcast")
+                .map_or("?", String::as_str),
+            machine
+                .metadata
+                .get("wake_port")
+                .map_or("?", String::as_str),
+        )?;
+    } else {
+        writeln!(out, "Wake-on-LAN disabled for {machine_id}")?;
+    }
+    Ok(())
+}
+
+async fn wake(config: &CliConfig, machine: Option<String>) -> anyhow::Result<()> {
+    let machine_id = target_machine(config, machine)?;
+    let channel = connect_relay(config).await?;
+    let mut client = MachineServiceClient::new(channel);
+    let request = make_authed_request(
+        WakeMachineRequest {
+            machine_id: machine_id.clone(),
+        },
+        config,
+    )?;
+    let resp = client.wake_machine(request).await?.into_inner();
+    let message = match WakeOutcome::try_from(resp.outcome) {
+        Ok(WakeOutcome::AlreadyOnline) => "is already online",
+        Ok(WakeOutcome::InProgress) => "is already being woken",
+        _ => "wake packet sent; queued requests will be delivered when it connects",
+    };
+    writeln!(io::stdout(), "{machine_id} {message}")?;
+    Ok(())
+}
+
 async fn status(config: &CliConfig) -> anyhow::Result<()> {
     let mut out = io::stdout();
     match &config.active_machine {
@@ -218,6 +417,20 @@ mod tests {
         assert_eq!(config.active_machine.as_deref(), Some("m-unit-test"));
     }
 
+    #[test]
+    fn target_machine_prefers_explicit_machine() {
+        let config = CliConfig {
+            active_machine: Some("m-active".into()),
+            ..CliConfig::default()
+        };
+        assert_eq!(
+            target_machine(&config, Some("m-other".into())).unwrap(),
+            "m-other"
+        );
+        assert_eq!(target_machine(&config, None).unwrap(), "m-active");
+        assert!(target_machine(&CliConfig::default(), None).is_err());
+    }
+
     #[tokio::test]
     async fn connect_relay_requires_relay_url() {
         let config = CliConfig::default();
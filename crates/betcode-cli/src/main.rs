@@ -6,16 +6,18 @@
 use std::io;
 
 use clap::Parser;
-use tracing::info;
+use tracing::{info, warn};
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
 use betcode_cli::auth_cmd::{self, AuthAction};
 use betcode_cli::config::CliConfig;
-use betcode_cli::connection::{ConnectionConfig, DaemonConnection};
+use betcode_cli::connection::{ConnectionConfig, ConnectionError, DaemonConnection};
 use betcode_cli::daemon_cmd::{self, DaemonAction};
 use betcode_cli::gitlab_cmd::{self, GitLabAction};
-use betcode_cli::headless::{self, HeadlessConfig};
+use betcode_cli::headless::{self, ExitStatus, HeadlessConfig, HeadlessError, OutputFormat};
 use betcode_cli::machine_cmd::{self, MachineAction};
+use betcode_cli::offline_queue::{self, OfflineQueue, QueuedRequest};
+use betcode_cli::orchestrate_cmd::{self, OrchestrateAction};
 use betcode_cli::repo_cmd::{self, RepoAction};
 use betcode_cli::session_cmd::{self, SessionAction};
 use betcode_cli::subagent_cmd::{self, SubagentAction};
@@ -65,6 +67,15 @@ struct Cli {
     #[arg(long)]
     yes: bool,
 
+    /// Headless output format: plain text, one JSON object on completion, or
+    /// newline-delimited JSON events followed by a summary
+    #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+    output_format: OutputFormat,
+
+    /// Fail instead of queueing prompts and session commands while the daemon is unreachable
+    #[arg(long)]
+    no_queue: bool,
+
     /// Subcommand to run (omit for chat mode)
     #[command(subcommand)]
     command: Option<Commands>,
@@ -93,7 +104,8 @@ enum Commands {
         #[command(subcommand)]
         action: MachineAction,
     },
-    /// GitLab project operations (MRs, pipelines, issues)
+    /// Forge project operations (MRs, pipelines, issues) on GitLab, GitHub or Gitea
+    #[command(alias = "forge")]
     Gitlab {
         #[command(subcommand)]
         action: GitLabAction,
@@ -113,6 +125,11 @@ enum Commands {
         #[command(subcommand)]
         action: SubagentAction,
     },
+    /// Run declarative multi-step orchestrations (run, validate, watch)
+    Orchestrate {
+        #[command(subcommand)]
+        action: OrchestrateAction,
+    },
 }
 
 #[tokio::main]
@@ -216,9 +233,47 @@ async fn main() -> anyhow::Result<()> {
         }
     };
 
+    // Prompts and session commands made while the daemon is unreachable are
+    // queued and replayed on the next successful connection.
+    let offline = if cli.no_queue {
+        None
+    } else {
+        offline_request(&cli)
+    };
+
+    let headless_prompt = (cli.prompt.is_some() && cli.command.is_none())
+        .then(|| (cli.output_format, cli.session.clone().unwrap_or_default()));
+
     let mut conn = DaemonConnection::new(conn_config);
-    conn.connect().await?;
+    let result = if let Err(e) = conn.connect().await {
+        queue_or_fail(&conn, offline, e.into()).await
+    } else {
+        if !cli.no_queue {
+            replay_offline_queue(&mut conn).await;
+        }
+        match dispatch(&mut conn, cli).await {
+            Err(e) => queue_or_fail(&conn, offline, e).await,
+            Ok(()) => Ok(()),
+        }
+    };
+
+    // Headless prompts exit with a code that tells scripts why they failed.
+    if let Some((format, session_id)) = headless_prompt
+        && let Err(e) = &result
+    {
+        let status = headless_exit_status(e);
+        if e.downcast_ref::<HeadlessError>().is_none() {
+            headless::report_failure(format, &session_id, status, e);
+        }
+        eprintln!("Error: {e:#}");
+        std::process::exit(status.code());
+    }
+    result
+}
 
+/// Run a subcommand, a headless prompt or the TUI against a connected daemon.
+#[allow(clippy::expect_used)]
+async fn dispatch(conn: &mut DaemonConnection, cli: Cli) -> anyhow::Result<()> {
     // Resolve --continue to a session ID
     let mut session_id = cli.session;
     if cli.continue_session && session_id.is_none() {
@@ -239,15 +294,17 @@ async fn main() -> anyhow::Result<()> {
 
     // Dispatch remaining subcommands or chat mode
     if let Some(Commands::Worktree { action }) = cli.command {
-        worktree_cmd::run(&mut conn, action).await?;
+        worktree_cmd::run(conn, action).await?;
     } else if let Some(Commands::Repo { action }) = cli.command {
-        repo_cmd::run(&mut conn, action).await?;
+        repo_cmd::run(conn, action).await?;
     } else if let Some(Commands::Gitlab { action }) = cli.command {
-        gitlab_cmd::run(&mut conn, action).await?;
+        gitlab_cmd::run(conn, action).await?;
     } else if let Some(Commands::Session { action }) = cli.command {
-        session_cmd::run(&mut conn, action).await?;
+        session_cmd::run(conn, action).await?;
     } else if let Some(Commands::Subagent { action }) = cli.command {
-        subagent_cmd::run(&mut conn, action).await?;
+        subagent_cmd::run(conn, action).await?;
+    } else if let Some(Commands::Orchestrate { action }) = cli.command {
+        orchestrate_cmd::run(conn, action).await?;
     } else if let Some(prompt) = cli.prompt {
         // Headless mode
         let working_dir = cli.working_dir.unwrap_or_else(|| {
@@ -263,13 +320,124 @@ async fn main() -> anyhow::Result<()> {
             working_directory: working_dir,
             model: cli.model,
             auto_accept: cli.yes,
+            output_format: cli.output_format,
         };
 
-        headless::run(&mut conn, config).await?;
+        let status = headless::run(conn, config).await?;
+        if status != ExitStatus::Success {
+            std::process::exit(status.code());
+        }
     } else {
         // Interactive TUI mode
-        betcode_cli::tui::run(&mut conn, &session_id, &cli.working_dir, &cli.model).await?;
+        betcode_cli::tui::run(conn, &session_id, &cli.working_dir, &cli.model).await?;
+    }
+
+    Ok(())
+}
+
+/// The request to queue if the daemon turns out to be unreachable: a session
+/// rename, delete or compaction, or a headless prompt.
+fn offline_request(cli: &Cli) -> Option<(String, QueuedRequest)> {
+    match &cli.command {
+        Some(Commands::Session { action }) => match action {
+            SessionAction::Rename { id, name } => {
+                Some((id.clone(), QueuedRequest::Rename { name: name.clone() }))
+            }
+            SessionAction::Delete { id } => Some((id.clone(), QueuedRequest::Delete)),
+            SessionAction::Compact { id } => Some((id.clone(), QueuedRequest::Compact)),
+            SessionAction::List { .. } | SessionAction::Cancel { .. } => None,
+        },
+        Some(_) => None,
+        // `--continue` needs the daemon to find the session, and scripts
+        // reading JSON output need the real result rather than "queued".
+        None if cli.continue_session || cli.output_format != OutputFormat::Text => None,
+        None => {
+            let prompt = cli.prompt.clone()?;
+            let working_directory = cli.working_dir.clone().or_else(|| {
+                std::env::current_dir()
+                    .ok()
+                    .map(|d| d.to_string_lossy().to_string())
+            })?;
+            let session_id = cli
+                .session
+                .clone()
+                .unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+            Some((
+                session_id,
+                QueuedRequest::Message {
+                    content: prompt,
+                    agent_id: String::new(),
+                    working_directory,
+                    model: cli.model.clone().unwrap_or_default(),
+                },
+            ))
+        }
     }
+}
+
+/// Exit status of a headless prompt that failed with `err`.
+fn headless_exit_status(err: &anyhow::Error) -> ExitStatus {
+    for cause in err.chain() {
+        if let Some(e) = cause.downcast_ref::<HeadlessError>() {
+            return e.exit_status();
+        }
+        if cause.downcast_ref::<ConnectionError>().is_some() {
+            return ExitStatus::ConnectionFailure;
+        }
+    }
+    ExitStatus::AgentError
+}
 
+/// Queue `offline` if `err` means the daemon could not be reached, otherwise
+/// return `err`.
+#[allow(clippy::print_stderr)]
+async fn queue_or_fail(
+    conn: &DaemonConnection,
+    offline: Option<(String, QueuedRequest)>,
+    err: anyhow::Error,
+) -> anyhow::Result<()> {
+    let unreachable = err.chain().any(|cause| {
+        cause
+            .downcast_ref::<ConnectionError>()
+            .is_some_and(ConnectionError::is_unreachable)
+    });
+    let Some((session_id, request)) = offline.filter(|_| unreachable) else {
+        return Err(err);
+    };
+
+    let queue = OfflineQueue::open_default().await?;
+    let item = queue
+        .enqueue(&offline_queue::queue_target(conn), &session_id, request)
+        .await?;
+    eprintln!("Daemon unreachable: {err}");
+    eprintln!(
+        "Queued {} for session {session_id}; it will be sent the next time betcode connects.",
+        item.request.summary()
+    );
     Ok(())
 }
+
+/// Replay requests queued while the daemon was unreachable.
+#[allow(clippy::print_stderr)]
+async fn replay_offline_queue(conn: &mut DaemonConnection) {
+    let queue = match OfflineQueue::open_default().await {
+        Ok(queue) => queue,
+        Err(e) => {
+            warn!(?e, "Failed to open offline queue");
+            return;
+        }
+    };
+    match offline_queue::replay(conn, &queue).await {
+        Ok(summary) if summary.delivered + summary.dropped + summary.remaining > 0 => {
+            eprintln!(
+                "Replayed offline queue: {} sent, {} dropped, {} still queued",
+                summary.delivered, summary.dropped, summary.remaining
+            );
+            if let Some(reason) = summary.stopped_by {
+                eprintln!("  Stopped: {reason}");
+            }
+        }
+        Ok(_) => {}
+        Err(e) => warn!(?e, "Failed to replay offline queue"),
+    }
+}
@@ -0,0 +1,680 @@
+//! Offline request queue.
+//!
+//! When the daemon (or the relay in front of it) cannot be reached, prompts,
+//! permission responses and session commands are kept in a local `SQLite`
+//! database at `~/.betcode/offline_queue.db` instead of being lost, and are
+//! replayed in order the next time the CLI connects to the same target.
+//!
+//! Every queued prompt and permission response carries an idempotency key,
+//! and stays queued until the daemon acknowledges that key. Replay cannot
+//! always tell whether a request reached the daemon before the connection
+//! dropped, so it may send one twice; the daemon applies each key once.
+//! Renames, deletes and compactions are idempotent on their own.
+//!
+//! Cancelling a turn is never queued: replayed later, it would cancel
+//! whichever turn happens to be running by then.
+
+use std::path::PathBuf;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc;
+use tracing::{info, warn};
+
+use betcode_core::db::{DatabaseError, unix_timestamp};
+use betcode_proto::v1::agent_event::Event;
+use betcode_proto::v1::agent_request::Request;
+use betcode_proto::v1::{
+    AgentEvent, AgentRequest, PermissionDecision, PermissionResponse, UserMessage,
+};
+
+use crate::config::CliConfig;
+use crate::connection::{self, ConnectionError, DaemonConnection};
+
+betcode_core::define_database!(OfflineQueue, "Offline queue migrations complete");
+
+/// How long to wait for the daemon to acknowledge a replayed stream request.
+const ACK_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Failed replays after which a request the daemon keeps refusing is dropped.
+const MAX_ATTEMPTS: i64 = 5;
+
+/// A request waiting for the daemon to become reachable.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum QueuedRequest {
+    /// A prompt for the session.
+    Message {
+        content: String,
+        #[serde(default)]
+        agent_id: String,
+        working_directory: String,
+        #[serde(default)]
+        model: String,
+    },
+    /// An answer to a pending permission request.
+    Permission {
+        request_id: String,
+        /// `PermissionDecision` as its protobuf value.
+        decision: i32,
+        #[serde(default)]
+        message: String,
+        working_directory: String,
+    },
+    /// Rename the session.
+    Rename { name: String },
+    /// Delete the session.
+    Delete,
+    /// Compact the session.
+    Compact,
+}
+
+impl QueuedRequest {
+    /// The queued form of a conversation stream request.
+    ///
+    /// Only prompts and permission responses are queued. Attachments are not
+    /// kept, and a permission response that edits the tool's input is not
+    /// queued at all since the pending request it edits may be gone by the
+    /// time it is replayed.
+    pub fn from_agent_request(
+        request: &AgentRequest,
+        working_directory: &str,
+        model: &str,
+    ) -> Option<Self> {
+        match request.request.as_ref()? {
+            Request::Message(msg) => Some(Self::Message {
+                content: msg.content.clone(),
+                agent_id: msg.agent_id.clone(),
+                working_directory: working_directory.to_string(),
+                model: model.to_string(),
+            }),
+            Request::Permission(perm) if perm.updated_input.is_none() => Some(Self::Permission {
+                request_id: perm.request_id.clone(),
+                decision: perm.decision,
+                message: perm.message.clone(),
+                working_directory: working_directory.to_string(),
+            }),
+            _ => None,
+        }
+    }
+
+    /// Whether the request is sent on a conversation stream rather than as a
+    /// unary RPC.
+    pub const fn is_streamed(&self) -> bool {
+        matches!(self, Self::Message { .. } | Self::Permission { .. })
+    }
+
+    /// One-line description for listings.
+    pub fn summary(&self) -> String {
+        match self {
+            Self::Message { content, .. } => {
+                let first_line = content.lines().next().unwrap_or_default();
+                let mut preview: String = first_line.chars().take(40).collect();
+                if preview.len() < content.len() {
+                    preview.push_str("...");
+                }
+                format!("prompt: {preview}")
+            }
+            Self::Permission { decision, .. } => {
+                let decision = PermissionDecision::try_from(*decision)
+                    .map_or_else(|_| "unknown".to_string(), |d| format!("{d:?}"));
+                format!("permission: {decision}")
+            }
+            Self::Rename { name } => format!("rename to {name:?}"),
+            Self::Delete => "delete session".to_string(),
+            Self::Compact => "compact session".to_string(),
+        }
+    }
+}
+
+/// A queued request as stored.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QueuedItem {
+    pub id: i64,
+    pub session_id: String,
+    pub request: QueuedRequest,
+    pub idempotency_key: String,
+    /// Replays that have failed so far.
+    pub attempts: i64,
+    pub last_error: Option<String>,
+    pub created_at: i64,
+}
+
+impl QueuedItem {
+    /// The `AgentRequest` to send on a conversation stream, or `None` for
+    /// unary requests.
+    pub fn to_agent_request(&self) -> Option<AgentRequest> {
+        let request = match &self.request {
+            QueuedRequest::Message {
+                content, agent_id, ..
+            } => Request::Message(UserMessage {
+                content: content.clone(),
+                attachments: Vec::new(),
+                agent_id: agent_id.clone(),
+                idempotency_key: self.idempotency_key.clone(),
+            }),
+            QueuedRequest::Permission {
+                request_id,
+                decision,
+                message,
+                ..
+            } => Request::Permission(PermissionResponse {
+                request_id: request_id.clone(),
+                decision: *decision,
+                updated_input: None,
+                message: message.clone(),
+                idempotency_key: self.idempotency_key.clone(),
+            }),
+            QueuedRequest::Rename { .. } | QueuedRequest::Delete | QueuedRequest::Compact => {
+                return None;
+            }
+        };
+        Some(AgentRequest {
+            request: Some(request),
+        })
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct QueuedRow {
+    id: i64,
+    session_id: String,
+    payload: String,
+    idempotency_key: String,
+    attempts: i64,
+    last_error: Option<String>,
+    created_at: i64,
+}
+
+/// Queue key for the daemon a connection talks to: the relay machine ID for
+/// relay connections, the daemon address otherwise.
+pub fn queue_target(conn: &DaemonConnection) -> String {
+    match conn.machine_id() {
+        Some(machine_id) if conn.is_relay() => format!("machine:{machine_id}"),
+        _ => format!("daemon:{}", conn.addr()),
+    }
+}
+
+impl OfflineQueue {
+    /// Path to the queue database: `~/.betcode/offline_queue.db`.
+    pub fn default_path() -> Option<PathBuf> {
+        CliConfig::config_dir().map(|d| d.join("offline_queue.db"))
+    }
+
+    /// Open the queue at its default path.
+    pub async fn open_default() -> Result<Self, DatabaseError> {
+        let path = Self::default_path()
+            .ok_or_else(|| DatabaseError::Io("no home directory".to_string()))?;
+        Self::open(&path).await
+    }
+
+    /// Queue a request for `target` and return it as stored.
+    pub async fn enqueue(
+        &self,
+        target: &str,
+        session_id: &str,
+        request: QueuedRequest,
+    ) -> Result<QueuedItem, DatabaseError> {
+        let payload =
+            serde_json::to_string(&request).map_err(|e| DatabaseError::Query(e.to_string()))?;
+        let idempotency_key = uuid::Uuid::new_v4().to_string();
+        let now = unix_timestamp();
+
+        let result = sqlx::query(
+            "INSERT INTO queued_requests (target, session_id, payload, idempotency_key, created_at) \
+             VALUES (?, ?, ?, ?, ?)",
+        )
+        .bind(target)
+        .bind(session_id)
+        .bind(&payload)
+        .bind(&idempotency_key)
+        .bind(now)
+        .execute(self.pool())
+        .await?;
+
+        Ok(QueuedItem {
+            id: result.last_insert_rowid(),
+            session_id: session_id.to_string(),
+            request,
+            idempotency_key,
+            attempts: 0,
+            last_error: None,
+            created_at: now,
+        })
+    }
+
+    /// Requests queued for `target`, oldest first.
+    ///
+    /// Rows that no longer decode are skipped.
+    pub async fn pending(&self, target: &str) -> Result<Vec<QueuedItem>, DatabaseError> {
+        let rows = sqlx::query_as::<_, QueuedRow>(
+            "SELECT id, session_id, payload, idempotency_key, attempts, last_error, created_at \
+             FROM queued_requests WHERE target = ? ORDER BY id",
+        )
+        .bind(target)
+        .fetch_all(self.pool())
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .filter_map(|row| match serde_json::from_str(&row.payload) {
+                Ok(request) => Some(QueuedItem {
+                    id: row.id,
+                    session_id: row.session_id,
+                    request,
+                    idempotency_key: row.idempotency_key,
+                    attempts: row.attempts,
+                    last_error: row.last_error,
+                    created_at: row.created_at,
+                }),
+                Err(e) => {
+                    warn!(id = row.id, error = %e, "Skipping undecodable queued request");
+                    None
+                }
+            })
+            .collect())
+    }
+
+    /// Number of requests queued for `target`.
+    pub async fn count(&self, target: &str) -> Result<i64, DatabaseError> {
+        let count: (i64,) = sqlx::query_as("SELECT COUNT(*) FROM queued_requests WHERE target = ?")
+            .bind(target)
+            .fetch_one(self.pool())
+            .await?;
+        Ok(count.0)
+    }
+
+    /// Remove a request from the queue.
+    pub async fn remove(&self, id: i64) -> Result<(), DatabaseError> {
+        sqlx::query("DELETE FROM queued_requests WHERE id = ?")
+            .bind(id)
+            .execute(self.pool())
+            .await?;
+        Ok(())
+    }
+
+    /// Record a failed replay of a request.
+    pub async fn record_failure(&self, id: i64, error: &str) -> Result<(), DatabaseError> {
+        sqlx::query(
+            "UPDATE queued_requests SET attempts = attempts + 1, last_error = ? WHERE id = ?",
+        )
+        .bind(error)
+        .bind(id)
+        .execute(self.pool())
+        .await?;
+        Ok(())
+    }
+}
+
+/// Outcome of a replay.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ReplaySummary {
+    /// Requests delivered and removed from the queue.
+    pub delivered: usize,
+    /// Requests the daemon refused, removed from the queue.
+    pub dropped: usize,
+    /// Requests still queued.
+    pub remaining: usize,
+    /// Why replay stopped early, if it did.
+    pub stopped_by: Option<String>,
+}
+
+/// Replay the requests queued for the daemon `conn` is connected to.
+///
+/// Requests are sent in the order they were queued; consecutive prompts and
+/// permission responses for the same session share one conversation stream.
+/// Replay stops at the first request that cannot be delivered, leaving it and
+/// everything after it queued, so later requests never overtake earlier ones.
+/// A session command the daemon rejects outright is dropped, as is any
+/// request that has failed [`MAX_ATTEMPTS`] times.
+pub async fn replay(
+    conn: &mut DaemonConnection,
+    queue: &OfflineQueue,
+) -> Result<ReplaySummary, DatabaseError> {
+    let items = queue.pending(&queue_target(conn)).await?;
+    let mut summary = ReplaySummary::default();
+    let mut rest = items.as_slice();
+
+    while let Some(first) = rest.first() {
+        let batch_len = if first.request.is_streamed() {
+            rest.iter()
+                .take_while(|i| i.request.is_streamed() && i.session_id == first.session_id)
+                .count()
+        } else {
+            1
+        };
+
+        let (delivered, result) = send_batch(conn, &rest[..batch_len]).await;
+        for item in &rest[..delivered] {
+            queue.remove(item.id).await?;
+        }
+        summary.delivered += delivered;
+        rest = &rest[delivered..];
+
+        let Err(e) = result else {
+            continue;
+        };
+        // The request that failed; those after it in the batch were not sent.
+        let Some(failed) = rest.first() else {
+            break;
+        };
+        let error = e.to_string();
+        if matches!(e, ConnectionError::RpcFailed(_)) && !failed.request.is_streamed() {
+            warn!(session_id = %failed.session_id, error = %error, "Dropping queued request refused by daemon");
+        } else if !e.is_unreachable() && failed.attempts + 1 >= MAX_ATTEMPTS {
+            warn!(session_id = %failed.session_id, error = %error, "Dropping queued request after repeated failures");
+        } else {
+            queue.record_failure(failed.id, &error).await?;
+            summary.remaining = rest.len();
+            summary.stopped_by = Some(error);
+            break;
+        }
+        queue.remove(failed.id).await?;
+        summary.dropped += 1;
+        rest = &rest[1..];
+    }
+
+    info!(
+        delivered = summary.delivered,
+        dropped = summary.dropped,
+        remaining = summary.remaining,
+        "Replayed offline queue"
+    );
+    Ok(summary)
+}
+
+/// Send one batch: a single session command, or a run of stream requests for
+/// one session.
+///
+/// Returns how many requests, from the start of the batch, were delivered,
+/// and the error that stopped the rest.
+async fn send_batch(
+    conn: &mut DaemonConnection,
+    batch: &[QueuedItem],
+) -> (usize, Result<(), ConnectionError>) {
+    let Some(first) = batch.first() else {
+        return (0, Ok(()));
+    };
+    let result = match &first.request {
+        QueuedRequest::Rename { name } => {
+            conn.rename_session(&first.session_id, name).await.map(drop)
+        }
+        QueuedRequest::Delete => conn.delete_session(&first.session_id).await.map(drop),
+        QueuedRequest::Compact => conn.compact_session(&first.session_id).await.map(drop),
+        QueuedRequest::Message {
+            working_directory,
+            model,
+            ..
+        } => return send_streamed(conn, batch, working_directory, model).await,
+        QueuedRequest::Permission {
+            working_directory, ..
+        } => return send_streamed(conn, batch, working_directory, "").await,
+    };
+    (usize::from(result.is_ok()), result)
+}
+
+/// Send stream requests on a fresh conversation stream, one at a time.
+///
+/// Each request counts as delivered once the daemon acknowledges its
+/// idempotency key, and the next one is only sent after that.
+async fn send_streamed(
+    conn: &mut DaemonConnection,
+    batch: &[QueuedItem],
+    working_directory: &str,
+    model: &str,
+) -> (usize, Result<(), ConnectionError>) {
+    let Some(first) = batch.first() else {
+        return (0, Ok(()));
+    };
+    // Keep an existing crypto session: a new key exchange would replace the
+    // keys a live conversation stream on this connection is using.
+    if !conn.has_crypto()
+        && let Err(e) = conn.ensure_relay_key_exchange().await
+    {
+        return (0, Err(e));
+    }
+    let (request_tx, mut event_rx, stream_handle) = match conn.converse().await {
+        Ok(stream) => stream,
+        Err(e) => return (0, Err(e)),
+    };
+    let closed = |_| ConnectionError::Unavailable("conversation stream closed".to_string());
+
+    let mut delivered = 0;
+    let result = async {
+        request_tx
+            .send(connection::start_conversation_request(
+                first.session_id.clone(),
+                working_directory.to_string(),
+                model.to_string(),
+            ))
+            .await
+            .map_err(closed)?;
+        for item in batch {
+            if let Some(request) = item.to_agent_request() {
+                request_tx.send(request).await.map_err(closed)?;
+                await_ack(&mut event_rx, &item.idempotency_key, ACK_TIMEOUT).await?;
+            }
+            delivered += 1;
+        }
+        Ok(())
+    }
+    .await;
+
+    stream_handle.abort();
+    (delivered, result)
+}
+
+/// Wait for the daemon to acknowledge the request tagged `key`.
+///
+/// A stream that ends first may have dropped the request, so it counts as
+/// unavailable. No acknowledgement within `timeout` means the daemon did not
+/// apply the request, which counts as a failed attempt.
+async fn await_ack(
+    event_rx: &mut mpsc::Receiver<Result<AgentEvent, tonic::Status>>,
+    key: &str,
+    timeout: Duration,
+) -> Result<(), ConnectionError> {
+    let deadline = tokio::time::sleep(timeout);
+    tokio::pin!(deadline);
+    loop {
+        tokio::select! {
+            () = &mut deadline => {
+                return Err(ConnectionError::RpcFailed(
+                    "daemon did not acknowledge the replayed request".to_string(),
+                ));
+            }
+            event = event_rx.recv() => match event {
+                Some(Ok(AgentEvent {
+                    event: Some(Event::RequestApplied(ack)),
+                    ..
+                })) if ack.idempotency_key == key => return Ok(()),
+                Some(Ok(_)) => {}
+                Some(Err(status)) => return Err(connection::rpc_error(&status)),
+                None => {
+                    return Err(ConnectionError::Unavailable(
+                        "conversation stream closed before the request was acknowledged"
+                            .to_string(),
+                    ));
+                }
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::panic, clippy::expect_used, clippy::unwrap_used)]
+mod tests {
+    use super::*;
+
+    fn message(content: &str) -> QueuedRequest {
+        QueuedRequest::Message {
+            content: content.to_string(),
+            agent_id: String::new(),
+            working_directory: "/tmp".to_string(),
+            model: String::new(),
+        }
+    }
+
+    #[tokio::test]
+    async fn enqueue_and_drain_in_order() {
+        let queue = OfflineQueue::open_in_memory().await.unwrap();
+        let first = queue
+            .enqueue("daemon:a", "s1", message("one"))
+            .await
+            .unwrap();
+        queue
+            .enqueue(
+                "daemon:a",
+                "s1",
+                QueuedRequest::Rename {
+                    name: "x".to_string(),
+                },
+            )
+            .await
+            .unwrap();
+        queue
+            .enqueue("machine:m1", "s2", QueuedRequest::Delete)
+            .await
+            .unwrap();
+
+        let pending = queue.pending("daemon:a").await.unwrap();
+        assert_eq!(pending.len(), 2);
+        assert_eq!(pending[0], first);
+        assert_eq!(pending[1].request.summary(), "rename to \"x\"");
+        assert_ne!(pending[0].idempotency_key, pending[1].idempotency_key);
+        assert_eq!(queue.count("machine:m1").await.unwrap(), 1);
+
+        queue.record_failure(first.id, "offline").await.unwrap();
+        let pending = queue.pending("daemon:a").await.unwrap();
+        assert_eq!(pending[0].attempts, 1);
+        assert_eq!(pending[0].last_error.as_deref(), Some("offline"));
+
+        queue.remove(first.id).await.unwrap();
+        assert_eq!(queue.count("daemon:a").await.unwrap(), 1);
+    }
+
+    #[test]
+    fn stream_requests_carry_idempotency_key() {
+        let item = QueuedItem {
+            id: 1,
+            session_id: "s1".to_string(),
+            request: QueuedRequest::Permission {
+                request_id: "r1".to_string(),
+                decision: PermissionDecision::AllowOnce.into(),
+                message: String::new(),
+                working_directory: "/tmp".to_string(),
+            },
+            idempotency_key: "k1".to_string(),
+            attempts: 0,
+            last_error: None,
+            created_at: 0,
+        };
+        let Some(Request::Permission(perm)) = item.to_agent_request().unwrap().request else {
+            panic!("expected a permission response");
+        };
+        assert_eq!(perm.request_id, "r1");
+        assert_eq!(perm.idempotency_key, "k1");
+        assert_eq!(item.request.summary(), "permission: AllowOnce");
+
+        let item = QueuedItem {
+            request: QueuedRequest::Compact,
+            ..item
+        };
+        assert!(item.to_agent_request().is_none());
+    }
+
+    #[test]
+    fn only_prompts_and_plain_permission_responses_are_queued() {
+        let prompt = AgentRequest {
+            request: Some(Request::Message(UserMessage {
+                content: "hi".to_string(),
+                attachments: Vec::new(),
+                agent_id: String::new(),
+                idempotency_key: String::new(),
+            })),
+        };
+        assert_eq!(
+            QueuedRequest::from_agent_request(&prompt, "/tmp", "opus"),
+            Some(QueuedRequest::Message {
+                content: "hi".to_string(),
+                agent_id: String::new(),
+                working_directory: "/tmp".to_string(),
+                model: "opus".to_string(),
+            })
+        );
+
+        let mut perm = PermissionResponse {
+            request_id: "r1".to_string(),
+            decision: PermissionDecision::Deny.into(),
+            updated_input: None,
+            message: "no".to_string(),
+            idempotency_key: String::new(),
+        };
+        let request = AgentRequest {
+            request: Some(Request::Permission(perm.clone())),
+        };
+        assert!(QueuedRequest::from_agent_request(&request, "/tmp", "").is_some());
+
+        perm.updated_input = Some(betcode_proto::prost_types::Struct::default());
+        let request = AgentRequest {
+            request: Some(Request::Permission(perm)),
+        };
+        assert!(QueuedRequest::from_agent_request(&request, "/tmp", "").is_none());
+        assert!(
+            QueuedRequest::from_agent_request(&AgentRequest { request: None }, "/tmp", "")
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn message_summary_is_truncated() {
+        assert_eq!(message("fix the build").summary(), "prompt: fix the build");
+        let summary = message(&"a".repeat(100)).summary();
+        assert_eq!(summary, format!("prompt: {}...", "a".repeat(40)));
+        assert_eq!(
+            message("line one\nline two").summary(),
+            "prompt: line one..."
+        );
+    }
+
+    fn ack(key: &str) -> AgentEvent {
+        AgentEvent {
+            event: Some(Event::RequestApplied(betcode_proto::v1::RequestApplied {
+                idempotency_key: key.to_string(),
+                duplicate: false,
+            })),
+            ..AgentEvent::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn only_the_matching_ack_confirms_delivery() {
+        let (tx, mut rx) = mpsc::channel(4);
+        tx.send(Ok(AgentEvent::default())).await.unwrap();
+        tx.send(Ok(ack("other"))).await.unwrap();
+        tx.send(Ok(ack("k1"))).await.unwrap();
+        assert!(
+            await_ack(&mut rx, "k1", Duration::from_secs(5))
+                .await
+                .is_ok()
+        );
+
+        // Without an ack the request is not delivered
+        let err = await_ack(&mut rx, "k2", Duration::from_millis(10))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, ConnectionError::RpcFailed(_)), "{err}");
+
+        tx.send(Ok(ack("k2"))).await.unwrap();
+        drop(tx);
+        assert!(
+            await_ack(&mut rx, "k2", Duration::from_secs(5))
+                .await
+                .is_ok()
+        );
+        let err = await_ack(&mut rx, "k3", Duration::from_secs(5))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, ConnectionError::Unavailable(_)), "{err}");
+    }
+}
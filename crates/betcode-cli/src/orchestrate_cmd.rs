@@ -0,0 +1,509 @@
+//! CLI orchestration subcommands.
+//!
+//! User-facing output uses writeln! to stdout (this is a CLI binary, not debug output).
+
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::io::{self, IsTerminal, Write};
+use std::path::PathBuf;
+
+use betcode_proto::v1::{OrchestrationEvent, StepFailed, StepStarted};
+use clap::Subcommand;
+use crossterm::{cursor, queue, terminal};
+
+use crate::connection::DaemonConnection;
+use crate::gitlab_fmt::truncate;
+use crate::orchestrate_plan::OrchestrationPlan;
+
+/// Orchestration subcommand actions.
+#[derive(Subcommand, Debug)]
+pub enum OrchestrateAction {
+    /// Validate and submit an orchestration file, then follow its progress
+    Run {
+        /// Orchestration file (TOML, or YAML with a .yaml/.yml extension)
+        file: PathBuf,
+        /// Parent session ID (overrides `session` in the file)
+        #[arg(short, long)]
+        session: Option<String>,
+        /// Print the orchestration ID and exit instead of following progress
+        #[arg(long)]
+        detach: bool,
+    },
+    /// Check an orchestration file without submitting it
+    Validate {
+        /// Orchestration file (TOML, or YAML with a .yaml/.yml extension)
+        file: PathBuf,
+    },
+    /// Follow the progress of a running orchestration
+    Watch {
+        /// Orchestration ID
+        id: String,
+    },
+}
+
+/// Execute an orchestration subcommand.
+pub async fn run(conn: &mut DaemonConnection, action: OrchestrateAction) -> anyhow::Result<()> {
+    let mut out = io::stdout();
+    match action {
+        OrchestrateAction::Run {
+            file,
+            session,
+            detach,
+        } => {
+            let plan = OrchestrationPlan::load(&file)?;
+            let Some(session) = session.or_else(|| plan.session.clone()) else {
+                anyhow::bail!("No parent session: pass --session or set `session` in the file");
+            };
+            let mut progress = DagProgress::new(&plan);
+            let resp = conn
+                .create_orchestration(&session, plan.steps, plan.strategy)
+                .await?;
+            writeln!(
+                out,
+                "Orchestration {} started ({} steps).",
+                resp.orchestration_id, resp.total_steps
+            )?;
+            if detach {
+                writeln!(
+                    out,
+                    "Follow it with: betcode orchestrate watch {}",
+                    resp.orchestration_id
+                )?;
+                return Ok(());
+            }
+            follow(conn, &mut out, &resp.orchestration_id, &mut progress).await?;
+        }
+        OrchestrateAction::Validate { file } => {
+            let plan = OrchestrationPlan::load(&file)?;
+            writeln!(
+                out,
+                "{} is valid: {} step(s), {} level(s).",
+                file.display(),
+                plan.steps.len(),
+                plan.levels().len()
+            )?;
+            DagProgress::new(&plan).render(&mut out)?;
+        }
+        OrchestrateAction::Watch { id } => {
+            follow(conn, &mut out, &id, &mut DagProgress::default()).await?;
+        }
+    }
+    Ok(())
+}
+
+/// Stream orchestration events, redrawing the progress view after each one.
+///
+/// Fails if the orchestration ends in failure.
+async fn follow(
+    conn: &mut DaemonConnection,
+    out: &mut impl Write,
+    orchestration_id: &str,
+    progress: &mut DagProgress,
+) -> anyhow::Result<()> {
+    let redraw = io::stdout().is_terminal();
+    let mut stream = conn.watch_orchestration(orchestration_id).await?;
+    let mut drawn = progress.render(out)?;
+    while let Some(event) = stream
+        .message()
+        .await
+        .map_err(|e| anyhow::anyhow!("Stream error: {e}"))?
+    {
+        let Some(line) = progress.apply(&event) else {
+            continue;
+        };
+        if redraw && drawn > 0 {
+            queue!(
+                out,
+                cursor::MoveUp(u16::try_from(drawn).unwrap_or(u16::MAX)),
+                terminal::Clear(terminal::ClearType::FromCursorDown)
+            )?;
+        }
+        writeln!(out, "{line}")?;
+        drawn = if redraw { progress.render(out)? } else { 0 };
+        out.flush()?;
+    }
+    if !redraw {
+        progress.render(out)?;
+    }
+    match progress.outcome {
+        Some(Outcome::Failed) => anyhow::bail!("orchestration failed"),
+        Some(Outcome::Succeeded) => {}
+        None => writeln!(out, "Stream ended.")?,
+    }
+    Ok(())
+}
+
+/// Terminal state of a finished orchestration.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Outcome {
+    Succeeded,
+    Failed,
+}
+
+/// Display state of a single step.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum StepState {
+    Pending,
+    Running,
+    Reviewing,
+    Retrying,
+    Completed,
+    Failed,
+    Blocked,
+    Skipped,
+}
+
+impl StepState {
+    const fn label(self) -> &'static str {
+        match self {
+            Self::Pending => "pending",
+            Self::Running => "running",
+            Self::Reviewing => "review",
+            Self::Retrying => "retrying",
+            Self::Completed => "done",
+            Self::Failed => "failed",
+            Self::Blocked => "blocked",
+            Self::Skipped => "skipped",
+        }
+    }
+}
+
+/// A step row in the progress view.
+#[derive(Debug, Clone)]
+struct StepRow {
+    id: String,
+    name: String,
+    depth: usize,
+    depends_on: Vec<String>,
+    state: StepState,
+}
+
+/// Live view of an orchestration's steps, indented by DAG depth.
+///
+/// When following an orchestration that was not submitted from this process
+/// the plan is unknown, so rows are added as steps start.
+#[derive(Debug, Default)]
+struct DagProgress {
+    rows: Vec<StepRow>,
+    index: HashMap<String, usize>,
+    outcome: Option<Outcome>,
+}
+
+impl DagProgress {
+    fn new(plan: &OrchestrationPlan) -> Self {
+        let mut progress = Self::default();
+        let steps: HashMap<&str, _> = plan.steps.iter().map(|s| (s.id.as_str(), s)).collect();
+        for (depth, level) in plan.levels().into_iter().enumerate() {
+            for id in level {
+                let step = steps[id];
+                progress.index.insert(step.id.clone(), progress.rows.len());
+                progress.rows.push(StepRow {
+                    id: step.id.clone(),
+                    name: step.name.clone(),
+                    depth,
+                    depends_on: step.depends_on.clone(),
+                    state: StepState::Pending,
+                });
+            }
+        }
+        progress
+    }
+
+    /// Look up a step row, adding an unplanned one if needed.
+    fn row(&mut self, id: &str, name: &str) -> &mut StepRow {
+        let i = *self.index.entry(id.to_string()).or_insert_with(|| {
+            self.rows.push(StepRow {
+                id: id.to_string(),
+                name: if name.is_empty() { id } else { name }.to_string(),
+                depth: 0,
+                depends_on: Vec::new(),
+                state: StepState::Pending,
+            });
+            self.rows.len() - 1
+        });
+        &mut self.rows[i]
+    }
+
+    /// Apply an event, returning a log line describing it.
+    fn apply(&mut self, event: &OrchestrationEvent) -> Option<String> {
+        use betcode_proto::v1::orchestration_event::Event;
+        match event.event.as_ref()? {
+            Event::StepStarted(s) => Some(self.step_started(s)),
+            Event::ReviewStarted(r) => {
+                self.row(&r.step_id, "").state = StepState::Reviewing;
+                Some(format!(
+                    "[reviewing] {} iteration {} (subagent {})",
+                    r.step_id, r.iteration, r.subagent_id
+                ))
+            }
+            Event::Reviewed(r) if r.approved => Some(format!(
+                "[approved]  {} (iteration {}/{})",
+                r.step_id, r.iteration, r.max_iterations
+            )),
+            Event::Reviewed(r) => {
+                let critique = r.critique.lines().find(|l| !l.trim().is_empty());
+                Some(format!(
+                    "[changes]   {} (iteration {}/{}): {}",
+                    r.step_id,
+                    r.iteration,
+                    r.max_iterations,
+                    truncate(critique.unwrap_or("no critique given").trim(), 100)
+                ))
+            }
+            Event::StepFannedOut(f) => Some(format!(
+                "[fan-out]   {} over {} item(s)",
+                f.step_id,
+                f.items.len()
+            )),
+            Event::StepRetrying(r) => {
+                self.row(&r.step_id, "").state = StepState::Retrying;
+                Some(format!(
+                    "[retrying]  {}: {} (attempt {}/{} in {}s)",
+                    r.step_id, r.error_message, r.next_attempt, r.max_attempts, r.retry_in_secs
+                ))
+            }
+            Event::StepSkipped(sk) => {
+                self.row(&sk.step_id, "").state = StepState::Skipped;
+                Some(format!("[skipped]   {}: {}", sk.step_id, sk.reason))
+            }
+            Event::StepCompleted(c) => {
+                self.row(&c.step_id, "").state = StepState::Completed;
+                Some(format!(
+                    "[completed] {} ({}/{})",
+                    c.step_id, c.completed_count, c.total_count
+                ))
+            }
+            Event::StepFailed(f) => Some(self.step_failed(f)),
+            Event::Completed(c) => {
+                // Failures of steps that continue on failure do not fail the run
+                self.outcome = Some(Outcome::Succeeded);
+                Some(format!(
+                    "[finished]  {} succeeded, {} failed, {} skipped of {} step(s)",
+                    c.succeeded, c.failed, c.skipped, c.total_steps
+                ))
+            }
+            Event::Failed(f) => {
+                self.outcome = Some(Outcome::Failed);
+                Some(format!(
+                    "[failed]    orchestration: {} ({} completed, {} failed)",
+                    f.error_message, f.completed_steps, f.failed_steps
+                ))
+            }
+        }
+    }
+
+    fn step_started(&mut self, s: &StepStarted) -> String {
+        self.row(&s.step_id, &s.name).state = StepState::Running;
+        let mut line = format!("[started]   {} (subagent {})", s.step_id, s.subagent_id);
+        if s.attempt > 1 {
+            let _ = write!(line, " attempt {}", s.attempt);
+        }
+        if s.iteration > 1 {
+            let _ = write!(line, " iteration {}", s.iteration);
+        }
+        line
+    }
+
+    fn step_failed(&mut self, f: &StepFailed) -> String {
+        self.row(&f.step_id, "").state = StepState::Failed;
+        for blocked in &f.blocked_steps {
+            self.row(blocked, "").state = StepState::Blocked;
+        }
+        let mut line = format!("[failed]    {}: {}", f.step_id, f.error_message);
+        if f.tolerated {
+            line.push_str(" (continuing)");
+        }
+        if !f.blocked_steps.is_empty() {
+            let _ = write!(line, " (blocked: {})", f.blocked_steps.join(", "));
+        }
+        line
+    }
+
+    /// Write the step table, returning the number of lines written.
+    fn render(&self, w: &mut impl Write) -> io::Result<usize> {
+        for row in &self.rows {
+            let label = format!("{}{}", "  ".repeat(row.depth), truncate(&row.name, 32));
+            write!(w, "  {:<8} {label:<40}", row.state.label())?;
+            if !row.depends_on.is_empty() {
+                write!(w, " <- {}", row.depends_on.join(", "))?;
+            }
+            if row.name != row.id {
+                write!(w, " [{}]", row.id)?;
+            }
+            writeln!(w)?;
+        }
+        Ok(self.rows.len())
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::panic, clippy::unwrap_used)]
+mod tests {
+    use super::*;
+    use crate::orchestrate_plan::PlanFormat;
+    use betcode_proto::v1::{
+        StepCompleted, StepFailed, StepRetrying, StepReviewStarted, StepReviewed, StepSkipped,
+        StepStarted, orchestration_event::Event,
+    };
+    use clap::Parser;
+
+    /// Test wrapper to parse CLI arguments.
+    #[derive(Parser, Debug)]
+    struct TestCli {
+        #[command(subcommand)]
+        action: OrchestrateAction,
+    }
+
+    fn event(e: Event) -> OrchestrationEvent {
+        OrchestrationEvent {
+            orchestration_id: "o1".into(),
+            timestamp: None,
+            event: Some(e),
+        }
+    }
+
+    #[test]
+    fn parse_run_command() {
+        let cli = TestCli::parse_from(["test", "run", "plan.toml", "-s", "s1", "--detach"]);
+        match cli.action {
+            OrchestrateAction::Run {
+                file,
+                session,
+                detach,
+            } => {
+                assert_eq!(file, PathBuf::from("plan.toml"));
+                assert_eq!(session.as_deref(), Some("s1"));
+                assert!(detach);
+            }
+            other => panic!("Expected Run, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn progress_tracks_step_states() {
+        let plan = OrchestrationPlan::parse(
+            r#"
+            [[steps]]
+            id = "a"
+            prompt = "x"
+            [[steps]]
+            id = "b"
+            prompt = "y"
+            depends_on = ["a"]
+            [[steps]]
+            id = "c"
+            prompt = "z"
+            depends_on = ["b"]
+            "#,
+            PlanFormat::Toml,
+        )
+        .unwrap();
+        let mut progress = DagProgress::new(&plan);
+        progress.apply(&event(Event::StepStarted(StepStarted {
+            step_id: "a".into(),
+            subagent_id: "o1-a".into(),
+            name: "a".into(),
+            attempt: 1,
+            iteration: 1,
+        })));
+        progress.apply(&event(Event::StepCompleted(StepCompleted {
+            step_id: "a".into(),
+            result_summary: String::new(),
+            completed_count: 1,
+            total_count: 3,
+        })));
+        let line = progress
+            .apply(&event(Event::StepFailed(StepFailed {
+                step_id: "b".into(),
+                error_message: "boom".into(),
+                blocked_steps: vec!["c".into()],
+                tolerated: false,
+                conflicts: Vec::new(),
+            })))
+            .unwrap();
+        assert!(line.contains("blocked: c"));
+
+        let mut buf = Vec::new();
+        assert_eq!(progress.render(&mut buf).unwrap(), 3);
+        let text = String::from_utf8(buf).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+        assert!(lines[0].trim_start().starts_with("done"));
+        assert!(
+            lines[1].contains("failed") && lines[1].contains("  b") && lines[1].contains("<- a")
+        );
+        assert!(lines[2].contains("blocked"));
+    }
+
+    #[test]
+    fn progress_adds_unplanned_steps() {
+        let mut progress = DagProgress::default();
+        progress.apply(&event(Event::StepStarted(StepStarted {
+            step_id: "x".into(),
+            subagent_id: "o1-x".into(),
+            name: "Step X".into(),
+            attempt: 1,
+            iteration: 1,
+        })));
+        assert_eq!(progress.rows.len(), 1);
+        assert_eq!(progress.rows[0].name, "Step X");
+        assert_eq!(progress.rows[0].state, StepState::Running);
+    }
+
+    #[test]
+    fn progress_shows_retries_and_skips() {
+        let mut progress = DagProgress::default();
+        let line = progress
+            .apply(&event(Event::StepRetrying(StepRetrying {
+                step_id: "test".into(),
+                error_message: "Timed out".into(),
+                next_attempt: 2,
+                max_attempts: 3,
+                retry_in_secs: 10,
+            })))
+            .unwrap();
+        assert!(line.contains("attempt 2/3 in 10s"), "{line}");
+        assert_eq!(progress.rows[0].state, StepState::Retrying);
+
+        progress.apply(&event(Event::StepSkipped(StepSkipped {
+            step_id: "fix".into(),
+            reason: "No dependency failed".into(),
+        })));
+        assert_eq!(progress.rows[1].state, StepState::Skipped);
+    }
+    #[test]
+    fn progress_shows_review_rounds() {
+        let mut progress = DagProgress::default();
+        progress.apply(&event(Event::ReviewStarted(StepReviewStarted {
+            step_id: "fix".into(),
+            subagent_id: "o1-fix-review1".into(),
+            iteration: 1,
+        })));
+        assert_eq!(progress.rows[0].state, StepState::Reviewing);
+
+        let line = progress
+            .apply(&event(Event::Reviewed(StepReviewed {
+                step_id: "fix".into(),
+                iteration: 1,
+                max_iterations: 3,
+                approved: false,
+                critique: "\nTests still fail.\nSee parser::tests.".into(),
+            })))
+            .unwrap();
+        assert!(
+            line.ends_with("(iteration 1/3): Tests still fail."),
+            "{line}"
+        );
+
+        let line = progress
+            .apply(&event(Event::StepStarted(StepStarted {
+                step_id: "fix".into(),
+                subagent_id: "o1-fix-i2".into(),
+                name: "fix".into(),
+                attempt: 1,
+                iteration: 2,
+            })))
+            .unwrap();
+        assert!(line.ends_with("iteration 2"), "{line}");
+        assert_eq!(progress.rows[0].state, StepState::Running);
+    }
+}
@@ -0,0 +1,770 @@
+//! Declarative orchestration files.
+//!
+//! An orchestration is described in TOML (or YAML, chosen by a `.yaml`/`.yml`
+//! extension):
+//!
+//! ```toml
+//! session = "abc123"            # parent session (or pass --session)
+//! strategy = "dag"              # dag (default), parallel, sequential or review-loop
+//! model = "claude-sonnet-4"     # defaults applied to every step
+//! timeout_secs = 900
+//! retries = 1                   # extra attempts after a failure
+//! review_iterations = 3         # worker runs of a reviewed step before it fails
+//! allowed_tools = ["Read", "Grep"]
+//!
+//! [[steps]]
+//! id = "plan"
+//! prompt = "Write a plan for the refactor"
+//!
+//! [[steps]]
+//! id = "implement"
+//! prompt = "Implement this plan:\n{{steps.plan.result}}"
+//! depends_on = ["plan"]
+//! worktree = "feature-x"        # worktree ID or name on the daemon machine
+//! max_turns = 30
+//!
+//! [[steps]]
+//! id = "test"
+//! prompt = "List the failing test files, one per line"
+//! depends_on = ["implement"]
+//! continue_on_failure = true    # a failure does not fail the orchestration
+//!
+//! [[steps]]
+//! id = "fix"
+//! prompt = "Make {{item}} pass"
+//! depends_on = ["test"]
+//! run_if = "failure"            # success (default), failure or always
+//! fan_out_from = "test"         # one subagent per line of test's output
+//! retry_backoff_secs = 30
+//! isolated = true               # own worktree per subagent, merged back afterwards
+//! merge = "cherry-pick"         # merge (default) or cherry-pick
+//! working_directory = "/src/app"
+//!
+//! [steps.review]                # a reviewer checks every worker run
+//! prompt = "`cargo test` passes and the public API is unchanged"
+//! model = "claude-opus-4"
+//! allowed_tools = ["Read", "Bash"]
+//! max_iterations = 5
+//! ```
+//!
+//! `{{steps.<id>.result}}` in a prompt is replaced with the output of an
+//! upstream step when the step is dispatched, and `{{item}}` with the item of
+//! a fan-out step. Plans are validated locally with the same dependency and
+//! reference rules as the daemon's scheduler before they are submitted.
+//!
+//! An `isolated` step must run in a registered repository (or one of its
+//! worktrees). Each of its subagents gets a fresh worktree branched from the
+//! orchestration's integration branch `betcode/<orchestration-id>`, and its
+//! changes are merged back when it succeeds; a conflict fails the step.
+//!
+//! A step with a `review` is followed by a reviewer subagent whose prompt
+//! holds the acceptance criteria. Until the reviewer approves, the worker runs
+//! again with its critique, at most `max_iterations` times. The `review-loop`
+//! strategy is shorthand for a plan of two steps: the second reviews the first.
+
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+
+use betcode_proto::v1::{
+    OrchestrationStep, OrchestrationStrategy, StepMergeStrategy, StepReview, StepRunCondition,
+};
+use serde::Deserialize;
+
+/// Why an orchestration file was rejected.
+#[derive(Debug, thiserror::Error)]
+pub enum PlanError {
+    #[error("Failed to read {path}: {source}")]
+    Read {
+        path: String,
+        source: std::io::Error,
+    },
+
+    #[error("Invalid orchestration file: {0}")]
+    Parse(String),
+
+    #[error("Invalid orchestration: {0}")]
+    Invalid(String),
+
+    #[error(transparent)]
+    Dag(#[from] betcode_core::dag::DagError),
+
+    #[error(transparent)]
+    Template(#[from] betcode_core::step_template::TemplateError),
+}
+
+/// File format of an orchestration plan.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlanFormat {
+    Toml,
+    Yaml,
+}
+
+impl PlanFormat {
+    /// Pick the format from a file extension; anything but YAML is TOML.
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("yaml") || ext.eq_ignore_ascii_case("yml") => {
+                Self::Yaml
+            }
+            _ => Self::Toml,
+        }
+    }
+}
+
+/// A validated orchestration, ready to submit.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OrchestrationPlan {
+    /// Parent session from the file, if any.
+    pub session: Option<String>,
+    pub strategy: OrchestrationStrategy,
+    /// Steps with defaults applied and (for `sequential`) dependencies chained.
+    pub steps: Vec<OrchestrationStep>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawPlan {
+    #[serde(default)]
+    session: Option<String>,
+    #[serde(default)]
+    strategy: Option<String>,
+    #[serde(default)]
+    model: Option<String>,
+    #[serde(default)]
+    allowed_tools: Vec<String>,
+    #[serde(default)]
+    max_turns: Option<i32>,
+    #[serde(default)]
+    timeout_secs: Option<u32>,
+    #[serde(default)]
+    retries: Option<u32>,
+    #[serde(default)]
+    retry_backoff_secs: Option<u32>,
+    #[serde(default)]
+    auto_approve: bool,
+    #[serde(default)]
+    review_iterations: Option<u32>,
+    #[serde(default)]
+    steps: Vec<RawStep>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawStep {
+    id: String,
+    #[serde(default)]
+    name: Option<String>,
+    prompt: String,
+    #[serde(default)]
+    depends_on: Vec<String>,
+    #[serde(default)]
+    model: Option<String>,
+    #[serde(default)]
+    allowed_tools: Option<Vec<String>>,
+    #[serde(default)]
+    worktree: Option<String>,
+    #[serde(default)]
+    working_directory: Option<String>,
+    #[serde(default)]
+    max_turns: Option<i32>,
+    #[serde(default)]
+    timeout_secs: Option<u32>,
+    #[serde(default)]
+    auto_approve: Option<bool>,
+    #[serde(default)]
+    retries: Option<u32>,
+    #[serde(default)]
+    retry_backoff_secs: Option<u32>,
+    #[serde(default)]
+    continue_on_failure: bool,
+    #[serde(default)]
+    run_if: Option<String>,
+    #[serde(default)]
+    fan_out_from: Option<String>,
+    #[serde(default)]
+    isolated: bool,
+    #[serde(default)]
+    merge: Option<String>,
+    #[serde(default)]
+    review: Option<RawReview>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawReview {
+    prompt: String,
+    #[serde(default)]
+    model: Option<String>,
+    #[serde(default)]
+    allowed_tools: Option<Vec<String>>,
+    #[serde(default)]
+    max_turns: Option<i32>,
+    #[serde(default)]
+    auto_approve: Option<bool>,
+    #[serde(default)]
+    max_iterations: Option<u32>,
+}
+
+impl OrchestrationPlan {
+    /// Read, parse and validate an orchestration file.
+    pub fn load(path: &Path) -> Result<Self, PlanError> {
+        let src = std::fs::read_to_string(path).map_err(|source| PlanError::Read {
+            path: path.display().to_string(),
+            source,
+        })?;
+        Self::parse(&src, PlanFormat::from_path(path))
+    }
+
+    /// Parse and validate an orchestration plan.
+    pub fn parse(src: &str, format: PlanFormat) -> Result<Self, PlanError> {
+        let raw: RawPlan = match format {
+            PlanFormat::Toml => toml::from_str(src).map_err(|e| PlanError::Parse(e.to_string()))?,
+            PlanFormat::Yaml => {
+                serde_yaml_ng::from_str(src).map_err(|e| PlanError::Parse(e.to_string()))?
+            }
+        };
+        raw.into_plan()
+    }
+
+    /// Step IDs grouped by depth in the dependency graph.
+    ///
+    /// Steps in the same level can run concurrently; the plan is already known
+    /// to be acyclic.
+    pub fn levels(&self) -> Vec<Vec<&str>> {
+        let mut depth: HashMap<&str, usize> = HashMap::new();
+        let mut levels: Vec<Vec<&str>> = Vec::new();
+        let mut remaining: Vec<&OrchestrationStep> = self.steps.iter().collect();
+        while !remaining.is_empty() {
+            let before = remaining.len();
+            remaining.retain(|step| {
+                let deps: Option<Vec<usize>> = step
+                    .depends_on
+                    .iter()
+                    .map(|d| depth.get(d.as_str()).copied())
+                    .collect();
+                let Some(deps) = deps else {
+                    return true;
+                };
+                let d = deps.into_iter().max().map_or(0, |m| m + 1);
+                depth.insert(step.id.as_str(), d);
+                if levels.len() <= d {
+                    levels.resize_with(d + 1, Vec::new);
+                }
+                levels[d].push(step.id.as_str());
+                false
+            });
+            if remaining.len() == before {
+                break;
+            }
+        }
+        levels
+    }
+}
+
+impl RawPlan {
+    fn into_plan(mut self) -> Result<OrchestrationPlan, PlanError> {
+        let strategy = parse_strategy(self.strategy.as_deref())?;
+        if self.steps.is_empty() {
+            return Err(PlanError::Invalid("no steps defined".into()));
+        }
+        if self.session.as_deref().is_some_and(str::is_empty) {
+            return Err(PlanError::Invalid("`session` must not be empty".into()));
+        }
+
+        let mut ids = HashSet::new();
+        let raw_steps = std::mem::take(&mut self.steps);
+        let mut steps = Vec::with_capacity(raw_steps.len());
+        for raw in raw_steps {
+            if raw.id.trim().is_empty() {
+                return Err(PlanError::Invalid("step `id` must not be empty".into()));
+            }
+            if !ids.insert(raw.id.clone()) {
+                return Err(PlanError::Invalid(format!(
+                    "duplicate step id \"{}\"",
+                    raw.id
+                )));
+            }
+            steps.push(self.step(raw)?);
+        }
+
+        // Mirror the daemon: a review loop's second step reviews the first.
+        if strategy == OrchestrationStrategy::ReviewLoop {
+            steps = fold_review_loop(steps, self.review_iterations.unwrap_or(0))?;
+        }
+
+        // Mirror the daemon: sequential plans chain each step to the previous one.
+        if strategy == OrchestrationStrategy::Sequential {
+            for i in 1..steps.len() {
+                let prev_id = steps[i - 1].id.clone();
+                if !steps[i].depends_on.contains(&prev_id) {
+                    steps[i].depends_on.push(prev_id);
+                }
+            }
+        }
+
+        validate_graph(&steps)?;
+        Ok(OrchestrationPlan {
+            session: self.session,
+            strategy,
+            steps,
+        })
+    }
+
+    /// Validate a step and apply the plan-wide defaults to it.
+    fn step(&self, raw: RawStep) -> Result<OrchestrationStep, PlanError> {
+        let (run_if, merge_strategy) = raw.validate()?;
+        let review = raw.review.map(|review| self.review(review));
+        Ok(OrchestrationStep {
+            name: raw.name.unwrap_or_else(|| raw.id.clone()),
+            id: raw.id,
+            prompt: raw.prompt,
+            model: raw.model.or_else(|| self.model.clone()).unwrap_or_default(),
+            working_directory: raw.working_directory.unwrap_or_default(),
+            allowed_tools: raw
+                .allowed_tools
+                .unwrap_or_else(|| self.allowed_tools.clone()),
+            depends_on: raw.depends_on,
+            max_turns: raw.max_turns.or(self.max_turns).unwrap_or(0),
+            auto_approve: raw.auto_approve.unwrap_or(self.auto_approve),
+            worktree: raw.worktree.unwrap_or_default(),
+            timeout_secs: raw.timeout_secs.or(self.timeout_secs).unwrap_or(0),
+            max_retries: raw.retries.or(self.retries).unwrap_or(0),
+            retry_backoff_secs: raw
+                .retry_backoff_secs
+                .or(self.retry_backoff_secs)
+                .unwrap_or(0),
+            continue_on_failure: raw.continue_on_failure,
+            run_if: run_if.into(),
+            fan_out_from: raw.fan_out_from.unwrap_or_default(),
+            isolated: raw.isolated,
+            merge_strategy: merge_strategy.into(),
+            review,
+        })
+    }
+
+    /// Apply the plan-wide defaults to a step's review.
+    fn review(&self, review: RawReview) -> StepReview {
+        StepReview {
+            prompt: review.prompt,
+            model: review
+                .model
+                .or_else(|| self.model.clone())
+                .unwrap_or_default(),
+            allowed_tools: review
+                .allowed_tools
+                .unwrap_or_else(|| self.allowed_tools.clone()),
+            auto_approve: review.auto_approve.unwrap_or(self.auto_approve),
+            max_turns: review.max_turns.or(self.max_turns).unwrap_or(0),
+            max_iterations: review
+                .max_iterations
+                .or(self.review_iterations)
+                .unwrap_or(0),
+        }
+    }
+}
+
+impl RawStep {
+    /// Check the fields of a single step, returning its parsed run condition
+    /// and merge strategy.
+    fn validate(&self) -> Result<(StepRunCondition, StepMergeStrategy), PlanError> {
+        let invalid = |msg: &str| PlanError::Invalid(format!("step \"{}\" {msg}", self.id));
+        if self.prompt.trim().is_empty() {
+            return Err(invalid("has an empty prompt"));
+        }
+        if self.worktree.is_some() && self.working_directory.is_some() {
+            return Err(invalid("sets both `worktree` and `working_directory`"));
+        }
+        let run_if = match self.run_if.as_deref().unwrap_or("success") {
+            "success" => StepRunCondition::Unspecified,
+            "failure" => StepRunCondition::OnFailure,
+            "always" => StepRunCondition::Always,
+            other => {
+                return Err(invalid(&format!(
+                    "has unknown run_if \"{other}\" (expected success, failure or always)"
+                )));
+            }
+        };
+        let merge_strategy = match self.merge.as_deref() {
+            None | Some("merge") => StepMergeStrategy::Unspecified,
+            Some("cherry-pick") => StepMergeStrategy::CherryPick,
+            Some(other) => {
+                return Err(invalid(&format!(
+                    "has unknown merge \"{other}\" (expected merge or cherry-pick)"
+                )));
+            }
+        };
+        if self.merge.is_some() && !self.isolated {
+            return Err(invalid("sets `merge` without `isolated`"));
+        }
+        if let Some(review) = &self.review {
+            if review.prompt.trim().is_empty() {
+                return Err(invalid("has a review with an empty prompt"));
+            }
+            if self.fan_out_from.is_some() {
+                return Err(invalid("cannot both fan out and be reviewed"));
+            }
+        }
+        Ok((run_if, merge_strategy))
+    }
+}
+
+fn parse_strategy(name: Option<&str>) -> Result<OrchestrationStrategy, PlanError> {
+    match name.unwrap_or("dag") {
+        "dag" => Ok(OrchestrationStrategy::Dag),
+        "parallel" => Ok(OrchestrationStrategy::Parallel),
+        "sequential" => Ok(OrchestrationStrategy::Sequential),
+        "review-loop" => Ok(OrchestrationStrategy::ReviewLoop),
+        other => Err(PlanError::Invalid(format!(
+            "unknown strategy \"{other}\" (expected dag, parallel, sequential or review-loop)"
+        ))),
+    }
+}
+
+/// Check dependencies, run conditions, fan-outs and prompt references across
+/// all steps, as the daemon will.
+fn validate_graph(steps: &[OrchestrationStep]) -> Result<(), PlanError> {
+    let step_ids: Vec<String> = steps.iter().map(|s| s.id.clone()).collect();
+    let deps: HashMap<String, Vec<String>> = steps
+        .iter()
+        .map(|s| (s.id.clone(), s.depends_on.clone()))
+        .collect();
+    betcode_core::dag::validate_dag(&step_ids, &deps)?;
+    for step in steps {
+        if step.run_if() == StepRunCondition::OnFailure && step.depends_on.is_empty() {
+            return Err(betcode_core::dag::DagError::NoDependencies {
+                step: step.id.clone(),
+            }
+            .into());
+        }
+        if !step.fan_out_from.is_empty() {
+            betcode_core::dag::validate_fan_out(&step.id, &step.fan_out_from, &step.depends_on)?;
+        }
+    }
+    let prompts: HashMap<String, String> = steps
+        .iter()
+        .map(|s| (s.id.clone(), s.prompt.clone()))
+        .collect();
+    betcode_core::step_template::validate_refs(&prompts, &deps)?;
+    Ok(())
+}
+
+/// The worker step of a `review-loop` plan, reviewed by its second step.
+fn fold_review_loop(
+    steps: Vec<OrchestrationStep>,
+    max_iterations: u32,
+) -> Result<Vec<OrchestrationStep>, PlanError> {
+    let [mut worker, reviewer]: [OrchestrationStep; 2] = steps.try_into().map_err(|_| {
+        PlanError::Invalid(
+            "review-loop plans have exactly two steps: a worker and a reviewer".into(),
+        )
+    })?;
+    if worker.review.is_some() || reviewer.review.is_some() {
+        return Err(PlanError::Invalid(
+            "steps of a review-loop plan must not set `review`".into(),
+        ));
+    }
+    if !worker.depends_on.is_empty() || !reviewer.depends_on.is_empty() {
+        return Err(PlanError::Invalid(
+            "steps of a review-loop plan must not set `depends_on`".into(),
+        ));
+    }
+    worker.review = Some(StepReview {
+        prompt: reviewer.prompt,
+        model: reviewer.model,
+        allowed_tools: reviewer.allowed_tools,
+        auto_approve: reviewer.auto_approve,
+        max_turns: reviewer.max_turns,
+        max_iterations,
+    });
+    Ok(vec![worker])
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used, clippy::panic)]
+mod tests {
+    use super::*;
+
+    const TOML_PLAN: &str = r#"
+        session = "s1"
+        model = "claude-sonnet-4"
+        timeout_secs = 600
+        allowed_tools = ["Read"]
+
+        [[steps]]
+        id = "plan"
+        prompt = "Plan it"
+
+        [[steps]]
+        id = "build"
+        name = "Build it"
+        prompt = "Build it"
+        depends_on = ["plan"]
+        model = "claude-opus-4"
+        worktree = "feature-x"
+        timeout_secs = 60
+
+        [[steps]]
+        id = "docs"
+        prompt = "Document it"
+        depends_on = ["plan"]
+        allowed_tools = []
+    "#;
+
+    #[test]
+    fn parses_toml_and_applies_defaults() {
+        let plan = OrchestrationPlan::parse(TOML_PLAN, PlanFormat::Toml).unwrap();
+        assert_eq!(plan.session.as_deref(), Some("s1"));
+        assert_eq!(plan.strategy, OrchestrationStrategy::Dag);
+        let [plan_step, build, docs] = plan.steps.as_slice() else {
+            panic!("expected three steps");
+        };
+        assert_eq!(plan_step.name, "plan");
+        assert_eq!(plan_step.model, "claude-sonnet-4");
+        assert_eq!(plan_step.timeout_secs, 600);
+        assert_eq!(plan_step.allowed_tools, vec!["Read".to_string()]);
+        assert_eq!(build.name, "Build it");
+        assert_eq!(build.model, "claude-opus-4");
+        assert_eq!(build.worktree, "feature-x");
+        assert_eq!(build.timeout_secs, 60);
+        assert!(docs.allowed_tools.is_empty());
+        assert_eq!(plan.levels(), vec![vec!["plan"], vec!["build", "docs"]]);
+    }
+
+    #[test]
+    fn parses_yaml() {
+        let src = "
+strategy: sequential
+steps:
+  - id: a
+    prompt: first
+  - id: b
+    prompt: second
+";
+        let plan = OrchestrationPlan::parse(src, PlanFormat::Yaml).unwrap();
+        assert_eq!(plan.strategy, OrchestrationStrategy::Sequential);
+        assert!(plan.session.is_none());
+        assert_eq!(plan.steps[1].depends_on, vec!["a".to_string()]);
+    }
+
+    #[test]
+    fn rejects_cycles_and_unknown_dependencies() {
+        let cyclic = r#"
+            [[steps]]
+            id = "a"
+            prompt = "x"
+            depends_on = ["b"]
+            [[steps]]
+            id = "b"
+            prompt = "y"
+            depends_on = ["a"]
+        "#;
+        let err = OrchestrationPlan::parse(cyclic, PlanFormat::Toml).unwrap_err();
+        assert!(err.to_string().contains("cycle"), "{err}");
+
+        let unknown = r#"
+            [[steps]]
+            id = "a"
+            prompt = "x"
+            depends_on = ["missing"]
+        "#;
+        let err = OrchestrationPlan::parse(unknown, PlanFormat::Toml).unwrap_err();
+        assert!(err.to_string().contains("unknown step 'missing'"), "{err}");
+    }
+
+    #[test]
+    fn rejects_references_to_non_upstream_steps() {
+        let src = r#"
+            [[steps]]
+            id = "a"
+            prompt = "x"
+            [[steps]]
+            id = "b"
+            prompt = "Summarise {{steps.a.result}}"
+        "#;
+        let err = OrchestrationPlan::parse(src, PlanFormat::Toml).unwrap_err();
+        assert!(matches!(err, PlanError::Template(_)), "{err}");
+
+        let chained = src.replace("id = \"b\"", "id = \"b\"\ndepends_on = [\"a\"]");
+        assert!(OrchestrationPlan::parse(&chained, PlanFormat::Toml).is_ok());
+    }
+
+    #[test]
+    fn rejects_invalid_steps() {
+        for (src, needle) in [
+            ("steps = []", "no steps"),
+            (
+                "[[steps]]\nid = \"a\"\nprompt = \"x\"\n[[steps]]\nid = \"a\"\nprompt = \"y\"",
+                "duplicate step id",
+            ),
+            ("[[steps]]\nid = \"a\"\nprompt = \"  \"", "empty prompt"),
+            (
+                "strategy = \"fast\"\n[[steps]]\nid = \"a\"\nprompt = \"x\"",
+                "unknown strategy",
+            ),
+            (
+                "[[steps]]\nid = \"a\"\nprompt = \"x\"\npriority = 3",
+                "unknown field",
+            ),
+            (
+                "[[steps]]\nid = \"a\"\nprompt = \"x\"\nrun_if = \"sometimes\"",
+                "unknown run_if",
+            ),
+            (
+                "[[steps]]\nid = \"a\"\nprompt = \"x\"\nworktree = \"w\"\nworking_directory = \"/tmp\"",
+                "both `worktree`",
+            ),
+        ] {
+            let err = OrchestrationPlan::parse(src, PlanFormat::Toml).unwrap_err();
+            assert!(err.to_string().contains(needle), "{src}: {err}");
+        }
+    }
+
+    #[test]
+    fn parses_retries_conditions_and_fan_out() {
+        let src = r#"
+            retries = 2
+            [[steps]]
+            id = "test"
+            prompt = "List failing tests"
+            continue_on_failure = true
+            [[steps]]
+            id = "fix"
+            prompt = "Fix {{item}}"
+            depends_on = ["test"]
+            run_if = "failure"
+            fan_out_from = "test"
+            retries = 0
+            retry_backoff_secs = 30
+        "#;
+        let plan = OrchestrationPlan::parse(src, PlanFormat::Toml).unwrap();
+        let [test, fix] = plan.steps.as_slice() else {
+            panic!("expected two steps");
+        };
+        assert_eq!(test.max_retries, 2);
+        assert!(test.continue_on_failure);
+        assert_eq!(test.run_if(), StepRunCondition::Unspecified);
+        assert_eq!(fix.max_retries, 0);
+        assert_eq!(fix.retry_backoff_secs, 30);
+        assert_eq!(fix.run_if(), StepRunCondition::OnFailure);
+        assert_eq!(fix.fan_out_from, "test");
+    }
+
+    #[test]
+    fn rejects_invalid_conditions_and_fan_out() {
+        let on_failure_root = "[[steps]]\nid = \"a\"\nprompt = \"x\"\nrun_if = \"failure\"";
+        let err = OrchestrationPlan::parse(on_failure_root, PlanFormat::Toml).unwrap_err();
+        assert!(err.to_string().contains("no dependencies"), "{err}");
+
+        let fan_out = r#"
+            [[steps]]
+            id = "a"
+            prompt = "x"
+            [[steps]]
+            id = "b"
+            prompt = "y"
+            fan_out_from = "a"
+        "#;
+        let err = OrchestrationPlan::parse(fan_out, PlanFormat::Toml).unwrap_err();
+        assert!(err.to_string().contains("fans out over 'a'"), "{err}");
+    }
+
+    #[test]
+    fn parses_reviewed_steps_and_review_loops() {
+        let src = r#"
+            model = "claude-sonnet-4"
+            review_iterations = 4
+            [[steps]]
+            id = "fix"
+            prompt = "Fix the parser"
+            [steps.review]
+            prompt = "cargo test passes"
+            model = "claude-opus-4"
+        "#;
+        let plan = OrchestrationPlan::parse(src, PlanFormat::Toml).unwrap();
+        let review = plan.steps[0].review.clone().unwrap();
+        assert_eq!(review.prompt, "cargo test passes");
+        assert_eq!(review.model, "claude-opus-4");
+        assert_eq!(review.max_iterations, 4);
+
+        let src = r#"
+            strategy = "review-loop"
+            model = "claude-sonnet-4"
+            [[steps]]
+            id = "fix"
+            prompt = "Fix the parser"
+            [[steps]]
+            id = "judge"
+            prompt = "cargo test passes"
+        "#;
+        let plan = OrchestrationPlan::parse(src, PlanFormat::Toml).unwrap();
+        assert_eq!(plan.strategy, OrchestrationStrategy::ReviewLoop);
+        assert_eq!(plan.steps.len(), 1);
+        let review = plan.steps[0].review.clone().unwrap();
+        assert_eq!(review.prompt, "cargo test passes");
+        assert_eq!(review.model, "claude-sonnet-4");
+        assert_eq!(review.max_iterations, 0);
+
+        for (src, needle) in [
+            (
+                "strategy = \"review-loop\"\n[[steps]]\nid = \"a\"\nprompt = \"x\"",
+                "exactly two steps",
+            ),
+            (
+                "[[steps]]\nid = \"a\"\nprompt = \"x\"\n[steps.review]\nprompt = \"\"",
+                "review with an empty prompt",
+            ),
+        ] {
+            let err = OrchestrationPlan::parse(src, PlanFormat::Toml).unwrap_err();
+            assert!(err.to_string().contains(needle), "{src}: {err}");
+        }
+    }
+
+    #[test]
+    fn parses_isolated_steps() {
+        let src = r#"
+            [[steps]]
+            id = "a"
+            prompt = "x"
+            worktree = "main"
+            isolated = true
+            [[steps]]
+            id = "b"
+            prompt = "y"
+            worktree = "main"
+            isolated = true
+            merge = "cherry-pick"
+        "#;
+        let plan = OrchestrationPlan::parse(src, PlanFormat::Toml).unwrap();
+        assert!(plan.steps[0].isolated);
+        assert_eq!(
+            plan.steps[0].merge_strategy(),
+            StepMergeStrategy::Unspecified
+        );
+        assert_eq!(
+            plan.steps[1].merge_strategy(),
+            StepMergeStrategy::CherryPick
+        );
+
+        for (src, needle) in [
+            (
+                "[[steps]]\nid = \"a\"\nprompt = \"x\"\nmerge = \"merge\"",
+                "without `isolated`",
+            ),
+            (
+                "[[steps]]\nid = \"a\"\nprompt = \"x\"\nisolated = true\nmerge = \"rebase\"",
+                "unknown merge",
+            ),
+        ] {
+            let err = OrchestrationPlan::parse(src, PlanFormat::Toml).unwrap_err();
+            assert!(err.to_string().contains(needle), "{src}: {err}");
+        }
+    }
+
+    #[test]
+    fn format_from_extension() {
+        assert_eq!(PlanFormat::from_path(Path::new("p.yaml")), PlanFormat::Yaml);
+        assert_eq!(PlanFormat::from_path(Path::new("p.YML")), PlanFormat::Yaml);
+        assert_eq!(PlanFormat::from_path(Path::new("p.toml")), PlanFormat::Toml);
+        assert_eq!(PlanFormat::from_path(Path::new("plan")), PlanFormat::Toml);
+    }
+}
@@ -4,9 +4,9 @@
 
 use std::io::{self, Write};
 
-use clap::Subcommand;
+use clap::{Args, Subcommand};
 
-use betcode_proto::v1::{GitRepoDetail, WorktreeMode};
+use betcode_proto::v1::{GitRepoDetail, SandboxBackend, SandboxPolicy, WorktreeMode};
 
 use crate::connection::DaemonConnection;
 use crate::gitlab_fmt::truncate;
@@ -84,6 +84,8 @@ pub enum RepoAction {
         /// Disable auto .gitignore
         #[arg(long)]
         no_auto_gitignore: bool,
+        #[command(flatten)]
+        sandbox: SandboxArgs,
     },
     /// Scan a directory for git repositories
     Scan {
@@ -95,6 +97,58 @@ pub enum RepoAction {
     },
 }
 
+/// Sandbox policy flags for `repo update`.
+#[derive(Args, Debug)]
+pub struct SandboxArgs {
+    /// Sandbox for Claude sessions in this repo: none, bubblewrap, podman,
+    /// or "default" to use the daemon's policy
+    #[arg(long)]
+    sandbox: Option<String>,
+    /// Sandbox network: host, none, or a podman network name
+    #[arg(long, requires = "sandbox")]
+    sandbox_network: Option<String>,
+    /// Container image for the podman sandbox
+    #[arg(long, requires = "sandbox")]
+    sandbox_image: Option<String>,
+    /// Extra host path visible read-only in the sandbox (repeatable)
+    #[arg(long = "sandbox-ro", requires = "sandbox")]
+    sandbox_ro: Vec<String>,
+    /// Extra host path visible read-write in the sandbox (repeatable)
+    #[arg(long = "sandbox-rw", requires = "sandbox")]
+    sandbox_rw: Vec<String>,
+    /// Skip permission prompts for sandboxed sessions
+    #[arg(long, requires = "sandbox")]
+    sandbox_auto_approve: bool,
+}
+
+impl SandboxArgs {
+    /// Build the proto policy; `None` leaves the repo's policy unchanged.
+    fn to_proto(&self) -> Result<Option<SandboxPolicy>, String> {
+        let Some(ref backend) = self.sandbox else {
+            return Ok(None);
+        };
+        let backend = match backend.as_str() {
+            "default" => SandboxBackend::Unspecified,
+            "none" => SandboxBackend::None,
+            "bubblewrap" | "bwrap" => SandboxBackend::Bubblewrap,
+            "podman" => SandboxBackend::Podman,
+            other => {
+                return Err(format!(
+                    "Invalid sandbox '{other}': must be 'none', 'bubblewrap', 'podman' or 'default'"
+                ));
+            }
+        };
+        Ok(Some(SandboxPolicy {
+            backend: backend as i32,
+            network: self.sandbox_network.clone().unwrap_or_default(),
+            read_only_paths: self.sandbox_ro.clone(),
+            read_write_paths: self.sandbox_rw.clone(),
+            image: self.sandbox_image.clone().unwrap_or_default(),
+            auto_approve: self.sandbox_auto_approve,
+        }))
+    }
+}
+
 /// Parse a worktree mode string to the proto enum i32 value.
 fn parse_worktree_mode(s: &str) -> Result<i32, String> {
     match s {
@@ -107,6 +161,31 @@ fn parse_worktree_mode(s: &str) -> Result<i32, String> {
     }
 }
 
+/// Write a repo's sandbox policy.
+fn write_sandbox(w: &mut impl Write, sb: &SandboxPolicy) -> io::Result<()> {
+    let backend = match SandboxBackend::try_from(sb.backend) {
+        Ok(SandboxBackend::None) => "none",
+        Ok(SandboxBackend::Bubblewrap) => "bubblewrap",
+        Ok(SandboxBackend::Podman) => "podman",
+        Ok(SandboxBackend::Unspecified) | Err(_) => "default",
+    };
+    write!(w, "  Sandbox:        {backend}, network {}", sb.network)?;
+    if !sb.image.is_empty() {
+        write!(w, ", image {}", sb.image)?;
+    }
+    if sb.auto_approve {
+        write!(w, ", auto-approve")?;
+    }
+    writeln!(w)?;
+    for p in &sb.read_only_paths {
+        writeln!(w, "    ro: {p}")?;
+    }
+    for p in &sb.read_write_paths {
+        writeln!(w, "    rw: {p}")?;
+    }
+    Ok(())
+}
+
 /// Format a `WorktreeMode` i32 for display.
 fn worktree_mode_str(mode: i32) -> &'static str {
     match WorktreeMode::try_from(mode) {
@@ -205,6 +284,7 @@ pub async fn run(conn: &mut DaemonConnection, action: RepoAction) -> anyhow::Res
             setup_script,
             auto_gitignore,
             no_auto_gitignore,
+            sandbox,
         } => {
             let worktree_mode = mode
                 .as_deref()
@@ -227,6 +307,7 @@ pub async fn run(conn: &mut DaemonConnection, action: RepoAction) -> anyhow::Res
                     custom_path.as_deref(),
                     setup_script.as_deref(),
                     auto_gitignore_opt,
+                    sandbox.to_proto().map_err(|e| anyhow::anyhow!(e))?,
                 )
                 .await?;
             writeln!(out, "Updated repository:")?;
@@ -279,6 +360,9 @@ fn write_detail(w: &mut impl Write, repo: &GitRepoDetail) -> io::Result<()> {
         "  Auto gitignore: {}",
         if repo.auto_gitignore { "yes" } else { "no" }
     )?;
+    if let Some(ref sb) = repo.sandbox {
+        write_sandbox(w, sb)?;
+    }
     writeln!(w, "  Worktrees:      {}", repo.worktree_count)?;
     if let Some(ref ts) = repo.created_at {
         writeln!(w, "  Created:        {} (unix)", ts.seconds)?;
@@ -0,0 +1,304 @@
+//! SSO login through the relay's identity provider.
+//!
+//! With a browser, the authorization code flow opens the provider's login
+//! page and receives the code on a loopback redirect. Headless, the device
+//! code flow prints a code to approve on another device and polls.
+//!
+//! User-facing output uses writeln! to stdout (this is a CLI binary, not debug output).
+
+use std::io::{self, Write};
+use std::time::Duration;
+
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpListener;
+use tonic::transport::Channel;
+
+use betcode_crypto::PkcePair;
+use betcode_proto::v1::auth_service_client::AuthServiceClient;
+use betcode_proto::v1::{
+    CompleteSsoLoginRequest, LoginResponse, PollSsoDeviceLoginRequest, StartSsoDeviceLoginRequest,
+    StartSsoLoginRequest,
+};
+
+use crate::auth_cmd::{call_with_retry, status_message};
+
+/// How long to wait for the browser to come back with a code.
+const CALLBACK_TIMEOUT: Duration = Duration::from_mins(5);
+
+/// Path of the loopback redirect URI.
+const CALLBACK_PATH: &str = "/callback";
+
+/// Seconds added to the polling interval when the provider asks to slow down
+/// (RFC 8628).
+const SLOW_DOWN_SECS: u64 = 5;
+
+const CALLBACK_PAGE: &str = "<html><body><p>Login complete. You can close this window and return to the terminal.</p></body></html>";
+
+/// Log in through the relay's identity provider, with the device code flow
+/// if `device` is set.
+pub async fn login(
+    client: &AuthServiceClient<Channel>,
+    device: bool,
+) -> anyhow::Result<LoginResponse> {
+    if device {
+        device_flow(client).await
+    } else {
+        code_flow(client).await
+    }
+}
+
+/// Whether this session likely has no browser to open: over SSH, or on
+/// Linux without a graphical display.
+pub fn is_headless() -> bool {
+    let set = |name| std::env::var_os(name).is_some_and(|v| !v.is_empty());
+    if set("SSH_CONNECTION") || set("SSH_TTY") {
+        return true;
+    }
+    cfg!(target_os = "linux") && !set("DISPLAY") && !set("WAYLAND_DISPLAY")
+}
+
+async fn code_flow(client: &AuthServiceClient<Channel>) -> anyhow::Result<LoginResponse> {
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let redirect_uri = format!(
+        "http://127.0.0.1:{}{CALLBACK_PATH}",
+        listener.local_addr()?.port()
+    );
+    let pkce = PkcePair::generate();
+
+    let request = StartSsoLoginRequest {
+        redirect_uri: redirect_uri.clone(),
+        code_challenge: pkce.challenge.clone(),
+    };
+    let start = call_with_retry(|| {
+        let mut client = client.clone();
+        let request = request.clone();
+        async move { client.start_sso_login(request).await }
+    })
+    .await
+    .map_err(|e| anyhow::anyhow!("SSO login failed: {}", status_message(&e)))?;
+
+    let mut out = io::stdout();
+    writeln!(
+        out,
+        "Opening your browser to log in. If it does not open, visit:\n\n  {}\n",
+        start.authorization_url
+    )?;
+    open_browser(&start.authorization_url);
+
+    let callback = tokio::time::timeout(CALLBACK_TIMEOUT, wait_for_callback(&listener))
+        .await
+        .map_err(|_| anyhow::anyhow!("Timed out waiting for the browser login"))??;
+    if callback.state != start.state {
+        anyhow::bail!("SSO login failed: state mismatch in the browser redirect");
+    }
+
+    let request = CompleteSsoLoginRequest {
+        code: callback.code,
+        code_verifier: pkce.verifier,
+        redirect_uri,
+        login_token: start.login_token,
+        state: callback.state,
+    };
+    call_with_retry(|| {
+        let mut client = client.clone();
+        let request = request.clone();
+        async move { client.complete_sso_login(request).await }
+    })
+    .await
+    .map_err(|e| anyhow::anyhow!("SSO login failed: {}", status_message(&e)))
+}
+
+async fn device_flow(client: &AuthServiceClient<Channel>) -> anyhow::Result<LoginResponse> {
+    let start = call_with_retry(|| {
+        let mut client = client.clone();
+        async move {
+            client
+                .start_sso_device_login(StartSsoDeviceLoginRequest {})
+                .await
+        }
+    })
+    .await
+    .map_err(|e| anyhow::anyhow!("SSO login failed: {}", status_message(&e)))?;
+
+    let mut out = io::stdout();
+    writeln!(
+        out,
+        "To log in, visit {} and enter the code:\n\n  {}\n",
+        start.verification_uri, start.user_code
+    )?;
+    if !start.verification_uri_complete.is_empty() {
+        writeln!(out, "Or open: {}\n", start.verification_uri_complete)?;
+    }
+    writeln!(out, "Waiting for approval...")?;
+
+    let deadline = tokio::time::Instant::now() + Duration::from_secs(start.expires_in_secs.into());
+    let mut interval = Duration::from_secs(start.interval_secs.max(1).into());
+    let request = PollSsoDeviceLoginRequest {
+        device_code: start.device_code,
+    };
+    loop {
+        tokio::time::sleep(interval).await;
+        if tokio::time::Instant::now() >= deadline {
+            anyhow::bail!("SSO login failed: the code expired before it was approved");
+        }
+        let poll = call_with_retry(|| {
+            let mut client = client.clone();
+            let request = request.clone();
+            async move { client.poll_sso_device_login(request).await }
+        })
+        .await
+        .map_err(|e| anyhow::anyhow!("SSO login failed: {}", status_message(&e)))?;
+        if let Some(login) = poll.login {
+            return Ok(login);
+        }
+        if poll.slow_down {
+            interval += Duration::from_secs(SLOW_DOWN_SECS);
+        }
+    }
+}
+
+/// Open `url` in the desktop's default browser, best effort.
+fn open_browser(url: &str) {
+    let mut command = if cfg!(target_os = "macos") {
+        std::process::Command::new("open")
+    } else if cfg!(windows) {
+        let mut command = std::process::Command::new("cmd");
+        command.args(["/C", "start", ""]);
+        command
+    } else {
+        std::process::Command::new("xdg-open")
+    };
+    let _ = command
+        .arg(url)
+        .stdin(std::process::Stdio::null())
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .spawn();
+}
+
+/// Authorization response delivered to the loopback redirect.
+#[derive(Debug, PartialEq, Eq)]
+struct Callback {
+    code: String,
+    state: String,
+}
+
+/// Serve the loopback redirect until the browser delivers the authorization
+/// response.
+async fn wait_for_callback(listener: &TcpListener) -> anyhow::Result<Callback> {
+    loop {
+        let (mut stream, _) = listener.accept().await?;
+        let mut reader = BufReader::new(&mut stream);
+        let mut request_line = String::new();
+        reader.read_line(&mut request_line).await?;
+        // Drain the headers so closing the connection does not reset it
+        let mut header = String::new();
+        while reader.read_line(&mut header).await? > 2 {
+            header.clear();
+        }
+        let target = request_line.split_whitespace().nth(1).unwrap_or_default();
+        let Some(result) = parse_callback(target) else {
+            // Favicon and other stray requests
+            let _ = stream
+                .write_all(
+                    b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                )
+                .await;
+            continue;
+        };
+        let response = format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: text/html; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{CALLBACK_PAGE}",
+            CALLBACK_PAGE.len()
+        );
+        let _ = stream.write_all(response.as_bytes()).await;
+        return result;
+    }
+}
+
+/// The authorization response in a redirect request target, or `None` if
+/// the target is not the callback path.
+fn parse_callback(target: &str) -> Option<anyhow::Result<Callback>> {
+    let url = url::Url::parse(&format!("http://127.0.0.1{target}")).ok()?;
+    if url.path() != CALLBACK_PATH {
+        return None;
+    }
+    let param = |name: &str| {
+        url.query_pairs()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.into_owned())
+    };
+    if let Some(error) = param("error") {
+        let detail = match param("error_description") {
+            Some(description) => format!("{error}: {description}"),
+            None => error,
+        };
+        return Some(Err(anyhow::anyhow!(
+            "SSO login failed: identity provider returned {detail}"
+        )));
+    }
+    Some(match (param("code"), param("state")) {
+        (Some(code), Some(state)) => Ok(Callback { code, state }),
+        _ => Err(anyhow::anyhow!(
+            "SSO login failed: the browser redirect carried no authorization code"
+        )),
+    })
+}
+
+#[cfg(test)]
+#[allow(clippy::panic, clippy::expect_used, clippy::unwrap_used)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn callback_carries_code_and_state() {
+        let callback = parse_callback("/callback?code=abc%2F123&state=xyz")
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            callback,
+            Callback {
+                code: "abc/123".into(),
+                state: "xyz".into(),
+            }
+        );
+    }
+
+    #[test]
+    fn provider_errors_and_stray_requests() {
+        let err = parse_callback("/callback?error=access_denied&error_description=User+cancelled")
+            .unwrap()
+            .unwrap_err();
+        assert!(
+            err.to_string().contains("access_denied: User cancelled"),
+            "got: {err}"
+        );
+        assert!(parse_callback("/callback?state=xyz").unwrap().is_err());
+        assert!(parse_callback("/favicon.ico").is_none());
+    }
+
+    #[tokio::test]
+    async fn loopback_redirect_is_received() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let browser = tokio::spawn(async move {
+            for target in ["/favicon.ico", "/callback?code=c1&state=s1"] {
+                let mut stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+                stream
+                    .write_all(format!("GET {target} HTTP/1.1\r\nHost: x\r\n\r\n").as_bytes())
+                    .await
+                    .unwrap();
+                let mut response = String::new();
+                tokio::io::AsyncReadExt::read_to_string(&mut stream, &mut response)
+                    .await
+                    .unwrap();
+                assert!(response.starts_with("HTTP/1.1"));
+            }
+        });
+
+        let callback = wait_for_callback(&listener).await.unwrap();
+        assert_eq!(callback.code, "c1");
+        assert_eq!(callback.state, "s1");
+        browser.await.unwrap();
+    }
+}
@@ -4,6 +4,7 @@
 
 use std::io::{self, Write};
 
+use betcode_proto::v1::PermissionDecision;
 use clap::Subcommand;
 
 use crate::connection::DaemonConnection;
@@ -42,6 +43,26 @@ pub enum SubagentAction {
         /// Subagent ID to watch
         id: String,
     },
+    /// Allow a tool call a subagent is waiting on
+    Approve {
+        /// Subagent ID
+        id: String,
+        /// Permission request ID (shown by `watch`)
+        request_id: String,
+        /// Also allow the tool for the rest of the parent session
+        #[arg(long)]
+        always: bool,
+    },
+    /// Deny a tool call a subagent is waiting on
+    Deny {
+        /// Subagent ID
+        id: String,
+        /// Permission request ID (shown by `watch`)
+        request_id: String,
+        /// Let the subagent carry on instead of stopping its turn
+        #[arg(long)]
+        no_interrupt: bool,
+    },
 }
 
 /// Execute a subagent subcommand.
@@ -104,10 +125,78 @@ pub async fn run(conn: &mut DaemonConnection, action: SubagentAction) -> anyhow:
             }
             writeln!(out, "Stream ended.")?;
         }
+        SubagentAction::Approve {
+            id,
+            request_id,
+            always,
+        } => approve(conn, &mut out, &id, &request_id, always).await?,
+        SubagentAction::Deny {
+            id,
+            request_id,
+            no_interrupt,
+        } => deny(conn, &mut out, &id, &request_id, no_interrupt).await?,
     }
     Ok(())
 }
 
+/// Allow the tool call behind `request_id`, for the session when `always`.
+async fn approve(
+    conn: &mut DaemonConnection,
+    out: &mut impl Write,
+    id: &str,
+    request_id: &str,
+    always: bool,
+) -> anyhow::Result<()> {
+    let decision = if always {
+        PermissionDecision::AllowSession
+    } else {
+        PermissionDecision::AllowOnce
+    };
+    let resp = conn
+        .respond_to_subagent_permission(id, request_id, decision)
+        .await?;
+    write_permission_result(out, request_id, resp.accepted, "approved")?;
+    Ok(())
+}
+
+/// Deny the tool call behind `request_id`, letting the subagent carry on
+/// when `no_interrupt`.
+async fn deny(
+    conn: &mut DaemonConnection,
+    out: &mut impl Write,
+    id: &str,
+    request_id: &str,
+    no_interrupt: bool,
+) -> anyhow::Result<()> {
+    let decision = if no_interrupt {
+        PermissionDecision::DenyNoInterrupt
+    } else {
+        PermissionDecision::Deny
+    };
+    let resp = conn
+        .respond_to_subagent_permission(id, request_id, decision)
+        .await?;
+    write_permission_result(out, request_id, resp.accepted, "denied")?;
+    Ok(())
+}
+
+/// Report whether a permission answer was taken.
+fn write_permission_result(
+    w: &mut impl Write,
+    request_id: &str,
+    accepted: bool,
+    verb: &str,
+) -> io::Result<()> {
+    if accepted {
+        writeln!(w, "Permission request {request_id} {verb}.")
+    } else {
+        writeln!(
+            w,
+            "Permission request {request_id} was already answered or has expired."
+        )
+    }
+}
+
 /// Truncate a string to a maximum display width.
 fn truncate(s: &str, max: usize) -> String {
     if s.len() <= max {
@@ -153,6 +242,10 @@ fn write_event(w: &mut impl Write, event: &betcode_proto::v1::SubagentEvent) ->
                 p.tool_name, p.description, p.request_id
             )?;
         }
+        Some(Event::PermissionResolved(r)) => {
+            let outcome = if r.granted { "allowed" } else { "denied" };
+            writeln!(w, "[permission] {} {outcome}: {}", r.request_id, r.reason)?;
+        }
         Some(Event::Completed(c)) => {
             writeln!(
                 w,
@@ -288,6 +381,36 @@ mod tests {
         }
     }
 
+    #[test]
+    fn parse_approve_and_deny_commands() {
+        let cli = TestCli::parse_from(["test", "approve", "sa-1", "req-1", "--always"]);
+        match cli.action {
+            SubagentAction::Approve {
+                id,
+                request_id,
+                always,
+            } => {
+                assert_eq!(id, "sa-1");
+                assert_eq!(request_id, "req-1");
+                assert!(always);
+            }
+            other => panic!("Expected Approve, got {other:?}"),
+        }
+
+        let cli = TestCli::parse_from(["test", "deny", "sa-1", "req-2"]);
+        match cli.action {
+            SubagentAction::Deny {
+                request_id,
+                no_interrupt,
+                ..
+            } => {
+                assert_eq!(request_id, "req-2");
+                assert!(!no_interrupt);
+            }
+            other => panic!("Expected Deny, got {other:?}"),
+        }
+    }
+
     #[test]
     fn format_status_values() {
         assert_eq!(format_status(0), "unknown");
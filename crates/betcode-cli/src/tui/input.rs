@@ -418,6 +418,7 @@ async fn handle_input_key(
                                                 content: trimmed.to_string(),
                                                 attachments: Vec::new(),
                                                 agent_id: String::new(),
+                                                idempotency_key: String::new(),
                                             },
                                         ),
                                     ),
@@ -435,6 +436,7 @@ async fn handle_input_key(
                                     content: text,
                                     attachments: Vec::new(),
                                     agent_id: String::new(),
+                                    idempotency_key: String::new(),
                                 },
                             )),
                         })
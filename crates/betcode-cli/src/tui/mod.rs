@@ -11,7 +11,9 @@ mod permission_tests;
 mod question_input;
 #[cfg(test)]
 mod question_tests;
+mod reconnect;
 
+use std::fmt::Write as _;
 use std::io;
 use std::time::{Duration, Instant};
 
@@ -23,12 +25,15 @@ use crossterm::terminal::{
 use ratatui::Terminal;
 use ratatui::backend::CrosstermBackend;
 use tokio_util::sync::CancellationToken;
-use tracing::error;
+use tracing::{error, warn};
+
+use betcode_proto::v1::AgentRequest;
 
 use crate::app::App;
 use crate::app::{CompletionFetchKind, CompletionRequest};
 use crate::commands::cache::{CachedCommand, CachedCommandCategory};
-use crate::connection::DaemonConnection;
+use crate::connection::{ConnectionState, DaemonConnection};
+use crate::offline_queue::{self, OfflineQueue, QueuedRequest};
 use crate::ui;
 
 /// Response from the async completion fetcher.
@@ -50,6 +55,55 @@ pub enum TermEvent {
     Resize(u16, u16),
 }
 
+/// Where requests go while the conversation stream is down.
+struct OfflineTarget {
+    /// `None` when the queue database could not be opened.
+    queue: Option<OfflineQueue>,
+    target: String,
+    working_directory: String,
+    model: String,
+}
+
+impl OfflineTarget {
+    /// Queue a request the conversation stream could not take.
+    async fn queue(&self, app: &mut App, session_id: &str, request: &AgentRequest) {
+        app.agent_busy = false;
+        let Some(queued) =
+            QueuedRequest::from_agent_request(request, &self.working_directory, &self.model)
+        else {
+            app.add_system_message(
+                crate::app::MessageRole::System,
+                "Not connected to the daemon; request dropped.".to_string(),
+            );
+            return;
+        };
+        let Some(queue) = &self.queue else {
+            app.add_system_message(
+                crate::app::MessageRole::System,
+                "Not connected to the daemon and the offline queue is unavailable; request dropped."
+                    .to_string(),
+            );
+            return;
+        };
+        match queue.enqueue(&self.target, session_id, queued).await {
+            Ok(item) => {
+                app.queued_requests.push(item.request.summary());
+                app.status = format!(
+                    "Disconnected | {} request(s) queued for replay",
+                    app.queued_requests.len()
+                );
+            }
+            Err(e) => {
+                warn!(?e, "Failed to queue request");
+                app.add_system_message(
+                    crate::app::MessageRole::System,
+                    format!("Not connected to the daemon; could not queue request: {e}"),
+                );
+            }
+        }
+    }
+}
+
 /// Spawn a one-shot task that fetches the command registry from the daemon and
 /// sends the result through `tx`. Does nothing if `cmd_client` is `None`.
 fn spawn_registry_fetch(
@@ -123,7 +177,7 @@ pub async fn run(
 
     // 1. Establish gRPC stream BEFORE entering raw mode so Ctrl+C works
     //    during the (potentially slow) handshake.
-    let (request_tx, mut event_rx, stream_handle) = conn.converse().await?;
+    let (mut request_tx, mut event_rx, mut stream_handle) = conn.converse().await?;
 
     let sid = session_id
         .clone()
@@ -138,11 +192,23 @@ pub async fn run(
     request_tx
         .send(crate::connection::start_conversation_request(
             sid.clone(),
-            wd,
+            wd.clone(),
             model.clone().unwrap_or_default(),
         ))
         .await?;
 
+    // Requests typed while the stream is down go to the offline queue and are
+    // replayed on the next connection.
+    let offline = OfflineTarget {
+        queue: OfflineQueue::open_default()
+            .await
+            .map_err(|e| warn!(?e, "Failed to open offline queue"))
+            .ok(),
+        target: offline_queue::queue_target(conn),
+        working_directory: wd,
+        model: model.clone().unwrap_or_default(),
+    };
+
     // 2. Enter raw mode, create terminal
     enable_raw_mode()?;
     let mut stdout = io::stdout();
@@ -198,6 +264,11 @@ pub async fn run(
     if conn.is_relay() {
         app.connection_type = "relay".to_string();
     }
+    if let Some(queue) = &offline.queue
+        && let Ok(items) = queue.pending(&offline.target).await
+    {
+        app.queued_requests = items.iter().map(|i| i.request.summary()).collect();
+    }
     if session_id.is_some() {
         app.status = "Loading history...".to_string();
         // Draw once to show the loading status
@@ -207,6 +278,7 @@ pub async fn run(
             Ok(events) => {
                 let count = events.len();
                 for event in events {
+                    app.track_sequence(event.sequence);
                     app.load_history_event(event);
                 }
                 app.finish_history_load();
@@ -425,6 +497,17 @@ pub async fn run(
         })
     });
 
+    // Input handlers send here; requests are forwarded to the stream while it
+    // is up and queued once it is lost.
+    let (outgoing_tx, mut outgoing_rx) = tokio::sync::mpsc::channel::<AgentRequest>(32);
+    let mut stream_lost = false;
+
+    // A lost stream is re-established in the background; the input buffer and
+    // conversation stay as they are meanwhile.
+    let (reconnect_tx, mut reconnect_rx) =
+        tokio::sync::mpsc::channel::<reconnect::ReconnectUpdate>(8);
+    let mut reconnect_task: Option<tokio::task::JoinHandle<()>> = None;
+
     let mut tick = tokio::time::interval(Duration::from_millis(50));
 
     let result: anyhow::Result<()> = loop {
@@ -434,7 +517,19 @@ pub async fn run(
                 terminal.draw(|f| ui::draw(f, &mut app))?;
             }
             Some(term_event) = term_rx.recv() => {
-                input::handle_term_event(&mut app, &request_tx, term_event).await;
+                input::handle_term_event(&mut app, &outgoing_tx, term_event).await;
+            }
+            Some(request) = outgoing_rx.recv() => {
+                let unsent = if stream_lost {
+                    Some(request)
+                } else {
+                    request_tx.send(request).await.err().map(|e| e.0)
+                };
+                if let Some(request) = unsent {
+                    stream_lost = true;
+                    let session_id = app.session_id.clone().unwrap_or_else(|| sid.clone());
+                    offline.queue(&mut app, &session_id, &request).await;
+                }
             }
             Some(cached) = cmd_registry_rx.recv() => {
                 app.command_cache.load(cached);
@@ -452,28 +547,33 @@ pub async fn run(
                 app.add_system_message(crate::app::MessageRole::System, line);
                 app.scroll_to_bottom();
             }
-            Some(grpc_result) = event_rx.recv() => {
+            grpc_result = event_rx.recv(), if !stream_lost => {
                 match grpc_result {
-                    Ok(event) => {
-                        // Re-fetch the command registry when a SessionInfo event
-                        // arrives — MCP tools may have been merged into the
-                        // registry during session initialisation.
-                        if matches!(
-                            event.event,
-                            Some(betcode_proto::v1::agent_event::Event::SessionInfo(_))
-                        ) {
-                            spawn_registry_fetch(
-                                registry_cmd_client.clone(),
-                                registry_auth_token.clone(),
-                                registry_machine_id.clone(),
-                                app.session_id.clone(),
-                                cmd_registry_tx.clone(),
-                            );
+                    Some(Ok(event)) => {
+                        // Already shown if it was among the events resumed
+                        // after a reconnect.
+                        if app.track_sequence(event.sequence) {
+                            // Re-fetch the command registry when a SessionInfo event
+                            // arrives — MCP tools may have been merged into the
+                            // registry during session initialisation.
+                            if matches!(
+                                event.event,
+                                Some(betcode_proto::v1::agent_event::Event::SessionInfo(_))
+                            ) {
+                                spawn_registry_fetch(
+                                    registry_cmd_client.clone(),
+                                    registry_auth_token.clone(),
+                                    registry_machine_id.clone(),
+                                    app.session_id.clone(),
+                                    cmd_registry_tx.clone(),
+                                );
+                            }
+                            app.handle_event(event);
                         }
-                        app.handle_event(event);
                     }
-                    Err(e) => {
+                    Some(Err(e)) => {
                         error!(?e, "Daemon stream error");
+                        stream_lost = true;
                         let msg = e.message();
                         app.status = if msg.contains("broken pipe")
                             || msg.contains("connection reset")
@@ -485,9 +585,88 @@ pub async fn run(
                         };
                         app.agent_busy = false;
                     }
+                    None => {
+                        stream_lost = true;
+                        app.status = "Disconnected: daemon stream ended".to_string();
+                        app.agent_busy = false;
+                    }
+                }
+            }
+            Some(update) = reconnect_rx.recv() => {
+                match update {
+                    reconnect::ReconnectUpdate::Waiting { attempt, delay, last_error } => {
+                        app.status = format!(
+                            "Reconnecting in {}s (attempt {attempt})...",
+                            delay.as_secs()
+                        );
+                        if let Some(e) = last_error {
+                            let _ = write!(app.status, " Last error: {e}");
+                        }
+                    }
+                    reconnect::ReconnectUpdate::Reconnected(reconnected) => {
+                        let reconnected = *reconnected;
+                        *conn = reconnected.conn;
+                        request_tx = reconnected.request_tx;
+                        event_rx = reconnected.event_rx;
+                        stream_handle.abort();
+                        stream_handle = reconnected.stream_handle;
+                        stream_lost = false;
+                        reconnect_task = None;
+                        app.connection_state = ConnectionState::Connected;
+
+                        for event in reconnected.missed_events {
+                            if app.track_sequence(event.sequence) {
+                                app.handle_event(event);
+                            }
+                        }
+                        app.scroll_to_bottom();
+
+                        if let Some(queue) = &offline.queue
+                            && let Ok(items) = queue.pending(&offline.target).await
+                        {
+                            app.queued_requests =
+                                items.iter().map(|i| i.request.summary()).collect();
+                        }
+                        let session_id = app.session_id.clone().unwrap_or_else(|| sid.clone());
+                        app.status = format!(
+                            "Reconnected | Session: {}",
+                            &session_id[..8.min(session_id.len())]
+                        );
+                        if let Some(replayed) = reconnected.replayed
+                            && replayed.delivered > 0
+                        {
+                            let _ = write!(
+                                app.status,
+                                " | {} queued request(s) sent",
+                                replayed.delivered
+                            );
+                        }
+                    }
+                    reconnect::ReconnectUpdate::Failed(e) => {
+                        reconnect_task = None;
+                        app.connection_state = ConnectionState::Disconnected;
+                        app.status = format!("Disconnected: {e}");
+                    }
                 }
             }
         }
+        if stream_lost
+            && reconnect_task.is_none()
+            && app.connection_state != ConnectionState::Disconnected
+        {
+            app.connection_state = ConnectionState::Reconnecting;
+            reconnect_task = Some(reconnect::spawn(
+                reconnect::ReconnectParams {
+                    config: conn.config().clone(),
+                    session_id: app.session_id.clone().unwrap_or_else(|| sid.clone()),
+                    working_directory: offline.working_directory.clone(),
+                    model: offline.model.clone(),
+                    from_sequence: app.last_sequence,
+                    queue: offline.queue.clone(),
+                },
+                reconnect_tx.clone(),
+            ));
+        }
         if app.should_quit {
             break Ok(());
         }
@@ -508,6 +687,9 @@ pub async fn run(
 
     // 10. Shutdown: signal UI thread to stop, clean up gRPC resources
     cancel.cancel();
+    if let Some(h) = reconnect_task {
+        h.abort();
+    }
     let _ = ui_thread.join(); // fast — <50ms due to poll timeout
 
     // Drop the request sender to close the client side of the bidi stream.
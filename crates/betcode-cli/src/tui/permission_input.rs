@@ -127,6 +127,7 @@ async fn submit_permission_edit(app: &mut App, tx: &mpsc::Sender<AgentRequest>)
                                 content: comment,
                                 attachments: Vec::new(),
                                 agent_id: String::new(),
+                                idempotency_key: String::new(),
                             },
                         )),
                     })
@@ -171,6 +172,7 @@ pub async fn send_permission(
                         decision: decision.into(),
                         updated_input,
                         message,
+                        idempotency_key: String::new(),
                     },
                 )),
             })
@@ -0,0 +1,206 @@
+//! Background reconnection of the TUI's conversation stream.
+//!
+//! When the stream drops (laptop sleep, relay or daemon restart) a task
+//! re-establishes it with exponential backoff: a fresh connection, key
+//! exchange for relay connections, a new conversation stream subscribed to
+//! the session, then `ResumeSession` from the last sequence number the TUI
+//! saw so that events emitted in the meantime are not lost. Subscribing
+//! before resuming leaves no gap; the overlap is dropped by sequence number
+//! (see [`App::track_sequence`](crate::app::App::track_sequence)).
+
+use std::time::Duration;
+
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+use betcode_proto::v1::{AgentEvent, AgentRequest};
+
+use crate::connection::{self, ConnectionConfig, ConnectionError, DaemonConnection};
+use crate::offline_queue::{self, OfflineQueue, ReplaySummary};
+
+/// Delay before the first reconnect attempt.
+const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+
+/// Upper bound on the delay between attempts.
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+/// Delay before reconnect attempt `attempt` (counting from 1).
+pub fn backoff_delay(attempt: u32) -> Duration {
+    let doublings = attempt.saturating_sub(1).min(16);
+    INITIAL_BACKOFF
+        .saturating_mul(1 << doublings)
+        .min(MAX_BACKOFF)
+}
+
+/// What to reconnect to.
+pub struct ReconnectParams {
+    pub config: ConnectionConfig,
+    pub session_id: String,
+    pub working_directory: String,
+    pub model: String,
+    /// Last event sequence number the TUI has seen.
+    pub from_sequence: u64,
+    /// Offline queue to replay once reconnected.
+    pub queue: Option<OfflineQueue>,
+}
+
+/// A re-established conversation stream.
+pub struct Reconnected {
+    pub conn: DaemonConnection,
+    pub request_tx: mpsc::Sender<AgentRequest>,
+    pub event_rx: mpsc::Receiver<Result<AgentEvent, tonic::Status>>,
+    pub stream_handle: JoinHandle<()>,
+    /// Events the session emitted after `from_sequence`.
+    pub missed_events: Vec<AgentEvent>,
+    /// Outcome of replaying the offline queue, if it was replayed.
+    pub replayed: Option<ReplaySummary>,
+}
+
+/// Progress of a reconnect task.
+pub enum ReconnectUpdate {
+    /// Waiting `delay` before attempt `attempt`.
+    Waiting {
+        attempt: u32,
+        delay: Duration,
+        last_error: Option<String>,
+    },
+    /// The stream is back.
+    Reconnected(Box<Reconnected>),
+    /// Retrying cannot help, e.g. because the daemon's fingerprint changed.
+    Failed(String),
+}
+
+/// Spawn a task that reconnects, reporting progress on `updates` until it
+/// succeeds, fails for good, or `updates` is closed.
+pub fn spawn(params: ReconnectParams, updates: mpsc::Sender<ReconnectUpdate>) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut attempt: u32 = 0;
+        let mut last_error = None;
+        let mut retry_after = None;
+        loop {
+            attempt = attempt.saturating_add(1);
+            // Wait at least as long as the relay asked, if it throttled us
+            let delay = backoff_delay(attempt).max(retry_after.take().unwrap_or_default());
+            let waiting = ReconnectUpdate::Waiting {
+                attempt,
+                delay,
+                last_error: last_error.take(),
+            };
+            if updates.send(waiting).await.is_err() {
+                return;
+            }
+            tokio::time::sleep(delay).await;
+
+            match try_reconnect(&params).await {
+                Ok(reconnected) => {
+                    info!(attempt, session_id = %params.session_id, "Conversation stream reconnected");
+                    let _ = updates
+                        .send(ReconnectUpdate::Reconnected(Box::new(reconnected)))
+                        .await;
+                    return;
+                }
+                Err(
+                    e @ (ConnectionError::InvalidAddress(_)
+                    | ConnectionError::FingerprintRejected
+                    | ConnectionError::FingerprintMismatch { .. }),
+                ) => {
+                    warn!(error = %e, "Giving up reconnecting");
+                    let _ = updates.send(ReconnectUpdate::Failed(e.to_string())).await;
+                    return;
+                }
+                Err(e) => {
+                    warn!(attempt, error = %e, "Reconnect attempt failed");
+                    retry_after = e.retry_after();
+                    last_error = Some(e.to_string());
+                }
+            }
+        }
+    })
+}
+
+/// One reconnect attempt.
+async fn try_reconnect(params: &ReconnectParams) -> Result<Reconnected, ConnectionError> {
+    let mut conn = DaemonConnection::new(params.config.clone());
+    conn.connect().await?;
+    conn.ensure_relay_key_exchange().await?;
+
+    let (request_tx, event_rx, stream_handle) = conn.converse().await?;
+    let subscribed = request_tx
+        .send(connection::start_conversation_request(
+            params.session_id.clone(),
+            params.working_directory.clone(),
+            params.model.clone(),
+        ))
+        .await;
+    if subscribed.is_err() {
+        stream_handle.abort();
+        return Err(ConnectionError::Unavailable(
+            "conversation stream closed".to_string(),
+        ));
+    }
+
+    let missed_events = match conn
+        .resume_session(&params.session_id, params.from_sequence)
+        .await
+    {
+        Ok(events) => events,
+        Err(e) => {
+            stream_handle.abort();
+            return Err(e);
+        }
+    };
+
+    let replayed = match &params.queue {
+        Some(queue) => offline_queue::replay(&mut conn, queue)
+            .await
+            .map_err(|e| warn!(?e, "Failed to replay offline queue"))
+            .ok(),
+        None => None,
+    };
+
+    Ok(Reconnected {
+        conn,
+        request_tx,
+        event_rx,
+        stream_handle,
+        missed_events,
+        replayed,
+    })
+}
+
+#[cfg(test)]
+#[allow(clippy::panic, clippy::expect_used, clippy::unwrap_used)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backoff_doubles_up_to_cap() {
+        assert_eq!(backoff_delay(1), Duration::from_secs(1));
+        assert_eq!(backoff_delay(2), Duration::from_secs(2));
+        assert_eq!(backoff_delay(5), Duration::from_secs(16));
+        assert_eq!(backoff_delay(6), MAX_BACKOFF);
+        assert_eq!(backoff_delay(u32::MAX), MAX_BACKOFF);
+    }
+
+    #[tokio::test]
+    async fn stops_when_receiver_closes() {
+        let (tx, rx) = mpsc::channel(1);
+        drop(rx);
+        let handle = spawn(
+            ReconnectParams {
+                config: ConnectionConfig::default(),
+                session_id: "s1".to_string(),
+                working_directory: "/tmp".to_string(),
+                model: String::new(),
+                from_sequence: 0,
+                queue: None,
+            },
+            tx,
+        );
+        tokio::time::timeout(Duration::from_secs(1), handle)
+            .await
+            .expect("task should exit without waiting for a reconnect")
+            .unwrap();
+    }
+}
@@ -10,6 +10,7 @@ use unicode_width::UnicodeWidthStr;
 use super::detail_panel;
 use super::panels;
 use crate::app::{App, AppMode, DisplayMessage, MessageRole, ToolCallEntry, ToolCallStatus};
+use crate::connection::ConnectionState;
 
 /// Which panel to show at the bottom.
 enum BottomPanel {
@@ -166,6 +167,7 @@ pub fn draw(frame: &mut Frame<'_>, app: &mut App) {
             pending_permissions: usize::from(app.pending_permission.is_some()),
             worktree: None,
             uptime_secs: 0,
+            queued: app.queued_requests.clone(),
         };
         render_status_panel(frame, frame.area(), &info);
     }
@@ -183,6 +185,11 @@ fn draw_header(frame: &mut Frame<'_>, app: &App, area: Rect) {
         .map(|s| format!(" | Session: {}", &s[..8.min(s.len())]))
         .unwrap_or_default();
     let busy = if app.agent_busy { " [thinking...]" } else { "" };
+    let connection = match app.connection_state {
+        ConnectionState::Reconnecting => " [reconnecting...]",
+        ConnectionState::Disconnected => " [disconnected]",
+        ConnectionState::Connected | ConnectionState::Connecting => "",
+    };
 
     let header = Paragraph::new(Line::from(vec![
         Span::styled(
@@ -193,6 +200,7 @@ fn draw_header(frame: &mut Frame<'_>, app: &App, area: Rect) {
         ),
         Span::raw(session_info),
         Span::styled(busy, Style::default().fg(Color::Yellow)),
+        Span::styled(connection, Style::default().fg(Color::Red)),
     ]));
     frame.render_widget(header, area);
 }
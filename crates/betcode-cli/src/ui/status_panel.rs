@@ -20,12 +20,18 @@ pub struct SessionStatusInfo {
     pub pending_permissions: usize,
     pub worktree: Option<String>,
     pub uptime_secs: u64,
+    /// Summaries of requests waiting in the offline queue.
+    pub queued: Vec<String>,
 }
 
+/// Queued requests listed individually; the rest are only counted.
+const MAX_QUEUED_LINES: usize = 3;
+
 /// Render the session status panel as a centered overlay.
 pub fn render_status_panel(frame: &mut Frame<'_>, area: Rect, info: &SessionStatusInfo) {
     let panel_width = 50u16.min(area.width.saturating_sub(4));
-    let panel_height = 14u16.min(area.height.saturating_sub(2));
+    let queued_lines = u16::try_from(info.queued.len().min(MAX_QUEUED_LINES)).unwrap_or(0);
+    let panel_height = (14 + queued_lines).min(area.height.saturating_sub(2));
 
     let panel_area = centered_rect(panel_width, panel_height, area);
 
@@ -40,6 +46,7 @@ pub fn render_status_panel(frame: &mut Frame<'_>, area: Rect, info: &SessionStat
     let agents_str = info.active_agents.to_string();
     let perms_str = info.pending_permissions.to_string();
     let uptime_str = format_uptime(info.uptime_secs);
+    let queued_str = info.queued.len().to_string();
 
     let mut lines = vec![
         labeled_line("CWD:", &info.cwd, label_style, value_style),
@@ -61,6 +68,21 @@ pub fn render_status_panel(frame: &mut Frame<'_>, area: Rect, info: &SessionStat
         value_style,
     ));
 
+    if !info.queued.is_empty() {
+        lines.push(labeled_line(
+            "Queued:",
+            &queued_str,
+            label_style,
+            value_style,
+        ));
+        for summary in info.queued.iter().take(MAX_QUEUED_LINES) {
+            lines.push(Line::from(Span::styled(
+                format!("  {summary}"),
+                Style::default().fg(Color::Yellow),
+            )));
+        }
+    }
+
     let panel = Paragraph::new(lines).block(
         Block::default()
             .borders(Borders::ALL)
@@ -143,6 +165,7 @@ mod tests {
             pending_permissions: 0,
             worktree: Some("feature/auth".to_string()),
             uptime_secs: 3600,
+            queued: Vec::new(),
         });
         // Just verify it doesn't panic - detailed content checking is brittle
     }
@@ -166,6 +189,38 @@ mod tests {
             pending_permissions: 1,
             worktree: None,
             uptime_secs: 120,
+            queued: Vec::new(),
         });
     }
+
+    #[test]
+    fn test_status_panel_lists_queued_requests() {
+        let backend = TestBackend::new(60, 24);
+        let mut terminal = Terminal::new(backend).unwrap();
+        let info = SessionStatusInfo {
+            cwd: "/tmp".to_string(),
+            session_id: "s1".to_string(),
+            connection: "relay".to_string(),
+            model: "claude-opus-4".to_string(),
+            active_agents: 0,
+            pending_permissions: 0,
+            worktree: None,
+            uptime_secs: 0,
+            queued: (1..=5).map(|i| format!("prompt: task {i}")).collect(),
+        };
+        terminal
+            .draw(|f| render_status_panel(f, f.area(), &info))
+            .unwrap();
+
+        let screen: String = terminal
+            .backend()
+            .buffer()
+            .content()
+            .iter()
+            .map(ratatui::buffer::Cell::symbol)
+            .collect();
+        assert!(screen.contains("Queued: 5"));
+        assert!(screen.contains("prompt: task 3"));
+        assert!(!screen.contains("prompt: task 4"));
+    }
 }
@@ -4,6 +4,9 @@
 
 use std::io::{self, Write};
 
+use betcode_proto::v1::{
+    GcWorktreesResponse, SetupStepResult, SyncStrategy, WorktreeSetupEvent, WorktreeStatus,
+};
 use clap::Subcommand;
 
 use crate::connection::DaemonConnection;
@@ -22,10 +25,22 @@ pub enum WorktreeAction {
         /// Branch name to create
         #[arg(short, long)]
         branch: String,
-        /// Setup script to run after creation (e.g. "npm install")
+        /// Setup script to run after creation (e.g. "npm install"); overrides
+        /// the repository's `.betcode/worktree.toml` pipeline
         #[arg(long)]
         setup: Option<String>,
     },
+    /// Run a worktree's setup pipeline again, or resume it after a failure
+    Setup {
+        /// Worktree ID
+        id: String,
+        /// Resume at the first step that did not succeed
+        #[arg(long, conflicts_with = "from")]
+        retry: bool,
+        /// Start at the named step
+        #[arg(long)]
+        from: Option<String>,
+    },
     /// List all worktrees
     List {
         /// Filter by repository ID
@@ -42,6 +57,34 @@ pub enum WorktreeAction {
         /// Worktree ID
         id: String,
     },
+    /// Show git status: dirty files, ahead/behind and last commit
+    Status {
+        /// Worktree ID
+        id: String,
+    },
+    /// Fetch and rebase (or merge) the base branch into a worktree
+    Sync {
+        /// Worktree ID
+        id: String,
+        /// Merge instead of rebasing
+        #[arg(long)]
+        merge: bool,
+        /// Base ref to sync with (default: the remote's default branch)
+        #[arg(long)]
+        base: Option<String>,
+    },
+    /// Remove merged, idle or missing worktrees
+    Gc {
+        /// Only consider worktrees of this repository ID
+        #[arg(short, long)]
+        repo: Option<String>,
+        /// Report what would be removed without removing anything
+        #[arg(long)]
+        dry_run: bool,
+        /// Idle age in days (default: the daemon's configured value)
+        #[arg(long)]
+        max_idle_days: Option<u64>,
+    },
 }
 
 /// Execute a worktree subcommand.
@@ -55,10 +98,17 @@ pub async fn run(conn: &mut DaemonConnection, action: WorktreeAction) -> anyhow:
             setup,
         } => {
             let detail = conn
-                .create_worktree(&name, &repo, &branch, setup.as_deref())
+                .create_worktree(&name, &repo, &branch, setup.as_deref(), true)
                 .await?;
             writeln!(out, "Created worktree:")?;
             write_detail(&mut out, &detail)?;
+            if detail.setup_status == "pending" {
+                writeln!(out, "\nRunning setup...")?;
+                run_setup(conn, &mut out, &detail.id, false, None).await?;
+            }
+        }
+        WorktreeAction::Setup { id, retry, from } => {
+            run_setup(conn, &mut out, &id, retry, from.as_deref()).await?;
         }
         WorktreeAction::List { repo } => {
             let resp = conn.list_worktrees(repo.as_deref()).await?;
@@ -96,6 +146,193 @@ pub async fn run(conn: &mut DaemonConnection, action: WorktreeAction) -> anyhow:
                 writeln!(out, "Worktree {id} not found.")?;
             }
         }
+        WorktreeAction::Status { id } => {
+            let status = conn.get_worktree_status(&id).await?;
+            write_status(&mut out, &status)?;
+        }
+        WorktreeAction::Sync { id, merge, base } => {
+            sync(conn, &mut out, &id, merge, base.as_deref()).await?;
+        }
+        WorktreeAction::Gc {
+            repo,
+            dry_run,
+            max_idle_days,
+        } => {
+            let max_idle_secs = max_idle_days.map(|d| d.saturating_mul(24 * 60 * 60));
+            let resp = conn
+                .gc_worktrees(repo.as_deref(), max_idle_secs, dry_run)
+                .await?;
+            write_gc(&mut out, &resp)?;
+        }
+    }
+    Ok(())
+}
+
+/// Sync a worktree and report the outcome, failing on conflicts.
+async fn sync(
+    conn: &mut DaemonConnection,
+    out: &mut impl Write,
+    id: &str,
+    merge: bool,
+    base: Option<&str>,
+) -> anyhow::Result<()> {
+    let strategy = if merge {
+        SyncStrategy::Merge
+    } else {
+        SyncStrategy::Rebase
+    };
+    let resp = conn.sync_worktree(id, strategy, base).await?;
+    if resp.success {
+        writeln!(out, "Synced worktree {id} with {}.", resp.base_ref)?;
+    } else {
+        writeln!(out, "Sync with {} failed: {}", resp.base_ref, resp.message)?;
+        if !resp.conflicts.is_empty() {
+            writeln!(out, "Conflicts (sync aborted, worktree unchanged):")?;
+            for path in &resp.conflicts {
+                writeln!(out, "  {path}")?;
+            }
+        }
+    }
+    if let Some(ref status) = resp.status {
+        write_status(out, status)?;
+    }
+    if !resp.success {
+        anyhow::bail!("worktree sync failed");
+    }
+    Ok(())
+}
+
+/// Run a worktree's setup pipeline, streaming progress, failing if it fails.
+async fn run_setup(
+    conn: &mut DaemonConnection,
+    out: &mut impl Write,
+    id: &str,
+    retry: bool,
+    from_step: Option<&str>,
+) -> anyhow::Result<()> {
+    let mut stream = conn.run_worktree_setup(id, retry, from_step).await?;
+    let mut status = String::new();
+    while let Some(event) = stream
+        .message()
+        .await
+        .map_err(|e| anyhow::anyhow!("Stream error: {e}"))?
+    {
+        if let Some(s) = write_setup_event(out, &event)? {
+            status = s;
+        }
+    }
+    match status.as_str() {
+        "succeeded" => writeln!(out, "Setup succeeded.")?,
+        "failed" => {
+            writeln!(
+                out,
+                "Setup failed; the worktree was kept. Fix the problem and run \
+                 `betcode worktree setup {id} --retry` to resume."
+            )?;
+            anyhow::bail!("worktree setup failed");
+        }
+        "none" => writeln!(out, "No setup pipeline configured.")?,
+        other => writeln!(out, "Setup ended ({other}).")?,
+    }
+    Ok(())
+}
+
+/// Write a setup progress event, returning the final status if this was the last event.
+fn write_setup_event(w: &mut impl Write, event: &WorktreeSetupEvent) -> io::Result<Option<String>> {
+    use betcode_proto::v1::worktree_setup_event::Event;
+    match &event.event {
+        Some(Event::StepStarted(s)) => writeln!(w, "[{}/{}] {}", s.index, s.total, s.name)?,
+        Some(Event::Output(o)) => writeln!(w, "    {}", o.line)?,
+        Some(Event::StepFinished(r)) => write_step(w, r)?,
+        Some(Event::Finished(f)) => return Ok(Some(f.status.clone())),
+        None => {}
+    }
+    Ok(None)
+}
+
+/// Write a single setup step result line.
+fn write_step(w: &mut impl Write, r: &SetupStepResult) -> io::Result<()> {
+    write!(w, "    {:<10} {}", r.status, r.name)?;
+    if r.duration_ms > 0 {
+        write!(w, " ({} ms)", r.duration_ms)?;
+    }
+    if let Some(code) = r.exit_code {
+        write!(w, " exit {code}")?;
+    }
+    writeln!(w)?;
+    if !r.error.is_empty() {
+        for line in r.error.lines() {
+            writeln!(w, "      {line}")?;
+        }
+    }
+    Ok(())
+}
+
+/// Write a worktree git status to the given writer.
+fn write_status(w: &mut impl Write, st: &WorktreeStatus) -> io::Result<()> {
+    writeln!(w, "  ID:       {}", st.id)?;
+    writeln!(w, "  Branch:   {}", st.branch)?;
+    if !st.exists_on_disk {
+        writeln!(w, "  On disk:  no")?;
+        return Ok(());
+    }
+    if st.compare_ref.is_empty() {
+        writeln!(w, "  Tracking: (none)")?;
+    } else {
+        writeln!(
+            w,
+            "  Tracking: {} (ahead {}, behind {})",
+            st.compare_ref, st.ahead, st.behind
+        )?;
+    }
+    if let Some(ref c) = st.last_commit {
+        let sha = c.sha.get(..10).unwrap_or(&c.sha);
+        writeln!(w, "  Last:     {sha} {} ({})", c.subject, c.author)?;
+    }
+    if st.dirty_files.is_empty() {
+        writeln!(w, "  Changes:  clean")?;
+    } else {
+        writeln!(w, "  Changes:  {} file(s)", st.dirty_files.len())?;
+        for f in &st.dirty_files {
+            writeln!(w, "    {} {}", f.status, f.path)?;
+        }
+    }
+    Ok(())
+}
+
+/// Write a GC report to the given writer.
+fn write_gc(w: &mut impl Write, resp: &GcWorktreesResponse) -> io::Result<()> {
+    if resp.entries.is_empty() {
+        writeln!(w, "No stale worktrees found.")?;
+        return Ok(());
+    }
+    writeln!(
+        w,
+        "{:<36}  {:<16}  {:<16}  {:<16}  ACTION",
+        "ID", "NAME", "BRANCH", "REASONS"
+    )?;
+    for e in &resp.entries {
+        let action = if e.removed {
+            "removed".to_string()
+        } else if !e.skipped_reason.is_empty() {
+            format!("kept: {}", e.skipped_reason)
+        } else {
+            "would remove".to_string()
+        };
+        writeln!(
+            w,
+            "{:<36}  {:<16}  {:<16}  {:<16}  {action}",
+            e.id,
+            truncate(&e.name, 16),
+            truncate(&e.branch, 16),
+            e.reasons.join(","),
+        )?;
+    }
+    let removed = resp.entries.iter().filter(|e| e.removed).count();
+    if resp.dry_run {
+        writeln!(w, "\n{} stale worktree(s) (dry run)", resp.entries.len())?;
+    } else {
+        writeln!(w, "\n{removed} worktree(s) removed")?;
     }
     Ok(())
 }
@@ -122,5 +359,11 @@ fn write_detail(w: &mut impl Write, wt: &betcode_proto::v1::WorktreeDetail) -> i
     if let Some(ref ts) = wt.last_active {
         writeln!(w, "  Active:   {} (unix)", ts.seconds)?;
     }
+    if wt.setup_status != "none" && !wt.setup_status.is_empty() {
+        writeln!(w, "  Pipeline: {}", wt.setup_status)?;
+        for step in &wt.setup_steps {
+            write_step(w, step)?;
+        }
+    }
     Ok(())
 }
@@ -0,0 +1,180 @@
+//! Dependency-graph validation for orchestration steps.
+//!
+//! Shared by the daemon's `DagScheduler` and the CLI, which validates
+//! orchestration files locally before submitting them.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+
+/// Why a step dependency graph is not a valid DAG.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum DagError {
+    #[error("Step '{step}' depends on unknown step '{dep}'")]
+    UnknownDependency { step: String, dep: String },
+
+    #[error("Step '{step}' depends on itself")]
+    SelfDependency { step: String },
+
+    #[error("Dependency graph contains a cycle involving: {}", steps.join(", "))]
+    Cycle { steps: Vec<String> },
+
+    #[error("Step '{step}' fans out over '{source_step}', which is not one of its dependencies")]
+    FanOutSource { step: String, source_step: String },
+
+    #[error("Step '{step}' runs on dependency failure but has no dependencies")]
+    NoDependencies { step: String },
+}
+
+/// Check a fan-out step draws its items from one of its direct dependencies.
+pub fn validate_fan_out(step: &str, source: &str, depends_on: &[String]) -> Result<(), DagError> {
+    if depends_on.iter().any(|d| d == source) {
+        Ok(())
+    } else {
+        Err(DagError::FanOutSource {
+            step: step.to_string(),
+            source_step: source.to_string(),
+        })
+    }
+}
+
+/// Validate that the dependency graph is a DAG (no cycles) using Kahn's algorithm.
+///
+/// `deps` maps a step ID to the IDs it depends on. Every dependency must name
+/// a step in `steps`.
+pub fn validate_dag<S: std::hash::BuildHasher>(
+    steps: &[String],
+    deps: &HashMap<String, Vec<String>, S>,
+) -> Result<(), DagError> {
+    let step_set: HashSet<&str> = steps.iter().map(String::as_str).collect();
+
+    // Validate that all dependencies reference known steps
+    for (step, dep_list) in deps {
+        for dep in dep_list {
+            if !step_set.contains(dep.as_str()) {
+                return Err(DagError::UnknownDependency {
+                    step: step.clone(),
+                    dep: dep.clone(),
+                });
+            }
+        }
+        // Self-dependency check
+        if dep_list.contains(step) {
+            return Err(DagError::SelfDependency { step: step.clone() });
+        }
+    }
+
+    // Kahn's algorithm: compute in-degrees, process zero-degree nodes
+    let mut in_degree: HashMap<&str, usize> = HashMap::new();
+    let mut adj: HashMap<&str, Vec<&str>> = HashMap::new();
+
+    for step in steps {
+        in_degree.entry(step.as_str()).or_insert(0);
+        adj.entry(step.as_str()).or_default();
+    }
+
+    for (step, dep_list) in deps {
+        for dep in dep_list {
+            adj.entry(dep.as_str()).or_default().push(step.as_str());
+            *in_degree.entry(step.as_str()).or_insert(0) += 1;
+        }
+    }
+
+    let mut queue: VecDeque<&str> = VecDeque::new();
+    for (step, &deg) in &in_degree {
+        if deg == 0 {
+            queue.push_back(step);
+        }
+    }
+
+    let mut processed: HashSet<&str> = HashSet::new();
+    while let Some(step) = queue.pop_front() {
+        processed.insert(step);
+        if let Some(neighbors) = adj.get(step) {
+            for &neighbor in neighbors {
+                if let Some(deg) = in_degree.get_mut(neighbor) {
+                    *deg = deg.saturating_sub(1);
+                    if *deg == 0 {
+                        queue.push_back(neighbor);
+                    }
+                }
+            }
+        }
+    }
+
+    if processed.len() != steps.len() {
+        // Steps never reaching in-degree zero are on (or behind) a cycle.
+        let steps = steps
+            .iter()
+            .filter(|s| !processed.contains(s.as_str()))
+            .cloned()
+            .collect();
+        return Err(DagError::Cycle { steps });
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+
+    fn graph(edges: &[(&str, &[&str])]) -> (Vec<String>, HashMap<String, Vec<String>>) {
+        let steps = edges.iter().map(|(s, _)| (*s).to_string()).collect();
+        let deps = edges
+            .iter()
+            .map(|(s, d)| {
+                (
+                    (*s).to_string(),
+                    d.iter().map(|x| (*x).to_string()).collect(),
+                )
+            })
+            .collect();
+        (steps, deps)
+    }
+
+    #[test]
+    fn accepts_diamond() {
+        let (steps, deps) = graph(&[("a", &[]), ("b", &["a"]), ("c", &["a"]), ("d", &["b", "c"])]);
+        assert!(validate_dag(&steps, &deps).is_ok());
+    }
+
+    #[test]
+    fn reports_cycle_members() {
+        let (steps, deps) = graph(&[("a", &[]), ("b", &["c"]), ("c", &["b"])]);
+        let err = validate_dag(&steps, &deps).unwrap_err();
+        assert_eq!(
+            err,
+            DagError::Cycle {
+                steps: vec!["b".into(), "c".into()]
+            }
+        );
+        assert!(err.to_string().contains("cycle"));
+    }
+
+    #[test]
+    fn fan_out_source_must_be_a_dependency() {
+        let deps = vec!["list".to_string()];
+        assert!(validate_fan_out("each", "list", &deps).is_ok());
+        assert_eq!(
+            validate_fan_out("each", "other", &deps),
+            Err(DagError::FanOutSource {
+                step: "each".into(),
+                source_step: "other".into()
+            })
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_and_self_dependencies() {
+        let (steps, deps) = graph(&[("a", &["zz"])]);
+        assert!(matches!(
+            validate_dag(&steps, &deps),
+            Err(DagError::UnknownDependency { .. })
+        ));
+        let (steps, deps) = graph(&[("a", &["a"])]);
+        assert!(matches!(
+            validate_dag(&steps, &deps),
+            Err(DagError::SelfDependency { .. })
+        ));
+    }
+}
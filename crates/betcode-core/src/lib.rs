@@ -4,17 +4,24 @@
 //! - NDJSON parsing for Claude Code stream-json protocol
 //! - Configuration resolution and hierarchy
 //! - Permission rule matching engine
+//! - Orchestration dependency-graph validation, step output templates and
+//!   review loop prompts
+//! - Wake-on-LAN targets and magic packets
 //! - Common error types
 
 pub mod commands;
 pub mod config;
+pub mod dag;
 pub mod db;
 pub mod error;
 #[cfg(feature = "metrics")]
 pub mod metrics;
 pub mod ndjson;
 pub mod permissions;
+pub mod review;
+pub mod step_template;
 pub mod tracing_init;
+pub mod wake;
 
 pub use config::Config;
 pub use error::{Error, Result};
@@ -0,0 +1,146 @@
+//! Prompts and verdicts of orchestration review loops.
+//!
+//! A step with a reviewer runs in rounds: the worker does the task, then a
+//! reviewer checks the result against its acceptance criteria and ends its
+//! reply with a verdict line. Until the reviewer approves, the worker is sent
+//! back to the task with the reviewer's critique.
+
+use crate::step_template::truncate_output;
+
+/// Verdict line prefix a reviewer ends its reply with.
+pub const VERDICT_PREFIX: &str = "VERDICT:";
+
+/// Verdict that accepts the worker's result.
+pub const APPROVED: &str = "APPROVED";
+
+/// Verdict that sends the worker back with the critique.
+pub const CHANGES_REQUESTED: &str = "CHANGES REQUESTED";
+
+/// Outcome of one review round.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Verdict {
+    /// Whether the reviewer accepted the result.
+    pub approved: bool,
+    /// The reviewer's reply without its verdict line.
+    pub critique: String,
+}
+
+/// Prompt for the reviewer of one round.
+///
+/// The worker's task and output are capped at `max_chars` each.
+pub fn review_prompt(criteria: &str, task: &str, output: &str, max_chars: usize) -> String {
+    format!(
+        "You are reviewing another agent's work on this task:\n\n{task}\n\n\
+         The agent reported:\n\n{output}\n\n\
+         Check the work in the working directory against these acceptance \
+         criteria, running whatever commands you need:\n\n{criteria}\n\n\
+         Explain what is wrong or missing, if anything. End your reply with a \
+         line reading exactly `{VERDICT_PREFIX} {APPROVED}` if the work meets \
+         every criterion, or `{VERDICT_PREFIX} {CHANGES_REQUESTED}` otherwise.",
+        task = truncate_output(task.trim(), max_chars),
+        output = truncate_output(output.trim(), max_chars),
+        criteria = criteria.trim(),
+    )
+}
+
+/// Prompt sending the worker back to its task with the reviewer's critique.
+pub fn revision_prompt(task: &str, critique: &str, max_chars: usize) -> String {
+    format!(
+        "{task}\n\n\
+         A reviewer checked your previous attempt at this task, whose changes \
+         are still in the working directory, and asked for changes:\n\n\
+         {critique}\n\n\
+         Address the review and finish the task.",
+        task = task.trim(),
+        critique = truncate_output(critique.trim(), max_chars),
+    )
+}
+
+/// Read the verdict from a reviewer's reply.
+///
+/// The last line starting with [`VERDICT_PREFIX`] (in any case) counts. A
+/// reply without one, or with an unrecognised verdict, is not an approval.
+pub fn parse_verdict(reply: &str) -> Verdict {
+    let lines: Vec<&str> = reply.lines().collect();
+    let verdict_line = lines.iter().rposition(|line| {
+        let line = line.trim().trim_matches(|c| c == '*' || c == '`');
+        line.to_ascii_uppercase().starts_with(VERDICT_PREFIX)
+    });
+    let Some(index) = verdict_line else {
+        return Verdict {
+            approved: false,
+            critique: reply.trim().to_string(),
+        };
+    };
+
+    let verdict = lines[index]
+        .trim()
+        .trim_matches(|c| c == '*' || c == '`')
+        .to_ascii_uppercase();
+    let verdict = verdict[VERDICT_PREFIX.len()..]
+        .trim()
+        .trim_matches(|c: char| !c.is_ascii_alphanumeric());
+    let critique = lines
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| *i != index)
+        .map(|(_, line)| *line)
+        .collect::<Vec<_>>()
+        .join("\n");
+    Verdict {
+        approved: verdict == APPROVED,
+        critique: critique.trim().to_string(),
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::panic, clippy::expect_used, clippy::unwrap_used)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn approves_on_last_verdict_line() {
+        let verdict = parse_verdict("Tests pass.\nLooks good.\n\nVERDICT: APPROVED\n");
+        assert!(verdict.approved);
+        assert_eq!(verdict.critique, "Tests pass.\nLooks good.");
+
+        let verdict = parse_verdict("**Verdict: approved.**");
+        assert!(verdict.approved);
+    }
+
+    #[test]
+    fn changes_requested_keeps_critique() {
+        let reply = "VERDICT: APPROVED was my first thought, but\n\
+                     `cargo test` fails in parser::tests.\n\
+                     VERDICT: CHANGES REQUESTED";
+        let verdict = parse_verdict(reply);
+        assert!(!verdict.approved);
+        assert_eq!(
+            verdict.critique,
+            "VERDICT: APPROVED was my first thought, but\n`cargo test` fails in parser::tests."
+        );
+    }
+
+    #[test]
+    fn missing_or_unknown_verdict_is_not_approval() {
+        let verdict = parse_verdict("I could not run the tests.");
+        assert!(!verdict.approved);
+        assert_eq!(verdict.critique, "I could not run the tests.");
+
+        assert!(!parse_verdict("VERDICT: MOSTLY APPROVED").approved);
+        assert!(!parse_verdict("").approved);
+    }
+
+    #[test]
+    fn prompts_include_task_and_feedback() {
+        let prompt = review_prompt("cargo test passes", "Fix the parser", "Fixed it", 100);
+        assert!(prompt.contains("Fix the parser"));
+        assert!(prompt.contains("Fixed it"));
+        assert!(prompt.contains("cargo test passes"));
+        assert!(prompt.contains("VERDICT: APPROVED"));
+
+        let prompt = revision_prompt("Fix the parser", &"x".repeat(200), 50);
+        assert!(prompt.starts_with("Fix the parser"));
+        assert!(prompt.contains(crate::step_template::TRUNCATION_MARKER));
+    }
+}
@@ -0,0 +1,385 @@
+//! Step output references in orchestration prompts.
+//!
+//! A step prompt may embed `{{steps.<id>.result}}` to receive what an upstream
+//! step produced (its final assistant text). References are resolved when the
+//! step is dispatched; each substituted output is truncated to a size cap so a
+//! verbose upstream step cannot blow up downstream prompts.
+//!
+//! A fan-out step runs once per item of an upstream step's output; its prompt
+//! receives the item as `{{item}}`.
+//!
+//! Other `{{ ... }}` sequences are left untouched, so prompts can still quote
+//! template syntax from other tools.
+
+use std::collections::{HashMap, HashSet};
+
+/// Marker appended to truncated step output.
+pub const TRUNCATION_MARKER: &str = "\n[... output truncated]";
+
+/// Why a step prompt's output references are invalid.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum TemplateError {
+    #[error("Step '{step}' has a malformed reference '{{{{{reference}}}}}'")]
+    Malformed { step: String, reference: String },
+
+    #[error(
+        "Step '{step}' references unknown field '{field}' of step '{target}' (expected 'result')"
+    )]
+    UnknownField {
+        step: String,
+        target: String,
+        field: String,
+    },
+
+    #[error("Step '{step}' references unknown step '{target}'")]
+    UnknownStep { step: String, target: String },
+
+    #[error("Step '{step}' references step '{target}', which is not one of its dependencies")]
+    NotUpstream { step: String, target: String },
+}
+
+/// A `{{steps.<id>.<field>}}` placeholder found in a prompt.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Placeholder<'a> {
+    /// Byte range of the whole placeholder, braces included.
+    start: usize,
+    end: usize,
+    /// Text between the braces, trimmed.
+    inner: &'a str,
+}
+
+/// Find every `{{ steps.… }}` placeholder in a prompt.
+fn placeholders(prompt: &str) -> Vec<Placeholder<'_>> {
+    let mut found = Vec::new();
+    let mut pos = 0;
+    while let Some(open) = prompt[pos..].find("{{") {
+        let start = pos + open;
+        let Some(close) = prompt[start + 2..].find("}}") else {
+            break;
+        };
+        let end = start + 2 + close + 2;
+        let inner = prompt[start + 2..end - 2].trim();
+        if inner.starts_with("steps.") {
+            found.push(Placeholder { start, end, inner });
+            pos = end;
+        } else {
+            pos = start + 2;
+        }
+    }
+    found
+}
+
+/// Split a placeholder into the referenced step ID, validating the field.
+fn target<'a>(step: &str, inner: &'a str) -> Result<&'a str, TemplateError> {
+    let rest = &inner["steps.".len()..];
+    let Some((target, field)) = rest.rsplit_once('.') else {
+        return Err(TemplateError::Malformed {
+            step: step.to_string(),
+            reference: inner.to_string(),
+        });
+    };
+    if target.is_empty() {
+        return Err(TemplateError::Malformed {
+            step: step.to_string(),
+            reference: inner.to_string(),
+        });
+    }
+    if field != "result" {
+        return Err(TemplateError::UnknownField {
+            step: step.to_string(),
+            target: target.to_string(),
+            field: field.to_string(),
+        });
+    }
+    Ok(target)
+}
+
+/// Step IDs referenced by a prompt, in order of first appearance.
+pub fn step_refs<'a>(step: &str, prompt: &'a str) -> Result<Vec<&'a str>, TemplateError> {
+    let mut refs: Vec<&str> = Vec::new();
+    for p in placeholders(prompt) {
+        let t = target(step, p.inner)?;
+        if !refs.contains(&t) {
+            refs.push(t);
+        }
+    }
+    Ok(refs)
+}
+
+/// Check that every reference in every prompt names a transitive dependency.
+///
+/// `prompts` maps step ID to prompt and `deps` maps step ID to the IDs it
+/// depends on. The dependency graph itself should already be validated.
+pub fn validate_refs<S1, S2>(
+    prompts: &HashMap<String, String, S1>,
+    deps: &HashMap<String, Vec<String>, S2>,
+) -> Result<(), TemplateError>
+where
+    S1: std::hash::BuildHasher,
+    S2: std::hash::BuildHasher,
+{
+    for (step, prompt) in prompts {
+        let refs = step_refs(step, prompt)?;
+        if refs.is_empty() {
+            continue;
+        }
+        let upstream = upstream_of(step, deps);
+        for t in refs {
+            if !prompts.contains_key(t) {
+                return Err(TemplateError::UnknownStep {
+                    step: step.clone(),
+                    target: t.to_string(),
+                });
+            }
+            if !upstream.contains(t) {
+                return Err(TemplateError::NotUpstream {
+                    step: step.clone(),
+                    target: t.to_string(),
+                });
+            }
+        }
+    }
+    Ok(())
+}
+
+/// All steps `step` transitively depends on.
+fn upstream_of<'a, S: std::hash::BuildHasher>(
+    step: &str,
+    deps: &'a HashMap<String, Vec<String>, S>,
+) -> HashSet<&'a str> {
+    let mut seen = HashSet::new();
+    let mut stack: Vec<&str> = deps
+        .get(step)
+        .map(|d| d.iter().map(String::as_str).collect())
+        .unwrap_or_default();
+    while let Some(s) = stack.pop() {
+        if seen.insert(s)
+            && let Some(d) = deps.get(s)
+        {
+            stack.extend(d.iter().map(String::as_str));
+        }
+    }
+    seen
+}
+
+/// Substitute step output references in a prompt.
+///
+/// Each substituted output is cut to `max_chars`, and all substitutions
+/// together to `budget_chars`; a reference to a step without output becomes
+/// empty. Returns the rendered prompt. Unknown fields are left as written,
+/// since prompts are validated before dispatch.
+pub fn render<'a>(
+    prompt: &str,
+    output: impl Fn(&str) -> Option<&'a str>,
+    max_chars: usize,
+    budget_chars: usize,
+) -> String {
+    let mut rendered = String::with_capacity(prompt.len());
+    let mut budget = budget_chars;
+    let mut pos = 0;
+    for p in placeholders(prompt) {
+        rendered.push_str(&prompt[pos..p.start]);
+        pos = p.end;
+        match target("", p.inner) {
+            Ok(t) => {
+                let text = truncate_output(output(t).unwrap_or_default(), max_chars.min(budget));
+                budget = budget.saturating_sub(text.chars().count());
+                rendered.push_str(&text);
+            }
+            Err(_) => rendered.push_str(&prompt[p.start..p.end]),
+        }
+    }
+    rendered.push_str(&prompt[pos..]);
+    rendered
+}
+
+/// Substitute the fan-out item for `{{item}}` placeholders.
+pub fn render_item(prompt: &str, item: &str) -> String {
+    let mut rendered = String::with_capacity(prompt.len() + item.len());
+    let mut pos = 0;
+    while let Some(open) = prompt[pos..].find("{{") {
+        let start = pos + open;
+        let Some(close) = prompt[start + 2..].find("}}") else {
+            break;
+        };
+        let end = start + 2 + close + 2;
+        rendered.push_str(&prompt[pos..start]);
+        if prompt[start + 2..end - 2].trim() == "item" {
+            rendered.push_str(item);
+        } else {
+            rendered.push_str(&prompt[start..end]);
+        }
+        pos = end;
+    }
+    rendered.push_str(&prompt[pos..]);
+    rendered
+}
+
+/// Split a step's output into fan-out items.
+///
+/// Output that is a JSON array yields one item per element (strings as-is,
+/// other values as JSON). Otherwise every non-empty line is an item, with a
+/// leading list bullet (`-`, `*`) removed.
+pub fn fan_out_items(output: &str) -> Vec<String> {
+    let trimmed = output.trim();
+    if trimmed.starts_with('[')
+        && let Ok(serde_json::Value::Array(values)) = serde_json::from_str(trimmed)
+    {
+        return values
+            .into_iter()
+            .map(|v| match v {
+                serde_json::Value::String(s) => s,
+                other => other.to_string(),
+            })
+            .filter(|s| !s.trim().is_empty())
+            .collect();
+    }
+    trimmed
+        .lines()
+        .map(|line| {
+            let line = line.trim();
+            line.strip_prefix("- ")
+                .or_else(|| line.strip_prefix("* "))
+                .unwrap_or(line)
+                .trim()
+                .to_string()
+        })
+        .filter(|line| !line.is_empty())
+        .collect()
+}
+
+/// Cut `text` to at most `max_chars` characters, marking the cut.
+///
+/// The marker counts towards the limit; a limit too small for it yields an
+/// empty string.
+pub fn truncate_output(text: &str, max_chars: usize) -> String {
+    if text.chars().count() <= max_chars {
+        return text.to_string();
+    }
+    let marker_len = TRUNCATION_MARKER.chars().count();
+    if max_chars < marker_len {
+        return String::new();
+    }
+    let mut out: String = text.chars().take(max_chars - marker_len).collect();
+    out.push_str(TRUNCATION_MARKER);
+    out
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+
+    fn map<V: Clone>(entries: &[(&str, V)]) -> HashMap<String, V> {
+        entries
+            .iter()
+            .map(|(k, v)| ((*k).to_string(), v.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn finds_refs_and_ignores_other_braces() {
+        let prompt = "Use {{steps.analyze.result}} and {{ steps.plan.result }}, \
+                      not {{name}}; again {{steps.analyze.result}}";
+        assert_eq!(step_refs("x", prompt).unwrap(), vec!["analyze", "plan"]);
+        assert!(step_refs("x", "no refs {{ here }}").unwrap().is_empty());
+    }
+
+    #[test]
+    fn rejects_bad_references() {
+        assert!(matches!(
+            step_refs("x", "{{steps.a}}"),
+            Err(TemplateError::Malformed { .. })
+        ));
+        assert!(matches!(
+            step_refs("x", "{{steps.a.output}}"),
+            Err(TemplateError::UnknownField { .. })
+        ));
+    }
+
+    #[test]
+    fn validates_refs_against_upstream_steps() {
+        let deps = map(&[
+            ("a", vec![]),
+            ("b", vec!["a".to_string()]),
+            ("c", vec!["b".to_string()]),
+            ("d", vec![]),
+        ]);
+        let ok = map(&[
+            ("a", "start".to_string()),
+            ("b", "x".to_string()),
+            ("c", "{{steps.a.result}} {{steps.b.result}}".to_string()),
+            ("d", "y".to_string()),
+        ]);
+        assert!(validate_refs(&ok, &deps).is_ok());
+
+        let mut sibling = ok.clone();
+        sibling.insert("d".into(), "{{steps.a.result}}".into());
+        assert_eq!(
+            validate_refs(&sibling, &deps),
+            Err(TemplateError::NotUpstream {
+                step: "d".into(),
+                target: "a".into()
+            })
+        );
+
+        let mut unknown = ok;
+        unknown.insert("d".into(), "{{steps.zz.result}}".into());
+        assert!(matches!(
+            validate_refs(&unknown, &deps),
+            Err(TemplateError::UnknownStep { .. })
+        ));
+    }
+
+    #[test]
+    fn renders_with_caps() {
+        let outputs = map(&[
+            ("a", "short"),
+            ("b", "0123456789abcdefghijklmnopqrstuvwxyz"),
+        ]);
+        let get = |id: &str| outputs.get(id).copied();
+
+        let out = render("A={{steps.a.result}} M={{steps.m.result}}.", get, 100, 100);
+        assert_eq!(out, "A=short M=.");
+
+        let cap = TRUNCATION_MARKER.chars().count() + 4;
+        let out = render("{{steps.b.result}}", get, cap, 100);
+        assert_eq!(out, format!("0123{TRUNCATION_MARKER}"));
+
+        // The shared budget is exhausted by the first reference.
+        let out = render("{{steps.a.result}}|{{steps.a.result}}", get, 100, 5);
+        assert_eq!(out, "short|");
+    }
+
+    #[test]
+    fn renders_fan_out_item() {
+        assert_eq!(
+            render_item("Review {{item}} ({{ item }}), keep {{other}}", "src/a.rs"),
+            "Review src/a.rs (src/a.rs), keep {{other}}"
+        );
+    }
+
+    #[test]
+    fn splits_fan_out_items() {
+        assert_eq!(
+            fan_out_items(r#" ["a.rs", "b.rs", 3, ""] "#),
+            vec!["a.rs", "b.rs", "3"]
+        );
+        assert_eq!(
+            fan_out_items("- src/a.rs\n\n* src/b.rs\n  src/c.rs  \n"),
+            vec!["src/a.rs", "src/b.rs", "src/c.rs"]
+        );
+        // Not valid JSON: treated as lines.
+        assert_eq!(fan_out_items("[draft]\nnext"), vec!["[draft]", "next"]);
+        assert!(fan_out_items("  \n").is_empty());
+    }
+
+    #[test]
+    fn truncate_respects_char_boundaries() {
+        let text = "ééééééééééééééééééééééééééééééé";
+        let cap = TRUNCATION_MARKER.chars().count() + 2;
+        assert_eq!(truncate_output(text, cap), format!("éé{TRUNCATION_MARKER}"));
+        assert_eq!(truncate_output(text, 3), "");
+        assert_eq!(truncate_output("abc", 3), "abc");
+    }
+}
@@ -0,0 +1,206 @@
+//! Wake-on-LAN targets and magic packets.
+//!
+//! Shared by the relay, which asks an always-on "waker" daemon to wake an
+//! offline machine, and the waker daemon, which broadcasts the magic packet
+//! on its LAN. The request travels as the params of a `WAKE` tunnel control
+//! frame (see [`WakeTarget::to_params`]).
+
+use std::collections::HashMap;
+use std::fmt;
+use std::net::Ipv4Addr;
+use std::str::FromStr;
+
+/// UDP port magic packets are sent to by default (discard).
+pub const DEFAULT_WAKE_PORT: u16 = 9;
+
+/// Length of a magic packet: 6 bytes of `0xFF`, then the MAC 16 times.
+pub const MAGIC_PACKET_LEN: usize = 6 + 16 * 6;
+
+/// Why a wake target could not be parsed.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum WakeError {
+    #[error("Invalid MAC address '{0}': expected six hex bytes like aa:bb:cc:dd:ee:ff")]
+    InvalidMac(String),
+
+    #[error("Invalid broadcast address '{0}': expected an IPv4 address")]
+    InvalidBroadcast(String),
+
+    #[error("Invalid wake port '{0}'")]
+    InvalidPort(String),
+
+    #[error("Missing wake parameter '{0}'")]
+    Missing(&'static str),
+}
+
+/// A 48-bit Ethernet MAC address.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct MacAddress(pub [u8; 6]);
+
+impl MacAddress {
+    /// The Wake-on-LAN magic packet for this address.
+    pub fn magic_packet(&self) -> [u8; MAGIC_PACKET_LEN] {
+        let mut packet = [0xFF; MAGIC_PACKET_LEN];
+        for chunk in packet[6..].chunks_exact_mut(6) {
+            chunk.copy_from_slice(&self.0);
+        }
+        packet
+    }
+}
+
+impl FromStr for MacAddress {
+    type Err = WakeError;
+
+    /// Accepts `aa:bb:cc:dd:ee:ff`, `aa-bb-cc-dd-ee-ff` or `aabbccddeeff`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || WakeError::InvalidMac(s.to_string());
+        let hex: String = s.chars().filter(|c| !matches!(c, ':' | '-')).collect();
+        let separators = s.len() - hex.len();
+        if hex.len() != 12 || !(separators == 0 || separators == 5) {
+            return Err(invalid());
+        }
+        let mut bytes = [0u8; 6];
+        for (i, byte) in bytes.iter_mut().enumerate() {
+            *byte = hex
+                .get(i * 2..i * 2 + 2)
+                .and_then(|pair| u8::from_str_radix(pair, 16).ok())
+                .ok_or_else(invalid)?;
+        }
+        Ok(Self(bytes))
+    }
+}
+
+impl fmt::Display for MacAddress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, byte) in self.0.iter().enumerate() {
+            if i > 0 {
+                f.write_str(":")?;
+            }
+            write!(f, "{byte:02x}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Where to send a magic packet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WakeTarget {
+    pub mac: MacAddress,
+    /// Broadcast address of the target's LAN segment.
+    pub broadcast: Ipv4Addr,
+    pub port: u16,
+}
+
+impl WakeTarget {
+    /// A target reached through the limited broadcast address on the
+    /// default port.
+    pub const fn new(mac: MacAddress) -> Self {
+        Self {
+            mac,
+            broadcast: Ipv4Addr::BROADCAST,
+            port: DEFAULT_WAKE_PORT,
+        }
+    }
+
+    /// Parse a target from its parts; empty optional parts take defaults.
+    pub fn parse(mac: &str, broadcast: &str, port: &str) -> Result<Self, WakeError> {
+        let mut target = Self::new(mac.parse()?);
+        if !broadcast.is_empty() {
+            target.broadcast = broadcast
+                .parse()
+                .map_err(|_| WakeError::InvalidBroadcast(broadcast.to_string()))?;
+        }
+        if !port.is_empty() {
+            target.port = port
+                .parse()
+                .ok()
+                .filter(|p| *p != 0)
+                .ok_or_else(|| WakeError::InvalidPort(port.to_string()))?;
+        }
+        Ok(target)
+    }
+
+    /// Control frame params carrying this target.
+    pub fn to_params(&self) -> HashMap<String, String> {
+        HashMap::from([
+            ("mac".to_string(), self.mac.to_string()),
+            ("broadcast".to_string(), self.broadcast.to_string()),
+            ("port".to_string(), self.port.to_string()),
+        ])
+    }
+
+    /// Parse a target from control frame params.
+    pub fn from_params<S: std::hash::BuildHasher>(
+        params: &HashMap<String, String, S>,
+    ) -> Result<Self, WakeError> {
+        let get = |key: &str| params.get(key).map_or("", String::as_str);
+        let mac = params.get("mac").ok_or(WakeError::Missing("mac"))?;
+        Self::parse(mac, get("broadcast"), get("port"))
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::panic, clippy::expect_used, clippy::unwrap_used)]
+mod tests {
+    use super::*;
+
+    const MAC: MacAddress = MacAddress([0xAA, 0xBB, 0xCC, 0x01, 0x02, 0x03]);
+
+    #[test]
+    fn parses_common_mac_formats() {
+        for s in ["aa:bb:cc:01:02:03", "AA-BB-CC-01-02-03", "aabbcc010203"] {
+            assert_eq!(s.parse::<MacAddress>().unwrap(), MAC, "{s}");
+        }
+        assert_eq!(MAC.to_string(), "aa:bb:cc:01:02:03");
+    }
+
+    #[test]
+    fn rejects_malformed_macs() {
+        for s in [
+            "",
+            "aa:bb:cc:01:02",
+            "aa:bb:cc:01:02:zz",
+            "a:ab:bc:c0:10:20:3",
+            "aab:bcc:010:203",
+        ] {
+            assert!(s.parse::<MacAddress>().is_err(), "{s}");
+        }
+    }
+
+    #[test]
+    fn magic_packet_repeats_mac() {
+        let packet = MAC.magic_packet();
+        assert_eq!(packet[..6], [0xFF; 6]);
+        assert!(packet[6..].chunks(6).all(|c| c == MAC.0));
+        assert_eq!(packet[6..].chunks(6).count(), 16);
+    }
+
+    #[test]
+    fn target_defaults_and_round_trip() {
+        let target = WakeTarget::parse("aa:bb:cc:01:02:03", "", "").unwrap();
+        assert_eq!(target, WakeTarget::new(MAC));
+        assert_eq!(target.broadcast, Ipv4Addr::BROADCAST);
+        assert_eq!(target.port, DEFAULT_WAKE_PORT);
+
+        let target = WakeTarget::parse("aa:bb:cc:01:02:03", "192.168.1.255", "7").unwrap();
+        assert_eq!(
+            WakeTarget::from_params(&target.to_params()).unwrap(),
+            target
+        );
+    }
+
+    #[test]
+    fn target_rejects_bad_parts() {
+        assert_eq!(
+            WakeTarget::parse("aa:bb:cc:01:02:03", "not-an-ip", ""),
+            Err(WakeError::InvalidBroadcast("not-an-ip".into()))
+        );
+        assert_eq!(
+            WakeTarget::parse("aa:bb:cc:01:02:03", "", "0"),
+            Err(WakeError::InvalidPort("0".into()))
+        );
+        assert_eq!(
+            WakeTarget::from_params(&HashMap::new()),
+            Err(WakeError::Missing("mac"))
+        );
+    }
+}
@@ -15,6 +15,7 @@ pub mod exchange;
 pub mod fingerprint_store;
 pub mod fingerprint_visual;
 pub mod identity;
+pub mod pkce;
 pub mod session;
 
 #[cfg(feature = "certs")]
@@ -29,6 +30,7 @@ pub use fingerprint_visual::{
     compare_fingerprints, fingerprint_randomart, format_fingerprint_display,
 };
 pub use identity::{IdentityKeyPair, fingerprint_of};
+pub use pkce::PkcePair;
 #[cfg(any(test, feature = "test-utils"))]
 pub use session::test_session_pair;
 pub use session::{CryptoSession, EncryptedData, NONCE_SIZE};
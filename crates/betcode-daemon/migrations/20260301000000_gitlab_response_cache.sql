@@ -0,0 +1,11 @@
+-- ETag cache for GitLab API responses, used for conditional (If-None-Match) requests.
+CREATE TABLE IF NOT EXISTS gitlab_response_cache (
+    url TEXT PRIMARY KEY,
+    etag TEXT NOT NULL,
+    body TEXT NOT NULL,
+    next_url TEXT,
+    updated_at INTEGER NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_gitlab_response_cache_updated
+    ON gitlab_response_cache(updated_at);
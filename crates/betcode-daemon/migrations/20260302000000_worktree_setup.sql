@@ -0,0 +1,5 @@
+-- Setup pipeline state for worktrees.
+-- setup_status: none | pending | running | succeeded | failed
+-- setup_steps: JSON array of per-step results, in pipeline order.
+ALTER TABLE worktrees ADD COLUMN setup_status TEXT NOT NULL DEFAULT 'none';
+ALTER TABLE worktrees ADD COLUMN setup_steps TEXT;
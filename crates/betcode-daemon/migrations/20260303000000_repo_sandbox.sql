@@ -0,0 +1,3 @@
+-- Per-repository sandbox policy for Claude subprocesses.
+-- sandbox: JSON-encoded SandboxPolicy; NULL uses the daemon default.
+ALTER TABLE git_repos ADD COLUMN sandbox TEXT;
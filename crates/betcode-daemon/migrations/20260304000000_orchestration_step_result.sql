@@ -0,0 +1,3 @@
+-- Output of a completed orchestration step, for `{{steps.<id>.result}}`
+-- references in downstream prompts. Truncated to the daemon's size cap.
+ALTER TABLE orchestration_steps ADD COLUMN result TEXT;
@@ -0,0 +1,35 @@
+-- Retries, conditional steps and fan-out for orchestration steps.
+--
+-- SQLite cannot alter a CHECK constraint, so the table is rebuilt. The rebuild
+-- also scopes step IDs to their orchestration: the primary key becomes
+-- (orchestration_id, id), so two orchestrations may reuse the same step IDs.
+--
+-- attempts: attempts started so far (retries included).
+-- error: last failure message, or why the step was skipped.
+-- fan_out_items: JSON array of items a fan-out step ran over.
+CREATE TABLE orchestration_steps_new (
+    id TEXT NOT NULL,
+    orchestration_id TEXT NOT NULL REFERENCES orchestrations(id) ON DELETE CASCADE,
+    subagent_id TEXT REFERENCES subagents(id) ON DELETE SET NULL,
+    step_index INTEGER NOT NULL,
+    prompt TEXT NOT NULL,
+    depends_on TEXT NOT NULL DEFAULT '[]',
+    status TEXT NOT NULL DEFAULT 'pending'
+        CHECK (status IN ('pending', 'running', 'retrying', 'completed', 'failed', 'blocked', 'skipped')),
+    created_at INTEGER NOT NULL,
+    result TEXT,
+    attempts INTEGER NOT NULL DEFAULT 0,
+    error TEXT,
+    fan_out_items TEXT,
+    PRIMARY KEY (orchestration_id, id)
+);
+
+INSERT INTO orchestration_steps_new
+    (id, orchestration_id, subagent_id, step_index, prompt, depends_on, status, created_at, result)
+SELECT id, orchestration_id, subagent_id, step_index, prompt, depends_on, status, created_at, result
+FROM orchestration_steps;
+
+DROP TABLE orchestration_steps;
+ALTER TABLE orchestration_steps_new RENAME TO orchestration_steps;
+
+CREATE INDEX IF NOT EXISTS idx_orch_steps ON orchestration_steps(orchestration_id, step_index);
@@ -0,0 +1,7 @@
+-- State needed to pick up subagents and orchestrations after a daemon restart.
+--
+-- claude_session_id is Claude's own session ID, so an interrupted subagent can
+-- continue with `claude --resume`. spec is the full step definition (a
+-- base64-encoded OrchestrationStep) from which the scheduler is rebuilt.
+ALTER TABLE subagents ADD COLUMN claude_session_id TEXT;
+ALTER TABLE orchestration_steps ADD COLUMN spec TEXT;
@@ -0,0 +1,11 @@
+-- Idempotency keys of client requests already applied.
+--
+-- Clients replaying requests they queued while offline tag them with a key,
+-- so a request that did reach the daemon before the connection dropped is
+-- not applied twice. Keys are kept for a week.
+CREATE TABLE request_keys (
+    key TEXT PRIMARY KEY,
+    created_at INTEGER NOT NULL
+);
+
+CREATE INDEX idx_request_keys_created_at ON request_keys(created_at);
@@ -0,0 +1,3 @@
+-- Timeout a subagent was spawned with, in seconds (0 = the daemon default),
+-- so a subagent resumed after a restart keeps it.
+ALTER TABLE subagents ADD COLUMN timeout_secs INTEGER NOT NULL DEFAULT 0;
@@ -0,0 +1,24 @@
+-- Counter bumped whenever a repo is registered, renamed, moved or removed,
+-- so lookups by repo ID or name can be cached until it changes.
+CREATE TABLE IF NOT EXISTS git_repos_version (
+    id      INTEGER PRIMARY KEY CHECK (id = 1),
+    version INTEGER NOT NULL
+);
+
+INSERT INTO git_repos_version (id, version) VALUES (1, 0);
+
+CREATE TRIGGER IF NOT EXISTS git_repos_version_insert AFTER INSERT ON git_repos
+BEGIN
+    UPDATE git_repos_version SET version = version + 1;
+END;
+
+CREATE TRIGGER IF NOT EXISTS git_repos_version_update
+AFTER UPDATE OF name, repo_path ON git_repos
+BEGIN
+    UPDATE git_repos_version SET version = version + 1;
+END;
+
+CREATE TRIGGER IF NOT EXISTS git_repos_version_delete AFTER DELETE ON git_repos
+BEGIN
+    UPDATE git_repos_version SET version = version + 1;
+END;
@@ -0,0 +1,17 @@
+-- Scope idempotency keys to the session they were sent in, and tell keys
+-- still being applied from keys already applied.
+--
+-- A key is claimed with applied = 0 before its request runs, set to 1 once
+-- it succeeded, and deleted if it failed so a later replay can retry it.
+-- Keys recorded before this migration are dropped: they have no session.
+DROP TABLE request_keys;
+
+CREATE TABLE request_keys (
+    session_id TEXT NOT NULL,
+    key TEXT NOT NULL,
+    applied INTEGER NOT NULL DEFAULT 0,
+    created_at INTEGER NOT NULL,
+    PRIMARY KEY (session_id, key)
+);
+
+CREATE INDEX idx_request_keys_created_at ON request_keys(created_at);
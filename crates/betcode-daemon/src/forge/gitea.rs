@@ -0,0 +1,174 @@
+//! Gitea REST API v1 client.
+//!
+//! Gitea's API mirrors GitHub's closely, so responses share the structs in
+//! the `rest` module. Workflow runs require Gitea Actions (Gitea 1.24+); older
+//! instances answer 404 for the pipeline calls.
+
+use reqwest::header::{ACCEPT, AUTHORIZATION, HeaderMap, HeaderValue};
+
+use super::rest::{self, PullFilter, PullRequest, RestIssue, WorkflowRun, WorkflowRuns};
+use super::types::{Issue, MergeRequest, Pipeline};
+use super::{Forge, ForgeError, ForgeKind, remote};
+
+/// Configuration for connecting to a Gitea (or Forgejo) instance.
+#[derive(Debug, Clone)]
+pub struct GiteaConfig {
+    /// Gitea instance URL (e.g., "<https://gitea.example.com>").
+    pub base_url: String,
+    /// Access token.
+    pub token: String,
+}
+
+/// Gitea REST API v1 client.
+#[derive(Debug)]
+pub struct GiteaClient {
+    http: reqwest::Client,
+    base_url: String,
+    host: String,
+}
+
+impl GiteaClient {
+    /// Create a new Gitea API client.
+    pub fn new(config: &GiteaConfig) -> Result<Self, ForgeError> {
+        if config.base_url.is_empty() {
+            return Err(ForgeError::Config("base_url is empty".into()));
+        }
+        if config.token.is_empty() {
+            return Err(ForgeError::Config("token is empty".into()));
+        }
+
+        let mut headers = HeaderMap::new();
+        let token_val = HeaderValue::from_str(&format!("token {}", config.token))
+            .map_err(|_| ForgeError::Config("Invalid token format".into()))?;
+        headers.insert(AUTHORIZATION, token_val);
+        headers.insert(ACCEPT, HeaderValue::from_static("application/json"));
+
+        // Ensure a TLS crypto provider is installed (reqwest uses rustls-no-provider).
+        // The `Err` case just means it was already installed — safe to ignore.
+        let _ = rustls::crypto::ring::default_provider().install_default();
+
+        let http = reqwest::Client::builder()
+            .default_headers(headers)
+            .build()?;
+
+        let base_url = config.base_url.trim_end_matches('/').to_string();
+        let host = remote::host_of(&base_url);
+        Ok(Self {
+            http,
+            base_url,
+            host,
+        })
+    }
+
+    /// Build the repository API URL for a given path.
+    pub(crate) fn repo_url(&self, project: &str, path: &str) -> Result<String, ForgeError> {
+        let repo = rest::owner_repo(ForgeKind::Gitea, project)?;
+        Ok(format!("{}/api/v1/repos/{repo}{path}", self.base_url))
+    }
+}
+
+#[tonic::async_trait]
+impl Forge for GiteaClient {
+    fn kind(&self) -> ForgeKind {
+        ForgeKind::Gitea
+    }
+
+    fn host(&self) -> &str {
+        &self.host
+    }
+
+    async fn list_merge_requests(
+        &self,
+        project: &str,
+        state: Option<&str>,
+        per_page: u32,
+        page: u32,
+    ) -> Result<Vec<MergeRequest>, ForgeError> {
+        let filter = PullFilter::from_state(ForgeKind::Gitea, state)?;
+        let url = format!(
+            "{}?state={}",
+            self.repo_url(project, "/pulls")?,
+            filter.query_state()
+        );
+        if let PullFilter::All(_) = filter {
+            let url = format!("{url}&limit={per_page}&page={page}");
+            let pulls: Vec<PullRequest> =
+                rest::get_json(&self.http, ForgeKind::Gitea, &url).await?;
+            return Ok(pulls.into_iter().map(MergeRequest::from).collect());
+        }
+        rest::list_filtered(
+            &self.http,
+            ForgeKind::Gitea,
+            &url,
+            "limit",
+            per_page,
+            page,
+            |pull: PullRequest| Some(MergeRequest::from(pull)).filter(|mr| filter.keep(mr)),
+        )
+        .await
+    }
+
+    async fn get_merge_request(&self, project: &str, iid: u64) -> Result<MergeRequest, ForgeError> {
+        let url = self.repo_url(project, &format!("/pulls/{iid}"))?;
+        let pull: PullRequest = rest::get_json(&self.http, ForgeKind::Gitea, &url).await?;
+        Ok(pull.into())
+    }
+
+    async fn list_pipelines(
+        &self,
+        project: &str,
+        status: Option<&str>,
+        per_page: u32,
+        page: u32,
+    ) -> Result<Vec<Pipeline>, ForgeError> {
+        let mut url = format!(
+            "{}?limit={per_page}&page={page}",
+            self.repo_url(project, "/actions/runs")?
+        );
+        if let Some(s) = status {
+            url.push_str("&status=");
+            url.push_str(rest::run_status_query(ForgeKind::Gitea, s)?);
+        }
+        let runs: WorkflowRuns = rest::get_json(&self.http, ForgeKind::Gitea, &url).await?;
+        Ok(runs.workflow_runs.into_iter().map(Pipeline::from).collect())
+    }
+
+    async fn get_pipeline(&self, project: &str, pipeline_id: u64) -> Result<Pipeline, ForgeError> {
+        let url = self.repo_url(project, &format!("/actions/runs/{pipeline_id}"))?;
+        let run: WorkflowRun = rest::get_json(&self.http, ForgeKind::Gitea, &url).await?;
+        Ok(run.into())
+    }
+
+    async fn list_issues(
+        &self,
+        project: &str,
+        state: Option<&str>,
+        per_page: u32,
+        page: u32,
+    ) -> Result<Vec<Issue>, ForgeError> {
+        let state = rest::issue_state_query(ForgeKind::Gitea, state)?;
+        let url = format!(
+            "{}?state={state}&type=issues&limit={per_page}&page={page}",
+            self.repo_url(project, "/issues")?
+        );
+        let issues: Vec<RestIssue> = rest::get_json(&self.http, ForgeKind::Gitea, &url).await?;
+        Ok(issues
+            .into_iter()
+            .filter(|i| !i.is_pull_request())
+            .map(Issue::from)
+            .collect())
+    }
+
+    async fn get_issue(&self, project: &str, iid: u64) -> Result<Issue, ForgeError> {
+        let url = self.repo_url(project, &format!("/issues/{iid}"))?;
+        let issue: RestIssue = rest::get_json(&self.http, ForgeKind::Gitea, &url).await?;
+        if issue.is_pull_request() {
+            return Err(ForgeError::Api {
+                kind: ForgeKind::Gitea,
+                status: 404,
+                message: format!("#{iid} is a pull request, not an issue"),
+            });
+        }
+        Ok(issue.into())
+    }
+}
@@ -0,0 +1,188 @@
+//! GitHub REST API client.
+//!
+//! Maps pull requests, GitHub Actions workflow runs and issues onto the
+//! [`Forge`] operations. Works against github.com and GitHub Enterprise Server.
+
+use reqwest::header::{ACCEPT, AUTHORIZATION, HeaderMap, HeaderName, HeaderValue, USER_AGENT};
+
+use super::rest::{self, PullFilter, PullRequest, RestIssue, WorkflowRun, WorkflowRuns};
+use super::types::{Issue, MergeRequest, Pipeline};
+use super::{Forge, ForgeError, ForgeKind, remote};
+
+/// Configuration for connecting to GitHub.
+#[derive(Debug, Clone)]
+pub struct GitHubConfig {
+    /// Web URL of the instance (e.g., "<https://github.com>" or a GHES host).
+    pub base_url: String,
+    /// Personal access token or fine-grained token.
+    pub token: String,
+}
+
+/// GitHub REST API client.
+#[derive(Debug)]
+pub struct GitHubClient {
+    http: reqwest::Client,
+    api_base: String,
+    host: String,
+}
+
+impl GitHubClient {
+    /// Create a new GitHub API client.
+    pub fn new(config: &GitHubConfig) -> Result<Self, ForgeError> {
+        if config.base_url.is_empty() {
+            return Err(ForgeError::Config("base_url is empty".into()));
+        }
+        if config.token.is_empty() {
+            return Err(ForgeError::Config("token is empty".into()));
+        }
+
+        let mut headers = HeaderMap::new();
+        let token_val = HeaderValue::from_str(&format!("Bearer {}", config.token))
+            .map_err(|_| ForgeError::Config("Invalid token format".into()))?;
+        headers.insert(AUTHORIZATION, token_val);
+        headers.insert(
+            ACCEPT,
+            HeaderValue::from_static("application/vnd.github+json"),
+        );
+        headers.insert(USER_AGENT, HeaderValue::from_static("betcode-daemon"));
+        headers.insert(
+            HeaderName::from_static("x-github-api-version"),
+            HeaderValue::from_static("2022-11-28"),
+        );
+
+        // Ensure a TLS crypto provider is installed (reqwest uses rustls-no-provider).
+        // The `Err` case just means it was already installed — safe to ignore.
+        let _ = rustls::crypto::ring::default_provider().install_default();
+
+        let http = reqwest::Client::builder()
+            .default_headers(headers)
+            .build()?;
+
+        let base_url = config.base_url.trim_end_matches('/');
+        let host = remote::host_of(base_url);
+        let api_base = if host == "github.com" {
+            "https://api.github.com".to_string()
+        } else {
+            format!("{base_url}/api/v3")
+        };
+        Ok(Self {
+            http,
+            api_base,
+            host,
+        })
+    }
+
+    /// Build the repository API URL for a given path.
+    pub(crate) fn repo_url(&self, project: &str, path: &str) -> Result<String, ForgeError> {
+        let repo = rest::owner_repo(ForgeKind::GitHub, project)?;
+        Ok(format!("{}/repos/{repo}{path}", self.api_base))
+    }
+}
+
+#[tonic::async_trait]
+impl Forge for GitHubClient {
+    fn kind(&self) -> ForgeKind {
+        ForgeKind::GitHub
+    }
+
+    fn host(&self) -> &str {
+        &self.host
+    }
+
+    async fn list_merge_requests(
+        &self,
+        project: &str,
+        state: Option<&str>,
+        per_page: u32,
+        page: u32,
+    ) -> Result<Vec<MergeRequest>, ForgeError> {
+        let filter = PullFilter::from_state(ForgeKind::GitHub, state)?;
+        let url = format!(
+            "{}?state={}",
+            self.repo_url(project, "/pulls")?,
+            filter.query_state()
+        );
+        if let PullFilter::All(_) = filter {
+            let url = format!("{url}&per_page={per_page}&page={page}");
+            let pulls: Vec<PullRequest> =
+                rest::get_json(&self.http, ForgeKind::GitHub, &url).await?;
+            return Ok(pulls.into_iter().map(MergeRequest::from).collect());
+        }
+        rest::list_filtered(
+            &self.http,
+            ForgeKind::GitHub,
+            &url,
+            "per_page",
+            per_page,
+            page,
+            |pull: PullRequest| Some(MergeRequest::from(pull)).filter(|mr| filter.keep(mr)),
+        )
+        .await
+    }
+
+    async fn get_merge_request(&self, project: &str, iid: u64) -> Result<MergeRequest, ForgeError> {
+        let url = self.repo_url(project, &format!("/pulls/{iid}"))?;
+        let pull: PullRequest = rest::get_json(&self.http, ForgeKind::GitHub, &url).await?;
+        Ok(pull.into())
+    }
+
+    async fn list_pipelines(
+        &self,
+        project: &str,
+        status: Option<&str>,
+        per_page: u32,
+        page: u32,
+    ) -> Result<Vec<Pipeline>, ForgeError> {
+        let mut url = format!(
+            "{}?per_page={per_page}&page={page}",
+            self.repo_url(project, "/actions/runs")?
+        );
+        if let Some(s) = status {
+            url.push_str("&status=");
+            url.push_str(rest::run_status_query(ForgeKind::GitHub, s)?);
+        }
+        let runs: WorkflowRuns = rest::get_json(&self.http, ForgeKind::GitHub, &url).await?;
+        Ok(runs.workflow_runs.into_iter().map(Pipeline::from).collect())
+    }
+
+    async fn get_pipeline(&self, project: &str, pipeline_id: u64) -> Result<Pipeline, ForgeError> {
+        let url = self.repo_url(project, &format!("/actions/runs/{pipeline_id}"))?;
+        let run: WorkflowRun = rest::get_json(&self.http, ForgeKind::GitHub, &url).await?;
+        Ok(run.into())
+    }
+
+    async fn list_issues(
+        &self,
+        project: &str,
+        state: Option<&str>,
+        per_page: u32,
+        page: u32,
+    ) -> Result<Vec<Issue>, ForgeError> {
+        let state = rest::issue_state_query(ForgeKind::GitHub, state)?;
+        let url = format!("{}?state={state}", self.repo_url(project, "/issues")?);
+        // The issues endpoint also returns pull requests.
+        rest::list_filtered(
+            &self.http,
+            ForgeKind::GitHub,
+            &url,
+            "per_page",
+            per_page,
+            page,
+            |issue: RestIssue| (!issue.is_pull_request()).then(|| Issue::from(issue)),
+        )
+        .await
+    }
+
+    async fn get_issue(&self, project: &str, iid: u64) -> Result<Issue, ForgeError> {
+        let url = self.repo_url(project, &format!("/issues/{iid}"))?;
+        let issue: RestIssue = rest::get_json(&self.http, ForgeKind::GitHub, &url).await?;
+        if issue.is_pull_request() {
+            return Err(ForgeError::Api {
+                kind: ForgeKind::GitHub,
+                status: 404,
+                message: format!("#{iid} is a pull request, not an issue"),
+            });
+        }
+        Ok(issue.into())
+    }
+}
@@ -0,0 +1,124 @@
+//! Code-forge integration shared by GitLab, GitHub and Gitea.
+//!
+//! The [`Forge`] trait covers the operations behind `GitLabService`: merge
+//! requests (pull requests), pipelines (workflow runs) and issues. Every
+//! implementation speaks GitLab's vocabulary for states and statuses so the
+//! RPC layer stays forge-agnostic. [`ForgeRegistry`] picks the forge for a
+//! request from the registered repo's remote URL.
+
+pub mod gitea;
+pub mod github;
+mod registry;
+pub mod remote;
+mod rest;
+pub mod types;
+
+#[cfg(test)]
+mod tests;
+
+use std::fmt;
+
+use thiserror::Error;
+
+pub use gitea::{GiteaClient, GiteaConfig};
+pub use github::{GitHubClient, GitHubConfig};
+pub use registry::{ForgeRegistry, ResolvedProject};
+pub use types::{Issue, MergeRequest, Pipeline};
+
+/// Forge client errors.
+#[derive(Debug, Error)]
+pub enum ForgeError {
+    #[error("HTTP request failed: {0}")]
+    Http(#[from] reqwest::Error),
+
+    #[error("{kind} API error ({status}): {message}")]
+    Api {
+        kind: ForgeKind,
+        status: u16,
+        message: String,
+    },
+
+    #[error("Configuration error: {0}")]
+    Config(String),
+
+    #[error("Invalid response: {0}")]
+    Decode(String),
+
+    #[error("Unsupported by {kind}: {message}")]
+    Unsupported { kind: ForgeKind, message: String },
+}
+
+/// Supported forge flavours.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ForgeKind {
+    GitLab,
+    GitHub,
+    Gitea,
+}
+
+impl ForgeKind {
+    /// Human-readable name.
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::GitLab => "GitLab",
+            Self::GitHub => "GitHub",
+            Self::Gitea => "Gitea",
+        }
+    }
+}
+
+impl fmt::Display for ForgeKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Operations exposed through `GitLabService`, implemented per forge.
+///
+/// States and statuses use GitLab's strings (`opened`, `merged`, `running`,
+/// `success`, ...). Implementations translate filters and results to and from
+/// their own API's vocabulary.
+#[tonic::async_trait]
+pub trait Forge: Send + Sync + fmt::Debug {
+    /// Which forge this client talks to.
+    fn kind(&self) -> ForgeKind;
+
+    /// Web host of the instance (e.g. `github.com`), used to match remotes.
+    fn host(&self) -> &str;
+
+    /// List merge (pull) requests for a project.
+    async fn list_merge_requests(
+        &self,
+        project: &str,
+        state: Option<&str>,
+        per_page: u32,
+        page: u32,
+    ) -> Result<Vec<MergeRequest>, ForgeError>;
+
+    /// Get a single merge (pull) request by its project-scoped number.
+    async fn get_merge_request(&self, project: &str, iid: u64) -> Result<MergeRequest, ForgeError>;
+
+    /// List pipelines (workflow runs) for a project.
+    async fn list_pipelines(
+        &self,
+        project: &str,
+        status: Option<&str>,
+        per_page: u32,
+        page: u32,
+    ) -> Result<Vec<Pipeline>, ForgeError>;
+
+    /// Get a single pipeline (workflow run) by ID.
+    async fn get_pipeline(&self, project: &str, pipeline_id: u64) -> Result<Pipeline, ForgeError>;
+
+    /// List issues for a project.
+    async fn list_issues(
+        &self,
+        project: &str,
+        state: Option<&str>,
+        per_page: u32,
+        page: u32,
+    ) -> Result<Vec<Issue>, ForgeError>;
+
+    /// Get a single issue by its project-scoped number.
+    async fn get_issue(&self, project: &str, iid: u64) -> Result<Issue, ForgeError>;
+}
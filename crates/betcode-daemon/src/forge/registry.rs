@@ -0,0 +1,281 @@
+//! Forge selection for incoming requests.
+//!
+//! A request's `project` may name a registered repo (by ID or name), in which
+//! case the forge and project path come from the repo's `origin` remote.
+//! Otherwise it is taken as a project path on the default (first configured)
+//! forge, optionally prefixed with a host (`github.com/owner/repo`) or given as
+//! a full remote URL.
+//!
+//! Resolutions are cached until a repo is registered, renamed, moved or
+//! removed (see [`Database::git_repos_version`]), or [`RESOLVE_TTL`] passes so
+//! an edited `origin` remote is picked up.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::path::Path;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, Instant};
+
+use tracing::warn;
+
+use super::remote::RemoteUrl;
+use super::{Forge, ForgeError, GitHubClient, GitHubConfig, GiteaClient, GiteaConfig};
+use crate::gitlab::{GitLabClient, GitLabConfig, ResponseCache};
+use crate::storage::Database;
+
+/// Timeout for the `git remote get-url` lookup.
+const GIT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How long a cached resolution is used while the repos are unchanged.
+pub const RESOLVE_TTL: Duration = Duration::from_mins(5);
+
+/// Cached resolutions beyond which the cache is cleared.
+const MAX_CACHED_PROJECTS: usize = 1024;
+
+/// A forge together with the project path to pass to it.
+#[derive(Debug, Clone)]
+pub struct ResolvedProject {
+    pub forge: Arc<dyn Forge>,
+    pub project: String,
+}
+
+/// Resolutions made while the repos were at `version`.
+#[derive(Default)]
+struct ResolveCache {
+    version: i64,
+    projects: HashMap<String, (Instant, ResolvedProject)>,
+}
+
+/// Set of configured forges, keyed by host.
+#[derive(Default)]
+pub struct ForgeRegistry {
+    forges: Vec<Arc<dyn Forge>>,
+    db: Option<Database>,
+    cache: Mutex<ResolveCache>,
+}
+
+impl fmt::Debug for ForgeRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ForgeRegistry")
+            .field("forges", &self.forges)
+            .field("db", &self.db.is_some())
+            .finish_non_exhaustive()
+    }
+}
+
+impl ForgeRegistry {
+    /// Create an empty registry. `db` enables lookup of registered repos.
+    pub fn new(db: Option<Database>) -> Self {
+        Self {
+            forges: Vec::new(),
+            db,
+            cache: Mutex::default(),
+        }
+    }
+
+    /// Build a registry from environment variables.
+    ///
+    /// - `BETCODE_GITLAB_URL` + `BETCODE_GITLAB_TOKEN`
+    /// - `BETCODE_GITHUB_TOKEN` (+ optional `BETCODE_GITHUB_URL` for GHES)
+    /// - `BETCODE_GITEA_URL` + `BETCODE_GITEA_TOKEN`
+    ///
+    /// The GitLab client gets an `ETag` response cache persisted in `db`.
+    /// Forges whose client fails to build are skipped with a warning.
+    pub fn from_env(db: Option<Database>) -> Self {
+        let mut registry = Self::new(db);
+
+        if let (Some(base_url), Some(token)) =
+            (env("BETCODE_GITLAB_URL"), env("BETCODE_GITLAB_TOKEN"))
+        {
+            match GitLabClient::new(&GitLabConfig { base_url, token }) {
+                Ok(c) => {
+                    let cache = ResponseCache::new(registry.db.clone());
+                    registry.register(Arc::new(c.with_cache(Arc::new(cache))));
+                }
+                Err(e) => warn!("Failed to create GitLab client: {e}"),
+            }
+        }
+        if let Some(token) = env("BETCODE_GITHUB_TOKEN") {
+            let base_url = env("BETCODE_GITHUB_URL").unwrap_or_else(|| "https://github.com".into());
+            match GitHubClient::new(&GitHubConfig { base_url, token }) {
+                Ok(c) => registry.register(Arc::new(c)),
+                Err(e) => warn!("Failed to create GitHub client: {e}"),
+            }
+        }
+        if let (Some(base_url), Some(token)) =
+            (env("BETCODE_GITEA_URL"), env("BETCODE_GITEA_TOKEN"))
+        {
+            match GiteaClient::new(&GiteaConfig { base_url, token }) {
+                Ok(c) => registry.register(Arc::new(c)),
+                Err(e) => warn!("Failed to create Gitea client: {e}"),
+            }
+        }
+        registry
+    }
+
+    /// Add a forge. The first registered forge is the default.
+    pub fn register(&mut self, forge: Arc<dyn Forge>) {
+        self.forges.push(forge);
+    }
+
+    /// Whether no forge is configured.
+    pub fn is_empty(&self) -> bool {
+        self.forges.is_empty()
+    }
+
+    /// Find the forge serving `host`.
+    pub fn forge_for_host(&self, host: &str) -> Option<Arc<dyn Forge>> {
+        self.forges
+            .iter()
+            .find(|f| f.host().eq_ignore_ascii_case(host))
+            .cloned()
+    }
+
+    /// Pick the forge and project path for a request's `project` field.
+    pub async fn resolve(&self, project: &str) -> Result<ResolvedProject, ForgeError> {
+        let project = project.trim();
+        if project.is_empty() {
+            return Err(ForgeError::Config("project is empty".into()));
+        }
+
+        let Some(db) = &self.db else {
+            return self.resolve_uncached(project).await;
+        };
+        let version = db
+            .git_repos_version()
+            .await
+            .map_err(|e| ForgeError::Config(format!("repo lookup failed: {e}")))?;
+        if let Some(resolved) = self.cached(project, version) {
+            return Ok(resolved);
+        }
+        let resolved = self.resolve_uncached(project).await?;
+        self.remember(project, version, &resolved);
+        Ok(resolved)
+    }
+
+    /// The cached resolution of `project`, if still valid at `version`.
+    fn cached(&self, project: &str, version: i64) -> Option<ResolvedProject> {
+        let mut cache = self.cache.lock().unwrap_or_else(PoisonError::into_inner);
+        if cache.version != version {
+            cache.version = version;
+            cache.projects.clear();
+            return None;
+        }
+        let hit = cache
+            .projects
+            .get(project)
+            .filter(|(at, _)| at.elapsed() < RESOLVE_TTL)
+            .map(|(_, resolved)| resolved.clone());
+        drop(cache);
+        hit
+    }
+
+    /// Cache `resolved` as `project`'s resolution at `version`.
+    fn remember(&self, project: &str, version: i64, resolved: &ResolvedProject) {
+        let mut cache = self.cache.lock().unwrap_or_else(PoisonError::into_inner);
+        // The repos changed while resolving; the next lookup starts over
+        if cache.version != version {
+            return;
+        }
+        if cache.projects.len() >= MAX_CACHED_PROJECTS {
+            cache.projects.clear();
+        }
+        cache
+            .projects
+            .insert(project.to_string(), (Instant::now(), resolved.clone()));
+    }
+
+    async fn resolve_uncached(&self, project: &str) -> Result<ResolvedProject, ForgeError> {
+        if let Some(repo_path) = self.registered_repo_path(project).await {
+            let url = origin_url(Path::new(&repo_path)).await.ok_or_else(|| {
+                ForgeError::Config(format!("repo \"{project}\" has no origin remote"))
+            })?;
+            return self.resolve_remote(&url);
+        }
+
+        // Explicit host prefix or full remote URL matching a configured forge.
+        if let Some(resolved) = self.match_host_prefix(project) {
+            return Ok(resolved);
+        }
+
+        let forge = self
+            .forges
+            .first()
+            .cloned()
+            .ok_or_else(|| ForgeError::Config("no forge configured".into()))?;
+        Ok(ResolvedProject {
+            forge,
+            project: project.to_string(),
+        })
+    }
+
+    /// Resolve a remote URL to a configured forge.
+    pub fn resolve_remote(&self, url: &str) -> Result<ResolvedProject, ForgeError> {
+        let remote = RemoteUrl::parse(url)
+            .ok_or_else(|| ForgeError::Config(format!("unrecognised remote URL \"{url}\"")))?;
+        let forge = self.forge_for_host(&remote.host).ok_or_else(|| {
+            ForgeError::Config(format!("no forge configured for host {}", remote.host))
+        })?;
+        Ok(ResolvedProject {
+            forge,
+            project: remote.path,
+        })
+    }
+
+    fn match_host_prefix(&self, project: &str) -> Option<ResolvedProject> {
+        let remote = if project.contains("://") || project.contains('@') {
+            RemoteUrl::parse(project)?
+        } else {
+            let (host, path) = project.split_once('/')?;
+            RemoteUrl {
+                host: host.to_ascii_lowercase(),
+                path: path.trim_end_matches(".git").to_string(),
+            }
+        };
+        let forge = self.forge_for_host(&remote.host)?;
+        Some(ResolvedProject {
+            forge,
+            project: remote.path,
+        })
+    }
+
+    /// Look up a registered repo by ID, then by name.
+    async fn registered_repo_path(&self, project: &str) -> Option<String> {
+        let db = self.db.as_ref()?;
+        if let Ok(repo) = db.get_git_repo(project).await {
+            return Some(repo.repo_path);
+        }
+        let repos = db.list_git_repos().await.ok()?;
+        repos
+            .into_iter()
+            .find(|r| r.name == project)
+            .map(|r| r.repo_path)
+    }
+}
+
+fn env(key: &str) -> Option<String> {
+    std::env::var(key).ok().filter(|s| !s.is_empty())
+}
+
+/// URL of the `origin` remote of the repo at `repo_path`.
+async fn origin_url(repo_path: &Path) -> Option<String> {
+    let output = tokio::time::timeout(
+        GIT_TIMEOUT,
+        tokio::process::Command::new("git")
+            .args(["remote", "get-url", "origin"])
+            .current_dir(repo_path)
+            .env_remove("GIT_DIR")
+            .env_remove("GIT_INDEX_FILE")
+            .env_remove("GIT_WORK_TREE")
+            .output(),
+    )
+    .await
+    .ok()?
+    .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let url = String::from_utf8(output.stdout).ok()?;
+    let url = url.trim();
+    (!url.is_empty()).then(|| url.to_string())
+}
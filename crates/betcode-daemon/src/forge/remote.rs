@@ -0,0 +1,69 @@
+//! Git remote URL parsing.
+//!
+//! Splits a remote such as `git@github.com:owner/repo.git` or
+//! `https://gitea.example.com/org/repo` into the forge host and the project
+//! path the forge APIs expect.
+
+/// Host and project path extracted from a git remote URL.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RemoteUrl {
+    /// Host name without user info or port (e.g. `github.com`).
+    pub host: String,
+    /// Project path without leading slash or `.git` suffix (e.g. `owner/repo`).
+    pub path: String,
+}
+
+impl RemoteUrl {
+    /// Parse a remote URL in URL form (`https://`, `ssh://`, `git://`) or
+    /// scp-like form (`user@host:path`).
+    ///
+    /// Returns `None` for local paths and anything without both a host and a
+    /// project path.
+    pub fn parse(url: &str) -> Option<Self> {
+        let url = url.trim();
+        let (authority, path) = if let Some((_, rest)) = url.split_once("://") {
+            rest.split_once('/')?
+        } else {
+            // scp-like syntax: [user@]host:path (a '/' before ':' means a local path)
+            let (authority, path) = url.split_once(':')?;
+            if authority.contains('/') {
+                return None;
+            }
+            (authority, path)
+        };
+
+        let host = strip_port(strip_userinfo(authority));
+        let path = path
+            .trim_matches('/')
+            .trim_end_matches(".git")
+            .trim_end_matches('/');
+        if host.is_empty() || path.is_empty() {
+            return None;
+        }
+        Some(Self {
+            host: host.to_ascii_lowercase(),
+            path: path.to_string(),
+        })
+    }
+}
+
+/// Extract the host from a base URL such as `https://gitlab.example.com/`.
+///
+/// Lenient: a bare host (`gitlab.example.com`) is accepted as-is.
+pub fn host_of(base_url: &str) -> String {
+    let rest = base_url
+        .split_once("://")
+        .map_or(base_url, |(_, rest)| rest);
+    let authority = rest.split('/').next().unwrap_or_default();
+    strip_port(strip_userinfo(authority)).to_ascii_lowercase()
+}
+
+fn strip_userinfo(authority: &str) -> &str {
+    authority
+        .rsplit_once('@')
+        .map_or(authority, |(_, host)| host)
+}
+
+fn strip_port(host: &str) -> &str {
+    host.split_once(':').map_or(host, |(host, _)| host)
+}
@@ -0,0 +1,440 @@
+//! Shared REST plumbing for GitHub-compatible APIs.
+//!
+//! Gitea models its API on GitHub's, so pull requests, issues and workflow runs
+//! deserialise into the same structs; only URLs, auth and a few query
+//! parameters differ between the two clients.
+
+use reqwest::Url;
+use reqwest::header::LINK;
+use serde::Deserialize;
+use serde::de::DeserializeOwned;
+
+use super::types::{Issue, MergeRequest, Pipeline};
+use super::{ForgeError, ForgeKind};
+use crate::gitlab::http::{MAX_PAGES_PER_CALL, MAX_PER_PAGE, parse_next_link, same_origin};
+
+/// GET a URL and deserialise the JSON body, mapping non-2xx to `ForgeError::Api`.
+pub(super) async fn get_json<T: DeserializeOwned>(
+    http: &reqwest::Client,
+    kind: ForgeKind,
+    url: &str,
+) -> Result<T, ForgeError> {
+    Ok(get_page(http, kind, url).await?.0)
+}
+
+/// GET one page of a listing, with the `Link: rel="next"` URL if any.
+async fn get_page<T: DeserializeOwned>(
+    http: &reqwest::Client,
+    kind: ForgeKind,
+    url: &str,
+) -> Result<(T, Option<String>), ForgeError> {
+    let resp = http.get(url).send().await?;
+    let status = resp.status();
+    if !status.is_success() {
+        return Err(ForgeError::Api {
+            kind,
+            status: status.as_u16(),
+            message: status.canonical_reason().unwrap_or("Unknown").into(),
+        });
+    }
+    let next = resp
+        .headers()
+        .get(LINK)
+        .and_then(|v| v.to_str().ok())
+        .and_then(parse_next_link);
+    Ok((resp.json().await?, next))
+}
+
+/// Page `page` of a listing that is filtered after fetching.
+///
+/// Returns kept items `[(page - 1) * per_page, page * per_page)`, where
+/// `keep` converts each item or drops it. Since the server cannot skip
+/// dropped items, fetching starts at the first page of `url` (without page
+/// parameters; `size_param` names the page size one) and follows `Link: rel="next"` until enough
+/// items are kept, the pages run out or [`MAX_PAGES_PER_CALL`] is reached.
+#[allow(clippy::too_many_arguments)]
+pub(super) async fn list_filtered<T: DeserializeOwned, U>(
+    http: &reqwest::Client,
+    kind: ForgeKind,
+    url: &str,
+    size_param: &str,
+    per_page: u32,
+    page: u32,
+    keep: impl Fn(T) -> Option<U>,
+) -> Result<Vec<U>, ForgeError> {
+    let per_page = per_page.max(1) as usize;
+    let skip = (page.max(1) as usize - 1).saturating_mul(per_page);
+    let wanted = skip.saturating_add(per_page);
+
+    let mut first = Url::parse(url).map_err(|e| ForgeError::Config(format!("Invalid URL: {e}")))?;
+    first
+        .query_pairs_mut()
+        .append_pair(size_param, &MAX_PER_PAGE.to_string());
+
+    let mut kept = Vec::new();
+    let mut next = Some(first.to_string());
+    for _ in 0..MAX_PAGES_PER_CALL {
+        let Some(url) = next.take() else { break };
+        let (batch, next_url): (Vec<T>, _) = get_page(http, kind, &url).await?;
+        kept.extend(batch.into_iter().filter_map(&keep));
+        if kept.len() >= wanted {
+            break;
+        }
+        // The token goes out with every request, so never follow a link
+        // to another origin
+        if let Some(next) = &next_url
+            && !same_origin(next, &first)
+        {
+            return Err(ForgeError::Decode(format!(
+                "next page link leaves the {kind} instance: {next}"
+            )));
+        }
+        next = next_url;
+    }
+    Ok(kept.into_iter().skip(skip).take(per_page).collect())
+}
+
+/// Validate that a project is an `owner/repo` pair.
+pub(super) fn owner_repo(kind: ForgeKind, project: &str) -> Result<&str, ForgeError> {
+    let project = project.trim_matches('/');
+    match project.split_once('/') {
+        Some((owner, repo)) if !owner.is_empty() && !repo.is_empty() && !repo.contains('/') => {
+            Ok(project)
+        }
+        _ => Err(ForgeError::Config(format!(
+            "{kind} project must be \"owner/repo\", got \"{project}\""
+        ))),
+    }
+}
+
+// =============================================================================
+// Filters: GitLab vocabulary -> GitHub-compatible query parameters
+// =============================================================================
+
+/// How to query pull requests for a GitLab merge request state filter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum PullFilter {
+    /// Pass `state=<value>` and keep every result.
+    All(&'static str),
+    /// Query closed pull requests and keep only merged ones.
+    MergedOnly,
+    /// Query closed pull requests and drop merged ones.
+    ClosedUnmerged,
+}
+
+impl PullFilter {
+    pub(super) fn from_state(kind: ForgeKind, state: Option<&str>) -> Result<Self, ForgeError> {
+        match state {
+            None => Ok(Self::All("all")),
+            Some("opened") => Ok(Self::All("open")),
+            Some("closed") => Ok(Self::ClosedUnmerged),
+            Some("merged") => Ok(Self::MergedOnly),
+            Some(other) => Err(ForgeError::Unsupported {
+                kind,
+                message: format!("merge request state filter \"{other}\""),
+            }),
+        }
+    }
+
+    pub(super) const fn query_state(self) -> &'static str {
+        match self {
+            Self::All(state) => state,
+            Self::MergedOnly | Self::ClosedUnmerged => "closed",
+        }
+    }
+
+    pub(super) fn keep(self, mr: &MergeRequest) -> bool {
+        match self {
+            Self::All(_) => true,
+            Self::MergedOnly => mr.state == "merged",
+            Self::ClosedUnmerged => mr.state == "closed",
+        }
+    }
+}
+
+/// Map a GitLab issue state filter to a GitHub-compatible `state` parameter.
+pub(super) fn issue_state_query(
+    kind: ForgeKind,
+    state: Option<&str>,
+) -> Result<&'static str, ForgeError> {
+    match state {
+        None => Ok("all"),
+        Some("opened") => Ok("open"),
+        Some("closed") => Ok("closed"),
+        Some(other) => Err(ForgeError::Unsupported {
+            kind,
+            message: format!("issue state filter \"{other}\""),
+        }),
+    }
+}
+
+/// Map a GitLab pipeline status filter to a workflow run `status` parameter.
+pub(super) fn run_status_query(kind: ForgeKind, status: &str) -> Result<&'static str, ForgeError> {
+    Ok(match status {
+        "created" => "requested",
+        "waiting_for_resource" => "waiting",
+        "preparing" => "pending",
+        "pending" => "queued",
+        "running" => "in_progress",
+        "success" => "success",
+        "failed" => "failure",
+        "canceled" => "cancelled",
+        "skipped" => "skipped",
+        "manual" => "action_required",
+        other => {
+            return Err(ForgeError::Unsupported {
+                kind,
+                message: format!("pipeline status filter \"{other}\""),
+            });
+        }
+    })
+}
+
+/// Map a workflow run `status`/`conclusion` pair to a GitLab pipeline status.
+pub(super) fn run_status_to_gitlab(status: &str, conclusion: Option<&str>) -> &'static str {
+    match status {
+        "requested" => "created",
+        "waiting" | "blocked" => "waiting_for_resource",
+        "pending" => "preparing",
+        "queued" => "pending",
+        "in_progress" | "running" => "running",
+        // Gitea reports the outcome directly in `status`.
+        "success" => "success",
+        "failure" => "failed",
+        "cancelled" => "canceled",
+        "skipped" => "skipped",
+        _ => match conclusion.unwrap_or_default() {
+            "success" => "success",
+            "cancelled" => "canceled",
+            "skipped" | "neutral" | "stale" => "skipped",
+            "action_required" => "manual",
+            _ => "failed",
+        },
+    }
+}
+
+// =============================================================================
+// REST response types
+// =============================================================================
+
+#[derive(Debug, Deserialize)]
+pub(super) struct User {
+    pub login: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub(super) struct Label {
+    pub name: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub(super) struct Milestone {
+    pub title: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub(super) struct BranchRef {
+    #[serde(rename = "ref")]
+    pub ref_name: String,
+}
+
+/// Pull request as returned by GitHub and Gitea.
+#[derive(Debug, Deserialize)]
+pub(super) struct PullRequest {
+    pub id: u64,
+    pub number: u64,
+    pub title: String,
+    #[serde(default)]
+    pub body: Option<String>,
+    pub state: String,
+    pub head: BranchRef,
+    pub base: BranchRef,
+    #[serde(default)]
+    pub user: Option<User>,
+    #[serde(default)]
+    pub labels: Option<Vec<Label>>,
+    pub created_at: String,
+    pub updated_at: String,
+    pub html_url: String,
+    #[serde(default)]
+    pub draft: bool,
+    /// Gitea only.
+    #[serde(default)]
+    pub merged: bool,
+    #[serde(default)]
+    pub merged_at: Option<String>,
+    #[serde(default)]
+    pub mergeable: Option<bool>,
+    #[serde(default)]
+    pub assignee: Option<User>,
+    #[serde(default)]
+    pub assignees: Option<Vec<User>>,
+    #[serde(default)]
+    pub requested_reviewers: Option<Vec<User>>,
+    #[serde(default)]
+    pub milestone: Option<Milestone>,
+}
+
+/// Issue as returned by GitHub and Gitea (both also list pull requests here).
+#[derive(Debug, Deserialize)]
+pub(super) struct RestIssue {
+    pub id: u64,
+    pub number: u64,
+    pub title: String,
+    #[serde(default)]
+    pub body: Option<String>,
+    pub state: String,
+    #[serde(default)]
+    pub user: Option<User>,
+    #[serde(default)]
+    pub labels: Option<Vec<Label>>,
+    pub created_at: String,
+    pub updated_at: String,
+    pub html_url: String,
+    #[serde(default)]
+    pub assignee: Option<User>,
+    #[serde(default)]
+    pub assignees: Option<Vec<User>>,
+    #[serde(default)]
+    pub milestone: Option<Milestone>,
+    #[serde(default)]
+    pub pull_request: Option<serde_json::Value>,
+}
+
+impl RestIssue {
+    /// Whether this entry is actually a pull request.
+    pub(super) const fn is_pull_request(&self) -> bool {
+        self.pull_request.is_some()
+    }
+}
+
+/// Envelope of the workflow run list endpoints.
+#[derive(Debug, Deserialize)]
+pub(super) struct WorkflowRuns {
+    #[serde(default)]
+    pub workflow_runs: Vec<WorkflowRun>,
+}
+
+/// GitHub Actions / Gitea Actions workflow run.
+#[derive(Debug, Deserialize)]
+pub(super) struct WorkflowRun {
+    pub id: u64,
+    #[serde(default)]
+    pub status: Option<String>,
+    #[serde(default)]
+    pub conclusion: Option<String>,
+    #[serde(default)]
+    pub head_branch: Option<String>,
+    pub head_sha: String,
+    #[serde(default)]
+    pub event: Option<String>,
+    #[serde(default, alias = "started_at")]
+    pub created_at: Option<String>,
+    #[serde(default, alias = "completed_at")]
+    pub updated_at: Option<String>,
+    pub html_url: String,
+}
+
+// =============================================================================
+// Conversions to forge-neutral types
+// =============================================================================
+
+fn logins(users: Option<Vec<User>>) -> Vec<String> {
+    users
+        .unwrap_or_default()
+        .into_iter()
+        .map(|u| u.login)
+        .collect()
+}
+
+fn label_names(labels: Option<Vec<Label>>) -> Vec<String> {
+    labels
+        .unwrap_or_default()
+        .into_iter()
+        .map(|l| l.name)
+        .collect()
+}
+
+impl From<PullRequest> for MergeRequest {
+    fn from(pr: PullRequest) -> Self {
+        let state = if pr.merged || pr.merged_at.is_some() {
+            "merged"
+        } else if pr.state == "open" {
+            "opened"
+        } else {
+            "closed"
+        };
+        Self {
+            id: pr.id,
+            iid: pr.number,
+            title: pr.title,
+            description: pr.body,
+            state: state.into(),
+            source_branch: pr.head.ref_name,
+            target_branch: pr.base.ref_name,
+            author: pr.user.map(|u| u.login).unwrap_or_default(),
+            labels: label_names(pr.labels),
+            created_at: pr.created_at,
+            updated_at: pr.updated_at,
+            web_url: pr.html_url,
+            draft: pr.draft,
+            merge_status: pr.mergeable.map(|m| {
+                if m {
+                    "can_be_merged".into()
+                } else {
+                    "cannot_be_merged".into()
+                }
+            }),
+            assignee: pr.assignee.map(|u| u.login),
+            assignees: logins(pr.assignees),
+            reviewers: logins(pr.requested_reviewers),
+            milestone: pr.milestone.map(|m| m.title),
+        }
+    }
+}
+
+impl From<RestIssue> for Issue {
+    fn from(issue: RestIssue) -> Self {
+        let state = if issue.state == "open" {
+            "opened"
+        } else {
+            "closed"
+        };
+        Self {
+            id: issue.id,
+            iid: issue.number,
+            title: issue.title,
+            description: issue.body,
+            state: state.into(),
+            author: issue.user.map(|u| u.login).unwrap_or_default(),
+            labels: label_names(issue.labels),
+            created_at: issue.created_at,
+            updated_at: issue.updated_at,
+            web_url: issue.html_url,
+            confidential: false,
+            assignee: issue.assignee.map(|u| u.login),
+            assignees: logins(issue.assignees),
+            milestone: issue.milestone.map(|m| m.title),
+        }
+    }
+}
+
+impl From<WorkflowRun> for Pipeline {
+    fn from(run: WorkflowRun) -> Self {
+        let status = run_status_to_gitlab(
+            run.status.as_deref().unwrap_or_default(),
+            run.conclusion.as_deref(),
+        );
+        let created_at = run.created_at.unwrap_or_default();
+        Self {
+            id: run.id,
+            status: status.into(),
+            ref_name: run.head_branch.unwrap_or_default(),
+            sha: run.head_sha,
+            source: run.event,
+            updated_at: run.updated_at.unwrap_or_else(|| created_at.clone()),
+            created_at,
+            web_url: run.html_url,
+        }
+    }
+}
@@ -0,0 +1,639 @@
+//! Tests for forge clients, remote parsing and forge resolution.
+
+use std::sync::Arc;
+
+use wiremock::matchers::{method, path, query_param, query_param_is_missing};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+use super::remote::{RemoteUrl, host_of};
+use super::rest::{self, PullFilter, PullRequest, RestIssue, WorkflowRun, WorkflowRuns};
+use super::*;
+use crate::gitlab::{GitLabClient, GitLabConfig};
+use crate::storage::{Database, GitRepoParams};
+
+fn github() -> GitHubClient {
+    GitHubClient::new(&GitHubConfig {
+        base_url: "https://github.com".into(),
+        token: "ghp-test".into(),
+    })
+    .unwrap()
+}
+
+fn gitea() -> GiteaClient {
+    GiteaClient::new(&GiteaConfig {
+        base_url: "https://gitea.example.com/".into(),
+        token: "gitea-test".into(),
+    })
+    .unwrap()
+}
+
+fn gitlab() -> GitLabClient {
+    GitLabClient::new(&GitLabConfig {
+        base_url: "https://gitlab.example.com".into(),
+        token: "glpat-test".into(),
+    })
+    .unwrap()
+}
+
+fn registry(db: Option<Database>) -> ForgeRegistry {
+    let mut registry = ForgeRegistry::new(db);
+    registry.register(Arc::new(gitlab()));
+    registry.register(Arc::new(github()));
+    registry.register(Arc::new(gitea()));
+    registry
+}
+
+// =============================================================================
+// Remote URL parsing
+// =============================================================================
+
+#[test]
+fn parse_scp_like_remote() {
+    let r = RemoteUrl::parse("git@github.com:owner/repo.git").unwrap();
+    assert_eq!(r.host, "github.com");
+    assert_eq!(r.path, "owner/repo");
+}
+
+#[test]
+fn parse_https_remote_with_userinfo_and_port() {
+    let r = RemoteUrl::parse("https://user:pw@Gitea.Example.com:3000/org/repo/").unwrap();
+    assert_eq!(r.host, "gitea.example.com");
+    assert_eq!(r.path, "org/repo");
+}
+
+#[test]
+fn parse_ssh_remote_with_nested_groups() {
+    let r = RemoteUrl::parse("ssh://git@gitlab.example.com:2222/group/sub/project.git").unwrap();
+    assert_eq!(r.host, "gitlab.example.com");
+    assert_eq!(r.path, "group/sub/project");
+}
+
+#[test]
+fn parse_rejects_local_paths() {
+    assert!(RemoteUrl::parse("/srv/git/repo.git").is_none());
+    assert!(RemoteUrl::parse("../repo").is_none());
+    assert!(RemoteUrl::parse("https://github.com").is_none());
+}
+
+#[test]
+fn host_of_strips_scheme_port_and_path() {
+    assert_eq!(
+        host_of("https://GitLab.example.com:8443/"),
+        "gitlab.example.com"
+    );
+    assert_eq!(host_of("gitea.local"), "gitea.local");
+}
+
+// =============================================================================
+// Client construction
+// =============================================================================
+
+#[test]
+fn github_empty_token_returns_config_error() {
+    let err = GitHubClient::new(&GitHubConfig {
+        base_url: "https://github.com".into(),
+        token: String::new(),
+    })
+    .unwrap_err();
+    assert!(matches!(err, ForgeError::Config(_)));
+}
+
+#[test]
+fn github_dot_com_uses_api_host() {
+    let client = github();
+    assert_eq!(client.host(), "github.com");
+    assert_eq!(
+        client.repo_url("owner/repo", "/pulls").unwrap(),
+        "https://api.github.com/repos/owner/repo/pulls"
+    );
+}
+
+#[test]
+fn github_enterprise_uses_api_v3_prefix() {
+    let client = GitHubClient::new(&GitHubConfig {
+        base_url: "https://ghe.corp.example/".into(),
+        token: "tok".into(),
+    })
+    .unwrap();
+    assert_eq!(client.host(), "ghe.corp.example");
+    assert_eq!(
+        client.repo_url("owner/repo", "/issues").unwrap(),
+        "https://ghe.corp.example/api/v3/repos/owner/repo/issues"
+    );
+}
+
+#[test]
+fn gitea_repo_url_uses_api_v1() {
+    let client = gitea();
+    assert_eq!(client.host(), "gitea.example.com");
+    assert_eq!(
+        client.repo_url("org/repo", "/pulls/3").unwrap(),
+        "https://gitea.example.com/api/v1/repos/org/repo/pulls/3"
+    );
+}
+
+#[test]
+fn repo_url_rejects_non_owner_repo_projects() {
+    assert!(matches!(
+        github().repo_url("just-a-name", "/pulls"),
+        Err(ForgeError::Config(_))
+    ));
+    assert!(matches!(
+        gitea().repo_url("group/sub/project", "/pulls"),
+        Err(ForgeError::Config(_))
+    ));
+}
+
+// =============================================================================
+// Filter mapping
+// =============================================================================
+
+#[test]
+fn pull_filter_maps_gitlab_states() {
+    let k = ForgeKind::GitHub;
+    assert_eq!(
+        PullFilter::from_state(k, None).unwrap().query_state(),
+        "all"
+    );
+    assert_eq!(
+        PullFilter::from_state(k, Some("opened"))
+            .unwrap()
+            .query_state(),
+        "open"
+    );
+    assert_eq!(
+        PullFilter::from_state(k, Some("merged")).unwrap(),
+        PullFilter::MergedOnly
+    );
+    assert_eq!(
+        PullFilter::from_state(k, Some("closed")).unwrap(),
+        PullFilter::ClosedUnmerged
+    );
+    assert!(matches!(
+        PullFilter::from_state(k, Some("locked")),
+        Err(ForgeError::Unsupported { .. })
+    ));
+}
+
+#[test]
+fn pull_filter_separates_merged_from_closed() {
+    let merged = MergeRequest {
+        state: "merged".into(),
+        ..Default::default()
+    };
+    let closed = MergeRequest {
+        state: "closed".into(),
+        ..Default::default()
+    };
+    assert!(PullFilter::MergedOnly.keep(&merged));
+    assert!(!PullFilter::MergedOnly.keep(&closed));
+    assert!(PullFilter::ClosedUnmerged.keep(&closed));
+    assert!(!PullFilter::ClosedUnmerged.keep(&merged));
+}
+
+#[test]
+fn run_status_query_maps_pipeline_statuses() {
+    let k = ForgeKind::Gitea;
+    assert_eq!(rest::run_status_query(k, "running").unwrap(), "in_progress");
+    assert_eq!(rest::run_status_query(k, "failed").unwrap(), "failure");
+    assert_eq!(rest::run_status_query(k, "canceled").unwrap(), "cancelled");
+    assert!(matches!(
+        rest::run_status_query(k, "scheduled"),
+        Err(ForgeError::Unsupported { .. })
+    ));
+}
+
+#[test]
+fn run_status_to_gitlab_uses_conclusion_when_completed() {
+    assert_eq!(rest::run_status_to_gitlab("queued", None), "pending");
+    assert_eq!(rest::run_status_to_gitlab("in_progress", None), "running");
+    assert_eq!(
+        rest::run_status_to_gitlab("completed", Some("success")),
+        "success"
+    );
+    assert_eq!(
+        rest::run_status_to_gitlab("completed", Some("timed_out")),
+        "failed"
+    );
+    assert_eq!(
+        rest::run_status_to_gitlab("completed", Some("cancelled")),
+        "canceled"
+    );
+    // Gitea puts the outcome in `status`.
+    assert_eq!(rest::run_status_to_gitlab("failure", None), "failed");
+}
+
+// =============================================================================
+// REST JSON -> forge types
+// =============================================================================
+
+#[test]
+fn github_pull_request_deserializes_and_converts() {
+    let json = r#"{
+        "id": 1, "number": 42, "title": "Add feature", "body": null,
+        "state": "closed", "locked": false,
+        "head": {"ref": "feat", "sha": "abc"}, "base": {"ref": "main", "sha": "def"},
+        "user": {"login": "alice"}, "labels": [{"name": "enhancement"}],
+        "created_at": "2026-01-01T00:00:00Z", "updated_at": "2026-01-02T00:00:00Z",
+        "html_url": "https://github.com/o/r/pull/42", "draft": true,
+        "merged_at": "2026-01-02T00:00:00Z",
+        "assignee": {"login": "bob"}, "assignees": [{"login": "bob"}],
+        "requested_reviewers": [{"login": "carol"}],
+        "milestone": {"title": "v1"}
+    }"#;
+    let pr: PullRequest = serde_json::from_str(json).unwrap();
+    let mr = MergeRequest::from(pr);
+    assert_eq!(mr.iid, 42);
+    assert_eq!(mr.state, "merged");
+    assert_eq!(mr.source_branch, "feat");
+    assert_eq!(mr.target_branch, "main");
+    assert_eq!(mr.author, "alice");
+    assert_eq!(mr.labels, vec!["enhancement"]);
+    assert_eq!(mr.reviewers, vec!["carol"]);
+    assert_eq!(mr.milestone.as_deref(), Some("v1"));
+    assert!(mr.draft);
+    assert!(mr.merge_status.is_none());
+}
+
+#[test]
+fn gitea_pull_request_uses_merged_flag_and_mergeable() {
+    let json = r#"{
+        "id": 7, "number": 3, "title": "Fix", "body": "desc", "state": "open",
+        "head": {"ref": "fix"}, "base": {"ref": "main"},
+        "user": {"login": "dave"}, "labels": [], "assignees": null,
+        "created_at": "2026-01-01T00:00:00Z", "updated_at": "2026-01-01T00:00:00Z",
+        "html_url": "https://gitea.example.com/org/repo/pulls/3",
+        "merged": false, "mergeable": true
+    }"#;
+    let mr = MergeRequest::from(serde_json::from_str::<PullRequest>(json).unwrap());
+    assert_eq!(mr.state, "opened");
+    assert_eq!(mr.merge_status.as_deref(), Some("can_be_merged"));
+    assert!(mr.assignees.is_empty());
+}
+
+#[test]
+fn issue_listing_marks_pull_requests() {
+    let json = r#"[
+        {"id": 1, "number": 1, "title": "Bug", "state": "open",
+         "user": {"login": "alice"}, "labels": [{"name": "bug"}],
+         "created_at": "2026-01-01T00:00:00Z", "updated_at": "2026-01-01T00:00:00Z",
+         "html_url": "https://github.com/o/r/issues/1", "pull_request": null},
+        {"id": 2, "number": 2, "title": "PR", "state": "closed",
+         "created_at": "2026-01-01T00:00:00Z", "updated_at": "2026-01-01T00:00:00Z",
+         "html_url": "https://github.com/o/r/pull/2",
+         "pull_request": {"url": "https://api.github.com/repos/o/r/pulls/2"}}
+    ]"#;
+    let issues: Vec<RestIssue> = serde_json::from_str(json).unwrap();
+    assert!(!issues[0].is_pull_request());
+    assert!(issues[1].is_pull_request());
+    let issue = Issue::from(issues.into_iter().next().unwrap());
+    assert_eq!(issue.state, "opened");
+    assert_eq!(issue.author, "alice");
+    assert_eq!(issue.labels, vec!["bug"]);
+}
+
+// =============================================================================
+// Filtered listings against a mock server
+// =============================================================================
+
+/// A GitHub Enterprise client talking to `server`.
+fn mock_github(server: &MockServer) -> GitHubClient {
+    GitHubClient::new(&GitHubConfig {
+        base_url: server.uri(),
+        token: "ghp-test".into(),
+    })
+    .unwrap()
+}
+
+/// Pull request `number`, merged if `merged`, closed otherwise.
+fn closed_pull_json(number: u64, merged: bool) -> serde_json::Value {
+    serde_json::json!({
+        "id": number, "number": number, "title": format!("PR {number}"),
+        "state": "closed", "head": {"ref": "feat"}, "base": {"ref": "main"},
+        "created_at": "2026-01-01T00:00:00Z", "updated_at": "2026-01-01T00:00:00Z",
+        "html_url": format!("https://ghe.example/o/r/pull/{number}"),
+        "merged_at": merged.then_some("2026-01-02T00:00:00Z")
+    })
+}
+
+/// Issue `number`, or a pull request in the issue listing if `pull`.
+fn issue_json(number: u64, pull: bool) -> serde_json::Value {
+    serde_json::json!({
+        "id": number, "number": number, "title": format!("#{number}"),
+        "state": "open",
+        "created_at": "2026-01-01T00:00:00Z", "updated_at": "2026-01-01T00:00:00Z",
+        "html_url": format!("https://ghe.example/o/r/issues/{number}"),
+        "pull_request": pull.then(|| serde_json::json!({"url": "x"}))
+    })
+}
+
+#[tokio::test]
+async fn merged_filter_fills_the_page_across_server_pages() {
+    let server = MockServer::start().await;
+    let pulls = "/api/v3/repos/o/r/pulls";
+    let next = format!("{}{pulls}?state=closed&per_page=100&page=2", server.uri());
+    // Merged pull requests are every third one: 3, 6, 9, ...
+    Mock::given(method("GET"))
+        .and(path(pulls))
+        .and(query_param("state", "closed"))
+        .and(query_param_is_missing("page"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .insert_header("Link", format!("<{next}>; rel=\"next\"").as_str())
+                .set_body_json(
+                    (1..=6)
+                        .map(|n| closed_pull_json(n, n % 3 == 0))
+                        .collect::<Vec<_>>(),
+                ),
+        )
+        .expect(2)
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path(pulls))
+        .and(query_param("page", "2"))
+        .respond_with(
+            ResponseTemplate::new(200).set_body_json(
+                (7..=12)
+                    .map(|n| closed_pull_json(n, n % 3 == 0))
+                    .collect::<Vec<_>>(),
+            ),
+        )
+        .expect(2)
+        .mount(&server)
+        .await;
+
+    let client = mock_github(&server);
+    let merged = client
+        .list_merge_requests("o/r", Some("merged"), 3, 1)
+        .await
+        .unwrap();
+    assert_eq!(merged.iter().map(|m| m.iid).collect::<Vec<_>>(), [3, 6, 9]);
+
+    // The second page of closed-but-unmerged ones skips the first page's
+    let closed = client
+        .list_merge_requests("o/r", Some("closed"), 3, 2)
+        .await
+        .unwrap();
+    assert_eq!(closed.iter().map(|m| m.iid).collect::<Vec<_>>(), [5, 7, 8]);
+}
+
+#[tokio::test]
+async fn issue_listing_drops_pull_requests_without_short_pages() {
+    let server = MockServer::start().await;
+    let issues = "/api/v3/repos/o/r/issues";
+    let next = format!("{}{issues}?state=open&per_page=100&page=2", server.uri());
+    Mock::given(method("GET"))
+        .and(path(issues))
+        .and(query_param_is_missing("page"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .insert_header("Link", format!("<{next}>; rel=\"next\"").as_str())
+                .set_body_json(vec![
+                    issue_json(1, true),
+                    issue_json(2, false),
+                    issue_json(3, true),
+                ]),
+        )
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path(issues))
+        .and(query_param("page", "2"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .set_body_json(vec![issue_json(4, false), issue_json(5, true)]),
+        )
+        .mount(&server)
+        .await;
+
+    let listed = mock_github(&server)
+        .list_issues("o/r", Some("opened"), 5, 1)
+        .await
+        .unwrap();
+    assert_eq!(listed.iter().map(|i| i.iid).collect::<Vec<_>>(), [2, 4]);
+}
+
+#[tokio::test]
+async fn filtered_listing_refuses_next_link_to_another_origin() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .insert_header("Link", "<https://elsewhere.example/p?page=2>; rel=\"next\"")
+                .set_body_json(vec![issue_json(1, true)]),
+        )
+        .mount(&server)
+        .await;
+
+    let err = mock_github(&server)
+        .list_issues("o/r", None, 5, 1)
+        .await
+        .unwrap_err();
+    assert!(matches!(err, ForgeError::Decode(_)), "{err}");
+}
+
+#[test]
+fn workflow_runs_convert_to_pipelines() {
+    let json = r#"{"total_count": 2, "workflow_runs": [
+        {"id": 10, "status": "completed", "conclusion": "success",
+         "head_branch": "main", "head_sha": "abc", "event": "push",
+         "created_at": "2026-01-01T00:00:00Z", "updated_at": "2026-01-01T00:05:00Z",
+         "html_url": "https://github.com/o/r/actions/runs/10"},
+        {"id": 11, "status": "running", "head_branch": "dev", "head_sha": "def",
+         "event": "pull_request", "started_at": "2026-01-02T00:00:00Z",
+         "html_url": "https://gitea.example.com/org/repo/actions/runs/11"}
+    ]}"#;
+    let runs: WorkflowRuns = serde_json::from_str(json).unwrap();
+    let pipelines: Vec<Pipeline> = runs.workflow_runs.into_iter().map(Pipeline::from).collect();
+    assert_eq!(pipelines[0].status, "success");
+    assert_eq!(pipelines[0].ref_name, "main");
+    assert_eq!(pipelines[0].source.as_deref(), Some("push"));
+    assert_eq!(pipelines[1].status, "running");
+    assert_eq!(pipelines[1].created_at, "2026-01-02T00:00:00Z");
+    assert_eq!(pipelines[1].updated_at, pipelines[1].created_at);
+}
+
+#[test]
+fn single_workflow_run_deserializes() {
+    let json = r#"{"id": 5, "status": "queued", "head_sha": "abc",
+                   "html_url": "https://github.com/o/r/actions/runs/5"}"#;
+    let p = Pipeline::from(serde_json::from_str::<WorkflowRun>(json).unwrap());
+    assert_eq!(p.status, "pending");
+    assert!(p.ref_name.is_empty());
+}
+
+// =============================================================================
+// Registry resolution
+// =============================================================================
+
+#[tokio::test]
+async fn resolve_defaults_to_first_forge() {
+    let r = registry(None).resolve("group/project").await.unwrap();
+    assert_eq!(r.forge.kind(), ForgeKind::GitLab);
+    assert_eq!(r.project, "group/project");
+}
+
+#[tokio::test]
+async fn resolve_honours_host_prefix_and_urls() {
+    let reg = registry(None);
+    let r = reg.resolve("github.com/owner/repo").await.unwrap();
+    assert_eq!(r.forge.kind(), ForgeKind::GitHub);
+    assert_eq!(r.project, "owner/repo");
+
+    let r = reg
+        .resolve("https://gitea.example.com/org/repo.git")
+        .await
+        .unwrap();
+    assert_eq!(r.forge.kind(), ForgeKind::Gitea);
+    assert_eq!(r.project, "org/repo");
+}
+
+#[tokio::test]
+async fn resolve_without_forges_is_config_error() {
+    let err = ForgeRegistry::new(None).resolve("o/r").await.unwrap_err();
+    assert!(matches!(err, ForgeError::Config(_)));
+}
+
+#[test]
+fn resolve_remote_rejects_unknown_host() {
+    let err = registry(None)
+        .resolve_remote("git@bitbucket.org:o/r.git")
+        .unwrap_err();
+    assert!(matches!(err, ForgeError::Config(_)));
+}
+
+async fn git(dir: &std::path::Path, args: &[&str]) {
+    let status = tokio::process::Command::new("git")
+        .args(args)
+        .current_dir(dir)
+        .env_remove("GIT_DIR")
+        .env_remove("GIT_INDEX_FILE")
+        .env_remove("GIT_WORK_TREE")
+        .status()
+        .await
+        .unwrap();
+    assert!(status.success(), "git {args:?} failed");
+}
+
+#[tokio::test]
+async fn resolve_registered_repo_uses_origin_remote() {
+    let dir = tempfile::tempdir().unwrap();
+    git(dir.path(), &["init", "-q"]).await;
+    git(
+        dir.path(),
+        &[
+            "remote",
+            "add",
+            "origin",
+            "git@github.com:sakost/betcode.git",
+        ],
+    )
+    .await;
+
+    let db = Database::open_in_memory().await.unwrap();
+    let repo_path = dir.path().to_string_lossy();
+    db.create_git_repo(
+        "repo-1",
+        &repo_path,
+        &GitRepoParams {
+            name: "betcode",
+            worktree_mode: "global",
+            local_subfolder: ".worktree",
+            custom_path: None,
+            setup_script: None,
+            auto_gitignore: true,
+        },
+    )
+    .await
+    .unwrap();
+
+    let reg = registry(Some(db));
+    for key in ["repo-1", "betcode"] {
+        let r = reg.resolve(key).await.unwrap();
+        assert_eq!(r.forge.kind(), ForgeKind::GitHub, "resolving {key}");
+        assert_eq!(r.project, "sakost/betcode");
+    }
+}
+
+#[tokio::test]
+async fn resolve_registered_repo_without_origin_is_config_error() {
+    let dir = tempfile::tempdir().unwrap();
+    git(dir.path(), &["init", "-q"]).await;
+
+    let db = Database::open_in_memory().await.unwrap();
+    db.create_git_repo(
+        "repo-2",
+        &dir.path().to_string_lossy(),
+        &GitRepoParams {
+            name: "local-only",
+            worktree_mode: "global",
+            local_subfolder: ".worktree",
+            custom_path: None,
+            setup_script: None,
+            auto_gitignore: true,
+        },
+    )
+    .await
+    .unwrap();
+
+    let err = registry(Some(db)).resolve("repo-2").await.unwrap_err();
+    assert!(matches!(err, ForgeError::Config(_)));
+}
+
+#[tokio::test]
+async fn resolved_repos_are_cached_until_the_repos_change() {
+    let dir = tempfile::tempdir().unwrap();
+    git(dir.path(), &["init", "-q"]).await;
+    git(
+        dir.path(),
+        &["remote", "add", "origin", "git@github.com:owner/first.git"],
+    )
+    .await;
+
+    let db = Database::open_in_memory().await.unwrap();
+    db.create_git_repo(
+        "repo-3",
+        &dir.path().to_string_lossy(),
+        &GitRepoParams {
+            name: "cached",
+            worktree_mode: "global",
+            local_subfolder: ".worktree",
+            custom_path: None,
+            setup_script: None,
+            auto_gitignore: true,
+        },
+    )
+    .await
+    .unwrap();
+    let reg = registry(Some(db.clone()));
+    assert_eq!(reg.resolve("cached").await.unwrap().project, "owner/first");
+
+    // The remote is not read again while the repos are unchanged
+    git(
+        dir.path(),
+        &[
+            "remote",
+            "set-url",
+            "origin",
+            "git@github.com:owner/second.git",
+        ],
+    )
+    .await;
+    assert_eq!(reg.resolve("cached").await.unwrap().project, "owner/first");
+
+    // Renaming the repo drops the cache: the old name is a plain project
+    // path again and the new one reads the current remote
+    db.update_git_repo_partial("repo-3", Some("renamed"), None, None, None, None, None)
+        .await
+        .unwrap();
+    let old = reg.resolve("cached").await.unwrap();
+    assert_eq!(
+        (old.forge.kind(), old.project.as_str()),
+        (ForgeKind::GitLab, "cached")
+    );
+    assert_eq!(
+        reg.resolve("renamed").await.unwrap().project,
+        "owner/second"
+    );
+}
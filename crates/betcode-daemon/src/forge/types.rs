@@ -0,0 +1,63 @@
+//! Forge-neutral merge request, pipeline and issue records.
+//!
+//! Field semantics follow GitLab: `iid` is the project-scoped number, states
+//! are GitLab strings and timestamps are RFC 3339.
+
+/// Merge request (GitLab) or pull request (GitHub, Gitea).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MergeRequest {
+    pub id: u64,
+    pub iid: u64,
+    pub title: String,
+    pub description: Option<String>,
+    /// `opened`, `closed`, `merged` or `locked`.
+    pub state: String,
+    pub source_branch: String,
+    pub target_branch: String,
+    pub author: String,
+    pub labels: Vec<String>,
+    pub created_at: String,
+    pub updated_at: String,
+    pub web_url: String,
+    pub draft: bool,
+    /// `can_be_merged`, `cannot_be_merged`, `checking` or `unchecked`.
+    pub merge_status: Option<String>,
+    pub assignee: Option<String>,
+    pub assignees: Vec<String>,
+    pub reviewers: Vec<String>,
+    pub milestone: Option<String>,
+}
+
+/// Pipeline (GitLab) or workflow run (GitHub Actions, Gitea Actions).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Pipeline {
+    pub id: u64,
+    /// GitLab pipeline status (`running`, `success`, `failed`, ...).
+    pub status: String,
+    pub ref_name: String,
+    pub sha: String,
+    pub source: Option<String>,
+    pub created_at: String,
+    pub updated_at: String,
+    pub web_url: String,
+}
+
+/// Issue on any forge.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Issue {
+    pub id: u64,
+    pub iid: u64,
+    pub title: String,
+    pub description: Option<String>,
+    /// `opened` or `closed`.
+    pub state: String,
+    pub author: String,
+    pub labels: Vec<String>,
+    pub created_at: String,
+    pub updated_at: String,
+    pub web_url: String,
+    pub confidential: bool,
+    pub assignee: Option<String>,
+    pub assignees: Vec<String>,
+    pub milestone: Option<String>,
+}
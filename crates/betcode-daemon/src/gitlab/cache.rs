@@ -0,0 +1,144 @@
+//! `ETag` response cache for the GitLab client.
+//!
+//! Responses carrying an `ETag` are kept in memory and, when a database is
+//! attached, in the `gitlab_response_cache` table so they survive daemon
+//! restarts. The client replays the `ETag` as `If-None-Match` and serves the
+//! cached body on `304 Not Modified`, which does not count against GitLab's
+//! rate limits. Persisted responses not refreshed within
+//! [`PERSISTENT_TTL`] are pruned periodically by [`spawn_pruner`].
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use betcode_core::db::unix_timestamp;
+use tokio::sync::RwLock;
+use tokio::task::JoinHandle;
+use tracing::{debug, warn};
+
+use crate::storage::{Database, DatabaseError};
+
+/// Default number of responses kept in memory.
+pub const DEFAULT_MEMORY_CAPACITY: usize = 256;
+
+/// Persisted responses not refreshed for this long are pruned.
+pub const PERSISTENT_TTL: Duration = Duration::from_hours(7 * 24);
+
+/// How often [`spawn_pruner`] prunes the persistent tier.
+pub const PRUNE_INTERVAL: Duration = Duration::from_hours(1);
+
+/// A cached response body with its validator.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CachedResponse {
+    pub etag: String,
+    pub body: String,
+    /// Next-page URL advertised alongside the body (304s carry no links).
+    pub next_url: Option<String>,
+}
+
+/// Two-tier (memory + `SQLite`) `ETag` cache keyed by request URL.
+pub struct ResponseCache {
+    memory: RwLock<HashMap<String, CachedResponse>>,
+    capacity: usize,
+    db: Option<Database>,
+}
+
+impl std::fmt::Debug for ResponseCache {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ResponseCache")
+            .field("capacity", &self.capacity)
+            .field("persistent", &self.db.is_some())
+            .finish_non_exhaustive()
+    }
+}
+
+impl ResponseCache {
+    /// Create a cache; `db` adds a persistent tier behind the in-memory one.
+    pub fn new(db: Option<Database>) -> Self {
+        Self::with_capacity(db, DEFAULT_MEMORY_CAPACITY)
+    }
+
+    /// Create a cache holding at most `capacity` responses in memory.
+    pub fn with_capacity(db: Option<Database>, capacity: usize) -> Self {
+        Self {
+            memory: RwLock::new(HashMap::new()),
+            capacity: capacity.max(1),
+            db,
+        }
+    }
+
+    /// Look up a URL, falling back to the database and promoting hits to memory.
+    pub async fn get(&self, url: &str) -> Option<CachedResponse> {
+        if let Some(hit) = self.memory.read().await.get(url) {
+            return Some(hit.clone());
+        }
+        let db = self.db.as_ref()?;
+        let row = match db.get_gitlab_cache(url).await {
+            Ok(row) => row?,
+            Err(e) => {
+                warn!(error = %e, "Failed to read GitLab response cache");
+                return None;
+            }
+        };
+        let entry = CachedResponse {
+            etag: row.etag,
+            body: row.body,
+            next_url: row.next_url,
+        };
+        self.insert_memory(url, entry.clone()).await;
+        Some(entry)
+    }
+
+    /// Store a response in both tiers. Database failures are logged, not returned.
+    pub async fn put(&self, url: &str, entry: CachedResponse) {
+        if let Some(db) = &self.db
+            && let Err(e) = db
+                .put_gitlab_cache(url, &entry.etag, &entry.body, entry.next_url.as_deref())
+                .await
+        {
+            warn!(error = %e, "Failed to write GitLab response cache");
+        }
+        self.insert_memory(url, entry).await;
+    }
+
+    /// Number of responses currently held in memory.
+    pub async fn memory_len(&self) -> usize {
+        self.memory.read().await.len()
+    }
+
+    async fn insert_memory(&self, url: &str, entry: CachedResponse) {
+        let mut memory = self.memory.write().await;
+        if memory.len() >= self.capacity && !memory.contains_key(url) {
+            // Evicted entries stay in the database tier, so dropping an
+            // arbitrary one is good enough.
+            if let Some(victim) = memory.keys().next().cloned() {
+                memory.remove(&victim);
+            }
+        }
+        memory.insert(url.to_string(), entry);
+    }
+}
+
+/// Delete persisted responses that were last refreshed more than
+/// [`PERSISTENT_TTL`] before `now` (unix seconds).
+///
+/// Returns the number of responses removed.
+pub async fn prune_expired(db: &Database, now: i64) -> Result<u64, DatabaseError> {
+    #[allow(clippy::cast_possible_wrap)]
+    let cutoff = now - PERSISTENT_TTL.as_secs() as i64;
+    db.prune_gitlab_cache(cutoff).await
+}
+
+/// Run [`prune_expired`] every [`PRUNE_INTERVAL`] for the life of the daemon.
+pub fn spawn_pruner(db: Database) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut timer = tokio::time::interval(PRUNE_INTERVAL);
+        loop {
+            timer.tick().await;
+            match prune_expired(&db, unix_timestamp()).await {
+                Ok(0) => {}
+                Ok(pruned) => debug!(pruned, "Pruned expired GitLab responses"),
+                Err(e) => warn!(error = %e, "Failed to prune GitLab response cache"),
+            }
+        }
+    })
+}
@@ -1,10 +1,20 @@
 //! GitLab REST API v4 client.
 //!
 //! Uses reqwest to call GitLab endpoints for merge requests, pipelines, and issues.
+//! List calls follow `X-Next-Page`/`Link` headers across pages (never off the
+//! instance, since every request carries the token), throttled
+//! requests are retried per [`RetryPolicy`], and an optional [`ResponseCache`]
+//! turns repeat GETs into conditional requests.
 
-use reqwest::header::{AUTHORIZATION, HeaderMap, HeaderValue};
+use std::sync::Arc;
+
+use reqwest::header::{AUTHORIZATION, ETAG, HeaderMap, HeaderValue, IF_NONE_MATCH};
+use reqwest::{StatusCode, Url};
 use thiserror::Error;
+use tracing::{debug, warn};
 
+use super::cache::{CachedResponse, ResponseCache};
+use super::http::{self, MAX_PAGES_PER_CALL, MAX_PER_PAGE, RetryPolicy};
 use super::types::{Issue, MergeRequest, Pipeline};
 
 /// GitLab API client errors.
@@ -18,6 +28,12 @@ pub enum GitLabError {
 
     #[error("Configuration error: {0}")]
     Config(String),
+
+    #[error("Invalid JSON response: {0}")]
+    Decode(#[from] serde_json::Error),
+
+    #[error("Next page link leaves the GitLab instance: {0}")]
+    ForeignLink(String),
 }
 
 /// Configuration for connecting to a GitLab instance.
@@ -34,6 +50,17 @@ pub struct GitLabConfig {
 pub struct GitLabClient {
     http: reqwest::Client,
     base_url: String,
+    /// Parsed `base_url`; next page links must share its origin.
+    base: Url,
+    host: String,
+    retry: RetryPolicy,
+    cache: Option<Arc<ResponseCache>>,
+}
+
+/// Raw body of a successful GET plus the advertised next page.
+struct RawPage {
+    body: String,
+    next_url: Option<String>,
 }
 
 impl GitLabClient {
@@ -60,7 +87,36 @@ impl GitLabClient {
             .build()?;
 
         let base_url = config.base_url.trim_end_matches('/').to_string();
-        Ok(Self { http, base_url })
+        let base = Url::parse(&base_url)
+            .map_err(|e| GitLabError::Config(format!("Invalid base_url: {e}")))?;
+        let host = crate::forge::remote::host_of(&base_url);
+        Ok(Self {
+            http,
+            base_url,
+            base,
+            host,
+            retry: RetryPolicy::default(),
+            cache: None,
+        })
+    }
+
+    /// Replace the backoff policy for throttled requests.
+    #[must_use]
+    pub const fn with_retry_policy(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// Attach an `ETag` response cache.
+    #[must_use]
+    pub fn with_cache(mut self, cache: Arc<ResponseCache>) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    /// Web host of the instance (e.g. `gitlab.com`).
+    pub fn host(&self) -> &str {
+        &self.host
     }
 
     /// Build the API v4 URL for a given path.
@@ -89,11 +145,87 @@ impl GitLabClient {
     // Generic helpers
     // =========================================================================
 
+    /// GET a URL with `ETag` revalidation and 429 backoff.
+    async fn fetch(&self, url: &str) -> Result<RawPage, GitLabError> {
+        let cached = match &self.cache {
+            Some(cache) => cache.get(url).await,
+            None => None,
+        };
+
+        let mut attempt = 0;
+        loop {
+            let mut req = self.http.get(url);
+            if let Some(c) = &cached {
+                req = req.header(IF_NONE_MATCH, &c.etag);
+            }
+            let resp = req.send().await?;
+
+            if resp.status() == StatusCode::NOT_MODIFIED
+                && let Some(c) = cached
+            {
+                debug!(url, "GitLab response not modified, serving from cache");
+                return Ok(RawPage {
+                    body: c.body,
+                    next_url: c.next_url,
+                });
+            }
+
+            if resp.status() == StatusCode::TOO_MANY_REQUESTS && attempt < self.retry.max_retries {
+                let delay = self.retry.delay(resp.headers(), attempt);
+                warn!(
+                    url,
+                    attempt,
+                    delay_ms = delay.as_millis(),
+                    "GitLab rate limit hit, backing off"
+                );
+                tokio::time::sleep(delay).await;
+                attempt += 1;
+                continue;
+            }
+
+            Self::check_status(&resp)?;
+            let next_url = http::next_page_url(resp.headers(), url);
+            // The token goes out with every request, so never follow a link
+            // to another origin
+            if let Some(next) = &next_url
+                && !http::same_origin(next, &self.base)
+            {
+                return Err(GitLabError::ForeignLink(next.clone()));
+            }
+            let etag = resp
+                .headers()
+                .get(ETAG)
+                .and_then(|v| v.to_str().ok())
+                .map(str::to_string);
+            let body = resp.text().await?;
+
+            if let (Some(cache), Some(etag)) = (&self.cache, etag) {
+                let entry = CachedResponse {
+                    etag,
+                    body: body.clone(),
+                    next_url: next_url.clone(),
+                };
+                cache.put(url, entry).await;
+            }
+            return Ok(RawPage { body, next_url });
+        }
+    }
+
+    /// GET a URL and deserialise the JSON body.
+    async fn fetch_json<T: serde::de::DeserializeOwned>(
+        &self,
+        url: &str,
+    ) -> Result<(T, Option<String>), GitLabError> {
+        let page = self.fetch(url).await?;
+        Ok((serde_json::from_str(&page.body)?, page.next_url))
+    }
+
     /// Paginated list request with an optional filter query parameter.
     ///
-    /// Builds a URL of the form
-    /// `<base>/api/v4/projects/<encoded>/…?per_page=N&page=M[&<filter_key>=<filter_value>]`,
-    /// sends a GET request, checks the status, and deserialises the JSON body.
+    /// Returns items `[(page - 1) * per_page, page * per_page)`. Requests are
+    /// issued with at most [`MAX_PER_PAGE`] items each, following the
+    /// `Link`/`X-Next-Page` headers until enough items are collected or the
+    /// last page is reached.
     #[allow(clippy::too_many_arguments)]
     async fn list_paginated<T: serde::de::DeserializeOwned>(
         &self,
@@ -104,20 +236,43 @@ impl GitLabClient {
         per_page: u32,
         page: u32,
     ) -> Result<Vec<T>, GitLabError> {
+        let per_page = per_page.max(1);
+        let api_per_page = per_page.min(MAX_PER_PAGE);
+        let start = u64::from(page.max(1) - 1) * u64::from(per_page);
+        let api_page = start / u64::from(api_per_page) + 1;
+        #[allow(clippy::cast_possible_truncation)]
+        let skip = (start % u64::from(api_per_page)) as usize;
+        let wanted = skip + per_page as usize;
+
         let encoded = Self::encode_project(project);
-        let mut url = format!(
-            "{}?per_page={}&page={}",
-            self.api_url(&format!("/projects/{encoded}/{resource}")),
-            per_page,
-            page
-        );
-        if let Some(v) = filter_value {
-            use std::fmt::Write;
-            let _ = write!(url, "&{filter_key}={v}");
+        let mut url = Url::parse(&self.api_url(&format!("/projects/{encoded}/{resource}")))
+            .map_err(|e| GitLabError::Config(format!("Invalid project path: {e}")))?;
+        {
+            let mut query = url.query_pairs_mut();
+            query
+                .append_pair("per_page", &api_per_page.to_string())
+                .append_pair("page", &api_page.to_string());
+            if let Some(v) = filter_value {
+                query.append_pair(filter_key, v);
+            }
+        }
+
+        let mut items: Vec<T> = Vec::new();
+        let mut next = Some(String::from(url));
+        for _ in 0..MAX_PAGES_PER_CALL {
+            let Some(url) = next.take() else { break };
+            let (batch, next_url): (Vec<T>, _) = self.fetch_json(&url).await?;
+            items.extend(batch);
+            if items.len() >= wanted {
+                break;
+            }
+            next = next_url;
         }
-        let resp = self.http.get(&url).send().await?;
-        Self::check_status(&resp)?;
-        Ok(resp.json().await?)
+        Ok(items
+            .into_iter()
+            .skip(skip)
+            .take(per_page as usize)
+            .collect())
     }
 
     /// GET a single resource and deserialise.
@@ -128,9 +283,7 @@ impl GitLabClient {
     ) -> Result<T, GitLabError> {
         let encoded = Self::encode_project(project);
         let url = self.api_url(&format!("/projects/{encoded}/{resource_path}"));
-        let resp = self.http.get(&url).send().await?;
-        Self::check_status(&resp)?;
-        Ok(resp.json().await?)
+        Ok(self.fetch_json(&url).await?.0)
     }
 
     // =========================================================================
@@ -0,0 +1,150 @@
+//! `Forge` implementation and type conversions for `GitLabClient`.
+
+use super::client::{GitLabClient, GitLabError};
+use super::types::{self, GitLabUser};
+use crate::forge::{self, Forge, ForgeError, ForgeKind};
+
+impl From<GitLabError> for ForgeError {
+    fn from(err: GitLabError) -> Self {
+        match err {
+            GitLabError::Http(e) => Self::Http(e),
+            GitLabError::Api { status, message } => Self::Api {
+                kind: ForgeKind::GitLab,
+                status,
+                message,
+            },
+            GitLabError::Config(msg) => Self::Config(msg),
+            GitLabError::Decode(e) => Self::Decode(e.to_string()),
+            GitLabError::ForeignLink(_) => Self::Decode(err.to_string()),
+        }
+    }
+}
+
+fn usernames(users: Vec<GitLabUser>) -> Vec<String> {
+    users.into_iter().map(|u| u.username).collect()
+}
+
+impl From<types::MergeRequest> for forge::MergeRequest {
+    fn from(mr: types::MergeRequest) -> Self {
+        Self {
+            id: mr.id,
+            iid: mr.iid,
+            title: mr.title,
+            description: mr.description,
+            state: mr.state,
+            source_branch: mr.source_branch,
+            target_branch: mr.target_branch,
+            author: mr.author.username,
+            labels: mr.labels,
+            created_at: mr.created_at,
+            updated_at: mr.updated_at,
+            web_url: mr.web_url,
+            draft: mr.draft,
+            merge_status: mr.merge_status,
+            assignee: mr.assignee.map(|u| u.username),
+            assignees: usernames(mr.assignees),
+            reviewers: usernames(mr.reviewers),
+            milestone: mr.milestone.map(|m| m.title),
+        }
+    }
+}
+
+impl From<types::Pipeline> for forge::Pipeline {
+    fn from(p: types::Pipeline) -> Self {
+        Self {
+            id: p.id,
+            status: p.status,
+            ref_name: p.ref_name,
+            sha: p.sha,
+            source: p.source,
+            created_at: p.created_at,
+            updated_at: p.updated_at,
+            web_url: p.web_url,
+        }
+    }
+}
+
+impl From<types::Issue> for forge::Issue {
+    fn from(issue: types::Issue) -> Self {
+        Self {
+            id: issue.id,
+            iid: issue.iid,
+            title: issue.title,
+            description: issue.description,
+            state: issue.state,
+            author: issue.author.username,
+            labels: issue.labels,
+            created_at: issue.created_at,
+            updated_at: issue.updated_at,
+            web_url: issue.web_url,
+            confidential: issue.confidential,
+            assignee: issue.assignee.map(|u| u.username),
+            assignees: usernames(issue.assignees),
+            milestone: issue.milestone.map(|m| m.title),
+        }
+    }
+}
+
+#[tonic::async_trait]
+impl Forge for GitLabClient {
+    fn kind(&self) -> ForgeKind {
+        ForgeKind::GitLab
+    }
+
+    fn host(&self) -> &str {
+        Self::host(self)
+    }
+
+    async fn list_merge_requests(
+        &self,
+        project: &str,
+        state: Option<&str>,
+        per_page: u32,
+        page: u32,
+    ) -> Result<Vec<forge::MergeRequest>, ForgeError> {
+        let mrs = Self::list_merge_requests(self, project, state, per_page, page).await?;
+        Ok(mrs.into_iter().map(Into::into).collect())
+    }
+
+    async fn get_merge_request(
+        &self,
+        project: &str,
+        iid: u64,
+    ) -> Result<forge::MergeRequest, ForgeError> {
+        Ok(Self::get_merge_request(self, project, iid).await?.into())
+    }
+
+    async fn list_pipelines(
+        &self,
+        project: &str,
+        status: Option<&str>,
+        per_page: u32,
+        page: u32,
+    ) -> Result<Vec<forge::Pipeline>, ForgeError> {
+        let pipelines = Self::list_pipelines(self, project, status, per_page, page).await?;
+        Ok(pipelines.into_iter().map(Into::into).collect())
+    }
+
+    async fn get_pipeline(
+        &self,
+        project: &str,
+        pipeline_id: u64,
+    ) -> Result<forge::Pipeline, ForgeError> {
+        Ok(Self::get_pipeline(self, project, pipeline_id).await?.into())
+    }
+
+    async fn list_issues(
+        &self,
+        project: &str,
+        state: Option<&str>,
+        per_page: u32,
+        page: u32,
+    ) -> Result<Vec<forge::Issue>, ForgeError> {
+        let issues = Self::list_issues(self, project, state, per_page, page).await?;
+        Ok(issues.into_iter().map(Into::into).collect())
+    }
+
+    async fn get_issue(&self, project: &str, iid: u64) -> Result<forge::Issue, ForgeError> {
+        Ok(Self::get_issue(self, project, iid).await?.into())
+    }
+}
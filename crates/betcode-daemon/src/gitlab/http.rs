@@ -0,0 +1,108 @@
+//! Pagination and rate-limit helpers for the GitLab REST API.
+//!
+//! GitLab advertises the next page through `X-Next-Page` (offset pagination)
+//! and/or a `Link: <...>; rel="next"` header (keyset pagination). Throttled
+//! requests answer 429 with `Retry-After` and `RateLimit-Reset` headers.
+
+use std::time::Duration;
+
+use reqwest::Url;
+use reqwest::header::{HeaderMap, LINK, RETRY_AFTER};
+
+/// GitLab's maximum `per_page` value.
+pub const MAX_PER_PAGE: u32 = 100;
+
+/// Upper bound on pages fetched for a single list call.
+pub const MAX_PAGES_PER_CALL: usize = 50;
+
+/// Backoff settings for throttled (429) requests.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Retries after the first attempt; 0 disables retrying.
+    pub max_retries: u32,
+    /// Delay before the first retry when the server sends no hint. Doubles per attempt.
+    pub base_delay: Duration,
+    /// Cap applied to both server hints and computed backoff.
+    pub max_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 3,
+            base_delay: Duration::from_secs(1),
+            max_delay: Duration::from_mins(1),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Delay before retry number `attempt` (0-based), honouring server hints.
+    pub fn delay(&self, headers: &HeaderMap, attempt: u32) -> Duration {
+        let backoff = self.base_delay.saturating_mul(2u32.saturating_pow(attempt));
+        server_hint(headers).unwrap_or(backoff).min(self.max_delay)
+    }
+}
+
+/// Wait time requested by the server via `Retry-After` (seconds) or
+/// `RateLimit-Reset` (unix timestamp).
+fn server_hint(headers: &HeaderMap) -> Option<Duration> {
+    let header = |name| headers.get(name).and_then(|v| v.to_str().ok());
+    if let Some(secs) = header(RETRY_AFTER.as_str()).and_then(|v| v.trim().parse::<u64>().ok()) {
+        return Some(Duration::from_secs(secs));
+    }
+    let reset = header("ratelimit-reset").and_then(|v| v.trim().parse::<i64>().ok())?;
+    let wait = reset - betcode_core::db::unix_timestamp();
+    u64::try_from(wait).ok().map(Duration::from_secs)
+}
+
+/// URL of the next page, if the response advertises one.
+///
+/// Prefers the `Link` header (works for keyset pagination) and falls back to
+/// rewriting the `page` parameter of `current_url` from `X-Next-Page`.
+pub fn next_page_url(headers: &HeaderMap, current_url: &str) -> Option<String> {
+    if let Some(link) = headers.get(LINK).and_then(|v| v.to_str().ok())
+        && let Some(next) = parse_next_link(link)
+    {
+        return Some(next);
+    }
+    let next_page = headers
+        .get("x-next-page")
+        .and_then(|v| v.to_str().ok())
+        .map(str::trim)
+        .filter(|v| !v.is_empty())?;
+    set_query_param(current_url, "page", next_page)
+}
+
+/// Whether `url` has the same scheme, host and port as `base`.
+pub fn same_origin(url: &str, base: &Url) -> bool {
+    Url::parse(url).is_ok_and(|url| url.origin() == base.origin())
+}
+
+/// Extract the `rel="next"` target from an RFC 8288 `Link` header.
+pub fn parse_next_link(link: &str) -> Option<String> {
+    link.split(',').find_map(|part| {
+        let (target, params) = part.split_once(';')?;
+        let is_next = params
+            .split(';')
+            .any(|p| matches!(p.trim(), "rel=\"next\"" | "rel=next"));
+        let target = target.trim().strip_prefix('<')?.strip_suffix('>')?;
+        is_next.then(|| target.to_string())
+    })
+}
+
+/// Replace (or add) a query parameter, keeping the others in order.
+fn set_query_param(url: &str, key: &str, value: &str) -> Option<String> {
+    let mut parsed = Url::parse(url).ok()?;
+    let pairs: Vec<(String, String)> = parsed
+        .query_pairs()
+        .filter(|(k, _)| k != key)
+        .map(|(k, v)| (k.into_owned(), v.into_owned()))
+        .collect();
+    parsed
+        .query_pairs_mut()
+        .clear()
+        .extend_pairs(pairs)
+        .append_pair(key, value);
+    Some(parsed.into())
+}
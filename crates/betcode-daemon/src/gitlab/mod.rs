@@ -1,13 +1,20 @@
 //! GitLab API integration.
 //!
 //! Provides a reqwest-based client for the GitLab REST API v4,
-//! covering merge requests, pipelines, and issues.
+//! covering merge requests, pipelines, and issues, with multi-page iteration,
+//! rate-limit backoff and an `ETag` response cache. `GitLabClient` also
+//! implements [`crate::forge::Forge`].
 
+pub mod cache;
 mod client;
+mod forge;
+pub mod http;
 pub mod types;
 
 #[cfg(test)]
 mod tests;
 
+pub use cache::ResponseCache;
 pub use client::{GitLabClient, GitLabConfig, GitLabError};
+pub use http::RetryPolicy;
 pub use types::{Issue, MergeRequest, Pipeline};
@@ -1,7 +1,17 @@
 //! Tests for the GitLab API client and types.
 
+use std::sync::Arc;
+use std::time::Duration;
+
+use reqwest::header::{HeaderMap, HeaderValue};
+use wiremock::matchers::{header, method, path, query_param, query_param_is_missing};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+use super::cache::ResponseCache;
 use super::client::{GitLabClient, GitLabConfig, GitLabError};
+use super::http::{RetryPolicy, next_page_url, parse_next_link};
 use super::types::{Issue, MergeRequest, Pipeline};
+use crate::storage::Database;
 
 // =============================================================================
 // Client construction tests
@@ -243,3 +253,380 @@ fn gitlab_error_display_config() {
     let err = GitLabError::Config("bad".into());
     assert_eq!(err.to_string(), "Configuration error: bad");
 }
+
+// =============================================================================
+// Pagination header parsing
+// =============================================================================
+
+#[test]
+fn parse_next_link_picks_rel_next() {
+    let link = r#"<https://gitlab.com/api/v4/projects?page=1>; rel="first", <https://gitlab.com/api/v4/projects?page=3>; rel="next""#;
+    assert_eq!(
+        parse_next_link(link).as_deref(),
+        Some("https://gitlab.com/api/v4/projects?page=3")
+    );
+    assert!(parse_next_link(r#"<https://x/?page=1>; rel="prev""#).is_none());
+}
+
+#[test]
+fn next_page_url_rewrites_page_from_x_next_page() {
+    let mut headers = HeaderMap::new();
+    headers.insert("x-next-page", HeaderValue::from_static("2"));
+    assert_eq!(
+        next_page_url(
+            &headers,
+            "https://g/api/v4/x?per_page=5&page=1&state=opened"
+        )
+        .as_deref(),
+        Some("https://g/api/v4/x?per_page=5&state=opened&page=2")
+    );
+
+    headers.insert("x-next-page", HeaderValue::from_static(""));
+    assert!(next_page_url(&headers, "https://g/api/v4/x?page=1").is_none());
+}
+
+#[test]
+fn retry_delay_prefers_retry_after_and_caps() {
+    let policy = RetryPolicy {
+        max_retries: 3,
+        base_delay: Duration::from_millis(100),
+        max_delay: Duration::from_secs(5),
+    };
+    let mut headers = HeaderMap::new();
+    assert_eq!(policy.delay(&headers, 0), Duration::from_millis(100));
+    assert_eq!(policy.delay(&headers, 2), Duration::from_millis(400));
+
+    headers.insert("retry-after", HeaderValue::from_static("2"));
+    assert_eq!(policy.delay(&headers, 0), Duration::from_secs(2));
+
+    headers.insert("retry-after", HeaderValue::from_static("3600"));
+    assert_eq!(policy.delay(&headers, 0), Duration::from_secs(5));
+}
+
+// =============================================================================
+// Mock server tests
+// =============================================================================
+
+const MR_PATH: &str = "/api/v4/projects/group%2Fproject/merge_requests";
+
+fn mock_client(server: &MockServer) -> GitLabClient {
+    GitLabClient::new(&GitLabConfig {
+        base_url: server.uri(),
+        token: "glpat-test".into(),
+    })
+    .unwrap()
+    .with_retry_policy(RetryPolicy {
+        max_retries: 2,
+        base_delay: Duration::from_millis(10),
+        max_delay: Duration::from_millis(50),
+    })
+}
+
+fn mr_json(iid: u64) -> serde_json::Value {
+    serde_json::json!({
+        "id": iid + 1000,
+        "iid": iid,
+        "title": format!("MR {iid}"),
+        "state": "opened",
+        "source_branch": "feat",
+        "target_branch": "main",
+        "author": {"username": "alice"},
+        "created_at": "2026-01-01T00:00:00Z",
+        "updated_at": "2026-01-01T00:00:00Z",
+        "web_url": format!("https://gitlab.com/mr/{iid}")
+    })
+}
+
+fn mrs_json(iids: std::ops::RangeInclusive<u64>) -> serde_json::Value {
+    serde_json::Value::Array(iids.map(mr_json).collect())
+}
+
+fn iids(mrs: &[MergeRequest]) -> Vec<u64> {
+    mrs.iter().map(|m| m.iid).collect()
+}
+
+#[tokio::test]
+async fn list_follows_x_next_page_beyond_max_per_page() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path(MR_PATH))
+        .and(query_param("page", "1"))
+        .and(query_param("per_page", "100"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .insert_header("X-Next-Page", "2")
+                .set_body_json(mrs_json(1..=100)),
+        )
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path(MR_PATH))
+        .and(query_param("page", "2"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .insert_header("X-Next-Page", "")
+                .set_body_json(mrs_json(101..=130)),
+        )
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let mrs = mock_client(&server)
+        .list_merge_requests("group/project", None, 150, 1)
+        .await
+        .unwrap();
+    assert_eq!(mrs.len(), 130);
+    assert_eq!(mrs.last().map(|m| m.iid), Some(130));
+}
+
+#[tokio::test]
+async fn list_follows_link_header_and_honours_offset() {
+    let server = MockServer::start().await;
+    let next = format!("{}{MR_PATH}?per_page=100&cursor=abc", server.uri());
+    // Page 2 of 150 starts at item 150: API page 2 (items 100..200), skip 50.
+    Mock::given(method("GET"))
+        .and(path(MR_PATH))
+        .and(query_param("page", "2"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .insert_header("Link", format!("<{next}>; rel=\"next\"").as_str())
+                .set_body_json(mrs_json(101..=200)),
+        )
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path(MR_PATH))
+        .and(query_param("cursor", "abc"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(mrs_json(201..=300)))
+        .mount(&server)
+        .await;
+
+    let mrs = mock_client(&server)
+        .list_merge_requests("group/project", None, 150, 2)
+        .await
+        .unwrap();
+    assert_eq!(mrs.len(), 150);
+    assert_eq!(iids(&mrs)[..2], [151, 152]);
+    assert_eq!(mrs.last().map(|m| m.iid), Some(300));
+}
+
+#[tokio::test]
+async fn list_refuses_next_link_to_another_origin() {
+    let server = MockServer::start().await;
+    let elsewhere = MockServer::start().await;
+    let next = format!("{}{MR_PATH}?cursor=abc", elsewhere.uri());
+    Mock::given(method("GET"))
+        .and(path(MR_PATH))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .insert_header("Link", format!("<{next}>; rel=\"next\"").as_str())
+                .set_body_json(mrs_json(1..=100)),
+        )
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(mrs_json(101..=200)))
+        .expect(0)
+        .mount(&elsewhere)
+        .await;
+
+    let err = mock_client(&server)
+        .list_merge_requests("group/project", None, 150, 1)
+        .await
+        .unwrap_err();
+    assert!(matches!(err, GitLabError::ForeignLink(url) if url == next));
+}
+
+#[tokio::test]
+async fn list_filter_value_is_query_encoded() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path(MR_PATH))
+        .and(query_param("state", "opened&scope=all"))
+        .and(query_param_is_missing("scope"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(mrs_json(1..=2)))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let mrs = mock_client(&server)
+        .list_merge_requests("group/project", Some("opened&scope=all"), 20, 1)
+        .await
+        .unwrap();
+    assert_eq!(iids(&mrs), [1, 2]);
+}
+
+#[tokio::test]
+async fn single_page_request_does_not_follow_next() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path(MR_PATH))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .insert_header("X-Next-Page", "2")
+                .set_body_json(mrs_json(1..=20)),
+        )
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let mrs = mock_client(&server)
+        .list_merge_requests("group/project", Some("opened"), 20, 1)
+        .await
+        .unwrap();
+    assert_eq!(mrs.len(), 20);
+}
+
+#[tokio::test]
+async fn rate_limited_request_retries_after_delay() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path(MR_PATH))
+        .respond_with(ResponseTemplate::new(429).insert_header("Retry-After", "0"))
+        .up_to_n_times(1)
+        .with_priority(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path(MR_PATH))
+        .respond_with(ResponseTemplate::new(200).set_body_json(mrs_json(1..=2)))
+        .mount(&server)
+        .await;
+
+    let mrs = mock_client(&server)
+        .list_merge_requests("group/project", None, 20, 1)
+        .await
+        .unwrap();
+    assert_eq!(iids(&mrs), vec![1, 2]);
+    assert_eq!(server.received_requests().await.unwrap().len(), 2);
+}
+
+#[tokio::test]
+async fn rate_limit_gives_up_after_max_retries() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path(MR_PATH))
+        .respond_with(ResponseTemplate::new(429))
+        .expect(3)
+        .mount(&server)
+        .await;
+
+    let err = mock_client(&server)
+        .list_merge_requests("group/project", None, 20, 1)
+        .await
+        .unwrap_err();
+    assert!(matches!(err, GitLabError::Api { status: 429, .. }));
+}
+
+async fn mount_etag_pair(server: &MockServer) {
+    Mock::given(method("GET"))
+        .and(path(MR_PATH))
+        .and(header("If-None-Match", "\"v1\""))
+        .respond_with(ResponseTemplate::new(304))
+        .with_priority(1)
+        .mount(server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path(MR_PATH))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .insert_header("ETag", "\"v1\"")
+                .set_body_json(mrs_json(1..=3)),
+        )
+        .expect(1)
+        .mount(server)
+        .await;
+}
+
+#[tokio::test]
+async fn etag_cache_serves_not_modified_from_memory() {
+    let server = MockServer::start().await;
+    mount_etag_pair(&server).await;
+
+    let cache = Arc::new(ResponseCache::new(None));
+    let client = mock_client(&server).with_cache(Arc::clone(&cache));
+    let first = client
+        .list_merge_requests("group/project", None, 20, 1)
+        .await
+        .unwrap();
+    let second = client
+        .list_merge_requests("group/project", None, 20, 1)
+        .await
+        .unwrap();
+
+    assert_eq!(iids(&first), vec![1, 2, 3]);
+    assert_eq!(iids(&second), iids(&first));
+    assert_eq!(cache.memory_len().await, 1);
+}
+
+#[tokio::test]
+async fn etag_cache_persists_in_sqlite_across_clients() {
+    let server = MockServer::start().await;
+    mount_etag_pair(&server).await;
+    let db = Database::open_in_memory().await.unwrap();
+
+    let first = mock_client(&server)
+        .with_cache(Arc::new(ResponseCache::new(Some(db.clone()))))
+        .list_merge_requests("group/project", None, 20, 1)
+        .await
+        .unwrap();
+    // Fresh memory tier: the ETag must come from the database.
+    let second = mock_client(&server)
+        .with_cache(Arc::new(ResponseCache::new(Some(db))))
+        .list_merge_requests("group/project", None, 20, 1)
+        .await
+        .unwrap();
+
+    assert_eq!(iids(&second), iids(&first));
+}
+
+#[tokio::test]
+async fn memory_cache_respects_capacity() {
+    use super::cache::CachedResponse;
+
+    let cache = ResponseCache::with_capacity(None, 2);
+    for url in ["a", "b", "c"] {
+        let entry = CachedResponse {
+            etag: "\"e\"".into(),
+            body: "[]".into(),
+            next_url: None,
+        };
+        cache.put(url, entry).await;
+    }
+    assert_eq!(cache.memory_len().await, 2);
+    assert!(cache.get("c").await.is_some());
+}
+
+#[tokio::test]
+async fn expired_responses_are_pruned_from_sqlite() {
+    use super::cache::{PERSISTENT_TTL, prune_expired};
+
+    let db = Database::open_in_memory().await.unwrap();
+    db.put_gitlab_cache("u", "\"e\"", "[]", None).await.unwrap();
+    let now = betcode_core::db::unix_timestamp();
+
+    assert_eq!(prune_expired(&db, now).await.unwrap(), 0);
+    assert!(db.get_gitlab_cache("u").await.unwrap().is_some());
+
+    #[allow(clippy::cast_possible_wrap)]
+    let later = now + PERSISTENT_TTL.as_secs() as i64 + 1;
+    assert_eq!(prune_expired(&db, later).await.unwrap(), 1);
+    assert!(db.get_gitlab_cache("u").await.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn get_one_maps_not_found() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/api/v4/projects/group%2Fproject/issues/9"))
+        .respond_with(ResponseTemplate::new(404))
+        .mount(&server)
+        .await;
+
+    let err = mock_client(&server)
+        .get_issue("group/project", 9)
+        .await
+        .unwrap_err();
+    assert!(matches!(err, GitLabError::Api { status: 404, .. }));
+}
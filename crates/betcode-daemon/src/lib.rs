@@ -9,6 +9,7 @@
 
 pub mod commands;
 pub mod completion;
+pub mod forge;
 pub mod gitlab;
 pub mod orchestration;
 pub mod permission;
@@ -10,9 +10,10 @@ use std::sync::Arc;
 use clap::Parser;
 use tracing::info;
 
+use betcode_daemon::orchestration::manager::PermissionFallback;
 use betcode_daemon::server::{GrpcServer, ServerConfig};
 use betcode_daemon::storage::Database;
-use betcode_daemon::subprocess::SubprocessManager;
+use betcode_daemon::subprocess::{SandboxPolicy, SubprocessManager};
 use betcode_daemon::tunnel::{TunnelClient, TunnelConfig};
 
 #[derive(Parser, Debug)]
@@ -74,6 +75,10 @@ struct Args {
     #[arg(long, env = "BETCODE_WORKTREE_DIR")]
     worktree_dir: Option<PathBuf>,
 
+    /// Days without activity after which `worktree gc` considers a worktree stale
+    #[arg(long, default_value_t = 30, env = "BETCODE_WORKTREE_MAX_IDLE_DAYS")]
+    worktree_max_idle_days: u64,
+
     /// Path to the `claude` CLI binary
     #[arg(long, default_value = "claude", env = "BETCODE_CLAUDE_BIN")]
     claude_bin: PathBuf,
@@ -91,6 +96,28 @@ struct Args {
     )]
     permission_strategy: String,
 
+    #[command(flatten)]
+    sandbox: SandboxArgs,
+
+    /// Mark subagents interrupted by a restart failed instead of resuming them.
+    #[arg(long, env = "BETCODE_NO_RESUME_SUBAGENTS")]
+    no_resume_subagents: bool,
+
+    /// What happens to subagent permission requests while no client watches:
+    /// "deny" them or "wait" until a client answers or they expire.
+    #[arg(
+        long,
+        default_value = "deny",
+        env = "BETCODE_SUBAGENT_PERMISSION_FALLBACK",
+        value_parser = ["deny", "wait"]
+    )]
+    subagent_permission_fallback: String,
+
+    /// Send Wake-on-LAN packets on the local network for other machines of
+    /// the same user when the relay asks (for always-on machines).
+    #[arg(long, env = "BETCODE_ALLOW_WAKE")]
+    allow_wake: bool,
+
     /// Seconds to wait for graceful subprocess shutdown before SIGKILL.
     #[arg(long, default_value_t = 5, env = "BETCODE_TERMINATE_TIMEOUT")]
     terminate_timeout: u64,
@@ -106,6 +133,65 @@ struct Args {
     metrics_endpoint: Option<String>,
 }
 
+/// Sandbox flags for Claude subprocesses.
+#[derive(clap::Args, Debug)]
+struct SandboxArgs {
+    /// Default sandbox for Claude subprocesses; repositories can override it.
+    #[arg(
+        long = "sandbox",
+        default_value = "none",
+        env = "BETCODE_SANDBOX",
+        value_parser = ["none", "bubblewrap", "podman"]
+    )]
+    backend: String,
+
+    /// Sandbox network: "host", "none", or a podman network name.
+    #[arg(
+        long = "sandbox-network",
+        default_value = "host",
+        env = "BETCODE_SANDBOX_NETWORK"
+    )]
+    network: String,
+
+    /// Container image for the podman sandbox (must provide `claude`).
+    #[arg(long = "sandbox-image", env = "BETCODE_SANDBOX_IMAGE")]
+    image: Option<String>,
+
+    /// Extra host paths visible read-only inside the sandbox.
+    #[arg(
+        long = "sandbox-ro-path",
+        env = "BETCODE_SANDBOX_RO_PATHS",
+        value_delimiter = ','
+    )]
+    ro_path: Vec<PathBuf>,
+
+    /// Extra host paths visible read-write inside the sandbox.
+    #[arg(
+        long = "sandbox-rw-path",
+        env = "BETCODE_SANDBOX_RW_PATHS",
+        value_delimiter = ','
+    )]
+    rw_path: Vec<PathBuf>,
+
+    /// Skip Claude's permission prompts for sandboxed sessions.
+    #[arg(long = "sandbox-auto-approve", env = "BETCODE_SANDBOX_AUTO_APPROVE")]
+    auto_approve: bool,
+}
+
+impl SandboxArgs {
+    /// The default sandbox policy these flags describe.
+    fn policy(&self) -> anyhow::Result<SandboxPolicy> {
+        Ok(SandboxPolicy {
+            backend: self.backend.parse()?,
+            network: self.network.parse()?,
+            read_only_paths: self.ro_path.clone(),
+            read_write_paths: self.rw_path.clone(),
+            image: self.image.clone(),
+            auto_approve: self.auto_approve,
+        })
+    }
+}
+
 // jscpd:ignore-start -- binary bootstrap is inherently similar across daemons
 #[tokio::main]
 #[allow(clippy::too_many_lines)]
@@ -150,13 +236,20 @@ async fn main() -> anyhow::Result<()> {
         _ => betcode_daemon::subprocess::PermissionStrategy::PromptToolStdio,
     };
 
+    let sandbox = args.sandbox.policy()?;
+    sandbox.validate()?;
+    if sandbox.is_enabled() {
+        info!(backend = sandbox.backend.as_str(), network = %sandbox.network, "Sandboxing Claude subprocesses by default");
+    }
+
     // Create subprocess manager
     let subprocess_manager = SubprocessManager::with_options(
         args.max_processes,
         args.claude_bin.clone(),
         default_permission_strategy,
         args.terminate_timeout,
-    );
+    )
+    .with_sandbox(sandbox);
 
     // Daemon-level shutdown channel (triggered by exit-daemon command or Ctrl+C)
     let (shutdown_tx, shutdown_rx) = tokio::sync::watch::channel(false);
@@ -174,7 +267,13 @@ async fn main() -> anyhow::Result<()> {
     // Create and start gRPC server
     let config = ServerConfig::tcp(args.addr)
         .with_max_sessions(args.max_sessions)
-        .with_max_processes(args.max_processes);
+        .with_max_processes(args.max_processes)
+        .with_worktree_max_idle_secs(args.worktree_max_idle_days.saturating_mul(24 * 60 * 60))
+        .with_resume_subagents(!args.no_resume_subagents)
+        .with_subagent_permission_fallback(match args.subagent_permission_fallback.as_str() {
+            "wait" => PermissionFallback::Wait,
+            _ => PermissionFallback::Deny,
+        });
     let server = GrpcServer::new(
         config,
         db,
@@ -206,6 +305,7 @@ async fn main() -> anyhow::Result<()> {
             .clone_from(&args.relay_custom_ca_cert);
         tunnel_config.client_cert_path.clone_from(&args.client_cert);
         tunnel_config.client_key_path.clone_from(&args.client_key);
+        tunnel_config.allow_wake = args.allow_wake;
 
         info!(
             relay_url = %relay_url,
@@ -224,8 +324,8 @@ async fn main() -> anyhow::Result<()> {
         tunnel_client.set_worktree_service(Arc::new(server.worktree_service_impl()));
         tunnel_client.set_config_service(Arc::new(server.config_service_impl()));
         tunnel_client.set_version_service(Arc::new(server.version_service_impl()));
-        if let Some(gitlab_svc) = GrpcServer::gitlab_service_impl_from_env() {
-            info!("GitLab service configured for tunnel");
+        if let Some(gitlab_svc) = server.gitlab_service_impl_from_env() {
+            info!("Forge service configured for tunnel");
             tunnel_client.set_gitlab_service(Arc::new(gitlab_svc));
         }
         Some(tokio::spawn(async move {
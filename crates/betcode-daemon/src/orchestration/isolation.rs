@@ -0,0 +1,349 @@
+//! Per-step worktrees for isolated orchestration steps.
+//!
+//! An isolated step runs in a fresh worktree on its own branch, started from
+//! the orchestration's integration branch. When the step succeeds its changes
+//! are committed and merged (or cherry-picked) into the integration branch,
+//! so downstream steps start from the combined result. A step that fails or
+//! conflicts keeps its worktree for inspection.
+
+use std::path::Path;
+
+use betcode_proto::v1::StepMergeStrategy;
+use tracing::info;
+
+use crate::storage::{Database, DatabaseError, Worktree};
+use crate::worktree::{
+    GitRepo, IntegrateStrategy, SetupStart, SetupStatus, WorktreeError, WorktreeManager,
+};
+
+/// Errors from preparing or merging a step worktree.
+#[derive(Debug, thiserror::Error)]
+pub enum IsolationError {
+    #[error("Worktree error: {0}")]
+    Worktree(#[from] WorktreeError),
+
+    #[error("Database error: {0}")]
+    Database(#[from] DatabaseError),
+
+    #[error("Merging {branch} into {target} conflicts in: {}", files.join(", "))]
+    Conflict {
+        branch: String,
+        target: String,
+        files: Vec<String>,
+    },
+
+    #[error("Merging {branch} into {target} failed: {message}")]
+    MergeFailed {
+        branch: String,
+        target: String,
+        message: String,
+    },
+}
+
+/// Branch collecting the results of an orchestration's isolated steps.
+pub fn integration_branch(orchestration_id: &str) -> String {
+    format!("betcode/{orchestration_id}")
+}
+
+/// Branch of the worktree one subagent of an isolated step runs in.
+pub fn step_branch(subagent_id: &str) -> String {
+    format!("betcode/{subagent_id}")
+}
+
+/// Registered repository a step's working directory belongs to: the
+/// repository itself or one of its worktrees.
+pub async fn step_repo(
+    db: &Database,
+    working_directory: &str,
+) -> Result<Option<GitRepo>, DatabaseError> {
+    if working_directory.is_empty() {
+        return Ok(None);
+    }
+    let dir = Path::new(working_directory);
+    for repo in db.list_git_repos().await? {
+        if Path::new(&repo.repo_path) == dir {
+            return Ok(Some(GitRepo::from(repo)));
+        }
+    }
+    let worktree = db
+        .list_worktrees(None)
+        .await?
+        .into_iter()
+        .find(|wt| Path::new(&wt.path) == dir);
+    match worktree {
+        Some(wt) => Ok(Some(GitRepo::from(db.get_git_repo(&wt.repo_id).await?))),
+        None => Ok(None),
+    }
+}
+
+/// Look up a worktree of `repo` by name.
+async fn find_worktree(
+    worktrees: &WorktreeManager,
+    repo: &GitRepo,
+    name: &str,
+) -> Result<Option<Worktree>, WorktreeError> {
+    Ok(worktrees
+        .list(Some(&repo.id))
+        .await?
+        .into_iter()
+        .map(|info| info.worktree)
+        .find(|wt| wt.name == name))
+}
+
+/// The orchestration's integration worktree in `repo`, created on first use
+/// from the repository's `HEAD`.
+async fn integration_worktree(
+    worktrees: &WorktreeManager,
+    repo: &GitRepo,
+    orchestration_id: &str,
+) -> Result<Worktree, WorktreeError> {
+    let name = format!("{orchestration_id}-integration");
+    if let Some(wt) = find_worktree(worktrees, repo, &name).await? {
+        return Ok(wt);
+    }
+    let branch = integration_branch(orchestration_id);
+    let wt = worktrees
+        .create_deferred(&name, repo, &branch, None)
+        .await?;
+    info!(orchestration_id, branch, path = %wt.path, "Created integration worktree");
+    Ok(wt)
+}
+
+/// The worktree a subagent of an isolated step runs in.
+///
+/// A new worktree starts from the integration branch and runs the
+/// repository's setup pipeline; an existing one (a subagent resumed after a
+/// restart) is reused as is.
+pub async fn step_worktree(
+    worktrees: &WorktreeManager,
+    repo: &GitRepo,
+    orchestration_id: &str,
+    subagent_id: &str,
+) -> Result<Worktree, WorktreeError> {
+    if let Some(wt) = find_worktree(worktrees, repo, subagent_id).await? {
+        return Ok(wt);
+    }
+    let integration = integration_worktree(worktrees, repo, orchestration_id).await?;
+    let wt = worktrees
+        .create_deferred(subagent_id, repo, &step_branch(subagent_id), None)
+        .await?;
+    if let Err(e) = worktrees.reset_to(&wt.id, &integration.branch).await {
+        let _ = worktrees.remove(&wt.id).await;
+        return Err(e);
+    }
+    if wt.setup_status != SetupStatus::None.as_str() {
+        let status = worktrees
+            .run_setup(&wt.id, SetupStart::Beginning, None)
+            .await?;
+        if status == SetupStatus::Failed {
+            return Err(WorktreeError::SetupFailed(format!(
+                "setup pipeline of worktree {subagent_id} failed"
+            )));
+        }
+    }
+    Ok(worktrees.get(&wt.id).await?.worktree)
+}
+
+/// Commit a subagent's changes and bring them into the integration branch.
+///
+/// The step worktree is removed once merged; its branch is kept.
+pub async fn merge_step_worktree(
+    worktrees: &WorktreeManager,
+    repo: &GitRepo,
+    orchestration_id: &str,
+    subagent_id: &str,
+    message: &str,
+    strategy: StepMergeStrategy,
+) -> Result<(), IsolationError> {
+    let Some(wt) = find_worktree(worktrees, repo, subagent_id).await? else {
+        return Err(WorktreeError::NotFound(format!("step worktree {subagent_id}")).into());
+    };
+    worktrees.commit_all(&wt.id, message).await?;
+
+    let integration = integration_worktree(worktrees, repo, orchestration_id).await?;
+    let strategy = match strategy {
+        StepMergeStrategy::CherryPick => IntegrateStrategy::CherryPick,
+        StepMergeStrategy::Unspecified => IntegrateStrategy::Merge,
+    };
+    let outcome = worktrees
+        .integrate(&integration.id, &wt.branch, strategy)
+        .await?;
+    if !outcome.success {
+        return Err(if outcome.conflicts.is_empty() {
+            IsolationError::MergeFailed {
+                branch: wt.branch,
+                target: integration.branch,
+                message: outcome.message,
+            }
+        } else {
+            IsolationError::Conflict {
+                branch: wt.branch,
+                target: integration.branch,
+                files: outcome.conflicts,
+            }
+        });
+    }
+
+    worktrees.remove(&wt.id).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+#[allow(clippy::panic, clippy::unwrap_used, clippy::expect_used)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn run_git(dir: &Path, args: &[&str]) -> String {
+        let output = std::process::Command::new("git")
+            .args(args)
+            .current_dir(dir)
+            .env_remove("GIT_DIR")
+            .env_remove("GIT_INDEX_FILE")
+            .env_remove("GIT_WORK_TREE")
+            .output()
+            .unwrap();
+        assert!(
+            output.status.success(),
+            "git {args:?} failed: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        String::from_utf8_lossy(&output.stdout).trim().to_string()
+    }
+
+    struct Fixture {
+        db: Database,
+        worktrees: WorktreeManager,
+        repo: GitRepo,
+        _repo_dir: tempfile::TempDir,
+        _wt_base: tempfile::TempDir,
+    }
+
+    /// A registered repo on `main` with one commit.
+    async fn fixture() -> Fixture {
+        let repo_dir = tempfile::tempdir().unwrap();
+        let wt_base = tempfile::tempdir().unwrap();
+        let path = repo_dir.path();
+        run_git(path, &["init", "-b", "main"]);
+        run_git(path, &["config", "user.name", "Test"]);
+        run_git(path, &["config", "user.email", "test@example.com"]);
+        std::fs::write(path.join("a.txt"), "base\n").unwrap();
+        run_git(path, &["add", "a.txt"]);
+        run_git(path, &["commit", "-m", "init"]);
+
+        let db = Database::open_in_memory().await.unwrap();
+        db.create_git_repo(
+            "r1",
+            &path.to_string_lossy(),
+            &crate::storage::GitRepoParams {
+                name: "testrepo",
+                worktree_mode: "global",
+                local_subfolder: ".worktree",
+                custom_path: None,
+                setup_script: None,
+                auto_gitignore: true,
+            },
+        )
+        .await
+        .unwrap();
+        let repo = GitRepo::from(db.get_git_repo("r1").await.unwrap());
+        Fixture {
+            worktrees: WorktreeManager::new(db.clone(), wt_base.path().to_path_buf()),
+            db,
+            repo,
+            _repo_dir: repo_dir,
+            _wt_base: wt_base,
+        }
+    }
+
+    #[tokio::test]
+    async fn step_repo_matches_repository_and_worktree_paths() {
+        let f = fixture().await;
+        let repo_path = f.repo.repo_path.to_string_lossy().into_owned();
+        let found = step_repo(&f.db, &repo_path).await.unwrap().unwrap();
+        assert_eq!(found.id, "r1");
+
+        let wt = step_worktree(&f.worktrees, &f.repo, "o1", "o1-a")
+            .await
+            .unwrap();
+        let found = step_repo(&f.db, &wt.path).await.unwrap().unwrap();
+        assert_eq!(found.id, "r1");
+        assert!(step_repo(&f.db, "/nowhere").await.unwrap().is_none());
+        assert!(step_repo(&f.db, "").await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn steps_start_from_merged_results() {
+        let f = fixture().await;
+        let a = step_worktree(&f.worktrees, &f.repo, "o1", "o1-a")
+            .await
+            .unwrap();
+        assert_eq!(a.branch, "betcode/o1-a");
+        std::fs::write(PathBuf::from(&a.path).join("a-out.txt"), "a\n").unwrap();
+        merge_step_worktree(
+            &f.worktrees,
+            &f.repo,
+            "o1",
+            "o1-a",
+            "step a",
+            StepMergeStrategy::Unspecified,
+        )
+        .await
+        .unwrap();
+        assert!(!Path::new(&a.path).exists());
+
+        // A later step sees the merged output
+        let b = step_worktree(&f.worktrees, &f.repo, "o1", "o1-b")
+            .await
+            .unwrap();
+        assert!(PathBuf::from(&b.path).join("a-out.txt").exists());
+        // Asking again reuses the worktree
+        let again = step_worktree(&f.worktrees, &f.repo, "o1", "o1-b")
+            .await
+            .unwrap();
+        assert_eq!(again.id, b.id);
+    }
+
+    #[tokio::test]
+    async fn conflicting_steps_report_files() {
+        let f = fixture().await;
+        let a = step_worktree(&f.worktrees, &f.repo, "o1", "o1-a")
+            .await
+            .unwrap();
+        let b = step_worktree(&f.worktrees, &f.repo, "o1", "o1-b")
+            .await
+            .unwrap();
+        std::fs::write(PathBuf::from(&a.path).join("a.txt"), "from a\n").unwrap();
+        std::fs::write(PathBuf::from(&b.path).join("a.txt"), "from b\n").unwrap();
+
+        merge_step_worktree(
+            &f.worktrees,
+            &f.repo,
+            "o1",
+            "o1-a",
+            "edit",
+            StepMergeStrategy::Unspecified,
+        )
+        .await
+        .unwrap();
+        let err = merge_step_worktree(
+            &f.worktrees,
+            &f.repo,
+            "o1",
+            "o1-b",
+            "edit",
+            StepMergeStrategy::CherryPick,
+        )
+        .await
+        .unwrap_err();
+        match err {
+            IsolationError::Conflict { files, target, .. } => {
+                assert_eq!(files, vec!["a.txt".to_string()]);
+                assert_eq!(target, "betcode/o1");
+            }
+            other => panic!("Expected Conflict, got {other:?}"),
+        }
+        // The conflicting worktree is kept for inspection
+        assert!(Path::new(&b.path).exists());
+    }
+}
@@ -5,10 +5,10 @@
 //! - monitors subprocess exit and updates DB status
 //! - enforces per-subagent timeouts (SIGTERM -> 5 s grace -> SIGKILL)
 //! - supports cancellation of running subagents
+//! - routes subagent permission requests to watching clients
 //! - manages orchestration lifecycles
 
 use std::collections::HashMap;
-use std::fmt::Write as _;
 use std::path::PathBuf;
 use std::process::Stdio;
 use std::sync::Arc;
@@ -16,18 +16,23 @@ use std::sync::Arc;
 use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
 use tokio::process::Command;
 use tokio::sync::{Notify, RwLock, broadcast, mpsc};
-use tracing::{debug, error, info, warn};
+use tracing::{error, info, warn};
 
+use betcode_core::permissions::PermissionEngine;
+use betcode_core::step_template::truncate_output;
 use betcode_proto::v1::{
-    OrchestrationCompleted, OrchestrationEvent, OrchestrationFailed, OrchestrationStrategy,
-    StepCompleted, StepFailed, StepStarted, SubagentCancelled, SubagentCompleted, SubagentEvent,
-    SubagentFailed, SubagentOutput, SubagentPermissionRequest, SubagentStarted, SubagentToolUse,
+    OrchestrationEvent, SubagentCancelled, SubagentCompleted, SubagentEvent, SubagentFailed,
+    SubagentPermissionRequest, SubagentStarted,
 };
 
+use crate::permission::{DaemonPermissionEngine, PendingConfig};
+use crate::session::{NotificationIntent, NotificationKind};
 use crate::storage::Database;
+use crate::worktree::WorktreeManager;
 
+use super::output::{final_output_text, init_session_id, parse_stdout_line};
+use super::permissions::{permission_response_line, route_permission_request};
 use super::pool::{PoolEntry, SubprocessPool};
-use super::scheduler::DagScheduler;
 
 /// Default timeout per subagent in seconds (10 minutes).
 const DEFAULT_TIMEOUT_SECS: u64 = 600;
@@ -35,6 +40,9 @@ const DEFAULT_TIMEOUT_SECS: u64 = 600;
 /// Grace period after SIGTERM before SIGKILL.
 const GRACE_PERIOD_SECS: u64 = 5;
 
+/// Longest subagent result kept as `result_summary`, in characters.
+pub(super) const MAX_RESULT_CHARS: usize = 64 * 1024;
+
 /// Configuration for spawning a subagent.
 #[derive(Debug, Clone)]
 pub struct SubagentConfig {
@@ -56,25 +64,53 @@ pub struct SubagentConfig {
     pub auto_approve: bool,
     /// Timeout in seconds (0 = default).
     pub timeout_secs: u64,
+    /// Claude session to continue; the subagent record already exists.
+    pub resume_session: Option<String>,
+}
+
+/// What happens to a subagent permission request while no client watches
+/// the subagent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PermissionFallback {
+    /// Deny the tool call, as Claude does for tools it was not allowed.
+    #[default]
+    Deny,
+    /// Keep the request pending until a client answers it or it expires.
+    Wait,
 }
 
 /// Per-orchestration state for event broadcasting and loop notification.
-struct OrchestrationState {
+pub(super) struct OrchestrationState {
     /// Broadcast sender for orchestration events (for `WatchOrchestration` subscribers).
-    event_tx: broadcast::Sender<OrchestrationEvent>,
+    pub(super) event_tx: broadcast::Sender<OrchestrationEvent>,
     /// Notify handle to wake the orchestration loop when a subagent finishes.
-    step_notify: Arc<Notify>,
+    pub(super) step_notify: Arc<Notify>,
 }
 
 /// Handle to track a running subagent for event broadcasting.
-struct RunningSubagent {
+pub(super) struct RunningSubagent {
     /// Subscribers watching this subagent.
-    event_txs: Vec<mpsc::Sender<SubagentEvent>>,
+    pub(super) event_txs: Vec<mpsc::Sender<SubagentEvent>>,
     /// Process ID for signaling.
     #[cfg(unix)]
-    pid: Option<u32>,
+    pub(super) pid: Option<u32>,
     /// Whether this subagent has auto-approve enabled.
-    auto_approve: bool,
+    pub(super) auto_approve: bool,
+    /// Tools answered without asking while auto-approve is enabled.
+    pub(super) allowed_tools: Vec<String>,
+    /// Parent session, whose permission grants apply to the subagent.
+    pub(super) parent_session_id: String,
+    /// Permission requests waiting for a client, keyed by request ID.
+    pub(super) pending_permissions: HashMap<String, SubagentPermissionRequest>,
+    /// Whether a client was watching at the last check.
+    pub(super) watched: bool,
+}
+
+impl RunningSubagent {
+    /// Whether any subscriber is still connected.
+    pub(super) fn has_watchers(&self) -> bool {
+        self.event_txs.iter().any(|tx| !tx.is_closed())
+    }
 }
 
 /// Errors from the subagent manager.
@@ -102,21 +138,26 @@ pub enum ManagerError {
     Validation { message: String },
 }
 
-/// Broadcast channel buffer size for orchestration events.
-const ORCHESTRATION_BROADCAST_CAPACITY: usize = 256;
-
 /// High-level subagent lifecycle manager.
 pub struct SubagentManager {
-    pool: Arc<SubprocessPool>,
-    db: Database,
+    pub(super) pool: Arc<SubprocessPool>,
+    pub(super) db: Database,
     /// Path to the `claude` binary.
-    claude_bin: PathBuf,
+    pub(super) claude_bin: PathBuf,
     /// Active subagents keyed by subagent ID.
-    running: Arc<RwLock<HashMap<String, RunningSubagent>>>,
+    pub(super) running: Arc<RwLock<HashMap<String, RunningSubagent>>>,
     /// Active orchestrations keyed by orchestration ID.
-    orchestrations: Arc<RwLock<HashMap<String, OrchestrationState>>>,
+    pub(super) orchestrations: Arc<RwLock<HashMap<String, OrchestrationState>>>,
     /// Maps `subagent_id` to `orchestration_id` for notifying the right orchestration.
-    subagent_to_orchestration: Arc<RwLock<HashMap<String, String>>>,
+    pub(super) subagent_to_orchestration: Arc<RwLock<HashMap<String, String>>>,
+    /// Creates and merges the worktrees of isolated steps.
+    pub(super) worktrees: Option<WorktreeManager>,
+    /// Decides subagent permission requests and tracks the pending ones.
+    pub(super) permissions: Arc<DaemonPermissionEngine>,
+    /// Handling of permission requests nobody is watching for.
+    pub(super) permission_fallback: PermissionFallback,
+    /// Where to raise notification intents when a subagent ends.
+    pub(super) notifications: Option<broadcast::Sender<NotificationIntent>>,
 }
 
 impl SubagentManager {
@@ -124,14 +165,54 @@ impl SubagentManager {
     pub fn new(pool: Arc<SubprocessPool>, db: Database, claude_bin: PathBuf) -> Self {
         Self {
             pool,
-            db,
             claude_bin,
             running: Arc::new(RwLock::new(HashMap::new())),
             orchestrations: Arc::new(RwLock::new(HashMap::new())),
             subagent_to_orchestration: Arc::new(RwLock::new(HashMap::new())),
+            worktrees: None,
+            permissions: Arc::new(DaemonPermissionEngine::with_database(
+                PermissionEngine::new(),
+                PendingConfig::default(),
+                db.clone(),
+            )),
+            permission_fallback: PermissionFallback::default(),
+            notifications: None,
+            db,
         }
     }
 
+    /// Enable isolated orchestration steps, which need worktrees.
+    #[must_use]
+    pub fn with_worktrees(mut self, worktrees: WorktreeManager) -> Self {
+        self.worktrees = Some(worktrees);
+        self
+    }
+
+    /// Use `engine` to decide subagent permission requests.
+    #[must_use]
+    pub fn with_permission_engine(mut self, engine: Arc<DaemonPermissionEngine>) -> Self {
+        self.permissions = engine;
+        self
+    }
+
+    /// Set what happens to permission requests nobody is watching for.
+    #[must_use]
+    pub const fn with_permission_fallback(mut self, fallback: PermissionFallback) -> Self {
+        self.permission_fallback = fallback;
+        self
+    }
+
+    /// Raise a notification intent on `notifications` when a subagent
+    /// completes or fails.
+    #[must_use]
+    pub fn with_notifications(
+        mut self,
+        notifications: broadcast::Sender<NotificationIntent>,
+    ) -> Self {
+        self.notifications = Some(notifications);
+        self
+    }
+
     /// Spawn a new subagent subprocess.
     ///
     /// Returns the subagent ID on success.
@@ -156,19 +237,22 @@ impl SubagentManager {
         let allowed_tools_json =
             serde_json::to_string(&config.allowed_tools).unwrap_or_else(|_| "[]".to_string());
 
-        // Create DB record
-        self.db
-            .create_subagent(
-                &subagent_id,
-                &config.parent_session_id,
-                &config.prompt,
-                config.model.as_deref(),
-                i64::from(config.max_turns),
-                config.auto_approve,
-                &allowed_tools_json,
-                Some(config.working_directory.to_string_lossy().as_ref()),
-            )
-            .await?;
+        // Create DB record (a resumed subagent keeps its existing one)
+        if config.resume_session.is_none() {
+            self.db
+                .create_subagent(
+                    &subagent_id,
+                    &config.parent_session_id,
+                    &config.prompt,
+                    config.model.as_deref(),
+                    i64::from(config.max_turns),
+                    config.auto_approve,
+                    &allowed_tools_json,
+                    Some(config.working_directory.to_string_lossy().as_ref()),
+                    i64::try_from(config.timeout_secs).unwrap_or(i64::MAX),
+                )
+                .await?;
+        }
 
         // Build claude command
         let working_dir = if config.working_directory.as_os_str().is_empty()
@@ -207,9 +291,12 @@ impl SubagentManager {
             cmd.env("ANTHROPIC_API_KEY", &key);
         }
 
-        // Permission handling
-        if config.auto_approve && !config.allowed_tools.is_empty() {
-            cmd.arg("--allowedTools").args(&config.allowed_tools);
+        // Permission handling: every prompt comes back as a control_request,
+        // answered by auto-approve, the permission engine or a client
+        cmd.arg("--permission-prompt-tool").arg("stdio");
+
+        if let Some(ref session) = config.resume_session {
+            cmd.arg("--resume").arg(session);
         }
 
         // Prompt
@@ -231,6 +318,7 @@ impl SubagentManager {
             subagent_id = %subagent_id,
             working_dir = %working_dir.display(),
             auto_approve = config.auto_approve,
+            resume_session = ?config.resume_session,
             "Spawning subagent subprocess"
         );
 
@@ -282,6 +370,10 @@ impl SubagentManager {
                     #[cfg(unix)]
                     pid,
                     auto_approve: config.auto_approve,
+                    allowed_tools: config.allowed_tools.clone(),
+                    parent_session_id: config.parent_session_id.clone(),
+                    pending_permissions: HashMap::new(),
+                    watched: false,
                 },
             );
         }
@@ -300,6 +392,10 @@ impl SubagentManager {
         let db = self.db.clone();
         let subagent_to_orch = Arc::clone(&self.subagent_to_orchestration);
         let orchestrations_map = Arc::clone(&self.orchestrations);
+        let permissions = Arc::clone(&self.permissions);
+        let fallback = self.permission_fallback;
+        let notifications = self.notifications.clone();
+        let parent_session_id = config.parent_session_id.clone();
 
         let timeout = if config.timeout_secs == 0 {
             DEFAULT_TIMEOUT_SECS
@@ -328,23 +424,61 @@ impl SubagentManager {
             )
             .await;
 
-            // Read stdout lines and broadcast events
-            if let Some(stdout) = stdout {
+            // Read stdout lines and broadcast events, keeping the final
+            // assistant text as the subagent's result
+            let stdout_task = stdout.map(|stdout| {
                 let reader = BufReader::new(stdout);
                 let mut lines = reader.lines();
                 let sa_id_stdout = sa_id.clone();
                 let running_map_stdout = Arc::clone(&running_map);
+                let db_stdout = db.clone();
+                let stdin_stdout = stdin_tx.clone();
+                let permissions_stdout = Arc::clone(&permissions);
 
                 tokio::spawn(async move {
+                    let mut final_text = String::new();
                     while let Ok(Some(line)) = lines.next_line().await {
+                        if let Some(text) = final_output_text(&line) {
+                            final_text = text;
+                        }
+                        // Remember Claude's session so the subagent can be resumed
+                        if let Some(session) = init_session_id(&line)
+                            && let Err(e) = db_stdout
+                                .set_subagent_claude_session(&sa_id_stdout, &session)
+                                .await
+                        {
+                            warn!(subagent_id = %sa_id_stdout, error = %e, "Failed to record Claude session");
+                        }
                         // Parse NDJSON line and convert to subagent events
                         let events = parse_stdout_line(&sa_id_stdout, &line);
                         for event in events {
+                            // Permission requests only reach clients when
+                            // nothing can answer them here
+                            if let Some(betcode_proto::v1::subagent_event::Event::PermissionRequest(
+                                ref request,
+                            )) = event.event
+                                && let Some((granted, reason)) = route_permission_request(
+                                    &permissions_stdout,
+                                    &running_map_stdout,
+                                    fallback,
+                                    &sa_id_stdout,
+                                    request,
+                                )
+                                .await
+                            {
+                                let line =
+                                    permission_response_line(request, granted, false, reason);
+                                if stdin_stdout.send(line).await.is_err() {
+                                    warn!(subagent_id = %sa_id_stdout, "Subagent stdin closed before permission response");
+                                }
+                                continue;
+                            }
                             broadcast_event(&running_map_stdout, &sa_id_stdout, event).await;
                         }
                     }
-                });
-            }
+                    final_text
+                })
+            });
 
             // Read stderr for diagnostics
             if let Some(stderr) = stderr {
@@ -362,15 +496,28 @@ impl SubagentManager {
             let exit_result =
                 tokio::time::timeout(std::time::Duration::from_secs(timeout), child.wait()).await;
 
+            // stdout closes with the process; don't wait on a lingering grandchild
+            let output = match stdout_task {
+                Some(task) => {
+                    tokio::time::timeout(std::time::Duration::from_secs(GRACE_PERIOD_SECS), task)
+                        .await
+                        .ok()
+                        .and_then(Result::ok)
+                        .unwrap_or_default()
+                }
+                None => String::new(),
+            };
+
             let (status_str, exit_code, summary) = match exit_result {
                 Ok(Ok(status)) => {
                     let code = status.code();
                     if code == Some(0) {
-                        (
-                            "completed",
-                            code.map(i64::from),
-                            Some("Completed successfully".to_string()),
-                        )
+                        let summary = if output.trim().is_empty() {
+                            "Completed successfully".to_string()
+                        } else {
+                            truncate_output(output.trim(), MAX_RESULT_CHARS)
+                        };
+                        ("completed", code.map(i64::from), Some(summary))
                     } else {
                         (
                             "failed",
@@ -434,9 +581,28 @@ impl SubagentManager {
             };
             broadcast_event(&running_map, &sa_id, terminal_event).await;
 
+            // A cancellation was asked for, so only completion and failure notify
+            let kind = match status_str {
+                "completed" => Some(NotificationKind::SubagentCompleted),
+                "cancelled" => None,
+                _ => Some(NotificationKind::SubagentFailed),
+            };
+            if let (Some(tx), Some(kind)) = (&notifications, kind) {
+                let _ = tx.send(NotificationIntent::subagent(
+                    kind,
+                    &parent_session_id,
+                    &sa_id,
+                ));
+            }
+
             // Cleanup
             pool.unregister(&sa_id).await;
-            running_map.write().await.remove(&sa_id);
+            let finished = running_map.write().await.remove(&sa_id);
+            if let Some(sa) = finished {
+                for request_id in sa.pending_permissions.keys() {
+                    permissions.cancel_pending(request_id).await;
+                }
+            }
 
             // Notify orchestration if this subagent belongs to one
             let orch_id = subagent_to_orch.read().await.get(&sa_id).cloned();
@@ -455,21 +621,43 @@ impl SubagentManager {
 
     /// Subscribe to a subagent's event stream.
     ///
-    /// Returns a receiver for `SubagentEvent` messages.
-    #[allow(clippy::significant_drop_tightening)]
+    /// Returns a receiver for `SubagentEvent` messages. Permission requests
+    /// still waiting for an answer are replayed to the new subscriber.
     pub async fn subscribe(
         &self,
         subagent_id: &str,
     ) -> Result<mpsc::Receiver<SubagentEvent>, ManagerError> {
-        let mut running = self.running.write().await;
-        let entry = running
+        let (tx, rx) = mpsc::channel(128);
+        // The write guard is a temporary and is released at the end of the statement
+        let (pending, newly_watched) = self
+            .running
+            .write()
+            .await
             .get_mut(subagent_id)
+            .map(|entry| {
+                entry.event_txs.push(tx.clone());
+                let pending: Vec<SubagentPermissionRequest> =
+                    entry.pending_permissions.values().cloned().collect();
+                (pending, !std::mem::replace(&mut entry.watched, true))
+            })
             .ok_or_else(|| ManagerError::NotFound {
                 id: subagent_id.to_string(),
             })?;
 
-        let (tx, rx) = mpsc::channel(128);
-        entry.event_txs.push(tx);
+        if newly_watched {
+            self.permissions
+                .update_client_status(subagent_id, true)
+                .await;
+        }
+        for request in pending {
+            let _ = tx.try_send(SubagentEvent {
+                subagent_id: subagent_id.to_string(),
+                timestamp: Some(now_timestamp()),
+                event: Some(betcode_proto::v1::subagent_event::Event::PermissionRequest(
+                    request,
+                )),
+            });
+        }
         Ok(rx)
     }
 
@@ -522,17 +710,13 @@ impl SubagentManager {
 
     /// Revoke auto-approve on a running subagent.
     ///
-    /// This doesn't actually change the subprocess's `--allowedTools` flag
-    /// (that's a launch-time argument), but it marks the subagent as no longer
-    /// auto-approved in the database, so future permission queries will be
-    /// forwarded to the parent session.
+    /// Subagents ask for every tool over the control protocol, so later
+    /// requests for its allowed tools go through the permission engine and
+    /// watching clients like any other.
     pub async fn revoke_auto_approve(&self, subagent_id: &str) -> Result<bool, ManagerError> {
         let mut running = self.running.write().await;
         if let Some(sa) = running.get_mut(subagent_id) {
             sa.auto_approve = false;
-            // Note: We can't retroactively change the subprocess's --allowedTools flag.
-            // The revocation is tracked in-memory and in the DB for future permission
-            // requests that are forwarded via the permission bridge.
             Ok(true)
         } else {
             Ok(false)
@@ -553,487 +737,10 @@ impl SubagentManager {
     pub const fn db(&self) -> &Database {
         &self.db
     }
-
-    /// Subscribe to an orchestration's event broadcast channel.
-    ///
-    /// Returns a `broadcast::Receiver` that receives all `OrchestrationEvent`
-    /// messages for the given orchestration.
-    #[allow(clippy::significant_drop_tightening)]
-    pub async fn subscribe_orchestration(
-        &self,
-        orchestration_id: &str,
-    ) -> Result<broadcast::Receiver<OrchestrationEvent>, ManagerError> {
-        let orchestrations = self.orchestrations.read().await;
-        let state = orchestrations.get(orchestration_id).ok_or_else(|| {
-            ManagerError::OrchestrationNotFound {
-                id: orchestration_id.to_string(),
-            }
-        })?;
-        Ok(state.event_tx.subscribe())
-    }
-
-    /// Run an orchestration lifecycle.
-    ///
-    /// This is used by `CreateOrchestration` to kick off the scheduler loop.
-    /// The manager owns the broadcast channel; subscribers connect via
-    /// [`subscribe_orchestration`].
-    #[allow(clippy::too_many_lines)]
-    pub async fn run_orchestration(
-        self: &Arc<Self>,
-        orchestration_id: String,
-        parent_session_id: String,
-        strategy: OrchestrationStrategy,
-        steps: Vec<betcode_proto::v1::OrchestrationStep>,
-    ) -> Result<(), ManagerError> {
-        // Validate and create scheduler
-        let step_ids: Vec<String> = steps.iter().map(|s| s.id.clone()).collect();
-        let step_deps: Vec<Vec<String>> = steps.iter().map(|s| s.depends_on.clone()).collect();
-        // Build adjacency for scheduler
-        let mut dep_map: HashMap<String, Vec<String>> = HashMap::new();
-        for (i, id) in step_ids.iter().enumerate() {
-            dep_map.insert(id.clone(), step_deps[i].clone());
-        }
-
-        let scheduler = DagScheduler::new(step_ids.clone(), dep_map)?;
-
-        // Create DB records for steps
-        let strategy_str = match strategy {
-            OrchestrationStrategy::Sequential => "sequential",
-            OrchestrationStrategy::Dag => "dag",
-            // Parallel and Unspecified both default to parallel
-            OrchestrationStrategy::Parallel | OrchestrationStrategy::Unspecified => "parallel",
-        };
-
-        self.db
-            .create_orchestration(&orchestration_id, &parent_session_id, strategy_str)
-            .await?;
-        self.db
-            .update_orchestration_status(&orchestration_id, "running")
-            .await?;
-
-        for (i, step) in steps.iter().enumerate() {
-            let deps_json =
-                serde_json::to_string(&step.depends_on).unwrap_or_else(|_| "[]".to_string());
-            #[allow(clippy::cast_possible_wrap)]
-            self.db
-                .create_orchestration_step(
-                    &step.id,
-                    &orchestration_id,
-                    i as i64,
-                    &step.prompt,
-                    &deps_json,
-                )
-                .await?;
-        }
-
-        // Create broadcast channel and Notify for this orchestration
-        let (event_tx, _) = broadcast::channel(ORCHESTRATION_BROADCAST_CAPACITY);
-        let step_notify = Arc::new(Notify::new());
-        {
-            let mut orchestrations = self.orchestrations.write().await;
-            orchestrations.insert(
-                orchestration_id.clone(),
-                OrchestrationState {
-                    event_tx: event_tx.clone(),
-                    step_notify: Arc::clone(&step_notify),
-                },
-            );
-        }
-
-        // Build step configs from proto steps (owned, for 'static in tokio::spawn)
-        let step_configs: HashMap<String, betcode_proto::v1::OrchestrationStep> =
-            steps.into_iter().map(|s| (s.id.clone(), s)).collect();
-
-        // Orchestration loop
-        let manager = Arc::clone(self);
-        let orch_id = orchestration_id.clone();
-        let db = self.db.clone();
-        let orchestrations_map = Arc::clone(&self.orchestrations);
-        let subagent_to_orch = Arc::clone(&self.subagent_to_orchestration);
-
-        tokio::spawn(async move {
-            let mut scheduler = scheduler;
-            let mut completed_count: i32 = 0;
-            let mut failed_count: i32 = 0;
-            let mut step_results: HashMap<String, String> = HashMap::new();
-            #[allow(clippy::cast_possible_truncation, clippy::cast_possible_wrap)]
-            let total = step_ids.len() as i32;
-
-            loop {
-                let ready = scheduler.next_ready();
-                if ready.is_empty() && !scheduler.is_complete() && failed_count == 0 {
-                    // Wait for a subagent to complete (event-driven via Notify)
-                    step_notify.notified().await;
-                    continue;
-                }
-
-                if ready.is_empty() && (scheduler.is_complete() || failed_count > 0) {
-                    break;
-                }
-
-                // Spawn ready steps
-                for step_id in &ready {
-                    let Some(step_cfg) = step_configs.get(step_id) else {
-                        continue;
-                    };
-
-                    // Build prompt with context from completed dependencies
-                    let mut full_prompt = String::new();
-                    for dep_id in &step_cfg.depends_on {
-                        if let Some(result) = step_results.get(dep_id) {
-                            let _ =
-                                write!(full_prompt, "[Context from step {dep_id}]: {result}\n\n");
-                        }
-                    }
-                    full_prompt.push_str(&step_cfg.prompt);
-
-                    let sa_id = format!("{orch_id}-{step_id}");
-                    let working_dir = if step_cfg.working_directory.is_empty() {
-                        std::env::current_dir().unwrap_or_default()
-                    } else {
-                        PathBuf::from(&step_cfg.working_directory)
-                    };
-
-                    let sa_config = SubagentConfig {
-                        id: sa_id.clone(),
-                        parent_session_id: parent_session_id.clone(),
-                        prompt: full_prompt,
-                        model: if step_cfg.model.is_empty() {
-                            None
-                        } else {
-                            Some(step_cfg.model.clone())
-                        },
-                        working_directory: working_dir,
-                        allowed_tools: step_cfg.allowed_tools.clone(),
-                        max_turns: step_cfg.max_turns,
-                        auto_approve: step_cfg.auto_approve,
-                        timeout_secs: 0,
-                    };
-
-                    // Register subagent -> orchestration mapping before spawning
-                    subagent_to_orch
-                        .write()
-                        .await
-                        .insert(sa_id.clone(), orch_id.clone());
-
-                    // Update step status
-                    let _ = db
-                        .update_step_status(step_id, "running", Some(&sa_id))
-                        .await;
-
-                    match manager.spawn(sa_config).await {
-                        Ok(_) => {
-                            // Broadcast StepStarted
-                            let _ = event_tx.send(OrchestrationEvent {
-                                orchestration_id: orch_id.clone(),
-                                timestamp: Some(now_timestamp()),
-                                event: Some(
-                                    betcode_proto::v1::orchestration_event::Event::StepStarted(
-                                        StepStarted {
-                                            step_id: step_id.clone(),
-                                            subagent_id: sa_id,
-                                            name: step_cfg.name.clone(),
-                                        },
-                                    ),
-                                ),
-                            });
-
-                            scheduler.mark_running(step_id);
-                        }
-                        Err(e) => {
-                            error!(step_id, error = %e, "Failed to spawn step subagent");
-                            let _ = db.update_step_status(step_id, "failed", None).await;
-
-                            // Remove the mapping since spawn failed
-                            subagent_to_orch
-                                .write()
-                                .await
-                                .remove(&format!("{orch_id}-{step_id}"));
-
-                            // Cascade failure
-                            let blocked = scheduler.mark_failed(step_id);
-                            for bid in &blocked {
-                                let _ = db.update_step_status(bid, "blocked", None).await;
-                            }
-                            failed_count += 1;
-
-                            let _ = event_tx.send(OrchestrationEvent {
-                                orchestration_id: orch_id.clone(),
-                                timestamp: Some(now_timestamp()),
-                                event: Some(
-                                    betcode_proto::v1::orchestration_event::Event::StepFailed(
-                                        StepFailed {
-                                            step_id: step_id.clone(),
-                                            error_message: e.to_string(),
-                                            blocked_steps: blocked,
-                                        },
-                                    ),
-                                ),
-                            });
-                        }
-                    }
-                }
-
-                // If there are still running steps, wait for notification
-                if !scheduler.running_ids().is_empty() {
-                    step_notify.notified().await;
-                }
-
-                // Check all running steps for completion
-                let running_ids = scheduler.running_ids();
-                for step_id in &running_ids {
-                    let sa_id = format!("{orch_id}-{step_id}");
-                    if manager.is_running(&sa_id).await {
-                        continue; // Still running
-                    }
-
-                    // Check DB for result
-                    match db.get_subagent(&sa_id).await {
-                        Ok(sa) if sa.status == "completed" => {
-                            let summary = sa.result_summary.unwrap_or_default();
-                            step_results.insert(step_id.clone(), summary.clone());
-                            let _ = db
-                                .update_step_status(step_id, "completed", Some(&sa_id))
-                                .await;
-
-                            let blocked = scheduler.mark_completed(step_id);
-                            // `blocked` should be empty for completed — it's the freed steps
-                            let _ = blocked; // freed downstream steps are now ready
-                            completed_count += 1;
-
-                            let _ = event_tx.send(OrchestrationEvent {
-                                orchestration_id: orch_id.clone(),
-                                timestamp: Some(now_timestamp()),
-                                event: Some(
-                                    betcode_proto::v1::orchestration_event::Event::StepCompleted(
-                                        StepCompleted {
-                                            step_id: step_id.clone(),
-                                            result_summary: summary,
-                                            completed_count,
-                                            total_count: total,
-                                        },
-                                    ),
-                                ),
-                            });
-                        }
-                        Ok(sa) if sa.status == "failed" || sa.status == "cancelled" => {
-                            let error_msg = sa
-                                .result_summary
-                                .unwrap_or_else(|| "Unknown failure".to_string());
-                            let _ = db.update_step_status(step_id, "failed", Some(&sa_id)).await;
-
-                            let blocked = scheduler.mark_failed(step_id);
-                            for bid in &blocked {
-                                let _ = db.update_step_status(bid, "blocked", None).await;
-                            }
-                            failed_count += 1;
-
-                            let _ = event_tx.send(OrchestrationEvent {
-                                orchestration_id: orch_id.clone(),
-                                timestamp: Some(now_timestamp()),
-                                event: Some(
-                                    betcode_proto::v1::orchestration_event::Event::StepFailed(
-                                        StepFailed {
-                                            step_id: step_id.clone(),
-                                            error_message: error_msg,
-                                            blocked_steps: blocked,
-                                        },
-                                    ),
-                                ),
-                            });
-                        }
-                        _ => {
-                            // Still pending or unknown — check again next iteration
-                        }
-                    }
-                }
-            }
-
-            // Final orchestration status
-            let (final_status, final_event) = if failed_count > 0 {
-                (
-                    "failed",
-                    OrchestrationEvent {
-                        orchestration_id: orch_id.clone(),
-                        timestamp: Some(now_timestamp()),
-                        event: Some(betcode_proto::v1::orchestration_event::Event::Failed(
-                            OrchestrationFailed {
-                                error_message: format!("{failed_count} step(s) failed"),
-                                completed_steps: completed_count,
-                                failed_steps: failed_count,
-                            },
-                        )),
-                    },
-                )
-            } else {
-                (
-                    "completed",
-                    OrchestrationEvent {
-                        orchestration_id: orch_id.clone(),
-                        timestamp: Some(now_timestamp()),
-                        event: Some(betcode_proto::v1::orchestration_event::Event::Completed(
-                            OrchestrationCompleted {
-                                total_steps: total,
-                                succeeded: completed_count,
-                                failed: 0,
-                            },
-                        )),
-                    },
-                )
-            };
-
-            let _ = db.update_orchestration_status(&orch_id, final_status).await;
-            let _ = event_tx.send(final_event);
-
-            // Clean up orchestration state
-            orchestrations_map.write().await.remove(&orch_id);
-
-            info!(
-                orchestration_id = %orch_id,
-                status = final_status,
-                completed = completed_count,
-                failed = failed_count,
-                "Orchestration finished"
-            );
-        });
-
-        Ok(())
-    }
-}
-
-/// Parse an NDJSON stdout line into subagent events.
-#[allow(clippy::too_many_lines)]
-fn parse_stdout_line(subagent_id: &str, line: &str) -> Vec<SubagentEvent> {
-    let Ok(value) = serde_json::from_str::<serde_json::Value>(line) else {
-        debug!(subagent_id, "Non-JSON stdout line: {}", line);
-        return vec![];
-    };
-
-    let msg_type = value.get("type").and_then(|v| v.as_str()).unwrap_or("");
-
-    match msg_type {
-        "assistant" => {
-            // Extract text content from assistant message
-            let text = value
-                .get("message")
-                .and_then(|m| m.get("content"))
-                .and_then(|c| c.as_array())
-                .map(|blocks| {
-                    blocks
-                        .iter()
-                        .filter_map(|b| {
-                            if b.get("type").and_then(|t| t.as_str()) == Some("text") {
-                                b.get("text").and_then(|t| t.as_str()).map(String::from)
-                            } else {
-                                None
-                            }
-                        })
-                        .collect::<String>()
-                })
-                .unwrap_or_default();
-
-            let mut events = Vec::new();
-            if !text.is_empty() {
-                events.push(SubagentEvent {
-                    subagent_id: subagent_id.to_string(),
-                    timestamp: Some(now_timestamp()),
-                    event: Some(betcode_proto::v1::subagent_event::Event::Output(
-                        SubagentOutput {
-                            text,
-                            is_complete: false,
-                        },
-                    )),
-                });
-            }
-
-            // Extract tool_use blocks
-            if let Some(content) = value
-                .get("message")
-                .and_then(|m| m.get("content"))
-                .and_then(|c| c.as_array())
-            {
-                for block in content {
-                    if block.get("type").and_then(|t| t.as_str()) == Some("tool_use") {
-                        let tool_name = block
-                            .get("name")
-                            .and_then(|n| n.as_str())
-                            .unwrap_or("")
-                            .to_string();
-                        let tool_id = block
-                            .get("id")
-                            .and_then(|n| n.as_str())
-                            .unwrap_or("")
-                            .to_string();
-                        events.push(SubagentEvent {
-                            subagent_id: subagent_id.to_string(),
-                            timestamp: Some(now_timestamp()),
-                            event: Some(betcode_proto::v1::subagent_event::Event::ToolUse(
-                                SubagentToolUse {
-                                    tool_id,
-                                    tool_name,
-                                    description: String::new(),
-                                },
-                            )),
-                        });
-                    }
-                }
-            }
-
-            events
-        }
-        "content_block_delta" => {
-            let text = value
-                .get("delta")
-                .and_then(|d| d.get("text"))
-                .and_then(|t| t.as_str())
-                .unwrap_or("");
-
-            if text.is_empty() {
-                return vec![];
-            }
-
-            vec![SubagentEvent {
-                subagent_id: subagent_id.to_string(),
-                timestamp: Some(now_timestamp()),
-                event: Some(betcode_proto::v1::subagent_event::Event::Output(
-                    SubagentOutput {
-                        text: text.to_string(),
-                        is_complete: false,
-                    },
-                )),
-            }]
-        }
-        "control_request" => {
-            let tool_name = value
-                .get("request")
-                .and_then(|r| r.get("tool_name"))
-                .and_then(|t| t.as_str())
-                .unwrap_or("")
-                .to_string();
-            let request_id = value
-                .get("request_id")
-                .and_then(|r| r.as_str())
-                .unwrap_or("")
-                .to_string();
-
-            vec![SubagentEvent {
-                subagent_id: subagent_id.to_string(),
-                timestamp: Some(now_timestamp()),
-                event: Some(betcode_proto::v1::subagent_event::Event::PermissionRequest(
-                    SubagentPermissionRequest {
-                        request_id,
-                        tool_name,
-                        description: String::new(),
-                    },
-                )),
-            }]
-        }
-        _ => vec![],
-    }
 }
 
 /// Broadcast an event to all subscribers of a subagent.
-async fn broadcast_event(
+pub(super) async fn broadcast_event(
     running: &Arc<RwLock<HashMap<String, RunningSubagent>>>,
     subagent_id: &str,
     event: SubagentEvent,
@@ -1076,7 +783,7 @@ async fn terminate_process(child: &mut tokio::process::Child) {
 }
 
 /// Generate a protobuf Timestamp for the current time.
-fn now_timestamp() -> prost_types::Timestamp {
+pub(super) fn now_timestamp() -> prost_types::Timestamp {
     let now = std::time::SystemTime::now()
         .duration_since(std::time::UNIX_EPOCH)
         .unwrap_or_default();
@@ -1116,6 +823,7 @@ mod tests {
             max_turns: 10,
             auto_approve: false,
             timeout_secs: 30,
+            resume_session: None,
         }
     }
 
@@ -1167,6 +875,24 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn spawn_records_timeout_for_recovery() {
+        let db = test_db().await;
+        let manager = SubagentManager::new(
+            test_pool(),
+            db.clone(),
+            "/nonexistent/betcode-test-claude".into(),
+        );
+        let config = SubagentConfig {
+            timeout_secs: 45,
+            ..test_config()
+        };
+        assert!(manager.spawn(config).await.is_err());
+
+        let sa = db.get_subagent("test-sa-1").await.unwrap();
+        assert_eq!(sa.timeout_secs, 45);
+    }
+
     #[tokio::test]
     async fn is_running_returns_false_for_nonexistent() {
         let db = test_db().await;
@@ -1216,398 +942,10 @@ mod tests {
         assert!(matches!(result, Ok(false)));
     }
 
-    #[test]
-    fn parse_stdout_text_delta() {
-        let line = r#"{"type":"content_block_delta","delta":{"type":"text_delta","text":"Hello"}}"#;
-        let events = parse_stdout_line("sa-1", line);
-        assert_eq!(events.len(), 1);
-        match &events[0].event {
-            Some(betcode_proto::v1::subagent_event::Event::Output(out)) => {
-                assert_eq!(out.text, "Hello");
-            }
-            other => panic!("Expected Output, got {other:?}"),
-        }
-    }
-
-    #[test]
-    fn parse_stdout_empty_text_delta_suppressed() {
-        let line = r#"{"type":"content_block_delta","delta":{"type":"text_delta","text":""}}"#;
-        let events = parse_stdout_line("sa-1", line);
-        assert!(events.is_empty());
-    }
-
-    #[test]
-    fn parse_stdout_non_json() {
-        let events = parse_stdout_line("sa-1", "not json at all");
-        assert!(events.is_empty());
-    }
-
-    #[test]
-    fn parse_stdout_unknown_type() {
-        let line = r#"{"type":"unknown_event","data":123}"#;
-        let events = parse_stdout_line("sa-1", line);
-        assert!(events.is_empty());
-    }
-
-    #[test]
-    fn parse_stdout_control_request() {
-        let line = r#"{"type":"control_request","request_id":"req-1","request":{"type":"CanUseTool","tool_name":"Bash","input":{"command":"ls"}}}"#;
-        let events = parse_stdout_line("sa-1", line);
-        assert_eq!(events.len(), 1);
-        match &events[0].event {
-            Some(betcode_proto::v1::subagent_event::Event::PermissionRequest(pr)) => {
-                assert_eq!(pr.request_id, "req-1");
-                assert_eq!(pr.tool_name, "Bash");
-            }
-            other => panic!("Expected PermissionRequest, got {other:?}"),
-        }
-    }
-
     #[test]
     fn now_timestamp_is_reasonable() {
         let ts = now_timestamp();
         // Should be after 2020
         assert!(ts.seconds > 1_577_836_800);
     }
-
-    // =========================================================================
-    // Orchestration subscription & Notify tests
-    // =========================================================================
-
-    #[tokio::test]
-    async fn subscribe_orchestration_returns_error_for_unknown() {
-        let db = test_db().await;
-        let pool = test_pool();
-        let manager = SubagentManager::new(pool, db, "claude".into());
-
-        let result = manager.subscribe_orchestration("nonexistent").await;
-        assert!(result.is_err());
-        let err = result.unwrap_err().to_string();
-        assert!(
-            err.contains("Orchestration not found"),
-            "Expected OrchestrationNotFound, got: {err}"
-        );
-    }
-
-    #[tokio::test]
-    async fn notify_wakes_orchestration_loop() {
-        // Verify that Notify::notify_one wakes a waiting notified().await
-        let notify = Arc::new(Notify::new());
-        let notify_clone = Arc::clone(&notify);
-
-        let handle = tokio::spawn(async move {
-            notify_clone.notified().await;
-            true
-        });
-
-        // Give the spawned task time to start waiting
-        tokio::time::sleep(std::time::Duration::from_millis(10)).await;
-        notify.notify_one();
-
-        let result = tokio::time::timeout(std::time::Duration::from_secs(1), handle)
-            .await
-            .expect("should not time out")
-            .expect("task should not panic");
-        assert!(result, "notified task should have completed");
-    }
-
-    #[tokio::test]
-    async fn broadcast_delivers_to_multiple_subscribers() {
-        let (tx, _) = broadcast::channel::<OrchestrationEvent>(16);
-
-        let mut rx1 = tx.subscribe();
-        let mut rx2 = tx.subscribe();
-
-        let event = OrchestrationEvent {
-            orchestration_id: "orch-1".to_string(),
-            timestamp: Some(now_timestamp()),
-            event: Some(betcode_proto::v1::orchestration_event::Event::Completed(
-                OrchestrationCompleted {
-                    total_steps: 1,
-                    succeeded: 1,
-                    failed: 0,
-                },
-            )),
-        };
-
-        tx.send(event.clone()).expect("send should succeed");
-
-        let ev1 = rx1.recv().await.expect("rx1 should receive event");
-        let ev2 = rx2.recv().await.expect("rx2 should receive event");
-
-        assert_eq!(ev1.orchestration_id, "orch-1");
-        assert_eq!(ev2.orchestration_id, "orch-1");
-    }
-
-    #[tokio::test]
-    async fn orchestration_state_stored_and_cleaned_up() {
-        // Verify that OrchestrationState is properly managed in the map
-        let db = test_db().await;
-        let pool = test_pool();
-        let manager = SubagentManager::new(pool, db, "claude".into());
-
-        // Manually insert an orchestration state
-        let (event_tx, _) = broadcast::channel(16);
-        let step_notify = Arc::new(Notify::new());
-        {
-            let mut orchestrations = manager.orchestrations.write().await;
-            orchestrations.insert(
-                "test-orch-1".to_string(),
-                OrchestrationState {
-                    event_tx,
-                    step_notify,
-                },
-            );
-        }
-
-        // Should be able to subscribe now
-        let result = manager.subscribe_orchestration("test-orch-1").await;
-        assert!(
-            result.is_ok(),
-            "subscribe should succeed for existing orchestration"
-        );
-
-        // Remove it
-        manager.orchestrations.write().await.remove("test-orch-1");
-
-        // Should fail now
-        let result = manager.subscribe_orchestration("test-orch-1").await;
-        assert!(result.is_err(), "subscribe should fail after cleanup");
-    }
-
-    // =========================================================================
-    // Orchestration lifecycle integration tests
-    // =========================================================================
-
-    /// Helper: insert an `OrchestrationState` into the manager and return its
-    /// broadcast sender for manual event injection.
-    async fn insert_orchestration_state(
-        manager: &SubagentManager,
-        orch_id: &str,
-    ) -> broadcast::Sender<OrchestrationEvent> {
-        let (event_tx, _) = broadcast::channel(ORCHESTRATION_BROADCAST_CAPACITY);
-        let step_notify = Arc::new(Notify::new());
-        let tx_clone = event_tx.clone();
-        manager.orchestrations.write().await.insert(
-            orch_id.to_string(),
-            OrchestrationState {
-                event_tx,
-                step_notify,
-            },
-        );
-        tx_clone
-    }
-
-    /// Build a minimal `OrchestrationStep` proto for testing.
-    fn make_step(
-        id: &str,
-        prompt: &str,
-        depends_on: Vec<String>,
-    ) -> betcode_proto::v1::OrchestrationStep {
-        betcode_proto::v1::OrchestrationStep {
-            id: id.to_string(),
-            name: id.to_string(),
-            prompt: prompt.to_string(),
-            depends_on,
-            working_directory: std::env::temp_dir().to_string_lossy().into_owned(),
-            ..Default::default()
-        }
-    }
-
-    #[tokio::test]
-    async fn subscribe_orchestration_receives_events() {
-        let db = test_db().await;
-        let pool = test_pool();
-        let manager = SubagentManager::new(pool, db, "claude".into());
-
-        let tx = insert_orchestration_state(&manager, "orch-sub-1").await;
-
-        // Subscribe to the orchestration
-        let mut rx = manager
-            .subscribe_orchestration("orch-sub-1")
-            .await
-            .expect("subscribe should succeed");
-
-        // Send an event through the broadcast sender
-        let event = OrchestrationEvent {
-            orchestration_id: "orch-sub-1".to_string(),
-            timestamp: Some(now_timestamp()),
-            event: Some(betcode_proto::v1::orchestration_event::Event::StepStarted(
-                StepStarted {
-                    step_id: "step-0".to_string(),
-                    subagent_id: "sa-0".to_string(),
-                    name: "first".to_string(),
-                },
-            )),
-        };
-        tx.send(event.clone())
-            .expect("broadcast send should succeed");
-
-        let received = rx.recv().await.expect("subscriber should receive event");
-        assert_eq!(received.orchestration_id, "orch-sub-1");
-        match &received.event {
-            Some(betcode_proto::v1::orchestration_event::Event::StepStarted(started)) => {
-                assert_eq!(started.step_id, "step-0");
-                assert_eq!(started.name, "first");
-            }
-            other => panic!("Expected StepStarted event, got {other:?}"),
-        }
-    }
-
-    #[tokio::test]
-    async fn subscribe_orchestration_multiple_subscribers() {
-        let db = test_db().await;
-        let pool = test_pool();
-        let manager = SubagentManager::new(pool, db, "claude".into());
-
-        let tx = insert_orchestration_state(&manager, "orch-multi-1").await;
-
-        // Subscribe twice
-        let mut rx1 = manager
-            .subscribe_orchestration("orch-multi-1")
-            .await
-            .expect("first subscribe should succeed");
-        let mut rx2 = manager
-            .subscribe_orchestration("orch-multi-1")
-            .await
-            .expect("second subscribe should succeed");
-
-        // Send one event
-        let event = OrchestrationEvent {
-            orchestration_id: "orch-multi-1".to_string(),
-            timestamp: Some(now_timestamp()),
-            event: Some(betcode_proto::v1::orchestration_event::Event::Completed(
-                OrchestrationCompleted {
-                    total_steps: 2,
-                    succeeded: 2,
-                    failed: 0,
-                },
-            )),
-        };
-        tx.send(event).expect("broadcast send should succeed");
-
-        // Both receivers should get the event
-        let ev1 = rx1.recv().await.expect("rx1 should receive event");
-        let ev2 = rx2.recv().await.expect("rx2 should receive event");
-
-        assert_eq!(ev1.orchestration_id, "orch-multi-1");
-        assert_eq!(ev2.orchestration_id, "orch-multi-1");
-        match (&ev1.event, &ev2.event) {
-            (
-                Some(betcode_proto::v1::orchestration_event::Event::Completed(c1)),
-                Some(betcode_proto::v1::orchestration_event::Event::Completed(c2)),
-            ) => {
-                assert_eq!(c1.succeeded, 2);
-                assert_eq!(c2.succeeded, 2);
-            }
-            other => panic!("Expected Completed events, got {other:?}"),
-        }
-    }
-
-    #[tokio::test]
-    async fn orchestration_db_records_created() {
-        // run_orchestration creates DB records (orchestration + steps) before
-        // spawning subprocesses, so they should exist even if claude isn't
-        // available.
-        let db = test_db().await;
-        let pool = test_pool();
-        let manager = Arc::new(SubagentManager::new(pool, db.clone(), "claude".into()));
-
-        let steps = vec![
-            make_step("p-1", "task one", vec![]),
-            make_step("p-2", "task two", vec![]),
-        ];
-
-        manager
-            .run_orchestration(
-                "orch-db-1".to_string(),
-                "parent-1".to_string(),
-                OrchestrationStrategy::Parallel,
-                steps,
-            )
-            .await
-            .expect("run_orchestration should succeed (DB records created)");
-
-        // Allow the spawned task to start and attempt subprocess spawn
-        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
-
-        // Orchestration record should exist in DB
-        let orch = db
-            .get_orchestration("orch-db-1")
-            .await
-            .expect("orchestration record should exist");
-        assert_eq!(orch.parent_session_id, "parent-1");
-        assert_eq!(orch.strategy, "parallel");
-
-        // Step records should exist in DB
-        let db_steps = db
-            .get_steps_for_orchestration("orch-db-1")
-            .await
-            .expect("steps should be retrievable");
-        assert_eq!(db_steps.len(), 2);
-        assert_eq!(db_steps[0].id, "p-1");
-        assert_eq!(db_steps[1].id, "p-2");
-    }
-
-    #[tokio::test]
-    async fn orchestration_sequential_dependency_chaining() {
-        // Sequential strategy chains steps A -> B -> C in the DAG.
-        // Dependencies are pre-chained (as the gRPC layer would do) and the
-        // manager stores them in the DB via run_orchestration.
-        let db = test_db().await;
-        let pool = test_pool();
-        let manager = Arc::new(SubagentManager::new(pool, db.clone(), "claude".into()));
-
-        // Pre-chain dependencies the same way the gRPC layer does for Sequential.
-        let steps = vec![
-            make_step("seq-a", "first", vec![]),
-            make_step("seq-b", "second", vec!["seq-a".to_string()]),
-            make_step("seq-c", "third", vec!["seq-b".to_string()]),
-        ];
-
-        manager
-            .run_orchestration(
-                "orch-seq-1".to_string(),
-                "parent-1".to_string(),
-                OrchestrationStrategy::Sequential,
-                steps,
-            )
-            .await
-            .expect("run_orchestration should succeed");
-
-        // Allow spawned task to start
-        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
-
-        // Verify orchestration stored with sequential strategy
-        let orch = db
-            .get_orchestration("orch-seq-1")
-            .await
-            .expect("orchestration should exist");
-        assert_eq!(orch.strategy, "sequential");
-
-        // Verify steps exist and have correct order
-        let db_steps = db
-            .get_steps_for_orchestration("orch-seq-1")
-            .await
-            .expect("steps should be retrievable");
-        assert_eq!(db_steps.len(), 3);
-        assert_eq!(db_steps[0].id, "seq-a");
-        assert_eq!(db_steps[1].id, "seq-b");
-        assert_eq!(db_steps[2].id, "seq-c");
-
-        // Verify dependency chaining: seq-b depends on seq-a, seq-c depends on seq-b.
-        // Dependencies are stored as JSON arrays in the `depends_on` column.
-        let deps_a: Vec<String> = serde_json::from_str(&db_steps[0].depends_on).unwrap();
-        let deps_b: Vec<String> = serde_json::from_str(&db_steps[1].depends_on).unwrap();
-        let deps_c: Vec<String> = serde_json::from_str(&db_steps[2].depends_on).unwrap();
-        assert!(deps_a.is_empty(), "first step should have no dependencies");
-        assert!(
-            deps_b.contains(&"seq-a".to_string()),
-            "seq-b should depend on seq-a"
-        );
-        assert!(
-            deps_c.contains(&"seq-b".to_string()),
-            "seq-c should depend on seq-b"
-        );
-    }
 }
@@ -7,11 +7,18 @@
 //! - [`SubagentManager`]: High-level lifecycle manager that spawns, monitors,
 //!   times out, and cancels subagent subprocesses.
 //! - [`DagScheduler`]: DAG-based step scheduler for multi-step orchestrations.
+//! - [`isolation`]: per-step worktrees merged back into an integration branch.
 
+pub mod isolation;
 pub mod manager;
+mod output;
+mod permissions;
 pub mod pool;
+mod recovery;
+mod run;
 pub mod scheduler;
 
 pub use manager::SubagentManager;
 pub use pool::SubprocessPool;
+pub use recovery::RecoveryReport;
 pub use scheduler::DagScheduler;
@@ -0,0 +1,270 @@
+//! Parsing of the stream-json output of subagent subprocesses.
+
+use tracing::debug;
+
+use betcode_proto::v1::{
+    SubagentEvent, SubagentOutput, SubagentPermissionRequest, SubagentToolUse,
+};
+
+use crate::subprocess::bridge::tool_description;
+
+use super::manager::now_timestamp;
+
+/// Parse an NDJSON stdout line into subagent events.
+#[allow(clippy::too_many_lines)]
+pub(super) fn parse_stdout_line(subagent_id: &str, line: &str) -> Vec<SubagentEvent> {
+    let Ok(value) = serde_json::from_str::<serde_json::Value>(line) else {
+        debug!(subagent_id, "Non-JSON stdout line: {}", line);
+        return vec![];
+    };
+
+    let msg_type = value.get("type").and_then(|v| v.as_str()).unwrap_or("");
+
+    match msg_type {
+        "assistant" => {
+            // Extract text content from assistant message
+            let text = value
+                .get("message")
+                .and_then(|m| m.get("content"))
+                .and_then(|c| c.as_array())
+                .map(|blocks| {
+                    blocks
+                        .iter()
+                        .filter_map(|b| {
+                            if b.get("type").and_then(|t| t.as_str()) == Some("text") {
+                                b.get("text").and_then(|t| t.as_str()).map(String::from)
+                            } else {
+                                None
+                            }
+                        })
+                        .collect::<String>()
+                })
+                .unwrap_or_default();
+
+            let mut events = Vec::new();
+            if !text.is_empty() {
+                events.push(SubagentEvent {
+                    subagent_id: subagent_id.to_string(),
+                    timestamp: Some(now_timestamp()),
+                    event: Some(betcode_proto::v1::subagent_event::Event::Output(
+                        SubagentOutput {
+                            text,
+                            is_complete: false,
+                        },
+                    )),
+                });
+            }
+
+            // Extract tool_use blocks
+            if let Some(content) = value
+                .get("message")
+                .and_then(|m| m.get("content"))
+                .and_then(|c| c.as_array())
+            {
+                for block in content {
+                    if block.get("type").and_then(|t| t.as_str()) == Some("tool_use") {
+                        let tool_name = block
+                            .get("name")
+                            .and_then(|n| n.as_str())
+                            .unwrap_or("")
+                            .to_string();
+                        let tool_id = block
+                            .get("id")
+                            .and_then(|n| n.as_str())
+                            .unwrap_or("")
+                            .to_string();
+                        events.push(SubagentEvent {
+                            subagent_id: subagent_id.to_string(),
+                            timestamp: Some(now_timestamp()),
+                            event: Some(betcode_proto::v1::subagent_event::Event::ToolUse(
+                                SubagentToolUse {
+                                    tool_id,
+                                    tool_name,
+                                    description: String::new(),
+                                },
+                            )),
+                        });
+                    }
+                }
+            }
+
+            events
+        }
+        "content_block_delta" => {
+            let text = value
+                .get("delta")
+                .and_then(|d| d.get("text"))
+                .and_then(|t| t.as_str())
+                .unwrap_or("");
+
+            if text.is_empty() {
+                return vec![];
+            }
+
+            vec![SubagentEvent {
+                subagent_id: subagent_id.to_string(),
+                timestamp: Some(now_timestamp()),
+                event: Some(betcode_proto::v1::subagent_event::Event::Output(
+                    SubagentOutput {
+                        text: text.to_string(),
+                        is_complete: false,
+                    },
+                )),
+            }]
+        }
+        "control_request" => {
+            let tool_name = value
+                .get("request")
+                .and_then(|r| r.get("tool_name"))
+                .and_then(|t| t.as_str())
+                .unwrap_or("")
+                .to_string();
+            let request_id = value
+                .get("request_id")
+                .and_then(|r| r.as_str())
+                .unwrap_or("")
+                .to_string();
+            let input = value
+                .get("request")
+                .and_then(|r| r.get("input"))
+                .cloned()
+                .unwrap_or_else(|| serde_json::json!({}));
+
+            vec![SubagentEvent {
+                subagent_id: subagent_id.to_string(),
+                timestamp: Some(now_timestamp()),
+                event: Some(betcode_proto::v1::subagent_event::Event::PermissionRequest(
+                    SubagentPermissionRequest {
+                        request_id,
+                        description: tool_description(&tool_name, &input),
+                        tool_name,
+                        input_json: input.to_string(),
+                    },
+                )),
+            }]
+        }
+        _ => vec![],
+    }
+}
+
+/// Final assistant text carried by an NDJSON stdout line, if any.
+///
+/// The `result` message holds the text of the last turn; assistant messages
+/// are used as a fallback when the process exits without one.
+pub(super) fn final_output_text(line: &str) -> Option<String> {
+    let value = serde_json::from_str::<serde_json::Value>(line).ok()?;
+    match value.get("type").and_then(|v| v.as_str())? {
+        "result" => value
+            .get("result")
+            .and_then(|r| r.as_str())
+            .map(String::from),
+        "assistant" => {
+            let text: String = value
+                .get("message")
+                .and_then(|m| m.get("content"))
+                .and_then(|c| c.as_array())?
+                .iter()
+                .filter(|b| b.get("type").and_then(|t| t.as_str()) == Some("text"))
+                .filter_map(|b| b.get("text").and_then(|t| t.as_str()))
+                .collect();
+            (!text.is_empty()).then_some(text)
+        }
+        _ => None,
+    }
+}
+
+/// Claude's session ID from the `system` init line of a stream-json run.
+pub(super) fn init_session_id(line: &str) -> Option<String> {
+    if !line.contains("\"init\"") {
+        return None;
+    }
+    let value = serde_json::from_str::<serde_json::Value>(line).ok()?;
+    if value.get("type").and_then(|v| v.as_str()) != Some("system")
+        || value.get("subtype").and_then(|v| v.as_str()) != Some("init")
+    {
+        return None;
+    }
+    value
+        .get("session_id")
+        .and_then(|v| v.as_str())
+        .filter(|s| !s.is_empty())
+        .map(String::from)
+}
+
+#[cfg(test)]
+#[allow(clippy::panic, clippy::expect_used, clippy::unwrap_used)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_stdout_text_delta() {
+        let line = r#"{"type":"content_block_delta","delta":{"type":"text_delta","text":"Hello"}}"#;
+        let events = parse_stdout_line("sa-1", line);
+        assert_eq!(events.len(), 1);
+        match &events[0].event {
+            Some(betcode_proto::v1::subagent_event::Event::Output(out)) => {
+                assert_eq!(out.text, "Hello");
+            }
+            other => panic!("Expected Output, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn parse_stdout_empty_text_delta_suppressed() {
+        let line = r#"{"type":"content_block_delta","delta":{"type":"text_delta","text":""}}"#;
+        let events = parse_stdout_line("sa-1", line);
+        assert!(events.is_empty());
+    }
+
+    #[test]
+    fn parse_stdout_non_json() {
+        let events = parse_stdout_line("sa-1", "not json at all");
+        assert!(events.is_empty());
+    }
+
+    #[test]
+    fn parse_stdout_unknown_type() {
+        let line = r#"{"type":"unknown_event","data":123}"#;
+        let events = parse_stdout_line("sa-1", line);
+        assert!(events.is_empty());
+    }
+
+    #[test]
+    fn parse_stdout_control_request() {
+        let line = r#"{"type":"control_request","request_id":"req-1","request":{"type":"CanUseTool","tool_name":"Bash","input":{"command":"ls"}}}"#;
+        let events = parse_stdout_line("sa-1", line);
+        assert_eq!(events.len(), 1);
+        match &events[0].event {
+            Some(betcode_proto::v1::subagent_event::Event::PermissionRequest(pr)) => {
+                assert_eq!(pr.request_id, "req-1");
+                assert_eq!(pr.tool_name, "Bash");
+                assert_eq!(pr.description, "ls");
+                assert_eq!(pr.input_json, r#"{"command":"ls"}"#);
+            }
+            other => panic!("Expected PermissionRequest, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn final_output_prefers_result_message() {
+        let assistant = r#"{"type":"assistant","message":{"content":[{"type":"text","text":"Done."},{"type":"tool_use","name":"Bash","id":"t1"}]}}"#;
+        assert_eq!(final_output_text(assistant).as_deref(), Some("Done."));
+        let result = r#"{"type":"result","subtype":"success","result":"Refactored 3 files."}"#;
+        assert_eq!(
+            final_output_text(result).as_deref(),
+            Some("Refactored 3 files.")
+        );
+        let tool_only = r#"{"type":"assistant","message":{"content":[{"type":"tool_use","name":"Bash","id":"t1"}]}}"#;
+        assert!(final_output_text(tool_only).is_none());
+        assert!(final_output_text("not json").is_none());
+    }
+
+    #[test]
+    fn init_session_id_reads_system_init_line() {
+        let line = r#"{"type":"system","subtype":"init","session_id":"sess-42"}"#;
+        assert_eq!(init_session_id(line).as_deref(), Some("sess-42"));
+        let other = r#"{"type":"assistant","subtype":"init","session_id":"sess-42"}"#;
+        assert_eq!(init_session_id(other), None);
+        assert_eq!(init_session_id("not json"), None);
+    }
+}
@@ -0,0 +1,528 @@
+//! Routing of subagent permission requests to the permission engine and
+//! watching clients.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Weak};
+use std::time::Duration;
+
+use tokio::sync::RwLock;
+use tracing::{debug, info, warn};
+
+use betcode_core::step_template::truncate_output;
+use betcode_proto::v1::{
+    PermissionDecision, SubagentEvent, SubagentPermissionRequest, SubagentPermissionResolved,
+};
+
+use crate::permission::{
+    DaemonPermissionEngine, PermissionEvalRequest, PermissionEvaluation, PermissionResponse,
+};
+use crate::relay::is_granted;
+
+use super::manager::{
+    ManagerError, PermissionFallback, RunningSubagent, SubagentManager, broadcast_event,
+    now_timestamp,
+};
+
+/// How often pending subagent permission requests are checked for expiry.
+const PERMISSION_SWEEP_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Denial reason for a permission request while no client watches.
+const UNWATCHED_PERMISSION_REASON: &str =
+    "No client is watching this subagent to approve the request";
+
+/// Denial reason for a permission request no client answered in time.
+const EXPIRED_PERMISSION_REASON: &str = "No client answered the permission request in time";
+
+/// Denial reason for a permission request of a subagent that has exited.
+const FINISHED_PERMISSION_REASON: &str = "Subagent is no longer running";
+
+impl SubagentManager {
+    /// Number of permission requests of a subagent waiting for a client.
+    pub async fn pending_permission_count(&self, subagent_id: &str) -> usize {
+        self.running
+            .read()
+            .await
+            .get(subagent_id)
+            .map_or(0, |sa| sa.pending_permissions.len())
+    }
+
+    /// Answer a permission request a subagent is waiting on.
+    ///
+    /// Returns `false` when the request was already answered or expired.
+    pub async fn respond_to_permission(
+        &self,
+        subagent_id: &str,
+        request_id: &str,
+        decision: PermissionDecision,
+    ) -> Result<bool, ManagerError> {
+        let request = self
+            .running
+            .write()
+            .await
+            .get_mut(subagent_id)
+            .ok_or_else(|| ManagerError::NotFound {
+                id: subagent_id.to_string(),
+            })?
+            .pending_permissions
+            .remove(request_id);
+        let Some(request) = request else {
+            return Ok(false);
+        };
+
+        let granted = is_granted(decision);
+        if let Err(e) = self
+            .permissions
+            .process_response(PermissionResponse {
+                request_id: request_id.to_string(),
+                granted,
+                remember_session: decision == PermissionDecision::AllowSession,
+                remember_permanent: false,
+            })
+            .await
+        {
+            // Expired but not swept yet; the answer still reaches Claude
+            debug!(subagent_id, request_id, error = %e, "Permission request already gone from engine");
+        }
+
+        let (interrupt, reason) = if granted {
+            (false, "Approved by user")
+        } else {
+            (
+                decision != PermissionDecision::DenyNoInterrupt,
+                "User denied permission",
+            )
+        };
+        self.send_permission_answer(subagent_id, &request, granted, interrupt, reason)
+            .await;
+        Ok(true)
+    }
+
+    /// Deny permission requests that expired or that nobody watches for.
+    ///
+    /// Also tells the permission engine when a subagent gains or loses its
+    /// last watcher, which switches its requests between the connected and
+    /// disconnected timeouts.
+    pub async fn expire_permissions(&self) {
+        self.permissions.cleanup_expired().await;
+
+        let snapshot: Vec<(String, bool, bool, Vec<String>)> = self
+            .running
+            .read()
+            .await
+            .iter()
+            .map(|(id, sa)| {
+                (
+                    id.clone(),
+                    sa.watched,
+                    sa.has_watchers(),
+                    sa.pending_permissions.keys().cloned().collect(),
+                )
+            })
+            .collect();
+
+        for (subagent_id, was_watched, watched, request_ids) in snapshot {
+            if watched != was_watched {
+                if let Some(sa) = self.running.write().await.get_mut(&subagent_id) {
+                    sa.watched = watched;
+                }
+                self.permissions
+                    .update_client_status(&subagent_id, watched)
+                    .await;
+            }
+            let unattended = !watched && self.permission_fallback == PermissionFallback::Deny;
+            for request_id in request_ids {
+                let reason = if unattended {
+                    self.permissions.cancel_pending(&request_id).await;
+                    UNWATCHED_PERMISSION_REASON
+                } else if self.permissions.get_pending(&request_id).await.is_none() {
+                    EXPIRED_PERMISSION_REASON
+                } else {
+                    continue;
+                };
+                let request = self
+                    .running
+                    .write()
+                    .await
+                    .get_mut(&subagent_id)
+                    .and_then(|sa| sa.pending_permissions.remove(&request_id));
+                if let Some(request) = request {
+                    info!(
+                        subagent_id = %subagent_id,
+                        request_id = %request_id,
+                        reason,
+                        "Denying subagent permission request"
+                    );
+                    self.send_permission_answer(&subagent_id, &request, false, false, reason)
+                        .await;
+                }
+            }
+        }
+    }
+
+    /// Run [`Self::expire_permissions`] periodically until the manager is
+    /// dropped.
+    pub fn spawn_permission_sweeper(self: &Arc<Self>) -> tokio::task::JoinHandle<()> {
+        let manager: Weak<Self> = Arc::downgrade(self);
+        tokio::spawn(async move {
+            let mut timer = tokio::time::interval(PERMISSION_SWEEP_INTERVAL);
+            loop {
+                timer.tick().await;
+                let Some(manager) = manager.upgrade() else {
+                    break;
+                };
+                manager.expire_permissions().await;
+            }
+        })
+    }
+
+    /// Write the answer to a permission request to the subagent's stdin and
+    /// tell its watchers.
+    async fn send_permission_answer(
+        &self,
+        subagent_id: &str,
+        request: &SubagentPermissionRequest,
+        granted: bool,
+        interrupt: bool,
+        reason: &str,
+    ) {
+        let line = permission_response_line(request, granted, interrupt, reason);
+        let delivered = match self.pool.get(subagent_id).await {
+            Some(entry) => entry.stdin_tx.send(line).await.is_ok(),
+            None => false,
+        };
+        if !delivered {
+            warn!(subagent_id, request_id = %request.request_id, "Subagent stdin closed before permission response");
+        }
+        broadcast_event(
+            &self.running,
+            subagent_id,
+            SubagentEvent {
+                subagent_id: subagent_id.to_string(),
+                timestamp: Some(now_timestamp()),
+                event: Some(
+                    betcode_proto::v1::subagent_event::Event::PermissionResolved(
+                        SubagentPermissionResolved {
+                            request_id: request.request_id.clone(),
+                            granted,
+                            reason: reason.to_string(),
+                        },
+                    ),
+                ),
+            },
+        )
+        .await;
+    }
+}
+
+/// Answer a subagent's permission request without a client where possible.
+///
+/// Grants and rules of the permission engine come first, so a deny rule
+/// holds even for auto-approved tools. A request the engine leaves to the
+/// user is allowed when auto-approved, denied when nobody watches and the
+/// fallback says so, and otherwise stored for clients with `None` returned.
+/// Decisions come with the reason given to Claude.
+pub(super) async fn route_permission_request(
+    permissions: &DaemonPermissionEngine,
+    running: &RwLock<HashMap<String, RunningSubagent>>,
+    fallback: PermissionFallback,
+    subagent_id: &str,
+    request: &SubagentPermissionRequest,
+) -> Option<(bool, &'static str)> {
+    // The read guard is a temporary and is released at the end of the statement
+    let snapshot = running.read().await.get(subagent_id).map(|sa| {
+        (
+            sa.parent_session_id.clone(),
+            sa.auto_approve && sa.allowed_tools.contains(&request.tool_name),
+            sa.has_watchers(),
+        )
+    });
+    let Some((parent_session_id, auto_approved, watched)) = snapshot else {
+        return Some((false, FINISHED_PERMISSION_REASON));
+    };
+
+    let evaluation = permissions
+        .evaluate(&PermissionEvalRequest {
+            session_id: &parent_session_id,
+            request_id: &request.request_id,
+            tool_name: &request.tool_name,
+            description: &request.description,
+            input_json: &request.input_json,
+            path: None,
+            target_client: Some(subagent_id),
+            client_connected: watched,
+        })
+        .await;
+    match evaluation {
+        PermissionEvaluation::Allowed { .. } => Some((true, "Allowed by permission rules")),
+        PermissionEvaluation::Denied { .. } => Some((false, "Denied by permission rules")),
+        PermissionEvaluation::Pending { .. } if auto_approved => {
+            permissions.cancel_pending(&request.request_id).await;
+            info!(
+                subagent_id,
+                parent_session_id = %parent_session_id,
+                tool_name = %request.tool_name,
+                input = %truncate_output(&request.input_json, 1024),
+                auto_approve = true,
+                "Auto-approved subagent permission request"
+            );
+            Some((true, "Auto-approved"))
+        }
+        PermissionEvaluation::Pending { .. } => {
+            if !watched && fallback == PermissionFallback::Deny {
+                permissions.cancel_pending(&request.request_id).await;
+                return Some((false, UNWATCHED_PERMISSION_REASON));
+            }
+            let stored = {
+                let mut map = running.write().await;
+                if let Some(sa) = map.get_mut(subagent_id) {
+                    sa.watched = watched;
+                    sa.pending_permissions
+                        .insert(request.request_id.clone(), request.clone());
+                    true
+                } else {
+                    false
+                }
+            };
+            if !stored {
+                permissions.cancel_pending(&request.request_id).await;
+                return Some((false, FINISHED_PERMISSION_REASON));
+            }
+            info!(
+                subagent_id,
+                request_id = %request.request_id,
+                tool_name = %request.tool_name,
+                "Subagent permission request waiting for a client"
+            );
+            None
+        }
+    }
+}
+
+/// Build the `control_response` line answering a subagent permission request.
+///
+/// An allow hands the original tool input back as `updatedInput`, which
+/// Claude requires; a deny carries `reason` as its message.
+pub(super) fn permission_response_line(
+    request: &SubagentPermissionRequest,
+    granted: bool,
+    interrupt: bool,
+    reason: &str,
+) -> String {
+    let response = if granted {
+        let input = serde_json::from_str::<serde_json::Value>(&request.input_json)
+            .unwrap_or_else(|_| serde_json::json!({}));
+        serde_json::json!({
+            "behavior": "allow",
+            "updatedInput": input
+        })
+    } else {
+        serde_json::json!({
+            "behavior": "deny",
+            "message": reason,
+            "interrupt": interrupt
+        })
+    };
+    serde_json::json!({
+        "type": "control_response",
+        "response": {
+            "subtype": "success",
+            "request_id": request.request_id,
+            "response": response
+        }
+    })
+    .to_string()
+}
+
+#[cfg(test)]
+#[allow(clippy::panic, clippy::expect_used, clippy::unwrap_used)]
+mod tests {
+    use super::*;
+    use crate::orchestration::pool::{PoolEntry, SubprocessPool};
+    use crate::storage::Database;
+    use tokio::sync::mpsc;
+
+    fn test_pool() -> Arc<SubprocessPool> {
+        Arc::new(SubprocessPool::new(3))
+    }
+
+    async fn test_db() -> Database {
+        let db = Database::open_in_memory().await.unwrap();
+        db.create_session("parent-1", "claude-sonnet-4", "/tmp")
+            .await
+            .unwrap();
+        db
+    }
+
+    fn bash_request(request_id: &str) -> SubagentPermissionRequest {
+        SubagentPermissionRequest {
+            request_id: request_id.to_string(),
+            tool_name: "Bash".to_string(),
+            description: "ls".to_string(),
+            input_json: r#"{"command":"ls"}"#.to_string(),
+        }
+    }
+
+    fn running_subagent(auto_approve: bool, allowed_tools: &[&str]) -> RunningSubagent {
+        RunningSubagent {
+            event_txs: Vec::new(),
+            #[cfg(unix)]
+            pid: None,
+            auto_approve,
+            allowed_tools: allowed_tools.iter().map(ToString::to_string).collect(),
+            parent_session_id: "parent-1".to_string(),
+            pending_permissions: HashMap::new(),
+            watched: false,
+        }
+    }
+
+    /// A manager with one fake running subagent whose stdin lines are returned.
+    async fn permission_manager(
+        fallback: PermissionFallback,
+        subagent: RunningSubagent,
+    ) -> (SubagentManager, mpsc::Receiver<String>) {
+        let manager = SubagentManager::new(test_pool(), test_db().await, "claude".into())
+            .with_permission_fallback(fallback);
+        let (stdin_tx, stdin_rx) = mpsc::channel(8);
+        manager
+            .pool
+            .register(PoolEntry {
+                subagent_id: "sa-1".to_string(),
+                stdin_tx,
+            })
+            .await;
+        manager
+            .running
+            .write()
+            .await
+            .insert("sa-1".to_string(), subagent);
+        (manager, stdin_rx)
+    }
+
+    async fn route(manager: &SubagentManager, request_id: &str) -> Option<(bool, &'static str)> {
+        route_permission_request(
+            &manager.permissions,
+            &manager.running,
+            manager.permission_fallback,
+            "sa-1",
+            &bash_request(request_id),
+        )
+        .await
+    }
+
+    #[test]
+    fn permission_response_line_allow_and_deny() {
+        let request = bash_request("req-1");
+        let allow: serde_json::Value =
+            serde_json::from_str(&permission_response_line(&request, true, false, "ok")).unwrap();
+        assert_eq!(allow["type"], "control_response");
+        assert_eq!(allow["response"]["request_id"], "req-1");
+        assert_eq!(allow["response"]["response"]["behavior"], "allow");
+        assert_eq!(
+            allow["response"]["response"]["updatedInput"]["command"],
+            "ls"
+        );
+
+        let deny: serde_json::Value =
+            serde_json::from_str(&permission_response_line(&request, false, false, "nope"))
+                .unwrap();
+        assert_eq!(deny["response"]["response"]["behavior"], "deny");
+        assert_eq!(deny["response"]["response"]["message"], "nope");
+        assert_eq!(deny["response"]["response"]["interrupt"], false);
+    }
+
+    #[tokio::test]
+    async fn auto_approved_tools_are_allowed_until_revoked() {
+        let (manager, _stdin) =
+            permission_manager(PermissionFallback::Deny, running_subagent(true, &["Bash"])).await;
+        assert_eq!(route(&manager, "req-1").await.map(|d| d.0), Some(true));
+
+        manager.revoke_auto_approve("sa-1").await.unwrap();
+        assert_eq!(
+            route(&manager, "req-2").await,
+            Some((false, UNWATCHED_PERMISSION_REASON))
+        );
+        assert!(manager.permissions.get_pending("req-2").await.is_none());
+    }
+
+    #[tokio::test]
+    async fn unwatched_requests_wait_with_wait_fallback() {
+        let (manager, _stdin) =
+            permission_manager(PermissionFallback::Wait, running_subagent(false, &[])).await;
+        assert_eq!(route(&manager, "req-1").await, None);
+        assert_eq!(manager.pending_permission_count("sa-1").await, 1);
+
+        // Nobody watching is fine while waiting
+        manager.expire_permissions().await;
+        assert_eq!(manager.pending_permission_count("sa-1").await, 1);
+
+        // A late subscriber is shown the request
+        let mut rx = manager.subscribe("sa-1").await.unwrap();
+        match rx.recv().await.unwrap().event {
+            Some(betcode_proto::v1::subagent_event::Event::PermissionRequest(pr)) => {
+                assert_eq!(pr.request_id, "req-1");
+            }
+            other => panic!("Expected PermissionRequest, got {other:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn client_answer_reaches_subagent_and_is_remembered() {
+        let (manager, mut stdin) =
+            permission_manager(PermissionFallback::Deny, running_subagent(false, &[])).await;
+        let mut rx = manager.subscribe("sa-1").await.unwrap();
+        assert_eq!(route(&manager, "req-1").await, None);
+
+        let answered = manager
+            .respond_to_permission("sa-1", "req-1", PermissionDecision::AllowSession)
+            .await
+            .unwrap();
+        assert!(answered);
+        let line: serde_json::Value = serde_json::from_str(&stdin.recv().await.unwrap()).unwrap();
+        assert_eq!(line["response"]["response"]["behavior"], "allow");
+        match rx.recv().await.unwrap().event {
+            Some(betcode_proto::v1::subagent_event::Event::PermissionResolved(r)) => {
+                assert_eq!(r.request_id, "req-1");
+                assert!(r.granted);
+            }
+            other => panic!("Expected PermissionResolved, got {other:?}"),
+        }
+
+        // Answered requests can't be answered again
+        let again = manager
+            .respond_to_permission("sa-1", "req-1", PermissionDecision::Deny)
+            .await
+            .unwrap();
+        assert!(!again);
+
+        // AllowSession covers later requests for the tool
+        assert_eq!(route(&manager, "req-2").await.map(|d| d.0), Some(true));
+    }
+
+    #[tokio::test]
+    async fn requests_are_denied_once_the_last_watcher_leaves() {
+        let (manager, mut stdin) =
+            permission_manager(PermissionFallback::Deny, running_subagent(false, &[])).await;
+        let rx = manager.subscribe("sa-1").await.unwrap();
+        assert_eq!(route(&manager, "req-1").await, None);
+
+        drop(rx);
+        manager.expire_permissions().await;
+        assert_eq!(manager.pending_permission_count("sa-1").await, 0);
+        assert!(manager.permissions.get_pending("req-1").await.is_none());
+        let line: serde_json::Value = serde_json::from_str(&stdin.recv().await.unwrap()).unwrap();
+        assert_eq!(line["response"]["response"]["behavior"], "deny");
+        assert_eq!(
+            line["response"]["response"]["message"],
+            UNWATCHED_PERMISSION_REASON
+        );
+    }
+
+    #[tokio::test]
+    async fn respond_to_permission_unknown_subagent() {
+        let manager = SubagentManager::new(test_pool(), test_db().await, "claude".into());
+        let result = manager
+            .respond_to_permission("nonexistent", "req-1", PermissionDecision::AllowOnce)
+            .await;
+        assert!(matches!(result, Err(ManagerError::NotFound { .. })));
+    }
+}
@@ -0,0 +1,600 @@
+//! Recovery of subagents and orchestrations interrupted by a daemon restart.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use tokio::sync::{Notify, broadcast};
+use tracing::{info, warn};
+
+use betcode_core::db::base64_decode;
+
+use prost::Message as _;
+
+use crate::storage::{OrchestrationRow, OrchestrationStepRow, SubagentRow};
+
+use super::manager::{ManagerError, OrchestrationState, SubagentConfig, SubagentManager};
+use super::run::{
+    ORCHESTRATION_BROADCAST_CAPACITY, OrchestrationRun, StepRun, build_scheduler,
+    is_review_round_id, step_subagent_ids,
+};
+use super::scheduler::StepState;
+
+/// Prompt that continues a subagent interrupted by a daemon restart.
+const RESUME_PROMPT: &str = "The daemon running this task restarted and interrupted you. \
+                             Continue where you left off and finish the task.";
+
+/// Result summary of subagents and steps that a restart cut short.
+const INTERRUPTED_MESSAGE: &str = "Interrupted by daemon restart";
+
+impl SubagentManager {
+    /// Reconcile subagents and orchestrations left unfinished by a previous
+    /// daemon process.
+    ///
+    /// With `resume`, interrupted subagents whose Claude session is known
+    /// continue it with `--resume`; the others are marked failed.
+    /// Orchestrations get their scheduler rebuilt from `orchestration_steps`
+    /// and carry on, so a failed subagent goes through its step's retry
+    /// policy. Call once at startup, before any subagent is spawned.
+    pub async fn recover(self: &Arc<Self>, resume: bool) -> Result<RecoveryReport, ManagerError> {
+        let mut report = RecoveryReport::default();
+        let mut orphans: HashMap<String, SubagentRow> = self
+            .db
+            .list_unfinished_subagents()
+            .await?
+            .into_iter()
+            .map(|sa| (sa.id.clone(), sa))
+            .collect();
+
+        for orch in self.db.list_unfinished_orchestrations().await? {
+            match self
+                .recover_orchestration(&orch, &mut orphans, resume, &mut report)
+                .await
+            {
+                Ok(()) => report.orchestrations_resumed += 1,
+                Err(e) => {
+                    warn!(orchestration_id = %orch.id, error = %e, "Cannot resume orchestration");
+                    self.fail_orchestration(&orch.id, &mut orphans, &mut report)
+                        .await?;
+                    report.orchestrations_failed += 1;
+                }
+            }
+        }
+
+        // Whatever is left was spawned directly with SpawnSubagent
+        for sa in orphans.into_values() {
+            let config = SubagentConfig {
+                id: sa.id.clone(),
+                parent_session_id: sa.parent_session_id.clone(),
+                prompt: RESUME_PROMPT.to_string(),
+                model: sa.model.clone(),
+                working_directory: sa.working_directory.clone().unwrap_or_default().into(),
+                allowed_tools: serde_json::from_str(&sa.allowed_tools).unwrap_or_default(),
+                max_turns: i32::try_from(sa.max_turns).unwrap_or(0),
+                auto_approve: sa.auto_approve != 0,
+                timeout_secs: u64::try_from(sa.timeout_secs).unwrap_or(0),
+                resume_session: None,
+            };
+            self.resume_or_fail(&sa, config, resume, &mut report)
+                .await?;
+        }
+
+        if report != RecoveryReport::default() {
+            info!(?report, "Recovered work interrupted by a daemon restart");
+        }
+        Ok(report)
+    }
+
+    /// Rebuild an orchestration's loop from its persisted steps.
+    #[allow(clippy::too_many_lines)]
+    async fn recover_orchestration(
+        self: &Arc<Self>,
+        orch: &OrchestrationRow,
+        orphans: &mut HashMap<String, SubagentRow>,
+        resume: bool,
+        report: &mut RecoveryReport,
+    ) -> Result<(), ManagerError> {
+        let rows = self.db.get_steps_for_orchestration(&orch.id).await?;
+        let steps = rows
+            .iter()
+            .map(decode_step_spec)
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut scheduler = build_scheduler(&steps)?;
+        let states: HashMap<String, StepState> = rows
+            .iter()
+            .map(|row| (row.id.clone(), step_state_from_db(&row.status)))
+            .collect();
+        let settled = scheduler.restore(&states);
+
+        let (event_tx, _) = broadcast::channel(ORCHESTRATION_BROADCAST_CAPACITY);
+        let step_notify = Arc::new(Notify::new());
+        self.orchestrations.write().await.insert(
+            orch.id.clone(),
+            OrchestrationState {
+                event_tx: event_tx.clone(),
+                step_notify: Arc::clone(&step_notify),
+            },
+        );
+
+        let mut run = OrchestrationRun {
+            manager: Arc::clone(self),
+            orch_id: orch.id.clone(),
+            parent_session_id: orch.parent_session_id.clone(),
+            steps: steps.into_iter().map(|s| (s.id.clone(), s)).collect(),
+            scheduler,
+            event_tx,
+            results: HashMap::new(),
+            runs: HashMap::new(),
+            completed: 0,
+            failed: 0,
+            tolerated: 0,
+            skipped: 0,
+            persist_error: None,
+        };
+
+        let mut interrupted = Vec::new();
+        for row in &rows {
+            match states[&row.id] {
+                StepState::Completed => {
+                    run.completed += 1;
+                    run.results
+                        .insert(row.id.clone(), row.result.clone().unwrap_or_default());
+                }
+                StepState::Failed if run.scheduler.policy(&row.id).continue_on_failure => {
+                    run.tolerated += 1;
+                }
+                StepState::Failed => run.failed += 1,
+                StepState::Skipped => run.skipped += 1,
+                state @ (StepState::Running | StepState::Retrying) => {
+                    let attempt = u32::try_from(row.attempts).unwrap_or(1).max(1);
+                    let items: Option<Vec<String>> = row
+                        .fan_out_items
+                        .as_deref()
+                        .and_then(|json| serde_json::from_str(json).ok());
+                    let fan_out = !run.steps[&row.id].fan_out_from.is_empty();
+                    let ids = step_subagent_ids(
+                        &orch.id,
+                        &row.id,
+                        attempt,
+                        fan_out.then(|| items.as_ref().map_or(0, Vec::len)),
+                    );
+                    // The daemon stopped before it recorded the fan-out items,
+                    // so there are no subagents to wait for
+                    if state == StepState::Running && fan_out && items.is_none() {
+                        interrupted.push(row.id.clone());
+                    }
+                    // Where a review loop stood is not persisted, so the
+                    // attempt starts over
+                    if state == StepState::Running && run.steps[&row.id].review.is_some() {
+                        interrupted.push(row.id.clone());
+                    }
+                    run.runs.insert(
+                        row.id.clone(),
+                        StepRun {
+                            attempt,
+                            subagents: ids,
+                            items: items.unwrap_or_default(),
+                            retry_at: (state == StepState::Retrying)
+                                .then(tokio::time::Instant::now),
+                            review: None,
+                        },
+                    );
+                }
+                _ => {}
+            }
+        }
+
+        // Failed orphans and subagents that were never spawned fail their
+        // step once the loop collects them, which applies its retry policy
+        for step_id in run.scheduler.running_ids() {
+            let step = run.steps[&step_id].clone();
+            let mut ids = run
+                .runs
+                .get(&step_id)
+                .map(|r| r.subagents.clone())
+                .unwrap_or_default();
+            if step.review.is_some() {
+                let base = ids.first().cloned().unwrap_or_default();
+                ids.extend(
+                    orphans
+                        .keys()
+                        .filter(|id| is_review_round_id(&base, id))
+                        .cloned(),
+                );
+            }
+            for sa_id in ids {
+                let Some(sa) = orphans.remove(&sa_id) else {
+                    continue;
+                };
+                if resume
+                    && step.review.is_none()
+                    && let Some(session) = sa.claude_session_id.clone()
+                {
+                    let resumed = match run.instance_step(&step, &sa.id).await {
+                        Ok(instance) => {
+                            run.spawn_instance(
+                                &instance,
+                                &sa.id,
+                                RESUME_PROMPT.to_string(),
+                                Some(session),
+                            )
+                            .await
+                        }
+                        Err(e) => Err(e),
+                    };
+                    match resumed {
+                        Ok(()) => {
+                            report.subagents_resumed += 1;
+                            continue;
+                        }
+                        Err(e) => {
+                            warn!(subagent_id = %sa.id, error = %e, "Failed to resume subagent");
+                        }
+                    }
+                }
+                self.fail_orphan(&sa.id).await?;
+                report.subagents_failed += 1;
+            }
+        }
+
+        run.record_settled(settled).await;
+        for step_id in interrupted {
+            run.fail_step(&step_id, INTERRUPTED_MESSAGE.to_string(), true)
+                .await;
+        }
+        info!(orchestration_id = %orch.id, "Resuming orchestration after restart");
+        tokio::spawn(run.drive(step_notify));
+        Ok(())
+    }
+
+    /// Give up on an orchestration that cannot be rebuilt.
+    async fn fail_orchestration(
+        &self,
+        orchestration_id: &str,
+        orphans: &mut HashMap<String, SubagentRow>,
+        report: &mut RecoveryReport,
+    ) -> Result<(), ManagerError> {
+        self.orchestrations.write().await.remove(orchestration_id);
+        for row in self
+            .db
+            .get_steps_for_orchestration(orchestration_id)
+            .await?
+        {
+            if !step_state_from_db(&row.status).is_terminal() {
+                self.db
+                    .update_step_error(orchestration_id, &row.id, INTERRUPTED_MESSAGE)
+                    .await?;
+                self.db
+                    .update_step_status(orchestration_id, &row.id, "failed", None)
+                    .await?;
+            }
+        }
+        // Subagent IDs of a step start with the orchestration ID
+        let prefix = format!("{orchestration_id}-");
+        let ids: Vec<String> = orphans
+            .keys()
+            .filter(|id| id.starts_with(&prefix))
+            .cloned()
+            .collect();
+        for id in ids {
+            orphans.remove(&id);
+            self.fail_orphan(&id).await?;
+            report.subagents_failed += 1;
+        }
+        self.db
+            .update_orchestration_status(orchestration_id, "failed")
+            .await?;
+        Ok(())
+    }
+
+    /// Continue an interrupted standalone subagent, or mark it failed.
+    async fn resume_or_fail(
+        &self,
+        sa: &SubagentRow,
+        mut config: SubagentConfig,
+        resume: bool,
+        report: &mut RecoveryReport,
+    ) -> Result<(), ManagerError> {
+        if resume && let Some(session) = sa.claude_session_id.clone() {
+            config.resume_session = Some(session);
+            match self.spawn(config).await {
+                Ok(_) => {
+                    report.subagents_resumed += 1;
+                    return Ok(());
+                }
+                Err(e) => warn!(subagent_id = %sa.id, error = %e, "Failed to resume subagent"),
+            }
+        }
+        self.fail_orphan(&sa.id).await?;
+        report.subagents_failed += 1;
+        Ok(())
+    }
+
+    /// Mark a subagent whose process died with the previous daemon failed.
+    async fn fail_orphan(&self, subagent_id: &str) -> Result<(), ManagerError> {
+        self.db
+            .update_subagent_status(subagent_id, "failed", None, Some(INTERRUPTED_MESSAGE))
+            .await?;
+        Ok(())
+    }
+}
+
+/// What [`SubagentManager::recover`] did at startup.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct RecoveryReport {
+    /// Interrupted subagents that continued their Claude session.
+    pub subagents_resumed: usize,
+    /// Interrupted subagents marked failed.
+    pub subagents_failed: usize,
+    /// Orchestrations whose scheduling loop was rebuilt.
+    pub orchestrations_resumed: usize,
+    /// Orchestrations that could not be rebuilt and were marked failed.
+    pub orchestrations_failed: usize,
+}
+
+/// Decode the step definition persisted when the orchestration was created.
+fn decode_step_spec(
+    row: &OrchestrationStepRow,
+) -> Result<betcode_proto::v1::OrchestrationStep, ManagerError> {
+    let invalid = |reason: String| ManagerError::Validation {
+        message: format!("Step '{}' cannot be restored: {reason}", row.id),
+    };
+    let spec = row
+        .spec
+        .as_deref()
+        .ok_or_else(|| invalid("no stored definition".to_string()))?;
+    let bytes = base64_decode(spec).map_err(invalid)?;
+    betcode_proto::v1::OrchestrationStep::decode(bytes.as_slice())
+        .map_err(|e| invalid(e.to_string()))
+}
+
+/// Scheduler state of a persisted step status.
+fn step_state_from_db(status: &str) -> StepState {
+    match status {
+        "running" => StepState::Running,
+        "retrying" => StepState::Retrying,
+        "completed" => StepState::Completed,
+        "failed" => StepState::Failed,
+        "blocked" => StepState::Blocked,
+        "skipped" => StepState::Skipped,
+        _ => StepState::Pending,
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::panic, clippy::expect_used, clippy::unwrap_used)]
+mod tests {
+    use super::*;
+    use crate::orchestration::pool::SubprocessPool;
+    use crate::storage::Database;
+    use betcode_core::db::base64_encode;
+    use betcode_proto::v1::OrchestrationEvent;
+    use betcode_proto::v1::orchestration_event::Event;
+
+    fn test_pool() -> Arc<SubprocessPool> {
+        Arc::new(SubprocessPool::new(3))
+    }
+
+    async fn test_db() -> Database {
+        let db = Database::open_in_memory().await.unwrap();
+        db.create_session("parent-1", "claude-sonnet-4", "/tmp")
+            .await
+            .unwrap();
+        db
+    }
+
+    /// Collect orchestration events until the orchestration finishes.
+    async fn finished_events(
+        rx: &mut broadcast::Receiver<OrchestrationEvent>,
+    ) -> Vec<OrchestrationEvent> {
+        let mut events = Vec::new();
+        loop {
+            let event = tokio::time::timeout(std::time::Duration::from_secs(10), rx.recv())
+                .await
+                .expect("orchestration should finish")
+                .expect("event channel should stay open");
+            let done = matches!(event.event, Some(Event::Completed(_) | Event::Failed(_)));
+            events.push(event);
+            if done {
+                return events;
+            }
+        }
+    }
+
+    /// A manager whose subagents always fail to start.
+    fn failing_manager(db: Database) -> Arc<SubagentManager> {
+        Arc::new(SubagentManager::new(
+            test_pool(),
+            db,
+            "/nonexistent/betcode-test-claude".into(),
+        ))
+    }
+
+    /// Persist a step the way `run_orchestration` does, in the given status.
+    /// A running step links to its subagent, which must be seeded first.
+    async fn seed_step(
+        db: &Database,
+        orch_id: &str,
+        index: i64,
+        step: &betcode_proto::v1::OrchestrationStep,
+        status: &str,
+    ) {
+        let deps = serde_json::to_string(&step.depends_on).unwrap();
+        db.create_orchestration_step(&step.id, orch_id, index, &step.prompt, &deps)
+            .await
+            .unwrap();
+        db.update_step_spec(orch_id, &step.id, &base64_encode(&step.encode_to_vec()))
+            .await
+            .unwrap();
+        if status == "running" {
+            let sa_id = format!("{orch_id}-{}", step.id);
+            db.start_step_attempt(orch_id, &step.id, 1, Some(&sa_id))
+                .await
+                .unwrap();
+        } else if status != "pending" {
+            db.update_step_status(orch_id, &step.id, status, None)
+                .await
+                .unwrap();
+        }
+    }
+
+    /// Record a subagent left running by a previous daemon process.
+    async fn seed_running_subagent(db: &Database, id: &str, claude_session: Option<&str>) {
+        db.create_subagent(
+            id,
+            "parent-1",
+            "work",
+            None,
+            10,
+            false,
+            "[]",
+            Some("/tmp"),
+            0,
+        )
+        .await
+        .unwrap();
+        db.update_subagent_status(id, "running", None, None)
+            .await
+            .unwrap();
+        if let Some(session) = claude_session {
+            db.set_subagent_claude_session(id, session).await.unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn recover_continues_orchestration_interrupted_mid_run() {
+        let db = test_db().await;
+        db.create_orchestration("orch-crash", "parent-1", "dag")
+            .await
+            .unwrap();
+        db.update_orchestration_status("orch-crash", "running")
+            .await
+            .unwrap();
+        let build = make_step("build", "build it", vec![]);
+        let mut test = make_step("test", "test it", vec!["build".into()]);
+        test.max_retries = 1;
+        test.retry_backoff_secs = 1;
+        let report = make_step("report", "report", vec!["test".into()]);
+        seed_step(&db, "orch-crash", 0, &build, "completed").await;
+        db.update_step_result("orch-crash", "build", "built")
+            .await
+            .unwrap();
+        seed_running_subagent(&db, "orch-crash-test", None).await;
+        seed_step(&db, "orch-crash", 1, &test, "running").await;
+        seed_step(&db, "orch-crash", 2, &report, "pending").await;
+
+        // The daemon comes back up with a Claude binary that cannot start
+        let manager = failing_manager(db.clone());
+        let recovered = manager.recover(true).await.unwrap();
+        assert_eq!(
+            recovered,
+            RecoveryReport {
+                subagents_resumed: 0,
+                subagents_failed: 1,
+                orchestrations_resumed: 1,
+                orchestrations_failed: 0,
+            }
+        );
+        let mut rx = manager.subscribe_orchestration("orch-crash").await.unwrap();
+        let events = finished_events(&mut rx).await;
+
+        let orphan = db.get_subagent("orch-crash-test").await.unwrap();
+        assert_eq!(orphan.status, "failed");
+        assert_eq!(orphan.result_summary.as_deref(), Some(INTERRUPTED_MESSAGE));
+
+        // The interrupted step goes through its retry policy
+        let retry = events
+            .iter()
+            .find_map(|e| match &e.event {
+                Some(Event::StepRetrying(r)) => Some(r),
+                _ => None,
+            })
+            .expect("interrupted step should be retried");
+        assert_eq!((retry.step_id.as_str(), retry.next_attempt), ("test", 2));
+        assert!(db.get_subagent("orch-crash-test-r2").await.is_ok());
+
+        match &events.last().unwrap().event {
+            Some(Event::Failed(f)) => {
+                assert_eq!((f.completed_steps, f.failed_steps), (1, 1));
+            }
+            other => panic!("Expected Failed, got {other:?}"),
+        }
+        let step = db
+            .get_orchestration_step("orch-crash", "report")
+            .await
+            .unwrap();
+        assert_eq!(step.status, "blocked");
+        let orch = db.get_orchestration("orch-crash").await.unwrap();
+        assert_eq!(orch.status, "failed");
+    }
+
+    #[tokio::test]
+    async fn recover_fails_orchestration_without_stored_steps() {
+        let db = test_db().await;
+        db.create_orchestration("orch-old", "parent-1", "parallel")
+            .await
+            .unwrap();
+        db.update_orchestration_status("orch-old", "running")
+            .await
+            .unwrap();
+        // Created before step definitions were persisted
+        db.create_orchestration_step("only", "orch-old", 0, "do it", "[]")
+            .await
+            .unwrap();
+        seed_running_subagent(&db, "orch-old-only", Some("sess-1")).await;
+        db.start_step_attempt("orch-old", "only", 1, Some("orch-old-only"))
+            .await
+            .unwrap();
+
+        let manager = failing_manager(db.clone());
+        let recovered = manager.recover(true).await.unwrap();
+        assert_eq!(recovered.orchestrations_failed, 1);
+        assert_eq!(recovered.subagents_failed, 1);
+
+        let orch = db.get_orchestration("orch-old").await.unwrap();
+        assert_eq!(orch.status, "failed");
+        let step = db.get_orchestration_step("orch-old", "only").await.unwrap();
+        assert_eq!(step.status, "failed");
+        let sa = db.get_subagent("orch-old-only").await.unwrap();
+        assert_eq!(sa.status, "failed");
+        assert!(manager.subscribe_orchestration("orch-old").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn recover_marks_unresumable_subagents_failed() {
+        let db = test_db().await;
+        seed_running_subagent(&db, "sa-no-session", None).await;
+        seed_running_subagent(&db, "sa-with-session", Some("sess-7")).await;
+
+        // Resuming "sa-with-session" fails since the binary does not exist
+        let manager = failing_manager(db.clone());
+        let recovered = manager.recover(true).await.unwrap();
+        assert_eq!(recovered.subagents_failed, 2);
+        assert_eq!(recovered.subagents_resumed, 0);
+
+        for id in ["sa-no-session", "sa-with-session"] {
+            let sa = db.get_subagent(id).await.unwrap();
+            assert_eq!(sa.status, "failed");
+            assert_eq!(sa.result_summary.as_deref(), Some(INTERRUPTED_MESSAGE));
+        }
+        assert_eq!(
+            manager.recover(true).await.unwrap(),
+            RecoveryReport::default()
+        );
+    }
+
+    /// Build a minimal `OrchestrationStep` proto for testing.
+    fn make_step(
+        id: &str,
+        prompt: &str,
+        depends_on: Vec<String>,
+    ) -> betcode_proto::v1::OrchestrationStep {
+        betcode_proto::v1::OrchestrationStep {
+            id: id.to_string(),
+            name: id.to_string(),
+            prompt: prompt.to_string(),
+            depends_on,
+            working_directory: std::env::temp_dir().to_string_lossy().into_owned(),
+            ..Default::default()
+        }
+    }
+}
@@ -0,0 +1,1226 @@
+//! Starting orchestrations and the scheduling loop that drives them.
+
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use tokio::sync::{Notify, broadcast};
+use tracing::{error, info, warn};
+
+use betcode_core::db::base64_encode;
+use betcode_core::review;
+use betcode_core::step_template::{self, truncate_output};
+use betcode_proto::v1::orchestration_event::Event;
+use betcode_proto::v1::{
+    OrchestrationCompleted, OrchestrationEvent, OrchestrationFailed, OrchestrationStrategy,
+    StepCompleted, StepFailed, StepFannedOut, StepRetrying, StepReviewStarted, StepReviewed,
+    StepRunCondition, StepSkipped, StepStarted,
+};
+
+use prost::Message as _;
+
+use crate::storage::DatabaseError;
+
+use super::isolation::{self, IsolationError};
+use super::manager::{
+    MAX_RESULT_CHARS, ManagerError, OrchestrationState, SubagentConfig, SubagentManager,
+    now_timestamp,
+};
+use super::scheduler::{DagScheduler, RunCondition, Settled, StepPolicy};
+
+/// Longest output of one upstream step substituted into a prompt, in characters.
+const MAX_STEP_OUTPUT_CHARS: usize = 16 * 1024;
+
+/// Total upstream output substituted into one prompt, in characters.
+const MAX_PROMPT_CONTEXT_CHARS: usize = 48 * 1024;
+
+/// Delay before the first retry of a failed step, in seconds.
+const DEFAULT_RETRY_BACKOFF_SECS: u64 = 5;
+
+/// Longest delay between attempts of a step, in seconds.
+const MAX_RETRY_BACKOFF_SECS: u64 = 600;
+
+/// Most subagents a single fan-out step may start.
+const MAX_FAN_OUT_ITEMS: usize = 32;
+
+/// Worker runs of a reviewed step before it fails, unless the step says otherwise.
+const DEFAULT_REVIEW_ITERATIONS: u32 = 3;
+
+/// Broadcast channel buffer size for orchestration events.
+pub(super) const ORCHESTRATION_BROADCAST_CAPACITY: usize = 256;
+
+impl SubagentManager {
+    /// Subscribe to an orchestration's event broadcast channel.
+    ///
+    /// Returns a `broadcast::Receiver` that receives all `OrchestrationEvent`
+    /// messages for the given orchestration.
+    #[allow(clippy::significant_drop_tightening)]
+    pub async fn subscribe_orchestration(
+        &self,
+        orchestration_id: &str,
+    ) -> Result<broadcast::Receiver<OrchestrationEvent>, ManagerError> {
+        let orchestrations = self.orchestrations.read().await;
+        let state = orchestrations.get(orchestration_id).ok_or_else(|| {
+            ManagerError::OrchestrationNotFound {
+                id: orchestration_id.to_string(),
+            }
+        })?;
+        Ok(state.event_tx.subscribe())
+    }
+
+    /// Point steps that name a worktree (by ID or unique name) at its path.
+    async fn resolve_step_worktrees(
+        &self,
+        steps: &mut [betcode_proto::v1::OrchestrationStep],
+    ) -> Result<(), ManagerError> {
+        if steps.iter().all(|s| s.worktree.is_empty()) {
+            return Ok(());
+        }
+        let worktrees = self.db.list_worktrees(None).await?;
+        for step in steps.iter_mut().filter(|s| !s.worktree.is_empty()) {
+            let by_id = worktrees.iter().find(|w| w.id == step.worktree);
+            let mut by_name = worktrees.iter().filter(|w| w.name == step.worktree);
+            let wt = match (by_id, by_name.next(), by_name.next()) {
+                (Some(wt), _, _) | (None, Some(wt), None) => wt,
+                (None, Some(_), Some(_)) => {
+                    return Err(ManagerError::Validation {
+                        message: format!(
+                            "Step '{}' worktree name '{}' is ambiguous; use its ID",
+                            step.id, step.worktree
+                        ),
+                    });
+                }
+                (None, None, _) => {
+                    return Err(ManagerError::Validation {
+                        message: format!(
+                            "Step '{}' references unknown worktree '{}'",
+                            step.id, step.worktree
+                        ),
+                    });
+                }
+            };
+            step.working_directory.clone_from(&wt.path);
+        }
+        Ok(())
+    }
+
+    /// Reject isolated steps that have no repository to branch from.
+    async fn check_isolated_steps(
+        &self,
+        steps: &[betcode_proto::v1::OrchestrationStep],
+    ) -> Result<(), ManagerError> {
+        for step in steps.iter().filter(|s| s.isolated) {
+            if self.worktrees.is_none() {
+                return Err(ManagerError::Validation {
+                    message: format!(
+                        "Step '{}' is isolated but this daemon cannot create worktrees",
+                        step.id
+                    ),
+                });
+            }
+            if isolation::step_repo(&self.db, &step.working_directory)
+                .await?
+                .is_none()
+            {
+                return Err(ManagerError::Validation {
+                    message: format!(
+                        "Isolated step '{}' must run in a registered repository or one of its worktrees",
+                        step.id
+                    ),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Run an orchestration lifecycle.
+    ///
+    /// This is used by `CreateOrchestration` to kick off the scheduler loop.
+    /// The manager owns the broadcast channel; subscribers connect via
+    /// [`subscribe_orchestration`].
+    #[allow(clippy::too_many_lines)]
+    pub async fn run_orchestration(
+        self: &Arc<Self>,
+        orchestration_id: String,
+        parent_session_id: String,
+        strategy: OrchestrationStrategy,
+        mut steps: Vec<betcode_proto::v1::OrchestrationStep>,
+    ) -> Result<(), ManagerError> {
+        // Validate and create scheduler
+        self.resolve_step_worktrees(&mut steps).await?;
+        self.check_isolated_steps(&steps).await?;
+        let scheduler = build_scheduler(&steps)?;
+
+        // Create DB records for steps
+        let strategy_str = match strategy {
+            OrchestrationStrategy::Sequential => "sequential",
+            OrchestrationStrategy::Dag => "dag",
+            OrchestrationStrategy::ReviewLoop => "review_loop",
+            // Parallel and Unspecified both default to parallel
+            OrchestrationStrategy::Parallel | OrchestrationStrategy::Unspecified => "parallel",
+        };
+
+        self.db
+            .create_orchestration(&orchestration_id, &parent_session_id, strategy_str)
+            .await?;
+        self.db
+            .update_orchestration_status(&orchestration_id, "running")
+            .await?;
+
+        for (i, step) in steps.iter().enumerate() {
+            let deps_json =
+                serde_json::to_string(&step.depends_on).unwrap_or_else(|_| "[]".to_string());
+            #[allow(clippy::cast_possible_wrap)]
+            self.db
+                .create_orchestration_step(
+                    &step.id,
+                    &orchestration_id,
+                    i as i64,
+                    &step.prompt,
+                    &deps_json,
+                )
+                .await?;
+            self.db
+                .update_step_spec(
+                    &orchestration_id,
+                    &step.id,
+                    &base64_encode(&step.encode_to_vec()),
+                )
+                .await?;
+        }
+
+        // Create broadcast channel and Notify for this orchestration
+        let (event_tx, _) = broadcast::channel(ORCHESTRATION_BROADCAST_CAPACITY);
+        let step_notify = Arc::new(Notify::new());
+        {
+            let mut orchestrations = self.orchestrations.write().await;
+            orchestrations.insert(
+                orchestration_id.clone(),
+                OrchestrationState {
+                    event_tx: event_tx.clone(),
+                    step_notify: Arc::clone(&step_notify),
+                },
+            );
+        }
+
+        // Build step configs from proto steps (owned, for 'static in tokio::spawn)
+        let step_configs: HashMap<String, betcode_proto::v1::OrchestrationStep> =
+            steps.into_iter().map(|s| (s.id.clone(), s)).collect();
+
+        let run = OrchestrationRun {
+            manager: Arc::clone(self),
+            orch_id: orchestration_id,
+            parent_session_id,
+            steps: step_configs,
+            scheduler,
+            event_tx,
+            results: HashMap::new(),
+            runs: HashMap::new(),
+            completed: 0,
+            failed: 0,
+            tolerated: 0,
+            skipped: 0,
+            persist_error: None,
+        };
+        tokio::spawn(run.drive(step_notify));
+
+        Ok(())
+    }
+}
+
+/// The current attempt of a started step.
+pub(super) struct StepRun {
+    /// Attempt number, starting at 1.
+    pub(super) attempt: u32,
+    /// Subagents of this attempt: one, or one per fan-out item.
+    pub(super) subagents: Vec<String>,
+    /// Fan-out items, parallel to `subagents`; empty for ordinary steps.
+    pub(super) items: Vec<String>,
+    /// When the next attempt is due, while the step is retrying.
+    pub(super) retry_at: Option<tokio::time::Instant>,
+    /// Progress of the review loop, for steps with a reviewer.
+    pub(super) review: Option<ReviewRun>,
+}
+
+/// The review loop of one attempt of a reviewed step.
+pub(super) struct ReviewRun {
+    /// Worker runs so far, starting at 1.
+    pub(super) iteration: u32,
+    /// Whether the reviewer, rather than the worker, is running.
+    pub(super) reviewing: bool,
+    /// Prompt the attempt's first worker was given.
+    pub(super) task: String,
+    /// Output of the latest worker run.
+    pub(super) output: String,
+    /// First worker of the attempt. Later workers and the reviewers run in
+    /// its working directory, and its worktree is what an isolated step merges.
+    pub(super) owner: String,
+}
+
+/// State of one orchestration's scheduling loop.
+pub(super) struct OrchestrationRun {
+    pub(super) manager: Arc<SubagentManager>,
+    pub(super) orch_id: String,
+    pub(super) parent_session_id: String,
+    pub(super) steps: HashMap<String, betcode_proto::v1::OrchestrationStep>,
+    pub(super) scheduler: DagScheduler,
+    pub(super) event_tx: broadcast::Sender<OrchestrationEvent>,
+    /// Output of completed steps, for downstream prompts.
+    pub(super) results: HashMap<String, String>,
+    pub(super) runs: HashMap<String, StepRun>,
+    pub(super) completed: i32,
+    /// Failures that fail the orchestration.
+    pub(super) failed: i32,
+    /// Failures of `continue_on_failure` steps.
+    pub(super) tolerated: i32,
+    pub(super) skipped: i32,
+    /// First step state that could not be written to the database. The
+    /// run stops and fails once set, since recovery relies on that state.
+    pub(super) persist_error: Option<String>,
+}
+
+impl OrchestrationRun {
+    /// Run steps until every one has settled, then report the outcome.
+    pub(super) async fn drive(mut self, step_notify: Arc<Notify>) {
+        loop {
+            self.collect_finished().await;
+            if self.persist_error.is_none() {
+                self.start_due_steps().await;
+            }
+            if self.persist_error.is_some() {
+                self.cancel_running().await;
+                break;
+            }
+            if self.scheduler.is_complete() {
+                break;
+            }
+
+            let next_retry = self.runs.values().filter_map(|r| r.retry_at).min();
+            if self.scheduler.running_ids().is_empty() && next_retry.is_none() {
+                error!(orchestration_id = %self.orch_id, "Orchestration stalled with unsettled steps");
+                break;
+            }
+
+            // Wait for a subagent to finish (event-driven via Notify) or a retry to fall due
+            match next_retry {
+                Some(at) => {
+                    tokio::select! {
+                        () = step_notify.notified() => {}
+                        () = tokio::time::sleep_until(at) => {}
+                    }
+                }
+                None => step_notify.notified().await,
+            }
+        }
+        self.finish().await;
+    }
+
+    /// Start ready steps and retries that are due.
+    ///
+    /// Repeats until nothing is left to start, since a step failing to start
+    /// can make others ready.
+    async fn start_due_steps(&mut self) {
+        loop {
+            let now = tokio::time::Instant::now();
+            let mut due: Vec<(String, u32)> = self
+                .scheduler
+                .next_ready()
+                .into_iter()
+                .map(|id| (id, 1))
+                .collect();
+            for (id, run) in &self.runs {
+                if run.retry_at.is_some_and(|at| at <= now) {
+                    due.push((id.clone(), run.attempt + 1));
+                }
+            }
+            if due.is_empty() {
+                return;
+            }
+            for (step_id, attempt) in due {
+                self.start_step(&step_id, attempt).await;
+                if self.persist_error.is_some() {
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Spawn the subagent(s) for one attempt of a step.
+    async fn start_step(&mut self, step_id: &str, attempt: u32) {
+        let Some(step) = self.steps.get(step_id).cloned() else {
+            return;
+        };
+        let (instances, items) = self.plan_attempt(&step, attempt);
+
+        let started = self
+            .manager
+            .db
+            .start_step_attempt(&self.orch_id, step_id, i64::from(attempt), None)
+            .await;
+        self.scheduler.mark_running(step_id);
+        if !self.persisted(step_id, started) {
+            return;
+        }
+
+        if items.len() > MAX_FAN_OUT_ITEMS {
+            let error = format!(
+                "Fan-out over {} items exceeds the limit of {MAX_FAN_OUT_ITEMS}",
+                items.len()
+            );
+            self.fail_step(step_id, error, false).await;
+            return;
+        }
+        if !step.fan_out_from.is_empty() {
+            let items_json = serde_json::to_string(&items).unwrap_or_else(|_| "[]".to_string());
+            let stored = self
+                .manager
+                .db
+                .update_step_fan_out_items(&self.orch_id, step_id, &items_json)
+                .await;
+            if !self.persisted(step_id, stored) {
+                return;
+            }
+        }
+
+        let Some(spawned) = self.spawn_attempt(&step, instances).await else {
+            return;
+        };
+        // Linked only now: the subagent row must exist for the foreign key
+        if let Some(first) = spawned.first() {
+            let linked = self
+                .manager
+                .db
+                .update_step_status(&self.orch_id, step_id, "running", Some(first))
+                .await;
+            self.persisted(step_id, linked);
+        }
+
+        self.send(Event::StepStarted(StepStarted {
+            step_id: step_id.to_string(),
+            subagent_id: spawned.first().cloned().unwrap_or_default(),
+            name: step.name.clone(),
+            attempt,
+            iteration: 1,
+        }));
+        if !step.fan_out_from.is_empty() {
+            let nothing_to_do = spawned.is_empty();
+            self.send(Event::StepFannedOut(StepFannedOut {
+                step_id: step_id.to_string(),
+                items,
+                subagent_ids: spawned,
+                attempt,
+            }));
+            // No subagent will wake the loop for an empty fan-out
+            if nothing_to_do {
+                self.complete_step(step_id, String::new()).await;
+            }
+        }
+    }
+
+    /// Record a new attempt as the step's current run.
+    ///
+    /// Returns the attempt's subagents with their prompts, and the fan-out
+    /// items.
+    fn plan_attempt(
+        &mut self,
+        step: &betcode_proto::v1::OrchestrationStep,
+        attempt: u32,
+    ) -> (Vec<(String, String)>, Vec<String>) {
+        let prompt = build_step_prompt(step, &self.results);
+        let items = if step.fan_out_from.is_empty() {
+            Vec::new()
+        } else {
+            let source = self.results.get(&step.fan_out_from);
+            step_template::fan_out_items(source.map_or("", String::as_str))
+        };
+
+        let fan_out = (!step.fan_out_from.is_empty()).then_some(items.len());
+        let ids = step_subagent_ids(&self.orch_id, &step.id, attempt, fan_out);
+        let instances: Vec<(String, String)> = if fan_out.is_none() {
+            ids.into_iter().map(|id| (id, prompt.clone())).collect()
+        } else {
+            ids.into_iter()
+                .zip(&items)
+                .map(|(id, item)| (id, item_prompt(&prompt, item)))
+                .collect()
+        };
+        let review = step.review.as_ref().map(|_| ReviewRun {
+            iteration: 1,
+            reviewing: false,
+            task: prompt.clone(),
+            output: String::new(),
+            owner: instances
+                .first()
+                .map(|(id, _)| id.clone())
+                .unwrap_or_default(),
+        });
+        self.runs.insert(
+            step.id.clone(),
+            StepRun {
+                attempt,
+                subagents: instances.iter().map(|(id, _)| id.clone()).collect(),
+                items: items.clone(),
+                retry_at: None,
+                review,
+            },
+        );
+        (instances, items)
+    }
+
+    /// Spawn the subagents of an attempt.
+    ///
+    /// If one fails to start, the others are cancelled, the step fails and
+    /// `None` is returned.
+    async fn spawn_attempt(
+        &mut self,
+        step: &betcode_proto::v1::OrchestrationStep,
+        instances: Vec<(String, String)>,
+    ) -> Option<Vec<String>> {
+        let mut spawned: Vec<String> = Vec::new();
+        for (sa_id, prompt) in instances {
+            let spawned_instance = match self.instance_step(step, &sa_id).await {
+                Ok(instance) => self.spawn_instance(&instance, &sa_id, prompt, None).await,
+                Err(e) => Err(e),
+            };
+            if let Err(e) = spawned_instance {
+                error!(step_id = %step.id, error = %e, "Failed to spawn step subagent");
+                for id in &spawned {
+                    let _ = self
+                        .manager
+                        .cancel(id, "Another instance of the step failed to start")
+                        .await;
+                }
+                self.fail_step(&step.id, e.to_string(), true).await;
+                return None;
+            }
+            spawned.push(sa_id);
+        }
+        Some(spawned)
+    }
+
+    /// The step as one of its subagents runs it: in its own worktree when
+    /// the step is isolated.
+    pub(super) async fn instance_step(
+        &self,
+        step: &betcode_proto::v1::OrchestrationStep,
+        sa_id: &str,
+    ) -> Result<betcode_proto::v1::OrchestrationStep, ManagerError> {
+        let mut instance = step.clone();
+        if !step.isolated {
+            return Ok(instance);
+        }
+        let spawn_failed = |reason: String| ManagerError::SpawnFailed {
+            reason: format!("Cannot create step worktree: {reason}"),
+        };
+        let Some(worktrees) = &self.manager.worktrees else {
+            return Err(spawn_failed("worktrees are not available".to_string()));
+        };
+        let repo = isolation::step_repo(&self.manager.db, &step.working_directory)
+            .await?
+            .ok_or_else(|| spawn_failed("no registered repository".to_string()))?;
+        let wt = isolation::step_worktree(worktrees, &repo, &self.orch_id, sa_id)
+            .await
+            .map_err(|e| spawn_failed(e.to_string()))?;
+        instance.working_directory = wt.path;
+        Ok(instance)
+    }
+
+    /// Merge the worktrees of an isolated step into the integration branch.
+    async fn merge_isolated(
+        &self,
+        step: &betcode_proto::v1::OrchestrationStep,
+    ) -> Result<(), IsolationError> {
+        let (Some(worktrees), Some(run)) = (&self.manager.worktrees, self.runs.get(&step.id))
+        else {
+            return Ok(());
+        };
+        let Some(repo) = isolation::step_repo(&self.manager.db, &step.working_directory).await?
+        else {
+            return Ok(());
+        };
+        // The workers of a review loop share the first one's worktree
+        let subagents = run
+            .review
+            .as_ref()
+            .map_or(run.subagents.as_slice(), |r| std::slice::from_ref(&r.owner));
+        for sa_id in subagents {
+            let message = format!("{} ({sa_id})", step.name);
+            isolation::merge_step_worktree(
+                worktrees,
+                &repo,
+                &self.orch_id,
+                sa_id,
+                &message,
+                step.merge_strategy(),
+            )
+            .await?;
+        }
+        info!(
+            step_id = %step.id,
+            branch = %isolation::integration_branch(&self.orch_id),
+            "Merged isolated step"
+        );
+        Ok(())
+    }
+
+    /// Spawn one subagent for a step, tying it to this orchestration.
+    ///
+    /// With `resume_session` the subagent continues an interrupted Claude
+    /// session under its existing ID.
+    pub(super) async fn spawn_instance(
+        &self,
+        step: &betcode_proto::v1::OrchestrationStep,
+        sa_id: &str,
+        prompt: String,
+        resume_session: Option<String>,
+    ) -> Result<(), ManagerError> {
+        let working_dir = if step.working_directory.is_empty() {
+            std::env::current_dir().unwrap_or_default()
+        } else {
+            PathBuf::from(&step.working_directory)
+        };
+        let config = SubagentConfig {
+            id: sa_id.to_string(),
+            parent_session_id: self.parent_session_id.clone(),
+            prompt,
+            model: if step.model.is_empty() {
+                None
+            } else {
+                Some(step.model.clone())
+            },
+            working_directory: working_dir,
+            allowed_tools: step.allowed_tools.clone(),
+            max_turns: step.max_turns,
+            auto_approve: step.auto_approve,
+            timeout_secs: u64::from(step.timeout_secs),
+            resume_session,
+        };
+
+        // Register subagent -> orchestration mapping before spawning
+        let mapping = &self.manager.subagent_to_orchestration;
+        mapping
+            .write()
+            .await
+            .insert(sa_id.to_string(), self.orch_id.clone());
+        let result = self.manager.spawn(config).await;
+        if result.is_err() {
+            mapping.write().await.remove(sa_id);
+        }
+        result.map(|_| ())
+    }
+
+    /// Settle running steps whose subagents have all exited.
+    async fn collect_finished(&mut self) {
+        for step_id in self.scheduler.running_ids() {
+            if self.persist_error.is_some() {
+                return;
+            }
+            let Some(run) = self.runs.get(&step_id) else {
+                continue;
+            };
+            let mut outputs = Vec::with_capacity(run.subagents.len());
+            let mut failure = None;
+            let mut finished = true;
+            for sa_id in &run.subagents {
+                if self.manager.is_running(sa_id).await {
+                    finished = false;
+                    break;
+                }
+                match self.manager.db.get_subagent(sa_id).await {
+                    Ok(sa) if sa.status == "completed" => {
+                        outputs.push(sa.result_summary.unwrap_or_default());
+                    }
+                    Ok(sa) if sa.status == "failed" || sa.status == "cancelled" => {
+                        failure.get_or_insert_with(|| {
+                            sa.result_summary
+                                .unwrap_or_else(|| "Unknown failure".to_string())
+                        });
+                    }
+                    // Still pending — check again next iteration
+                    Ok(_) => finished = false,
+                    Err(e) => {
+                        failure.get_or_insert_with(|| format!("Subagent {sa_id} lost: {e}"));
+                    }
+                }
+            }
+            if !finished {
+                continue;
+            }
+
+            let reviewed = run.review.is_some();
+            match failure {
+                Some(error) => self.fail_step(&step_id, error, true).await,
+                None if reviewed => {
+                    let output = outputs.into_iter().next().unwrap_or_default();
+                    self.advance_review(&step_id, output).await;
+                }
+                None => {
+                    let summary = combine_outputs(&run.items, outputs);
+                    self.complete_step(&step_id, summary).await;
+                }
+            }
+        }
+    }
+
+    /// Move a reviewed step on once its worker or reviewer has finished.
+    ///
+    /// A finished worker is followed by the reviewer. An approval completes
+    /// the step with the worker's output; otherwise the worker runs again with
+    /// the critique until the step runs out of iterations and fails.
+    async fn advance_review(&mut self, step_id: &str, output: String) {
+        let Some(step) = self.steps.get(step_id).cloned() else {
+            return;
+        };
+        let Some(reviewer) = step.review.clone() else {
+            return;
+        };
+        let Some(state) = self.runs.get_mut(step_id).and_then(|r| r.review.as_mut()) else {
+            return;
+        };
+        let iteration = state.iteration;
+        let owner = state.owner.clone();
+        let max_iterations = review_iterations(&reviewer);
+
+        if !state.reviewing {
+            state.reviewing = true;
+            state.output = output;
+            let prompt = review::review_prompt(
+                &reviewer.prompt,
+                &state.task,
+                &state.output,
+                MAX_STEP_OUTPUT_CHARS,
+            );
+            self.start_reviewer(&step, &reviewer, &owner, iteration, prompt)
+                .await;
+            return;
+        }
+
+        let verdict = review::parse_verdict(&output);
+        state.reviewing = false;
+        let task = state.task.clone();
+        let worker_output = state.output.clone();
+        info!(
+            step_id,
+            iteration,
+            approved = verdict.approved,
+            "Step reviewed"
+        );
+        self.send(Event::Reviewed(StepReviewed {
+            step_id: step_id.to_string(),
+            iteration,
+            max_iterations,
+            approved: verdict.approved,
+            critique: verdict.critique.clone(),
+        }));
+        if verdict.approved {
+            self.complete_step(step_id, worker_output).await;
+            return;
+        }
+        if iteration >= max_iterations {
+            let error = format!(
+                "Reviewer did not approve after {iteration} iteration(s): {}",
+                truncate_output(&verdict.critique, MAX_STEP_OUTPUT_CHARS)
+            );
+            self.fail_step(step_id, error, true).await;
+            return;
+        }
+
+        let iteration = iteration + 1;
+        let sa_id = format!("{owner}-i{iteration}");
+        let prompt = review::revision_prompt(&task, &verdict.critique, MAX_STEP_OUTPUT_CHARS);
+        if let Err(e) = self.spawn_round(&step, &owner, &sa_id, prompt, None).await {
+            self.fail_step(step_id, e.to_string(), true).await;
+            return;
+        }
+        let attempt = self.runs.get_mut(step_id).map_or(1, |run| {
+            if let Some(state) = run.review.as_mut() {
+                state.iteration = iteration;
+            }
+            run.attempt
+        });
+        self.send(Event::StepStarted(StepStarted {
+            step_id: step_id.to_string(),
+            subagent_id: sa_id,
+            name: step.name.clone(),
+            attempt,
+            iteration,
+        }));
+    }
+
+    /// Spawn the reviewer of a worker's output.
+    async fn start_reviewer(
+        &mut self,
+        step: &betcode_proto::v1::OrchestrationStep,
+        reviewer: &betcode_proto::v1::StepReview,
+        owner: &str,
+        iteration: u32,
+        prompt: String,
+    ) {
+        let sa_id = format!("{owner}-review{iteration}");
+        if let Err(e) = self
+            .spawn_round(step, owner, &sa_id, prompt, Some(reviewer))
+            .await
+        {
+            self.fail_step(&step.id, format!("Failed to start reviewer: {e}"), true)
+                .await;
+            return;
+        }
+        self.send(Event::ReviewStarted(StepReviewStarted {
+            step_id: step.id.clone(),
+            subagent_id: sa_id,
+            iteration,
+        }));
+    }
+
+    /// Spawn the next worker or reviewer of a review loop in the working
+    /// directory of the attempt's first worker.
+    async fn spawn_round(
+        &mut self,
+        step: &betcode_proto::v1::OrchestrationStep,
+        owner: &str,
+        sa_id: &str,
+        prompt: String,
+        reviewer: Option<&betcode_proto::v1::StepReview>,
+    ) -> Result<(), ManagerError> {
+        let mut instance = self.instance_step(step, owner).await?;
+        if let Some(reviewer) = reviewer {
+            instance.model.clone_from(&reviewer.model);
+            instance.allowed_tools.clone_from(&reviewer.allowed_tools);
+            instance.auto_approve = reviewer.auto_approve;
+            instance.max_turns = reviewer.max_turns;
+        }
+        if let Some(run) = self.runs.get_mut(&step.id) {
+            run.subagents = vec![sa_id.to_string()];
+        }
+        self.spawn_instance(&instance, sa_id, prompt, None).await
+    }
+
+    async fn complete_step(&mut self, step_id: &str, summary: String) {
+        if let Some(step) = self.steps.get(step_id).filter(|s| s.isolated).cloned()
+            && let Err(e) = self.merge_isolated(&step).await
+        {
+            let conflicts = match &e {
+                IsolationError::Conflict { files, .. } => files.clone(),
+                _ => Vec::new(),
+            };
+            self.fail_step_with(step_id, e.to_string(), true, conflicts)
+                .await;
+            return;
+        }
+        let db = &self.manager.db;
+        let stored = match db
+            .update_step_result(&self.orch_id, step_id, &summary)
+            .await
+        {
+            Ok(()) => {
+                db.update_step_status(&self.orch_id, step_id, "completed", None)
+                    .await
+            }
+            Err(e) => Err(e),
+        };
+        if !self.persisted(step_id, stored) {
+            return;
+        }
+        self.results.insert(step_id.to_string(), summary.clone());
+        self.runs.remove(step_id);
+        self.completed += 1;
+
+        let settled = self.scheduler.finish(step_id, true);
+        #[allow(clippy::cast_possible_truncation, clippy::cast_possible_wrap)]
+        let total = self.scheduler.total_steps() as i32;
+        self.send(Event::StepCompleted(StepCompleted {
+            step_id: step_id.to_string(),
+            result_summary: summary,
+            completed_count: self.completed,
+            total_count: total,
+        }));
+        self.record_settled(settled).await;
+    }
+
+    /// Schedule another attempt of a failed step, or fail it for good.
+    pub(super) async fn fail_step(&mut self, step_id: &str, error: String, retryable: bool) {
+        self.fail_step_with(step_id, error, retryable, Vec::new())
+            .await;
+    }
+
+    /// [`fail_step`](Self::fail_step), reporting the files that kept an
+    /// isolated step from merging.
+    async fn fail_step_with(
+        &mut self,
+        step_id: &str,
+        error: String,
+        retryable: bool,
+        conflicts: Vec<String>,
+    ) {
+        let db = &self.manager.db;
+        let stored = db.update_step_error(&self.orch_id, step_id, &error).await;
+        if !self.persisted(step_id, stored) {
+            return;
+        }
+        let (max_retries, backoff_secs) = self
+            .steps
+            .get(step_id)
+            .map_or((0, 0), |s| (s.max_retries, s.retry_backoff_secs));
+        let max_attempts = max_retries.saturating_add(1);
+
+        if retryable
+            && let Some(run) = self.runs.get_mut(step_id)
+            && run.attempt < max_attempts
+        {
+            let delay = retry_delay(backoff_secs, run.attempt);
+            run.retry_at = Some(tokio::time::Instant::now() + delay);
+            let next_attempt = run.attempt + 1;
+            let stored = self
+                .manager
+                .db
+                .update_step_status(&self.orch_id, step_id, "retrying", None)
+                .await;
+            if !self.persisted(step_id, stored) {
+                return;
+            }
+            self.scheduler.mark_retrying(step_id);
+            warn!(step_id, next_attempt, error = %error, "Step failed, retrying");
+            self.send(Event::StepRetrying(StepRetrying {
+                step_id: step_id.to_string(),
+                error_message: error,
+                next_attempt,
+                max_attempts,
+                retry_in_secs: u32::try_from(delay.as_secs()).unwrap_or(u32::MAX),
+            }));
+            return;
+        }
+
+        let stored = self
+            .manager
+            .db
+            .update_step_status(&self.orch_id, step_id, "failed", None)
+            .await;
+        if !self.persisted(step_id, stored) {
+            return;
+        }
+        self.runs.remove(step_id);
+        let tolerated = self.scheduler.policy(step_id).continue_on_failure;
+        if tolerated {
+            self.tolerated += 1;
+        } else {
+            self.failed += 1;
+        }
+
+        let settled = self.scheduler.finish(step_id, false);
+        self.send(Event::StepFailed(StepFailed {
+            step_id: step_id.to_string(),
+            error_message: error,
+            blocked_steps: settled.blocked.clone(),
+            tolerated,
+            conflicts,
+        }));
+        self.record_settled(settled).await;
+    }
+
+    /// Persist and report steps settled without running.
+    pub(super) async fn record_settled(&mut self, settled: Settled) {
+        for id in &settled.blocked {
+            let stored = self
+                .manager
+                .db
+                .update_step_status(&self.orch_id, id, "blocked", None)
+                .await;
+            self.persisted(id, stored);
+        }
+        for id in settled.skipped {
+            let reason = match self.scheduler.policy(&id).run_if {
+                RunCondition::OnFailure => "No dependency failed",
+                RunCondition::OnSuccess | RunCondition::Always => "A dependency was skipped",
+            };
+            let db = &self.manager.db;
+            let stored = match db.update_step_error(&self.orch_id, &id, reason).await {
+                Ok(()) => {
+                    db.update_step_status(&self.orch_id, &id, "skipped", None)
+                        .await
+                }
+                Err(e) => Err(e),
+            };
+            self.persisted(&id, stored);
+            self.skipped += 1;
+            self.send(Event::StepSkipped(StepSkipped {
+                step_id: id,
+                reason: reason.to_string(),
+            }));
+        }
+    }
+
+    /// Record the final orchestration status and drop its live state.
+    async fn finish(self) {
+        #[allow(clippy::cast_possible_truncation, clippy::cast_possible_wrap)]
+        let total = self.scheduler.total_steps() as i32;
+        let (final_status, final_event) = if let Some(error) = &self.persist_error {
+            (
+                "failed",
+                Event::Failed(OrchestrationFailed {
+                    error_message: error.clone(),
+                    completed_steps: self.completed,
+                    failed_steps: self.failed,
+                }),
+            )
+        } else if self.failed > 0 {
+            (
+                "failed",
+                Event::Failed(OrchestrationFailed {
+                    error_message: format!("{} step(s) failed", self.failed),
+                    completed_steps: self.completed,
+                    failed_steps: self.failed,
+                }),
+            )
+        } else {
+            (
+                "completed",
+                Event::Completed(OrchestrationCompleted {
+                    total_steps: total,
+                    succeeded: self.completed,
+                    failed: self.tolerated,
+                    skipped: self.skipped,
+                }),
+            )
+        };
+
+        if let Err(e) = self
+            .manager
+            .db
+            .update_orchestration_status(&self.orch_id, final_status)
+            .await
+        {
+            warn!(
+                orchestration_id = %self.orch_id,
+                status = final_status,
+                error = %e,
+                "Failed to persist orchestration status"
+            );
+        }
+        self.send(final_event);
+
+        // Clean up orchestration state
+        self.manager
+            .orchestrations
+            .write()
+            .await
+            .remove(&self.orch_id);
+
+        info!(
+            orchestration_id = %self.orch_id,
+            status = final_status,
+            completed = self.completed,
+            failed = self.failed,
+            skipped = self.skipped,
+            "Orchestration finished"
+        );
+    }
+
+    /// Whether a write of step state succeeded. A failed write is logged
+    /// and stops the run.
+    fn persisted(&mut self, step_id: &str, result: Result<(), DatabaseError>) -> bool {
+        let Err(e) = result else {
+            return true;
+        };
+        warn!(
+            orchestration_id = %self.orch_id,
+            step_id,
+            error = %e,
+            "Failed to persist step state"
+        );
+        self.persist_error
+            .get_or_insert_with(|| format!("Cannot persist state of step {step_id}: {e}"));
+        false
+    }
+
+    /// Cancel the subagents of every started step.
+    async fn cancel_running(&self) {
+        for sa_id in self.runs.values().flat_map(|run| &run.subagents) {
+            let _ = self
+                .manager
+                .cancel(sa_id, "The orchestration state cannot be persisted")
+                .await;
+        }
+    }
+
+    fn send(&self, event: Event) {
+        let _ = self.event_tx.send(OrchestrationEvent {
+            orchestration_id: self.orch_id.clone(),
+            timestamp: Some(now_timestamp()),
+            event: Some(event),
+        });
+    }
+}
+
+/// Validate an orchestration's steps and build its scheduler.
+pub(super) fn build_scheduler(
+    steps: &[betcode_proto::v1::OrchestrationStep],
+) -> Result<DagScheduler, ManagerError> {
+    let step_ids: Vec<String> = steps.iter().map(|s| s.id.clone()).collect();
+    let dep_map: HashMap<String, Vec<String>> = steps
+        .iter()
+        .map(|s| (s.id.clone(), s.depends_on.clone()))
+        .collect();
+
+    let prompts: HashMap<String, String> = steps
+        .iter()
+        .map(|s| (s.id.clone(), s.prompt.clone()))
+        .collect();
+    step_template::validate_refs(&prompts, &dep_map).map_err(|e| ManagerError::Validation {
+        message: e.to_string(),
+    })?;
+
+    let mut policies = HashMap::new();
+    for step in steps {
+        if !step.fan_out_from.is_empty() {
+            betcode_core::dag::validate_fan_out(&step.id, &step.fan_out_from, &step.depends_on)
+                .map_err(|e| ManagerError::Validation {
+                    message: e.to_string(),
+                })?;
+        }
+        if let Some(reviewer) = &step.review {
+            if !step.fan_out_from.is_empty() {
+                return Err(ManagerError::Validation {
+                    message: format!("Step '{}' cannot both fan out and be reviewed", step.id),
+                });
+            }
+            if reviewer.prompt.trim().is_empty() {
+                return Err(ManagerError::Validation {
+                    message: format!("Reviewer of step '{}' has an empty prompt", step.id),
+                });
+            }
+        }
+        policies.insert(step.id.clone(), step_policy(step));
+    }
+
+    DagScheduler::with_policies(step_ids, dep_map, policies)
+}
+
+/// Subagent IDs of one attempt of a step.
+///
+/// IDs are derived rather than random so a restarted daemon can find the
+/// subagents of an attempt. `fan_out` is the number of fan-out items.
+pub(super) fn step_subagent_ids(
+    orch_id: &str,
+    step_id: &str,
+    attempt: u32,
+    fan_out: Option<usize>,
+) -> Vec<String> {
+    let base_id = if attempt == 1 {
+        format!("{orch_id}-{step_id}")
+    } else {
+        format!("{orch_id}-{step_id}-r{attempt}")
+    };
+    match fan_out {
+        None => vec![base_id],
+        Some(n) => (0..n).map(|i| format!("{base_id}-{i}")).collect(),
+    }
+}
+
+/// Whether `id` is a later worker or a reviewer of the review loop whose
+/// first worker is `owner`.
+pub(super) fn is_review_round_id(owner: &str, id: &str) -> bool {
+    id.strip_prefix(owner)
+        .and_then(|rest| {
+            rest.strip_prefix("-review")
+                .or_else(|| rest.strip_prefix("-i"))
+        })
+        .is_some_and(|n| !n.is_empty() && n.bytes().all(|b| b.is_ascii_digit()))
+}
+
+/// Worker runs a reviewed step gets before it fails.
+const fn review_iterations(reviewer: &betcode_proto::v1::StepReview) -> u32 {
+    if reviewer.max_iterations == 0 {
+        DEFAULT_REVIEW_ITERATIONS
+    } else {
+        reviewer.max_iterations
+    }
+}
+
+/// Scheduling options a step asks for.
+fn step_policy(step: &betcode_proto::v1::OrchestrationStep) -> StepPolicy {
+    StepPolicy {
+        run_if: match step.run_if() {
+            StepRunCondition::OnFailure => RunCondition::OnFailure,
+            StepRunCondition::Always => RunCondition::Always,
+            StepRunCondition::Unspecified => RunCondition::OnSuccess,
+        },
+        continue_on_failure: step.continue_on_failure,
+    }
+}
+
+/// Delay before the attempt after `attempt`, doubling each time.
+fn retry_delay(backoff_secs: u32, attempt: u32) -> std::time::Duration {
+    let base = if backoff_secs == 0 {
+        DEFAULT_RETRY_BACKOFF_SECS
+    } else {
+        u64::from(backoff_secs)
+    };
+    let factor = 1u64
+        .checked_shl(attempt.saturating_sub(1))
+        .unwrap_or(u64::MAX);
+    std::time::Duration::from_secs(base.saturating_mul(factor).min(MAX_RETRY_BACKOFF_SECS))
+}
+
+/// Prompt for one fan-out instance.
+///
+/// The item replaces `{{item}}`; a prompt without the placeholder gets the
+/// item appended.
+fn item_prompt(prompt: &str, item: &str) -> String {
+    let rendered = step_template::render_item(prompt, item);
+    if rendered == prompt {
+        format!("{prompt}\n\nItem: {item}")
+    } else {
+        rendered
+    }
+}
+
+/// Result of a step from the output of its subagents.
+///
+/// Fan-out instances each get a heading naming their item.
+fn combine_outputs(items: &[String], outputs: Vec<String>) -> String {
+    if items.is_empty() {
+        return outputs.into_iter().next().unwrap_or_default();
+    }
+    let mut combined = String::new();
+    for (item, output) in items.iter().zip(outputs) {
+        let _ = write!(combined, "## {item}\n\n{}\n\n", output.trim());
+    }
+    truncate_output(combined.trim_end(), MAX_RESULT_CHARS)
+}
+
+/// Build the prompt a step is dispatched with.
+///
+/// `{{steps.<id>.result}}` references are replaced with upstream output; a
+/// prompt without references gets the output of each direct dependency
+/// prepended as context instead. Either way the upstream output is capped.
+fn build_step_prompt(
+    step: &betcode_proto::v1::OrchestrationStep,
+    results: &HashMap<String, String>,
+) -> String {
+    if step_template::step_refs(&step.id, &step.prompt).is_ok_and(|refs| !refs.is_empty()) {
+        return step_template::render(
+            &step.prompt,
+            |id: &str| results.get(id).map(String::as_str),
+            MAX_STEP_OUTPUT_CHARS,
+            MAX_PROMPT_CONTEXT_CHARS,
+        );
+    }
+
+    let mut full_prompt = String::new();
+    let mut budget = MAX_PROMPT_CONTEXT_CHARS;
+    for dep_id in &step.depends_on {
+        if let Some(result) = results.get(dep_id) {
+            let result = truncate_output(result, MAX_STEP_OUTPUT_CHARS.min(budget));
+            budget = budget.saturating_sub(result.chars().count());
+            let _ = write!(full_prompt, "[Context from step {dep_id}]: {result}\n\n");
+        }
+    }
+    full_prompt.push_str(&step.prompt);
+    full_prompt
+}
+
+#[cfg(test)]
+#[allow(clippy::panic, clippy::expect_used, clippy::unwrap_used)]
+#[path = "run_tests.rs"]
+mod tests;
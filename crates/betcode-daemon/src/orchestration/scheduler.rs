@@ -1,11 +1,11 @@
 //! DAG-based step scheduler for multi-step orchestrations.
 //!
 //! The [`DagScheduler`] validates that the dependency graph is acyclic (via
-//! Kahn's algorithm) and then drives step execution: it computes in-degrees,
-//! yields "ready" steps whose dependencies are met, and propagates failure
-//! downstream.
+//! Kahn's algorithm) and then drives step execution: it yields "ready" steps
+//! whose dependencies are met and settles downstream steps as their
+//! dependencies finish, honouring each step's [`StepPolicy`].
 
-use std::collections::{HashMap, HashSet, VecDeque};
+use std::collections::{HashMap, VecDeque};
 
 use super::manager::ManagerError;
 
@@ -18,12 +18,58 @@ pub enum StepState {
     Ready,
     /// Currently executing.
     Running,
+    /// Failed, waiting for its next attempt.
+    Retrying,
     /// Completed successfully.
     Completed,
     /// Failed.
     Failed,
     /// Blocked by a failed dependency.
     Blocked,
+    /// Not run because its run condition was not met.
+    Skipped,
+}
+
+impl StepState {
+    /// Whether the step will not change state again.
+    pub const fn is_terminal(self) -> bool {
+        matches!(
+            self,
+            Self::Completed | Self::Failed | Self::Blocked | Self::Skipped
+        )
+    }
+}
+
+/// When a step runs, judged by the outcome of its dependencies.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RunCondition {
+    /// Every dependency succeeded (or failed with `continue_on_failure`).
+    #[default]
+    OnSuccess,
+    /// At least one dependency failed.
+    OnFailure,
+    /// Once every dependency has finished, whatever the outcome.
+    Always,
+}
+
+/// Per-step scheduling options.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct StepPolicy {
+    /// When the step runs.
+    pub run_if: RunCondition,
+    /// Whether a failure of this step counts as success for its dependents.
+    pub continue_on_failure: bool,
+}
+
+/// Steps whose state changed as a result of another step finishing.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Settled {
+    /// Steps that became ready to run.
+    pub ready: Vec<String>,
+    /// Steps blocked by a failed dependency.
+    pub blocked: Vec<String>,
+    /// Steps whose run condition can no longer be met.
+    pub skipped: Vec<String>,
 }
 
 /// DAG scheduler for orchestration steps.
@@ -34,30 +80,55 @@ pub enum StepState {
 pub struct DagScheduler {
     /// All step IDs in the orchestration.
     steps: Vec<String>,
+    /// Direct dependencies of each step.
+    deps: HashMap<String, Vec<String>>,
     /// Reverse dependencies: `step_id` -> list of step IDs that depend on it.
     dependents: HashMap<String, Vec<String>>,
     /// Current state of each step.
     states: HashMap<String, StepState>,
-    /// Remaining in-degree for each step (number of unsatisfied dependencies).
-    in_degrees: HashMap<String, usize>,
+    /// Scheduling options of steps that have any.
+    policies: HashMap<String, StepPolicy>,
 }
 
 impl DagScheduler {
     /// Create a new scheduler and validate the DAG.
     ///
     /// Returns an error if the dependency graph contains a cycle.
-    #[allow(clippy::needless_pass_by_value)]
     pub fn new(
         steps: Vec<String>,
         deps: HashMap<String, Vec<String>>,
     ) -> Result<Self, ManagerError> {
+        Self::with_policies(steps, deps, HashMap::new())
+    }
+
+    /// Create a scheduler whose steps have scheduling options.
+    ///
+    /// Steps missing from `policies` use [`StepPolicy::default`]. A step that
+    /// runs on failure must have dependencies.
+    pub fn with_policies(
+        steps: Vec<String>,
+        mut deps: HashMap<String, Vec<String>>,
+        policies: HashMap<String, StepPolicy>,
+    ) -> Result<Self, ManagerError> {
+        validate_dag(&steps, &deps)?;
+        for (step, policy) in &policies {
+            if policy.run_if == RunCondition::OnFailure && deps.get(step).is_none_or(Vec::is_empty)
+            {
+                return Err(ManagerError::Validation {
+                    message: betcode_core::dag::DagError::NoDependencies { step: step.clone() }
+                        .to_string(),
+                });
+            }
+        }
+
         // Build reverse dependency map
         let mut dependents: HashMap<String, Vec<String>> = HashMap::new();
         for step in &steps {
             dependents.entry(step.clone()).or_default();
+            deps.entry(step.clone()).or_default();
         }
-        for (step, dep_list) in &deps {
-            for dep in dep_list {
+        for step in &steps {
+            for dep in &deps[step] {
                 dependents
                     .entry(dep.clone())
                     .or_default()
@@ -65,32 +136,25 @@ impl DagScheduler {
             }
         }
 
-        // Compute in-degrees
-        let mut in_degrees: HashMap<String, usize> = HashMap::new();
-        for step in &steps {
-            let count = deps.get(step).map_or(0, Vec::len);
-            in_degrees.insert(step.clone(), count);
-        }
-
-        // Validate acyclicity via Kahn's algorithm
-        validate_dag(&steps, &deps)?;
-
         // Initialize states
-        let mut states: HashMap<String, StepState> = HashMap::new();
-        for step in &steps {
-            let deg = in_degrees.get(step).copied().unwrap_or(0);
-            if deg == 0 {
-                states.insert(step.clone(), StepState::Ready);
-            } else {
-                states.insert(step.clone(), StepState::Pending);
-            }
-        }
+        let states = steps
+            .iter()
+            .map(|step| {
+                let state = if deps[step].is_empty() {
+                    StepState::Ready
+                } else {
+                    StepState::Pending
+                };
+                (step.clone(), state)
+            })
+            .collect();
 
         Ok(Self {
             steps,
+            deps,
             dependents,
             states,
-            in_degrees,
+            policies,
         })
     }
 
@@ -99,101 +163,149 @@ impl DagScheduler {
     /// Returns step IDs whose state is `Ready`. These can be started
     /// concurrently.
     pub fn next_ready(&self) -> Vec<String> {
-        self.steps
-            .iter()
-            .filter(|s| self.states.get(*s) == Some(&StepState::Ready))
-            .cloned()
-            .collect()
+        self.ids_in(StepState::Ready)
     }
 
     /// Mark a step as currently running.
     pub fn mark_running(&mut self, step_id: &str) {
-        if let Some(state) = self.states.get_mut(step_id) {
-            *state = StepState::Running;
-        }
+        self.set_state(step_id, StepState::Running);
+    }
+
+    /// Mark a failed step as waiting for another attempt.
+    ///
+    /// Its dependents stay pending until the step finishes for good.
+    pub fn mark_retrying(&mut self, step_id: &str) {
+        self.set_state(step_id, StepState::Retrying);
     }
 
     /// Mark a step as completed.
     ///
-    /// Decrements in-degrees of downstream steps and moves newly-unblocked
-    /// steps to `Ready`. Returns the IDs of newly-ready downstream steps.
+    /// Returns the IDs of newly-ready downstream steps; see [`Self::finish`]
+    /// for everything that changed.
     pub fn mark_completed(&mut self, step_id: &str) -> Vec<String> {
-        if let Some(state) = self.states.get_mut(step_id) {
-            *state = StepState::Completed;
-        }
+        self.finish(step_id, true).ready
+    }
 
-        let mut newly_ready = Vec::new();
-
-        // Decrement in-degrees of dependents
-        if let Some(downstream) = self.dependents.get(step_id).cloned() {
-            for ds in &downstream {
-                if let Some(deg) = self.in_degrees.get_mut(ds) {
-                    *deg = deg.saturating_sub(1);
-                    if *deg == 0
-                        && let Some(state) = self.states.get_mut(ds)
-                        && *state == StepState::Pending
-                    {
-                        *state = StepState::Ready;
-                        newly_ready.push(ds.clone());
+    /// Mark a step as failed.
+    ///
+    /// Returns the IDs of all (transitively) blocked steps; see
+    /// [`Self::finish`] for everything that changed.
+    pub fn mark_failed(&mut self, step_id: &str) -> Vec<String> {
+        self.finish(step_id, false).blocked
+    }
+
+    /// Record the final outcome of a step and settle its dependents.
+    ///
+    /// A dependent is settled as soon as its outcome is known: steps that
+    /// need every dependency to succeed are blocked by the first failure,
+    /// while the others wait until all dependencies have finished. Blocked
+    /// and skipped steps settle their own dependents in turn.
+    pub fn finish(&mut self, step_id: &str, succeeded: bool) -> Settled {
+        let state = if succeeded {
+            StepState::Completed
+        } else {
+            StepState::Failed
+        };
+        self.set_state(step_id, state);
+
+        let mut settled = Settled::default();
+        let mut queue: VecDeque<String> = VecDeque::from([step_id.to_string()]);
+        while let Some(finished) = queue.pop_front() {
+            for ds in self.dependents.get(&finished).cloned().unwrap_or_default() {
+                if self.states.get(&ds) != Some(&StepState::Pending) {
+                    continue;
+                }
+                let Some(next) = self.resolve(&ds) else {
+                    continue;
+                };
+                self.set_state(&ds, next);
+                match next {
+                    StepState::Ready => settled.ready.push(ds),
+                    StepState::Blocked => {
+                        settled.blocked.push(ds.clone());
+                        queue.push_back(ds);
+                    }
+                    StepState::Skipped => {
+                        settled.skipped.push(ds.clone());
+                        queue.push_back(ds);
                     }
+                    _ => {}
                 }
             }
         }
-
-        newly_ready
+        settled
     }
 
-    /// Mark a step as failed.
+    /// Restore step states persisted by an interrupted run.
     ///
-    /// Cascades failure to all downstream dependents (transitively), marking
-    /// them as `Blocked`. Returns the IDs of all blocked steps.
-    pub fn mark_failed(&mut self, step_id: &str) -> Vec<String> {
-        if let Some(state) = self.states.get_mut(step_id) {
-            *state = StepState::Failed;
-        }
-
-        // Cascade: BFS through dependents
-        let mut blocked = Vec::new();
-        let mut queue: VecDeque<String> = VecDeque::new();
-
-        if let Some(downstream) = self.dependents.get(step_id) {
-            for ds in downstream {
-                queue.push_back(ds.clone());
+    /// Steps missing from `states` or recorded as pending keep their initial
+    /// state. Pending steps whose dependencies had already settled are then
+    /// resolved as they would have been had the run not been interrupted.
+    pub fn restore(&mut self, states: &HashMap<String, StepState>) -> Settled {
+        for (step_id, state) in states {
+            if *state != StepState::Pending {
+                self.set_state(step_id, *state);
             }
         }
 
-        let mut visited: HashSet<String> = HashSet::new();
-        while let Some(ds) = queue.pop_front() {
-            if !visited.insert(ds.clone()) {
-                continue;
+        let mut settled = Settled::default();
+        loop {
+            let mut changed = false;
+            for step_id in self.ids_in(StepState::Pending) {
+                let Some(next) = self.resolve(&step_id) else {
+                    continue;
+                };
+                self.set_state(&step_id, next);
+                changed = true;
+                match next {
+                    StepState::Ready => settled.ready.push(step_id),
+                    StepState::Blocked => settled.blocked.push(step_id),
+                    StepState::Skipped => settled.skipped.push(step_id),
+                    _ => {}
+                }
             }
-
-            if let Some(state) = self.states.get_mut(&ds)
-                && (*state == StepState::Pending || *state == StepState::Ready)
-            {
-                *state = StepState::Blocked;
-                blocked.push(ds.clone());
+            if !changed {
+                return settled;
             }
+        }
+    }
 
-            // Continue cascading
-            if let Some(further_downstream) = self.dependents.get(&ds) {
-                for fds in further_downstream {
-                    queue.push_back(fds.clone());
-                }
-            }
+    /// Decide what a pending step becomes, if its dependencies settle it yet.
+    fn resolve(&self, step_id: &str) -> Option<StepState> {
+        let deps = &self.deps[step_id];
+        let state = |dep: &String| self.states[dep];
+        let run_if = self.policy(step_id).run_if;
+
+        if run_if == RunCondition::OnSuccess
+            && deps.iter().any(|d| match state(d) {
+                StepState::Blocked => true,
+                StepState::Failed => !self.policy(d).continue_on_failure,
+                _ => false,
+            })
+        {
+            return Some(StepState::Blocked);
+        }
+        if !deps.iter().all(|d| state(d).is_terminal()) {
+            return None;
         }
+        let any = |wanted: StepState| deps.iter().any(|d| state(d) == wanted);
+        Some(match run_if {
+            RunCondition::OnSuccess if any(StepState::Skipped) => StepState::Skipped,
+            RunCondition::OnFailure if !any(StepState::Failed) => StepState::Skipped,
+            RunCondition::OnSuccess | RunCondition::OnFailure | RunCondition::Always => {
+                StepState::Ready
+            }
+        })
+    }
 
-        blocked
+    /// Scheduling options of a step.
+    pub fn policy(&self, step_id: &str) -> StepPolicy {
+        self.policies.get(step_id).copied().unwrap_or_default()
     }
 
     /// Check if all steps have reached a terminal state.
     pub fn is_complete(&self) -> bool {
-        self.states.values().all(|s| {
-            matches!(
-                s,
-                StepState::Completed | StepState::Failed | StepState::Blocked
-            )
-        })
+        self.states.values().all(|s| s.is_terminal())
     }
 
     /// Get the state of a specific step.
@@ -203,11 +315,7 @@ impl DagScheduler {
 
     /// Get all step IDs currently in `Running` state.
     pub fn running_ids(&self) -> Vec<String> {
-        self.steps
-            .iter()
-            .filter(|s| self.states.get(*s) == Some(&StepState::Running))
-            .cloned()
-            .collect()
+        self.ids_in(StepState::Running)
     }
 
     /// Get the total number of steps.
@@ -223,74 +331,30 @@ impl DagScheduler {
         }
         counts
     }
-}
 
-/// Validate that the dependency graph is a DAG (no cycles) using Kahn's algorithm.
-fn validate_dag(steps: &[String], deps: &HashMap<String, Vec<String>>) -> Result<(), ManagerError> {
-    let step_set: HashSet<&str> = steps.iter().map(String::as_str).collect();
-
-    // Validate that all dependencies reference known steps
-    for (step, dep_list) in deps {
-        for dep in dep_list {
-            if !step_set.contains(dep.as_str()) {
-                return Err(ManagerError::Validation {
-                    message: format!("Step '{step}' depends on unknown step '{dep}'"),
-                });
-            }
-        }
-        // Self-dependency check
-        if dep_list.contains(step) {
-            return Err(ManagerError::Validation {
-                message: format!("Step '{step}' depends on itself"),
-            });
-        }
-    }
-
-    // Kahn's algorithm: compute in-degrees, process zero-degree nodes
-    let mut in_degree: HashMap<&str, usize> = HashMap::new();
-    let mut adj: HashMap<&str, Vec<&str>> = HashMap::new();
-
-    for step in steps {
-        in_degree.entry(step.as_str()).or_insert(0);
-        adj.entry(step.as_str()).or_default();
-    }
-
-    for (step, dep_list) in deps {
-        for dep in dep_list {
-            adj.entry(dep.as_str()).or_default().push(step.as_str());
-            *in_degree.entry(step.as_str()).or_insert(0) += 1;
-        }
-    }
-
-    let mut queue: VecDeque<&str> = VecDeque::new();
-    for (step, &deg) in &in_degree {
-        if deg == 0 {
-            queue.push_back(step);
-        }
+    fn ids_in(&self, state: StepState) -> Vec<String> {
+        self.steps
+            .iter()
+            .filter(|s| self.states.get(*s) == Some(&state))
+            .cloned()
+            .collect()
     }
 
-    let mut processed = 0usize;
-    while let Some(step) = queue.pop_front() {
-        processed += 1;
-        if let Some(neighbors) = adj.get(step) {
-            for &neighbor in neighbors {
-                if let Some(deg) = in_degree.get_mut(neighbor) {
-                    *deg = deg.saturating_sub(1);
-                    if *deg == 0 {
-                        queue.push_back(neighbor);
-                    }
-                }
-            }
+    fn set_state(&mut self, step_id: &str, state: StepState) {
+        if let Some(current) = self.states.get_mut(step_id) {
+            *current = state;
         }
     }
+}
 
-    if processed != steps.len() {
-        return Err(ManagerError::Validation {
-            message: "Dependency graph contains a cycle".to_string(),
-        });
-    }
-
-    Ok(())
+/// Validate that the dependency graph is a DAG (no cycles).
+///
+/// The rules live in [`betcode_core::dag`] so clients can check orchestration
+/// files before submitting them.
+fn validate_dag(steps: &[String], deps: &HashMap<String, Vec<String>>) -> Result<(), ManagerError> {
+    betcode_core::dag::validate_dag(steps, deps).map_err(|e| ManagerError::Validation {
+        message: e.to_string(),
+    })
 }
 
 #[cfg(test)]
@@ -536,6 +600,158 @@ mod tests {
         assert!(blocked.contains(&"d".to_string()));
     }
 
+    // =========================================================================
+    // Step policies
+    // =========================================================================
+
+    fn policies(pairs: &[(&str, RunCondition, bool)]) -> HashMap<String, StepPolicy> {
+        pairs
+            .iter()
+            .map(|(id, run_if, continue_on_failure)| {
+                (
+                    (*id).to_string(),
+                    StepPolicy {
+                        run_if: *run_if,
+                        continue_on_failure: *continue_on_failure,
+                    },
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn retrying_step_keeps_dependents_pending() {
+        let s = steps(&["a", "b"]);
+        let d = deps(&[("b", &["a"])]);
+        let mut sched = DagScheduler::new(s, d).unwrap();
+
+        sched.mark_running("a");
+        sched.mark_retrying("a");
+        assert_eq!(sched.step_state("a"), Some(StepState::Retrying));
+        assert_eq!(sched.step_state("b"), Some(StepState::Pending));
+        assert!(!sched.is_complete());
+
+        sched.mark_running("a");
+        assert_eq!(sched.mark_completed("a"), vec!["b"]);
+    }
+
+    #[test]
+    fn continue_on_failure_unblocks_dependents() {
+        let s = steps(&["a", "b"]);
+        let d = deps(&[("b", &["a"])]);
+        let p = policies(&[("a", RunCondition::OnSuccess, true)]);
+        let mut sched = DagScheduler::with_policies(s, d, p).unwrap();
+
+        sched.mark_running("a");
+        let settled = sched.finish("a", false);
+        assert_eq!(settled.ready, vec!["b"]);
+        assert!(settled.blocked.is_empty());
+        assert_eq!(sched.step_state("a"), Some(StepState::Failed));
+    }
+
+    #[test]
+    fn on_failure_step_runs_only_after_failure() {
+        // test -> fix (on failure), test -> report (on success)
+        let d = deps(&[("fix", &["test"]), ("report", &["test"])]);
+        let p = policies(&[("fix", RunCondition::OnFailure, false)]);
+
+        let mut failed =
+            DagScheduler::with_policies(steps(&["test", "fix", "report"]), d.clone(), p.clone())
+                .unwrap();
+        failed.mark_running("test");
+        let settled = failed.finish("test", false);
+        assert_eq!(settled.ready, vec!["fix"]);
+        assert_eq!(settled.blocked, vec!["report"]);
+
+        let mut passed =
+            DagScheduler::with_policies(steps(&["test", "fix", "report"]), d, p).unwrap();
+        passed.mark_running("test");
+        let settled = passed.finish("test", true);
+        assert_eq!(settled.ready, vec!["report"]);
+        assert_eq!(settled.skipped, vec!["fix"]);
+    }
+
+    #[test]
+    fn skipped_step_cascades_unless_always() {
+        // test -> fix (on failure) -> verify, fix -> cleanup (always)
+        let s = steps(&["test", "fix", "verify", "cleanup"]);
+        let d = deps(&[
+            ("fix", &["test"]),
+            ("verify", &["fix"]),
+            ("cleanup", &["fix"]),
+        ]);
+        let p = policies(&[
+            ("fix", RunCondition::OnFailure, false),
+            ("cleanup", RunCondition::Always, false),
+        ]);
+        let mut sched = DagScheduler::with_policies(s, d, p).unwrap();
+
+        sched.mark_running("test");
+        let settled = sched.finish("test", true);
+        assert_eq!(settled.skipped, vec!["fix", "verify"]);
+        assert_eq!(settled.ready, vec!["cleanup"]);
+    }
+
+    #[test]
+    fn always_step_waits_for_every_dependency() {
+        let s = steps(&["a", "b", "c"]);
+        let d = deps(&[("c", &["a", "b"])]);
+        let p = policies(&[("c", RunCondition::Always, false)]);
+        let mut sched = DagScheduler::with_policies(s, d, p).unwrap();
+
+        sched.mark_running("a");
+        sched.mark_running("b");
+        assert_eq!(sched.finish("a", false), Settled::default());
+        assert_eq!(sched.step_state("c"), Some(StepState::Pending));
+        assert_eq!(sched.mark_completed("b"), vec!["c"]);
+    }
+
+    #[test]
+    fn restore_resumes_interrupted_run() {
+        // a -> b -> d, a -> c (on failure), e independent
+        let s = steps(&["a", "b", "c", "d", "e"]);
+        let d = deps(&[("b", &["a"]), ("c", &["a"]), ("d", &["b"])]);
+        let p = policies(&[("c", RunCondition::OnFailure, false)]);
+        let mut sched = DagScheduler::with_policies(s, d, p).unwrap();
+
+        // Interrupted after `a` completed but before its dependents were
+        // dispatched, with `e` still running.
+        let persisted = HashMap::from([
+            ("a".to_string(), StepState::Completed),
+            ("b".to_string(), StepState::Pending),
+            ("e".to_string(), StepState::Running),
+        ]);
+        let settled = sched.restore(&persisted);
+        assert_eq!(settled.ready, vec!["b"]);
+        assert_eq!(settled.skipped, vec!["c"]);
+        assert_eq!(sched.step_state("d"), Some(StepState::Pending));
+        assert_eq!(sched.running_ids(), vec!["e"]);
+
+        sched.mark_running("b");
+        assert_eq!(sched.mark_completed("b"), vec!["d"]);
+    }
+
+    #[test]
+    fn restore_cascades_blocked_steps() {
+        let s = steps(&["a", "b", "c"]);
+        let d = deps(&[("b", &["a"]), ("c", &["b"])]);
+        let mut sched = DagScheduler::new(s, d).unwrap();
+
+        let persisted = HashMap::from([("a".to_string(), StepState::Failed)]);
+        let settled = sched.restore(&persisted);
+        assert_eq!(settled.blocked, vec!["b", "c"]);
+        assert!(sched.is_complete());
+    }
+
+    #[test]
+    fn rejects_on_failure_without_dependencies() {
+        let p = policies(&[("a", RunCondition::OnFailure, false)]);
+        let err = DagScheduler::with_policies(steps(&["a"]), HashMap::new(), p)
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("no dependencies"), "{err}");
+    }
+
     // =========================================================================
     // State queries
     // =========================================================================
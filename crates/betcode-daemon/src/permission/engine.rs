@@ -269,6 +269,11 @@ impl DaemonPermissionEngine {
         self.pending.get_for_session(session_id).await
     }
 
+    /// Drop a pending request without answering it.
+    pub async fn cancel_pending(&self, request_id: &str) -> Option<PendingRequest> {
+        self.pending.take(request_id).await
+    }
+
     /// Update client connection status for pending requests.
     pub async fn update_client_status(&self, client_id: &str, connected: bool) {
         self.pending
@@ -8,7 +8,7 @@
 //! ```
 
 use std::collections::{HashMap, HashSet};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use std::sync::atomic::{AtomicU64, Ordering};
 
@@ -21,7 +21,7 @@ use betcode_proto::v1::AgentEvent;
 use crate::commands::CommandRegistry;
 use crate::session::SessionMultiplexer;
 use crate::storage::Database;
-use crate::subprocess::{EventBridge, SpawnConfig, SubprocessManager};
+use crate::subprocess::{EventBridge, SandboxPolicy, SpawnConfig, SubprocessManager};
 
 use super::types::{RelayError, RelayHandle, RelaySessionConfig};
 
@@ -83,11 +83,13 @@ impl SessionRelay {
 
         // Spawn the Claude subprocess
         let spawn_working_directory = config.working_directory;
+        let sandbox = repo_sandbox(&self.db, &config.worktree_id, &spawn_working_directory).await?;
         let spawn_config = SpawnConfig {
             working_directory: spawn_working_directory.clone(),
             prompt: initial_prompt,
             resume_session: config.resume_session,
             model: config.model,
+            sandbox,
             ..Default::default()
         };
 
@@ -230,6 +232,41 @@ impl SessionRelay {
         Ok(())
     }
 
+    /// Answer a permission request that is still pending, on behalf of a
+    /// caller outside the session's event stream (e.g. a notification action).
+    ///
+    /// Returns `false` without touching the subprocess if the session is not
+    /// running or the request was already answered.
+    pub async fn respond_to_pending_permission(
+        &self,
+        session_id: &str,
+        request_id: &str,
+        decision: betcode_proto::v1::PermissionDecision,
+        source: &str,
+    ) -> Result<bool, RelayError> {
+        let Some(handle) = self.get_handle(session_id).await else {
+            return Ok(false);
+        };
+        if !handle
+            .pending_permissions
+            .read()
+            .await
+            .contains_key(request_id)
+        {
+            debug!(
+                session_id,
+                request_id, source, "Permission already answered"
+            );
+            return Ok(false);
+        }
+        let (granted, original_input) = handle
+            .process_permission_response(request_id, decision, source)
+            .await;
+        self.send_permission_response(session_id, request_id, granted, &original_input)
+            .await?;
+        Ok(true)
+    }
+
     /// Send an `AskUserQuestion` response to the subprocess.
     ///
     /// Format matches the Claude Agent SDK control protocol for `AskUserQuestion`:
@@ -355,6 +392,51 @@ struct StdoutPipelineContext {
     working_directory: PathBuf,
 }
 
+/// Resolve the sandbox policy of the repository a session runs in.
+///
+/// A session belongs to a repository through its worktree binding, or because
+/// its working directory lies inside a registered worktree or repository.
+/// `None` leaves the daemon default in place. A stored policy that fails to
+/// parse refuses the spawn rather than running unsandboxed.
+async fn repo_sandbox(
+    db: &Database,
+    worktree_id: &str,
+    working_dir: &Path,
+) -> Result<Option<SandboxPolicy>, RelayError> {
+    let storage = |e: crate::storage::DatabaseError| RelayError::Storage(e.to_string());
+    let mut repo_id = if worktree_id.is_empty() {
+        None
+    } else {
+        db.get_worktree(worktree_id).await.ok().map(|wt| wt.repo_id)
+    };
+    if repo_id.is_none() {
+        repo_id = db
+            .list_worktrees(None)
+            .await
+            .map_err(storage)?
+            .into_iter()
+            .filter(|wt| working_dir.starts_with(&wt.path))
+            .max_by_key(|wt| wt.path.len())
+            .map(|wt| wt.repo_id);
+    }
+    let row = if let Some(id) = repo_id {
+        db.get_git_repo(&id).await.ok()
+    } else {
+        db.list_git_repos()
+            .await
+            .map_err(storage)?
+            .into_iter()
+            .filter(|r| working_dir.starts_with(&r.repo_path))
+            .max_by_key(|r| r.repo_path.len())
+    };
+    let Some(json) = row.and_then(|r| r.sandbox) else {
+        return Ok(None);
+    };
+    serde_json::from_str(&json)
+        .map(Some)
+        .map_err(|e| RelayError::Storage(format!("invalid repository sandbox policy: {e}")))
+}
+
 /// Spawn the stdout → NDJSON parser → `EventBridge` → forwarder pipeline.
 #[allow(clippy::too_many_lines)]
 fn spawn_stdout_pipeline(ctx: StdoutPipelineContext) {
@@ -607,7 +689,7 @@ fn spawn_stdout_pipeline(ctx: StdoutPipelineContext) {
         if event_count == 0 {
             warn!(session_id = %sid, "Subprocess exited with zero events — sending error to client");
             let error_event = AgentEvent {
-                sequence: 0,
+                sequence: sequence_counter.fetch_add(1, Ordering::AcqRel) + 1,
                 timestamp: Some(prost_types::Timestamp::from(std::time::SystemTime::now())),
                 parent_tool_use_id: String::new(),
                 event: Some(betcode_proto::v1::agent_event::Event::Error(
@@ -619,6 +701,9 @@ fn spawn_stdout_pipeline(ctx: StdoutPipelineContext) {
                     },
                 )),
             };
+            if let Err(e) = store_event(&db, &sid, &error_event).await {
+                warn!(session_id = %sid, error = %e, "Failed to store error event");
+            }
             let _ = event_forwarder.send(error_event).await;
         }
 
@@ -657,7 +742,8 @@ const fn event_message_type(event: &AgentEvent) -> &'static str {
             | Event::Error(_)
             | Event::TodoUpdate(_)
             | Event::PlanMode(_)
-            | Event::Encrypted(_),
+            | Event::Encrypted(_)
+            | Event::RequestApplied(_),
         )
         | None => "stream_event",
     }
@@ -1164,4 +1250,61 @@ mod tests {
         // pending maps should be cleaned
         assert!(handle.pending_permissions.read().await.is_empty());
     }
+
+    #[tokio::test]
+    async fn repo_sandbox_resolves_by_worktree_and_path() {
+        let db = Database::open_in_memory().await.unwrap();
+        db.create_git_repo(
+            "r1",
+            "/src/repo",
+            &crate::storage::GitRepoParams {
+                name: "repo",
+                worktree_mode: "global",
+                local_subfolder: ".worktree",
+                custom_path: None,
+                setup_script: None,
+                auto_gitignore: true,
+            },
+        )
+        .await
+        .unwrap();
+        db.create_worktree("wt1", "wt", "/wts/wt1", "feat", "r1", None)
+            .await
+            .unwrap();
+
+        // No policy stored: daemon default.
+        assert!(
+            repo_sandbox(&db, "wt1", Path::new("/wts/wt1"))
+                .await
+                .unwrap()
+                .is_none()
+        );
+
+        db.set_git_repo_sandbox("r1", Some(r#"{"backend":"bubblewrap"}"#))
+            .await
+            .unwrap();
+        for (wt_id, dir) in [
+            ("wt1", "/elsewhere"),
+            ("", "/wts/wt1/sub"),
+            ("", "/src/repo"),
+        ] {
+            let policy = repo_sandbox(&db, wt_id, Path::new(dir)).await.unwrap();
+            assert!(policy.unwrap().is_enabled(), "{wt_id:?} {dir}");
+        }
+        assert!(
+            repo_sandbox(&db, "", Path::new("/tmp"))
+                .await
+                .unwrap()
+                .is_none()
+        );
+
+        db.set_git_repo_sandbox("r1", Some("not json"))
+            .await
+            .unwrap();
+        assert!(
+            repo_sandbox(&db, "wt1", Path::new("/wts/wt1"))
+                .await
+                .is_err()
+        );
+    }
 }
@@ -14,7 +14,8 @@ use betcode_proto::v1::{
     ClearSessionGrantsResponse, CompactSessionRequest, CompactSessionResponse,
     DeleteSessionRequest, DeleteSessionResponse, InputLockRequest, InputLockResponse,
     KeyExchangeRequest, KeyExchangeResponse, ListSessionGrantsRequest, ListSessionGrantsResponse,
-    ListSessionsRequest, ListSessionsResponse, RenameSessionRequest, RenameSessionResponse,
+    ListSessionsRequest, ListSessionsResponse, PermissionDecision, RenameSessionRequest,
+    RenameSessionResponse, RespondToPermissionRequest, RespondToPermissionResponse,
     ResumeSessionRequest, SessionSummary, SetSessionGrantRequest, SetSessionGrantResponse,
     agent_service_server::AgentService,
 };
@@ -442,6 +443,38 @@ impl AgentService for AgentServiceImpl {
 
         Ok(Response::new(DeleteSessionResponse { deleted }))
     }
+
+    #[instrument(skip(self, request), fields(rpc = "RespondToPermission"))]
+    async fn respond_to_permission(
+        &self,
+        request: Request<RespondToPermissionRequest>,
+    ) -> Result<Response<RespondToPermissionResponse>, Status> {
+        let req = request.into_inner();
+        let response = req
+            .response
+            .ok_or_else(|| Status::invalid_argument("response is required"))?;
+        let decision = PermissionDecision::try_from(response.decision).unwrap_or_else(|_| {
+            warn!(
+                raw_decision = response.decision,
+                "Unknown PermissionDecision, defaulting to Deny"
+            );
+            PermissionDecision::Deny
+        });
+
+        let accepted = self
+            .relay
+            .respond_to_pending_permission(&req.session_id, &response.request_id, decision, "grpc")
+            .await
+            .map_err(|e| Status::internal(e.to_string()))?;
+        info!(
+            session_id = %req.session_id,
+            request_id = %response.request_id,
+            accepted,
+            ?decision,
+            "Permission answered out of band"
+        );
+        Ok(Response::new(RespondToPermissionResponse { accepted }))
+    }
 }
 
 /// Extract `client_id` from gRPC request metadata.
@@ -520,6 +553,38 @@ mod tests {
         assert!(!resp.into_inner().deleted);
     }
 
+    #[tokio::test]
+    async fn respond_to_permission_without_session_is_not_accepted() {
+        let service = test_agent_service().await;
+
+        let resp = service
+            .respond_to_permission(Request::new(RespondToPermissionRequest {
+                session_id: "no-such-session".into(),
+                response: Some(betcode_proto::v1::PermissionResponse {
+                    request_id: "perm-1".into(),
+                    decision: PermissionDecision::AllowOnce.into(),
+                    ..Default::default()
+                }),
+            }))
+            .await
+            .unwrap();
+        assert!(!resp.into_inner().accepted);
+    }
+
+    #[tokio::test]
+    async fn respond_to_permission_requires_response() {
+        let service = test_agent_service().await;
+
+        let err = service
+            .respond_to_permission(Request::new(RespondToPermissionRequest {
+                session_id: "s1".into(),
+                response: None,
+            }))
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), tonic::Code::InvalidArgument);
+    }
+
     #[tokio::test]
     async fn delete_session_empty_id_returns_error() {
         let service = test_agent_service().await;
@@ -3,6 +3,8 @@
 use std::net::SocketAddr;
 use std::path::PathBuf;
 
+use crate::orchestration::manager::PermissionFallback;
+
 /// Server configuration.
 #[derive(Debug, Clone)]
 pub struct ServerConfig {
@@ -35,6 +37,16 @@ pub struct ServerConfig {
 
     /// HTTP/2 keepalive timeout in seconds.
     pub keepalive_timeout_secs: u64,
+
+    /// Idle age in seconds after which worktree GC considers a worktree stale.
+    pub worktree_max_idle_secs: u64,
+
+    /// Whether subagents interrupted by a restart continue their Claude
+    /// session instead of being marked failed.
+    pub resume_subagents: bool,
+
+    /// Handling of subagent permission requests while no client watches.
+    pub subagent_permission_fallback: PermissionFallback,
 }
 
 impl Default for ServerConfig {
@@ -50,6 +62,9 @@ impl Default for ServerConfig {
             stdout_channel_size: 256,
             keepalive_interval_secs: 30,
             keepalive_timeout_secs: 10,
+            worktree_max_idle_secs: crate::worktree::DEFAULT_MAX_IDLE.as_secs(),
+            resume_subagents: true,
+            subagent_permission_fallback: PermissionFallback::Deny,
         }
     }
 }
@@ -94,6 +109,27 @@ impl ServerConfig {
         self.max_processes = max;
         self
     }
+
+    /// Set the idle age after which worktree GC considers a worktree stale.
+    #[must_use]
+    pub const fn with_worktree_max_idle_secs(mut self, secs: u64) -> Self {
+        self.worktree_max_idle_secs = secs;
+        self
+    }
+
+    /// Set whether interrupted subagents are resumed at startup.
+    #[must_use]
+    pub const fn with_resume_subagents(mut self, resume: bool) -> Self {
+        self.resume_subagents = resume;
+        self
+    }
+
+    /// Set how unwatched subagent permission requests are handled.
+    #[must_use]
+    pub const fn with_subagent_permission_fallback(mut self, fallback: PermissionFallback) -> Self {
+        self.subagent_permission_fallback = fallback;
+        self
+    }
 }
 
 #[cfg(test)]
@@ -1,4 +1,4 @@
-//! Conversion helpers between GitLab API types and proto types.
+//! Conversion helpers between forge types and proto types.
 
 use betcode_proto::v1::{
     IssueInfo, IssueState, MergeRequestInfo, MergeRequestState, MergeStatus, PipelineInfo,
@@ -6,20 +6,23 @@ use betcode_proto::v1::{
 };
 use tonic::Status;
 
-use crate::gitlab::{self, GitLabError};
+use crate::forge::{self, ForgeError};
 
-/// Map `GitLabError` to tonic Status.
+/// Map `ForgeError` to tonic Status.
 #[allow(clippy::needless_pass_by_value)]
-pub fn to_status(err: GitLabError) -> Status {
+pub fn to_status(err: ForgeError) -> Status {
     match &err {
-        GitLabError::Api { status, .. } => match *status {
+        ForgeError::Api { status, .. } => match *status {
             401 => Status::unauthenticated(err.to_string()),
             403 => Status::permission_denied(err.to_string()),
             404 => Status::not_found(err.to_string()),
+            429 => Status::resource_exhausted(err.to_string()),
             _ => Status::internal(err.to_string()),
         },
-        GitLabError::Config(_) => Status::failed_precondition(err.to_string()),
-        GitLabError::Http(_) => Status::unavailable(err.to_string()),
+        ForgeError::Decode(_) => Status::internal(err.to_string()),
+        ForgeError::Config(_) => Status::failed_precondition(err.to_string()),
+        ForgeError::Unsupported { .. } => Status::invalid_argument(err.to_string()),
+        ForgeError::Http(_) => Status::unavailable(err.to_string()),
     }
 }
 
@@ -169,10 +172,10 @@ pub fn str_to_issue_state(s: &str) -> i32 {
 }
 
 // =============================================================================
-// Type conversions: GitLab REST -> Proto
+// Type conversions: forge -> Proto
 // =============================================================================
 
-pub fn to_mr_info(mr: gitlab::MergeRequest) -> MergeRequestInfo {
+pub fn to_mr_info(mr: forge::MergeRequest) -> MergeRequestInfo {
     MergeRequestInfo {
         id: mr.id,
         iid: mr.iid,
@@ -181,21 +184,21 @@ pub fn to_mr_info(mr: gitlab::MergeRequest) -> MergeRequestInfo {
         state: str_to_mr_state(&mr.state),
         source_branch: mr.source_branch,
         target_branch: mr.target_branch,
-        author: mr.author.username,
+        author: mr.author,
         labels: mr.labels,
         created_at: parse_timestamp(&mr.created_at),
         updated_at: parse_timestamp(&mr.updated_at),
         web_url: mr.web_url,
         draft: mr.draft,
         merge_status: mr.merge_status.as_deref().map_or(0, str_to_merge_status),
-        assignee: mr.assignee.map(|u| u.username).unwrap_or_default(),
-        assignees: mr.assignees.into_iter().map(|u| u.username).collect(),
-        reviewers: mr.reviewers.into_iter().map(|u| u.username).collect(),
-        milestone: mr.milestone.map(|m| m.title).unwrap_or_default(),
+        assignee: mr.assignee.unwrap_or_default(),
+        assignees: mr.assignees,
+        reviewers: mr.reviewers,
+        milestone: mr.milestone.unwrap_or_default(),
     }
 }
 
-pub fn to_pipeline_info(p: gitlab::Pipeline) -> PipelineInfo {
+pub fn to_pipeline_info(p: forge::Pipeline) -> PipelineInfo {
     PipelineInfo {
         id: p.id,
         status: str_to_pipeline_status(&p.status),
@@ -208,21 +211,21 @@ pub fn to_pipeline_info(p: gitlab::Pipeline) -> PipelineInfo {
     }
 }
 
-pub fn to_issue_info(issue: gitlab::Issue) -> IssueInfo {
+pub fn to_issue_info(issue: forge::Issue) -> IssueInfo {
     IssueInfo {
         id: issue.id,
         iid: issue.iid,
         title: issue.title,
         description: issue.description.unwrap_or_default(),
         state: str_to_issue_state(&issue.state),
-        author: issue.author.username,
+        author: issue.author,
         labels: issue.labels,
         created_at: parse_timestamp(&issue.created_at),
         updated_at: parse_timestamp(&issue.updated_at),
         web_url: issue.web_url,
         confidential: issue.confidential,
-        assignee: issue.assignee.map(|u| u.username).unwrap_or_default(),
-        assignees: issue.assignees.into_iter().map(|u| u.username).collect(),
-        milestone: issue.milestone.map(|m| m.title).unwrap_or_default(),
+        assignee: issue.assignee.unwrap_or_default(),
+        assignees: issue.assignees,
+        milestone: issue.milestone.unwrap_or_default(),
     }
 }
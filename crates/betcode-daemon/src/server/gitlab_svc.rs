@@ -1,6 +1,7 @@
 //! `GitLabService` gRPC implementation.
 //!
-//! Wraps the reqwest-based `GitLabClient` to serve gRPC requests.
+//! Serves GitLab, GitHub and Gitea through the `Forge` trait; each request's
+//! `project` is resolved to a forge by the `ForgeRegistry`.
 
 use std::sync::Arc;
 
@@ -19,10 +20,10 @@ use super::gitlab_convert::{
     issue_state_to_str, mr_state_to_str, pipeline_status_to_str, to_issue_info, to_mr_info,
     to_pipeline_info, to_status,
 };
-use crate::gitlab::GitLabClient;
+use crate::forge::{ForgeRegistry, ResolvedProject};
 
 /// Normalise limit/offset from a gRPC request into `(per_page, page)` for
-/// the GitLab REST API (1-indexed pages).
+/// the forge REST APIs (1-indexed pages).
 const fn paginate(limit: u32, offset: u32) -> (u32, u32) {
     let per_page = if limit == 0 { 20 } else { limit };
     let page = if offset == 0 {
@@ -33,15 +34,20 @@ const fn paginate(limit: u32, offset: u32) -> (u32, u32) {
     (per_page, page)
 }
 
-/// `GitLabService` implementation backed by `GitLabClient`.
+/// `GitLabService` implementation backed by a `ForgeRegistry`.
 pub struct GitLabServiceImpl {
-    client: Arc<GitLabClient>,
+    forges: Arc<ForgeRegistry>,
 }
 
 impl GitLabServiceImpl {
     /// Create a new `GitLabService`.
-    pub const fn new(client: Arc<GitLabClient>) -> Self {
-        Self { client }
+    pub const fn new(forges: Arc<ForgeRegistry>) -> Self {
+        Self { forges }
+    }
+
+    /// Resolve the request's project to a forge and forge-side project path.
+    async fn resolve(&self, project: &str) -> Result<ResolvedProject, Status> {
+        self.forges.resolve(project).await.map_err(to_status)
     }
 }
 
@@ -60,9 +66,10 @@ impl GitLabService for GitLabServiceImpl {
 
         info!(project = %req.project, state = ?state_str, "Listing merge requests");
 
-        let mrs = self
-            .client
-            .list_merge_requests(&req.project, state_str, per_page, page)
+        let target = self.resolve(&req.project).await?;
+        let mrs = target
+            .forge
+            .list_merge_requests(&target.project, state_str, per_page, page)
             .await
             .map_err(to_status)?;
 
@@ -83,9 +90,10 @@ impl GitLabService for GitLabServiceImpl {
         let req = request.into_inner();
         info!(project = %req.project, iid = req.iid, "Getting merge request");
 
-        let mr = self
-            .client
-            .get_merge_request(&req.project, req.iid)
+        let target = self.resolve(&req.project).await?;
+        let mr = target
+            .forge
+            .get_merge_request(&target.project, req.iid)
             .await
             .map_err(to_status)?;
 
@@ -107,9 +115,10 @@ impl GitLabService for GitLabServiceImpl {
 
         info!(project = %req.project, status = ?status_str, "Listing pipelines");
 
-        let pipelines = self
-            .client
-            .list_pipelines(&req.project, status_str, per_page, page)
+        let target = self.resolve(&req.project).await?;
+        let pipelines = target
+            .forge
+            .list_pipelines(&target.project, status_str, per_page, page)
             .await
             .map_err(to_status)?;
 
@@ -127,9 +136,10 @@ impl GitLabService for GitLabServiceImpl {
         let req = request.into_inner();
         info!(project = %req.project, pipeline_id = req.pipeline_id, "Getting pipeline");
 
-        let pipeline = self
-            .client
-            .get_pipeline(&req.project, req.pipeline_id)
+        let target = self.resolve(&req.project).await?;
+        let pipeline = target
+            .forge
+            .get_pipeline(&target.project, req.pipeline_id)
             .await
             .map_err(to_status)?;
 
@@ -151,9 +161,10 @@ impl GitLabService for GitLabServiceImpl {
 
         info!(project = %req.project, state = ?state_str, "Listing issues");
 
-        let issues = self
-            .client
-            .list_issues(&req.project, state_str, per_page, page)
+        let target = self.resolve(&req.project).await?;
+        let issues = target
+            .forge
+            .list_issues(&target.project, state_str, per_page, page)
             .await
             .map_err(to_status)?;
 
@@ -171,9 +182,10 @@ impl GitLabService for GitLabServiceImpl {
         let req = request.into_inner();
         info!(project = %req.project, iid = req.iid, "Getting issue");
 
-        let issue = self
-            .client
-            .get_issue(&req.project, req.iid)
+        let target = self.resolve(&req.project).await?;
+        let issue = target
+            .forge
+            .get_issue(&target.project, req.iid)
             .await
             .map_err(to_status)?;
 
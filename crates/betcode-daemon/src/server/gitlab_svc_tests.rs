@@ -1,6 +1,7 @@
-//! Tests for GitLab gRPC service conversions and helpers.
+//! Tests for forge gRPC service conversions and helpers.
 
 use super::gitlab_convert::*;
+use crate::forge::{ForgeError, ForgeKind};
 use crate::gitlab;
 use betcode_proto::v1::{IssueState, MergeRequestState, MergeStatus, PipelineStatus};
 
@@ -88,7 +89,7 @@ fn to_status_maps_401_to_unauthenticated() {
         status: 401,
         message: "Unauthorized".into(),
     };
-    assert_eq!(to_status(err).code(), tonic::Code::Unauthenticated);
+    assert_eq!(to_status(err.into()).code(), tonic::Code::Unauthenticated);
 }
 
 #[test]
@@ -97,7 +98,7 @@ fn to_status_maps_403_to_permission_denied() {
         status: 403,
         message: "Forbidden".into(),
     };
-    assert_eq!(to_status(err).code(), tonic::Code::PermissionDenied);
+    assert_eq!(to_status(err.into()).code(), tonic::Code::PermissionDenied);
 }
 
 #[test]
@@ -106,13 +107,34 @@ fn to_status_maps_404_to_not_found() {
         status: 404,
         message: "Not Found".into(),
     };
-    assert_eq!(to_status(err).code(), tonic::Code::NotFound);
+    assert_eq!(to_status(err.into()).code(), tonic::Code::NotFound);
+}
+
+#[test]
+fn to_status_maps_429_to_resource_exhausted() {
+    let err = gitlab::GitLabError::Api {
+        status: 429,
+        message: "Too Many Requests".into(),
+    };
+    assert_eq!(to_status(err.into()).code(), tonic::Code::ResourceExhausted);
+}
+
+#[test]
+fn to_status_maps_unsupported_to_invalid_argument() {
+    let err = ForgeError::Unsupported {
+        kind: ForgeKind::GitHub,
+        message: "pipeline status filter \"scheduled\"".into(),
+    };
+    assert_eq!(to_status(err).code(), tonic::Code::InvalidArgument);
 }
 
 #[test]
 fn to_status_maps_config_to_failed_precondition() {
     let err = gitlab::GitLabError::Config("missing".into());
-    assert_eq!(to_status(err).code(), tonic::Code::FailedPrecondition);
+    assert_eq!(
+        to_status(err.into()).code(),
+        tonic::Code::FailedPrecondition
+    );
 }
 
 // =============================================================================
@@ -149,7 +171,7 @@ fn to_mr_info_converts_full_mr() {
         }],
         milestone: Some(gitlab::types::GitLabMilestone { title: "v1".into() }),
     };
-    let info = to_mr_info(mr);
+    let info = to_mr_info(mr.into());
     assert_eq!(info.iid, 42);
     assert_eq!(info.author, "alice");
     assert_eq!(info.assignee, "bob");
@@ -171,7 +193,7 @@ fn to_pipeline_info_converts() {
         updated_at: "2026-01-01T00:00:00Z".into(),
         web_url: "https://x.com/p/100".into(),
     };
-    let info = to_pipeline_info(p);
+    let info = to_pipeline_info(p.into());
     assert_eq!(info.id, 100);
     assert_eq!(info.status, PipelineStatus::Success as i32);
     assert_eq!(info.source, "push");
@@ -201,7 +223,7 @@ fn to_issue_info_converts() {
             title: "Sprint 5".into(),
         }),
     };
-    let info = to_issue_info(issue);
+    let info = to_issue_info(issue.into());
     assert_eq!(info.iid, 10);
     assert!(info.confidential);
     assert_eq!(info.assignees, vec!["bob"]);
@@ -6,11 +6,12 @@ use tokio::sync::mpsc;
 use tonic::Status;
 use tracing::{info, warn};
 
-use betcode_proto::v1::{AgentEvent, AgentRequest, PermissionDecision};
+use betcode_proto::v1::agent_event::Event;
+use betcode_proto::v1::{AgentEvent, AgentRequest, PermissionDecision, RequestApplied};
 
 use crate::relay::{RelaySessionConfig, SessionRelay, is_granted};
 use crate::session::SessionMultiplexer;
-use crate::storage::Database;
+use crate::storage::{Database, KeyClaim};
 
 /// Shared context for agent request handling.
 pub struct HandlerContext<'a> {
@@ -21,12 +22,130 @@ pub struct HandlerContext<'a> {
     pub client_id: &'a str,
 }
 
+/// Whether to apply a request, after checking its idempotency key.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Claim {
+    /// Apply the request, then settle its key, if any, with [`settle_request`].
+    Apply(Option<String>),
+    /// Drop the request: it was applied already or is being applied now.
+    Skip,
+}
+
+/// Claim the idempotency key of `request` in `session_id`.
+///
+/// Clients replaying requests they queued while offline tag messages and
+/// permission responses with an idempotency key, and keep each request until
+/// the daemon acknowledges its key with a `RequestApplied` event. A copy of a
+/// request already applied is dropped and acknowledged again; a copy arriving
+/// while another is being applied, or when the key cannot be recorded, is
+/// dropped unacknowledged so the client retries it later. Untagged requests
+/// are always applied.
+pub async fn claim_request(
+    db: &Database,
+    multiplexer: &SessionMultiplexer,
+    session_id: &str,
+    request: &AgentRequest,
+) -> Claim {
+    use betcode_proto::v1::agent_request::Request;
+
+    let key = match &request.request {
+        Some(Request::Message(msg)) => msg.idempotency_key.as_str(),
+        Some(Request::Permission(perm)) => perm.idempotency_key.as_str(),
+        _ => return Claim::Apply(None),
+    };
+    if key.is_empty() {
+        return Claim::Apply(None);
+    }
+    match db.claim_request_key(session_id, key).await {
+        Ok(KeyClaim::Claimed) => Claim::Apply(Some(key.to_string())),
+        Ok(KeyClaim::Applied) => {
+            info!(session_id, idempotency_key = %key, "Dropping already applied replayed request");
+            acknowledge(multiplexer, session_id, key, true).await;
+            Claim::Skip
+        }
+        Ok(KeyClaim::Pending) => {
+            info!(session_id, idempotency_key = %key, "Dropping replayed request still being applied");
+            Claim::Skip
+        }
+        Err(e) => {
+            warn!(?e, session_id, idempotency_key = %key, "Failed to record idempotency key");
+            Claim::Skip
+        }
+    }
+}
+
+/// Settle a key claimed by [`claim_request`] once its request has run.
+///
+/// A key whose request succeeded is kept and acknowledged; one whose request
+/// failed is released so the client's next replay applies it.
+pub async fn settle_request(
+    db: &Database,
+    multiplexer: &SessionMultiplexer,
+    session_id: &str,
+    key: &str,
+    applied: bool,
+) {
+    if !applied {
+        if let Err(e) = db.release_request_key(session_id, key).await {
+            warn!(?e, session_id, idempotency_key = %key, "Failed to release idempotency key");
+        }
+        return;
+    }
+    if let Err(e) = db.finish_request_key(session_id, key).await {
+        // The request went through, so it is still acknowledged; the key
+        // stays pending until it expires.
+        warn!(?e, session_id, idempotency_key = %key, "Failed to mark idempotency key applied");
+    }
+    acknowledge(multiplexer, session_id, key, false).await;
+}
+
+/// Tell the clients of `session_id` that the request tagged `key` was applied.
+async fn acknowledge(
+    multiplexer: &SessionMultiplexer,
+    session_id: &str,
+    key: &str,
+    duplicate: bool,
+) {
+    let event = AgentEvent {
+        sequence: 0,
+        timestamp: Some(prost_types::Timestamp::from(std::time::SystemTime::now())),
+        parent_tool_use_id: String::new(),
+        event: Some(Event::RequestApplied(RequestApplied {
+            idempotency_key: key.to_string(),
+            duplicate,
+        })),
+    };
+    multiplexer.broadcast(session_id, event).await;
+}
+
 /// Handle a single agent request using the relay.
 pub async fn handle_agent_request(
     ctx: &HandlerContext<'_>,
     session_id: &mut Option<String>,
     pending_config: &mut Option<RelaySessionConfig>,
     request: AgentRequest,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let claimed = match session_id.as_deref() {
+        Some(sid) => match claim_request(ctx.db, ctx.multiplexer, sid, &request).await {
+            Claim::Apply(key) => key.map(|key| (sid.to_string(), key)),
+            Claim::Skip => return Ok(()),
+        },
+        None => None,
+    };
+
+    let result = apply_agent_request(ctx, session_id, pending_config, request).await;
+    if let Some((sid, key)) = claimed {
+        settle_request(ctx.db, ctx.multiplexer, &sid, &key, result.is_ok()).await;
+    }
+    result
+}
+
+/// Apply a single agent request through the relay.
+async fn apply_agent_request(
+    ctx: &HandlerContext<'_>,
+    session_id: &mut Option<String>,
+    pending_config: &mut Option<RelaySessionConfig>,
+    request: AgentRequest,
 ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     use betcode_proto::v1::agent_request::Request;
 
@@ -39,8 +158,12 @@ pub async fn handle_agent_request(
                 // Deferred subprocess spawn: start on first UserMessage.
                 // Pass the message content as `-p` so Claude starts in headless
                 // print mode (not interactive TUI). Skip send_user_message since
-                // the content was already passed as the prompt.
-                let consumed_as_prompt = if let Some(config) = pending_config.take() {
+                // the content was already passed as the prompt. A session that is
+                // already running (a client reconnecting to it) takes the message
+                // on stdin instead.
+                let consumed_as_prompt = if let Some(config) = pending_config.take()
+                    && !ctx.relay.is_active(sid).await
+                {
                     info!(session_id = %sid, "Starting deferred subprocess on first user message");
                     ctx.relay
                         .start_session(config, Some(msg.content.clone()))
@@ -23,6 +23,7 @@ pub use command_svc::CommandServiceImpl;
 pub use config::ServerConfig;
 pub use config_svc::ConfigServiceImpl;
 pub use gitlab_svc::GitLabServiceImpl;
+pub use handler::{Claim, claim_request, settle_request};
 pub use health::HealthServiceImpl;
 pub use repo_svc::GitRepoServiceImpl;
 pub use subagent_svc::SubagentServiceImpl;
@@ -52,7 +53,7 @@ use crate::commands::CommandRegistry;
 use crate::commands::service_executor::ServiceExecutor;
 use crate::completion::agent_lister::AgentLister;
 use crate::completion::file_index::FileIndex;
-use crate::gitlab::{GitLabClient, GitLabConfig};
+use crate::forge::ForgeRegistry;
 use crate::orchestration::manager::SubagentManager;
 use crate::orchestration::pool::SubprocessPool;
 use crate::plugin::manager::PluginManager;
@@ -87,6 +88,7 @@ pub struct GrpcServer {
     repo_service: GitRepoServiceImpl,
     version_service: VersionServiceImpl,
     worktree_service: WorktreeServiceImpl,
+    worktree_manager: WorktreeManager,
     claude_bin: std::path::PathBuf,
 }
 
@@ -161,9 +163,11 @@ impl GrpcServer {
             claude_bin.clone(),
         );
 
-        let worktree_manager = WorktreeManager::new(db.clone(), worktree_base_dir);
+        let worktree_manager = WorktreeManager::new(db.clone(), worktree_base_dir).with_max_idle(
+            std::time::Duration::from_secs(config.worktree_max_idle_secs),
+        );
         let repo_service = GitRepoServiceImpl::new(db.clone(), worktree_manager.clone());
-        let worktree_service = WorktreeServiceImpl::new(worktree_manager, db.clone());
+        let worktree_service = WorktreeServiceImpl::new(worktree_manager.clone(), db.clone());
 
         Self {
             config,
@@ -176,6 +180,7 @@ impl GrpcServer {
             repo_service,
             version_service,
             worktree_service,
+            worktree_manager,
             claude_bin,
         }
     }
@@ -195,11 +200,18 @@ impl GrpcServer {
 
         // Create subagent orchestration infrastructure
         let subagent_pool = Arc::new(SubprocessPool::new(self.config.max_processes));
-        let subagent_manager = Arc::new(SubagentManager::new(
-            subagent_pool,
-            self.db.clone(),
-            self.claude_bin.clone(),
-        ));
+        let subagent_manager = Arc::new(
+            SubagentManager::new(subagent_pool, self.db.clone(), self.claude_bin.clone())
+                .with_worktrees(self.worktree_manager.clone())
+                .with_permission_fallback(self.config.subagent_permission_fallback)
+                .with_notifications(self.multiplexer.notification_sender()),
+        );
+        // Pick up subagents and orchestrations a previous process left running
+        if let Err(e) = subagent_manager.recover(self.config.resume_subagents).await {
+            warn!(error = %e, "Failed to recover interrupted subagents");
+        }
+        subagent_manager.spawn_permission_sweeper();
+        crate::gitlab::cache::spawn_pruner(self.db.clone());
         let subagent_service = SubagentServiceImpl::new(subagent_manager, self.db.clone());
 
         let (grpc_health_reporter, grpc_health_service) = tonic_health::server::health_reporter();
@@ -303,24 +315,15 @@ impl GrpcServer {
 
     /// Try to build a `GitLabServiceImpl` from environment variables.
     ///
-    /// Returns `Some` when both `BETCODE_GITLAB_URL` and `BETCODE_GITLAB_TOKEN`
-    /// are set (and valid); returns `None` otherwise, meaning GitLab RPCs through
-    /// the tunnel will respond with "not available".
-    pub fn gitlab_service_impl_from_env() -> Option<GitLabServiceImpl> {
-        let base_url = std::env::var("BETCODE_GITLAB_URL")
-            .ok()
-            .filter(|s| !s.is_empty())?;
-        let token = std::env::var("BETCODE_GITLAB_TOKEN")
-            .ok()
-            .filter(|s| !s.is_empty())?;
-        let config = GitLabConfig { base_url, token };
-        let client = match GitLabClient::new(&config) {
-            Ok(c) => c,
-            Err(e) => {
-                warn!("Failed to create GitLab client: {e}");
-                return None;
-            }
-        };
-        Some(GitLabServiceImpl::new(Arc::new(client)))
+    /// Registers every forge configured via `BETCODE_GITLAB_*`,
+    /// `BETCODE_GITHUB_*` and `BETCODE_GITEA_*` (see [`ForgeRegistry::from_env`]).
+    /// Returns `None` when none is configured, meaning forge RPCs through the
+    /// tunnel will respond with "not available".
+    pub fn gitlab_service_impl_from_env(&self) -> Option<GitLabServiceImpl> {
+        let forges = ForgeRegistry::from_env(Some(self.db.clone()));
+        if forges.is_empty() {
+            return None;
+        }
+        Some(GitLabServiceImpl::new(Arc::new(forges)))
     }
 }
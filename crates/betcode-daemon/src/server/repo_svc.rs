@@ -8,12 +8,13 @@ use tracing::{info, instrument, warn};
 use betcode_proto::v1::{
     BranchInfo, CreateBranchRequest, DeleteBranchRequest, DeleteBranchResponse, GetBranchRequest,
     GetRepoRequest, GitRepoDetail, ListBranchesRequest, ListBranchesResponse, ListReposRequest,
-    ListReposResponse, RegisterRepoRequest, ScanReposRequest, UnregisterRepoRequest,
-    UnregisterRepoResponse, UpdateRepoRequest, WorktreeMode,
+    ListReposResponse, RegisterRepoRequest, SandboxBackend, ScanReposRequest,
+    UnregisterRepoRequest, UnregisterRepoResponse, UpdateRepoRequest, WorktreeMode,
     git_repo_service_server::GitRepoService,
 };
 
 use crate::storage::{Database, DatabaseError, GitRepoParams, GitRepoRow};
+use crate::subprocess::sandbox;
 use crate::worktree::WorktreeManager;
 
 /// Timeout for git subprocess commands.
@@ -64,6 +65,64 @@ fn worktree_mode_to_str(mode: i32) -> Result<&'static str, Status> {
     }
 }
 
+/// Convert a stored sandbox policy (JSON) to its proto form.
+///
+/// Unset or unreadable policies map to `None` (daemon default).
+fn sandbox_to_proto(json: Option<&str>) -> Option<betcode_proto::v1::SandboxPolicy> {
+    let policy: sandbox::SandboxPolicy = match serde_json::from_str(json?) {
+        Ok(p) => p,
+        Err(e) => {
+            warn!(error = %e, "Invalid sandbox policy in database");
+            return None;
+        }
+    };
+    let backend = match policy.backend {
+        sandbox::SandboxBackend::None => SandboxBackend::None,
+        sandbox::SandboxBackend::Bubblewrap => SandboxBackend::Bubblewrap,
+        sandbox::SandboxBackend::Podman => SandboxBackend::Podman,
+    };
+    let paths = |v: &[std::path::PathBuf]| v.iter().map(|p| p.display().to_string()).collect();
+    Some(betcode_proto::v1::SandboxPolicy {
+        backend: backend as i32,
+        network: policy.network.to_string(),
+        read_only_paths: paths(&policy.read_only_paths),
+        read_write_paths: paths(&policy.read_write_paths),
+        image: policy.image.unwrap_or_default(),
+        auto_approve: policy.auto_approve,
+    })
+}
+
+/// Convert a proto sandbox policy to the JSON stored on the repo.
+///
+/// An unspecified backend clears the override (`None`).
+fn sandbox_from_proto(p: betcode_proto::v1::SandboxPolicy) -> Result<Option<String>, Status> {
+    let backend = match SandboxBackend::try_from(p.backend) {
+        Ok(SandboxBackend::Unspecified) => return Ok(None),
+        Ok(SandboxBackend::None) => sandbox::SandboxBackend::None,
+        Ok(SandboxBackend::Bubblewrap) => sandbox::SandboxBackend::Bubblewrap,
+        Ok(SandboxBackend::Podman) => sandbox::SandboxBackend::Podman,
+        Err(_) => {
+            return Err(Status::invalid_argument(format!(
+                "Invalid sandbox backend value: {}",
+                p.backend
+            )));
+        }
+    };
+    let invalid = |e: sandbox::SandboxError| Status::invalid_argument(e.to_string());
+    let policy = sandbox::SandboxPolicy {
+        backend,
+        network: p.network.parse().map_err(invalid)?,
+        read_only_paths: p.read_only_paths.into_iter().map(Into::into).collect(),
+        read_write_paths: p.read_write_paths.into_iter().map(Into::into).collect(),
+        image: Some(p.image).filter(|i| !i.is_empty()),
+        auto_approve: p.auto_approve,
+    };
+    policy.validate().map_err(invalid)?;
+    serde_json::to_string(&policy)
+        .map(Some)
+        .map_err(|e| Status::internal(e.to_string()))
+}
+
 /// Convert a `GitRepoRow` into a proto `GitRepoDetail`.
 fn to_detail(row: GitRepoRow, worktree_count: u32) -> GitRepoDetail {
     GitRepoDetail {
@@ -76,6 +135,7 @@ fn to_detail(row: GitRepoRow, worktree_count: u32) -> GitRepoDetail {
         setup_script: row.setup_script.unwrap_or_default(),
         auto_gitignore: row.auto_gitignore != 0,
         worktree_count,
+        sandbox: sandbox_to_proto(row.sandbox.as_deref()),
         created_at: Some(prost_types::Timestamp {
             seconds: row.created_at,
             nanos: 0,
@@ -430,7 +490,12 @@ impl GitRepoService for GitRepoServiceImpl {
             .setup_script
             .as_deref()
             .map(|s| if s.is_empty() { None } else { Some(s) });
+        let sandbox_json = req.sandbox.map(sandbox_from_proto).transpose()?;
 
+        let not_found_or_internal = |e: DatabaseError| match e {
+            DatabaseError::NotFound(_) => Status::not_found(e.to_string()),
+            _ => Status::internal(e.to_string()),
+        };
         let row = self
             .db
             .update_git_repo_partial(
@@ -443,10 +508,15 @@ impl GitRepoService for GitRepoServiceImpl {
                 req.auto_gitignore,
             )
             .await
-            .map_err(|e| match e {
-                DatabaseError::NotFound(_) => Status::not_found(e.to_string()),
-                _ => Status::internal(e.to_string()),
-            })?;
+            .map_err(not_found_or_internal)?;
+        let row = match sandbox_json {
+            Some(json) => self
+                .db
+                .set_git_repo_sandbox(&req.id, json.as_deref())
+                .await
+                .map_err(not_found_or_internal)?,
+            None => row,
+        };
 
         info!(id = %row.id, "Repository updated via gRPC");
 
@@ -1159,6 +1229,7 @@ mod tests {
                 custom_path: Some("/custom/path".into()),
                 setup_script: Some("make build".into()),
                 auto_gitignore: Some(false),
+                sandbox: None,
             }))
             .await
             .unwrap();
@@ -1200,6 +1271,7 @@ mod tests {
                 custom_path: None,
                 setup_script: None,
                 auto_gitignore: None,
+                sandbox: None,
             }))
             .await
             .unwrap();
@@ -1212,6 +1284,62 @@ mod tests {
         assert!(detail.auto_gitignore);
     }
 
+    #[tokio::test]
+    async fn update_repo_sets_and_clears_sandbox() {
+        let (svc, tmp) = test_service().await;
+        let repo_path = make_fake_repo(tmp.path(), "sandboxed");
+        let id = svc
+            .register_repo(Request::new(RegisterRepoRequest {
+                repo_path,
+                ..Default::default()
+            }))
+            .await
+            .unwrap()
+            .into_inner()
+            .id;
+        let update = |sandbox| UpdateRepoRequest {
+            id: id.clone(),
+            sandbox: Some(sandbox),
+            ..Default::default()
+        };
+
+        let detail = svc
+            .update_repo(Request::new(update(betcode_proto::v1::SandboxPolicy {
+                backend: SandboxBackend::Bubblewrap as i32,
+                network: "none".into(),
+                read_only_paths: vec!["/opt/node".into()],
+                auto_approve: true,
+                ..Default::default()
+            })))
+            .await
+            .unwrap()
+            .into_inner();
+        let policy = detail.sandbox.unwrap();
+        assert_eq!(policy.backend, SandboxBackend::Bubblewrap as i32);
+        assert_eq!(policy.network, "none");
+        assert_eq!(policy.read_only_paths, vec!["/opt/node".to_string()]);
+        assert!(policy.auto_approve);
+
+        // Podman without an image is rejected.
+        let err = svc
+            .update_repo(Request::new(update(betcode_proto::v1::SandboxPolicy {
+                backend: SandboxBackend::Podman as i32,
+                ..Default::default()
+            })))
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), tonic::Code::InvalidArgument);
+
+        let detail = svc
+            .update_repo(Request::new(update(
+                betcode_proto::v1::SandboxPolicy::default(),
+            )))
+            .await
+            .unwrap()
+            .into_inner();
+        assert!(detail.sandbox.is_none());
+    }
+
     #[tokio::test]
     async fn scan_repos_empty_path_returns_error() {
         let (svc, _tmp) = test_service().await;
@@ -10,9 +10,10 @@ use tracing::{info, instrument, warn};
 use betcode_proto::v1::{
     CancelSubagentRequest, CancelSubagentResponse, CreateOrchestrationRequest,
     CreateOrchestrationResponse, ListSubagentsRequest, ListSubagentsResponse, OrchestrationEvent,
-    OrchestrationStrategy, RevokeAutoApproveRequest, RevokeAutoApproveResponse,
+    OrchestrationStrategy, PermissionDecision, RevokeAutoApproveRequest, RevokeAutoApproveResponse,
     SendToSubagentRequest, SendToSubagentResponse, SpawnSubagentRequest, SpawnSubagentResponse,
-    SubagentEvent, SubagentInfo, SubagentStatus, WatchOrchestrationRequest, WatchSubagentRequest,
+    SubagentEvent, SubagentInfo, SubagentPermissionResponse, SubagentPermissionResult,
+    SubagentStatus, WatchOrchestrationRequest, WatchSubagentRequest,
     subagent_service_server::SubagentService,
 };
 
@@ -75,6 +76,40 @@ fn strategy_from_i32(v: i32) -> OrchestrationStrategy {
     OrchestrationStrategy::try_from(v).unwrap_or(OrchestrationStrategy::Parallel)
 }
 
+/// Turn the worker and reviewer steps of a review loop into one reviewed step.
+///
+/// A single step that already sets its review is taken as is.
+fn fold_review_loop(
+    steps: Vec<betcode_proto::v1::OrchestrationStep>,
+    max_iterations: u32,
+) -> Result<Vec<betcode_proto::v1::OrchestrationStep>, Status> {
+    if let [step] = steps.as_slice()
+        && step.review.is_some()
+    {
+        return Ok(steps);
+    }
+    let [mut worker, reviewer]: [betcode_proto::v1::OrchestrationStep; 2] =
+        steps.try_into().map_err(|_| {
+            Status::invalid_argument(
+                "review_loop takes a worker and a reviewer step, or one step with a review",
+            )
+        })?;
+    if worker.review.is_some() {
+        return Err(Status::invalid_argument(
+            "review_loop worker step must not set its own review",
+        ));
+    }
+    worker.review = Some(betcode_proto::v1::StepReview {
+        prompt: reviewer.prompt,
+        model: reviewer.model,
+        allowed_tools: reviewer.allowed_tools,
+        auto_approve: reviewer.auto_approve,
+        max_turns: reviewer.max_turns,
+        max_iterations,
+    });
+    Ok(vec![worker])
+}
+
 #[tonic::async_trait]
 impl SubagentService for SubagentServiceImpl {
     type WatchSubagentStream = SubagentEventStream;
@@ -122,6 +157,7 @@ impl SubagentService for SubagentServiceImpl {
             max_turns: req.max_turns,
             auto_approve: req.auto_approve,
             timeout_secs: 0,
+            resume_session: None,
         };
 
         let id = self
@@ -303,6 +339,10 @@ impl SubagentService for SubagentServiceImpl {
             })
             .collect();
 
+        if strategy == OrchestrationStrategy::ReviewLoop {
+            steps = fold_review_loop(steps, req.max_review_iterations)?;
+        }
+
         // For sequential strategy, chain each step to depend on the previous
         if strategy == OrchestrationStrategy::Sequential {
             for i in 1..steps.len() {
@@ -400,6 +440,10 @@ impl SubagentService for SubagentServiceImpl {
             .revoke_auto_approve(&req.subagent_id)
             .await
             .map_err(|e| manager_err_to_status(&e))?;
+        let pending = self
+            .manager
+            .pending_permission_count(&req.subagent_id)
+            .await;
 
         if revoked {
             info!(subagent_id = %req.subagent_id, "Auto-approve revoked");
@@ -410,6 +454,18 @@ impl SubagentService for SubagentServiceImpl {
             );
         }
 
+        if revoked && req.terminate_if_pending && pending > 0 {
+            let reason = if req.reason.is_empty() {
+                "Auto-approve revoked with pending permission requests"
+            } else {
+                req.reason.as_str()
+            };
+            self.manager
+                .cancel(&req.subagent_id, reason)
+                .await
+                .map_err(|e| manager_err_to_status(&e))?;
+        }
+
         // Get current status from DB
         let status = match self.db.get_subagent(&req.subagent_id).await {
             Ok(sa) => sa.status,
@@ -418,10 +474,48 @@ impl SubagentService for SubagentServiceImpl {
 
         Ok(Response::new(RevokeAutoApproveResponse {
             revoked,
-            pending_tool_calls: 0,
+            #[allow(clippy::cast_possible_truncation)]
+            pending_tool_calls: pending as u32,
             subagent_status: status,
         }))
     }
+
+    #[instrument(skip(self, request), fields(rpc = "RespondToSubagentPermission"))]
+    async fn respond_to_subagent_permission(
+        &self,
+        request: Request<SubagentPermissionResponse>,
+    ) -> Result<Response<SubagentPermissionResult>, Status> {
+        let req = request.into_inner();
+
+        if req.subagent_id.is_empty() {
+            return Err(Status::invalid_argument("subagent_id must not be empty"));
+        }
+        if req.request_id.is_empty() {
+            return Err(Status::invalid_argument("request_id must not be empty"));
+        }
+        let decision = match PermissionDecision::try_from(req.decision) {
+            Ok(PermissionDecision::Unspecified) | Err(_) => {
+                return Err(Status::invalid_argument("decision must be specified"));
+            }
+            Ok(decision) => decision,
+        };
+
+        let accepted = self
+            .manager
+            .respond_to_permission(&req.subagent_id, &req.request_id, decision)
+            .await
+            .map_err(|e| manager_err_to_status(&e))?;
+
+        info!(
+            subagent_id = %req.subagent_id,
+            request_id = %req.request_id,
+            ?decision,
+            accepted,
+            "Subagent permission response"
+        );
+
+        Ok(Response::new(SubagentPermissionResult { accepted }))
+    }
 }
 
 #[cfg(test)]
@@ -554,6 +648,38 @@ mod tests {
     }
 
     // jscpd:ignore-start -- validation tests are intentionally repetitive
+    #[tokio::test]
+    async fn respond_to_permission_rejects_missing_fields() {
+        let svc = test_service().await;
+        let req = Request::new(SubagentPermissionResponse {
+            subagent_id: String::new(),
+            request_id: "req-1".to_string(),
+            decision: PermissionDecision::AllowOnce.into(),
+        });
+        let err = svc.respond_to_subagent_permission(req).await.unwrap_err();
+        assert_eq!(err.code(), tonic::Code::InvalidArgument);
+
+        let req = Request::new(SubagentPermissionResponse {
+            subagent_id: "sa-1".to_string(),
+            request_id: "req-1".to_string(),
+            decision: PermissionDecision::Unspecified.into(),
+        });
+        let err = svc.respond_to_subagent_permission(req).await.unwrap_err();
+        assert_eq!(err.code(), tonic::Code::InvalidArgument);
+    }
+
+    #[tokio::test]
+    async fn respond_to_permission_unknown_subagent() {
+        let svc = test_service().await;
+        let req = Request::new(SubagentPermissionResponse {
+            subagent_id: "nonexistent".to_string(),
+            request_id: "req-1".to_string(),
+            decision: PermissionDecision::Deny.into(),
+        });
+        let err = svc.respond_to_subagent_permission(req).await.unwrap_err();
+        assert_eq!(err.code(), tonic::Code::NotFound);
+    }
+
     #[tokio::test]
     async fn create_orchestration_rejects_empty_session() {
         let svc = test_service().await;
@@ -561,6 +687,7 @@ mod tests {
             parent_session_id: String::new(),
             steps: vec![],
             strategy: 1,
+            max_review_iterations: 0,
         });
         let err = svc.create_orchestration(req).await.unwrap_err();
         assert_eq!(err.code(), tonic::Code::InvalidArgument);
@@ -573,6 +700,7 @@ mod tests {
             parent_session_id: "parent-1".to_string(),
             steps: vec![],
             strategy: 1,
+            max_review_iterations: 0,
         });
         let err = svc.create_orchestration(req).await.unwrap_err();
         assert_eq!(err.code(), tonic::Code::InvalidArgument);
@@ -636,9 +764,39 @@ mod tests {
         assert_eq!(strategy_from_i32(1), OrchestrationStrategy::Parallel);
         assert_eq!(strategy_from_i32(2), OrchestrationStrategy::Sequential);
         assert_eq!(strategy_from_i32(3), OrchestrationStrategy::Dag);
+        assert_eq!(strategy_from_i32(4), OrchestrationStrategy::ReviewLoop);
         assert_eq!(strategy_from_i32(99), OrchestrationStrategy::Parallel); // default
     }
 
+    #[test]
+    fn review_loop_folds_reviewer_into_worker() {
+        let worker = betcode_proto::v1::OrchestrationStep {
+            id: "work".to_string(),
+            prompt: "Fix the parser".to_string(),
+            ..Default::default()
+        };
+        let reviewer = betcode_proto::v1::OrchestrationStep {
+            id: "review".to_string(),
+            prompt: "cargo test passes".to_string(),
+            model: "claude-opus-4".to_string(),
+            ..Default::default()
+        };
+
+        let steps = fold_review_loop(vec![worker.clone(), reviewer.clone()], 5).unwrap();
+        assert_eq!(steps.len(), 1);
+        assert_eq!(fold_review_loop(steps.clone(), 0).unwrap(), steps);
+        assert_eq!(steps[0].id, "work");
+        let review = steps[0].review.clone().unwrap();
+        assert_eq!(review.prompt, "cargo test passes");
+        assert_eq!(review.model, "claude-opus-4");
+        assert_eq!(review.max_iterations, 5);
+
+        let err = fold_review_loop(vec![worker], 0).unwrap_err();
+        assert_eq!(err.code(), tonic::Code::InvalidArgument);
+        let err = fold_review_loop(steps.into_iter().chain([reviewer]).collect(), 0).unwrap_err();
+        assert_eq!(err.code(), tonic::Code::InvalidArgument);
+    }
+
     #[tokio::test]
     async fn sequential_strategy_adds_dependencies() {
         // The gRPC layer auto-chains sequential dependencies before passing
@@ -670,6 +828,7 @@ mod tests {
             parent_session_id: "parent-1".to_string(),
             steps,
             strategy: i32::from(OrchestrationStrategy::Sequential),
+            max_review_iterations: 0,
         });
 
         let resp = svc
@@ -1,16 +1,30 @@
 //! `WorktreeService` gRPC implementation.
 
+use std::pin::Pin;
+
+use tokio::sync::mpsc;
+use tokio_stream::Stream;
+use tokio_stream::wrappers::ReceiverStream;
 use tonic::{Request, Response, Status};
 use tracing::{debug, info, instrument};
 
 use betcode_proto::v1::{
-    CreateWorktreeRequest, GetWorktreeRequest, ListWorktreesRequest, ListWorktreesResponse,
-    RemoveWorktreeRequest, RemoveWorktreeResponse, WorktreeDetail,
-    worktree_service_server::WorktreeService,
+    CreateWorktreeRequest, GcWorktreeEntry, GcWorktreesRequest, GcWorktreesResponse,
+    GetWorktreeRequest, GetWorktreeStatusRequest, ListWorktreesRequest, ListWorktreesResponse,
+    RemoveWorktreeRequest, RemoveWorktreeResponse, RunWorktreeSetupRequest, SetupFinished,
+    SetupOutputLine, SetupStepResult, SetupStepStarted, SyncWorktreeRequest, SyncWorktreeResponse,
+    WorktreeDetail, WorktreeSetupEvent, worktree_service_server::WorktreeService,
+    worktree_setup_event::Event,
+};
+
+use crate::storage::{Database, DatabaseError};
+use crate::worktree::setup::stored_steps;
+use crate::worktree::{
+    GcEntry, GitRepo, SetupEvent, SetupStart, StepResult, SyncStrategy, WorktreeError,
+    WorktreeInfo, WorktreeManager, WorktreeStatus,
 };
 
-use crate::storage::Database;
-use crate::worktree::{GitRepo, WorktreeInfo, WorktreeManager};
+type WorktreeSetupStream = Pin<Box<dyn Stream<Item = Result<WorktreeSetupEvent, Status>> + Send>>;
 
 /// `WorktreeService` implementation backed by `WorktreeManager`.
 #[derive(Clone)]
@@ -28,6 +42,10 @@ impl WorktreeServiceImpl {
 
 /// Convert a `WorktreeInfo` into a proto `WorktreeDetail`.
 fn to_detail(info: WorktreeInfo) -> WorktreeDetail {
+    let setup_steps = stored_steps(&info.worktree)
+        .into_iter()
+        .map(to_step_proto)
+        .collect();
     WorktreeDetail {
         id: info.worktree.id,
         name: info.worktree.name,
@@ -46,11 +64,109 @@ fn to_detail(info: WorktreeInfo) -> WorktreeDetail {
             seconds: info.worktree.last_active,
             nanos: 0,
         }),
+        setup_status: info.worktree.setup_status,
+        setup_steps,
+    }
+}
+
+fn to_step_proto(step: StepResult) -> SetupStepResult {
+    SetupStepResult {
+        name: step.name,
+        status: step.status.as_str().to_string(),
+        exit_code: step.exit_code,
+        duration_ms: step.duration_ms,
+        error: step.error.unwrap_or_default(),
+    }
+}
+
+#[allow(clippy::cast_possible_truncation)]
+fn to_setup_event(event: SetupEvent) -> WorktreeSetupEvent {
+    let event = match event {
+        SetupEvent::StepStarted { index, total, name } => Event::StepStarted(SetupStepStarted {
+            index: index as u32,
+            total: total as u32,
+            name,
+        }),
+        SetupEvent::Output { step, stderr, line } => {
+            Event::Output(SetupOutputLine { step, stderr, line })
+        }
+        SetupEvent::StepFinished(result) => Event::StepFinished(to_step_proto(result)),
+        SetupEvent::Finished(status) => Event::Finished(SetupFinished {
+            status: status.as_str().to_string(),
+        }),
+    };
+    WorktreeSetupEvent { event: Some(event) }
+}
+
+/// Convert a lifecycle `WorktreeStatus` into its proto form.
+fn to_status_proto(status: WorktreeStatus) -> betcode_proto::v1::WorktreeStatus {
+    betcode_proto::v1::WorktreeStatus {
+        id: status.worktree.id,
+        branch: status.worktree.branch,
+        dirty_files: status
+            .dirty_files
+            .into_iter()
+            .map(|f| betcode_proto::v1::DirtyFile {
+                path: f.path,
+                status: f.status,
+            })
+            .collect(),
+        compare_ref: status.compare_ref.unwrap_or_default(),
+        ahead: status.ahead,
+        behind: status.behind,
+        last_commit: status
+            .last_commit
+            .map(|c| betcode_proto::v1::CommitSummary {
+                sha: c.sha,
+                subject: c.subject,
+                author: c.author,
+                committed_at: Some(prost_types::Timestamp {
+                    seconds: c.committed_at,
+                    nanos: 0,
+                }),
+            }),
+        exists_on_disk: status.exists_on_disk,
+    }
+}
+
+fn to_gc_entry(entry: GcEntry) -> GcWorktreeEntry {
+    GcWorktreeEntry {
+        id: entry.worktree.id,
+        name: entry.worktree.name,
+        branch: entry.worktree.branch,
+        path: entry.worktree.path,
+        reasons: entry
+            .reasons
+            .iter()
+            .map(|r| r.as_str().to_string())
+            .collect(),
+        removed: entry.removed,
+        skipped_reason: entry.skipped_reason.unwrap_or_default(),
+    }
+}
+
+/// Map lifecycle errors: unknown worktrees are `NotFound`, git refusals
+/// (dirty tree, missing base branch) and concurrent setup runs are
+/// `FailedPrecondition`, invalid pipeline files are `InvalidArgument`.
+fn lifecycle_status(e: &WorktreeError) -> Status {
+    match e {
+        WorktreeError::NotFound(_) | WorktreeError::Database(DatabaseError::NotFound(_)) => {
+            Status::not_found(e.to_string())
+        }
+        WorktreeError::Git(_) | WorktreeError::SetupFailed(_) => {
+            Status::failed_precondition(e.to_string())
+        }
+        WorktreeError::InvalidSetup(_) | WorktreeError::InvalidRef(_) => {
+            Status::invalid_argument(e.to_string())
+        }
+        _ => Status::internal(e.to_string()),
     }
 }
 
 #[tonic::async_trait]
 impl WorktreeService for WorktreeServiceImpl {
+    type RunWorktreeSetupStream = WorktreeSetupStream;
+
     #[instrument(skip(self, request), fields(rpc = "CreateWorktree"))]
     async fn create_worktree(
         &self,
@@ -81,10 +197,16 @@ impl WorktreeService for WorktreeServiceImpl {
             Some(req.setup_script.as_str())
         };
 
-        let wt = self
-            .manager
-            .create(&req.name, &repo, &req.branch, setup_script)
-            .await
+        let created = if req.defer_setup {
+            self.manager
+                .create_deferred(&req.name, &repo, &req.branch, setup_script)
+                .await
+        } else {
+            self.manager
+                .create(&req.name, &repo, &req.branch, setup_script)
+                .await
+        };
+        let wt = created
             .map_err(|e| {
                 debug!(elapsed_ms = start.elapsed().as_millis(), error = %e, "CreateWorktree manager.create failed");
                 Status::internal(e.to_string())
@@ -178,6 +300,138 @@ impl WorktreeService for WorktreeServiceImpl {
 
         Ok(Response::new(to_detail(info)))
     }
+
+    #[instrument(skip(self, request), fields(rpc = "GetWorktreeStatus"))]
+    async fn get_worktree_status(
+        &self,
+        request: Request<GetWorktreeStatusRequest>,
+    ) -> Result<Response<betcode_proto::v1::WorktreeStatus>, Status> {
+        let req = request.into_inner();
+
+        let status = self
+            .manager
+            .status(&req.id)
+            .await
+            .map_err(|e| lifecycle_status(&e))?;
+
+        Ok(Response::new(to_status_proto(status)))
+    }
+
+    #[instrument(skip(self, request), fields(rpc = "SyncWorktree"))]
+    async fn sync_worktree(
+        &self,
+        request: Request<SyncWorktreeRequest>,
+    ) -> Result<Response<SyncWorktreeResponse>, Status> {
+        let req = request.into_inner();
+
+        let strategy = match betcode_proto::v1::SyncStrategy::try_from(req.strategy) {
+            Ok(betcode_proto::v1::SyncStrategy::Merge) => SyncStrategy::Merge,
+            Ok(_) => SyncStrategy::Rebase,
+            Err(_) => {
+                return Err(Status::invalid_argument(format!(
+                    "Unknown sync strategy: {}",
+                    req.strategy
+                )));
+            }
+        };
+        let base_ref = if req.base_ref.is_empty() {
+            None
+        } else {
+            Some(req.base_ref.as_str())
+        };
+
+        let outcome = self
+            .manager
+            .sync(&req.id, strategy, base_ref)
+            .await
+            .map_err(|e| lifecycle_status(&e))?;
+
+        let status = self
+            .manager
+            .status(&req.id)
+            .await
+            .map_err(|e| lifecycle_status(&e))?;
+
+        if outcome.success {
+            info!(id = %req.id, base_ref = %outcome.base_ref, "Worktree synced via gRPC");
+        }
+
+        Ok(Response::new(SyncWorktreeResponse {
+            success: outcome.success,
+            base_ref: outcome.base_ref,
+            conflicts: outcome.conflicts,
+            message: outcome.message,
+            status: Some(to_status_proto(status)),
+        }))
+    }
+
+    #[instrument(skip(self, request), fields(rpc = "GcWorktrees"))]
+    async fn gc_worktrees(
+        &self,
+        request: Request<GcWorktreesRequest>,
+    ) -> Result<Response<GcWorktreesResponse>, Status> {
+        let req = request.into_inner();
+
+        let repo_id = if req.repo_id.is_empty() {
+            None
+        } else {
+            Some(req.repo_id.as_str())
+        };
+        let max_idle =
+            (req.max_idle_secs > 0).then(|| std::time::Duration::from_secs(req.max_idle_secs));
+
+        let report = self
+            .manager
+            .gc(repo_id, max_idle, req.dry_run)
+            .await
+            .map_err(|e| lifecycle_status(&e))?;
+
+        Ok(Response::new(GcWorktreesResponse {
+            entries: report.entries.into_iter().map(to_gc_entry).collect(),
+            dry_run: report.dry_run,
+        }))
+    }
+
+    #[instrument(skip(self, request), fields(rpc = "RunWorktreeSetup"))]
+    async fn run_worktree_setup(
+        &self,
+        request: Request<RunWorktreeSetupRequest>,
+    ) -> Result<Response<Self::RunWorktreeSetupStream>, Status> {
+        let req = request.into_inner();
+        let start = if !req.from_step.is_empty() {
+            SetupStart::Step(req.from_step)
+        } else if req.retry {
+            SetupStart::Resume
+        } else {
+            SetupStart::Beginning
+        };
+
+        // Surface unknown worktrees as a status rather than a stream error.
+        self.manager
+            .get(&req.id)
+            .await
+            .map_err(|e| lifecycle_status(&e))?;
+
+        let (tx, rx) = mpsc::channel(64);
+        let manager = self.manager.clone();
+        tokio::spawn(async move {
+            let (event_tx, mut event_rx) = mpsc::channel(64);
+            let run = manager.run_setup(&req.id, start, Some(event_tx));
+            let forward = async {
+                while let Some(event) = event_rx.recv().await {
+                    if tx.send(Ok(to_setup_event(event))).await.is_err() {
+                        break;
+                    }
+                }
+            };
+            let (result, ()) = tokio::join!(run, forward);
+            if let Err(e) = result {
+                let _ = tx.send(Err(lifecycle_status(&e))).await;
+            }
+        });
+
+        Ok(Response::new(Box::pin(ReceiverStream::new(rx))))
+    }
 }
 
 #[cfg(test)]
@@ -255,8 +509,103 @@ mod tests {
                 repo_id: "r1".to_string(),
                 branch: "main".to_string(),
                 setup_script: String::new(),
+                defer_setup: false,
             }))
             .await;
         assert!(result.is_err());
     }
+
+    #[tokio::test]
+    async fn status_nonexistent_returns_not_found() {
+        let (svc, _tmp) = test_service().await;
+        let err = svc
+            .get_worktree_status(Request::new(GetWorktreeStatusRequest {
+                id: "nope".to_string(),
+            }))
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), tonic::Code::NotFound);
+    }
+
+    #[tokio::test]
+    async fn sync_nonexistent_returns_not_found() {
+        let (svc, _tmp) = test_service().await;
+        let err = svc
+            .sync_worktree(Request::new(SyncWorktreeRequest {
+                id: "nope".to_string(),
+                strategy: betcode_proto::v1::SyncStrategy::Merge.into(),
+                base_ref: String::new(),
+            }))
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), tonic::Code::NotFound);
+    }
+
+    #[tokio::test]
+    async fn sync_rejects_unknown_strategy() {
+        let (svc, _tmp) = test_service().await;
+        let err = svc
+            .sync_worktree(Request::new(SyncWorktreeRequest {
+                id: "nope".to_string(),
+                strategy: 42,
+                base_ref: String::new(),
+            }))
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), tonic::Code::InvalidArgument);
+    }
+
+    #[tokio::test]
+    async fn sync_rejects_option_like_base_ref() {
+        let (svc, _tmp) = test_service().await;
+        let err = svc
+            .sync_worktree(Request::new(SyncWorktreeRequest {
+                id: "nope".to_string(),
+                strategy: betcode_proto::v1::SyncStrategy::Rebase.into(),
+                base_ref: "--exec=touch pwned".to_string(),
+            }))
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), tonic::Code::InvalidArgument);
+    }
+
+    #[tokio::test]
+    async fn gc_dry_run_reports_missing_worktree() {
+        let (svc, _tmp) = test_service().await;
+        svc.db
+            .create_git_repo(
+                "r1",
+                "/tmp/repo",
+                &crate::storage::GitRepoParams {
+                    name: "repo",
+                    worktree_mode: "global",
+                    local_subfolder: ".worktree",
+                    custom_path: None,
+                    setup_script: None,
+                    auto_gitignore: true,
+                },
+            )
+            .await
+            .unwrap();
+        svc.db
+            .create_worktree("wt-1", "feat", "/tmp/nonexistent-wt", "feat", "r1", None)
+            .await
+            .unwrap();
+
+        let resp = svc
+            .gc_worktrees(Request::new(GcWorktreesRequest {
+                repo_id: String::new(),
+                dry_run: true,
+                max_idle_secs: 0,
+            }))
+            .await
+            .unwrap()
+            .into_inner();
+        assert!(resp.dry_run);
+        assert_eq!(resp.entries.len(), 1);
+        assert_eq!(resp.entries[0].reasons, vec!["missing".to_string()]);
+        assert!(!resp.entries[0].removed);
+        // Dry run leaves the record in place.
+        assert!(svc.db.get_worktree("wt-1").await.is_ok());
+    }
 }
@@ -3,10 +3,12 @@
 //! Handles multiple client connections to a single session with event fan-out.
 
 mod multiplexer;
+mod notify;
 mod state;
 mod types;
 
 pub use multiplexer::SessionMultiplexer;
+pub use notify::{NotificationIntent, NotificationKind};
 pub use types::{
     ClientHandle, InputLockResult, MultiplexerConfig, MultiplexerError, MultiplexerStats,
 };
@@ -12,23 +12,31 @@ use tracing::{debug, info, warn};
 
 use betcode_proto::v1::AgentEvent;
 
+use super::notify::NotificationIntent;
 use super::state::SessionState;
 use super::types::{
     ClientHandle, InputLockResult, MultiplexerConfig, MultiplexerError, MultiplexerStats,
 };
 
+/// Capacity of the notification intent channel.
+const NOTIFICATION_CAPACITY: usize = 64;
+
 /// Session multiplexer manages multiple client connections to sessions.
 pub struct SessionMultiplexer {
     sessions: Arc<RwLock<HashMap<String, SessionState>>>,
     config: MultiplexerConfig,
+    /// Intents raised by forwarded events, for the tunnel to pass on.
+    notifications: broadcast::Sender<NotificationIntent>,
 }
 
 impl SessionMultiplexer {
     /// Create a new session multiplexer.
     pub fn new(config: MultiplexerConfig) -> Self {
+        let (notifications, _) = broadcast::channel(NOTIFICATION_CAPACITY);
         Self {
             sessions: Arc::new(RwLock::new(HashMap::new())),
             config,
+            notifications,
         }
     }
 
@@ -246,19 +254,41 @@ impl SessionMultiplexer {
             .is_some_and(|s| s.input_lock_holder.as_deref() == Some(client_id))
     }
 
+    /// Sender for notification intents raised outside session event streams,
+    /// e.g. by subagents.
+    pub fn notification_sender(&self) -> broadcast::Sender<NotificationIntent> {
+        self.notifications.clone()
+    }
+
+    /// Receive the notification intents raised from now on.
+    pub fn subscribe_notifications(&self) -> broadcast::Receiver<NotificationIntent> {
+        self.notifications.subscribe()
+    }
+
     /// Create a sender channel for forwarding subprocess events.
     ///
-    /// Events forwarded through this channel are assigned sequence numbers
-    /// before being broadcast, ensuring correct ordering for all clients.
+    /// Events forwarded through this channel keep the sequence number the
+    /// relay stored them under, so a client that reconnects can resume from
+    /// the last one it saw; events without one are numbered here before being
+    /// broadcast. Events worth a push notification also raise an intent.
     pub fn create_event_forwarder(&self, session_id: String) -> mpsc::Sender<AgentEvent> {
         let (tx, mut rx) = mpsc::channel::<AgentEvent>(128);
         let sessions = Arc::clone(&self.sessions);
+        let notifications = self.notifications.clone();
 
         tokio::spawn(async move {
             while let Some(mut event) = rx.recv().await {
+                if let Some(intent) = NotificationIntent::from_event(&session_id, &event) {
+                    // No receivers just means no tunnel is connected.
+                    let _ = notifications.send(intent);
+                }
                 let mut sessions = sessions.write().await;
                 if let Some(session) = sessions.get_mut(&session_id) {
-                    event.sequence = session.next_sequence();
+                    if event.sequence == 0 {
+                        event.sequence = session.next_sequence();
+                    } else {
+                        session.observe_sequence(event.sequence);
+                    }
                     let _ = session.event_tx.send(event);
                 }
             }
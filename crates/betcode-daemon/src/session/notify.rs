@@ -0,0 +1,165 @@
+//! Notification intents.
+//!
+//! An intent is the daemon's signal that something happened in a session
+//! that a user away from their terminal may want a push notification for.
+//! Intents carry identifiers only, never event content, so the tunnel can
+//! hand them to the relay in the clear while session traffic itself stays
+//! end-to-end encrypted.
+
+use std::collections::HashMap;
+
+use betcode_proto::v1::AgentEvent;
+use betcode_proto::v1::agent_event::Event;
+
+/// What a notification is about.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NotificationKind {
+    /// A tool call is waiting for permission.
+    PermissionRequest,
+    /// Claude asked the user a question.
+    UserQuestion,
+    /// A turn finished.
+    TurnComplete,
+    /// The session hit a fatal error.
+    Error,
+    /// A subagent finished successfully.
+    SubagentCompleted,
+    /// A subagent failed or timed out.
+    SubagentFailed,
+}
+
+impl NotificationKind {
+    /// Wire name, as understood by the relay.
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::PermissionRequest => "permission_request",
+            Self::UserQuestion => "user_question",
+            Self::TurnComplete => "turn_complete",
+            Self::Error => "error",
+            Self::SubagentCompleted => "subagent_completed",
+            Self::SubagentFailed => "subagent_failed",
+        }
+    }
+}
+
+/// A request to notify the machine's owner.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NotificationIntent {
+    pub kind: NotificationKind,
+    pub session_id: String,
+    /// Permission request or question the notification is about, if any.
+    pub request_id: String,
+    /// Subagent the notification is about, if any.
+    pub subagent_id: String,
+}
+
+impl NotificationIntent {
+    /// The intent raised by `event`, if it is one worth a notification.
+    pub fn from_event(session_id: &str, event: &AgentEvent) -> Option<Self> {
+        let (kind, request_id) = match event.event.as_ref()? {
+            Event::PermissionRequest(p) => {
+                (NotificationKind::PermissionRequest, p.request_id.clone())
+            }
+            Event::UserQuestion(q) => (NotificationKind::UserQuestion, q.question_id.clone()),
+            Event::TurnComplete(_) => (NotificationKind::TurnComplete, String::new()),
+            Event::Error(e) if e.is_fatal => (NotificationKind::Error, String::new()),
+            _ => return None,
+        };
+        Some(Self {
+            kind,
+            session_id: session_id.to_string(),
+            request_id,
+            subagent_id: String::new(),
+        })
+    }
+
+    /// The intent raised when subagent `subagent_id` of `session_id` ends.
+    pub fn subagent(kind: NotificationKind, session_id: &str, subagent_id: &str) -> Self {
+        Self {
+            kind,
+            session_id: session_id.to_string(),
+            request_id: String::new(),
+            subagent_id: subagent_id.to_string(),
+        }
+    }
+
+    /// Parameters of the tunnel control frame that carries this intent.
+    pub fn to_params(&self, machine_id: &str) -> HashMap<String, String> {
+        let mut params = HashMap::from([
+            ("machine_id".to_string(), machine_id.to_string()),
+            ("kind".to_string(), self.kind.as_str().to_string()),
+            ("session_id".to_string(), self.session_id.clone()),
+        ]);
+        if !self.request_id.is_empty() {
+            params.insert("request_id".to_string(), self.request_id.clone());
+        }
+        if !self.subagent_id.is_empty() {
+            params.insert("subagent_id".to_string(), self.subagent_id.clone());
+        }
+        params
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::panic, clippy::expect_used, clippy::unwrap_used)]
+mod tests {
+    use super::*;
+    use betcode_proto::v1::{ErrorEvent, PermissionRequest, TextDelta};
+
+    fn event(event: Event) -> AgentEvent {
+        AgentEvent {
+            event: Some(event),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn permission_request_raises_intent_without_content() {
+        let intent = NotificationIntent::from_event(
+            "s1",
+            &event(Event::PermissionRequest(PermissionRequest {
+                request_id: "r1".into(),
+                tool_name: "Bash".into(),
+                description: "rm -rf build".into(),
+                input: None,
+            })),
+        )
+        .unwrap();
+        assert_eq!(intent.kind, NotificationKind::PermissionRequest);
+        assert_eq!(intent.request_id, "r1");
+
+        let params = intent.to_params("m1");
+        assert_eq!(params["kind"], "permission_request");
+        assert_eq!(params["session_id"], "s1");
+        assert!(
+            !params
+                .values()
+                .any(|v| v.contains("Bash") || v.contains("rm -rf"))
+        );
+    }
+
+    #[test]
+    fn only_notable_events_raise_intents() {
+        let text = event(Event::TextDelta(TextDelta {
+            text: "hi".into(),
+            is_complete: true,
+        }));
+        assert!(NotificationIntent::from_event("s1", &text).is_none());
+
+        let error = |is_fatal| {
+            event(Event::Error(ErrorEvent {
+                code: "x".into(),
+                message: "y".into(),
+                is_fatal,
+                details: HashMap::new(),
+            }))
+        };
+        assert!(NotificationIntent::from_event("s1", &error(false)).is_none());
+        assert_eq!(
+            NotificationIntent::from_event("s1", &error(true))
+                .unwrap()
+                .kind,
+            NotificationKind::Error
+        );
+    }
+}
@@ -41,6 +41,11 @@ impl SessionState {
         self.sequence
     }
 
+    /// Note a sequence number assigned elsewhere so later numbers follow it.
+    pub fn observe_sequence(&mut self, sequence: u64) {
+        self.sequence = self.sequence.max(sequence);
+    }
+
     pub fn add_client(&mut self, client_id: String, client_type: String) {
         debug!(
             session_id = %self.session_id,
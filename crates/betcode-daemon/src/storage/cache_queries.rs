@@ -0,0 +1,94 @@
+//! Database queries for the `gitlab_response_cache` table.
+
+use betcode_core::db::unix_timestamp;
+
+use super::db::{Database, DatabaseError};
+use super::models::GitLabCacheRow;
+
+impl Database {
+    /// Get the cached response for a GitLab API URL.
+    pub async fn get_gitlab_cache(
+        &self,
+        url: &str,
+    ) -> Result<Option<GitLabCacheRow>, DatabaseError> {
+        let row = sqlx::query_as::<_, GitLabCacheRow>(
+            "SELECT * FROM gitlab_response_cache WHERE url = ?",
+        )
+        .bind(url)
+        .fetch_optional(self.pool())
+        .await?;
+
+        Ok(row)
+    }
+
+    /// Insert or replace the cached response for a GitLab API URL.
+    pub async fn put_gitlab_cache(
+        &self,
+        url: &str,
+        etag: &str,
+        body: &str,
+        next_url: Option<&str>,
+    ) -> Result<(), DatabaseError> {
+        sqlx::query(
+            "INSERT INTO gitlab_response_cache (url, etag, body, next_url, updated_at) \
+             VALUES (?, ?, ?, ?, ?) \
+             ON CONFLICT(url) DO UPDATE SET etag = excluded.etag, body = excluded.body, \
+             next_url = excluded.next_url, updated_at = excluded.updated_at",
+        )
+        .bind(url)
+        .bind(etag)
+        .bind(body)
+        .bind(next_url)
+        .bind(unix_timestamp())
+        .execute(self.pool())
+        .await?;
+
+        Ok(())
+    }
+
+    /// Delete cached responses not refreshed since `before` (unix seconds).
+    ///
+    /// Returns the number of rows removed.
+    pub async fn prune_gitlab_cache(&self, before: i64) -> Result<u64, DatabaseError> {
+        let result = sqlx::query("DELETE FROM gitlab_response_cache WHERE updated_at < ?")
+            .bind(before)
+            .execute(self.pool())
+            .await?;
+
+        Ok(result.rows_affected())
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use crate::storage::Database;
+
+    #[tokio::test]
+    async fn put_get_and_overwrite() {
+        let db = Database::open_in_memory().await.unwrap();
+        assert!(db.get_gitlab_cache("u").await.unwrap().is_none());
+
+        db.put_gitlab_cache("u", "\"e1\"", "[]", None)
+            .await
+            .unwrap();
+        db.put_gitlab_cache("u", "\"e2\"", "[1]", Some("u?page=2"))
+            .await
+            .unwrap();
+
+        let row = db.get_gitlab_cache("u").await.unwrap().unwrap();
+        assert_eq!(row.etag, "\"e2\"");
+        assert_eq!(row.body, "[1]");
+        assert_eq!(row.next_url.as_deref(), Some("u?page=2"));
+    }
+
+    #[tokio::test]
+    async fn prune_removes_stale_rows() {
+        let db = Database::open_in_memory().await.unwrap();
+        db.put_gitlab_cache("u", "\"e\"", "{}", None).await.unwrap();
+
+        assert_eq!(db.prune_gitlab_cache(0).await.unwrap(), 0);
+        assert_eq!(db.prune_gitlab_cache(i64::MAX).await.unwrap(), 1);
+        assert!(db.get_gitlab_cache("u").await.unwrap().is_none());
+    }
+}
@@ -2,12 +2,15 @@
 //!
 //! Provides persistence for sessions, messages, worktrees, and permissions.
 
+mod cache_queries;
 mod db;
 mod models;
 mod queries;
 mod queries_subagents;
 mod repo_queries;
+mod request_key_queries;
 
 pub use db::{Database, DatabaseError};
 pub use models::*;
 pub use repo_queries::GitRepoParams;
+pub use request_key_queries::KeyClaim;
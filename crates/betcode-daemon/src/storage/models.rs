@@ -73,6 +73,10 @@ pub struct Worktree {
     pub setup_script: Option<String>,
     pub created_at: i64,
     pub last_active: i64,
+    /// Setup pipeline state: `none`, `pending`, `running`, `succeeded` or `failed`.
+    pub setup_status: String,
+    /// JSON array of per-step setup results.
+    pub setup_steps: Option<String>,
 }
 
 /// Git repository record from the database.
@@ -88,6 +92,8 @@ pub struct GitRepoRow {
     pub auto_gitignore: i64,
     pub created_at: i64,
     pub last_active: i64,
+    /// JSON-encoded sandbox policy; `None` uses the daemon default.
+    pub sandbox: Option<String>,
 }
 
 /// Permission grant record from the database.
@@ -142,6 +148,10 @@ pub struct SubagentRow {
     pub created_at: i64,
     pub started_at: Option<i64>,
     pub completed_at: Option<i64>,
+    /// Claude's session ID, once the subprocess reported it.
+    pub claude_session_id: Option<String>,
+    /// Timeout the subagent was spawned with, in seconds (0 = default).
+    pub timeout_secs: i64,
 }
 
 /// Orchestration record from the database.
@@ -166,6 +176,26 @@ pub struct OrchestrationStepRow {
     pub depends_on: String,
     pub status: String,
     pub created_at: i64,
+    /// Output of the completed step, truncated.
+    pub result: Option<String>,
+    /// Attempts started so far, retries included.
+    pub attempts: i64,
+    /// Last failure message, or why the step was skipped.
+    pub error: Option<String>,
+    /// JSON array of the items a fan-out step ran over.
+    pub fan_out_items: Option<String>,
+    /// Base64-encoded `OrchestrationStep` the step was submitted as.
+    pub spec: Option<String>,
+}
+
+/// Cached GitLab API response keyed by request URL.
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct GitLabCacheRow {
+    pub url: String,
+    pub etag: String,
+    pub body: String,
+    pub next_url: Option<String>,
+    pub updated_at: i64,
 }
 
 /// Session status enum.
@@ -352,6 +352,27 @@ impl Database {
         Ok(())
     }
 
+    /// Record a worktree's setup pipeline status and per-step results (JSON).
+    pub async fn update_worktree_setup(
+        &self,
+        id: &str,
+        status: &str,
+        steps_json: Option<&str>,
+    ) -> Result<(), DatabaseError> {
+        let result =
+            sqlx::query("UPDATE worktrees SET setup_status = ?, setup_steps = ? WHERE id = ?")
+                .bind(status)
+                .bind(steps_json)
+                .bind(id)
+                .execute(self.pool())
+                .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(DatabaseError::NotFound(format!("Worktree {id}")));
+        }
+        Ok(())
+    }
+
     /// Bind a session to a worktree.
     pub async fn bind_session_to_worktree(
         &self,
@@ -671,6 +692,29 @@ mod tests {
         assert_eq!(db.list_worktrees(None).await.unwrap().len(), 3);
     }
 
+    #[tokio::test]
+    async fn worktree_setup_state_roundtrip() {
+        let db = Database::open_in_memory().await.unwrap();
+        seed_repo_and_worktree(&db).await;
+
+        let wt = db.get_worktree("wt-1").await.unwrap();
+        assert_eq!(wt.setup_status, "none");
+        assert!(wt.setup_steps.is_none());
+
+        db.update_worktree_setup("wt-1", "failed", Some(r#"[{"name":"install"}]"#))
+            .await
+            .unwrap();
+        let wt = db.get_worktree("wt-1").await.unwrap();
+        assert_eq!(wt.setup_status, "failed");
+        assert_eq!(wt.setup_steps.as_deref(), Some(r#"[{"name":"install"}]"#));
+
+        assert!(
+            db.update_worktree_setup("missing", "failed", None)
+                .await
+                .is_err()
+        );
+    }
+
     #[tokio::test]
     async fn remove_worktree_clears_session_binding() {
         let db = Database::open_in_memory().await.unwrap();
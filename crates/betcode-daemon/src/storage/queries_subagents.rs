@@ -22,6 +22,7 @@ impl Database {
         auto_approve: bool,
         allowed_tools: &str,
         working_directory: Option<&str>,
+        timeout_secs: i64,
     ) -> Result<SubagentRow, DatabaseError> {
         let now = unix_timestamp();
 
@@ -29,8 +30,8 @@ impl Database {
             r"
             INSERT INTO subagents
                 (id, parent_session_id, prompt, model, max_turns, auto_approve,
-                 allowed_tools, working_directory, created_at)
-            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)
+                 allowed_tools, working_directory, timeout_secs, created_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
             ",
         )
         .bind(id)
@@ -41,6 +42,7 @@ impl Database {
         .bind(i64::from(auto_approve))
         .bind(allowed_tools)
         .bind(working_directory)
+        .bind(timeout_secs)
         .bind(now)
         .execute(self.pool())
         .await?;
@@ -130,6 +132,30 @@ impl Database {
         Ok(())
     }
 
+    /// Record Claude's session ID for a subagent, for `--resume`.
+    pub async fn set_subagent_claude_session(
+        &self,
+        id: &str,
+        claude_session_id: &str,
+    ) -> Result<(), DatabaseError> {
+        sqlx::query("UPDATE subagents SET claude_session_id = ? WHERE id = ?")
+            .bind(claude_session_id)
+            .bind(id)
+            .execute(self.pool())
+            .await?;
+        Ok(())
+    }
+
+    /// List subagents left pending or running, oldest first.
+    pub async fn list_unfinished_subagents(&self) -> Result<Vec<SubagentRow>, DatabaseError> {
+        let subagents = sqlx::query_as::<_, SubagentRow>(
+            "SELECT * FROM subagents WHERE status IN ('pending', 'running') ORDER BY created_at ASC",
+        )
+        .fetch_all(self.pool())
+        .await?;
+        Ok(subagents)
+    }
+
     // =========================================================================
     // Orchestration queries
     // =========================================================================
@@ -168,6 +194,18 @@ impl Database {
             .ok_or_else(|| DatabaseError::NotFound(format!("Orchestration {id}")))
     }
 
+    /// List orchestrations left pending or running, oldest first.
+    pub async fn list_unfinished_orchestrations(
+        &self,
+    ) -> Result<Vec<OrchestrationRow>, DatabaseError> {
+        let orchestrations = sqlx::query_as::<_, OrchestrationRow>(
+            "SELECT * FROM orchestrations WHERE status IN ('pending', 'running') ORDER BY created_at ASC",
+        )
+        .fetch_all(self.pool())
+        .await?;
+        Ok(orchestrations)
+    }
+
     /// Update an orchestration's status.
     pub async fn update_orchestration_status(
         &self,
@@ -224,19 +262,25 @@ impl Database {
         .execute(self.pool())
         .await?;
 
-        self.get_orchestration_step(id).await
+        self.get_orchestration_step(orchestration_id, id).await
     }
 
-    /// Get an orchestration step by ID.
+    /// Get an orchestration step by its ID within an orchestration.
     pub async fn get_orchestration_step(
         &self,
+        orchestration_id: &str,
         id: &str,
     ) -> Result<OrchestrationStepRow, DatabaseError> {
-        sqlx::query_as::<_, OrchestrationStepRow>("SELECT * FROM orchestration_steps WHERE id = ?")
-            .bind(id)
-            .fetch_optional(self.pool())
-            .await?
-            .ok_or_else(|| DatabaseError::NotFound(format!("OrchestrationStep {id}")))
+        sqlx::query_as::<_, OrchestrationStepRow>(
+            "SELECT * FROM orchestration_steps WHERE orchestration_id = ? AND id = ?",
+        )
+        .bind(orchestration_id)
+        .bind(id)
+        .fetch_optional(self.pool())
+        .await?
+        .ok_or_else(|| {
+            DatabaseError::NotFound(format!("OrchestrationStep {orchestration_id}/{id}"))
+        })
     }
 
     /// Get all steps for an orchestration, ordered by `step_index`.
@@ -257,25 +301,122 @@ impl Database {
     /// Update an orchestration step's status and optionally link it to a subagent.
     pub async fn update_step_status(
         &self,
+        orchestration_id: &str,
         id: &str,
         status: &str,
         subagent_id: Option<&str>,
     ) -> Result<(), DatabaseError> {
-        if let Some(sa_id) = subagent_id {
-            sqlx::query("UPDATE orchestration_steps SET status = ?, subagent_id = ? WHERE id = ?")
-                .bind(status)
-                .bind(sa_id)
-                .bind(id)
-                .execute(self.pool())
-                .await?;
-        } else {
-            sqlx::query("UPDATE orchestration_steps SET status = ? WHERE id = ?")
-                .bind(status)
-                .bind(id)
-                .execute(self.pool())
-                .await?;
-        }
+        sqlx::query(
+            r"
+            UPDATE orchestration_steps
+            SET status = ?, subagent_id = COALESCE(?, subagent_id)
+            WHERE orchestration_id = ? AND id = ?
+            ",
+        )
+        .bind(status)
+        .bind(subagent_id)
+        .bind(orchestration_id)
+        .bind(id)
+        .execute(self.pool())
+        .await?;
+
+        Ok(())
+    }
+
+    /// Record the start of a step attempt (1-based), clearing the last error.
+    pub async fn start_step_attempt(
+        &self,
+        orchestration_id: &str,
+        id: &str,
+        attempt: i64,
+        subagent_id: Option<&str>,
+    ) -> Result<(), DatabaseError> {
+        sqlx::query(
+            r"
+            UPDATE orchestration_steps
+            SET status = 'running', attempts = ?, error = NULL,
+                subagent_id = COALESCE(?, subagent_id)
+            WHERE orchestration_id = ? AND id = ?
+            ",
+        )
+        .bind(attempt)
+        .bind(subagent_id)
+        .bind(orchestration_id)
+        .bind(id)
+        .execute(self.pool())
+        .await?;
+        Ok(())
+    }
+
+    /// Record why a step failed, is being retried or was skipped.
+    pub async fn update_step_error(
+        &self,
+        orchestration_id: &str,
+        id: &str,
+        error: &str,
+    ) -> Result<(), DatabaseError> {
+        sqlx::query(
+            "UPDATE orchestration_steps SET error = ? WHERE orchestration_id = ? AND id = ?",
+        )
+        .bind(error)
+        .bind(orchestration_id)
+        .bind(id)
+        .execute(self.pool())
+        .await?;
+        Ok(())
+    }
+
+    /// Record the items a fan-out step runs over (a JSON array).
+    pub async fn update_step_fan_out_items(
+        &self,
+        orchestration_id: &str,
+        id: &str,
+        items_json: &str,
+    ) -> Result<(), DatabaseError> {
+        sqlx::query(
+            "UPDATE orchestration_steps SET fan_out_items = ? WHERE orchestration_id = ? AND id = ?",
+        )
+        .bind(items_json)
+        .bind(orchestration_id)
+        .bind(id)
+        .execute(self.pool())
+        .await?;
+        Ok(())
+    }
+
+    /// Store the full definition of an orchestration step.
+    pub async fn update_step_spec(
+        &self,
+        orchestration_id: &str,
+        id: &str,
+        spec: &str,
+    ) -> Result<(), DatabaseError> {
+        sqlx::query(
+            "UPDATE orchestration_steps SET spec = ? WHERE orchestration_id = ? AND id = ?",
+        )
+        .bind(spec)
+        .bind(orchestration_id)
+        .bind(id)
+        .execute(self.pool())
+        .await?;
+        Ok(())
+    }
 
+    /// Record the output of a completed orchestration step.
+    pub async fn update_step_result(
+        &self,
+        orchestration_id: &str,
+        id: &str,
+        result: &str,
+    ) -> Result<(), DatabaseError> {
+        sqlx::query(
+            "UPDATE orchestration_steps SET result = ? WHERE orchestration_id = ? AND id = ?",
+        )
+        .bind(result)
+        .bind(orchestration_id)
+        .bind(id)
+        .execute(self.pool())
+        .await?;
         Ok(())
     }
 }
@@ -302,7 +443,7 @@ mod tests {
     /// Create a subagent with common defaults (for tests that only care
     /// about status transitions or cascades, not specific field values).
     async fn create_default_subagent(db: &Database, id: &str) {
-        db.create_subagent(id, "parent-1", "task", None, 10, false, "[]", None)
+        db.create_subagent(id, "parent-1", "task", None, 10, false, "[]", None, 0)
             .await
             .unwrap();
     }
@@ -333,6 +474,7 @@ mod tests {
                 false,
                 "[]",
                 Some("/tmp/work"),
+                0,
             )
             .await
             .unwrap();
@@ -368,6 +510,7 @@ mod tests {
                 true,
                 r#"["Read","Bash"]"#,
                 None,
+                0,
             )
             .await
             .unwrap();
@@ -648,11 +791,11 @@ mod tests {
             .await
             .unwrap();
 
-        db.update_step_status("step-1", "running", Some("sa-1"))
+        db.update_step_status("orch-1", "step-1", "running", Some("sa-1"))
             .await
             .unwrap();
 
-        let step = db.get_orchestration_step("step-1").await.unwrap();
+        let step = db.get_orchestration_step("orch-1", "step-1").await.unwrap();
         assert_eq!(step.status, "running");
         assert_eq!(step.subagent_id.as_deref(), Some("sa-1"));
     }
@@ -665,15 +808,168 @@ mod tests {
             .await
             .unwrap();
 
-        db.update_step_status("step-1", "blocked", None)
+        db.update_step_status("orch-1", "step-1", "blocked", None)
             .await
             .unwrap();
 
-        let step = db.get_orchestration_step("step-1").await.unwrap();
+        let step = db.get_orchestration_step("orch-1", "step-1").await.unwrap();
         assert_eq!(step.status, "blocked");
         assert!(step.subagent_id.is_none());
     }
 
+    #[tokio::test]
+    async fn update_step_result_stores_output() {
+        let db = db_with_parent().await;
+        create_default_orchestration(&db, "orch-1", "dag").await;
+        db.create_orchestration_step("step-1", "orch-1", 0, "Run task", "[]")
+            .await
+            .unwrap();
+        assert!(
+            db.get_orchestration_step("orch-1", "step-1")
+                .await
+                .unwrap()
+                .result
+                .is_none()
+        );
+
+        db.update_step_result("orch-1", "step-1", "All tests pass")
+            .await
+            .unwrap();
+
+        let step = db.get_orchestration_step("orch-1", "step-1").await.unwrap();
+        assert_eq!(step.result.as_deref(), Some("All tests pass"));
+    }
+
+    #[tokio::test]
+    async fn step_ids_are_scoped_to_orchestration() {
+        let db = db_with_parent().await;
+        create_default_orchestration(&db, "orch-1", "dag").await;
+        create_default_orchestration(&db, "orch-2", "dag").await;
+        db.create_orchestration_step("plan", "orch-1", 0, "First", "[]")
+            .await
+            .unwrap();
+        db.create_orchestration_step("plan", "orch-2", 0, "Second", "[]")
+            .await
+            .unwrap();
+
+        db.update_step_status("orch-2", "plan", "completed", None)
+            .await
+            .unwrap();
+
+        let first = db.get_orchestration_step("orch-1", "plan").await.unwrap();
+        let second = db.get_orchestration_step("orch-2", "plan").await.unwrap();
+        assert_eq!(
+            (first.prompt.as_str(), first.status.as_str()),
+            ("First", "pending")
+        );
+        assert_eq!(
+            (second.prompt.as_str(), second.status.as_str()),
+            ("Second", "completed")
+        );
+    }
+
+    #[tokio::test]
+    async fn step_attempts_errors_and_fan_out_items() {
+        let db = db_with_parent().await;
+        create_default_orchestration(&db, "orch-1", "dag").await;
+        create_default_subagent(&db, "sa-1").await;
+        db.create_orchestration_step("step-1", "orch-1", 0, "Task", "[]")
+            .await
+            .unwrap();
+
+        db.update_step_error("orch-1", "step-1", "Exited with code 1")
+            .await
+            .unwrap();
+        db.update_step_status("orch-1", "step-1", "retrying", None)
+            .await
+            .unwrap();
+        let step = db.get_orchestration_step("orch-1", "step-1").await.unwrap();
+        assert_eq!(step.status, "retrying");
+        assert_eq!(step.error.as_deref(), Some("Exited with code 1"));
+
+        db.start_step_attempt("orch-1", "step-1", 2, Some("sa-1"))
+            .await
+            .unwrap();
+        db.update_step_fan_out_items("orch-1", "step-1", r#"["a","b"]"#)
+            .await
+            .unwrap();
+        let step = db.get_orchestration_step("orch-1", "step-1").await.unwrap();
+        assert_eq!(step.status, "running");
+        assert_eq!(step.attempts, 2);
+        assert!(step.error.is_none());
+        assert_eq!(step.subagent_id.as_deref(), Some("sa-1"));
+        assert_eq!(step.fan_out_items.as_deref(), Some(r#"["a","b"]"#));
+
+        db.update_step_status("orch-1", "step-1", "skipped", None)
+            .await
+            .unwrap();
+        let step = db.get_orchestration_step("orch-1", "step-1").await.unwrap();
+        assert_eq!(step.status, "skipped");
+        // Status updates without a subagent keep the existing link.
+        assert_eq!(step.subagent_id.as_deref(), Some("sa-1"));
+    }
+
+    #[tokio::test]
+    async fn lists_unfinished_subagents_and_orchestrations() {
+        let db = db_with_parent().await;
+        create_default_subagent(&db, "sa-1").await;
+        db.create_subagent("sa-2", "parent-1", "task", None, 10, false, "[]", None, 90)
+            .await
+            .unwrap();
+        create_default_subagent(&db, "sa-3").await;
+        db.update_subagent_status("sa-2", "running", None, None)
+            .await
+            .unwrap();
+        db.update_subagent_status("sa-3", "completed", Some(0), Some("done"))
+            .await
+            .unwrap();
+        db.set_subagent_claude_session("sa-2", "claude-abc")
+            .await
+            .unwrap();
+
+        let unfinished = db.list_unfinished_subagents().await.unwrap();
+        let mut ids: Vec<&str> = unfinished.iter().map(|s| s.id.as_str()).collect();
+        ids.sort_unstable();
+        assert_eq!(ids, vec!["sa-1", "sa-2"]);
+        let resumable = unfinished.iter().find(|s| s.id == "sa-2").unwrap();
+        assert_eq!(resumable.claude_session_id.as_deref(), Some("claude-abc"));
+        assert_eq!(resumable.timeout_secs, 90);
+
+        create_default_orchestration(&db, "orch-1", "dag").await;
+        create_default_orchestration(&db, "orch-2", "dag").await;
+        db.update_orchestration_status("orch-1", "running")
+            .await
+            .unwrap();
+        db.update_orchestration_status("orch-2", "completed")
+            .await
+            .unwrap();
+        let orchestrations = db.list_unfinished_orchestrations().await.unwrap();
+        assert_eq!(orchestrations.len(), 1);
+        assert_eq!(orchestrations[0].id, "orch-1");
+    }
+
+    #[tokio::test]
+    async fn stores_step_spec() {
+        let db = db_with_parent().await;
+        create_default_orchestration(&db, "orch-1", "dag").await;
+        db.create_orchestration_step("step-1", "orch-1", 0, "Task", "[]")
+            .await
+            .unwrap();
+        assert!(
+            db.get_orchestration_step("orch-1", "step-1")
+                .await
+                .unwrap()
+                .spec
+                .is_none()
+        );
+
+        db.update_step_spec("orch-1", "step-1", "CgZzdGVwLTE=")
+            .await
+            .unwrap();
+        let step = db.get_orchestration_step("orch-1", "step-1").await.unwrap();
+        assert_eq!(step.spec.as_deref(), Some("CgZzdGVwLTE="));
+    }
+
     #[tokio::test]
     async fn steps_cascade_on_orchestration_delete() {
         let db = db_with_parent().await;
@@ -685,7 +981,7 @@ mod tests {
         // Delete session -> orchestration cascades -> steps cascade
         db.delete_session("parent-1").await.unwrap();
 
-        let result = db.get_orchestration_step("step-1").await;
+        let result = db.get_orchestration_step("orch-1", "step-1").await;
         assert!(matches!(
             result,
             Err(crate::storage::DatabaseError::NotFound(_))
@@ -721,11 +1017,11 @@ mod tests {
             .unwrap();
 
         // Link step to subagent
-        db.update_step_status("step-1", "running", Some("sa-1"))
+        db.update_step_status("orch-1", "step-1", "running", Some("sa-1"))
             .await
             .unwrap();
 
-        let step = db.get_orchestration_step("step-1").await.unwrap();
+        let step = db.get_orchestration_step("orch-1", "step-1").await.unwrap();
         assert_eq!(step.subagent_id.as_deref(), Some("sa-1"));
 
         // Manually delete subagent to trigger ON DELETE SET NULL
@@ -735,7 +1031,7 @@ mod tests {
             .await
             .unwrap();
 
-        let step = db.get_orchestration_step("step-1").await.unwrap();
+        let step = db.get_orchestration_step("orch-1", "step-1").await.unwrap();
         assert!(step.subagent_id.is_none());
     }
 
@@ -766,7 +1062,7 @@ mod tests {
 
         // Verify tables exist by inserting and querying
         seed_parent_session(&db).await;
-        db.create_subagent("sa-test", "parent-1", "p", None, 5, false, "[]", None)
+        db.create_subagent("sa-test", "parent-1", "p", None, 5, false, "[]", None, 0)
             .await
             .unwrap();
         db.create_orchestration("orch-test", "parent-1", "parallel")
@@ -783,7 +1079,10 @@ mod tests {
             "orch-test"
         );
         assert_eq!(
-            db.get_orchestration_step("step-test").await.unwrap().id,
+            db.get_orchestration_step("orch-test", "step-test")
+                .await
+                .unwrap()
+                .id,
             "step-test"
         );
     }
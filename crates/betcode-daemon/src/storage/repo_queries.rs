@@ -94,6 +94,15 @@ impl Database {
         Ok(repos)
     }
 
+    /// Counter that changes whenever a repo is registered, renamed, moved or
+    /// removed.
+    pub async fn git_repos_version(&self) -> Result<i64, DatabaseError> {
+        let row: (i64,) = sqlx::query_as("SELECT version FROM git_repos_version WHERE id = 1")
+            .fetch_one(self.pool())
+            .await?;
+        Ok(row.0)
+    }
+
     /// List git repos with pagination (limit/offset).
     ///
     /// When `limit` is 0 it is treated as "no limit" (`SQLite` `LIMIT -1`).
@@ -262,6 +271,24 @@ impl Database {
         Ok(row.0 as u32)
     }
 
+    /// Set (or clear, with `None`) a git repo's sandbox policy JSON.
+    pub async fn set_git_repo_sandbox(
+        &self,
+        id: &str,
+        sandbox_json: Option<&str>,
+    ) -> Result<GitRepoRow, DatabaseError> {
+        let result = sqlx::query("UPDATE git_repos SET sandbox = ? WHERE id = ?")
+            .bind(sandbox_json)
+            .bind(id)
+            .execute(self.pool())
+            .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(DatabaseError::NotFound(format!("GitRepo {id}")));
+        }
+        self.get_git_repo(id).await
+    }
+
     /// Update the `last_active` timestamp on a git repo.
     pub async fn touch_git_repo(&self, id: &str) -> Result<(), DatabaseError> {
         let now = unix_timestamp();
@@ -466,6 +493,29 @@ mod tests {
         assert!(!db.remove_git_repo("nope").await.unwrap());
     }
 
+    #[tokio::test]
+    async fn version_changes_with_identity_not_activity() {
+        let db = Database::open_in_memory().await.unwrap();
+        let v0 = db.git_repos_version().await.unwrap();
+
+        create_test_repo(&db, "r1", "repo", "/repo").await;
+        let v1 = db.git_repos_version().await.unwrap();
+        assert!(v1 > v0);
+
+        db.touch_git_repo("r1").await.unwrap();
+        db.set_git_repo_sandbox("r1", None).await.unwrap();
+        assert_eq!(db.git_repos_version().await.unwrap(), v1);
+
+        db.update_git_repo_partial("r1", Some("renamed"), None, None, None, None, None)
+            .await
+            .unwrap();
+        let v2 = db.git_repos_version().await.unwrap();
+        assert!(v2 > v1);
+
+        db.remove_git_repo("r1").await.unwrap();
+        assert!(db.git_repos_version().await.unwrap() > v2);
+    }
+
     #[tokio::test]
     async fn list_repos_paginated() {
         let db = Database::open_in_memory().await.unwrap();
@@ -634,4 +684,20 @@ mod tests {
             .await;
         assert!(err.is_err());
     }
+
+    #[tokio::test]
+    async fn set_git_repo_sandbox_roundtrip() {
+        let db = Database::open_in_memory().await.unwrap();
+        let row = create_test_repo(&db, "r1", "a", "/a").await;
+        assert!(row.sandbox.is_none());
+
+        let json = r#"{"backend":"bubblewrap"}"#;
+        let row = db.set_git_repo_sandbox("r1", Some(json)).await.unwrap();
+        assert_eq!(row.sandbox.as_deref(), Some(json));
+
+        let row = db.set_git_repo_sandbox("r1", None).await.unwrap();
+        assert!(row.sandbox.is_none());
+
+        assert!(db.set_git_repo_sandbox("missing", None).await.is_err());
+    }
 }
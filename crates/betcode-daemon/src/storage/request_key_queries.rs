@@ -0,0 +1,153 @@
+//! Database queries for the `request_keys` table.
+
+use betcode_core::db::unix_timestamp;
+
+use super::db::{Database, DatabaseError};
+
+/// How long an idempotency key is remembered, in seconds.
+const REQUEST_KEY_TTL_SECS: i64 = 7 * 24 * 60 * 60;
+
+/// How long a claimed key may stay unapplied, in seconds.
+///
+/// No request takes this long to apply, so an older claim belongs to a daemon
+/// that stopped mid-request and is dropped to let the request be retried.
+const PENDING_KEY_TTL_SECS: i64 = 10 * 60;
+
+/// Outcome of claiming an idempotency key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyClaim {
+    /// The key was new; the caller applies the request.
+    Claimed,
+    /// Another copy of the request is being applied right now.
+    Pending,
+    /// The request was already applied.
+    Applied,
+}
+
+impl Database {
+    /// Claim an idempotency key sent in `session_id`.
+    ///
+    /// A claimed key must be settled with [`Database::finish_request_key`]
+    /// or [`Database::release_request_key`]. Keys older than a week, and
+    /// claims never settled, are forgotten first.
+    pub async fn claim_request_key(
+        &self,
+        session_id: &str,
+        key: &str,
+    ) -> Result<KeyClaim, DatabaseError> {
+        let now = unix_timestamp();
+        sqlx::query(
+            "DELETE FROM request_keys WHERE created_at < ? OR (applied = 0 AND created_at < ?)",
+        )
+        .bind(now - REQUEST_KEY_TTL_SECS)
+        .bind(now - PENDING_KEY_TTL_SECS)
+        .execute(self.pool())
+        .await?;
+        let result = sqlx::query(
+            "INSERT OR IGNORE INTO request_keys (session_id, key, created_at) VALUES (?, ?, ?)",
+        )
+        .bind(session_id)
+        .bind(key)
+        .bind(now)
+        .execute(self.pool())
+        .await?;
+        if result.rows_affected() > 0 {
+            return Ok(KeyClaim::Claimed);
+        }
+
+        let applied: Option<bool> =
+            sqlx::query_scalar("SELECT applied FROM request_keys WHERE session_id = ? AND key = ?")
+                .bind(session_id)
+                .bind(key)
+                .fetch_optional(self.pool())
+                .await?;
+        // A claim released between the insert and the lookup is still being
+        // settled; the client retries later either way.
+        Ok(if applied == Some(true) {
+            KeyClaim::Applied
+        } else {
+            KeyClaim::Pending
+        })
+    }
+
+    /// Mark a claimed key as applied.
+    pub async fn finish_request_key(
+        &self,
+        session_id: &str,
+        key: &str,
+    ) -> Result<(), DatabaseError> {
+        sqlx::query("UPDATE request_keys SET applied = 1 WHERE session_id = ? AND key = ?")
+            .bind(session_id)
+            .bind(key)
+            .execute(self.pool())
+            .await?;
+        Ok(())
+    }
+
+    /// Drop a claimed key whose request failed, so a replay can retry it.
+    pub async fn release_request_key(
+        &self,
+        session_id: &str,
+        key: &str,
+    ) -> Result<(), DatabaseError> {
+        sqlx::query("DELETE FROM request_keys WHERE session_id = ? AND key = ? AND applied = 0")
+            .bind(session_id)
+            .bind(key)
+            .execute(self.pool())
+            .await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::KeyClaim;
+    use crate::storage::Database;
+
+    #[tokio::test]
+    async fn key_is_applied_once_per_session() {
+        let db = Database::open_in_memory().await.unwrap();
+        assert_eq!(
+            db.claim_request_key("s1", "k1").await.unwrap(),
+            KeyClaim::Claimed
+        );
+        assert_eq!(
+            db.claim_request_key("s1", "k1").await.unwrap(),
+            KeyClaim::Pending
+        );
+        db.finish_request_key("s1", "k1").await.unwrap();
+        assert_eq!(
+            db.claim_request_key("s1", "k1").await.unwrap(),
+            KeyClaim::Applied
+        );
+
+        // The same key in another session is a different request
+        assert_eq!(
+            db.claim_request_key("s2", "k1").await.unwrap(),
+            KeyClaim::Claimed
+        );
+    }
+
+    #[tokio::test]
+    async fn released_key_can_be_claimed_again() {
+        let db = Database::open_in_memory().await.unwrap();
+        assert_eq!(
+            db.claim_request_key("s1", "k1").await.unwrap(),
+            KeyClaim::Claimed
+        );
+        db.release_request_key("s1", "k1").await.unwrap();
+        assert_eq!(
+            db.claim_request_key("s1", "k1").await.unwrap(),
+            KeyClaim::Claimed
+        );
+
+        // Releasing an applied key keeps it
+        db.finish_request_key("s1", "k1").await.unwrap();
+        db.release_request_key("s1", "k1").await.unwrap();
+        assert_eq!(
+            db.claim_request_key("s1", "k1").await.unwrap(),
+            KeyClaim::Applied
+        );
+    }
+}
@@ -401,7 +401,7 @@ fn now_timestamp() -> Timestamp {
 }
 
 /// Generate a human-readable description from tool name and input.
-fn tool_description(name: &str, input: &serde_json::Value) -> String {
+pub(crate) fn tool_description(name: &str, input: &serde_json::Value) -> String {
     let Some(obj) = input.as_object() else {
         return String::new();
     };
@@ -3,14 +3,17 @@
 //! Manages spawning, monitoring, and graceful shutdown of Claude CLI processes.
 
 use std::collections::HashMap;
+use std::ffi::OsString;
 use std::path::PathBuf;
 use std::process::Stdio;
 use std::sync::Arc;
 use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
-use tokio::process::{Child, Command};
+use tokio::process::Child;
 use tokio::sync::{RwLock, mpsc};
 use tracing::{debug, error, info, warn};
 
+use super::sandbox::SandboxPolicy;
+
 /// Strategy for handling permission prompts in the subprocess.
 #[derive(Debug, Clone, Default)]
 pub enum PermissionStrategy {
@@ -39,6 +42,8 @@ pub struct SpawnConfig {
     pub model: Option<String>,
     /// Permission handling strategy.
     pub permission_strategy: PermissionStrategy,
+    /// Sandbox policy; `None` uses the manager's default.
+    pub sandbox: Option<SandboxPolicy>,
 }
 
 impl Default for SpawnConfig {
@@ -49,6 +54,7 @@ impl Default for SpawnConfig {
             resume_session: None,
             model: None,
             permission_strategy: PermissionStrategy::default(),
+            sandbox: None,
         }
     }
 }
@@ -78,6 +84,8 @@ pub struct SubprocessManager {
     default_permission_strategy: PermissionStrategy,
     /// Timeout for graceful subprocess termination before SIGKILL.
     terminate_timeout: std::time::Duration,
+    /// Sandbox policy for subprocesses that don't specify one.
+    default_sandbox: SandboxPolicy,
 }
 
 struct ProcessState {
@@ -96,6 +104,7 @@ impl SubprocessManager {
             claude_bin,
             default_permission_strategy: PermissionStrategy::default(),
             terminate_timeout: std::time::Duration::from_secs(5),
+            default_sandbox: SandboxPolicy::default(),
         }
     }
 
@@ -112,14 +121,27 @@ impl SubprocessManager {
             claude_bin,
             default_permission_strategy,
             terminate_timeout: std::time::Duration::from_secs(terminate_timeout_secs),
+            default_sandbox: SandboxPolicy::default(),
         }
     }
 
+    /// Set the sandbox policy for subprocesses that don't specify one.
+    #[must_use]
+    pub fn with_sandbox(mut self, policy: SandboxPolicy) -> Self {
+        self.default_sandbox = policy;
+        self
+    }
+
     /// Get the default permission strategy.
     pub const fn default_permission_strategy(&self) -> &PermissionStrategy {
         &self.default_permission_strategy
     }
 
+    /// Get the default sandbox policy.
+    pub const fn default_sandbox(&self) -> &SandboxPolicy {
+        &self.default_sandbox
+    }
+
     /// Spawn a new Claude subprocess.
     #[allow(clippy::too_many_lines)]
     pub async fn spawn(
@@ -156,14 +178,17 @@ impl SubprocessManager {
         } else {
             config.working_directory.clone()
         };
-        let mut cmd = Command::new(&self.claude_bin);
-        cmd.current_dir(&working_dir)
-            .arg("--output-format")
-            .arg("stream-json")
-            .arg("--input-format")
-            .arg("stream-json")
-            .arg("--verbose")
-            .stdin(Stdio::piped())
+        let sandbox = config.sandbox.as_ref().unwrap_or(&self.default_sandbox);
+        let mut cmd = sandbox
+            .command(
+                &self.claude_bin,
+                &working_dir,
+                &claude_args(&config, sandbox),
+            )
+            .map_err(|e| SubprocessError::SpawnFailed {
+                reason: e.to_string(),
+            })?;
+        cmd.stdin(Stdio::piped())
             .stdout(Stdio::piped())
             .stderr(Stdio::piped());
 
@@ -179,44 +204,21 @@ impl SubprocessManager {
             cmd.env("ANTHROPIC_API_KEY", &key);
         }
 
-        match &config.permission_strategy {
-            PermissionStrategy::PromptToolStdio => {
-                cmd.arg("--permission-prompt-tool").arg("stdio");
-            }
-            PermissionStrategy::AllowedTools(tools) => {
-                if !tools.is_empty() {
-                    cmd.arg("--allowedTools").args(tools);
-                }
-            }
-            PermissionStrategy::SkipPermissions => {
-                cmd.arg("--dangerously-skip-permissions");
-            }
-        }
-
-        if let Some(ref prompt) = config.prompt {
-            cmd.arg("-p").arg(prompt);
-            // --include-partial-messages requires -p (--print mode)
-            cmd.arg("--include-partial-messages");
-        }
-
-        if let Some(ref session) = config.resume_session {
-            cmd.arg("--resume").arg(session);
-        }
-
-        if let Some(ref model) = config.model {
-            cmd.arg("--model").arg(model);
-        }
-
         // Spawn process
         info!(
             working_dir = %working_dir.display(),
             has_prompt = config.prompt.is_some(),
             resume_session = ?config.resume_session,
             model = ?config.model,
+            sandbox = sandbox.backend.as_str(),
             "Spawning claude subprocess"
         );
         let mut child = cmd.spawn().map_err(|e| SubprocessError::SpawnFailed {
-            reason: e.to_string(),
+            reason: if sandbox.is_enabled() {
+                format!("{} sandbox: {e}", sandbox.backend.as_str())
+            } else {
+                e.to_string()
+            },
         })?;
 
         let process_id = uuid::Uuid::new_v4().to_string();
@@ -407,6 +409,57 @@ impl SubprocessManager {
     }
 }
 
+/// Claude CLI arguments for a spawn config.
+///
+/// A sandbox with `auto_approve` overrides the permission strategy.
+fn claude_args(config: &SpawnConfig, sandbox: &SandboxPolicy) -> Vec<OsString> {
+    let mut args: Vec<OsString> = [
+        "--output-format",
+        "stream-json",
+        "--input-format",
+        "stream-json",
+        "--verbose",
+    ]
+    .into_iter()
+    .map(OsString::from)
+    .collect();
+
+    let strategy = if sandbox.auto_approves() {
+        &PermissionStrategy::SkipPermissions
+    } else {
+        &config.permission_strategy
+    };
+    match strategy {
+        PermissionStrategy::PromptToolStdio => {
+            args.extend(["--permission-prompt-tool".into(), "stdio".into()]);
+        }
+        PermissionStrategy::AllowedTools(tools) => {
+            if !tools.is_empty() {
+                args.push("--allowedTools".into());
+                args.extend(tools.iter().map(OsString::from));
+            }
+        }
+        PermissionStrategy::SkipPermissions => {
+            args.push("--dangerously-skip-permissions".into());
+        }
+    }
+
+    if let Some(ref prompt) = config.prompt {
+        args.extend(["-p".into(), prompt.into()]);
+        // --include-partial-messages requires -p (--print mode)
+        args.push("--include-partial-messages".into());
+    }
+
+    if let Some(ref session) = config.resume_session {
+        args.extend(["--resume".into(), session.into()]);
+    }
+
+    if let Some(ref model) = config.model {
+        args.extend(["--model".into(), model.into()]);
+    }
+    args
+}
+
 /// Errors from subprocess operations.
 #[derive(Debug, thiserror::Error)]
 pub enum SubprocessError {
@@ -439,5 +492,24 @@ mod tests {
         let config = SpawnConfig::default();
         assert!(config.prompt.is_none());
         assert!(config.resume_session.is_none());
+        assert!(config.sandbox.is_none());
+    }
+
+    #[test]
+    fn sandbox_auto_approve_skips_permissions() {
+        use super::super::sandbox::SandboxBackend;
+
+        let config = SpawnConfig::default();
+        let args = claude_args(&config, &SandboxPolicy::default());
+        assert!(args.iter().any(|a| a == "--permission-prompt-tool"));
+
+        let sandbox = SandboxPolicy {
+            backend: SandboxBackend::Bubblewrap,
+            auto_approve: true,
+            ..Default::default()
+        };
+        let args = claude_args(&config, &sandbox);
+        assert!(args.iter().any(|a| a == "--dangerously-skip-permissions"));
+        assert!(!args.iter().any(|a| a == "--permission-prompt-tool"));
     }
 }
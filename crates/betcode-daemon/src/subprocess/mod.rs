@@ -2,8 +2,10 @@
 
 pub mod bridge;
 pub mod manager;
+pub mod sandbox;
 
 pub use bridge::EventBridge;
 pub use manager::{
     PermissionStrategy, ProcessHandle, SpawnConfig, SubprocessError, SubprocessManager,
 };
+pub use sandbox::{NetworkPolicy, SandboxBackend, SandboxPolicy};
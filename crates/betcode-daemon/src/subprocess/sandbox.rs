@@ -0,0 +1,749 @@
+//! Optional sandboxing of Claude subprocesses.
+//!
+//! A [`SandboxPolicy`] wraps the `claude` invocation in bubblewrap (`bwrap`)
+//! or a rootless podman container. Inside the sandbox only the system
+//! directories, the session's working directory (plus the git directory a
+//! worktree points at, with everything that configures git read-only),
+//! Claude's own state under `$HOME` (read-only apart from the data Claude
+//! writes) and explicitly approved paths are visible, only an allowlist of
+//! environment variables is passed on, and networking follows
+//! [`NetworkPolicy`].
+//! NDJSON still flows over stdio, so the relay pipeline is unaffected.
+//!
+//! Policies are stored as JSON on `git_repos.sandbox`; the daemon-wide
+//! default comes from the `--sandbox*` flags.
+
+use std::ffi::{OsStr, OsString};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+use tokio::process::Command;
+use tracing::warn;
+
+/// System directories mounted read-only into a bubblewrap sandbox (if present).
+const BWRAP_SYSTEM_DIRS: &[&str] = &["/usr", "/bin", "/sbin", "/lib", "/lib64", "/etc", "/opt"];
+
+/// Claude state under `$HOME`, visible read-only inside the sandbox. Settings
+/// there can define hooks that later run unsandboxed, so it must not be
+/// writable.
+const CLAUDE_STATE: &[&str] = &[".claude", ".claude.json"];
+
+/// Directories under `~/.claude` holding data Claude writes during a session
+/// (transcripts, todos, ...), writable inside the sandbox.
+const CLAUDE_DATA_DIRS: &[&str] = &["projects", "todos", "shell-snapshots", "statsig"];
+
+/// Environment variables passed into a bubblewrap sandbox; everything else,
+/// including the daemon's own secrets, is cleared.
+const BWRAP_ENV: &[&str] = &[
+    "HOME",
+    "PATH",
+    "ANTHROPIC_API_KEY",
+    "LANG",
+    "LANGUAGE",
+    "LC_ALL",
+    "LC_CTYPE",
+];
+
+/// Environment variables passed into a podman container.
+const PODMAN_ENV: &[&str] = &["ANTHROPIC_API_KEY"];
+
+/// Isolation backend.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SandboxBackend {
+    /// Run Claude directly with the daemon user's privileges.
+    #[default]
+    None,
+    /// Linux user namespaces via `bwrap`.
+    Bubblewrap,
+    /// Rootless `podman run`.
+    Podman,
+}
+
+impl SandboxBackend {
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::None => "none",
+            Self::Bubblewrap => "bubblewrap",
+            Self::Podman => "podman",
+        }
+    }
+}
+
+impl FromStr for SandboxBackend {
+    type Err = SandboxError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "none" => Ok(Self::None),
+            "bubblewrap" | "bwrap" => Ok(Self::Bubblewrap),
+            "podman" => Ok(Self::Podman),
+            other => Err(SandboxError::Invalid(format!(
+                "unknown sandbox backend \"{other}\" (expected none, bubblewrap or podman)"
+            ))),
+        }
+    }
+}
+
+/// Network access inside the sandbox.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(into = "String", try_from = "String")]
+pub enum NetworkPolicy {
+    /// Share the host network.
+    #[default]
+    Host,
+    /// No network at all (loopback only).
+    None,
+    /// A named podman network, e.g. one with an egress firewall or proxy.
+    Named(String),
+}
+
+impl fmt::Display for NetworkPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Host => f.write_str("host"),
+            Self::None => f.write_str("none"),
+            Self::Named(name) => f.write_str(name),
+        }
+    }
+}
+
+impl FromStr for NetworkPolicy {
+    type Err = SandboxError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "" | "host" => Ok(Self::Host),
+            "none" => Ok(Self::None),
+            name if name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.')) =>
+            {
+                Ok(Self::Named(name.to_string()))
+            }
+            other => Err(SandboxError::Invalid(format!(
+                "invalid network \"{other}\" (expected host, none or a podman network name)"
+            ))),
+        }
+    }
+}
+
+impl From<NetworkPolicy> for String {
+    fn from(n: NetworkPolicy) -> Self {
+        n.to_string()
+    }
+}
+
+impl TryFrom<String> for NetworkPolicy {
+    type Error = SandboxError;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+/// How a Claude subprocess is isolated.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SandboxPolicy {
+    pub backend: SandboxBackend,
+    pub network: NetworkPolicy,
+    /// Extra host paths visible read-only (e.g. a toolchain outside `/usr`).
+    pub read_only_paths: Vec<PathBuf>,
+    /// Extra host paths visible read-write (e.g. a shared build cache).
+    pub read_write_paths: Vec<PathBuf>,
+    /// Container image for the podman backend; it must provide `claude`.
+    pub image: Option<String>,
+    /// Skip Claude's permission prompts while sandboxed.
+    pub auto_approve: bool,
+}
+
+/// Errors from building a sandboxed command.
+#[derive(Debug, thiserror::Error)]
+pub enum SandboxError {
+    #[error("Invalid sandbox policy: {0}")]
+    Invalid(String),
+}
+
+impl SandboxPolicy {
+    /// Whether the subprocess runs inside a sandbox.
+    pub fn is_enabled(&self) -> bool {
+        self.backend != SandboxBackend::None
+    }
+
+    /// Whether permission prompts are skipped. Only ever true when sandboxed.
+    pub fn auto_approves(&self) -> bool {
+        self.is_enabled() && self.auto_approve
+    }
+
+    /// Check that the policy can be applied.
+    pub fn validate(&self) -> Result<(), SandboxError> {
+        if let Some(p) = self
+            .read_only_paths
+            .iter()
+            .chain(&self.read_write_paths)
+            .find(|p| !p.is_absolute())
+        {
+            return Err(SandboxError::Invalid(format!(
+                "sandbox path \"{}\" must be absolute",
+                p.display()
+            )));
+        }
+        match self.backend {
+            SandboxBackend::Bubblewrap if matches!(self.network, NetworkPolicy::Named(_)) => {
+                Err(SandboxError::Invalid(format!(
+                    "network \"{}\" requires the podman backend",
+                    self.network
+                )))
+            }
+            SandboxBackend::Podman if self.image.as_deref().unwrap_or_default().is_empty() => Err(
+                SandboxError::Invalid("the podman backend requires an image".into()),
+            ),
+            _ => Ok(()),
+        }
+    }
+
+    /// Build the command that runs `program args` in `working_dir` under this policy.
+    pub fn command(
+        &self,
+        program: &Path,
+        working_dir: &Path,
+        args: &[OsString],
+    ) -> Result<Command, SandboxError> {
+        self.validate()?;
+        let home = std::env::var_os("HOME").map(PathBuf::from);
+        if self.is_enabled()
+            && let Some(home) = &home
+        {
+            create_claude_data_dirs(home);
+        }
+        let mut cmd = match self.backend {
+            SandboxBackend::None => {
+                let mut cmd = Command::new(program);
+                cmd.args(args);
+                cmd
+            }
+            SandboxBackend::Bubblewrap => {
+                let mut cmd = Command::new("bwrap");
+                cmd.args(self.bwrap_args(program, working_dir, home.as_deref(), args))
+                    .env_clear()
+                    .envs(sandbox_env(std::env::vars_os()));
+                cmd
+            }
+            SandboxBackend::Podman => {
+                let mut cmd = Command::new("podman");
+                cmd.args(self.podman_args(program, working_dir, home.as_deref(), args));
+                cmd
+            }
+        };
+        cmd.current_dir(working_dir);
+        Ok(cmd)
+    }
+
+    fn bwrap_args(
+        &self,
+        program: &Path,
+        working_dir: &Path,
+        home: Option<&Path>,
+        args: &[OsString],
+    ) -> Vec<OsString> {
+        let mut out = Args::default();
+        out.push(["--die-with-parent", "--unshare-all"]);
+        if self.network == NetworkPolicy::Host {
+            out.push(["--share-net"]);
+        }
+        for dir in BWRAP_SYSTEM_DIRS {
+            out.mount("--ro-bind-try", Path::new(dir));
+        }
+        out.push(["--proc", "/proc", "--dev", "/dev", "--tmpfs", "/tmp"]);
+        if let Some(home) = home {
+            // An empty home hides the daemon user's keys and dotfiles.
+            out.push([OsStr::new("--tmpfs"), home.as_os_str()]);
+            for entry in CLAUDE_STATE {
+                out.mount("--ro-bind-try", &home.join(entry));
+            }
+            for dir in CLAUDE_DATA_DIRS {
+                out.mount("--bind-try", &home.join(".claude").join(dir));
+            }
+        }
+        for path in program_paths(program) {
+            out.mount("--ro-bind-try", &path);
+        }
+        for path in &self.read_only_paths {
+            out.mount("--ro-bind", path);
+        }
+        for path in writable_paths(working_dir)
+            .iter()
+            .chain(&self.read_write_paths)
+        {
+            out.mount("--bind", path);
+        }
+        for (source, path) in protected_git_paths(working_dir) {
+            out.push([
+                OsStr::new("--ro-bind"),
+                source.as_os_str(),
+                path.as_os_str(),
+            ]);
+        }
+        out.push([OsStr::new("--chdir"), working_dir.as_os_str()]);
+        out.push([OsStr::new("--"), program.as_os_str()]);
+        out.0.extend(args.iter().cloned());
+        out.0
+    }
+
+    fn podman_args(
+        &self,
+        program: &Path,
+        working_dir: &Path,
+        home: Option<&Path>,
+        args: &[OsString],
+    ) -> Vec<OsString> {
+        let mut out = Args::default();
+        out.push([
+            "run",
+            "--rm",
+            "--interactive",
+            "--init",
+            "--userns=keep-id",
+            "--cap-drop=ALL",
+            "--security-opt=no-new-privileges",
+        ]);
+        out.flag("--network=", OsStr::new(&self.network.to_string()));
+        if let Some(home) = home {
+            out.flag("--env=HOME=", home.as_os_str());
+            for entry in CLAUDE_STATE {
+                let path = home.join(entry);
+                if path.exists() {
+                    out.volume(&path, "ro");
+                }
+            }
+            for dir in CLAUDE_DATA_DIRS {
+                let path = home.join(".claude").join(dir);
+                if path.exists() {
+                    out.volume(&path, "rw");
+                }
+            }
+        }
+        for name in PODMAN_ENV {
+            // Name-only `--env` copies the value from the daemon's environment.
+            out.flag("--env=", OsStr::new(name));
+        }
+        for path in &self.read_only_paths {
+            out.volume(path, "ro");
+        }
+        for path in writable_paths(working_dir)
+            .iter()
+            .chain(&self.read_write_paths)
+        {
+            out.volume(path, "rw");
+        }
+        for (source, path) in protected_git_paths(working_dir) {
+            out.volume_from(&source, &path, "ro");
+        }
+        out.flag("--workdir=", working_dir.as_os_str());
+        out.push([self.image.as_deref().unwrap_or_default()]);
+        // The image provides its own `claude`; a host path would not exist there.
+        out.push([program.file_name().unwrap_or(program.as_os_str())]);
+        out.0.extend(args.iter().cloned());
+        out.0
+    }
+}
+
+/// Argument list builder.
+#[derive(Default)]
+struct Args(Vec<OsString>);
+
+impl Args {
+    fn push<I: IntoIterator<Item = S>, S: AsRef<OsStr>>(&mut self, items: I) {
+        self.0
+            .extend(items.into_iter().map(|s| s.as_ref().to_os_string()));
+    }
+
+    /// `<option> <path> <path>`, binding a host path to the same location.
+    fn mount(&mut self, option: &str, path: &Path) {
+        self.push([OsStr::new(option), path.as_os_str(), path.as_os_str()]);
+    }
+
+    /// `<prefix><value>` as a single argument.
+    fn flag(&mut self, prefix: &str, value: &OsStr) {
+        let mut arg = OsString::from(prefix);
+        arg.push(value);
+        self.0.push(arg);
+    }
+
+    /// `--volume=<path>:<path>:<mode>`.
+    fn volume(&mut self, path: &Path, mode: &str) {
+        self.volume_from(path, path, mode);
+    }
+
+    /// `--volume=<source>:<path>:<mode>`.
+    fn volume_from(&mut self, source: &Path, path: &Path, mode: &str) {
+        let mut arg = OsString::from("--volume=");
+        arg.push(source.as_os_str());
+        arg.push(":");
+        arg.push(path.as_os_str());
+        arg.push(":");
+        arg.push(mode);
+        self.0.push(arg);
+    }
+}
+
+/// The working directory plus, for a linked git worktree, the main
+/// repository's git directory that commits are written to.
+fn writable_paths(working_dir: &Path) -> Vec<PathBuf> {
+    let mut paths = vec![working_dir.to_path_buf()];
+    if let Some(common) = git_common_dir(working_dir)
+        && !common.starts_with(working_dir)
+    {
+        paths.push(common);
+    }
+    paths
+}
+
+/// Paths that configure git for `working_dir`, as `(source, path)` mounts
+/// laid read-only over the writable binds: the daemon runs git there on the
+/// host, so anything planted in them would escape the sandbox. That is the
+/// hooks and config of the git directory and, for a linked worktree, its
+/// `.git` file and the admin files naming its git directories and holding
+/// its own config. The rest of the admin directory (index, `HEAD`) stays
+/// writable for git inside the sandbox; a missing worktree config is
+/// covered with an empty file so it cannot be created.
+fn protected_git_paths(working_dir: &Path) -> Vec<(PathBuf, PathBuf)> {
+    let admin = linked_git_dir(working_dir);
+    let git_dir = git_common_dir(working_dir).unwrap_or_else(|| working_dir.join(".git"));
+    let mut paths = vec![git_dir.join("hooks"), git_dir.join("config")];
+    if let Some(admin) = &admin {
+        paths.push(working_dir.join(".git"));
+        paths.push(admin.join("commondir"));
+        paths.push(admin.join("gitdir"));
+    }
+    let mut mounts: Vec<_> = paths
+        .into_iter()
+        .filter(|path| path.exists())
+        .map(|path| (path.clone(), path))
+        .collect();
+    if let Some(admin) = admin {
+        let config = admin.join("config.worktree");
+        let source = if config.exists() {
+            config.clone()
+        } else {
+            PathBuf::from("/dev/null")
+        };
+        mounts.push((source, config));
+    }
+    mounts
+}
+
+/// Admin directory of a linked worktree (`.git` is a file naming it).
+fn linked_git_dir(working_dir: &Path) -> Option<PathBuf> {
+    let dot_git = std::fs::read_to_string(working_dir.join(".git")).ok()?;
+    let gitdir = working_dir.join(dot_git.strip_prefix("gitdir:")?.trim());
+    gitdir.canonicalize().ok()
+}
+
+/// Resolve the common git directory of a linked worktree (`.git` is a file).
+fn git_common_dir(working_dir: &Path) -> Option<PathBuf> {
+    let gitdir = linked_git_dir(working_dir)?;
+    let common = std::fs::read_to_string(gitdir.join("commondir"))
+        .map_or_else(|_| gitdir.clone(), |c| gitdir.join(c.trim()));
+    common.canonicalize().ok()
+}
+
+/// The variables of `vars` a bubblewrap sandbox may see.
+fn sandbox_env(vars: impl IntoIterator<Item = (OsString, OsString)>) -> Vec<(OsString, OsString)> {
+    vars.into_iter()
+        .filter(|(name, _)| BWRAP_ENV.iter().any(|allowed| name == allowed))
+        .collect()
+}
+
+/// Create the writable Claude data directories so they can be mounted; a
+/// directory missing in a read-only `~/.claude` could not be created later.
+fn create_claude_data_dirs(home: &Path) {
+    for dir in CLAUDE_DATA_DIRS {
+        let path = home.join(".claude").join(dir);
+        if let Err(e) = std::fs::create_dir_all(&path) {
+            warn!(path = %path.display(), error = %e, "Cannot create Claude data directory");
+        }
+    }
+}
+
+/// Host paths needed to execute `program`: the directory it is found in and,
+/// when that is a symlink into an installation tree, the real location.
+fn program_paths(program: &Path) -> Vec<PathBuf> {
+    let Some(found) = find_program(program) else {
+        return Vec::new();
+    };
+    let mut paths = Vec::new();
+    if let Some(dir) = found.parent() {
+        paths.push(dir.to_path_buf());
+    }
+    if let Some(dir) = found.canonicalize().ok().as_deref().and_then(Path::parent)
+        && !paths.iter().any(|p| dir.starts_with(p))
+    {
+        paths.push(dir.to_path_buf());
+    }
+    paths
+}
+
+fn find_program(program: &Path) -> Option<PathBuf> {
+    if program.components().count() > 1 {
+        return program.is_file().then(|| program.to_path_buf());
+    }
+    std::env::split_paths(&std::env::var_os("PATH")?)
+        .map(|dir| dir.join(program))
+        .find(|p| p.is_file())
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used, clippy::expect_used)]
+mod tests {
+    use super::*;
+
+    fn strings(args: &[OsString]) -> Vec<String> {
+        args.iter()
+            .map(|a| a.to_string_lossy().into_owned())
+            .collect()
+    }
+
+    fn contains_seq(haystack: &[String], needle: &[&str]) -> bool {
+        haystack
+            .windows(needle.len())
+            .any(|w| w.iter().zip(needle).all(|(a, b)| a == b))
+    }
+
+    #[test]
+    fn policy_json_roundtrip_and_defaults() {
+        let policy: SandboxPolicy =
+            serde_json::from_str(r#"{"backend":"podman","network":"egress","image":"img"}"#)
+                .unwrap();
+        assert_eq!(policy.backend, SandboxBackend::Podman);
+        assert_eq!(policy.network, NetworkPolicy::Named("egress".into()));
+        assert!(!policy.auto_approve);
+        let json = serde_json::to_string(&policy).unwrap();
+        assert_eq!(
+            serde_json::from_str::<SandboxPolicy>(&json).unwrap(),
+            policy
+        );
+        assert!(serde_json::from_str::<SandboxPolicy>(r#"{"bogus":1}"#).is_err());
+        assert!(!SandboxPolicy::default().is_enabled());
+    }
+
+    #[test]
+    fn validate_rejects_unusable_policies() {
+        let bwrap_named = SandboxPolicy {
+            backend: SandboxBackend::Bubblewrap,
+            network: NetworkPolicy::Named("egress".into()),
+            ..Default::default()
+        };
+        assert!(bwrap_named.validate().is_err());
+        let podman_no_image = SandboxPolicy {
+            backend: SandboxBackend::Podman,
+            ..Default::default()
+        };
+        assert!(podman_no_image.validate().is_err());
+        let relative = SandboxPolicy {
+            backend: SandboxBackend::Bubblewrap,
+            read_only_paths: vec!["tools".into()],
+            ..Default::default()
+        };
+        assert!(relative.validate().is_err());
+        assert!("bad net!".parse::<NetworkPolicy>().is_err());
+    }
+
+    #[test]
+    fn auto_approve_requires_a_sandbox() {
+        let mut policy = SandboxPolicy {
+            auto_approve: true,
+            ..Default::default()
+        };
+        assert!(!policy.auto_approves());
+        policy.backend = SandboxBackend::Bubblewrap;
+        assert!(policy.auto_approves());
+    }
+
+    #[test]
+    fn bwrap_args_bind_worktree_and_hide_home() {
+        let policy = SandboxPolicy {
+            backend: SandboxBackend::Bubblewrap,
+            network: NetworkPolicy::None,
+            read_only_paths: vec!["/opt/node".into()],
+            ..Default::default()
+        };
+        let args = strings(&policy.bwrap_args(
+            Path::new("/nonexistent/claude"),
+            Path::new("/work/wt"),
+            Some(Path::new("/home/u")),
+            &["--verbose".into()],
+        ));
+        assert!(!args.contains(&"--share-net".to_string()));
+        assert!(contains_seq(&args, &["--tmpfs", "/home/u"]));
+        assert!(contains_seq(
+            &args,
+            &["--ro-bind-try", "/home/u/.claude", "/home/u/.claude"]
+        ));
+        assert!(contains_seq(
+            &args,
+            &[
+                "--ro-bind-try",
+                "/home/u/.claude.json",
+                "/home/u/.claude.json"
+            ]
+        ));
+        assert!(contains_seq(
+            &args,
+            &[
+                "--bind-try",
+                "/home/u/.claude/projects",
+                "/home/u/.claude/projects"
+            ]
+        ));
+        assert!(!args.iter().any(|a| a.ends_with("settings.json")));
+        assert!(contains_seq(
+            &args,
+            &["--ro-bind", "/opt/node", "/opt/node"]
+        ));
+        assert!(contains_seq(&args, &["--bind", "/work/wt", "/work/wt"]));
+        assert!(contains_seq(
+            &args,
+            &[
+                "--chdir",
+                "/work/wt",
+                "--",
+                "/nonexistent/claude",
+                "--verbose"
+            ]
+        ));
+        let tmpfs = args.iter().position(|a| a == "/home/u").unwrap();
+        let claude = args.iter().position(|a| a == "/home/u/.claude").unwrap();
+        let projects = args
+            .iter()
+            .position(|a| a == "/home/u/.claude/projects")
+            .unwrap();
+        assert!(tmpfs < claude, "home must be hidden before state is bound");
+        assert!(claude < projects, "data dirs must be bound over the state");
+    }
+
+    #[test]
+    fn sandbox_env_keeps_only_allowlisted_variables() {
+        let vars = [
+            ("HOME", "/home/u"),
+            ("PATH", "/usr/bin"),
+            ("ANTHROPIC_API_KEY", "sk-test"),
+            ("LANG", "C.UTF-8"),
+            ("BETCODE_GITLAB_TOKEN", "glpat-secret"),
+            ("BETCODE_GITHUB_TOKEN", "ghp-secret"),
+            ("SSH_AUTH_SOCK", "/run/agent"),
+        ]
+        .map(|(k, v)| (OsString::from(k), OsString::from(v)));
+        let env = sandbox_env(vars);
+        let names: Vec<_> = env.iter().map(|(k, _)| k.to_string_lossy()).collect();
+        assert_eq!(names, ["HOME", "PATH", "ANTHROPIC_API_KEY", "LANG"]);
+        assert!(!names.iter().any(|n| n == "BETCODE_GITLAB_TOKEN"));
+    }
+
+    #[test]
+    fn podman_args_use_image_network_and_volumes() {
+        let policy = SandboxPolicy {
+            backend: SandboxBackend::Podman,
+            network: NetworkPolicy::Named("egress".into()),
+            read_write_paths: vec!["/cache".into()],
+            image: Some("ghcr.io/example/claude:latest".into()),
+            ..Default::default()
+        };
+        let args = strings(&policy.podman_args(
+            Path::new("/usr/local/bin/claude"),
+            Path::new("/work/wt"),
+            None,
+            &["-p".into(), "hi".into()],
+        ));
+        assert_eq!(args[0], "run");
+        assert!(args.contains(&"--network=egress".to_string()));
+        assert!(args.contains(&"--volume=/work/wt:/work/wt:rw".to_string()));
+        assert!(args.contains(&"--volume=/cache:/cache:rw".to_string()));
+        assert!(args.contains(&"--workdir=/work/wt".to_string()));
+        assert!(contains_seq(
+            &args,
+            &["ghcr.io/example/claude:latest", "claude", "-p", "hi"]
+        ));
+    }
+
+    #[test]
+    fn linked_worktree_exposes_common_git_dir() {
+        let tmp = tempfile::tempdir().unwrap();
+        let repo_git = tmp.path().join("repo/.git");
+        let gitdir = repo_git.join("worktrees/wt");
+        std::fs::create_dir_all(&gitdir).unwrap();
+        std::fs::write(gitdir.join("commondir"), "../..\n").unwrap();
+        let wt = tmp.path().join("wt");
+        std::fs::create_dir_all(&wt).unwrap();
+        std::fs::write(wt.join(".git"), format!("gitdir: {}\n", gitdir.display())).unwrap();
+
+        let paths = writable_paths(&wt);
+        assert_eq!(paths.len(), 2);
+        assert_eq!(paths[1], repo_git.canonicalize().unwrap());
+        assert_eq!(writable_paths(tmp.path()).len(), 1);
+    }
+
+    #[test]
+    fn git_configuration_is_mounted_read_only() {
+        let tmp = tempfile::tempdir().unwrap();
+        let repo_git = tmp.path().join("repo/.git");
+        let gitdir = repo_git.join("worktrees/wt");
+        std::fs::create_dir_all(&gitdir).unwrap();
+        std::fs::create_dir_all(repo_git.join("hooks")).unwrap();
+        std::fs::write(repo_git.join("config"), "").unwrap();
+        std::fs::write(gitdir.join("commondir"), "../..\n").unwrap();
+        let wt = tmp.path().join("wt");
+        std::fs::create_dir_all(&wt).unwrap();
+        std::fs::write(wt.join(".git"), format!("gitdir: {}\n", gitdir.display())).unwrap();
+        std::fs::write(
+            gitdir.join("gitdir"),
+            format!("{}\n", wt.join(".git").display()),
+        )
+        .unwrap();
+        let common = repo_git.canonicalize().unwrap();
+        let admin = gitdir.canonicalize().unwrap();
+        let dot_git = wt.join(".git").to_string_lossy().into_owned();
+        let commondir = admin.join("commondir").to_string_lossy().into_owned();
+        let gitdir_file = admin.join("gitdir").to_string_lossy().into_owned();
+        let worktree_config = admin.join("config.worktree").to_string_lossy().into_owned();
+        let common_str = common.to_string_lossy().into_owned();
+        let hooks = common.join("hooks").to_string_lossy().into_owned();
+        let config = common.join("config").to_string_lossy().into_owned();
+
+        let policy = SandboxPolicy {
+            backend: SandboxBackend::Bubblewrap,
+            ..Default::default()
+        };
+        let args = strings(&policy.bwrap_args(Path::new("/nonexistent/claude"), &wt, None, &[]));
+        let rw = args.iter().position(|a| a == &common_str).unwrap();
+        for path in [&hooks, &config, &dot_git, &commondir, &gitdir_file] {
+            assert!(contains_seq(&args, &["--ro-bind", path, path]), "{path}");
+            // Must come after the read-write bind to take effect
+            assert!(args.iter().position(|a| a == path).unwrap() > rw);
+        }
+        // A missing worktree config is covered so it cannot be planted
+        assert!(contains_seq(
+            &args,
+            &["--ro-bind", "/dev/null", &worktree_config]
+        ));
+
+        let policy = SandboxPolicy {
+            backend: SandboxBackend::Podman,
+            image: Some("img".into()),
+            ..Default::default()
+        };
+        let args = strings(&policy.podman_args(Path::new("claude"), &wt, None, &[]));
+        let rw = args
+            .iter()
+            .position(|a| a == &format!("--volume={common_str}:{common_str}:rw"))
+            .unwrap();
+        for path in [&hooks, &config, &dot_git, &commondir, &gitdir_file] {
+            let ro = format!("--volume={path}:{path}:ro");
+            assert!(args.iter().position(|a| a == &ro).unwrap() > rw, "{path}");
+        }
+        assert!(args.contains(&format!("--volume=/dev/null:{worktree_config}:ro")));
+    }
+}
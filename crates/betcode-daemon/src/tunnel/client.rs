@@ -3,7 +3,7 @@
 use std::collections::HashMap;
 use std::sync::Arc;
 
-use tokio::sync::mpsc;
+use tokio::sync::{broadcast, mpsc};
 use tokio::time::sleep;
 use tokio_stream::StreamExt;
 use tokio_stream::wrappers::ReceiverStream;
@@ -27,7 +27,7 @@ use crate::server::{
     CommandServiceImpl, ConfigServiceImpl, GitLabServiceImpl, GitRepoServiceImpl,
     SubagentServiceImpl, VersionServiceImpl, WorktreeServiceImpl,
 };
-use crate::session::SessionMultiplexer;
+use crate::session::{NotificationIntent, SessionMultiplexer};
 use crate::storage::Database;
 
 /// Tunnel client that maintains a persistent connection to the relay.
@@ -307,6 +307,7 @@ impl TunnelClient {
         if let Some(subagent_svc) = &self.subagent_service {
             handler.set_subagent_service(Arc::clone(subagent_svc));
         }
+        handler.set_wake_enabled(self.config.allow_wake);
         let handler = Arc::new(handler);
 
         let outbound_stream = ReceiverStream::new(outbound_rx);
@@ -416,6 +417,8 @@ impl TunnelClient {
         let machine_id = self.config.machine_id.clone();
         let mut heartbeat_timer = tokio::time::interval(self.config.heartbeat_interval);
         heartbeat_timer.tick().await; // Skip first immediate tick
+        let mut notifications = self.multiplexer.subscribe_notifications();
+        let mut notifications_open = true;
 
         loop {
             tokio::select! {
@@ -475,6 +478,10 @@ impl TunnelClient {
                         ));
                     }
                 }
+                intent = notifications.recv(), if notifications_open => {
+                    notifications_open =
+                        forward_notification(&outbound_tx, &machine_id, intent).await?;
+                }
                 _ = shutdown.changed() => {
                     info!("Tunnel client received shutdown signal");
                     return Ok(());
@@ -484,6 +491,44 @@ impl TunnelClient {
     }
 }
 
+/// Send a notification intent to the relay. Returns whether more intents
+/// can follow.
+async fn forward_notification(
+    tx: &mpsc::Sender<TunnelFrame>,
+    machine_id: &str,
+    intent: Result<NotificationIntent, broadcast::error::RecvError>,
+) -> Result<bool, TunnelClientError> {
+    match intent {
+        Ok(intent) => {
+            if tx.send(notify_frame(machine_id, &intent)).await.is_err() {
+                return Err(TunnelClientError::Connection(
+                    "Outbound channel closed while sending notification".into(),
+                ));
+            }
+        }
+        Err(broadcast::error::RecvError::Lagged(skipped)) => {
+            warn!(skipped, "Dropped notification intents");
+        }
+        Err(broadcast::error::RecvError::Closed) => return Ok(false),
+    }
+    Ok(true)
+}
+
+/// Control frame asking the relay to notify the machine's owner.
+fn notify_frame(machine_id: &str, intent: &NotificationIntent) -> TunnelFrame {
+    TunnelFrame {
+        request_id: String::new(),
+        frame_type: FrameType::Control as i32,
+        timestamp: Some(prost_types::Timestamp::from(std::time::SystemTime::now())),
+        payload: Some(betcode_proto::v1::tunnel_frame::Payload::Control(
+            TunnelControl {
+                control_type: TunnelControlType::Notify as i32,
+                params: intent.to_params(machine_id),
+            },
+        )),
+    }
+}
+
 /// Auto-discover mTLS client certificate files in `$HOME/.betcode/certs/`.
 ///
 /// Returns `Some((cert_path, key_path))` if both files exist, `None` otherwise.
@@ -44,6 +44,9 @@ pub struct TunnelConfig {
 
     /// Path to PEM-encoded client private key for mTLS.
     pub client_key_path: Option<PathBuf>,
+
+    /// Send Wake-on-LAN packets for other machines when the relay asks.
+    pub allow_wake: bool,
 }
 
 /// Exponential backoff reconnection policy.
@@ -115,6 +118,7 @@ impl TunnelConfig {
             identity_key_path: None,
             client_cert_path: None,
             client_key_path: None,
+            allow_wake: false,
         }
     }
 }
@@ -133,6 +137,7 @@ impl fmt::Debug for TunnelConfig {
             .field("identity_key_path", &self.identity_key_path)
             .field("client_cert_path", &self.client_cert_path)
             .field("client_key_path", &self.client_key_path)
+            .field("allow_wake", &self.allow_wake)
             .finish()
     }
 }
@@ -23,21 +23,25 @@ use betcode_proto::v1::{
     CompactSessionResponse, CreateBranchRequest, CreateOrchestrationRequest, CreateWorktreeRequest,
     DeleteBranchRequest, DeleteSessionRequest, DeleteSessionResponse, DisablePluginRequest,
     EnablePluginRequest, EncryptedPayload, ExecuteServiceCommandRequest, FrameType,
-    GetBranchRequest, GetCommandRegistryRequest, GetIssueRequest, GetMergeRequestRequest,
-    GetPermissionsRequest, GetPipelineRequest, GetPluginStatusRequest, GetRepoRequest,
-    GetSettingsRequest, GetVersionRequest, GetWorktreeRequest, InputLockRequest, InputLockResponse,
-    KeyExchangeRequest, KeyExchangeResponse, ListAgentsRequest, ListBranchesRequest,
-    ListIssuesRequest, ListMcpServersRequest, ListMergeRequestsRequest, ListPathRequest,
-    ListPipelinesRequest, ListPluginsRequest, ListReposRequest, ListSessionGrantsRequest,
-    ListSessionGrantsResponse, ListSessionsRequest, ListSessionsResponse, ListSubagentsRequest,
-    ListWorktreesRequest, NegotiateRequest, RegisterRepoRequest, RemovePluginRequest,
-    RemoveWorktreeRequest, RenameSessionRequest, RenameSessionResponse, ResumeSessionRequest,
-    RevokeAutoApproveRequest, ScanReposRequest, SendToSubagentRequest, SessionSummary,
+    GcWorktreesRequest, GetBranchRequest, GetCommandRegistryRequest, GetIssueRequest,
+    GetMergeRequestRequest, GetPermissionsRequest, GetPipelineRequest, GetPluginStatusRequest,
+    GetRepoRequest, GetSettingsRequest, GetVersionRequest, GetWorktreeRequest,
+    GetWorktreeStatusRequest, InputLockRequest, InputLockResponse, KeyExchangeRequest,
+    KeyExchangeResponse, ListAgentsRequest, ListBranchesRequest, ListIssuesRequest,
+    ListMcpServersRequest, ListMergeRequestsRequest, ListPathRequest, ListPipelinesRequest,
+    ListPluginsRequest, ListReposRequest, ListSessionGrantsRequest, ListSessionGrantsResponse,
+    ListSessionsRequest, ListSessionsResponse, ListSubagentsRequest, ListWorktreesRequest,
+    NegotiateRequest, PermissionDecision, RegisterRepoRequest, RemovePluginRequest,
+    RemoveWorktreeRequest, RenameSessionRequest, RenameSessionResponse, RespondToPermissionRequest,
+    RespondToPermissionResponse, ResumeSessionRequest, RevokeAutoApproveRequest,
+    RunWorktreeSetupRequest, ScanReposRequest, SendToSubagentRequest, SessionSummary,
     SetSessionGrantRequest, SetSessionGrantResponse, SpawnSubagentRequest, StreamPayload,
-    TunnelError, TunnelErrorCode, TunnelFrame, UnregisterRepoRequest, UpdateRepoRequest,
-    UpdateSettingsRequest, WatchOrchestrationRequest, WatchSubagentRequest,
+    SubagentPermissionResponse, SyncWorktreeRequest, TunnelControlType, TunnelError,
+    TunnelErrorCode, TunnelFrame, UnregisterRepoRequest, UpdateRepoRequest, UpdateSettingsRequest,
+    WatchOrchestrationRequest, WatchSubagentRequest,
 };
 
+use betcode_core::wake::WakeTarget;
 use betcode_crypto::{CryptoSession, IdentityKeyPair, KeyExchangeState};
 
 use crate::relay::{SessionRelay, is_granted};
@@ -54,18 +58,21 @@ pub use betcode_proto::methods::{
     METHOD_ADD_PLUGIN, METHOD_CANCEL_SUBAGENT, METHOD_CANCEL_TURN, METHOD_CLEAR_SESSION_GRANTS,
     METHOD_COMPACT_SESSION, METHOD_CONVERSE, METHOD_CREATE_BRANCH, METHOD_CREATE_ORCHESTRATION,
     METHOD_CREATE_WORKTREE, METHOD_DELETE_BRANCH, METHOD_DELETE_SESSION, METHOD_DISABLE_PLUGIN,
-    METHOD_ENABLE_PLUGIN, METHOD_EXCHANGE_KEYS, METHOD_EXECUTE_SERVICE_COMMAND, METHOD_GET_BRANCH,
-    METHOD_GET_COMMAND_REGISTRY, METHOD_GET_ISSUE, METHOD_GET_MERGE_REQUEST,
-    METHOD_GET_PERMISSIONS, METHOD_GET_PIPELINE, METHOD_GET_PLUGIN_STATUS, METHOD_GET_REPO,
-    METHOD_GET_SETTINGS, METHOD_GET_VERSION, METHOD_GET_WORKTREE, METHOD_LIST_AGENTS,
-    METHOD_LIST_BRANCHES, METHOD_LIST_ISSUES, METHOD_LIST_MCP_SERVERS, METHOD_LIST_MERGE_REQUESTS,
-    METHOD_LIST_PATH, METHOD_LIST_PIPELINES, METHOD_LIST_PLUGINS, METHOD_LIST_REPOS,
-    METHOD_LIST_SESSION_GRANTS, METHOD_LIST_SESSIONS, METHOD_LIST_SUBAGENTS, METHOD_LIST_WORKTREES,
+    METHOD_ENABLE_PLUGIN, METHOD_EXCHANGE_KEYS, METHOD_EXECUTE_SERVICE_COMMAND,
+    METHOD_GC_WORKTREES, METHOD_GET_BRANCH, METHOD_GET_COMMAND_REGISTRY, METHOD_GET_ISSUE,
+    METHOD_GET_MERGE_REQUEST, METHOD_GET_PERMISSIONS, METHOD_GET_PIPELINE,
+    METHOD_GET_PLUGIN_STATUS, METHOD_GET_REPO, METHOD_GET_SETTINGS, METHOD_GET_VERSION,
+    METHOD_GET_WORKTREE, METHOD_GET_WORKTREE_STATUS, METHOD_LIST_AGENTS, METHOD_LIST_BRANCHES,
+    METHOD_LIST_ISSUES, METHOD_LIST_MCP_SERVERS, METHOD_LIST_MERGE_REQUESTS, METHOD_LIST_PATH,
+    METHOD_LIST_PIPELINES, METHOD_LIST_PLUGINS, METHOD_LIST_REPOS, METHOD_LIST_SESSION_GRANTS,
+    METHOD_LIST_SESSIONS, METHOD_LIST_SUBAGENTS, METHOD_LIST_WORKTREES,
     METHOD_NEGOTIATE_CAPABILITIES, METHOD_REGISTER_REPO, METHOD_REMOVE_PLUGIN,
     METHOD_REMOVE_WORKTREE, METHOD_RENAME_SESSION, METHOD_REQUEST_INPUT_LOCK,
-    METHOD_RESUME_SESSION, METHOD_REVOKE_AUTO_APPROVE, METHOD_SCAN_REPOS, METHOD_SEND_TO_SUBAGENT,
-    METHOD_SET_SESSION_GRANT, METHOD_SPAWN_SUBAGENT, METHOD_UNREGISTER_REPO, METHOD_UPDATE_REPO,
-    METHOD_UPDATE_SETTINGS, METHOD_WATCH_ORCHESTRATION, METHOD_WATCH_SUBAGENT,
+    METHOD_RESPOND_TO_PERMISSION, METHOD_RESPOND_TO_SUBAGENT_PERMISSION, METHOD_RESUME_SESSION,
+    METHOD_REVOKE_AUTO_APPROVE, METHOD_RUN_WORKTREE_SETUP, METHOD_SCAN_REPOS,
+    METHOD_SEND_TO_SUBAGENT, METHOD_SET_SESSION_GRANT, METHOD_SPAWN_SUBAGENT, METHOD_SYNC_WORKTREE,
+    METHOD_UNREGISTER_REPO, METHOD_UPDATE_REPO, METHOD_UPDATE_SETTINGS, METHOD_WATCH_ORCHESTRATION,
+    METHOD_WATCH_SUBAGENT,
 };
 
 /// Default maximum number of sessions returned by `ListSessions`.
@@ -154,6 +161,8 @@ pub struct TunnelRequestHandler {
     version_service: Option<Arc<VersionServiceImpl>>,
     /// `SubagentService` implementation for handling subagent RPCs through the tunnel.
     subagent_service: Option<Arc<SubagentServiceImpl>>,
+    /// Whether to send Wake-on-LAN packets when the relay asks.
+    wake_enabled: bool,
 }
 
 impl TunnelRequestHandler {
@@ -183,6 +192,7 @@ impl TunnelRequestHandler {
             config_service: None,
             version_service: None,
             subagent_service: None,
+            wake_enabled: false,
         }
     }
 
@@ -221,13 +231,25 @@ impl TunnelRequestHandler {
         self.subagent_service = Some(svc);
     }
 
+    /// Allow the relay to have this daemon wake other machines on its LAN.
+    pub const fn set_wake_enabled(&mut self, enabled: bool) {
+        self.wake_enabled = enabled;
+    }
+
     /// Process an incoming frame and produce zero or more response frames.
     pub async fn handle_frame(&self, frame: TunnelFrame) -> Vec<TunnelFrame> {
         let request_id = frame.request_id.clone();
         match FrameType::try_from(frame.frame_type) {
             Ok(FrameType::Request) => self.handle_request(request_id, frame).await,
             Ok(FrameType::Control) => {
-                debug!(request_id = %request_id, "Received control frame");
+                if let Some(betcode_proto::v1::tunnel_frame::Payload::Control(ctrl)) =
+                    &frame.payload
+                    && ctrl.control_type == TunnelControlType::Wake as i32
+                {
+                    self.handle_wake(&ctrl.params).await;
+                } else {
+                    debug!(request_id = %request_id, "Received control frame");
+                }
                 vec![]
             }
             Ok(FrameType::Error) => {
@@ -273,6 +295,33 @@ impl TunnelRequestHandler {
         }
     }
 
+    /// Send a Wake-on-LAN packet requested by the relay.
+    async fn handle_wake(&self, params: &HashMap<String, String>) {
+        let machine_id = params.get("machine_id").map_or("", String::as_str);
+        if !self.wake_enabled {
+            warn!(machine_id = %machine_id, "Ignoring wake request: wake is not enabled on this daemon");
+            return;
+        }
+        let target = match WakeTarget::from_params(params) {
+            Ok(target) => target,
+            Err(e) => {
+                warn!(machine_id = %machine_id, error = %e, "Ignoring malformed wake request");
+                return;
+            }
+        };
+        match super::wake::send_magic_packet(&target).await {
+            Ok(()) => info!(
+                machine_id = %machine_id,
+                mac = %target.mac,
+                broadcast = %target.broadcast,
+                "Sent Wake-on-LAN packet"
+            ),
+            Err(e) => {
+                warn!(machine_id = %machine_id, error = %e, "Failed to send Wake-on-LAN packet");
+            }
+        }
+    }
+
     /// Decrypt an `EncryptedPayload` using the session key, or passthrough if no crypto.
     ///
     /// When the nonce is empty, the payload is treated as plaintext passthrough.
@@ -382,6 +431,10 @@ impl TunnelRequestHandler {
                 self.handle_request_input_lock(&request_id, &data, relay_forwarded)
                     .await
             }
+            METHOD_RESPOND_TO_PERMISSION => {
+                self.handle_respond_to_permission(&request_id, &data, relay_forwarded)
+                    .await
+            }
             METHOD_CONVERSE => {
                 self.handle_converse(&request_id, &data, relay_forwarded)
                     .await;
@@ -447,7 +500,10 @@ impl TunnelRequestHandler {
             METHOD_CREATE_WORKTREE
             | METHOD_REMOVE_WORKTREE
             | METHOD_LIST_WORKTREES
-            | METHOD_GET_WORKTREE => {
+            | METHOD_GET_WORKTREE
+            | METHOD_GET_WORKTREE_STATUS
+            | METHOD_SYNC_WORKTREE
+            | METHOD_GC_WORKTREES => {
                 self.dispatch_worktree_rpc(
                     &request_id,
                     payload.method.as_str(),
@@ -508,7 +564,8 @@ impl TunnelRequestHandler {
             | METHOD_CANCEL_SUBAGENT
             | METHOD_LIST_SUBAGENTS
             | METHOD_CREATE_ORCHESTRATION
-            | METHOD_REVOKE_AUTO_APPROVE => {
+            | METHOD_REVOKE_AUTO_APPROVE
+            | METHOD_RESPOND_TO_SUBAGENT_PERMISSION => {
                 self.dispatch_subagent_rpc(
                     &request_id,
                     payload.method.as_str(),
@@ -528,6 +585,12 @@ impl TunnelRequestHandler {
                     .await;
                 vec![] // Responses sent asynchronously via outbound_tx
             }
+            // WorktreeService server-streaming RPCs
+            METHOD_RUN_WORKTREE_SETUP => {
+                self.handle_run_worktree_setup(&request_id, &data, relay_forwarded)
+                    .await;
+                vec![] // Responses sent asynchronously via outbound_tx
+            }
             other => vec![Self::error_response(
                 &request_id,
                 TunnelErrorCode::NotFound,
@@ -723,6 +786,65 @@ impl TunnelRequestHandler {
         ]
     }
 
+    async fn handle_respond_to_permission(
+        &self,
+        request_id: &str,
+        data: &[u8],
+        relay_forwarded: bool,
+    ) -> Vec<TunnelFrame> {
+        let req = match RespondToPermissionRequest::decode(data) {
+            Ok(r) => r,
+            Err(e) => {
+                return vec![Self::error_response(
+                    request_id,
+                    TunnelErrorCode::Internal,
+                    &format!("Decode error: {e}"),
+                )];
+            }
+        };
+        let Some(response) = req.response else {
+            return vec![Self::error_response(
+                request_id,
+                TunnelErrorCode::InvalidArgument,
+                "Missing permission response",
+            )];
+        };
+        let decision = PermissionDecision::try_from(response.decision).unwrap_or_else(|_| {
+            warn!(
+                raw_decision = response.decision,
+                "Unknown PermissionDecision, defaulting to Deny"
+            );
+            PermissionDecision::Deny
+        });
+        let accepted = match self
+            .relay
+            .respond_to_pending_permission(
+                &req.session_id,
+                &response.request_id,
+                decision,
+                "remote",
+            )
+            .await
+        {
+            Ok(accepted) => accepted,
+            Err(e) => {
+                return vec![Self::error_response(
+                    request_id,
+                    TunnelErrorCode::Internal,
+                    &format!("Failed to answer permission: {e}"),
+                )];
+            }
+        };
+        vec![
+            self.unary_response_frame(
+                request_id,
+                &RespondToPermissionResponse { accepted },
+                relay_forwarded,
+            )
+            .await,
+        ]
+    }
+
     async fn handle_request_input_lock(
         &self,
         request_id: &str,
@@ -1017,6 +1139,12 @@ impl TunnelRequestHandler {
             (None, _) => outer_req,
         };
 
+        let key = match crate::server::claim_request(&self.db, &self.multiplexer, &sid, &req).await
+        {
+            crate::server::Claim::Apply(key) => key,
+            crate::server::Claim::Skip => return,
+        };
+
         // Check if we need to start the subprocess (deferred from handle_converse).
         // Only consume the pending config when the request is a UserMessage so we
         // can pass the content as the `-p` prompt for headless mode. If the first
@@ -1050,6 +1178,14 @@ impl TunnelRequestHandler {
             }
         };
 
+        // A session already running (a client reconnecting to it) takes the
+        // message on stdin rather than as the prompt of a new subprocess.
+        let (pending, initial_prompt) = if pending.is_some() && self.relay.is_active(&sid).await {
+            (None, None)
+        } else {
+            (pending, initial_prompt)
+        };
+
         if let Some(config) = pending {
             debug!(
                 request_id = %request_id,
@@ -1077,10 +1213,12 @@ impl TunnelRequestHandler {
                     ))
                     .await;
                 self.active_streams.write().await.remove(request_id);
+                self.settle_request(&sid, key.as_deref(), false).await;
                 return;
             }
         }
 
+        let mut applied = true;
         match req.request {
             Some(Request::Message(msg)) => {
                 // Skip sending if the message was already passed as the `-p` prompt
@@ -1093,6 +1231,7 @@ impl TunnelRequestHandler {
                         .await
                     {
                         warn!(session_id = %sid, error = %e, "Failed to send user message via tunnel");
+                        applied = false;
                     }
                 }
             }
@@ -1123,6 +1262,7 @@ impl TunnelRequestHandler {
                     .await
                 {
                     warn!(session_id = %sid, error = %e, "Failed to send permission via tunnel");
+                    applied = false;
                 }
             }
             Some(Request::QuestionResponse(qr)) => {
@@ -1159,6 +1299,14 @@ impl TunnelRequestHandler {
                 warn!(request_id = %request_id, request_type = ?other.map(|_| "unknown"), "Ignoring non-actionable StreamData request");
             }
         }
+        self.settle_request(&sid, key.as_deref(), applied).await;
+    }
+
+    /// Settle the idempotency key, if any, of a request applied via tunnel.
+    async fn settle_request(&self, sid: &str, key: Option<&str>, applied: bool) {
+        if let Some(key) = key {
+            crate::server::settle_request(&self.db, &self.multiplexer, sid, key, applied).await;
+        }
     }
 
     /// Check if a `request_id` has an active streaming session.
@@ -2174,6 +2322,33 @@ impl TunnelRequestHandler {
                 GetWorktreeRequest,
                 get_worktree
             ),
+            METHOD_GET_WORKTREE_STATUS => dispatch_rpc!(
+                self,
+                svc,
+                request_id,
+                data,
+                relay_forwarded,
+                GetWorktreeStatusRequest,
+                get_worktree_status
+            ),
+            METHOD_SYNC_WORKTREE => dispatch_rpc!(
+                self,
+                svc,
+                request_id,
+                data,
+                relay_forwarded,
+                SyncWorktreeRequest,
+                sync_worktree
+            ),
+            METHOD_GC_WORKTREES => dispatch_rpc!(
+                self,
+                svc,
+                request_id,
+                data,
+                relay_forwarded,
+                GcWorktreesRequest,
+                gc_worktrees
+            ),
             _ => vec![Self::error_response(
                 request_id,
                 TunnelErrorCode::NotFound,
@@ -2357,6 +2532,15 @@ impl TunnelRequestHandler {
                 RevokeAutoApproveRequest,
                 revoke_auto_approve
             ),
+            METHOD_RESPOND_TO_SUBAGENT_PERMISSION => dispatch_rpc!(
+                self,
+                svc,
+                request_id,
+                data,
+                relay_forwarded,
+                SubagentPermissionResponse,
+                respond_to_subagent_permission
+            ),
             _ => vec![Self::error_response(
                 request_id,
                 TunnelErrorCode::NotFound,
@@ -2366,7 +2550,6 @@ impl TunnelRequestHandler {
     }
 
     /// Handle a `WatchSubagent` server-streaming request through the tunnel.
-    #[allow(clippy::too_many_lines)]
     async fn handle_watch_subagent(&self, request_id: &str, data: &[u8], relay_forwarded: bool) {
         let Some(svc) = &self.subagent_service else {
             let _ = self
@@ -2408,81 +2591,21 @@ impl TunnelRequestHandler {
             }
         };
 
-        let outbound_tx = self.outbound_tx.clone();
-        let rid = request_id.to_string();
         let crypto_for_response = if relay_forwarded {
             None
         } else {
             self.crypto_snapshot().await
         };
-        tokio::spawn(async move {
-            let mut stream = stream_resp.into_inner();
-            let mut seq = 0u64;
-            while let Some(item) = stream.next().await {
-                match item {
-                    Ok(event) => {
-                        let mut buf = Vec::with_capacity(event.encoded_len());
-                        if let Err(e) = event.encode(&mut buf) {
-                            warn!(request_id = %rid, error = %e, "Failed to encode subagent event");
-                            continue;
-                        }
-                        let encrypted = match make_encrypted_payload(
-                            crypto_for_response.as_deref(),
-                            &buf,
-                        ) {
-                            Ok(enc) => enc,
-                            Err(e) => {
-                                warn!(request_id = %rid, error = %e, "Failed to encrypt subagent event");
-                                continue;
-                            }
-                        };
-                        let frame = TunnelFrame {
-                            request_id: rid.clone(),
-                            frame_type: FrameType::StreamData as i32,
-                            timestamp: Some(prost_types::Timestamp::from(
-                                std::time::SystemTime::now(),
-                            )),
-                            payload: Some(betcode_proto::v1::tunnel_frame::Payload::StreamData(
-                                StreamPayload {
-                                    method: String::new(),
-                                    encrypted: Some(encrypted),
-                                    sequence: seq,
-                                    metadata: HashMap::new(),
-                                },
-                            )),
-                        };
-                        seq += 1;
-                        if outbound_tx.send(frame).await.is_err() {
-                            break;
-                        }
-                    }
-                    Err(e) => {
-                        warn!(error = %e, request_id = %rid, "WatchSubagent stream error");
-                        let _ = outbound_tx
-                            .send(Self::error_response(
-                                &rid,
-                                TunnelErrorCode::Internal,
-                                &format!("Stream error: {e}"),
-                            ))
-                            .await;
-                        break;
-                    }
-                }
-            }
-            let _ = outbound_tx
-                .send(TunnelFrame {
-                    request_id: rid.clone(),
-                    frame_type: FrameType::StreamEnd as i32,
-                    timestamp: Some(prost_types::Timestamp::from(std::time::SystemTime::now())),
-                    payload: None,
-                })
-                .await;
-            info!(request_id = %rid, "WatchSubagent stream ended");
-        });
+        spawn_stream_forwarder(
+            self.outbound_tx.clone(),
+            request_id.to_string(),
+            crypto_for_response,
+            stream_resp.into_inner(),
+            "WatchSubagent",
+        );
     }
 
     /// Handle a `WatchOrchestration` server-streaming request through the tunnel.
-    #[allow(clippy::too_many_lines)]
     async fn handle_watch_orchestration(
         &self,
         request_id: &str,
@@ -2529,77 +2652,79 @@ impl TunnelRequestHandler {
             }
         };
 
-        let outbound_tx = self.outbound_tx.clone();
-        let rid = request_id.to_string();
         let crypto_for_response = if relay_forwarded {
             None
         } else {
             self.crypto_snapshot().await
         };
-        tokio::spawn(async move {
-            let mut stream = stream_resp.into_inner();
-            let mut seq = 0u64;
-            while let Some(item) = stream.next().await {
-                match item {
-                    Ok(event) => {
-                        let mut buf = Vec::with_capacity(event.encoded_len());
-                        if let Err(e) = event.encode(&mut buf) {
-                            warn!(request_id = %rid, error = %e, "Failed to encode orchestration event");
-                            continue;
-                        }
-                        let encrypted = match make_encrypted_payload(
-                            crypto_for_response.as_deref(),
-                            &buf,
-                        ) {
-                            Ok(enc) => enc,
-                            Err(e) => {
-                                warn!(request_id = %rid, error = %e, "Failed to encrypt orchestration event");
-                                continue;
-                            }
-                        };
-                        let frame = TunnelFrame {
-                            request_id: rid.clone(),
-                            frame_type: FrameType::StreamData as i32,
-                            timestamp: Some(prost_types::Timestamp::from(
-                                std::time::SystemTime::now(),
-                            )),
-                            payload: Some(betcode_proto::v1::tunnel_frame::Payload::StreamData(
-                                StreamPayload {
-                                    method: String::new(),
-                                    encrypted: Some(encrypted),
-                                    sequence: seq,
-                                    metadata: HashMap::new(),
-                                },
-                            )),
-                        };
-                        seq += 1;
-                        if outbound_tx.send(frame).await.is_err() {
-                            break;
-                        }
-                    }
-                    Err(e) => {
-                        warn!(error = %e, request_id = %rid, "WatchOrchestration stream error");
-                        let _ = outbound_tx
-                            .send(Self::error_response(
-                                &rid,
-                                TunnelErrorCode::Internal,
-                                &format!("Stream error: {e}"),
-                            ))
-                            .await;
-                        break;
-                    }
-                }
-            }
-            let _ = outbound_tx
-                .send(TunnelFrame {
-                    request_id: rid.clone(),
-                    frame_type: FrameType::StreamEnd as i32,
-                    timestamp: Some(prost_types::Timestamp::from(std::time::SystemTime::now())),
-                    payload: None,
-                })
+        spawn_stream_forwarder(
+            self.outbound_tx.clone(),
+            request_id.to_string(),
+            crypto_for_response,
+            stream_resp.into_inner(),
+            "WatchOrchestration",
+        );
+    }
+
+    /// Handle a `RunWorktreeSetup` server-streaming request through the tunnel.
+    async fn handle_run_worktree_setup(
+        &self,
+        request_id: &str,
+        data: &[u8],
+        relay_forwarded: bool,
+    ) {
+        let Some(svc) = &self.worktree_service else {
+            let _ = self
+                .outbound_tx
+                .send(Self::error_response(
+                    request_id,
+                    TunnelErrorCode::Internal,
+                    "WorktreeService not available in tunnel handler",
+                ))
                 .await;
-            info!(request_id = %rid, "WatchOrchestration stream ended");
-        });
+            return;
+        };
+        let req = match RunWorktreeSetupRequest::decode(data) {
+            Ok(r) => r,
+            Err(e) => {
+                let _ = self
+                    .outbound_tx
+                    .send(Self::error_response(
+                        request_id,
+                        TunnelErrorCode::Internal,
+                        &format!("Decode error: {e}"),
+                    ))
+                    .await;
+                return;
+            }
+        };
+        let stream_resp = match svc.run_worktree_setup(Request::new(req)).await {
+            Ok(resp) => resp,
+            Err(status) => {
+                let _ = self
+                    .outbound_tx
+                    .send(Self::error_response(
+                        request_id,
+                        TunnelErrorCode::Internal,
+                        &format!("RunWorktreeSetup failed: {}", status.message()),
+                    ))
+                    .await;
+                return;
+            }
+        };
+
+        let crypto_for_response = if relay_forwarded {
+            None
+        } else {
+            self.crypto_snapshot().await
+        };
+        spawn_stream_forwarder(
+            self.outbound_tx.clone(),
+            request_id.to_string(),
+            crypto_for_response,
+            stream_resp.into_inner(),
+            "RunWorktreeSetup",
+        );
     }
 
     /// Build a unary response frame, skipping tunnel-layer encryption for
@@ -2704,6 +2829,81 @@ impl TunnelRequestHandler {
     }
 }
 
+/// Forward a server-streaming RPC response to the tunnel as `StreamData`
+/// frames, followed by a `StreamEnd` frame.
+fn spawn_stream_forwarder<M, S>(
+    outbound_tx: mpsc::Sender<TunnelFrame>,
+    rid: String,
+    crypto_for_response: Option<Arc<CryptoSession>>,
+    mut stream: S,
+    rpc: &'static str,
+) where
+    M: Message,
+    S: tokio_stream::Stream<Item = Result<M, tonic::Status>> + Send + Unpin + 'static,
+{
+    tokio::spawn(async move {
+        let mut seq = 0u64;
+        while let Some(item) = stream.next().await {
+            match item {
+                Ok(event) => {
+                    let mut buf = Vec::with_capacity(event.encoded_len());
+                    if let Err(e) = event.encode(&mut buf) {
+                        warn!(request_id = %rid, error = %e, rpc, "Failed to encode stream event");
+                        continue;
+                    }
+                    let encrypted = match make_encrypted_payload(
+                        crypto_for_response.as_deref(),
+                        &buf,
+                    ) {
+                        Ok(enc) => enc,
+                        Err(e) => {
+                            warn!(request_id = %rid, error = %e, rpc, "Failed to encrypt stream event");
+                            continue;
+                        }
+                    };
+                    let frame = TunnelFrame {
+                        request_id: rid.clone(),
+                        frame_type: FrameType::StreamData as i32,
+                        timestamp: Some(prost_types::Timestamp::from(std::time::SystemTime::now())),
+                        payload: Some(betcode_proto::v1::tunnel_frame::Payload::StreamData(
+                            StreamPayload {
+                                method: String::new(),
+                                encrypted: Some(encrypted),
+                                sequence: seq,
+                                metadata: HashMap::new(),
+                            },
+                        )),
+                    };
+                    seq += 1;
+                    if outbound_tx.send(frame).await.is_err() {
+                        break;
+                    }
+                }
+                Err(e) => {
+                    warn!(error = %e, request_id = %rid, "{rpc} stream error");
+                    let _ = outbound_tx
+                        .send(TunnelRequestHandler::error_response(
+                            &rid,
+                            TunnelErrorCode::Internal,
+                            &format!("Stream error: {e}"),
+                        ))
+                        .await;
+                    break;
+                }
+            }
+        }
+        let _ = outbound_tx
+            .send(TunnelFrame {
+                request_id: rid.clone(),
+                frame_type: FrameType::StreamEnd as i32,
+                timestamp: Some(prost_types::Timestamp::from(std::time::SystemTime::now())),
+                payload: None,
+            })
+            .await;
+        info!(request_id = %rid, "{rpc} stream ended");
+    });
+}
+
 /// Encrypt data with the session if available, or wrap raw bytes (passthrough).
 fn make_encrypted_payload(
     crypto: Option<&CryptoSession>,
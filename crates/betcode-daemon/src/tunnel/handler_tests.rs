@@ -146,14 +146,16 @@ impl HandlerTestBuilder {
         }
 
         if self.with_gitlab_service {
+            use crate::forge::ForgeRegistry;
             use crate::gitlab::{GitLabClient, GitLabConfig};
 
             let config = GitLabConfig {
                 base_url: "http://127.0.0.1:1".into(),
                 token: "test-token".into(),
             };
-            let client = Arc::new(GitLabClient::new(&config).unwrap());
-            let gitlab_svc = Arc::new(GitLabServiceImpl::new(client));
+            let mut forges = ForgeRegistry::new(None);
+            forges.register(Arc::new(GitLabClient::new(&config).unwrap()));
+            let gitlab_svc = Arc::new(GitLabServiceImpl::new(Arc::new(forges)));
             handler.set_gitlab_service(gitlab_svc);
         }
 
@@ -237,6 +239,62 @@ async fn control_frame_returns_empty() {
     assert!(h.handle_frame(f).await.is_empty());
 }
 
+/// A `WAKE` control frame aimed at a local UDP socket instead of a broadcast
+/// address.
+async fn wake_frame_to_local_socket() -> (TunnelFrame, tokio::net::UdpSocket) {
+    let socket = tokio::net::UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    let target = WakeTarget::parse(
+        "aa:bb:cc:dd:ee:ff",
+        "127.0.0.1",
+        &socket.local_addr().unwrap().port().to_string(),
+    )
+    .unwrap();
+    let mut params = target.to_params();
+    params.insert("machine_id".into(), "sleeper".into());
+    let frame = TunnelFrame {
+        request_id: String::new(),
+        frame_type: FrameType::Control as i32,
+        timestamp: None,
+        payload: Some(betcode_proto::v1::tunnel_frame::Payload::Control(
+            betcode_proto::v1::TunnelControl {
+                control_type: TunnelControlType::Wake as i32,
+                params,
+            },
+        )),
+    };
+    (frame, socket)
+}
+
+#[tokio::test]
+async fn wake_frame_sends_magic_packet_when_enabled() {
+    let HandlerTestOutput { mut handler, .. } = HandlerTestBuilder::new().build().await;
+    handler.set_wake_enabled(true);
+    let (frame, socket) = wake_frame_to_local_socket().await;
+
+    assert!(handler.handle_frame(frame).await.is_empty());
+    let mut buf = [0u8; 256];
+    let len = tokio::time::timeout(std::time::Duration::from_secs(2), socket.recv(&mut buf))
+        .await
+        .unwrap()
+        .unwrap();
+    let mac = "aa:bb:cc:dd:ee:ff"
+        .parse::<betcode_core::wake::MacAddress>()
+        .unwrap();
+    assert_eq!(buf[..len], mac.magic_packet());
+}
+
+#[tokio::test]
+async fn wake_frame_ignored_when_disabled() {
+    let HandlerTestOutput { handler, .. } = HandlerTestBuilder::new().build().await;
+    let (frame, socket) = wake_frame_to_local_socket().await;
+
+    assert!(handler.handle_frame(frame).await.is_empty());
+    let mut buf = [0u8; 256];
+    let received =
+        tokio::time::timeout(std::time::Duration::from_millis(100), socket.recv(&mut buf)).await;
+    assert!(received.is_err());
+}
+
 #[tokio::test]
 async fn error_frame_returns_empty() {
     let HandlerTestOutput { handler: h, .. } = HandlerTestBuilder::new().build().await;
@@ -465,6 +523,7 @@ async fn converse_non_start_sends_error_on_outbound() {
             content: "hello".into(),
             attachments: vec![],
             agent_id: String::new(),
+            idempotency_key: String::new(),
         })),
     };
     let result = h
@@ -501,6 +560,7 @@ async fn converse_start_session_failure_sends_error_cleans_active() {
             content: "hello".into(),
             attachments: vec![],
             agent_id: String::new(),
+            idempotency_key: String::new(),
         })),
     };
     let stream_frame = TunnelFrame {
@@ -535,6 +595,105 @@ async fn converse_start_session_failure_sends_error_cleans_active() {
     assert!(!h.has_active_stream("conv3").await);
 }
 
+fn keyed_message_frame(rid: &str, key: &str) -> TunnelFrame {
+    use betcode_proto::v1::{UserMessage, agent_request::Request};
+    let user_msg = AgentRequest {
+        request: Some(Request::Message(UserMessage {
+            content: "queued while offline".into(),
+            attachments: vec![],
+            agent_id: String::new(),
+            idempotency_key: key.into(),
+        })),
+    };
+    TunnelFrame {
+        request_id: rid.into(),
+        frame_type: FrameType::StreamData as i32,
+        timestamp: None,
+        payload: Some(betcode_proto::v1::tunnel_frame::Payload::StreamData(
+            StreamPayload {
+                method: String::new(),
+                encrypted: Some(betcode_proto::v1::EncryptedPayload {
+                    ciphertext: encode(&user_msg),
+                    nonce: Vec::new(),
+                    ephemeral_pubkey: Vec::new(),
+                }),
+                sequence: 0,
+                metadata: HashMap::new(),
+            },
+        )),
+    }
+}
+
+#[tokio::test]
+async fn failed_replayed_message_releases_its_key() {
+    let HandlerTestOutput {
+        handler: h, mut rx, ..
+    } = HandlerTestBuilder::new().max_processes(0).build().await;
+
+    h.handle_frame(req_frame(
+        "conv-key",
+        METHOD_CONVERSE,
+        make_start_request("sess-key"),
+    ))
+    .await;
+    h.handle_frame(keyed_message_frame("conv-key", "key-1"))
+        .await;
+
+    // The spawn fails, and no acknowledgement precedes the error
+    let frame = tokio::time::timeout(std::time::Duration::from_millis(500), rx.recv())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(frame.frame_type, FrameType::Error as i32);
+
+    // The key is free for the client's next replay
+    assert_eq!(
+        h.db().claim_request_key("sess-key", "key-1").await.unwrap(),
+        crate::storage::KeyClaim::Claimed
+    );
+}
+
+#[tokio::test]
+async fn applied_replayed_message_is_acknowledged_not_reapplied() {
+    let HandlerTestOutput {
+        handler: h, mut rx, ..
+    } = HandlerTestBuilder::new().build().await;
+
+    h.db().claim_request_key("sess-dup", "key-1").await.unwrap();
+    h.db()
+        .finish_request_key("sess-dup", "key-1")
+        .await
+        .unwrap();
+    h.handle_frame(req_frame(
+        "conv-dup",
+        METHOD_CONVERSE,
+        make_start_request("sess-dup"),
+    ))
+    .await;
+    h.handle_frame(keyed_message_frame("conv-dup", "key-1"))
+        .await;
+
+    let frame = tokio::time::timeout(std::time::Duration::from_millis(500), rx.recv())
+        .await
+        .unwrap()
+        .unwrap();
+    let Some(betcode_proto::v1::tunnel_frame::Payload::StreamData(p)) = &frame.payload else {
+        panic!("Expected StreamData payload");
+    };
+    let event = AgentEvent::decode(p.encrypted.as_ref().unwrap().ciphertext.as_slice()).unwrap();
+    match event.event {
+        Some(betcode_proto::v1::agent_event::Event::RequestApplied(ack)) => {
+            assert_eq!(ack.idempotency_key, "key-1");
+            assert!(ack.duplicate);
+        }
+        other => panic!("Expected RequestApplied, got {other:?}"),
+    }
+
+    // The duplicate did not start the deferred subprocess
+    let streams = h.active_streams.read().await;
+    assert!(streams.get("conv-dup").unwrap().pending_config.is_some());
+}
+
 #[tokio::test]
 async fn resume_session_returns_empty_vec_async() {
     let HandlerTestOutput { handler: h, .. } = HandlerTestBuilder::new().build().await;
@@ -1107,6 +1266,7 @@ async fn encrypted_agent_request_is_decrypted() {
                 content: "encrypted hello".into(),
                 attachments: vec![],
                 agent_id: String::new(),
+                idempotency_key: String::new(),
             },
         )),
     };
@@ -1178,6 +1338,7 @@ async fn encrypted_agent_request_with_wrong_key_rejected() {
                 content: "wrong key".into(),
                 attachments: vec![],
                 agent_id: String::new(),
+                idempotency_key: String::new(),
             },
         )),
     };
@@ -1239,6 +1400,7 @@ async fn encrypted_agent_request_with_corrupted_data_rejected() {
                 content: "will be corrupted".into(),
                 attachments: vec![],
                 agent_id: String::new(),
+                idempotency_key: String::new(),
             },
         )),
     };
@@ -1309,6 +1471,7 @@ async fn plaintext_agent_request_rejected_when_crypto_active() {
                 content: "injected plaintext".into(),
                 attachments: vec![],
                 agent_id: String::new(),
+                idempotency_key: String::new(),
             },
         )),
     };
@@ -1518,6 +1681,7 @@ async fn decrypt_payload_passthrough_on_empty_nonce_with_crypto_active() {
                 content: "hello from relay".into(),
                 attachments: vec![],
                 agent_id: String::new(),
+                idempotency_key: String::new(),
             },
         )),
     };
@@ -1556,6 +1720,7 @@ async fn relay_forwarded_plaintext_request_rejected_when_crypto_active() {
                 content: "plain attack".into(),
                 attachments: vec![],
                 agent_id: String::new(),
+                idempotency_key: String::new(),
             },
         )),
     };
@@ -2068,6 +2233,9 @@ async fn worktree_all_methods_dispatch_without_service() {
         METHOD_REMOVE_WORKTREE,
         METHOD_LIST_WORKTREES,
         METHOD_GET_WORKTREE,
+        METHOD_GET_WORKTREE_STATUS,
+        METHOD_SYNC_WORKTREE,
+        METHOD_GC_WORKTREES,
     ];
     for method in methods {
         let r = h
@@ -2098,6 +2266,58 @@ async fn worktree_malformed_data_returns_error() {
     assert_eq!(r[0].frame_type, FrameType::Error as i32);
 }
 
+#[tokio::test]
+async fn run_worktree_setup_without_service_sends_error() {
+    let HandlerTestOutput {
+        handler: h, mut rx, ..
+    } = HandlerTestBuilder::new().build().await;
+    let req = betcode_proto::v1::RunWorktreeSetupRequest {
+        id: "wt-1".into(),
+        ..Default::default()
+    };
+    let r = h
+        .handle_frame(req_frame(
+            "wt-setup",
+            METHOD_RUN_WORKTREE_SETUP,
+            encode(&req),
+        ))
+        .await;
+    assert!(r.is_empty());
+    let frame = tokio::time::timeout(std::time::Duration::from_secs(5), rx.recv())
+        .await
+        .expect("Timed out waiting for frame")
+        .expect("Channel closed unexpectedly");
+    assert_eq!(frame.frame_type, FrameType::Error as i32);
+}
+
+#[tokio::test]
+async fn run_worktree_setup_unknown_worktree_sends_error() {
+    let HandlerTestOutput {
+        handler: h, mut rx, ..
+    } = HandlerTestBuilder::new()
+        .with_worktree_service()
+        .build()
+        .await;
+    let req = betcode_proto::v1::RunWorktreeSetupRequest {
+        id: "nope".into(),
+        ..Default::default()
+    };
+    let r = h
+        .handle_frame(req_frame(
+            "wt-setup",
+            METHOD_RUN_WORKTREE_SETUP,
+            encode(&req),
+        ))
+        .await;
+    assert!(r.is_empty());
+    let frame = tokio::time::timeout(std::time::Duration::from_secs(5), rx.recv())
+        .await
+        .expect("Timed out waiting for frame")
+        .expect("Channel closed unexpectedly");
+    assert_eq!(frame.request_id, "wt-setup");
+    assert_eq!(frame.frame_type, FrameType::Error as i32);
+}
+
 // --- I-2: ExecuteServiceCommand streaming tests (daemon side) ---
 
 use betcode_proto::v1::{ExecuteServiceCommandRequest, ServiceCommandOutput};
@@ -2607,3 +2827,48 @@ async fn delete_session_via_tunnel_nonexistent_returns_false() {
         panic!("expected response payload");
     }
 }
+
+// --- RespondToPermission tunnel handler tests ---
+
+#[tokio::test]
+async fn respond_to_permission_via_tunnel_without_session_is_not_accepted() {
+    let HandlerTestOutput { handler: h, .. } = HandlerTestBuilder::new().build().await;
+
+    let req = RespondToPermissionRequest {
+        session_id: "no-such-session".into(),
+        response: Some(betcode_proto::v1::PermissionResponse {
+            request_id: "perm-1".into(),
+            decision: PermissionDecision::Deny.into(),
+            ..Default::default()
+        }),
+    };
+    let r = h
+        .handle_frame(req_frame("rp1", METHOD_RESPOND_TO_PERMISSION, encode(&req)))
+        .await;
+    assert_eq!(r.len(), 1);
+    assert_eq!(r[0].frame_type, FrameType::Response as i32);
+    if let Some(betcode_proto::v1::tunnel_frame::Payload::StreamData(p)) = &r[0].payload {
+        let resp = RespondToPermissionResponse::decode(
+            p.encrypted.as_ref().unwrap().ciphertext.as_slice(),
+        )
+        .unwrap();
+        assert!(!resp.accepted);
+    } else {
+        panic!("expected response payload");
+    }
+}
+
+#[tokio::test]
+async fn respond_to_permission_via_tunnel_requires_response() {
+    let HandlerTestOutput { handler: h, .. } = HandlerTestBuilder::new().build().await;
+
+    let req = RespondToPermissionRequest {
+        session_id: "s1".into(),
+        response: None,
+    };
+    let r = h
+        .handle_frame(req_frame("rp2", METHOD_RESPOND_TO_PERMISSION, encode(&req)))
+        .await;
+    assert_eq!(r.len(), 1);
+    assert_eq!(r[0].frame_type, FrameType::Error as i32);
+}
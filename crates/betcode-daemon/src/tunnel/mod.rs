@@ -1,8 +1,8 @@
 //! Tunnel client for connecting the daemon to a relay server.
 //!
 //! Provides outbound tunnel connectivity with automatic reconnection,
-//! frame-level request handling, heartbeat keepalive, and certificate
-//! rotation.
+//! frame-level request handling, heartbeat keepalive, certificate
+//! rotation, and Wake-on-LAN on behalf of sleeping machines.
 
 pub mod cert_rotation;
 pub mod client;
@@ -10,6 +10,7 @@ pub mod config;
 pub mod error;
 pub mod handler;
 pub mod heartbeat;
+pub mod wake;
 
 pub use cert_rotation::{RotationResult, spawn_cert_monitor};
 pub use client::TunnelClient;
@@ -0,0 +1,23 @@
+//! Wake-on-LAN sender for daemons acting as a waker.
+//!
+//! The relay asks an always-on daemon to wake a sleeping machine on the same
+//! LAN by sending it a `WAKE` control frame; the daemon broadcasts the magic
+//! packet. Only daemons started with `--allow-wake` act on these frames.
+
+use std::net::{Ipv4Addr, SocketAddr};
+
+use betcode_core::wake::WakeTarget;
+use tokio::net::UdpSocket;
+
+/// Broadcast the magic packet for `target`.
+pub async fn send_magic_packet(target: &WakeTarget) -> std::io::Result<()> {
+    let socket = UdpSocket::bind(SocketAddr::from((Ipv4Addr::UNSPECIFIED, 0))).await?;
+    socket.set_broadcast(true)?;
+    socket
+        .send_to(
+            &target.mac.magic_packet(),
+            SocketAddr::from((target.broadcast, target.port)),
+        )
+        .await?;
+    Ok(())
+}
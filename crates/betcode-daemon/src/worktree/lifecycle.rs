@@ -0,0 +1,1024 @@
+//! Worktree lifecycle operations: status, sync, integration and garbage
+//! collection.
+
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use betcode_core::db::unix_timestamp;
+use tracing::{info, warn};
+
+use super::manager::{WorktreeError, WorktreeManager};
+use crate::storage::Worktree;
+
+/// Timeout for local git commands (status, log, rebase, ...).
+const GIT_TIMEOUT: Duration = Duration::from_mins(1);
+
+/// Timeout for `git fetch`, which talks to the network.
+const FETCH_TIMEOUT: Duration = Duration::from_mins(2);
+
+/// Separator used in `git log --format` output (ASCII Unit Separator).
+const FIELD_SEP: char = '\x1f';
+
+/// Config overrides for every git command run here. Sandboxed sessions can
+/// write to worktrees, so hooks, fsmonitor and signing commands must never
+/// run on the host on their behalf.
+const SAFE_GIT_CONFIG: &[&str] = &[
+    "-c",
+    "core.hooksPath=/dev/null",
+    "-c",
+    "core.fsmonitor=false",
+    "-c",
+    "gpg.program=",
+    "-c",
+    "gpg.ssh.program=",
+    "-c",
+    "gpg.x509.program=",
+    "-c",
+    "commit.gpgSign=false",
+    "-c",
+    "tag.gpgSign=false",
+];
+
+/// A file with uncommitted changes, as reported by `git status --porcelain`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DirtyFile {
+    pub path: String,
+    /// Two-letter porcelain code (e.g. ` M`, `??`).
+    pub status: String,
+}
+
+/// The most recent commit on a worktree's branch.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommitSummary {
+    pub sha: String,
+    pub subject: String,
+    pub author: String,
+    /// Commit time (unix seconds).
+    pub committed_at: i64,
+}
+
+/// Git status of a worktree.
+#[derive(Debug, Clone)]
+pub struct WorktreeStatus {
+    pub worktree: Worktree,
+    pub exists_on_disk: bool,
+    pub dirty_files: Vec<DirtyFile>,
+    /// Ref used for ahead/behind: the upstream if set, otherwise the base branch.
+    pub compare_ref: Option<String>,
+    pub ahead: u32,
+    pub behind: u32,
+    pub last_commit: Option<CommitSummary>,
+}
+
+/// How `sync` integrates the base branch.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SyncStrategy {
+    #[default]
+    Rebase,
+    Merge,
+}
+
+/// How `integrate` brings another branch's commits into a worktree.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum IntegrateStrategy {
+    /// A merge commit, even when a fast-forward is possible.
+    #[default]
+    Merge,
+    /// Each commit re-applied on top, keeping history linear.
+    CherryPick,
+}
+
+/// Result of a `sync` or `integrate`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyncOutcome {
+    pub success: bool,
+    /// The ref that was integrated.
+    pub base_ref: String,
+    /// Conflicting paths; the rebase/merge was aborted when non-empty.
+    pub conflicts: Vec<String>,
+    pub message: String,
+}
+
+/// Why a worktree is eligible for garbage collection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GcReason {
+    /// The branch has been merged into the base branch.
+    Merged,
+    /// `last_active` is older than the configured idle age.
+    Idle,
+    /// The worktree directory no longer exists.
+    Missing,
+}
+
+impl GcReason {
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Merged => "merged",
+            Self::Idle => "idle",
+            Self::Missing => "missing",
+        }
+    }
+}
+
+/// One worktree considered by a GC pass.
+#[derive(Debug, Clone)]
+pub struct GcEntry {
+    pub worktree: Worktree,
+    pub reasons: Vec<GcReason>,
+    pub removed: bool,
+    /// Set when the worktree was eligible but kept (e.g. uncommitted changes).
+    pub skipped_reason: Option<String>,
+}
+
+/// Result of a GC pass. Only eligible worktrees are listed.
+#[derive(Debug, Clone)]
+pub struct GcReport {
+    pub dry_run: bool,
+    pub entries: Vec<GcEntry>,
+}
+
+impl WorktreeManager {
+    /// Report dirty files, ahead/behind counts and the last commit.
+    pub async fn status(&self, id: &str) -> Result<WorktreeStatus, WorktreeError> {
+        let wt = self.db.get_worktree(id).await?;
+        let path = PathBuf::from(&wt.path);
+        if !path.exists() {
+            return Ok(WorktreeStatus {
+                worktree: wt,
+                exists_on_disk: false,
+                dirty_files: Vec::new(),
+                compare_ref: None,
+                ahead: 0,
+                behind: 0,
+                last_commit: None,
+            });
+        }
+
+        let dirty_files = dirty_files(&path).await?;
+        let compare_ref = match upstream(&path).await {
+            Some(upstream) => Some(upstream),
+            None => self.base_ref(&wt).await,
+        };
+        let (ahead, behind) = match &compare_ref {
+            Some(r) => ahead_behind(&path, r).await?,
+            None => (0, 0),
+        };
+        let last_commit = last_commit(&path).await;
+
+        Ok(WorktreeStatus {
+            worktree: wt,
+            exists_on_disk: true,
+            dirty_files,
+            compare_ref,
+            ahead,
+            behind,
+            last_commit,
+        })
+    }
+
+    /// Fetch and integrate the base branch into a worktree.
+    ///
+    /// `base_ref` defaults to the remote's default branch (`origin/HEAD`) or,
+    /// without a remote, the branch checked out in the main repository.
+    /// Refuses to run with uncommitted changes. On conflicts the rebase or
+    /// merge is aborted, leaving the worktree as it was, and the conflicting
+    /// paths are reported. An explicit `base_ref` must name a commit and
+    /// cannot look like an option.
+    pub async fn sync(
+        &self,
+        id: &str,
+        strategy: SyncStrategy,
+        base_ref: Option<&str>,
+    ) -> Result<SyncOutcome, WorktreeError> {
+        if let Some(b) = base_ref
+            && b.starts_with('-')
+        {
+            return Err(WorktreeError::InvalidRef(format!(
+                "base ref cannot start with '-': {b}"
+            )));
+        }
+        let wt = self.db.get_worktree(id).await?;
+        let path = PathBuf::from(&wt.path);
+        if !path.exists() {
+            return Err(WorktreeError::NotFound(format!(
+                "Worktree {id} path does not exist on disk: {}",
+                path.display()
+            )));
+        }
+        if dirty_files(&path).await?.iter().any(|f| f.status != "??") {
+            return Err(WorktreeError::Git(
+                "worktree has uncommitted changes; commit or stash them first".into(),
+            ));
+        }
+
+        if has_remote(&path, "origin").await {
+            git(&path, &["fetch", "--prune", "origin"], FETCH_TIMEOUT).await?;
+        }
+
+        let base_ref = match base_ref {
+            Some(b) => b.to_string(),
+            None => self.base_ref(&wt).await.ok_or_else(|| {
+                WorktreeError::Git("cannot determine base branch; pass one explicitly".into())
+            })?,
+        };
+
+        let commit = format!("{base_ref}^{{commit}}");
+        let verify = [
+            "rev-parse",
+            "--verify",
+            "--quiet",
+            "--end-of-options",
+            &commit,
+        ];
+        if git(&path, &verify, GIT_TIMEOUT).await.is_err() {
+            return Err(WorktreeError::InvalidRef(format!(
+                "base ref does not name a commit: {base_ref}"
+            )));
+        }
+
+        let (op, abort) = match strategy {
+            SyncStrategy::Rebase => (
+                vec!["rebase", "--end-of-options", base_ref.as_str()],
+                "rebase",
+            ),
+            SyncStrategy::Merge => (
+                vec!["merge", "--no-edit", "--end-of-options", base_ref.as_str()],
+                "merge",
+            ),
+        };
+        let outcome = apply_or_abort(&path, &op, abort, base_ref.clone()).await?;
+        if outcome.success {
+            info!(id, base_ref = %outcome.base_ref, ?strategy, "Synced worktree");
+            self.db.touch_worktree(id).await?;
+        }
+        Ok(outcome)
+    }
+
+    /// Commit every change in a worktree, untracked files included.
+    ///
+    /// Returns whether a commit was made.
+    pub async fn commit_all(&self, id: &str, message: &str) -> Result<bool, WorktreeError> {
+        let wt = self.db.get_worktree(id).await?;
+        let path = PathBuf::from(&wt.path);
+        if dirty_files(&path).await?.is_empty() {
+            return Ok(false);
+        }
+        git(&path, &["add", "--all"], GIT_TIMEOUT).await?;
+        git(
+            &path,
+            &["commit", "--no-verify", "-m", message],
+            GIT_TIMEOUT,
+        )
+        .await?;
+        Ok(true)
+    }
+
+    /// Move a worktree's branch to `rev`, discarding its working tree.
+    ///
+    /// Meant for freshly created worktrees that should start from a branch
+    /// other than the repository's `HEAD`.
+    pub async fn reset_to(&self, id: &str, rev: &str) -> Result<(), WorktreeError> {
+        let wt = self.db.get_worktree(id).await?;
+        git(Path::new(&wt.path), &["reset", "--hard", rev], GIT_TIMEOUT).await?;
+        Ok(())
+    }
+
+    /// Bring the commits of `branch` into a worktree's branch.
+    ///
+    /// Refuses to run with uncommitted changes. On conflicts the merge or
+    /// cherry-pick is aborted, leaving the worktree as it was, and the
+    /// conflicting paths are reported.
+    pub async fn integrate(
+        &self,
+        id: &str,
+        branch: &str,
+        strategy: IntegrateStrategy,
+    ) -> Result<SyncOutcome, WorktreeError> {
+        let wt = self.db.get_worktree(id).await?;
+        let path = PathBuf::from(&wt.path);
+        if dirty_files(&path).await?.iter().any(|f| f.status != "??") {
+            return Err(WorktreeError::Git(
+                "worktree has uncommitted changes; commit or stash them first".into(),
+            ));
+        }
+
+        let outcome = match strategy {
+            IntegrateStrategy::Merge => {
+                let op = ["merge", "--no-ff", "--no-edit", branch];
+                apply_or_abort(&path, &op, "merge", branch.to_string()).await?
+            }
+            IntegrateStrategy::CherryPick => {
+                let range = format!("HEAD..{branch}");
+                let count = git(&path, &["rev-list", "--count", &range], GIT_TIMEOUT).await?;
+                // cherry-pick rejects an empty range
+                if count.trim() == "0" {
+                    return Ok(SyncOutcome {
+                        success: true,
+                        base_ref: branch.to_string(),
+                        conflicts: Vec::new(),
+                        message: "Nothing to cherry-pick".into(),
+                    });
+                }
+                let op = ["cherry-pick", "--allow-empty", &range];
+                apply_or_abort(&path, &op, "cherry-pick", branch.to_string()).await?
+            }
+        };
+        if outcome.success {
+            info!(id, branch, ?strategy, "Integrated branch into worktree");
+            self.db.touch_worktree(id).await?;
+        }
+        Ok(outcome)
+    }
+
+    /// Find (and unless `dry_run`, remove) merged, idle or missing worktrees.
+    ///
+    /// `max_idle` defaults to the manager's configured idle age. Worktrees
+    /// with uncommitted changes are never removed.
+    pub async fn gc(
+        &self,
+        repo_id: Option<&str>,
+        max_idle: Option<Duration>,
+        dry_run: bool,
+    ) -> Result<GcReport, WorktreeError> {
+        let max_idle = max_idle.unwrap_or(self.max_idle);
+        let cutoff =
+            unix_timestamp().saturating_sub(i64::try_from(max_idle.as_secs()).unwrap_or(i64::MAX));
+
+        let mut report = GcReport {
+            dry_run,
+            entries: Vec::new(),
+        };
+        for wt in self.db.list_worktrees(repo_id).await? {
+            let path = PathBuf::from(&wt.path);
+            let mut reasons = Vec::new();
+            if !path.exists() {
+                reasons.push(GcReason::Missing);
+            } else if self.is_merged(&wt).await {
+                reasons.push(GcReason::Merged);
+            }
+            if wt.last_active < cutoff {
+                reasons.push(GcReason::Idle);
+            }
+            if reasons.is_empty() {
+                continue;
+            }
+
+            let skipped_reason = if path.exists() && !dirty_files(&path).await?.is_empty() {
+                Some("uncommitted changes".to_string())
+            } else {
+                None
+            };
+            let removed = !dry_run && skipped_reason.is_none() && self.remove(&wt.id).await?;
+            if removed {
+                info!(id = %wt.id, name = %wt.name, ?reasons, "Garbage-collected worktree");
+            }
+            report.entries.push(GcEntry {
+                worktree: wt,
+                reasons,
+                removed,
+                skipped_reason,
+            });
+        }
+
+        if !dry_run {
+            self.prune_repos(&report).await;
+        }
+        Ok(report)
+    }
+
+    /// Default base ref for a worktree's repository.
+    async fn base_ref(&self, wt: &Worktree) -> Option<String> {
+        let repo_path = self.repo_path(wt).await?;
+        if let Ok(out) = git(
+            &repo_path,
+            &["symbolic-ref", "--short", "refs/remotes/origin/HEAD"],
+            GIT_TIMEOUT,
+        )
+        .await
+        {
+            let r = out.trim();
+            if !r.is_empty() {
+                return Some(r.to_string());
+            }
+        }
+        let head = git(
+            &repo_path,
+            &["symbolic-ref", "--short", "HEAD"],
+            GIT_TIMEOUT,
+        )
+        .await
+        .ok()?;
+        let head = head.trim();
+        (!head.is_empty() && head != wt.branch).then(|| head.to_string())
+    }
+
+    /// Whether the worktree's branch is merged into the base branch.
+    ///
+    /// A branch pointing at the same commit as the base (e.g. freshly
+    /// created) is not considered merged.
+    async fn is_merged(&self, wt: &Worktree) -> bool {
+        let Some(base) = self.base_ref(wt).await else {
+            return false;
+        };
+        let Some(repo_path) = self.repo_path(wt).await else {
+            return false;
+        };
+        let rev = |r: String| {
+            let repo_path = repo_path.clone();
+            async move {
+                git(
+                    &repo_path,
+                    &["rev-parse", "--verify", "--quiet", &r],
+                    GIT_TIMEOUT,
+                )
+                .await
+                .ok()
+                .map(|s| s.trim().to_string())
+            }
+        };
+        let (Some(branch_sha), Some(base_sha)) =
+            (rev(wt.branch.clone()).await, rev(base.clone()).await)
+        else {
+            return false;
+        };
+        if branch_sha == base_sha {
+            return false;
+        }
+        git_output(
+            &repo_path,
+            &["merge-base", "--is-ancestor", &branch_sha, &base_sha],
+            GIT_TIMEOUT,
+        )
+        .await
+        .is_ok_and(|o| o.status.success())
+    }
+
+    async fn repo_path(&self, wt: &Worktree) -> Option<PathBuf> {
+        self.db
+            .get_git_repo(&wt.repo_id)
+            .await
+            .ok()
+            .map(|r| PathBuf::from(r.repo_path))
+            .filter(|p| p.exists())
+    }
+
+    /// Run `git worktree prune` in every repo touched by a GC pass.
+    async fn prune_repos(&self, report: &GcReport) {
+        let mut repo_ids: Vec<&str> = report
+            .entries
+            .iter()
+            .filter(|e| e.removed)
+            .map(|e| e.worktree.repo_id.as_str())
+            .collect();
+        repo_ids.sort_unstable();
+        repo_ids.dedup();
+        for repo_id in repo_ids {
+            let Ok(repo) = self.db.get_git_repo(repo_id).await else {
+                continue;
+            };
+            let repo_path = PathBuf::from(&repo.repo_path);
+            if repo_path.exists()
+                && let Err(e) = git(&repo_path, &["worktree", "prune"], GIT_TIMEOUT).await
+            {
+                warn!(repo_id, error = %e, "git worktree prune failed");
+            }
+        }
+    }
+}
+
+// =============================================================================
+// git helpers
+// =============================================================================
+
+async fn git_output(
+    dir: &Path,
+    args: &[&str],
+    timeout: Duration,
+) -> Result<std::process::Output, WorktreeError> {
+    tokio::time::timeout(timeout, async {
+        let filters = filter_overrides(dir).await?;
+        git_command(dir).args(filters).args(args).output().await
+    })
+    .await
+    .map_err(|_| {
+        WorktreeError::Git(format!(
+            "git {} timed out after {}s",
+            args.first().unwrap_or(&""),
+            timeout.as_secs()
+        ))
+    })?
+    .map_err(WorktreeError::from)
+}
+
+/// `git` in `dir` with [`SAFE_GIT_CONFIG`] and no system config.
+fn git_command(dir: &Path) -> tokio::process::Command {
+    let mut cmd = tokio::process::Command::new("git");
+    cmd.args(SAFE_GIT_CONFIG)
+        .current_dir(dir)
+        .env_remove("GIT_DIR")
+        .env_remove("GIT_INDEX_FILE")
+        .env_remove("GIT_WORK_TREE")
+        .env("GIT_CONFIG_NOSYSTEM", "1")
+        // Never open an editor or prompt for credentials.
+        .env("GIT_EDITOR", "true")
+        .env("GIT_TERMINAL_PROMPT", "0");
+    cmd
+}
+
+/// `-c` overrides that blank every filter driver configured for `dir`.
+/// Attributes in a worktree choose the driver, so a sandboxed session could
+/// otherwise run one on the host through the daemon's own git commands.
+async fn filter_overrides(dir: &Path) -> std::io::Result<Vec<String>> {
+    let output = git_command(dir)
+        .args(["config", "--name-only", "--get-regexp", r"^filter\."])
+        .output()
+        .await?;
+    let mut drivers: Vec<&str> = Vec::new();
+    let names = String::from_utf8_lossy(&output.stdout);
+    for name in names.lines() {
+        if let Some(driver) = name
+            .strip_prefix("filter.")
+            .and_then(|rest| rest.rsplit_once('.'))
+            .map(|(driver, _)| driver)
+            && !drivers.contains(&driver)
+        {
+            drivers.push(driver);
+        }
+    }
+    Ok(drivers
+        .into_iter()
+        .flat_map(|driver| {
+            ["clean=", "smudge=", "process=", "required=false"]
+                .map(|setting| ["-c".to_string(), format!("filter.{driver}.{setting}")])
+        })
+        .flatten()
+        .collect())
+}
+
+/// Run a rebase, merge or cherry-pick, aborting it again on failure.
+async fn apply_or_abort(
+    path: &Path,
+    op: &[&str],
+    abort: &str,
+    base_ref: String,
+) -> Result<SyncOutcome, WorktreeError> {
+    let output = git_output(path, op, GIT_TIMEOUT).await?;
+    if output.status.success() {
+        return Ok(SyncOutcome {
+            success: true,
+            base_ref,
+            conflicts: Vec::new(),
+            message: String::from_utf8_lossy(&output.stdout).trim().to_string(),
+        });
+    }
+
+    let conflicts = git(
+        path,
+        &["diff", "--name-only", "--diff-filter=U"],
+        GIT_TIMEOUT,
+    )
+    .await
+    .map(|out| out.lines().map(str::to_string).collect())
+    .unwrap_or_default();
+    if let Err(e) = git(path, &[abort, "--abort"], GIT_TIMEOUT).await {
+        warn!(path = %path.display(), error = %e, "Failed to abort {abort}");
+    }
+    let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+    let stdout = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    Ok(SyncOutcome {
+        success: false,
+        base_ref,
+        conflicts,
+        message: if stderr.is_empty() { stdout } else { stderr },
+    })
+}
+
+/// Run git and return stdout, mapping a non-zero exit to `WorktreeError::Git`.
+async fn git(dir: &Path, args: &[&str], timeout: Duration) -> Result<String, WorktreeError> {
+    let output = git_output(dir, args, timeout).await?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(WorktreeError::Git(format!(
+            "git {} failed: {}",
+            args.first().unwrap_or(&""),
+            stderr.trim()
+        )));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+async fn dirty_files(path: &Path) -> Result<Vec<DirtyFile>, WorktreeError> {
+    let out = git(path, &["status", "--porcelain=v1"], GIT_TIMEOUT).await?;
+    Ok(out.lines().filter_map(parse_porcelain_line).collect())
+}
+
+/// Parse one `git status --porcelain=v1` line (`XY path` or `XY old -> new`).
+fn parse_porcelain_line(line: &str) -> Option<DirtyFile> {
+    let status = line.get(..2)?;
+    let path = line.get(3..)?;
+    let path = path.rsplit_once(" -> ").map_or(path, |(_, new)| new);
+    Some(DirtyFile {
+        path: path.trim_matches('"').to_string(),
+        status: status.to_string(),
+    })
+}
+
+async fn upstream(path: &Path) -> Option<String> {
+    let out = git(
+        path,
+        &[
+            "rev-parse",
+            "--abbrev-ref",
+            "--symbolic-full-name",
+            "@{upstream}",
+        ],
+        GIT_TIMEOUT,
+    )
+    .await
+    .ok()?;
+    let out = out.trim();
+    (!out.is_empty()).then(|| out.to_string())
+}
+
+async fn ahead_behind(path: &Path, compare_ref: &str) -> Result<(u32, u32), WorktreeError> {
+    let range = format!("{compare_ref}...HEAD");
+    let out = git(
+        path,
+        &["rev-list", "--left-right", "--count", &range],
+        GIT_TIMEOUT,
+    )
+    .await?;
+    Ok(parse_left_right(&out).unwrap_or_default())
+}
+
+/// Parse `git rev-list --left-right --count A...B` output into `(ahead, behind)`.
+fn parse_left_right(out: &str) -> Option<(u32, u32)> {
+    let mut parts = out.split_whitespace();
+    let behind = parts.next()?.parse().ok()?;
+    let ahead = parts.next()?.parse().ok()?;
+    Some((ahead, behind))
+}
+
+async fn last_commit(path: &Path) -> Option<CommitSummary> {
+    let format = format!("--format=%H{FIELD_SEP}%s{FIELD_SEP}%an{FIELD_SEP}%ct");
+    let out = git(path, &["log", "-1", &format], GIT_TIMEOUT).await.ok()?;
+    let mut fields = out.trim_end().split(FIELD_SEP);
+    Some(CommitSummary {
+        sha: fields.next()?.to_string(),
+        subject: fields.next()?.to_string(),
+        author: fields.next()?.to_string(),
+        committed_at: fields.next()?.parse().ok()?,
+    })
+}
+
+async fn has_remote(path: &Path, name: &str) -> bool {
+    git(path, &["remote"], GIT_TIMEOUT)
+        .await
+        .is_ok_and(|out| out.lines().any(|l| l.trim() == name))
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used, clippy::expect_used)]
+mod tests {
+    use super::*;
+    use crate::storage::Database;
+    use crate::worktree::{GitRepo, WorktreeMode};
+
+    fn run_git(dir: &Path, args: &[&str]) -> String {
+        let output = std::process::Command::new("git")
+            .args(args)
+            .current_dir(dir)
+            .env_remove("GIT_DIR")
+            .env_remove("GIT_INDEX_FILE")
+            .env_remove("GIT_WORK_TREE")
+            .output()
+            .unwrap();
+        assert!(
+            output.status.success(),
+            "git {args:?} failed: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        String::from_utf8_lossy(&output.stdout).into_owned()
+    }
+
+    fn commit_file(dir: &Path, file: &str, contents: &str, msg: &str) {
+        std::fs::write(dir.join(file), contents).unwrap();
+        run_git(dir, &["add", file]);
+        run_git(dir, &["commit", "-m", msg]);
+    }
+
+    struct Fixture {
+        mgr: WorktreeManager,
+        wt: Worktree,
+        repo_dir: tempfile::TempDir,
+        _wt_base: tempfile::TempDir,
+    }
+
+    impl Fixture {
+        fn repo(&self) -> &Path {
+            self.repo_dir.path()
+        }
+
+        fn wt_path(&self) -> PathBuf {
+            PathBuf::from(&self.wt.path)
+        }
+    }
+
+    /// A repo on `main` with one commit and a `feat` worktree branched from it.
+    async fn fixture() -> Fixture {
+        let repo_dir = tempfile::tempdir().unwrap();
+        let wt_base = tempfile::tempdir().unwrap();
+        let repo = repo_dir.path();
+        run_git(repo, &["init", "-b", "main"]);
+        run_git(repo, &["config", "user.name", "Test"]);
+        run_git(repo, &["config", "user.email", "test@example.com"]);
+        commit_file(repo, "a.txt", "base\n", "init");
+
+        let db = Database::open_in_memory().await.unwrap();
+        db.create_git_repo(
+            "r1",
+            &repo.to_string_lossy(),
+            &crate::storage::GitRepoParams {
+                name: "testrepo",
+                worktree_mode: "global",
+                local_subfolder: ".worktree",
+                custom_path: None,
+                setup_script: None,
+                auto_gitignore: true,
+            },
+        )
+        .await
+        .unwrap();
+        let git_repo = GitRepo {
+            id: "r1".into(),
+            name: "testrepo".into(),
+            repo_path: repo.to_path_buf(),
+            worktree_mode: WorktreeMode::Global,
+            local_subfolder: PathBuf::from(".worktree"),
+            setup_script: None,
+            auto_gitignore: true,
+            created_at: 0,
+            last_active: 0,
+        };
+        let mgr = WorktreeManager::new(db, wt_base.path().to_path_buf());
+        let wt = mgr.create("feat", &git_repo, "feat", None).await.unwrap();
+        Fixture {
+            mgr,
+            wt,
+            repo_dir,
+            _wt_base: wt_base,
+        }
+    }
+
+    #[test]
+    fn parse_porcelain_handles_renames_and_untracked() {
+        assert_eq!(
+            parse_porcelain_line(" M src/lib.rs"),
+            Some(DirtyFile {
+                path: "src/lib.rs".into(),
+                status: " M".into(),
+            })
+        );
+        assert_eq!(
+            parse_porcelain_line("R  old.rs -> new.rs").unwrap().path,
+            "new.rs"
+        );
+        assert_eq!(parse_porcelain_line("?? notes.md").unwrap().status, "??");
+        assert_eq!(parse_porcelain_line("M"), None);
+    }
+
+    #[test]
+    fn parse_left_right_orders_ahead_behind() {
+        assert_eq!(parse_left_right("3\t5\n"), Some((5, 3)));
+        assert_eq!(parse_left_right(""), None);
+    }
+
+    #[tokio::test]
+    async fn status_reports_dirty_files_and_ahead_count() {
+        let f = fixture().await;
+        commit_file(&f.wt_path(), "b.txt", "feature\n", "add feature");
+        std::fs::write(f.wt_path().join("scratch.txt"), "wip").unwrap();
+
+        let status = f.mgr.status(&f.wt.id).await.unwrap();
+        assert!(status.exists_on_disk);
+        assert_eq!(status.compare_ref.as_deref(), Some("main"));
+        assert_eq!((status.ahead, status.behind), (1, 0));
+        assert_eq!(status.dirty_files.len(), 1);
+        assert_eq!(status.dirty_files[0].path, "scratch.txt");
+        assert_eq!(status.last_commit.unwrap().subject, "add feature");
+    }
+
+    #[tokio::test]
+    async fn status_of_missing_directory_is_not_an_error() {
+        let f = fixture().await;
+        std::fs::remove_dir_all(f.wt_path()).unwrap();
+        let status = f.mgr.status(&f.wt.id).await.unwrap();
+        assert!(!status.exists_on_disk);
+        assert!(status.last_commit.is_none());
+    }
+
+    #[tokio::test]
+    async fn sync_merges_base_branch() {
+        let f = fixture().await;
+        commit_file(f.repo(), "main.txt", "main\n", "main change");
+        commit_file(&f.wt_path(), "b.txt", "feature\n", "feature change");
+
+        let outcome = f
+            .mgr
+            .sync(&f.wt.id, SyncStrategy::Merge, None)
+            .await
+            .unwrap();
+        assert!(outcome.success, "{}", outcome.message);
+        assert_eq!(outcome.base_ref, "main");
+        assert!(f.wt_path().join("main.txt").exists());
+        assert_eq!(f.mgr.status(&f.wt.id).await.unwrap().behind, 0);
+    }
+
+    #[tokio::test]
+    async fn sync_conflict_is_reported_and_aborted() {
+        let f = fixture().await;
+        commit_file(f.repo(), "a.txt", "main side\n", "main edit");
+        commit_file(&f.wt_path(), "a.txt", "feature side\n", "feature edit");
+        let head_before = run_git(&f.wt_path(), &["rev-parse", "HEAD"]);
+
+        let outcome = f
+            .mgr
+            .sync(&f.wt.id, SyncStrategy::Rebase, None)
+            .await
+            .unwrap();
+        assert!(!outcome.success);
+        assert_eq!(outcome.conflicts, vec!["a.txt".to_string()]);
+
+        // The rebase was aborted: same HEAD, clean tree.
+        assert_eq!(run_git(&f.wt_path(), &["rev-parse", "HEAD"]), head_before);
+        assert!(f.mgr.status(&f.wt.id).await.unwrap().dirty_files.is_empty());
+    }
+
+    /// Commit `file` on a new `side` branch of the main repository.
+    fn commit_on_side_branch(repo: &Path, file: &str, contents: &str) {
+        run_git(repo, &["checkout", "-q", "-b", "side"]);
+        commit_file(repo, file, contents, "side change");
+        run_git(repo, &["checkout", "-q", "main"]);
+    }
+
+    #[tokio::test]
+    async fn commit_all_commits_untracked_files() {
+        let f = fixture().await;
+        std::fs::write(f.wt_path().join("new.txt"), "new\n").unwrap();
+
+        assert!(f.mgr.commit_all(&f.wt.id, "step output").await.unwrap());
+        let status = f.mgr.status(&f.wt.id).await.unwrap();
+        assert!(status.dirty_files.is_empty());
+        assert_eq!(status.last_commit.unwrap().subject, "step output");
+        assert!(!f.mgr.commit_all(&f.wt.id, "again").await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn commit_all_runs_no_filter_or_signing_program() {
+        let f = fixture().await;
+        let marker = f.repo().join("ran");
+        let script = f.repo().join("planted.sh");
+        std::fs::write(
+            &script,
+            format!("#!/bin/sh\ntouch {}\ncat\n", marker.display()),
+        )
+        .unwrap();
+        std::fs::set_permissions(&script, std::os::unix::fs::PermissionsExt::from_mode(0o755))
+            .unwrap();
+        let script = script.to_string_lossy();
+        run_git(f.repo(), &["config", "filter.planted.clean", &script]);
+        run_git(f.repo(), &["config", "commit.gpgSign", "true"]);
+        run_git(f.repo(), &["config", "gpg.program", &script]);
+        std::fs::write(f.wt_path().join(".gitattributes"), "* filter=planted\n").unwrap();
+        std::fs::write(f.wt_path().join("new.txt"), "new\n").unwrap();
+
+        assert!(f.mgr.commit_all(&f.wt.id, "step output").await.unwrap());
+        assert!(!marker.exists(), "a configured program ran on the host");
+    }
+
+    #[tokio::test]
+    async fn integrate_merges_branch() {
+        let f = fixture().await;
+        commit_on_side_branch(f.repo(), "side.txt", "side\n");
+        commit_file(&f.wt_path(), "b.txt", "feature\n", "feature change");
+
+        let outcome = f
+            .mgr
+            .integrate(&f.wt.id, "side", IntegrateStrategy::Merge)
+            .await
+            .unwrap();
+        assert!(outcome.success, "{}", outcome.message);
+        assert!(f.wt_path().join("side.txt").exists());
+        assert!(f.wt_path().join("b.txt").exists());
+    }
+
+    #[tokio::test]
+    async fn integrate_cherry_pick_conflict_is_reported_and_aborted() {
+        let f = fixture().await;
+        commit_on_side_branch(f.repo(), "a.txt", "side\n");
+        commit_file(&f.wt_path(), "a.txt", "feature side\n", "feature edit");
+        let head_before = run_git(&f.wt_path(), &["rev-parse", "HEAD"]);
+
+        let outcome = f
+            .mgr
+            .integrate(&f.wt.id, "side", IntegrateStrategy::CherryPick)
+            .await
+            .unwrap();
+        assert!(!outcome.success);
+        assert_eq!(outcome.conflicts, vec!["a.txt".to_string()]);
+        assert_eq!(run_git(&f.wt_path(), &["rev-parse", "HEAD"]), head_before);
+        assert!(f.mgr.status(&f.wt.id).await.unwrap().dirty_files.is_empty());
+
+        // Nothing new on the branch is not an error
+        let outcome = f
+            .mgr
+            .integrate(&f.wt.id, "main", IntegrateStrategy::CherryPick)
+            .await
+            .unwrap();
+        assert!(outcome.success);
+    }
+
+    #[tokio::test]
+    async fn sync_refuses_uncommitted_changes() {
+        let f = fixture().await;
+        std::fs::write(f.wt_path().join("a.txt"), "edited\n").unwrap();
+        let err = f
+            .mgr
+            .sync(&f.wt.id, SyncStrategy::Rebase, None)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, WorktreeError::Git(_)));
+    }
+
+    #[tokio::test]
+    async fn sync_rejects_option_like_and_unknown_refs() {
+        let f = fixture().await;
+        for base_ref in ["--exec=touch pwned", "-Xtheirs"] {
+            let err = f
+                .mgr
+                .sync(&f.wt.id, SyncStrategy::Rebase, Some(base_ref))
+                .await
+                .unwrap_err();
+            assert!(matches!(err, WorktreeError::InvalidRef(_)), "{base_ref}");
+        }
+        assert!(!f.wt_path().join("pwned").exists());
+
+        let err = f
+            .mgr
+            .sync(&f.wt.id, SyncStrategy::Merge, Some("no-such-branch"))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, WorktreeError::InvalidRef(_)));
+
+        let outcome = f
+            .mgr
+            .sync(&f.wt.id, SyncStrategy::Merge, Some("main"))
+            .await
+            .unwrap();
+        assert!(outcome.success, "{}", outcome.message);
+    }
+
+    #[tokio::test]
+    async fn gc_ignores_fresh_unmerged_worktree() {
+        let f = fixture().await;
+        let report = f.mgr.gc(None, None, false).await.unwrap();
+        assert!(report.entries.is_empty());
+        assert!(f.wt_path().exists());
+    }
+
+    #[tokio::test]
+    async fn gc_dry_run_then_removes_merged_worktree() {
+        let f = fixture().await;
+        commit_file(&f.wt_path(), "b.txt", "feature\n", "feature");
+        run_git(f.repo(), &["merge", "--no-edit", "--no-ff", "feat"]);
+
+        let report = f.mgr.gc(None, None, true).await.unwrap();
+        assert!(report.dry_run);
+        assert_eq!(report.entries.len(), 1);
+        assert_eq!(report.entries[0].reasons, vec![GcReason::Merged]);
+        assert!(!report.entries[0].removed);
+        assert!(f.wt_path().exists());
+
+        let report = f.mgr.gc(None, None, false).await.unwrap();
+        assert!(report.entries[0].removed);
+        assert!(!f.wt_path().exists());
+        assert!(f.mgr.get(&f.wt.id).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn gc_keeps_dirty_worktree() {
+        let f = fixture().await;
+        commit_file(&f.wt_path(), "b.txt", "feature\n", "feature");
+        run_git(f.repo(), &["merge", "--no-edit", "--no-ff", "feat"]);
+        std::fs::write(f.wt_path().join("scratch.txt"), "wip").unwrap();
+
+        let report = f.mgr.gc(None, None, false).await.unwrap();
+        assert_eq!(report.entries.len(), 1);
+        assert!(!report.entries[0].removed);
+        assert_eq!(
+            report.entries[0].skipped_reason.as_deref(),
+            Some("uncommitted changes")
+        );
+        assert!(f.wt_path().exists());
+    }
+}
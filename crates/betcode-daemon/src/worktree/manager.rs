@@ -1,11 +1,15 @@
 //! Worktree manager: git worktree operations + DB persistence.
 
+use std::collections::HashSet;
 use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
 use thiserror::Error;
 use tracing::{debug, info, warn};
 
 use super::repo::{GitRepo, WorktreeMode};
+use super::setup::{PIPELINE_FILE, SetupStart, SetupStatus};
 use crate::storage::{Database, DatabaseError, Worktree};
 
 /// Errors from worktree operations.
@@ -31,6 +35,12 @@ pub enum WorktreeError {
 
     #[error("Invalid name: {0}")]
     InvalidName(String),
+
+    #[error("Invalid setup pipeline: {0}")]
+    InvalidSetup(String),
+
+    #[error("Invalid ref: {0}")]
+    InvalidRef(String),
 }
 
 /// Summary info about a worktree, combining DB record with on-disk status.
@@ -77,38 +87,84 @@ fn validate_branch(branch: &str) -> Result<(), WorktreeError> {
         .map_err(|_| WorktreeError::InvalidName(format!("invalid branch name: {branch}")))
 }
 
+/// Default idle age after which `gc` considers a worktree stale (30 days).
+pub const DEFAULT_MAX_IDLE: Duration = Duration::from_hours(30 * 24);
+
 /// Manages git worktrees and their lifecycle.
 #[derive(Clone)]
 pub struct WorktreeManager {
-    db: Database,
+    pub(super) db: Database,
     worktree_base_dir: PathBuf,
+    pub(super) max_idle: Duration,
+    /// Worktrees whose setup pipeline is currently running.
+    pub(super) running_setups: Arc<Mutex<HashSet<String>>>,
 }
 
 impl WorktreeManager {
     /// Create a new worktree manager.
-    pub const fn new(db: Database, worktree_base_dir: PathBuf) -> Self {
+    pub fn new(db: Database, worktree_base_dir: PathBuf) -> Self {
         Self {
             db,
             worktree_base_dir,
+            max_idle: DEFAULT_MAX_IDLE,
+            running_setups: Arc::new(Mutex::new(HashSet::new())),
         }
     }
 
-    /// Create a new git worktree and record it in the database.
+    /// Set the idle age after which `gc` considers a worktree stale.
+    #[must_use]
+    pub const fn with_max_idle(mut self, max_idle: Duration) -> Self {
+        self.max_idle = max_idle;
+        self
+    }
+
+    /// Create a new git worktree, record it in the database and run its
+    /// setup pipeline.
     ///
-    /// Runs `git worktree add <path> -b <branch>` in the repo directory,
-    /// then optionally runs a setup script (e.g. `npm install`).
+    /// A failed pipeline leaves the worktree in place with `setup_status`
+    /// set to `failed`, so the failing step can be retried with
+    /// [`run_setup`](Self::run_setup).
     ///
     /// # Safety
     /// The `setup_script` parameter is executed as a shell command.
     /// Callers must ensure this value comes from a trusted source
     /// (e.g. project configuration, not direct user input).
-    #[allow(clippy::too_many_lines)]
     pub async fn create(
         &self,
         name: &str,
         repo: &GitRepo,
         branch: &str,
         setup_script: Option<&str>,
+    ) -> Result<Worktree, WorktreeError> {
+        let wt = self
+            .create_deferred(name, repo, branch, setup_script)
+            .await?;
+        if wt.setup_status == SetupStatus::None.as_str() {
+            return Ok(wt);
+        }
+        let status = self.run_setup(&wt.id, SetupStart::Beginning, None).await?;
+        if status == SetupStatus::Failed {
+            warn!(id = %wt.id, "Worktree setup failed; worktree kept for retry");
+        }
+        Ok(self.db.get_worktree(&wt.id).await?)
+    }
+
+    /// Create a new git worktree and record it in the database without
+    /// running its setup pipeline.
+    ///
+    /// Runs `git worktree add <path> -b <branch>` in the repo directory and
+    /// records the pipeline (the repo's `.betcode/worktree.toml`, or a setup
+    /// script) as pending. An invalid pipeline file removes the worktree again.
+    ///
+    /// # Safety
+    /// See [`create`](Self::create).
+    #[allow(clippy::too_many_lines)]
+    pub async fn create_deferred(
+        &self,
+        name: &str,
+        repo: &GitRepo,
+        branch: &str,
+        setup_script: Option<&str>,
     ) -> Result<Worktree, WorktreeError> {
         debug!(name, repo_path = %repo.repo_path.display(), branch, ?setup_script, "create: validating inputs");
         validate_name(name)?;
@@ -214,8 +270,15 @@ impl WorktreeManager {
             "create: git worktree add completed"
         );
 
-        // Use repo's default setup script if none provided explicitly
-        let effective_script = setup_script.or(repo.setup_script.as_deref());
+        // An explicit script wins; otherwise the repo's default script
+        // applies only when the repo has no pipeline file.
+        let effective_script = setup_script.or_else(|| {
+            if repo.repo_path.join(PIPELINE_FILE).exists() {
+                None
+            } else {
+                repo.setup_script.as_deref()
+            }
+        });
 
         info!(
             name,
@@ -241,22 +304,13 @@ impl WorktreeManager {
             "create: database insert completed"
         );
 
-        // Run setup script if provided; clean up on failure
-        if let Some(script) = effective_script {
-            debug!(script, "create: running setup script");
-            let script_start = std::time::Instant::now();
-            if let Err(e) = self.run_setup_script(&worktree_path, script).await {
-                warn!(id = %id, error = %e, elapsed_ms = script_start.elapsed().as_millis(), "Setup script failed, cleaning up worktree");
-                let _ = self.remove(&id).await;
-                return Err(e);
-            }
-            debug!(
-                elapsed_ms = script_start.elapsed().as_millis(),
-                "create: setup script completed"
-            );
+        if let Err(e) = self.init_setup(&wt).await {
+            warn!(id = %id, error = %e, "Invalid setup pipeline, cleaning up worktree");
+            let _ = self.remove(&id).await;
+            return Err(e);
         }
 
-        Ok(wt)
+        Ok(self.db.get_worktree(&id).await?)
     }
 
     /// Remove a git worktree and its database record.
@@ -352,32 +406,6 @@ impl WorktreeManager {
         self.db.touch_worktree(id).await?;
         Ok(path)
     }
-
-    /// Run a setup script in a worktree directory.
-    async fn run_setup_script(&self, path: &Path, script: &str) -> Result<(), WorktreeError> {
-        info!(path = %path.display(), script, "Running worktree setup script");
-
-        let shell = if cfg!(windows) { "cmd" } else { "sh" };
-        let flag = if cfg!(windows) { "/C" } else { "-c" };
-
-        let output = tokio::process::Command::new(shell)
-            .args([flag, script])
-            .current_dir(path)
-            .output()
-            .await?;
-
-        if !output.status.success() {
-            let stderr = String::from_utf8_lossy(&output.stderr);
-            return Err(WorktreeError::SetupFailed(format!(
-                "Setup script '{}' failed: {}",
-                script,
-                stderr.trim()
-            )));
-        }
-
-        info!(path = %path.display(), "Setup script completed");
-        Ok(())
-    }
 }
 
 #[cfg(test)]
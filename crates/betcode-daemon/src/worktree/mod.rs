@@ -3,8 +3,15 @@
 //! Manages git worktrees and their association with Claude sessions.
 //! Each worktree gets its own working directory and can have sessions bound to it.
 
+mod lifecycle;
 mod manager;
 pub mod repo;
+pub mod setup;
 
-pub use manager::{WorktreeError, WorktreeInfo, WorktreeManager};
+pub use lifecycle::{
+    CommitSummary, DirtyFile, GcEntry, GcReason, GcReport, IntegrateStrategy, SyncOutcome,
+    SyncStrategy, WorktreeStatus,
+};
+pub use manager::{DEFAULT_MAX_IDLE, WorktreeError, WorktreeInfo, WorktreeManager};
 pub use repo::{GitRepo, WorktreeMode};
+pub use setup::{SetupEvent, SetupPipeline, SetupStart, SetupStatus, StepResult, StepStatus};
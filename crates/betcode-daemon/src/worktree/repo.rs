@@ -179,6 +179,7 @@ mod tests {
             auto_gitignore: 1,
             created_at: 100,
             last_active: 200,
+            sandbox: None,
         };
         let repo = GitRepo::from(row);
         assert_eq!(repo.worktree_mode, WorktreeMode::Global);
@@ -198,6 +199,7 @@ mod tests {
             auto_gitignore: 0,
             created_at: 100,
             last_active: 200,
+            sandbox: None,
         };
         let repo = GitRepo::from(row);
         assert_eq!(
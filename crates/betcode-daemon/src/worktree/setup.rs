@@ -0,0 +1,1133 @@
+//! Declarative worktree setup pipelines.
+//!
+//! A repository describes how to prepare a fresh worktree in
+//! `.betcode/worktree.toml` at the root of its main checkout:
+//!
+//! ```toml
+//! timeout_secs = 600            # default per-step timeout
+//!
+//! [env]                         # applied to every `run` step
+//! RUST_LOG = "info"
+//!
+//! [[steps]]
+//! copy = ".env"                 # from the main checkout; `to` defaults to the same path
+//! optional = true               # skip instead of failing when the source is missing
+//!
+//! [[steps]]
+//! link = "node_modules"         # symlink a dependency cache from the main checkout
+//!
+//! [[steps]]
+//! name = "install"
+//! run = "npm ci"
+//! timeout_secs = 900
+//! env = { CI = "1" }
+//! ```
+//!
+//! Without a pipeline file the repository's `setup_script` runs as a single
+//! `setup` step. Per-step results are stored on the worktree row, so a failed
+//! pipeline can be resumed from the failing step.
+
+use std::collections::{BTreeMap, HashSet, VecDeque};
+use std::path::{Component, Path, PathBuf};
+use std::process::Stdio;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::sync::mpsc;
+use tracing::{debug, info, warn};
+
+use super::manager::{WorktreeError, WorktreeManager};
+use crate::storage::Worktree;
+
+/// Pipeline file, relative to the main checkout.
+pub const PIPELINE_FILE: &str = ".betcode/worktree.toml";
+
+/// Per-step timeout when neither the step nor the pipeline sets one.
+pub const DEFAULT_STEP_TIMEOUT: Duration = Duration::from_mins(10);
+
+/// Output lines kept per step for the error message of a failed step.
+const OUTPUT_TAIL_LINES: usize = 20;
+
+/// Longest default name derived from a `run` command.
+const MAX_DERIVED_NAME_LEN: usize = 40;
+
+// =============================================================================
+// Pipeline definition
+// =============================================================================
+
+/// What a setup step does.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StepAction {
+    /// Run a shell command in the worktree.
+    Run(String),
+    /// Copy a file or directory from the main checkout into the worktree.
+    Copy { from: PathBuf, to: PathBuf },
+    /// Symlink a path in the worktree to one in the main checkout.
+    Link { from: PathBuf, to: PathBuf },
+}
+
+/// A single pipeline step.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SetupStep {
+    pub name: String,
+    pub action: StepAction,
+    pub env: BTreeMap<String, String>,
+    pub timeout: Duration,
+    /// A missing copy/link source skips the step; a failing command does not
+    /// fail the pipeline.
+    pub optional: bool,
+}
+
+/// An ordered list of setup steps.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SetupPipeline {
+    pub env: BTreeMap<String, String>,
+    pub steps: Vec<SetupStep>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawPipeline {
+    #[serde(default)]
+    timeout_secs: Option<u64>,
+    #[serde(default)]
+    env: BTreeMap<String, String>,
+    #[serde(default)]
+    steps: Vec<RawStep>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawStep {
+    #[serde(default)]
+    name: Option<String>,
+    #[serde(default)]
+    run: Option<String>,
+    #[serde(default)]
+    copy: Option<String>,
+    #[serde(default)]
+    link: Option<String>,
+    #[serde(default)]
+    to: Option<String>,
+    #[serde(default)]
+    optional: bool,
+    #[serde(default)]
+    env: BTreeMap<String, String>,
+    #[serde(default)]
+    timeout_secs: Option<u64>,
+}
+
+impl SetupPipeline {
+    /// Parse and validate a pipeline file.
+    pub fn parse(toml_src: &str) -> Result<Self, WorktreeError> {
+        let raw: RawPipeline = toml::from_str(toml_src)
+            .map_err(|e| WorktreeError::InvalidSetup(format!("{PIPELINE_FILE}: {e}")))?;
+        let default_timeout = raw
+            .timeout_secs
+            .map_or(DEFAULT_STEP_TIMEOUT, Duration::from_secs);
+
+        let mut names = HashSet::new();
+        let mut steps = Vec::with_capacity(raw.steps.len());
+        for (i, step) in raw.steps.into_iter().enumerate() {
+            let step = step.into_step(i + 1, default_timeout)?;
+            if !names.insert(step.name.clone()) {
+                return Err(WorktreeError::InvalidSetup(format!(
+                    "duplicate step name \"{}\"; set `name` to disambiguate",
+                    step.name
+                )));
+            }
+            steps.push(step);
+        }
+        Ok(Self {
+            env: raw.env,
+            steps,
+        })
+    }
+
+    /// A one-step pipeline running a legacy setup script.
+    pub fn from_script(script: &str) -> Self {
+        Self {
+            env: BTreeMap::new(),
+            steps: vec![SetupStep {
+                name: "setup".into(),
+                action: StepAction::Run(script.to_string()),
+                env: BTreeMap::new(),
+                timeout: DEFAULT_STEP_TIMEOUT,
+                optional: false,
+            }],
+        }
+    }
+
+    /// Load the pipeline for a worktree.
+    ///
+    /// A setup script recorded on the worktree (explicitly requested, or the
+    /// repository default when no pipeline file exists) takes precedence over
+    /// the pipeline file.
+    pub async fn load(
+        repo_path: &Path,
+        script: Option<&str>,
+    ) -> Result<Option<Self>, WorktreeError> {
+        if let Some(script) = script {
+            return Ok(Some(Self::from_script(script)));
+        }
+        let file = repo_path.join(PIPELINE_FILE);
+        match tokio::fs::read_to_string(&file).await {
+            Ok(src) => Self::parse(&src).map(Some),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+impl RawStep {
+    fn into_step(
+        self,
+        index: usize,
+        default_timeout: Duration,
+    ) -> Result<SetupStep, WorktreeError> {
+        let err = |msg: String| WorktreeError::InvalidSetup(format!("step {index}: {msg}"));
+        let action = match (self.run, self.copy, self.link) {
+            (Some(run), None, None) => {
+                if self.to.is_some() {
+                    return Err(err("`to` only applies to copy and link steps".into()));
+                }
+                StepAction::Run(run)
+            }
+            (None, Some(from), None) => {
+                let (from, to) = step_paths(&from, self.to.as_deref()).map_err(err)?;
+                StepAction::Copy { from, to }
+            }
+            (None, None, Some(from)) => {
+                let (from, to) = step_paths(&from, self.to.as_deref()).map_err(err)?;
+                StepAction::Link { from, to }
+            }
+            _ => {
+                return Err(err(
+                    "exactly one of `run`, `copy` or `link` is required".into()
+                ));
+            }
+        };
+        let name = self
+            .name
+            .filter(|n| !n.trim().is_empty())
+            .unwrap_or_else(|| default_name(&action));
+        Ok(SetupStep {
+            name,
+            action,
+            env: self.env,
+            timeout: self
+                .timeout_secs
+                .map_or(default_timeout, Duration::from_secs),
+            optional: self.optional,
+        })
+    }
+}
+
+fn step_paths(from: &str, to: Option<&str>) -> Result<(PathBuf, PathBuf), String> {
+    let from = relative_path(from)?;
+    let to = to.map_or_else(|| Ok(from.clone()), relative_path)?;
+    Ok((from, to))
+}
+
+/// Validate a path that must stay inside the checkout it is joined to.
+fn relative_path(p: &str) -> Result<PathBuf, String> {
+    let path = PathBuf::from(p);
+    let ok = !p.is_empty()
+        && path
+            .components()
+            .all(|c| matches!(c, Component::Normal(_) | Component::CurDir));
+    if ok {
+        Ok(path)
+    } else {
+        Err(format!(
+            "path \"{p}\" must be relative and must not contain '..'"
+        ))
+    }
+}
+
+fn default_name(action: &StepAction) -> String {
+    match action {
+        StepAction::Run(cmd) => {
+            let first = cmd.lines().next().unwrap_or_default().trim();
+            first.chars().take(MAX_DERIVED_NAME_LEN).collect()
+        }
+        StepAction::Copy { from, .. } => format!("copy {}", from.display()),
+        StepAction::Link { from, .. } => format!("link {}", from.display()),
+    }
+}
+
+// =============================================================================
+// Run state
+// =============================================================================
+
+/// Overall setup state of a worktree, stored in `worktrees.setup_status`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SetupStatus {
+    /// No pipeline configured.
+    None,
+    /// Pipeline recorded but not started (deferred setup).
+    Pending,
+    Running,
+    Succeeded,
+    Failed,
+}
+
+impl SetupStatus {
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::None => "none",
+            Self::Pending => "pending",
+            Self::Running => "running",
+            Self::Succeeded => "succeeded",
+            Self::Failed => "failed",
+        }
+    }
+
+    pub fn from_db(s: &str) -> Self {
+        match s {
+            "pending" => Self::Pending,
+            "running" => Self::Running,
+            "succeeded" => Self::Succeeded,
+            "failed" => Self::Failed,
+            _ => Self::None,
+        }
+    }
+}
+
+/// Outcome of a single step.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StepStatus {
+    Pending,
+    Running,
+    Succeeded,
+    Failed,
+    Skipped,
+}
+
+impl StepStatus {
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Pending => "pending",
+            Self::Running => "running",
+            Self::Succeeded => "succeeded",
+            Self::Failed => "failed",
+            Self::Skipped => "skipped",
+        }
+    }
+
+    const fn is_done(self) -> bool {
+        matches!(self, Self::Succeeded | Self::Skipped)
+    }
+}
+
+/// Recorded result of a single step, stored as JSON in `worktrees.setup_steps`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StepResult {
+    pub name: String,
+    pub status: StepStatus,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exit_code: Option<i32>,
+    #[serde(default)]
+    pub duration_ms: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl StepResult {
+    fn pending(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            status: StepStatus::Pending,
+            exit_code: None,
+            duration_ms: 0,
+            error: None,
+        }
+    }
+}
+
+/// Parse the step results stored on a worktree row. Malformed JSON yields none.
+pub fn stored_steps(wt: &Worktree) -> Vec<StepResult> {
+    wt.setup_steps
+        .as_deref()
+        .and_then(|json| serde_json::from_str(json).ok())
+        .unwrap_or_default()
+}
+
+/// Where `run_setup` starts.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SetupStart {
+    /// Run every step.
+    Beginning,
+    /// Skip steps that already succeeded; start at the first one that did not.
+    Resume,
+    /// Re-run from the named step onwards.
+    Step(String),
+}
+
+/// Progress events emitted while a pipeline runs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SetupEvent {
+    StepStarted {
+        /// 0-based position in the pipeline.
+        index: usize,
+        total: usize,
+        name: String,
+    },
+    Output {
+        step: String,
+        stderr: bool,
+        line: String,
+    },
+    StepFinished(StepResult),
+    Finished(SetupStatus),
+}
+
+/// Paths and metadata a step runs against.
+struct StepContext<'a> {
+    worktree: &'a Path,
+    repo: &'a Path,
+    branch: &'a str,
+    env: &'a BTreeMap<String, String>,
+    events: Option<&'a mpsc::Sender<SetupEvent>>,
+}
+
+impl StepContext<'_> {
+    async fn emit(&self, event: SetupEvent) {
+        // A client that stopped listening must not abort the setup.
+        if let Some(tx) = self.events {
+            let _ = tx.send(event).await;
+        }
+    }
+}
+
+/// Removes a worktree from the running-setup set when dropped.
+struct RunningGuard<'a> {
+    manager: &'a WorktreeManager,
+    id: String,
+}
+
+impl Drop for RunningGuard<'_> {
+    fn drop(&mut self) {
+        if let Ok(mut running) = self.manager.running_setups.lock() {
+            running.remove(&self.id);
+        }
+    }
+}
+
+impl WorktreeManager {
+    /// Record the worktree's pipeline as pending, without running it.
+    ///
+    /// Fails if the pipeline file is invalid.
+    pub(super) async fn init_setup(&self, wt: &Worktree) -> Result<(), WorktreeError> {
+        let repo = self.db.get_git_repo(&wt.repo_id).await?;
+        let Some(pipeline) =
+            SetupPipeline::load(Path::new(&repo.repo_path), wt.setup_script.as_deref()).await?
+        else {
+            return Ok(());
+        };
+        let steps: Vec<StepResult> = pipeline
+            .steps
+            .iter()
+            .map(|s| StepResult::pending(&s.name))
+            .collect();
+        self.save_setup(&wt.id, SetupStatus::Pending, &steps).await
+    }
+
+    /// Run (or resume) a worktree's setup pipeline.
+    ///
+    /// Progress is recorded on the worktree row after every step and, when
+    /// `events` is given, streamed to the caller. A failed pipeline leaves the
+    /// worktree in place so it can be retried with [`SetupStart::Resume`].
+    pub async fn run_setup(
+        &self,
+        id: &str,
+        start: SetupStart,
+        events: Option<mpsc::Sender<SetupEvent>>,
+    ) -> Result<SetupStatus, WorktreeError> {
+        let wt = self.db.get_worktree(id).await?;
+        let worktree_path = PathBuf::from(&wt.path);
+        if !worktree_path.exists() {
+            return Err(WorktreeError::NotFound(format!(
+                "Worktree {id} path does not exist on disk: {}",
+                worktree_path.display()
+            )));
+        }
+        let repo = self.db.get_git_repo(&wt.repo_id).await?;
+        let repo_path = PathBuf::from(&repo.repo_path);
+
+        let Some(pipeline) = SetupPipeline::load(&repo_path, wt.setup_script.as_deref()).await?
+        else {
+            self.save_setup(id, SetupStatus::None, &[]).await?;
+            if let Some(tx) = &events {
+                let _ = tx.send(SetupEvent::Finished(SetupStatus::None)).await;
+            }
+            return Ok(SetupStatus::None);
+        };
+
+        let _guard = self.claim_setup(id)?;
+        let mut results = initial_results(&pipeline, &stored_steps(&wt), &start)?;
+        let first = results
+            .iter()
+            .position(|r| r.status == StepStatus::Pending)
+            .unwrap_or(results.len());
+
+        let ctx = StepContext {
+            worktree: &worktree_path,
+            repo: &repo_path,
+            branch: &wt.branch,
+            env: &pipeline.env,
+            events: events.as_ref(),
+        };
+        info!(
+            id,
+            steps = pipeline.steps.len(),
+            first,
+            "Running worktree setup"
+        );
+        self.save_setup(id, SetupStatus::Running, &results).await?;
+
+        let mut status = SetupStatus::Succeeded;
+        for (index, step) in pipeline.steps.iter().enumerate().skip(first) {
+            ctx.emit(SetupEvent::StepStarted {
+                index,
+                total: pipeline.steps.len(),
+                name: step.name.clone(),
+            })
+            .await;
+            results[index].status = StepStatus::Running;
+            self.save_setup(id, SetupStatus::Running, &results).await?;
+
+            let result = run_step(&ctx, step).await;
+            let failed = result.status == StepStatus::Failed && !step.optional;
+            results[index] = result.clone();
+            ctx.emit(SetupEvent::StepFinished(result)).await;
+            if failed {
+                status = SetupStatus::Failed;
+                break;
+            }
+        }
+
+        self.save_setup(id, status, &results).await?;
+        ctx.emit(SetupEvent::Finished(status)).await;
+        info!(id, status = status.as_str(), "Worktree setup finished");
+        Ok(status)
+    }
+
+    fn claim_setup(&self, id: &str) -> Result<RunningGuard<'_>, WorktreeError> {
+        let inserted = self
+            .running_setups
+            .lock()
+            .map_err(|_| WorktreeError::SetupFailed("setup registry poisoned".into()))?
+            .insert(id.to_string());
+        if !inserted {
+            return Err(WorktreeError::SetupFailed(format!(
+                "setup is already running for worktree {id}"
+            )));
+        }
+        Ok(RunningGuard {
+            manager: self,
+            id: id.to_string(),
+        })
+    }
+
+    async fn save_setup(
+        &self,
+        id: &str,
+        status: SetupStatus,
+        steps: &[StepResult],
+    ) -> Result<(), WorktreeError> {
+        let json = if steps.is_empty() {
+            None
+        } else {
+            Some(
+                serde_json::to_string(steps)
+                    .map_err(|e| WorktreeError::SetupFailed(e.to_string()))?,
+            )
+        };
+        self.db
+            .update_worktree_setup(id, status.as_str(), json.as_deref())
+            .await?;
+        Ok(())
+    }
+}
+
+/// Step results before a run: steps before the start point keep their
+/// previous outcome, the rest are reset to pending.
+fn initial_results(
+    pipeline: &SetupPipeline,
+    previous: &[StepResult],
+    start: &SetupStart,
+) -> Result<Vec<StepResult>, WorktreeError> {
+    let previous_of = |name: &str| previous.iter().find(|r| r.name == name);
+    let first = match start {
+        SetupStart::Beginning => 0,
+        SetupStart::Resume => pipeline
+            .steps
+            .iter()
+            .position(|s| !previous_of(&s.name).is_some_and(|r| r.status.is_done()))
+            .unwrap_or(pipeline.steps.len()),
+        SetupStart::Step(name) => pipeline
+            .steps
+            .iter()
+            .position(|s| &s.name == name)
+            .ok_or_else(|| WorktreeError::InvalidSetup(format!("unknown setup step \"{name}\"")))?,
+    };
+    Ok(pipeline
+        .steps
+        .iter()
+        .enumerate()
+        .map(|(i, s)| match previous_of(&s.name) {
+            Some(prev) if i < first => prev.clone(),
+            _ => StepResult::pending(&s.name),
+        })
+        .collect())
+}
+
+// =============================================================================
+// Step execution
+// =============================================================================
+
+async fn run_step(ctx: &StepContext<'_>, step: &SetupStep) -> StepResult {
+    let started = Instant::now();
+    let outcome = match &step.action {
+        StepAction::Run(script) => run_command(ctx, step, script).await,
+        StepAction::Copy { from, to } => copy_path(ctx, step, from, to).await,
+        StepAction::Link { from, to } => link_path(ctx, step, from, to).await,
+    };
+    let duration_ms = u64::try_from(started.elapsed().as_millis()).unwrap_or(u64::MAX);
+    let (status, exit_code, error) = match outcome {
+        Ok(StepOutcome::Done(code)) => (StepStatus::Succeeded, code, None),
+        Ok(StepOutcome::Skipped(reason)) => (StepStatus::Skipped, None, Some(reason)),
+        Err((code, error)) => {
+            warn!(step = %step.name, error = %error, "Worktree setup step failed");
+            (StepStatus::Failed, code, Some(error))
+        }
+    };
+    StepResult {
+        name: step.name.clone(),
+        status,
+        exit_code,
+        duration_ms,
+        error,
+    }
+}
+
+enum StepOutcome {
+    /// Completed, with the exit code for commands.
+    Done(Option<i32>),
+    Skipped(String),
+}
+
+/// Failure with the command's exit code, if it ran to completion.
+type StepFailure = (Option<i32>, String);
+
+async fn run_command(
+    ctx: &StepContext<'_>,
+    step: &SetupStep,
+    script: &str,
+) -> Result<StepOutcome, StepFailure> {
+    let shell = if cfg!(windows) { "cmd" } else { "sh" };
+    let flag = if cfg!(windows) { "/C" } else { "-c" };
+    debug!(step = %step.name, script, "Running setup command");
+
+    let mut child = tokio::process::Command::new(shell)
+        .args([flag, script])
+        .current_dir(ctx.worktree)
+        .envs(ctx.env)
+        .envs(&step.env)
+        .env("BETCODE_WORKTREE_PATH", ctx.worktree)
+        .env("BETCODE_REPO_PATH", ctx.repo)
+        .env("BETCODE_WORKTREE_BRANCH", ctx.branch)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(|e| (None, format!("failed to start `{script}`: {e}")))?;
+    let (Some(stdout), Some(stderr)) = (child.stdout.take(), child.stderr.take()) else {
+        return Err((None, "failed to capture command output".into()));
+    };
+    let mut stdout = BufReader::new(stdout).lines();
+    let mut stderr = BufReader::new(stderr).lines();
+    let mut tail = VecDeque::with_capacity(OUTPUT_TAIL_LINES);
+
+    let run = async {
+        let (mut out_done, mut err_done) = (false, false);
+        while !(out_done && err_done) {
+            tokio::select! {
+                line = stdout.next_line(), if !out_done => match line {
+                    Ok(Some(line)) => record_line(ctx, step, &mut tail, false, line).await,
+                    _ => out_done = true,
+                },
+                line = stderr.next_line(), if !err_done => match line {
+                    Ok(Some(line)) => record_line(ctx, step, &mut tail, true, line).await,
+                    _ => err_done = true,
+                },
+            }
+        }
+        child.wait().await
+    };
+    let waited = tokio::time::timeout(step.timeout, run).await;
+
+    let status = match waited {
+        Ok(Ok(status)) => status,
+        Ok(Err(e)) => return Err((None, format!("failed to wait for `{script}`: {e}"))),
+        Err(_) => {
+            let _ = child.kill().await;
+            return Err((None, format!("timed out after {}s", step.timeout.as_secs())));
+        }
+    };
+    if status.success() {
+        return Ok(StepOutcome::Done(status.code()));
+    }
+    let output = Vec::from(tail).join("\n");
+    let message = status.code().map_or_else(
+        || format!("`{script}` was terminated by a signal"),
+        |code| format!("`{script}` exited with code {code}"),
+    );
+    Err((
+        status.code(),
+        if output.is_empty() {
+            message
+        } else {
+            format!("{message}:\n{output}")
+        },
+    ))
+}
+
+async fn record_line(
+    ctx: &StepContext<'_>,
+    step: &SetupStep,
+    tail: &mut VecDeque<String>,
+    stderr: bool,
+    line: String,
+) {
+    if tail.len() == OUTPUT_TAIL_LINES {
+        tail.pop_front();
+    }
+    tail.push_back(line.clone());
+    ctx.emit(SetupEvent::Output {
+        step: step.name.clone(),
+        stderr,
+        line,
+    })
+    .await;
+}
+
+/// Resolve a step's source in the main checkout, or skip an optional step
+/// whose source is missing.
+fn source_path(
+    ctx: &StepContext<'_>,
+    step: &SetupStep,
+    from: &Path,
+) -> Result<Result<PathBuf, StepOutcome>, StepFailure> {
+    let src = ctx.repo.join(from);
+    if src.exists() {
+        Ok(Ok(src))
+    } else if step.optional {
+        Ok(Err(StepOutcome::Skipped(format!(
+            "{} not found in the main checkout",
+            from.display()
+        ))))
+    } else {
+        Err((
+            None,
+            format!("{} not found in the main checkout", from.display()),
+        ))
+    }
+}
+
+async fn copy_path(
+    ctx: &StepContext<'_>,
+    step: &SetupStep,
+    from: &Path,
+    to: &Path,
+) -> Result<StepOutcome, StepFailure> {
+    let src = match source_path(ctx, step, from)? {
+        Ok(src) => src,
+        Err(skipped) => return Ok(skipped),
+    };
+    let dst = ctx.worktree.join(to);
+    let io_err = |e: std::io::Error| (None, format!("copy {} failed: {e}", from.display()));
+    if let Some(parent) = dst.parent() {
+        tokio::fs::create_dir_all(parent).await.map_err(io_err)?;
+    }
+    if tokio::fs::metadata(&src).await.map_err(io_err)?.is_dir() {
+        copy_dir(&src, &dst).await.map_err(io_err)?;
+    } else {
+        tokio::fs::copy(&src, &dst).await.map_err(io_err)?;
+    }
+    Ok(StepOutcome::Done(None))
+}
+
+/// Recursively copy a directory, merging into an existing destination.
+async fn copy_dir(src: &Path, dst: &Path) -> std::io::Result<()> {
+    let mut pending = vec![(src.to_path_buf(), dst.to_path_buf())];
+    while let Some((src, dst)) = pending.pop() {
+        tokio::fs::create_dir_all(&dst).await?;
+        let mut entries = tokio::fs::read_dir(&src).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let target = dst.join(entry.file_name());
+            if entry.file_type().await?.is_dir() {
+                pending.push((entry.path(), target));
+            } else {
+                tokio::fs::copy(entry.path(), target).await?;
+            }
+        }
+    }
+    Ok(())
+}
+
+async fn link_path(
+    ctx: &StepContext<'_>,
+    step: &SetupStep,
+    from: &Path,
+    to: &Path,
+) -> Result<StepOutcome, StepFailure> {
+    let src = match source_path(ctx, step, from)? {
+        Ok(src) => src,
+        Err(skipped) => return Ok(skipped),
+    };
+    let dst = ctx.worktree.join(to);
+    let io_err = |e: std::io::Error| (None, format!("link {} failed: {e}", from.display()));
+
+    if let Ok(existing) = tokio::fs::read_link(&dst).await {
+        if existing == src {
+            return Ok(StepOutcome::Done(None));
+        }
+        return Err((
+            None,
+            format!(
+                "{} is already a symlink to {}",
+                to.display(),
+                existing.display()
+            ),
+        ));
+    }
+    if tokio::fs::symlink_metadata(&dst).await.is_ok() {
+        return Err((
+            None,
+            format!("{} already exists in the worktree", to.display()),
+        ));
+    }
+    if let Some(parent) = dst.parent() {
+        tokio::fs::create_dir_all(parent).await.map_err(io_err)?;
+    }
+    symlink(&src, &dst).await.map_err(io_err)?;
+    Ok(StepOutcome::Done(None))
+}
+
+#[cfg(unix)]
+async fn symlink(src: &Path, dst: &Path) -> std::io::Result<()> {
+    tokio::fs::symlink(src, dst).await
+}
+
+#[cfg(windows)]
+async fn symlink(src: &Path, dst: &Path) -> std::io::Result<()> {
+    if tokio::fs::metadata(src).await?.is_dir() {
+        tokio::fs::symlink_dir(src, dst).await
+    } else {
+        tokio::fs::symlink_file(src, dst).await
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used, clippy::expect_used)]
+mod tests {
+    use super::*;
+    use crate::storage::{Database, GitRepoParams};
+    use crate::worktree::{GitRepo, WorktreeMode};
+
+    fn run_git(dir: &Path, args: &[&str]) {
+        let output = std::process::Command::new("git")
+            .args(args)
+            .current_dir(dir)
+            .env_remove("GIT_DIR")
+            .env_remove("GIT_INDEX_FILE")
+            .env_remove("GIT_WORK_TREE")
+            .output()
+            .unwrap();
+        assert!(
+            output.status.success(),
+            "git {args:?} failed: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+
+    struct Fixture {
+        mgr: WorktreeManager,
+        repo: GitRepo,
+        _repo_dir: tempfile::TempDir,
+        _wt_base: tempfile::TempDir,
+    }
+
+    /// A repo on `main` with one commit and the given pipeline file.
+    async fn fixture(pipeline: &str) -> Fixture {
+        let repo_dir = tempfile::tempdir().unwrap();
+        let wt_base = tempfile::tempdir().unwrap();
+        let path = repo_dir.path();
+        run_git(path, &["init", "-b", "main"]);
+        run_git(path, &["config", "user.name", "Test"]);
+        run_git(path, &["config", "user.email", "test@example.com"]);
+        std::fs::write(path.join("README.md"), "hi\n").unwrap();
+        run_git(path, &["add", "README.md"]);
+        run_git(path, &["commit", "-m", "init"]);
+        std::fs::create_dir_all(path.join(".betcode")).unwrap();
+        std::fs::write(path.join(PIPELINE_FILE), pipeline).unwrap();
+
+        let db = Database::open_in_memory().await.unwrap();
+        db.create_git_repo(
+            "r1",
+            &path.to_string_lossy(),
+            &GitRepoParams {
+                name: "testrepo",
+                worktree_mode: "global",
+                local_subfolder: ".worktree",
+                custom_path: None,
+                setup_script: None,
+                auto_gitignore: true,
+            },
+        )
+        .await
+        .unwrap();
+        let repo = GitRepo {
+            id: "r1".into(),
+            name: "testrepo".into(),
+            repo_path: path.to_path_buf(),
+            worktree_mode: WorktreeMode::Global,
+            local_subfolder: PathBuf::from(".worktree"),
+            setup_script: None,
+            auto_gitignore: true,
+            created_at: 0,
+            last_active: 0,
+        };
+        Fixture {
+            mgr: WorktreeManager::new(db, wt_base.path().to_path_buf()),
+            repo,
+            _repo_dir: repo_dir,
+            _wt_base: wt_base,
+        }
+    }
+
+    fn statuses(wt: &Worktree) -> Vec<(String, StepStatus)> {
+        stored_steps(wt)
+            .into_iter()
+            .map(|r| (r.name, r.status))
+            .collect()
+    }
+
+    #[test]
+    fn parse_pipeline_with_defaults() {
+        let p = SetupPipeline::parse(
+            r#"
+            timeout_secs = 30
+            [env]
+            A = "1"
+
+            [[steps]]
+            copy = ".env"
+            optional = true
+
+            [[steps]]
+            link = "node_modules"
+            to = "deps/node_modules"
+
+            [[steps]]
+            name = "install"
+            run = "npm ci"
+            timeout_secs = 5
+            "#,
+        )
+        .unwrap();
+        assert_eq!(p.env.get("A").map(String::as_str), Some("1"));
+        assert_eq!(p.steps.len(), 3);
+        assert_eq!(p.steps[0].name, "copy .env");
+        assert!(p.steps[0].optional);
+        assert_eq!(p.steps[0].timeout, Duration::from_secs(30));
+        assert_eq!(
+            p.steps[1].action,
+            StepAction::Link {
+                from: PathBuf::from("node_modules"),
+                to: PathBuf::from("deps/node_modules"),
+            }
+        );
+        assert_eq!(p.steps[2].name, "install");
+        assert_eq!(p.steps[2].timeout, Duration::from_secs(5));
+    }
+
+    #[test]
+    fn parse_rejects_invalid_pipelines() {
+        let invalid = [
+            "[[steps]]\ncopy = \"../secret\"",
+            "[[steps]]\ncopy = \"/etc/passwd\"",
+            "[[steps]]\nrun = \"a\"\ncopy = \"b\"",
+            "[[steps]]\nname = \"x\"",
+            "[[steps]]\nrun = \"a\"\nto = \"b\"",
+            "[[steps]]\nrun = \"make\"\n[[steps]]\nrun = \"make\"",
+            "[[steps]]\nrun = \"a\"\nunknown = 1",
+        ];
+        for src in invalid {
+            assert!(
+                matches!(
+                    SetupPipeline::parse(src),
+                    Err(WorktreeError::InvalidSetup(_))
+                ),
+                "expected {src:?} to be rejected"
+            );
+        }
+    }
+
+    #[test]
+    fn initial_results_resume_starts_at_first_unfinished_step() {
+        let pipeline = SetupPipeline::parse(
+            "[[steps]]\nrun = \"a\"\n[[steps]]\nrun = \"b\"\n[[steps]]\nrun = \"c\"",
+        )
+        .unwrap();
+        let mut previous: Vec<StepResult> = ["a", "b", "c"]
+            .iter()
+            .map(|n| StepResult::pending(n))
+            .collect();
+        previous[0].status = StepStatus::Succeeded;
+        previous[1].status = StepStatus::Failed;
+
+        let resumed = initial_results(&pipeline, &previous, &SetupStart::Resume).unwrap();
+        assert_eq!(resumed[0].status, StepStatus::Succeeded);
+        assert_eq!(resumed[1].status, StepStatus::Pending);
+
+        let from_c = initial_results(&pipeline, &previous, &SetupStart::Step("c".into())).unwrap();
+        assert_eq!(from_c[1].status, StepStatus::Failed);
+        assert_eq!(from_c[2].status, StepStatus::Pending);
+
+        let all = initial_results(&pipeline, &previous, &SetupStart::Beginning).unwrap();
+        assert!(all.iter().all(|r| r.status == StepStatus::Pending));
+
+        assert!(initial_results(&pipeline, &previous, &SetupStart::Step("zz".into())).is_err());
+    }
+
+    #[tokio::test]
+    async fn pipeline_copies_links_and_runs() {
+        let fx = fixture(
+            r#"
+            [[steps]]
+            copy = ".env"
+
+            [[steps]]
+            link = "cache"
+
+            [[steps]]
+            copy = "missing.txt"
+            optional = true
+
+            [[steps]]
+            name = "marker"
+            run = "echo \"$BETCODE_WORKTREE_BRANCH\" > marker.txt"
+            "#,
+        )
+        .await;
+        let repo = &fx.repo.repo_path;
+        std::fs::write(repo.join(".env"), "KEY=1\n").unwrap();
+        std::fs::create_dir(repo.join("cache")).unwrap();
+
+        let wt = fx.mgr.create("wt", &fx.repo, "feat", None).await.unwrap();
+        assert_eq!(wt.setup_status, "succeeded");
+        let path = PathBuf::from(&wt.path);
+        assert_eq!(
+            std::fs::read_to_string(path.join(".env")).unwrap(),
+            "KEY=1\n"
+        );
+        assert!(path.join("cache").symlink_metadata().unwrap().is_symlink());
+        assert_eq!(
+            std::fs::read_to_string(path.join("marker.txt"))
+                .unwrap()
+                .trim(),
+            "feat"
+        );
+        assert_eq!(
+            statuses(&wt),
+            vec![
+                ("copy .env".into(), StepStatus::Succeeded),
+                ("link cache".into(), StepStatus::Succeeded),
+                ("copy missing.txt".into(), StepStatus::Skipped),
+                ("marker".into(), StepStatus::Succeeded),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn failed_step_keeps_worktree_and_retry_resumes() {
+        let fx = fixture(
+            r#"
+            [[steps]]
+            name = "count"
+            run = "echo x >> count.txt"
+
+            [[steps]]
+            name = "check"
+            run = "echo boom >&2; test -f ok"
+            "#,
+        )
+        .await;
+        let wt = fx.mgr.create("wt", &fx.repo, "feat", None).await.unwrap();
+        let path = PathBuf::from(&wt.path);
+        assert_eq!(wt.setup_status, "failed");
+        assert!(path.exists(), "failed setup must keep the worktree");
+        let steps = stored_steps(&wt);
+        assert_eq!(steps[1].status, StepStatus::Failed);
+        assert_eq!(steps[1].exit_code, Some(1));
+        assert!(steps[1].error.as_deref().unwrap().contains("boom"));
+
+        std::fs::write(path.join("ok"), "").unwrap();
+        let (tx, mut rx) = mpsc::channel(64);
+        let status = fx
+            .mgr
+            .run_setup(&wt.id, SetupStart::Resume, Some(tx))
+            .await
+            .unwrap();
+        assert_eq!(status, SetupStatus::Succeeded);
+        // The first step was not re-run.
+        assert_eq!(
+            std::fs::read_to_string(path.join("count.txt")).unwrap(),
+            "x\n"
+        );
+
+        let mut started = Vec::new();
+        while let Ok(event) = rx.try_recv() {
+            if let SetupEvent::StepStarted { name, .. } = event {
+                started.push(name);
+            }
+        }
+        assert_eq!(started, vec!["check".to_string()]);
+    }
+
+    #[tokio::test]
+    async fn deferred_setup_is_pending_until_run() {
+        let fx = fixture("[[steps]]\nrun = \"true\"").await;
+        let wt = fx
+            .mgr
+            .create_deferred("wt", &fx.repo, "feat", None)
+            .await
+            .unwrap();
+        assert_eq!(wt.setup_status, "pending");
+        assert_eq!(statuses(&wt), vec![("true".into(), StepStatus::Pending)]);
+        let status = fx
+            .mgr
+            .run_setup(&wt.id, SetupStart::Beginning, None)
+            .await
+            .unwrap();
+        assert_eq!(status, SetupStatus::Succeeded);
+    }
+
+    #[tokio::test]
+    async fn invalid_pipeline_file_fails_create() {
+        let fx = fixture("[[steps]]\ncopy = \"../x\"").await;
+        let err = fx
+            .mgr
+            .create("wt", &fx.repo, "feat", None)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, WorktreeError::InvalidSetup(_)));
+        assert!(fx.mgr.list(None).await.unwrap().is_empty());
+    }
+}
@@ -186,6 +186,26 @@ async fn event_forwarder_assigns_sequences() {
     assert_eq!(rx.try_recv().unwrap().sequence, 3);
 }
 
+#[tokio::test]
+async fn event_forwarder_keeps_stored_sequences() {
+    let mux = SessionMultiplexer::with_defaults();
+    let handle = mux.subscribe("s1", "c1", "cli").await.unwrap();
+    let fwd = mux.create_event_forwarder("s1".to_string());
+
+    for sequence in [41, 42] {
+        let mut event = text_delta_event("x", false);
+        event.sequence = sequence;
+        fwd.send(event).await.unwrap();
+    }
+    fwd.send(text_delta_event("x", false)).await.unwrap();
+
+    tokio::time::sleep(tokio::time::Duration::from_millis(50)).await;
+    let mut rx = handle.event_rx;
+    assert_eq!(rx.try_recv().unwrap().sequence, 41);
+    assert_eq!(rx.try_recv().unwrap().sequence, 42);
+    assert_eq!(rx.try_recv().unwrap().sequence, 43);
+}
+
 #[tokio::test]
 async fn multiple_clients_receive_broadcast() {
     let mux = SessionMultiplexer::with_defaults();
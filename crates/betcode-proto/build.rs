@@ -21,6 +21,8 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         "betcode/v1/plugin.proto",
         "betcode/v1/notification.proto",
         "betcode/v1/subagent.proto",
+        "betcode/v1/cluster.proto",
+        "betcode/v1/admin.proto",
     ];
 
     let proto_paths: Vec<_> = protos.iter().map(|p| format!("{proto_root}/{p}")).collect();
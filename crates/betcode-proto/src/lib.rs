@@ -28,3 +28,6 @@ pub use prost_types;
 
 /// Named constants for gRPC method strings shared across the tunnel protocol.
 pub mod methods;
+
+/// Retry hints on throttled (`RESOURCE_EXHAUSTED`) responses.
+pub mod rate_limit;
@@ -38,6 +38,9 @@ pub const METHOD_CLEAR_SESSION_GRANTS: &str = "AgentService/ClearSessionGrants";
 /// `AgentService/SetSessionGrant`
 pub const METHOD_SET_SESSION_GRANT: &str = "AgentService/SetSessionGrant";
 
+/// `AgentService/RespondToPermission`
+pub const METHOD_RESPOND_TO_PERMISSION: &str = "AgentService/RespondToPermission";
+
 /// `AgentService/RenameSession`
 pub const METHOD_RENAME_SESSION: &str = "AgentService/RenameSession";
 
@@ -116,6 +119,18 @@ pub const METHOD_LIST_WORKTREES: &str = "WorktreeService/ListWorktrees";
 /// `WorktreeService/GetWorktree`
 pub const METHOD_GET_WORKTREE: &str = "WorktreeService/GetWorktree";
 
+/// `WorktreeService/GetWorktreeStatus`
+pub const METHOD_GET_WORKTREE_STATUS: &str = "WorktreeService/GetWorktreeStatus";
+
+/// `WorktreeService/SyncWorktree`
+pub const METHOD_SYNC_WORKTREE: &str = "WorktreeService/SyncWorktree";
+
+/// `WorktreeService/GcWorktrees`
+pub const METHOD_GC_WORKTREES: &str = "WorktreeService/GcWorktrees";
+
+/// `WorktreeService/RunWorktreeSetup`
+pub const METHOD_RUN_WORKTREE_SETUP: &str = "WorktreeService/RunWorktreeSetup";
+
 // ---------------------------------------------------------------------------
 // GitRepoService
 // ---------------------------------------------------------------------------
@@ -203,3 +218,7 @@ pub const METHOD_WATCH_ORCHESTRATION: &str = "SubagentService/WatchOrchestration
 
 /// `SubagentService/RevokeAutoApprove`
 pub const METHOD_REVOKE_AUTO_APPROVE: &str = "SubagentService/RevokeAutoApprove";
+
+/// `SubagentService/RespondToSubagentPermission`
+pub const METHOD_RESPOND_TO_SUBAGENT_PERMISSION: &str =
+    "SubagentService/RespondToSubagentPermission";
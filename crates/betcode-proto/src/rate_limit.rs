@@ -0,0 +1,69 @@
+//! Retry hints carried on `RESOURCE_EXHAUSTED` statuses.
+//!
+//! When the relay throttles a request it says how long to back off in the
+//! `retry-after` metadata entry, in whole seconds like the HTTP header of the
+//! same name. Clients read it back with [`retry_after`] and wait at least
+//! that long before trying again.
+
+use std::time::Duration;
+
+use tonic::metadata::MetadataValue;
+use tonic::{Code, Status};
+
+/// Metadata key holding the number of seconds to wait before retrying.
+pub const RETRY_AFTER_METADATA: &str = "retry-after";
+
+/// A `RESOURCE_EXHAUSTED` status asking the client to retry after `wait`.
+pub fn resource_exhausted(message: impl Into<String>, wait: Duration) -> Status {
+    let mut status = Status::resource_exhausted(message);
+    // Round up so that a client honouring the hint never retries too early
+    let secs = wait.as_secs() + u64::from(wait.subsec_nanos() > 0);
+    status
+        .metadata_mut()
+        .insert(RETRY_AFTER_METADATA, MetadataValue::from(secs));
+    status
+}
+
+/// How long a `RESOURCE_EXHAUSTED` status asks the client to wait, if it
+/// carries a retry hint.
+pub fn retry_after(status: &Status) -> Option<Duration> {
+    if status.code() != Code::ResourceExhausted {
+        return None;
+    }
+    let secs = status
+        .metadata()
+        .get(RETRY_AFTER_METADATA)?
+        .to_str()
+        .ok()?
+        .trim()
+        .parse()
+        .ok()?;
+    Some(Duration::from_secs(secs))
+}
+
+#[cfg(test)]
+#[allow(clippy::panic, clippy::expect_used, clippy::unwrap_used)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hint_round_trips_rounded_up() {
+        let status = resource_exhausted("slow down", Duration::from_millis(2500));
+        assert_eq!(status.code(), Code::ResourceExhausted);
+        assert_eq!(retry_after(&status), Some(Duration::from_secs(3)));
+
+        let status = resource_exhausted("slow down", Duration::from_secs(4));
+        assert_eq!(retry_after(&status), Some(Duration::from_secs(4)));
+    }
+
+    #[test]
+    fn no_hint_without_metadata_or_on_other_codes() {
+        assert_eq!(retry_after(&Status::resource_exhausted("full")), None);
+
+        let mut status = Status::unavailable("down");
+        status
+            .metadata_mut()
+            .insert(RETRY_AFTER_METADATA, MetadataValue::from(5u64));
+        assert_eq!(retry_after(&status), None);
+    }
+}
@@ -0,0 +1,11 @@
+//! Build script for betcode-relay
+//!
+//! Enables the `PostgreSQL` storage tests when `BETCODE_TEST_PG_URL` is set.
+
+fn main() {
+    println!("cargo::rustc-check-cfg=cfg(betcode_test_pg)");
+    println!("cargo::rerun-if-env-changed=BETCODE_TEST_PG_URL");
+    if std::env::var_os("BETCODE_TEST_PG_URL").is_some() {
+        println!("cargo::rustc-cfg=betcode_test_pg");
+    }
+}
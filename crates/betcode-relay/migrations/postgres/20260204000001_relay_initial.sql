@@ -0,0 +1,69 @@
+-- PostgreSQL counterpart of sqlite/20260204000001_relay_initial.sql.
+-- Timestamps are BIGINT Unix seconds and flags BIGINT 0/1, as in SQLite.
+
+-- Users table: relay authentication accounts
+CREATE TABLE IF NOT EXISTS users (
+    id TEXT PRIMARY KEY,
+    username TEXT NOT NULL UNIQUE,
+    email TEXT NOT NULL,
+    password_hash TEXT NOT NULL,
+    created_at BIGINT NOT NULL,
+    updated_at BIGINT NOT NULL
+);
+CREATE UNIQUE INDEX IF NOT EXISTS idx_users_username ON users(username);
+
+-- Tokens table: refresh token tracking
+CREATE TABLE IF NOT EXISTS tokens (
+    id TEXT PRIMARY KEY,
+    user_id TEXT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    token_hash TEXT NOT NULL,
+    expires_at BIGINT NOT NULL,
+    revoked BIGINT NOT NULL DEFAULT 0 CHECK (revoked IN (0, 1)),
+    created_at BIGINT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS idx_tokens_user ON tokens(user_id);
+CREATE INDEX IF NOT EXISTS idx_tokens_hash ON tokens(token_hash);
+
+-- Machines table: registered daemon machines
+CREATE TABLE IF NOT EXISTS machines (
+    id TEXT PRIMARY KEY,
+    name TEXT NOT NULL,
+    owner_id TEXT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    status TEXT NOT NULL DEFAULT 'offline'
+        CHECK (status IN ('online', 'offline')),
+    registered_at BIGINT NOT NULL,
+    last_seen BIGINT NOT NULL,
+    metadata TEXT NOT NULL DEFAULT '{}'
+);
+CREATE INDEX IF NOT EXISTS idx_machines_owner ON machines(owner_id);
+CREATE INDEX IF NOT EXISTS idx_machines_status ON machines(status) WHERE status = 'online';
+
+-- Message buffer table: buffered requests for offline machines
+CREATE TABLE IF NOT EXISTS message_buffer (
+    id BIGINT GENERATED BY DEFAULT AS IDENTITY PRIMARY KEY,
+    machine_id TEXT NOT NULL REFERENCES machines(id) ON DELETE CASCADE,
+    request_id TEXT NOT NULL,
+    method TEXT NOT NULL,
+    payload BYTEA NOT NULL,
+    metadata TEXT NOT NULL DEFAULT '{}',
+    priority BIGINT NOT NULL DEFAULT 0,
+    expires_at BIGINT NOT NULL,
+    created_at BIGINT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS idx_buffer_machine ON message_buffer(machine_id, priority DESC, created_at ASC);
+CREATE INDEX IF NOT EXISTS idx_buffer_expires ON message_buffer(expires_at);
+
+-- Certificates table: TLS certificate tracking
+CREATE TABLE IF NOT EXISTS certificates (
+    id TEXT PRIMARY KEY,
+    machine_id TEXT REFERENCES machines(id) ON DELETE CASCADE,
+    subject_cn TEXT NOT NULL,
+    serial_number TEXT NOT NULL,
+    not_before BIGINT NOT NULL,
+    not_after BIGINT NOT NULL,
+    pem_cert TEXT NOT NULL,
+    revoked BIGINT NOT NULL DEFAULT 0 CHECK (revoked IN (0, 1)),
+    created_at BIGINT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS idx_certs_machine ON certificates(machine_id);
+CREATE INDEX IF NOT EXISTS idx_certs_serial ON certificates(serial_number);
@@ -0,0 +1,3 @@
+-- Add identity_pubkey column to machines table for E2E encryption key exchange.
+-- Stores the daemon's X25519 public key (32 bytes).
+ALTER TABLE machines ADD COLUMN IF NOT EXISTS identity_pubkey BYTEA DEFAULT NULL;
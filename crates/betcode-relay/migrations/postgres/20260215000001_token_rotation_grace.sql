@@ -0,0 +1,4 @@
+-- Soft-rotation columns for refresh token grace window.
+-- See sqlite/20260215000001_token_rotation_grace.sql.
+ALTER TABLE tokens ADD COLUMN IF NOT EXISTS rotated_at BIGINT;
+ALTER TABLE tokens ADD COLUMN IF NOT EXISTS successor_id TEXT REFERENCES tokens(id);
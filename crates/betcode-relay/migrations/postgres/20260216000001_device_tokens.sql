@@ -0,0 +1,10 @@
+-- Device tokens table: push notification device registrations.
+CREATE TABLE IF NOT EXISTS device_tokens (
+    id TEXT PRIMARY KEY,
+    user_id TEXT NOT NULL,
+    device_token TEXT NOT NULL UNIQUE,
+    platform TEXT NOT NULL CHECK (platform IN ('android', 'ios')),
+    created_at BIGINT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS idx_device_tokens_user ON device_tokens(user_id);
+CREATE UNIQUE INDEX IF NOT EXISTS idx_device_tokens_token ON device_tokens(device_token);
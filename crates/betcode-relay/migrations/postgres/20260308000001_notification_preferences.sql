@@ -0,0 +1,12 @@
+-- Per-user push notification preferences.
+-- Users without a row get every event kind and no quiet hours.
+CREATE TABLE IF NOT EXISTS notification_preferences (
+    user_id TEXT PRIMARY KEY REFERENCES users(id) ON DELETE CASCADE,
+    -- Comma-separated event kinds to notify about (e.g. "permission_request,error")
+    events TEXT NOT NULL,
+    -- Quiet hours in minutes after local midnight; NULL when disabled
+    quiet_hours_start BIGINT,
+    quiet_hours_end BIGINT,
+    utc_offset_minutes BIGINT NOT NULL DEFAULT 0,
+    updated_at BIGINT NOT NULL
+);
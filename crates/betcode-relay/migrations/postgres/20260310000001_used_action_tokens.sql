@@ -0,0 +1,6 @@
+-- Notification action tokens that have been used, so each works only once.
+-- Rows are purged once the token has expired and can no longer validate.
+CREATE TABLE IF NOT EXISTS used_action_tokens (
+    jti TEXT PRIMARY KEY,
+    expires_at BIGINT NOT NULL
+);
@@ -0,0 +1,4 @@
+-- Delivery receipts for buffered requests.
+-- Delivered rows are kept (without their payload) until they expire.
+ALTER TABLE message_buffer ADD COLUMN IF NOT EXISTS delivered_at BIGINT;
+CREATE INDEX IF NOT EXISTS idx_buffer_pending ON message_buffer(machine_id, delivered_at);
@@ -0,0 +1,15 @@
+-- Tunnel traffic per machine and method, summed per hour. "Sent" frames
+-- went down the tunnel to the machine, "received" frames came back up it.
+-- Kept after the machine is removed, so there is no foreign key.
+CREATE TABLE IF NOT EXISTS usage_hourly (
+    hour_start BIGINT NOT NULL,
+    machine_id TEXT NOT NULL,
+    owner_id TEXT NOT NULL,
+    method TEXT NOT NULL,
+    frames_sent BIGINT NOT NULL DEFAULT 0,
+    bytes_sent BIGINT NOT NULL DEFAULT 0,
+    frames_received BIGINT NOT NULL DEFAULT 0,
+    bytes_received BIGINT NOT NULL DEFAULT 0,
+    PRIMARY KEY (hour_start, machine_id, method)
+);
+CREATE INDEX IF NOT EXISTS idx_usage_hourly_owner ON usage_hourly(owner_id, hour_start);
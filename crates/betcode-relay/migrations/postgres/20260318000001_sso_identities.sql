@@ -0,0 +1,11 @@
+-- Identity provider accounts linked to relay users, keyed by the ID token's
+-- issuer and subject.
+CREATE TABLE IF NOT EXISTS sso_identities (
+    issuer TEXT NOT NULL,
+    subject TEXT NOT NULL,
+    user_id TEXT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    email TEXT NOT NULL,
+    created_at BIGINT NOT NULL,
+    PRIMARY KEY (issuer, subject)
+);
+CREATE INDEX IF NOT EXISTS idx_sso_identities_user ON sso_identities(user_id);
@@ -0,0 +1,5 @@
+-- Delivery receipts for buffered requests.
+-- Delivered rows are kept (without their payload) until they expire, so
+-- clients can see that a request queued for an offline machine went out.
+ALTER TABLE message_buffer ADD COLUMN delivered_at INTEGER;
+CREATE INDEX IF NOT EXISTS idx_buffer_pending ON message_buffer(machine_id, delivered_at);
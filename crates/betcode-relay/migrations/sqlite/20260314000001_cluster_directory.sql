@@ -0,0 +1,15 @@
+-- Relay instances in a cluster sharing this database, with the address
+-- peers use to reach them. Instances whose heartbeat is stale are ignored.
+CREATE TABLE IF NOT EXISTS relay_instances (
+    id TEXT PRIMARY KEY,
+    address TEXT NOT NULL,
+    heartbeat_at INTEGER NOT NULL
+);
+
+-- Which relay instance terminates each connected machine's tunnel.
+CREATE TABLE IF NOT EXISTS machine_locations (
+    machine_id TEXT PRIMARY KEY REFERENCES machines(id) ON DELETE CASCADE,
+    instance_id TEXT NOT NULL REFERENCES relay_instances(id) ON DELETE CASCADE,
+    connected_at INTEGER NOT NULL
+);
+CREATE INDEX IF NOT EXISTS idx_machine_locations_instance ON machine_locations(instance_id);
@@ -1,4 +1,4 @@
-//! JWT claims structure for `BetCode` relay auth.
+//! JWT claims structures for `BetCode` relay auth.
 
 use serde::{Deserialize, Serialize};
 
@@ -28,3 +28,48 @@ impl Claims {
         self.token_type == "refresh"
     }
 }
+
+/// JWT claims embedded in notification action tokens.
+///
+/// An action token lets a notification answer one permission request without
+/// a session: it names the user, machine, session and request it applies to.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ActionClaims {
+    /// JWT ID, recorded when the token is used so it works only once.
+    pub jti: String,
+    /// Subject (user ID).
+    pub sub: String,
+    pub machine_id: String,
+    pub session_id: String,
+    /// The permission request this token answers.
+    pub request_id: String,
+    /// Issued at (unix timestamp).
+    pub iat: i64,
+    /// Expiration (unix timestamp).
+    pub exp: i64,
+    /// Token type: always "action".
+    pub token_type: String,
+}
+
+/// JWT claims embedded in SSO login tokens.
+///
+/// `StartSsoLogin` hands the client one of these so `CompleteSsoLogin` can
+/// check that the code it is given belongs to a login this relay started,
+/// without keeping per-login state.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SsoLoginClaims {
+    /// `state` sent to the provider; the client must return the one it got
+    /// back on the redirect.
+    pub state: String,
+    /// `nonce` the provider must put in the ID token.
+    pub nonce: String,
+    pub redirect_uri: String,
+    /// PKCE challenge the code verifier must match.
+    pub code_challenge: String,
+    /// Issued at (unix timestamp).
+    pub iat: i64,
+    /// Expiration (unix timestamp).
+    pub exp: i64,
+    /// Token type: always `"sso_login"`.
+    pub token_type: String,
+}
@@ -3,11 +3,18 @@
 use jsonwebtoken::{DecodingKey, EncodingKey, Header, Validation};
 use sha2::{Digest, Sha256};
 
-use super::claims::Claims;
+use super::claims::{ActionClaims, Claims, SsoLoginClaims};
 
 /// The well-known insecure default JWT secret that must never be used in production.
 const INSECURE_DEFAULT_SECRET: &str = "dev-secret-change-me";
 
+/// Lifetime of notification action tokens. Long enough to reach a phone and
+/// be acted on, short enough that a leaked notification is soon useless.
+pub const ACTION_TOKEN_TTL_SECS: i64 = 15 * 60;
+
+/// Lifetime of SSO login tokens: enough to sign in at the identity provider.
+pub const SSO_LOGIN_TOKEN_TTL_SECS: i64 = 10 * 60;
+
 /// Minimum length in bytes for a JWT secret.
 const MIN_SECRET_LENGTH: usize = 32;
 
@@ -109,6 +116,88 @@ impl JwtManager {
         Ok(data.claims)
     }
 
+    /// Issue a single-use token answering permission request `request_id`
+    /// in `session_id` on `machine_id`, on behalf of `user_id`.
+    pub fn issue_action_token(
+        &self,
+        user_id: &str,
+        machine_id: &str,
+        session_id: &str,
+        request_id: &str,
+    ) -> Result<String, jsonwebtoken::errors::Error> {
+        let now = now_secs();
+        let claims = ActionClaims {
+            jti: uuid::Uuid::new_v4().to_string(),
+            sub: user_id.to_string(),
+            machine_id: machine_id.to_string(),
+            session_id: session_id.to_string(),
+            request_id: request_id.to_string(),
+            iat: now,
+            exp: now + ACTION_TOKEN_TTL_SECS,
+            token_type: "action".to_string(),
+        };
+        jsonwebtoken::encode(&Header::default(), &claims, &self.encoding_key)
+    }
+
+    /// Validate an action token and return its claims.
+    ///
+    /// Access and refresh tokens are rejected.
+    pub fn validate_action_token(
+        &self,
+        token: &str,
+    ) -> Result<ActionClaims, jsonwebtoken::errors::Error> {
+        let data = jsonwebtoken::decode::<ActionClaims>(
+            token,
+            &self.decoding_key,
+            &Validation::default(),
+        )?;
+        if data.claims.token_type != "action" {
+            return Err(jsonwebtoken::errors::ErrorKind::InvalidToken.into());
+        }
+        Ok(data.claims)
+    }
+
+    /// Issue a token binding an SSO login's `state`, `nonce`, redirect URI
+    /// and PKCE challenge, for the client to hand back when it completes the
+    /// login.
+    pub fn issue_sso_login_token(
+        &self,
+        state: &str,
+        nonce: &str,
+        redirect_uri: &str,
+        code_challenge: &str,
+    ) -> Result<String, jsonwebtoken::errors::Error> {
+        let now = now_secs();
+        let claims = SsoLoginClaims {
+            state: state.to_string(),
+            nonce: nonce.to_string(),
+            redirect_uri: redirect_uri.to_string(),
+            code_challenge: code_challenge.to_string(),
+            iat: now,
+            exp: now + SSO_LOGIN_TOKEN_TTL_SECS,
+            token_type: "sso_login".to_string(),
+        };
+        jsonwebtoken::encode(&Header::default(), &claims, &self.encoding_key)
+    }
+
+    /// Validate an SSO login token and return its claims.
+    ///
+    /// Other token types are rejected.
+    pub fn validate_sso_login_token(
+        &self,
+        token: &str,
+    ) -> Result<SsoLoginClaims, jsonwebtoken::errors::Error> {
+        let data = jsonwebtoken::decode::<SsoLoginClaims>(
+            token,
+            &self.decoding_key,
+            &Validation::default(),
+        )?;
+        if data.claims.token_type != "sso_login" {
+            return Err(jsonwebtoken::errors::ErrorKind::InvalidToken.into());
+        }
+        Ok(data.claims)
+    }
+
     /// Hash a token for storage (we don't store raw tokens).
     pub fn hash_token(token: &str) -> String {
         let mut hasher = Sha256::new();
@@ -172,6 +261,54 @@ mod tests {
         assert!(jwt2.validate(&token).is_err());
     }
 
+    #[test]
+    fn issue_and_validate_action_token() {
+        let jwt = test_jwt();
+        let token = jwt
+            .issue_action_token("user-1", "m1", "s1", "perm-1")
+            .unwrap();
+
+        let claims = jwt.validate_action_token(&token).unwrap();
+        assert_eq!(claims.sub, "user-1");
+        assert_eq!(claims.machine_id, "m1");
+        assert_eq!(claims.session_id, "s1");
+        assert_eq!(claims.request_id, "perm-1");
+        assert_eq!(claims.exp - claims.iat, ACTION_TOKEN_TTL_SECS);
+    }
+
+    #[test]
+    fn action_and_access_tokens_are_not_interchangeable() {
+        let jwt = test_jwt();
+        let (access, _) = jwt.issue_access_token("user-1", "alice").unwrap();
+        assert!(jwt.validate_action_token(&access).is_err());
+
+        let action = jwt
+            .issue_action_token("user-1", "m1", "s1", "perm-1")
+            .unwrap();
+        assert!(jwt.validate(&action).is_err());
+    }
+
+    #[test]
+    fn issue_and_validate_sso_login_token() {
+        let jwt = test_jwt();
+        let token = jwt
+            .issue_sso_login_token("state-1", "nonce-1", "http://127.0.0.1/cb", "challenge")
+            .unwrap();
+
+        let claims = jwt.validate_sso_login_token(&token).unwrap();
+        assert_eq!(claims.state, "state-1");
+        assert_eq!(claims.nonce, "nonce-1");
+        assert_eq!(claims.redirect_uri, "http://127.0.0.1/cb");
+        assert_eq!(claims.code_challenge, "challenge");
+        assert_eq!(claims.exp - claims.iat, SSO_LOGIN_TOKEN_TTL_SECS);
+
+        let action = jwt
+            .issue_action_token("user-1", "m1", "s1", "perm-1")
+            .unwrap();
+        assert!(jwt.validate_sso_login_token(&action).is_err());
+        assert!(jwt.validate_action_token(&token).is_err());
+    }
+
     #[test]
     fn token_hash_is_deterministic() {
         let h1 = JwtManager::hash_token("same-token");
@@ -1,10 +1,13 @@
 //! Authentication module for `BetCode` relay.
 //!
-//! Provides JWT token management and password hashing.
+//! Provides JWT token management, password hashing and `OpenID` Connect
+//! login.
 
 pub mod claims;
 pub mod jwt;
+pub mod oidc;
 pub mod password;
 
-pub use claims::Claims;
+pub use claims::{ActionClaims, Claims, SsoLoginClaims};
 pub use jwt::{JwtManager, JwtSecretError, validate_jwt_secret};
+pub use oidc::{OidcConfig, OidcError, OidcProvider};
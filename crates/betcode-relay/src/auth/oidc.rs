@@ -0,0 +1,411 @@
+//! `OpenID` Connect login against an external identity provider.
+//!
+//! The relay is a confidential client of the provider. Clients start a login
+//! through `AuthService` (authorization code + PKCE, or device code), the
+//! relay exchanges the code at the provider's token endpoint and validates
+//! the returned ID token against the provider's published keys. A login's
+//! state and nonce travel in a token signed by the relay rather than being
+//! stored, so any instance of a cluster can finish a login another one
+//! started.
+
+use std::time::Duration;
+
+use jsonwebtoken::jwk::{AlgorithmParameters, JwkSet};
+use jsonwebtoken::{Algorithm, DecodingKey, Validation};
+use serde::Deserialize;
+use tokio::sync::RwLock;
+
+/// Timeout for each request to the identity provider.
+const HTTP_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// Polling interval when the provider does not specify one (RFC 8628).
+const DEFAULT_DEVICE_INTERVAL_SECS: u32 = 5;
+
+/// Grant type of device code token requests (RFC 8628).
+const DEVICE_CODE_GRANT: &str = "urn:ietf:params:oauth:grant-type:device_code";
+
+/// Identity provider registration of this relay.
+#[derive(Debug, Clone)]
+pub struct OidcConfig {
+    /// Issuer URL; discovery is read from
+    /// `{issuer}/.well-known/openid-configuration`.
+    pub issuer: String,
+    pub client_id: String,
+    /// Client secret, when the relay is registered as a confidential client.
+    pub client_secret: Option<String>,
+    /// Space-separated scopes requested on login.
+    pub scopes: String,
+    /// Email domains whose users get a relay account on first login. Empty
+    /// admits only identities already linked to an account.
+    pub allowed_domains: Vec<String>,
+}
+
+/// Errors talking to the identity provider or validating what it returned.
+#[derive(Debug, thiserror::Error)]
+pub enum OidcError {
+    #[error("Identity provider request failed: {0}")]
+    Http(#[from] reqwest::Error),
+
+    #[error("Identity provider discovery failed: {0}")]
+    Discovery(String),
+
+    #[error("Identity provider does not support the device authorization flow")]
+    DeviceFlowUnsupported,
+
+    #[error("Authorization is still pending")]
+    AuthorizationPending,
+
+    #[error("Polling too fast")]
+    SlowDown,
+
+    #[error("Device code expired")]
+    ExpiredToken,
+
+    #[error("Access denied by identity provider")]
+    AccessDenied,
+
+    #[error("Identity provider rejected the token request: {0}")]
+    TokenRequest(String),
+
+    #[error("Invalid ID token: {0}")]
+    InvalidIdToken(String),
+}
+
+/// Fields of the provider's discovery document the relay uses.
+#[derive(Debug, Clone, Deserialize)]
+struct ProviderMetadata {
+    issuer: String,
+    authorization_endpoint: String,
+    token_endpoint: String,
+    jwks_uri: String,
+    #[serde(default)]
+    device_authorization_endpoint: Option<String>,
+}
+
+/// Validated claims of an ID token.
+#[derive(Debug, Clone, Deserialize)]
+pub struct IdTokenClaims {
+    pub sub: String,
+    #[serde(default)]
+    pub email: Option<String>,
+    #[serde(default)]
+    pub email_verified: Option<bool>,
+    #[serde(default)]
+    pub preferred_username: Option<String>,
+    #[serde(default)]
+    pub nonce: Option<String>,
+}
+
+/// A device authorization started at the provider (RFC 8628).
+#[derive(Debug, Clone, Deserialize)]
+pub struct DeviceAuthorization {
+    pub device_code: String,
+    pub user_code: String,
+    // Google names it verification_url
+    #[serde(alias = "verification_url")]
+    pub verification_uri: String,
+    #[serde(default)]
+    pub verification_uri_complete: Option<String>,
+    pub expires_in: u32,
+    #[serde(default = "default_device_interval")]
+    pub interval: u32,
+}
+
+const fn default_device_interval() -> u32 {
+    DEFAULT_DEVICE_INTERVAL_SECS
+}
+
+#[derive(Debug, Deserialize)]
+struct TokenResponse {
+    #[serde(default)]
+    id_token: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ErrorResponse {
+    error: String,
+    #[serde(default)]
+    error_description: Option<String>,
+}
+
+/// A discovered identity provider.
+pub struct OidcProvider {
+    config: OidcConfig,
+    metadata: ProviderMetadata,
+    http: reqwest::Client,
+    jwks: RwLock<JwkSet>,
+}
+
+impl OidcProvider {
+    /// Read the provider's discovery document and signing keys.
+    pub async fn discover(config: OidcConfig) -> Result<Self, OidcError> {
+        let _ = rustls::crypto::ring::default_provider().install_default();
+        let http = reqwest::Client::builder().timeout(HTTP_TIMEOUT).build()?;
+
+        let url = format!(
+            "{}/.well-known/openid-configuration",
+            config.issuer.trim_end_matches('/')
+        );
+        let metadata: ProviderMetadata = http
+            .get(&url)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        if metadata.issuer.trim_end_matches('/') != config.issuer.trim_end_matches('/') {
+            return Err(OidcError::Discovery(format!(
+                "discovery document is for issuer {}, expected {}",
+                metadata.issuer, config.issuer
+            )));
+        }
+
+        let jwks = fetch_jwks(&http, &metadata.jwks_uri).await?;
+        Ok(Self {
+            config,
+            metadata,
+            http,
+            jwks: RwLock::new(jwks),
+        })
+    }
+
+    /// Issuer identities are recorded under.
+    pub fn issuer(&self) -> &str {
+        &self.metadata.issuer
+    }
+
+    /// URL to send the user's browser to for an authorization code login.
+    pub fn authorization_url(
+        &self,
+        redirect_uri: &str,
+        code_challenge: &str,
+        state: &str,
+        nonce: &str,
+    ) -> Result<String, OidcError> {
+        let mut url = reqwest::Url::parse(&self.metadata.authorization_endpoint)
+            .map_err(|e| OidcError::Discovery(format!("invalid authorization endpoint: {e}")))?;
+        url.query_pairs_mut()
+            .append_pair("response_type", "code")
+            .append_pair("client_id", &self.config.client_id)
+            .append_pair("redirect_uri", redirect_uri)
+            .append_pair("scope", &self.config.scopes)
+            .append_pair("state", state)
+            .append_pair("nonce", nonce)
+            .append_pair("code_challenge", code_challenge)
+            .append_pair("code_challenge_method", "S256");
+        Ok(url.into())
+    }
+
+    /// Exchange an authorization code for a validated ID token.
+    pub async fn exchange_code(
+        &self,
+        code: &str,
+        code_verifier: &str,
+        redirect_uri: &str,
+        nonce: &str,
+    ) -> Result<IdTokenClaims, OidcError> {
+        self.token_request(
+            &[
+                ("grant_type", "authorization_code"),
+                ("code", code),
+                ("code_verifier", code_verifier),
+                ("redirect_uri", redirect_uri),
+            ],
+            Some(nonce),
+        )
+        .await
+    }
+
+    /// Start a device authorization for the configured scopes.
+    pub async fn start_device_authorization(&self) -> Result<DeviceAuthorization, OidcError> {
+        let endpoint = self
+            .metadata
+            .device_authorization_endpoint
+            .as_deref()
+            .ok_or(OidcError::DeviceFlowUnsupported)?;
+        let mut form = vec![
+            ("client_id", self.config.client_id.as_str()),
+            ("scope", self.config.scopes.as_str()),
+        ];
+        if let Some(secret) = &self.config.client_secret {
+            form.push(("client_secret", secret.as_str()));
+        }
+        let response = self.http.post(endpoint).form(&form).send().await?;
+        if !response.status().is_success() {
+            return Err(token_error(response).await);
+        }
+        Ok(response.json().await?)
+    }
+
+    /// Ask the provider whether a device authorization was approved.
+    pub async fn poll_device_token(&self, device_code: &str) -> Result<IdTokenClaims, OidcError> {
+        self.token_request(
+            &[
+                ("grant_type", DEVICE_CODE_GRANT),
+                ("device_code", device_code),
+            ],
+            None,
+        )
+        .await
+    }
+
+    /// Whether an identity not yet linked to an account may get one: its
+    /// email must be in an allowed domain and verified by the provider.
+    pub fn may_provision(&self, claims: &IdTokenClaims) -> bool {
+        if claims.email_verified != Some(true) {
+            return false;
+        }
+        let Some((_, domain)) = claims.email.as_deref().and_then(|e| e.rsplit_once('@')) else {
+            return false;
+        };
+        self.config
+            .allowed_domains
+            .iter()
+            .any(|allowed| allowed.eq_ignore_ascii_case(domain))
+    }
+
+    /// POST to the token endpoint and validate the returned ID token.
+    async fn token_request(
+        &self,
+        params: &[(&str, &str)],
+        nonce: Option<&str>,
+    ) -> Result<IdTokenClaims, OidcError> {
+        let mut form = params.to_vec();
+        form.push(("client_id", self.config.client_id.as_str()));
+        if let Some(secret) = &self.config.client_secret {
+            form.push(("client_secret", secret.as_str()));
+        }
+        let response = self
+            .http
+            .post(&self.metadata.token_endpoint)
+            .form(&form)
+            .send()
+            .await?;
+        if !response.status().is_success() {
+            return Err(token_error(response).await);
+        }
+        let tokens: TokenResponse = response.json().await?;
+        let id_token = tokens
+            .id_token
+            .ok_or_else(|| OidcError::InvalidIdToken("token response has no id_token".into()))?;
+        self.validate_id_token(&id_token, nonce).await
+    }
+
+    /// Check an ID token's signature, issuer, audience, expiry and nonce.
+    async fn validate_id_token(
+        &self,
+        token: &str,
+        nonce: Option<&str>,
+    ) -> Result<IdTokenClaims, OidcError> {
+        let invalid = |e: jsonwebtoken::errors::Error| OidcError::InvalidIdToken(e.to_string());
+        let header = jsonwebtoken::decode_header(token).map_err(invalid)?;
+        if matches!(
+            header.alg,
+            Algorithm::HS256 | Algorithm::HS384 | Algorithm::HS512
+        ) {
+            return Err(OidcError::InvalidIdToken(format!(
+                "symmetric algorithm {:?} is not accepted",
+                header.alg
+            )));
+        }
+        let key = self.decoding_key(header.kid.as_deref()).await?;
+
+        let mut validation = Validation::new(header.alg);
+        validation.set_issuer(&[&self.metadata.issuer]);
+        validation.set_audience(&[&self.config.client_id]);
+        validation.set_required_spec_claims(&["exp", "iss", "aud", "sub"]);
+        let claims = jsonwebtoken::decode::<IdTokenClaims>(token, &key, &validation)
+            .map_err(invalid)?
+            .claims;
+
+        if let Some(expected) = nonce
+            && claims.nonce.as_deref() != Some(expected)
+        {
+            return Err(OidcError::InvalidIdToken("nonce mismatch".into()));
+        }
+        Ok(claims)
+    }
+
+    /// Key for `kid`, refetching the key set once if the provider rotated
+    /// keys since it was read.
+    async fn decoding_key(&self, kid: Option<&str>) -> Result<DecodingKey, OidcError> {
+        let cached = find_key(&*self.jwks.read().await, kid)?;
+        if let Some(key) = cached {
+            return Ok(key);
+        }
+        let jwks = fetch_jwks(&self.http, &self.metadata.jwks_uri).await?;
+        let key = find_key(&jwks, kid)?;
+        *self.jwks.write().await = jwks;
+        key.ok_or_else(|| OidcError::InvalidIdToken("no matching signing key".into()))
+    }
+}
+
+async fn fetch_jwks(http: &reqwest::Client, uri: &str) -> Result<JwkSet, OidcError> {
+    Ok(http
+        .get(uri)
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?)
+}
+
+/// Asymmetric key for `kid` in `jwks`; without a `kid` the set must hold a
+/// single key.
+fn find_key(jwks: &JwkSet, kid: Option<&str>) -> Result<Option<DecodingKey>, OidcError> {
+    let jwk = match kid {
+        Some(kid) => jwks.find(kid),
+        None if jwks.keys.len() == 1 => jwks.keys.first(),
+        None => None,
+    };
+    let Some(jwk) = jwk else {
+        return Ok(None);
+    };
+    if matches!(jwk.algorithm, AlgorithmParameters::OctetKey(_)) {
+        return Err(OidcError::InvalidIdToken(
+            "symmetric signing keys are not accepted".into(),
+        ));
+    }
+    DecodingKey::from_jwk(jwk)
+        .map(Some)
+        .map_err(|e| OidcError::InvalidIdToken(e.to_string()))
+}
+
+/// Map an `OAuth` error response to an [`OidcError`].
+async fn token_error(response: reqwest::Response) -> OidcError {
+    let status = response.status();
+    match response.json::<ErrorResponse>().await {
+        Ok(error) => match error.error.as_str() {
+            "authorization_pending" => OidcError::AuthorizationPending,
+            "slow_down" => OidcError::SlowDown,
+            "expired_token" => OidcError::ExpiredToken,
+            "access_denied" => OidcError::AccessDenied,
+            _ => OidcError::TokenRequest(match error.error_description {
+                Some(description) => format!("{}: {description}", error.error),
+                None => error.error,
+            }),
+        },
+        Err(_) => OidcError::TokenRequest(format!("HTTP {status}")),
+    }
+}
+
+/// Relay username to offer an identity on first login: its preferred
+/// username or email local part, reduced to characters usernames use.
+pub fn username_candidate(claims: &IdTokenClaims) -> String {
+    let source = claims
+        .preferred_username
+        .as_deref()
+        .or(claims.email.as_deref())
+        .unwrap_or_default();
+    let local = source.split('@').next().unwrap_or_default();
+    let name: String = local
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_'))
+        .collect::<String>()
+        .to_ascii_lowercase();
+    if name.len() < 3 {
+        "sso-user".to_string()
+    } else {
+        name
+    }
+}
@@ -1,15 +1,40 @@
 //! Buffer manager for offline machine message queuing.
 //!
 //! When a machine is offline, requests are buffered in the database.
-//! When the machine reconnects, buffered messages are drained and forwarded.
+//! When the machine reconnects, buffered messages are drained and forwarded,
+//! most urgent first (see [`method_priority`]). Delivered messages stay in
+//! the buffer without their payload until they expire, as delivery receipts.
 
 use std::sync::Arc;
 
 use tracing::{info, warn};
 
+use betcode_proto::methods::{
+    METHOD_CANCEL_SUBAGENT, METHOD_CANCEL_TURN, METHOD_RESPOND_TO_PERMISSION,
+    METHOD_RESPOND_TO_SUBAGENT_PERMISSION,
+};
+
 use crate::registry::ConnectionRegistry;
 use crate::router::forwarder::build_request_frame;
-use crate::storage::{BufferMessageParams, RelayDatabase};
+use crate::storage::{BufferCaps, BufferMessageParams, CappedBuffer, RelayDatabase};
+
+/// Priority of answers to permission requests: a blocked agent is waiting.
+pub const PRIORITY_PERMISSION: i64 = 20;
+
+/// Priority of cancellations, which should land before more work does.
+pub const PRIORITY_CANCEL: i64 = 10;
+
+/// Priority of everything else (new prompts, session management).
+pub const PRIORITY_DEFAULT: i64 = 0;
+
+/// Delivery priority of a buffered request, from its method.
+pub fn method_priority(method: &str) -> i64 {
+    match method {
+        METHOD_RESPOND_TO_PERMISSION | METHOD_RESPOND_TO_SUBAGENT_PERMISSION => PRIORITY_PERMISSION,
+        METHOD_CANCEL_TURN | METHOD_CANCEL_SUBAGENT => PRIORITY_CANCEL,
+        _ => PRIORITY_DEFAULT,
+    }
+}
 
 /// Manages message buffering for offline machines.
 pub struct BufferManager {
@@ -19,6 +44,8 @@ pub struct BufferManager {
     default_ttl_secs: i64,
     /// Maximum number of buffered messages per machine.
     max_per_machine: usize,
+    /// Maximum number of buffered messages across all of a user's machines.
+    max_per_user: Option<usize>,
 }
 
 impl BufferManager {
@@ -33,13 +60,22 @@ impl BufferManager {
             registry,
             default_ttl_secs: ttl_secs,
             max_per_machine,
+            max_per_user: None,
         }
     }
 
+    /// Also cap the messages buffered across all machines of one user.
+    #[must_use]
+    pub const fn with_max_per_user(mut self, max_per_user: usize) -> Self {
+        self.max_per_user = Some(max_per_user);
+        self
+    }
+
     /// Buffer a request for an offline machine.
     ///
     /// Returns `BufferError::CapExceeded` if the machine already has
-    /// `max_per_machine` messages buffered.
+    /// `max_per_machine` messages buffered, or `BufferError::UserCapExceeded`
+    /// if its owner has `max_per_user` buffered across their machines.
     pub async fn buffer_request(
         &self,
         machine_id: &str,
@@ -48,43 +84,65 @@ impl BufferManager {
         data: &[u8],
         metadata_json: &str,
     ) -> Result<i64, BufferError> {
-        let current = self
+        let priority = method_priority(method);
+        let caps = BufferCaps {
+            per_machine: i64::try_from(self.max_per_machine).unwrap_or(i64::MAX),
+            per_user: self
+                .max_per_user
+                .map(|cap| i64::try_from(cap).unwrap_or(i64::MAX)),
+        };
+        let outcome = self
             .db
-            .count_buffered_messages(machine_id)
+            .buffer_message_within_caps(
+                &BufferMessageParams {
+                    machine_id,
+                    request_id,
+                    method,
+                    payload: data,
+                    metadata: metadata_json,
+                    priority,
+                    ttl_secs: self.default_ttl_secs,
+                },
+                caps,
+            )
             .await
             .map_err(|e| BufferError::Storage(e.to_string()))?;
 
-        if usize::try_from(current).unwrap_or(usize::MAX) >= self.max_per_machine {
-            warn!(
-                machine_id = %machine_id,
-                current,
-                cap = self.max_per_machine,
-                "Buffer cap reached for machine, rejecting request"
-            );
-            return Err(BufferError::CapExceeded {
-                machine_id: machine_id.to_string(),
-                cap: self.max_per_machine,
-            });
-        }
-
-        let id = self
-            .db
-            .buffer_message(&BufferMessageParams {
-                machine_id,
-                request_id,
-                method,
-                payload: data,
-                metadata: metadata_json,
-                priority: 0,
-                ttl_secs: self.default_ttl_secs,
-            })
-            .await
-            .map_err(|e| BufferError::Storage(e.to_string()))?;
+        let id = match outcome {
+            CappedBuffer::Buffered(id) => id,
+            CappedBuffer::MachineFull { current } => {
+                warn!(
+                    machine_id = %machine_id,
+                    current,
+                    cap = self.max_per_machine,
+                    "Buffer cap reached for machine, rejecting request"
+                );
+                return Err(BufferError::CapExceeded {
+                    machine_id: machine_id.to_string(),
+                    cap: self.max_per_machine,
+                });
+            }
+            CappedBuffer::UserFull { owner_id, current } => {
+                let cap = self.max_per_user.unwrap_or_default();
+                warn!(
+                    machine_id = %machine_id,
+                    owner_id = %owner_id,
+                    current,
+                    cap,
+                    "Buffer cap reached for user, rejecting request"
+                );
+                return Err(BufferError::UserCapExceeded {
+                    user_id: owner_id,
+                    cap,
+                });
+            }
+        };
 
         info!(
             machine_id = %machine_id,
             request_id = %request_id,
             method = %method,
+            priority,
             "Request buffered for offline machine"
         );
 
@@ -135,13 +193,13 @@ impl BufferManager {
                 break;
             }
 
-            // Delete from DB only after successful send
-            if let Err(e) = self.db.delete_buffered_message(msg.id).await {
+            // Record the delivery only after a successful send
+            if let Err(e) = self.db.mark_buffered_message_delivered(msg.id).await {
                 warn!(
                     machine_id = %machine_id,
                     buffer_id = msg.id,
                     error = %e,
-                    "Failed to delete delivered buffer message"
+                    "Failed to record buffered message delivery"
                 );
             }
             sent += 1;
@@ -201,6 +259,9 @@ pub enum BufferError {
 
     #[error("Buffer cap exceeded for machine {machine_id} (cap: {cap})")]
     CapExceeded { machine_id: String, cap: usize },
+
+    #[error("Buffer cap exceeded for user {user_id} (cap: {cap})")]
+    UserCapExceeded { user_id: String, cap: usize },
 }
 
 #[cfg(test)]
@@ -428,4 +489,75 @@ mod tests {
         let second = rx.recv().await.unwrap();
         assert_eq!(second.request_id, "low");
     }
+
+    #[test]
+    fn permission_answers_outrank_cancellations_and_prompts() {
+        assert_eq!(
+            method_priority(METHOD_RESPOND_TO_PERMISSION),
+            PRIORITY_PERMISSION
+        );
+        assert_eq!(
+            method_priority(METHOD_RESPOND_TO_SUBAGENT_PERMISSION),
+            PRIORITY_PERMISSION
+        );
+        assert_eq!(method_priority(METHOD_CANCEL_TURN), PRIORITY_CANCEL);
+        assert_eq!(
+            method_priority("AgentService/RenameSession"),
+            PRIORITY_DEFAULT
+        );
+        const { assert!(PRIORITY_PERMISSION > PRIORITY_CANCEL && PRIORITY_CANCEL > PRIORITY_DEFAULT) };
+    }
+
+    #[tokio::test]
+    async fn drain_delivers_by_method_priority() {
+        let (manager, registry) = setup().await;
+        let (tx, mut rx) = mpsc::channel(16);
+
+        for (request_id, method) in [
+            ("rename", "AgentService/RenameSession"),
+            ("cancel", METHOD_CANCEL_TURN),
+            ("answer", METHOD_RESPOND_TO_PERMISSION),
+        ] {
+            manager
+                .buffer_request("m1", request_id, method, b"x", "{}")
+                .await
+                .unwrap();
+        }
+
+        registry.register("m1".into(), "u1".into(), tx).await;
+        assert_eq!(manager.drain_buffer("m1").await.unwrap(), 3);
+
+        for expected in ["answer", "cancel", "rename"] {
+            assert_eq!(rx.recv().await.unwrap().request_id, expected);
+        }
+        // Receipts remain for the delivered requests
+        let receipts = manager.db.list_buffered_messages("m1", true).await.unwrap();
+        assert_eq!(receipts.len(), 3);
+        assert!(receipts.iter().all(|m| m.delivered_at.is_some()));
+    }
+
+    #[tokio::test]
+    async fn buffer_respects_user_cap_across_machines() {
+        let (manager, _registry) = setup().await;
+        let manager = manager.with_max_per_user(2);
+        manager
+            .db
+            .create_machine("m2", "other-machine", "u1", "{}")
+            .await
+            .unwrap();
+
+        manager
+            .buffer_request("m1", "req-1", "Test", b"a", "{}")
+            .await
+            .unwrap();
+        manager
+            .buffer_request("m2", "req-2", "Test", b"b", "{}")
+            .await
+            .unwrap();
+
+        let result = manager
+            .buffer_request("m1", "req-3", "Test", b"c", "{}")
+            .await;
+        assert!(matches!(result, Err(BufferError::UserCapExceeded { .. })));
+    }
 }
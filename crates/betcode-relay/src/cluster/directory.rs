@@ -0,0 +1,114 @@
+//! Shared directory of relay instances and the machines they hold.
+
+use std::time::Duration;
+
+use tracing::{debug, info, warn};
+
+use betcode_core::db::unix_timestamp;
+
+use crate::storage::{DatabaseError, RelayDatabase, RelayInstance};
+
+/// This instance's entry in the cluster directory.
+#[derive(Clone)]
+pub struct ClusterDirectory {
+    db: RelayDatabase,
+    instance_id: String,
+    /// URL peers use to reach this instance.
+    address: String,
+    /// Instances silent for longer than this are treated as gone.
+    liveness: Duration,
+}
+
+impl ClusterDirectory {
+    pub const fn new(
+        db: RelayDatabase,
+        instance_id: String,
+        address: String,
+        liveness: Duration,
+    ) -> Self {
+        Self {
+            db,
+            instance_id,
+            address,
+            liveness,
+        }
+    }
+
+    /// This instance's ID.
+    pub fn instance_id(&self) -> &str {
+        &self.instance_id
+    }
+
+    /// Register this instance, or refresh its heartbeat.
+    pub async fn announce(&self) -> Result<(), DatabaseError> {
+        self.db
+            .upsert_relay_instance(&self.instance_id, &self.address)
+            .await
+    }
+
+    /// Record that this instance holds `machine_id`'s tunnel.
+    pub async fn claim(&self, machine_id: &str) -> Result<(), DatabaseError> {
+        self.db
+            .claim_machine_location(machine_id, &self.instance_id)
+            .await
+    }
+
+    /// Forget `machine_id`'s tunnel. Returns `false` if another instance has
+    /// claimed it since (the machine reconnected elsewhere).
+    pub async fn release(&self, machine_id: &str) -> Result<bool, DatabaseError> {
+        self.db
+            .release_machine_location(machine_id, &self.instance_id)
+            .await
+    }
+
+    /// The live peer holding `machine_id`'s tunnel, if any. Never returns
+    /// this instance.
+    pub async fn locate(&self, machine_id: &str) -> Result<Option<RelayInstance>, DatabaseError> {
+        let instance = self
+            .db
+            .locate_machine(machine_id, self.live_since())
+            .await?;
+        Ok(instance.filter(|i| i.id != self.instance_id))
+    }
+
+    /// Remove this instance and its claims, on shutdown.
+    pub async fn leave(&self) -> Result<(), DatabaseError> {
+        self.db.remove_relay_instance(&self.instance_id).await?;
+        info!(instance_id = %self.instance_id, "Left relay cluster");
+        Ok(())
+    }
+
+    fn live_since(&self) -> i64 {
+        unix_timestamp() - i64::try_from(self.liveness.as_secs()).unwrap_or(i64::MAX)
+    }
+
+    /// Heartbeat every `interval`, and drop instances that stopped
+    /// heartbeating (crashed without leaving) along with their claims.
+    pub fn spawn_heartbeat(&self, interval: Duration) -> tokio::task::JoinHandle<()> {
+        let directory = self.clone();
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                if let Err(e) = directory.announce().await {
+                    warn!(error = %e, "Failed to refresh relay instance heartbeat");
+                    continue;
+                }
+                match directory
+                    .db
+                    .purge_stale_relay_instances(directory.live_since())
+                    .await
+                {
+                    Ok(removed) if removed > 0 => {
+                        info!(
+                            removed,
+                            "Removed stale relay instances from cluster directory"
+                        );
+                    }
+                    Err(e) => warn!(error = %e, "Failed to purge stale relay instances"),
+                    _ => debug!("Relay instance heartbeat refreshed"),
+                }
+            }
+        })
+    }
+}
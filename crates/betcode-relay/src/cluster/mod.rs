@@ -0,0 +1,53 @@
+//! Horizontal relay clustering.
+//!
+//! Several relay instances can run behind one load balancer when they share
+//! a database. Each instance terminates some tunnels and records them in the
+//! shared directory (machine → instance). A request for a machine whose
+//! tunnel another instance holds is forwarded to that instance over
+//! `RelayPeerService`, so clients can land on any instance.
+
+pub mod directory;
+pub mod peer;
+pub mod service;
+
+#[cfg(test)]
+#[allow(clippy::panic, clippy::expect_used, clippy::unwrap_used)]
+mod tests;
+
+pub use directory::ClusterDirectory;
+pub use peer::PeerClient;
+pub use service::RelayPeerServiceImpl;
+
+/// Metadata key carrying the shared cluster secret on peer requests.
+pub const PEER_SECRET_HEADER: &str = "x-relay-peer-secret";
+
+/// Minimum length of the cluster secret in bytes.
+pub const MIN_SECRET_LEN: usize = 16;
+
+/// This instance's view of the cluster: where machines are, and how to
+/// reach the instances holding them.
+pub struct Cluster {
+    pub directory: ClusterDirectory,
+    pub peers: PeerClient,
+}
+
+impl Cluster {
+    pub const fn new(directory: ClusterDirectory, peers: PeerClient) -> Self {
+        Self { directory, peers }
+    }
+}
+
+/// Error returned by [`validate_secret`].
+#[derive(Debug, thiserror::Error)]
+pub enum ClusterSecretError {
+    #[error("Cluster secret must be at least {MIN_SECRET_LEN} bytes, got {0}")]
+    TooShort(usize),
+}
+
+/// Reject cluster secrets too short to resist guessing.
+pub const fn validate_secret(secret: &str) -> Result<(), ClusterSecretError> {
+    if secret.len() < MIN_SECRET_LEN {
+        return Err(ClusterSecretError::TooShort(secret.len()));
+    }
+    Ok(())
+}
@@ -0,0 +1,161 @@
+//! Client side of relay-to-relay forwarding.
+
+use std::collections::HashMap;
+
+use tokio::sync::{RwLock, mpsc};
+use tokio_stream::StreamExt as _;
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::Request;
+use tonic::metadata::MetadataValue;
+use tonic::transport::{Channel, ClientTlsConfig, Endpoint};
+use tracing::{debug, warn};
+
+use betcode_proto::v1::relay_peer_service_client::RelayPeerServiceClient;
+use betcode_proto::v1::{
+    PeerRequest, PeerStreamMessage, TunnelErrorCode, TunnelFrame, peer_stream_message,
+};
+
+use super::PEER_SECRET_HEADER;
+use crate::router::{RequestRouter, RouterError};
+
+/// Buffer size of the channels bridging peer streams.
+const PEER_STREAM_BUFFER: usize = 128;
+
+/// Forwards requests to the relay instances holding other machines' tunnels.
+pub struct PeerClient {
+    secret: String,
+    /// Lazily connected channels, keyed by peer address.
+    channels: RwLock<HashMap<String, Channel>>,
+}
+
+impl PeerClient {
+    pub fn new(secret: String) -> Self {
+        Self {
+            secret,
+            channels: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// A client for the peer at `address`, reusing its channel.
+    async fn client(&self, address: &str) -> Result<RelayPeerServiceClient<Channel>, RouterError> {
+        if let Some(channel) = self.channels.read().await.get(address) {
+            return Ok(RelayPeerServiceClient::new(channel.clone()));
+        }
+        let unreachable =
+            |e: tonic::transport::Error| RouterError::PeerUnreachable(format!("{address}: {e}"));
+        let mut endpoint = Endpoint::from_shared(address.to_string()).map_err(unreachable)?;
+        if address.starts_with("https://") {
+            endpoint = endpoint
+                .tls_config(ClientTlsConfig::new().with_enabled_roots())
+                .map_err(unreachable)?;
+        }
+        let channel = endpoint.connect_lazy();
+        self.channels
+            .write()
+            .await
+            .insert(address.to_string(), channel.clone());
+        Ok(RelayPeerServiceClient::new(channel))
+    }
+
+    /// Wrap `message` in a request carrying the cluster secret.
+    fn request<T>(&self, message: T) -> Result<Request<T>, RouterError> {
+        let mut request = Request::new(message);
+        let secret = MetadataValue::try_from(self.secret.as_str())
+            .map_err(|_| RouterError::PeerUnreachable("invalid cluster secret".to_string()))?;
+        request.metadata_mut().insert(PEER_SECRET_HEADER, secret);
+        Ok(request)
+    }
+
+    /// Forward a unary request and wait for the daemon's response frame.
+    pub async fn forward_request(
+        &self,
+        address: &str,
+        request: PeerRequest,
+    ) -> Result<TunnelFrame, RouterError> {
+        let mut client = self.client(address).await?;
+        let response = client
+            .forward_request(self.request(request)?)
+            .await
+            .map_err(|status| RouterError::Peer(Box::new(status)))?;
+        Ok(response.into_inner())
+    }
+
+    /// Forward a streaming request; frames arrive on the returned receiver
+    /// until the stream ends.
+    pub async fn forward_stream(
+        &self,
+        address: &str,
+        request: PeerRequest,
+    ) -> Result<mpsc::Receiver<TunnelFrame>, RouterError> {
+        let request_id = request.request_id.clone();
+        let mut client = self.client(address).await?;
+        let inbound = client
+            .forward_stream(self.request(request)?)
+            .await
+            .map_err(|status| RouterError::Peer(Box::new(status)))?
+            .into_inner();
+        Ok(pump(request_id, inbound))
+    }
+
+    /// Forward a bidirectional streaming request. Client frames sent on the
+    /// returned sender reach the daemon through the peer.
+    pub async fn forward_bidi_stream(
+        &self,
+        address: &str,
+        request: PeerRequest,
+    ) -> Result<(mpsc::Sender<TunnelFrame>, mpsc::Receiver<TunnelFrame>), RouterError> {
+        let request_id = request.request_id.clone();
+        let (client_tx, client_rx) = mpsc::channel::<TunnelFrame>(PEER_STREAM_BUFFER);
+        let first = PeerStreamMessage {
+            message: Some(peer_stream_message::Message::Request(request)),
+        };
+        let outbound =
+            tokio_stream::once(first).chain(ReceiverStream::new(client_rx).map(|frame| {
+                PeerStreamMessage {
+                    message: Some(peer_stream_message::Message::Frame(frame)),
+                }
+            }));
+
+        let mut client = self.client(address).await?;
+        let inbound = client
+            .forward_bidi_stream(self.request(outbound)?)
+            .await
+            .map_err(|status| RouterError::Peer(Box::new(status)))?
+            .into_inner();
+        Ok((client_tx, pump(request_id, inbound)))
+    }
+}
+
+/// Copy frames from a peer's response stream into a channel, ending with an
+/// error frame if the peer stream fails.
+fn pump(
+    request_id: String,
+    mut inbound: tonic::Streaming<TunnelFrame>,
+) -> mpsc::Receiver<TunnelFrame> {
+    let (tx, rx) = mpsc::channel(PEER_STREAM_BUFFER);
+    tokio::spawn(async move {
+        loop {
+            match inbound.message().await {
+                Ok(Some(frame)) => {
+                    if tx.send(frame).await.is_err() {
+                        debug!(request_id = %request_id, "Caller dropped peer stream");
+                        break;
+                    }
+                }
+                Ok(None) => break,
+                Err(status) => {
+                    warn!(request_id = %request_id, error = %status, "Peer stream failed");
+                    let _ = tx
+                        .send(RequestRouter::error_frame(
+                            &request_id,
+                            TunnelErrorCode::Unavailable,
+                            &format!("Peer relay stream failed: {}", status.message()),
+                        ))
+                        .await;
+                    break;
+                }
+            }
+        }
+    });
+    rx
+}
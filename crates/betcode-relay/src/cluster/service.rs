@@ -0,0 +1,162 @@
+//! `RelayPeerService`: serves requests forwarded by other relay instances.
+
+use std::pin::Pin;
+
+use sha2::{Digest, Sha256};
+use tokio_stream::{Stream, StreamExt, wrappers::ReceiverStream};
+use tonic::{Request, Response, Status, Streaming};
+use tracing::{debug, instrument, warn};
+
+use betcode_proto::v1::relay_peer_service_server::RelayPeerService;
+use betcode_proto::v1::{PeerRequest, PeerStreamMessage, TunnelFrame, peer_stream_message};
+
+use super::PEER_SECRET_HEADER;
+use crate::router::RequestRouter;
+use crate::server::agent_proxy::router_error_to_status;
+
+type FrameStream = Pin<Box<dyn Stream<Item = Result<TunnelFrame, Status>> + Send>>;
+
+/// Hex SHA-256 of a secret, so secrets of any length compare in constant time.
+pub(crate) fn secret_digest(secret: &str) -> String {
+    format!("{:x}", Sha256::digest(secret.as_bytes()))
+}
+
+pub struct RelayPeerServiceImpl {
+    /// Router limited to this instance's tunnels.
+    router: RequestRouter,
+    secret_digest: String,
+}
+
+impl RelayPeerServiceImpl {
+    /// `router` should be [`RequestRouter::local_only`]: a peer request is
+    /// never forwarded a second time.
+    pub fn new(router: RequestRouter, secret: &str) -> Self {
+        Self {
+            router,
+            secret_digest: secret_digest(secret),
+        }
+    }
+
+    /// Reject requests that don't carry the cluster secret.
+    fn authenticate<T>(&self, request: &Request<T>) -> Result<(), Status> {
+        let provided = request
+            .metadata()
+            .get(PEER_SECRET_HEADER)
+            .and_then(|v| v.to_str().ok())
+            .ok_or_else(|| Status::unauthenticated("Missing cluster secret"))?;
+        if betcode_crypto::constant_time_str_eq(&secret_digest(provided), &self.secret_digest) {
+            Ok(())
+        } else {
+            Err(Status::unauthenticated("Invalid cluster secret"))
+        }
+    }
+}
+
+#[tonic::async_trait]
+impl RelayPeerService for RelayPeerServiceImpl {
+    type ForwardStreamStream = FrameStream;
+    type ForwardBidiStreamStream = FrameStream;
+
+    #[instrument(skip(self, request), fields(rpc = "ForwardRequest"))]
+    async fn forward_request(
+        &self,
+        request: Request<PeerRequest>,
+    ) -> Result<Response<TunnelFrame>, Status> {
+        self.authenticate(&request)?;
+        let req = request.into_inner();
+        debug!(machine_id = %req.machine_id, request_id = %req.request_id, "Peer request");
+        let frame = self
+            .router
+            .forward_request(
+                &req.machine_id,
+                &req.request_id,
+                &req.method,
+                req.data,
+                req.metadata,
+            )
+            .await
+            .map_err(router_error_to_status)?;
+        Ok(Response::new(frame))
+    }
+
+    #[instrument(skip(self, request), fields(rpc = "ForwardStream"))]
+    async fn forward_stream(
+        &self,
+        request: Request<PeerRequest>,
+    ) -> Result<Response<Self::ForwardStreamStream>, Status> {
+        self.authenticate(&request)?;
+        let req = request.into_inner();
+        debug!(machine_id = %req.machine_id, request_id = %req.request_id, "Peer stream");
+        let rx = self
+            .router
+            .forward_stream(
+                &req.machine_id,
+                &req.request_id,
+                &req.method,
+                req.data,
+                req.metadata,
+            )
+            .await
+            .map_err(router_error_to_status)?;
+        Ok(Response::new(Box::pin(
+            ReceiverStream::new(rx).map(Ok::<_, Status>),
+        )))
+    }
+
+    #[instrument(skip(self, request), fields(rpc = "ForwardBidiStream"))]
+    async fn forward_bidi_stream(
+        &self,
+        request: Request<Streaming<PeerStreamMessage>>,
+    ) -> Result<Response<Self::ForwardBidiStreamStream>, Status> {
+        self.authenticate(&request)?;
+        let mut in_stream = request.into_inner();
+
+        let Some(peer_stream_message::Message::Request(req)) =
+            in_stream.message().await?.and_then(|m| m.message)
+        else {
+            return Err(Status::invalid_argument(
+                "First peer stream message must be the request",
+            ));
+        };
+        debug!(machine_id = %req.machine_id, request_id = %req.request_id, "Peer bidi stream");
+        let (daemon_tx, rx) = self
+            .router
+            .forward_bidi_stream(
+                &req.machine_id,
+                &req.request_id,
+                &req.method,
+                req.data,
+                req.metadata,
+            )
+            .await
+            .map_err(router_error_to_status)?;
+
+        // Relay the client's frames from the forwarding instance to the daemon
+        let request_id = req.request_id;
+        tokio::spawn(async move {
+            loop {
+                match in_stream.message().await {
+                    Ok(Some(PeerStreamMessage {
+                        message: Some(peer_stream_message::Message::Frame(frame)),
+                    })) => {
+                        if daemon_tx.send(frame).await.is_err() {
+                            break;
+                        }
+                    }
+                    Ok(Some(_)) => {
+                        warn!(request_id = %request_id, "Ignoring unexpected peer stream message");
+                    }
+                    Ok(None) => break,
+                    Err(e) => {
+                        debug!(request_id = %request_id, error = %e, "Peer stream closed");
+                        break;
+                    }
+                }
+            }
+        });
+
+        Ok(Response::new(Box::pin(
+            ReceiverStream::new(rx).map(Ok::<_, Status>),
+        )))
+    }
+}
@@ -0,0 +1,201 @@
+//! Two relay instances sharing a database, with machine "m1" connected to
+//! instance "b" and requests arriving at instance "a".
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::net::TcpListener;
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::TcpListenerStream;
+use tonic::Code;
+use tonic::transport::Server;
+
+use betcode_proto::v1::relay_peer_service_server::RelayPeerServiceServer;
+use betcode_proto::v1::{
+    FrameType, GetCommandRegistryRequest, GetCommandRegistryResponse, TunnelFrame,
+};
+
+use super::*;
+use crate::buffer::BufferManager;
+use crate::registry::ConnectionRegistry;
+use crate::router::{RequestRouter, RouterError};
+use crate::server::grpc_util::forward_unary;
+use crate::server::test_helpers::{
+    setup_router_with_machine, spawn_responder, spawn_stream_responder,
+};
+use crate::storage::RelayDatabase;
+
+const SECRET: &str = "cluster-secret-for-tests";
+const LIVENESS: Duration = Duration::from_secs(30);
+
+struct TwoInstances {
+    /// Router of instance "a", which has no tunnels of its own.
+    a: RequestRouter,
+    /// Router of instance "b", holding m1's tunnel.
+    b: Arc<RequestRouter>,
+    /// m1's end of its tunnel to "b".
+    tunnel_rx: mpsc::Receiver<TunnelFrame>,
+}
+
+/// Serve `RelayPeerService` for `router` on a loopback port, returning its URL.
+async fn serve_peer(router: RequestRouter, secret: &str) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = format!("http://{}", listener.local_addr().unwrap());
+    let service = RelayPeerServiceServer::new(RelayPeerServiceImpl::new(router, secret));
+    tokio::spawn(
+        Server::builder()
+            .add_service(service)
+            .serve_with_incoming(TcpListenerStream::new(listener)),
+    );
+    address
+}
+
+async fn two_instances(peer_secret: &str) -> TwoInstances {
+    let (b, tunnel_rx, db) = setup_router_with_machine("m1").await;
+    let b_address = serve_peer(b.local_only(), SECRET).await;
+    let b_directory = ClusterDirectory::new(db.clone(), "b".into(), b_address, LIVENESS);
+    b_directory.announce().await.unwrap();
+    b_directory.claim("m1").await.unwrap();
+
+    let registry = Arc::new(ConnectionRegistry::new());
+    let buffer = Arc::new(BufferManager::new(
+        db.clone(),
+        Arc::clone(&registry),
+        3600,
+        1000,
+    ));
+    let a_directory = ClusterDirectory::new(db, "a".into(), "http://a.invalid".into(), LIVENESS);
+    a_directory.announce().await.unwrap();
+    let cluster = Cluster::new(a_directory, PeerClient::new(peer_secret.to_string()));
+    let a = RequestRouter::new(registry, buffer, Duration::from_secs(5))
+        .with_cluster(Arc::new(cluster));
+
+    TwoInstances { a, b, tunnel_rx }
+}
+
+fn registry_response(name: &str) -> GetCommandRegistryResponse {
+    GetCommandRegistryResponse {
+        commands: vec![betcode_proto::v1::CommandEntry {
+            name: name.into(),
+            ..Default::default()
+        }],
+    }
+}
+
+#[tokio::test]
+async fn unary_request_is_forwarded_to_the_holding_instance() {
+    let TwoInstances { a, b, tunnel_rx } = two_instances(SECRET).await;
+    spawn_responder(&b, "m1", tunnel_rx, registry_response("from-b"));
+
+    assert!(a.is_machine_online("m1").await);
+    let resp: GetCommandRegistryResponse = forward_unary(
+        &a,
+        "m1",
+        "CommandService/GetCommandRegistry",
+        &GetCommandRegistryRequest::default(),
+    )
+    .await
+    .unwrap();
+    assert_eq!(resp.commands[0].name, "from-b");
+}
+
+#[tokio::test]
+async fn peer_rejects_wrong_secret() {
+    let TwoInstances { a, .. } = two_instances("not-the-cluster-secret").await;
+
+    let result = a
+        .forward_request("m1", "req-1", "Test", vec![], HashMap::new())
+        .await;
+    let Err(RouterError::Peer(status)) = result else {
+        panic!("expected peer error, got {result:?}");
+    };
+    assert_eq!(status.code(), Code::Unauthenticated);
+}
+
+#[tokio::test]
+async fn server_stream_is_forwarded() {
+    let TwoInstances { a, b, tunnel_rx } = two_instances(SECRET).await;
+    spawn_stream_responder(
+        &b,
+        "m1",
+        tunnel_rx,
+        vec![registry_response("one"), registry_response("two")],
+    );
+
+    let mut rx = a
+        .forward_stream("m1", "req-s1", "Test/Stream", vec![], HashMap::new())
+        .await
+        .unwrap();
+    let mut received = vec![];
+    while let Some(frame) = rx.recv().await {
+        received.push(frame);
+    }
+    assert_eq!(received.len(), 2);
+    assert!(received.iter().all(|f| f.request_id == "req-s1"));
+}
+
+#[tokio::test]
+async fn bidi_stream_carries_client_frames_to_the_daemon() {
+    let TwoInstances {
+        a,
+        b,
+        mut tunnel_rx,
+    } = two_instances(SECRET).await;
+    tokio::spawn(async move {
+        let request = tunnel_rx.recv().await.unwrap();
+        let rid = request.request_id;
+        let conn = b.registry().get("m1").await.unwrap();
+        let client_frame = tunnel_rx.recv().await.unwrap();
+        conn.send_stream_frame(&rid, client_frame).await;
+        conn.complete_stream(&rid).await;
+    });
+
+    let (tx, mut rx) = a
+        .forward_bidi_stream("m1", "req-b1", "Test/Bidi", vec![], HashMap::new())
+        .await
+        .unwrap();
+    tx.send(TunnelFrame {
+        request_id: "req-b1".into(),
+        frame_type: FrameType::StreamData as i32,
+        ..Default::default()
+    })
+    .await
+    .unwrap();
+
+    let echoed = rx.recv().await.unwrap();
+    assert_eq!(echoed.request_id, "req-b1");
+    assert_eq!(echoed.frame_type, FrameType::StreamData as i32);
+    assert!(rx.recv().await.is_none());
+}
+
+#[tokio::test]
+async fn locate_skips_this_instance_and_follows_reconnects() {
+    let db = RelayDatabase::open_in_memory().await.unwrap();
+    db.create_user("u1", "alice", "a@t.com", "hash")
+        .await
+        .unwrap();
+    db.create_machine("m1", "m1", "u1", "{}").await.unwrap();
+    let a = ClusterDirectory::new(db.clone(), "a".into(), "http://a".into(), LIVENESS);
+    let b = ClusterDirectory::new(db, "b".into(), "http://b".into(), LIVENESS);
+    a.announce().await.unwrap();
+    b.announce().await.unwrap();
+
+    a.claim("m1").await.unwrap();
+    assert!(a.locate("m1").await.unwrap().is_none());
+    assert_eq!(b.locate("m1").await.unwrap().unwrap().id, "a");
+
+    // m1 reconnects to "b" before "a" notices its old tunnel closed
+    b.claim("m1").await.unwrap();
+    assert!(!a.release("m1").await.unwrap());
+    assert_eq!(a.locate("m1").await.unwrap().unwrap().address, "http://b");
+
+    b.leave().await.unwrap();
+    assert!(a.locate("m1").await.unwrap().is_none());
+}
+
+#[test]
+fn short_secrets_are_rejected() {
+    assert!(validate_secret("short").is_err());
+    assert!(validate_secret(SECRET).is_ok());
+}
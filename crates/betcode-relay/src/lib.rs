@@ -2,17 +2,25 @@
 //!
 //! Core functionality for the `BetCode` relay:
 //! - `SQLite` storage for users, machines, tokens, and message buffer
-//! - JWT authentication and password hashing
+//! - JWT authentication, password hashing and `OpenID` Connect SSO login
 //! - gRPC services (Auth, Tunnel, Machine)
 //! - Connection registry for tunnel management
 //! - Request routing through tunnels to daemons
+//! - Wake-on-LAN for offline machines through waker daemons
+//! - Clustering: instances sharing a database forward requests to each other
+//! - Rate limiting of auth RPCs and tunnel traffic, and login lockout
+//! - Tunnel bandwidth accounting with hourly usage rollups
 
 pub mod auth;
 pub mod buffer;
+pub mod cluster;
 #[cfg(feature = "push-notifications")]
 pub mod notifications;
+pub mod ratelimit;
 pub mod registry;
 pub mod router;
 pub mod server;
 pub mod storage;
 pub mod tls;
+pub mod usage;
+pub mod wake;
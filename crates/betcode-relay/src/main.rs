@@ -2,12 +2,13 @@
 //!
 //! gRPC relay that routes requests through tunnels to daemon instances.
 
-use std::net::SocketAddr;
+use std::net::{IpAddr, SocketAddr};
 use std::path::PathBuf;
 use std::sync::Arc;
 use std::time::Duration;
 
 use clap::Parser;
+use ipnet::IpNet;
 use tonic::transport::Server;
 use tracing::{info, warn};
 
@@ -19,12 +20,20 @@ use betcode_proto::v1::git_lab_service_server::GitLabServiceServer;
 use betcode_proto::v1::git_repo_service_server::GitRepoServiceServer;
 use betcode_proto::v1::health_server::HealthServer;
 use betcode_proto::v1::machine_service_server::MachineServiceServer;
+use betcode_proto::v1::relay_admin_service_server::RelayAdminServiceServer;
+use betcode_proto::v1::relay_peer_service_server::RelayPeerServiceServer;
 use betcode_proto::v1::subagent_service_server::SubagentServiceServer;
 use betcode_proto::v1::tunnel_service_server::TunnelServiceServer;
 use betcode_proto::v1::worktree_service_server::WorktreeServiceServer;
 
-use betcode_relay::auth::JwtManager;
+use betcode_relay::auth::{JwtManager, OidcConfig, OidcProvider};
 use betcode_relay::buffer::BufferManager;
+use betcode_relay::cluster::{
+    Cluster, ClusterDirectory, PeerClient, RelayPeerServiceImpl, validate_secret,
+};
+use betcode_relay::ratelimit::{
+    ForwardedHeader, LockoutPolicy, Rate, TrustedProxies, TunnelLimiter,
+};
 use betcode_relay::registry::ConnectionRegistry;
 use betcode_relay::router::RequestRouter;
 use betcode_relay::server::{
@@ -34,6 +43,20 @@ use betcode_relay::server::{
 };
 use betcode_relay::storage::RelayDatabase;
 use betcode_relay::tls::TlsMode;
+use betcode_relay::usage::{RelayAdminServiceImpl, UsageMeter, validate_admin_token};
+use betcode_relay::wake::WakeManager;
+
+/// How often a clustered instance refreshes its directory entry.
+const CLUSTER_HEARTBEAT_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Instances silent for this long are treated as gone.
+const CLUSTER_LIVENESS: Duration = Duration::from_secs(30);
+
+/// Seconds in a day, for the usage retention period.
+const DAY_SECS: i64 = 24 * 60 * 60;
+
+/// Longest account lockout after repeated failed logins.
+const MAX_LOGIN_LOCKOUT: Duration = Duration::from_hours(1);
 
 #[derive(Parser, Debug)]
 #[command(name = "betcode-relay")]
@@ -50,6 +73,10 @@ struct Args {
     #[arg(long, env = "BETCODE_DB_PATH")]
     db_path: Option<PathBuf>,
 
+    /// Database URL (`postgres://...` or `sqlite:...`), instead of `--db-path`.
+    #[arg(long, env = "BETCODE_DATABASE_URL", conflicts_with = "db_path")]
+    database_url: Option<String>,
+
     /// JWT secret key (required; at least 32 bytes).
     #[arg(long, env = "BETCODE_JWT_SECRET")]
     jwt_secret: String,
@@ -96,6 +123,106 @@ struct Args {
     #[arg(long, default_value_t = 1000)]
     buffer_cap: usize,
 
+    /// Maximum buffered messages across all machines of one user.
+    #[arg(long, default_value_t = 5000)]
+    buffer_user_cap: usize,
+
+    /// Seconds to wait for a machine woken over Wake-on-LAN to connect
+    /// before another wake may be sent.
+    #[arg(long, default_value_t = 120)]
+    wake_timeout: u64,
+
+    /// URL other relay instances use to reach this one (e.g.
+    /// `https://relay-1.internal:443`). Enables clustering; every instance
+    /// must share the database.
+    #[arg(long, requires = "cluster_secret")]
+    cluster_advertise_url: Option<String>,
+
+    /// Unique ID of this instance in the cluster (random by default).
+    #[arg(long)]
+    cluster_instance_id: Option<String>,
+
+    /// Secret shared by all instances of the cluster (at least 16 bytes).
+    #[arg(long, env = "BETCODE_CLUSTER_SECRET")]
+    cluster_secret: Option<String>,
+
+    /// Auth RPCs allowed per client IP per minute (0 disables). Behind a
+    /// load balancer, list it in `--trusted-proxies`, or every client
+    /// shares its IP.
+    #[arg(long, default_value_t = 10)]
+    auth_rate_per_ip: u32,
+
+    /// Load balancers and reverse proxies in front of the relay, as
+    /// addresses or CIDR ranges (comma-separated). Requests through them
+    /// are attributed to the client in their forwarding header.
+    #[arg(long, value_delimiter = ',', value_parser = parse_network)]
+    trusted_proxies: Vec<IpNet>,
+
+    /// Header the trusted proxies record the client address in.
+    #[arg(long, value_enum, default_value_t = ForwardedHeader::XForwardedFor)]
+    forwarded_header: ForwardedHeader,
+
+    /// Logins, registrations and token refreshes allowed per username per
+    /// minute (0 disables).
+    #[arg(long, default_value_t = 30)]
+    auth_rate_per_user: u32,
+
+    /// Consecutive failed logins that lock an account (0 disables).
+    #[arg(long, default_value_t = 5)]
+    login_lockout_threshold: u32,
+
+    /// First account lockout in seconds; each further failure doubles it,
+    /// up to an hour.
+    #[arg(long, default_value_t = 60)]
+    login_lockout_secs: u64,
+
+    /// Frames clients may send down a machine's tunnel per second
+    /// (0 disables).
+    #[arg(long, default_value_t = 200)]
+    machine_frame_rate: u32,
+
+    /// Bytes clients may send down a machine's tunnel per second
+    /// (0 disables).
+    #[arg(long, default_value_t = 10 * 1024 * 1024)]
+    machine_byte_rate: u32,
+
+    /// Token operators present to `RelayAdminService` (at least 16 bytes).
+    /// The admin service is disabled without it.
+    #[arg(long, env = "BETCODE_ADMIN_TOKEN")]
+    admin_token: Option<String>,
+
+    /// Seconds between writes of tunnel usage counters to the database.
+    #[arg(long, default_value_t = 60)]
+    usage_flush_interval: u64,
+
+    /// Days of hourly usage rollups to keep (0 keeps them forever).
+    #[arg(long, default_value_t = 90)]
+    usage_retention_days: i64,
+
+    /// `OpenID` Connect issuer URL. Enables SSO login through this identity
+    /// provider.
+    #[arg(long, requires = "oidc_client_id")]
+    oidc_issuer: Option<String>,
+
+    /// Client ID the relay is registered under at the identity provider.
+    #[arg(long)]
+    oidc_client_id: Option<String>,
+
+    /// Client secret for the identity provider, if the relay is registered
+    /// as a confidential client.
+    #[arg(long, env = "BETCODE_OIDC_CLIENT_SECRET")]
+    oidc_client_secret: Option<String>,
+
+    /// Email domains whose users get a relay account on their first SSO
+    /// login (comma-separated). Without it only already linked identities
+    /// can log in.
+    #[arg(long, value_delimiter = ',')]
+    oidc_allowed_domains: Vec<String>,
+
+    /// Scopes requested from the identity provider.
+    #[arg(long, default_value = "openid email profile")]
+    oidc_scopes: String,
+
     /// Output logs as JSON (for structured log aggregation).
     #[arg(long)]
     log_json: bool,
@@ -106,11 +233,42 @@ struct Args {
     #[arg(long, env = "BETCODE_METRICS_ENDPOINT")]
     metrics_endpoint: Option<String>,
 
-    /// Path to FCM service account credentials JSON file.
-    /// Required when the `push-notifications` feature is enabled.
+    /// Path to FCM service account credentials JSON file. Enables delivery
+    /// to Android and iOS devices.
     #[cfg(feature = "push-notifications")]
     #[arg(long, env = "BETCODE_FCM_CREDENTIALS_PATH")]
-    fcm_credentials_path: PathBuf,
+    fcm_credentials_path: Option<PathBuf>,
+
+    /// VAPID private key (raw P-256 scalar, base64url). Enables Web Push.
+    #[cfg(feature = "push-notifications")]
+    #[arg(long, env = "BETCODE_VAPID_PRIVATE_KEY", requires = "vapid_subject")]
+    vapid_private_key: Option<String>,
+
+    /// Contact sent to Web Push services (`mailto:` or `https://` URL).
+    #[cfg(feature = "push-notifications")]
+    #[arg(long, env = "BETCODE_VAPID_SUBJECT")]
+    vapid_subject: Option<String>,
+
+    /// Enable delivery to ntfy topics.
+    #[cfg(feature = "push-notifications")]
+    #[arg(long)]
+    ntfy: bool,
+
+    /// Enable delivery to Gotify applications.
+    #[cfg(feature = "push-notifications")]
+    #[arg(long)]
+    gotify: bool,
+
+    /// Secret for signing webhook deliveries (at least 16 bytes). Enables
+    /// webhook devices.
+    #[cfg(feature = "push-notifications")]
+    #[arg(long, env = "BETCODE_WEBHOOK_SECRET")]
+    webhook_secret: Option<String>,
+
+    /// Maximum push notifications sent to a user per minute.
+    #[cfg(feature = "push-notifications")]
+    #[arg(long, default_value_t = 10)]
+    notification_rate_limit: usize,
 }
 
 #[tokio::main]
@@ -138,7 +296,11 @@ async fn main() -> anyhow::Result<()> {
 
     betcode_relay::auth::validate_jwt_secret(&args.jwt_secret)?;
 
-    let db = if let Some(path) = &args.db_path {
+    let db = if let Some(url) = &args.database_url {
+        // The URL may carry credentials, so it is never logged
+        info!("Connecting to relay database URL");
+        RelayDatabase::connect(url).await?
+    } else if let Some(path) = &args.db_path {
         info!(path = %path.display(), "Opening relay database");
         RelayDatabase::open(path).await?
     } else {
@@ -153,31 +315,130 @@ async fn main() -> anyhow::Result<()> {
         args.refresh_ttl,
     ));
 
-    let registry = Arc::new(ConnectionRegistry::new());
-    let buffer = Arc::new(BufferManager::new(
+    if let Some(token) = &args.admin_token {
+        validate_admin_token(token)?;
+    }
+
+    // Created after tracing so its counters bind to the OTel meter provider
+    let usage = Arc::new(UsageMeter::new());
+    usage.spawn_flush(
+        db.clone(),
+        Duration::from_secs(args.usage_flush_interval.max(1)),
+    );
+
+    let registry = Arc::new(ConnectionRegistry::new().with_usage(Arc::clone(&usage)));
+    let buffer = Arc::new(
+        BufferManager::new(
+            db.clone(),
+            Arc::clone(&registry),
+            args.buffer_ttl,
+            args.buffer_cap,
+        )
+        .with_max_per_user(args.buffer_user_cap),
+    );
+    let wake = Arc::new(WakeManager::new(
         db.clone(),
         Arc::clone(&registry),
-        args.buffer_ttl,
-        args.buffer_cap,
+        Duration::from_secs(args.wake_timeout),
     ));
-    let router = Arc::new(RequestRouter::new(
+    let cluster = match (&args.cluster_advertise_url, &args.cluster_secret) {
+        (Some(address), Some(secret)) => {
+            validate_secret(secret)?;
+            let instance_id = args
+                .cluster_instance_id
+                .clone()
+                .unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+            let directory = ClusterDirectory::new(
+                db.clone(),
+                instance_id.clone(),
+                address.clone(),
+                CLUSTER_LIVENESS,
+            );
+            directory.announce().await?;
+            let cluster = Arc::new(Cluster::new(directory, PeerClient::new(secret.clone())));
+            info!(instance_id = %instance_id, address = %address, "Joined relay cluster");
+            Some(cluster)
+        }
+        _ => None,
+    };
+
+    let mut router = RequestRouter::new(
         Arc::clone(&registry),
         Arc::clone(&buffer),
         Duration::from_secs(args.request_timeout),
-    ));
+    )
+    .with_wake(Arc::clone(&wake));
+    let tunnel_limiter = TunnelLimiter::new(
+        Rate::per_second(args.machine_frame_rate),
+        Rate::per_second(args.machine_byte_rate),
+    );
+    if tunnel_limiter.is_enabled() {
+        router = router.with_rate_limits(Arc::new(tunnel_limiter));
+    }
+    // Peers get a router that only reaches this instance's tunnels
+    let peer_router = router.local_only();
+    if let Some(cluster) = &cluster {
+        router = router.with_cluster(Arc::clone(cluster));
+    }
+    let router = Arc::new(router);
 
     // mTLS is enabled when a client CA cert path is provided
     let mtls_enabled = args.mtls_ca_cert.is_some();
 
     // Build services
-    let auth = AuthServiceImpl::new(db.clone(), Arc::clone(&jwt), args.refresh_grace_period);
+    let mut auth = AuthServiceImpl::new(db.clone(), Arc::clone(&jwt), args.refresh_grace_period)
+        .with_trusted_proxies(TrustedProxies::new(
+            args.trusted_proxies.clone(),
+            args.forwarded_header,
+        ));
+    if let Some(rate) = Rate::per_minute(args.auth_rate_per_ip) {
+        auth = auth.with_ip_rate_limit(rate);
+    }
+    if let Some(rate) = Rate::per_minute(args.auth_rate_per_user) {
+        auth = auth.with_user_rate_limit(rate);
+    }
+    if args.login_lockout_threshold > 0 {
+        auth = auth.with_lockout(LockoutPolicy {
+            threshold: args.login_lockout_threshold,
+            base: Duration::from_secs(args.login_lockout_secs),
+            max: MAX_LOGIN_LOCKOUT,
+        });
+    }
+    if let (Some(issuer), Some(client_id)) = (&args.oidc_issuer, &args.oidc_client_id) {
+        let provider = OidcProvider::discover(OidcConfig {
+            issuer: issuer.clone(),
+            client_id: client_id.clone(),
+            client_secret: args.oidc_client_secret.clone(),
+            scopes: args.oidc_scopes.clone(),
+            allowed_domains: args.oidc_allowed_domains.clone(),
+        })
+        .await?;
+        info!(issuer = %provider.issuer(), "SSO login enabled");
+        auth = auth.with_sso(Arc::new(provider));
+    }
+    #[cfg(feature = "push-notifications")]
+    let backends = Arc::new(notification_backends(&args)?);
+
     let tunnel = TunnelServiceImpl::new(
         Arc::clone(&registry),
         db.clone(),
         Arc::clone(&buffer),
         mtls_enabled,
     );
-    let machine = MachineServiceImpl::new(db.clone());
+    let tunnel = match &cluster {
+        Some(cluster) => tunnel.with_cluster(Arc::clone(cluster)),
+        None => tunnel,
+    };
+    #[cfg(feature = "push-notifications")]
+    let tunnel = tunnel.with_notifications(Arc::new(
+        betcode_relay::notifications::NotificationDispatcher::new(
+            db.clone(),
+            Arc::clone(&backends),
+            args.notification_rate_limit,
+        )
+        .with_actions(Arc::clone(&jwt)),
+    ));
+    let machine = MachineServiceImpl::new(db.clone()).with_wake(wake);
     let agent_proxy = AgentProxyService::new(Arc::clone(&router), db.clone());
     let command_proxy = CommandProxyService::new(Arc::clone(&router), db.clone());
     let worktree_proxy = WorktreeProxyService::new(Arc::clone(&router), db.clone());
@@ -185,18 +446,31 @@ async fn main() -> anyhow::Result<()> {
     let config_proxy = ConfigProxyService::new(Arc::clone(&router), db.clone());
     let gitlab_proxy = GitLabProxyService::new(Arc::clone(&router), db.clone());
     let subagent_proxy = SubagentProxyService::new(Arc::clone(&router), db.clone());
+    let admin_service = args.admin_token.as_deref().map(|token| {
+        RelayAdminServiceServer::new(RelayAdminServiceImpl::new(
+            db.clone(),
+            Arc::clone(&usage),
+            token,
+        ))
+    });
+    let peer_service = match (&cluster, &args.cluster_secret) {
+        (Some(_), Some(secret)) => Some(RelayPeerServiceServer::new(RelayPeerServiceImpl::new(
+            peer_router,
+            secret,
+        ))),
+        _ => None,
+    };
 
     // Build notification service (only with push-notifications feature)
     #[cfg(feature = "push-notifications")]
-    let notification_svc = {
-        use betcode_relay::notifications::{FcmClient, NotificationServiceImpl};
-        let fcm = FcmClient::from_credentials_file(&args.fcm_credentials_path)?;
-        info!(
-            project_id = %fcm.project_id(),
-            "FCM push notifications enabled"
-        );
-        NotificationServiceImpl::new(db.clone(), fcm)
-    };
+    let notification_svc =
+        betcode_relay::notifications::NotificationServiceImpl::new(db.clone(), backends);
+    #[cfg(feature = "push-notifications")]
+    let notification_action_svc = betcode_relay::notifications::NotificationActionServiceImpl::new(
+        db.clone(),
+        Arc::clone(&jwt),
+        Arc::clone(&router),
+    );
 
     let jwt_check = betcode_relay::server::jwt_interceptor(Arc::clone(&jwt));
 
@@ -241,8 +515,11 @@ async fn main() -> anyhow::Result<()> {
         info!(addr = %args.addr, "Relay server starting (plaintext)");
     }
 
-    // Spawn background task to clean up expired buffered messages (hourly)
+    // Spawn background task to clean up expired buffered messages and old
+    // usage rollups (hourly)
     let cleanup_buffer = Arc::clone(&buffer);
+    let cleanup_db = db.clone();
+    let usage_retention = args.usage_retention_days.saturating_mul(DAY_SECS);
     tokio::spawn(async move {
         let mut interval = tokio::time::interval(Duration::from_secs(3600));
         interval.tick().await; // Skip first immediate tick
@@ -257,9 +534,28 @@ async fn main() -> anyhow::Result<()> {
                 }
                 _ => {}
             }
+            if usage_retention > 0 {
+                let before = betcode_core::db::unix_timestamp() - usage_retention;
+                match cleanup_db.prune_usage(before).await {
+                    Ok(removed) if removed > 0 => {
+                        info!(removed, "Pruned old usage rollups");
+                    }
+                    Err(e) => {
+                        warn!(error = %e, "Usage rollup pruning failed");
+                    }
+                    _ => {}
+                }
+            }
         }
     });
 
+    // Keep this instance's directory entry fresh and drop crashed peers
+    if let Some(cluster) = &cluster {
+        cluster
+            .directory
+            .spawn_heartbeat(CLUSTER_HEARTBEAT_INTERVAL);
+    }
+
     // Standard grpc.health.v1 health service (no auth — used by load balancers/K8s)
     let (grpc_health_reporter, grpc_health_service) = tonic_health::server::health_reporter();
     grpc_health_reporter
@@ -307,14 +603,25 @@ async fn main() -> anyhow::Result<()> {
             jwt_check.clone(),
         ));
 
+    // Authenticated by the shared cluster secret instead of a JWT
+    let grpc_router = grpc_router.add_optional_service(peer_service);
+    // Authenticated by the admin token instead of a JWT
+    let grpc_router = grpc_router.add_optional_service(admin_service);
+
     // Conditionally add notification service when push-notifications feature is enabled
     #[cfg(feature = "push-notifications")]
     let grpc_router = {
+        use betcode_proto::v1::notification_action_service_server::NotificationActionServiceServer;
         use betcode_proto::v1::notification_service_server::NotificationServiceServer;
-        grpc_router.add_service(NotificationServiceServer::with_interceptor(
-            notification_svc,
-            jwt_check,
-        ))
+        grpc_router
+            .add_service(NotificationServiceServer::with_interceptor(
+                notification_svc,
+                jwt_check,
+            ))
+            // Authenticated by the action token in the request instead
+            .add_service(NotificationActionServiceServer::new(
+                notification_action_svc,
+            ))
     };
 
     tokio::select! {
@@ -326,12 +633,68 @@ async fn main() -> anyhow::Result<()> {
         }
     }
 
+    if let Err(e) = usage.flush(&db).await {
+        warn!(error = %e, "Failed to flush tunnel usage on shutdown");
+    }
+
+    if let Some(cluster) = &cluster
+        && let Err(e) = cluster.directory.leave().await
+    {
+        warn!(error = %e, "Failed to leave relay cluster");
+    }
+
     info!("Relay stopped");
     Ok(())
 }
 
+/// A network in CIDR notation, or a single address.
+fn parse_network(arg: &str) -> Result<IpNet, String> {
+    arg.parse::<IpNet>()
+        .or_else(|_| arg.parse::<IpAddr>().map(IpNet::from))
+        .map_err(|_| format!("not an address or CIDR range: {arg}"))
+}
+
 fn default_db_path() -> anyhow::Result<PathBuf> {
     let home =
         dirs::home_dir().ok_or_else(|| anyhow::anyhow!("Cannot determine home directory"))?;
     Ok(home.join(".betcode").join("relay.db"))
 }
+
+/// Build the notification backends enabled by the command line.
+#[cfg(feature = "push-notifications")]
+fn notification_backends(
+    args: &Args,
+) -> anyhow::Result<betcode_relay::notifications::NotificationBackends> {
+    use betcode_relay::notifications::{
+        FcmClient, NotificationBackends, TopicBackend, WebPushBackend, WebhookBackend,
+    };
+
+    let http = reqwest::Client::new();
+    let mut backends = NotificationBackends::new();
+    if let Some(path) = &args.fcm_credentials_path {
+        let fcm = FcmClient::from_credentials_file(path)?;
+        info!(project_id = %fcm.project_id(), "FCM push notifications enabled");
+        backends = backends.with_backend(fcm);
+    }
+    if let (Some(key), Some(subject)) = (&args.vapid_private_key, &args.vapid_subject) {
+        let web_push = WebPushBackend::new(http.clone(), key, subject)?;
+        info!("Web Push notifications enabled");
+        backends = backends.with_backend(web_push);
+    }
+    if args.ntfy {
+        info!("ntfy notifications enabled");
+        backends = backends.with_backend(TopicBackend::ntfy(http.clone()));
+    }
+    if args.gotify {
+        info!("Gotify notifications enabled");
+        backends = backends.with_backend(TopicBackend::gotify(http.clone()));
+    }
+    if let Some(secret) = &args.webhook_secret {
+        info!("Webhook notifications enabled");
+        backends = backends.with_backend(WebhookBackend::new(http, secret)?);
+    }
+    if backends.is_empty() {
+        warn!("No notification backends configured; push notifications are disabled");
+    }
+    Ok(backends)
+}
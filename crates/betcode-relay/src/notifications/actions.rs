@@ -0,0 +1,268 @@
+//! `NotificationActionService` gRPC implementation.
+//!
+//! Lets a notification answer the permission request it is about (the
+//! Approve/Deny buttons) without opening a session. The dispatcher attaches
+//! a short-lived, single-use action token to permission notifications; the
+//! token names the user, machine, session and request, so this service needs
+//! no access token and runs outside the JWT interceptor.
+
+use std::sync::Arc;
+
+use tonic::{Request, Response, Status};
+use tracing::{info, instrument, warn};
+
+use betcode_proto::methods::METHOD_RESPOND_TO_PERMISSION;
+use betcode_proto::v1::notification_action_service_server::NotificationActionService;
+use betcode_proto::v1::{
+    PermissionDecision, PermissionResponse, RespondToActionRequest, RespondToActionResponse,
+    RespondToPermissionRequest, RespondToPermissionResponse,
+};
+
+use crate::auth::JwtManager;
+use crate::router::RequestRouter;
+use crate::server::grpc_util::{forward_unary, verify_machine_ownership};
+use crate::storage::RelayDatabase;
+
+/// gRPC service answering permission requests from notifications.
+pub struct NotificationActionServiceImpl {
+    db: RelayDatabase,
+    jwt: Arc<JwtManager>,
+    router: Arc<RequestRouter>,
+}
+
+impl NotificationActionServiceImpl {
+    /// Create a new `NotificationActionServiceImpl`.
+    pub const fn new(db: RelayDatabase, jwt: Arc<JwtManager>, router: Arc<RequestRouter>) -> Self {
+        Self { db, jwt, router }
+    }
+}
+
+#[tonic::async_trait]
+impl NotificationActionService for NotificationActionServiceImpl {
+    #[instrument(skip(self, request), fields(rpc = "RespondToAction"))]
+    async fn respond_to_action(
+        &self,
+        request: Request<RespondToActionRequest>,
+    ) -> Result<Response<RespondToActionResponse>, Status> {
+        let req = request.into_inner();
+
+        let claims = self
+            .jwt
+            .validate_action_token(&req.action_token)
+            .map_err(|_| Status::unauthenticated("Invalid or expired action token"))?;
+
+        // Notifications only offer a plain approve or deny; anything richer
+        // (session grants, edited input) needs the full client.
+        let decision = match PermissionDecision::try_from(req.decision) {
+            Ok(d @ (PermissionDecision::AllowOnce | PermissionDecision::Deny)) => d,
+            _ => {
+                return Err(Status::invalid_argument(
+                    "decision must be ALLOW_ONCE or DENY",
+                ));
+            }
+        };
+
+        // The machine may have changed hands since the notification was sent
+        verify_machine_ownership(&self.db, &claims.machine_id, &claims.sub).await?;
+
+        let first_use = self
+            .db
+            .consume_action_token(&claims.jti, claims.exp)
+            .await
+            .map_err(|e| {
+                warn!(error = %e, "Failed to record action token use");
+                Status::internal("Internal error")
+            })?;
+        if !first_use {
+            return Err(Status::failed_precondition("Action token already used"));
+        }
+
+        let forwarded = RespondToPermissionRequest {
+            session_id: claims.session_id.clone(),
+            response: Some(PermissionResponse {
+                request_id: claims.request_id.clone(),
+                decision: decision.into(),
+                idempotency_key: claims.jti.clone(),
+                ..Default::default()
+            }),
+        };
+        // The token is consumed before forwarding so concurrent uses cannot
+        // both get through. A failed delivery releases it for another try;
+        // should the answer have arrived after all, the daemon drops the
+        // repeat by its idempotency key.
+        let resp: RespondToPermissionResponse = match forward_unary(
+            &self.router,
+            &claims.machine_id,
+            METHOD_RESPOND_TO_PERMISSION,
+            &forwarded,
+        )
+        .await
+        {
+            Ok(resp) => resp,
+            Err(status) => {
+                if let Err(e) = self.db.release_action_token(&claims.jti).await {
+                    warn!(error = %e, "Failed to release action token");
+                }
+                return Err(status);
+            }
+        };
+
+        info!(
+            user_id = %claims.sub,
+            machine_id = %claims.machine_id,
+            session_id = %claims.session_id,
+            request_id = %claims.request_id,
+            ?decision,
+            accepted = resp.accepted,
+            "Permission answered from notification"
+        );
+        Ok(Response::new(RespondToActionResponse {
+            accepted: resp.accepted,
+        }))
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::panic, clippy::expect_used, clippy::unwrap_used)]
+mod tests {
+    use super::*;
+    use crate::server::test_helpers::{setup_router_with_machine, spawn_responder};
+
+    fn test_jwt() -> Arc<JwtManager> {
+        Arc::new(JwtManager::new(b"test-secret-key-for-testing", 3600, 86400))
+    }
+
+    async fn setup() -> (
+        NotificationActionServiceImpl,
+        Arc<RequestRouter>,
+        tokio::sync::mpsc::Receiver<betcode_proto::v1::TunnelFrame>,
+    ) {
+        let (router, rx, db) = setup_router_with_machine("m1").await;
+        let svc = NotificationActionServiceImpl::new(db, test_jwt(), Arc::clone(&router));
+        (svc, router, rx)
+    }
+
+    fn action(svc: &NotificationActionServiceImpl, user_id: &str) -> String {
+        svc.jwt
+            .issue_action_token(user_id, "m1", "s1", "perm-1")
+            .unwrap()
+    }
+
+    fn respond(token: &str, decision: PermissionDecision) -> Request<RespondToActionRequest> {
+        Request::new(RespondToActionRequest {
+            action_token: token.to_string(),
+            decision: decision.into(),
+        })
+    }
+
+    #[tokio::test]
+    async fn approves_through_the_tunnel() {
+        let (svc, router, rx) = setup().await;
+        spawn_responder(
+            &router,
+            "m1",
+            rx,
+            RespondToPermissionResponse { accepted: true },
+        );
+        let token = action(&svc, "u1");
+
+        let resp = svc
+            .respond_to_action(respond(&token, PermissionDecision::AllowOnce))
+            .await
+            .unwrap();
+        assert!(resp.into_inner().accepted);
+    }
+
+    #[tokio::test]
+    async fn token_is_single_use() {
+        let (svc, router, rx) = setup().await;
+        spawn_responder(
+            &router,
+            "m1",
+            rx,
+            RespondToPermissionResponse { accepted: true },
+        );
+        let token = action(&svc, "u1");
+
+        svc.respond_to_action(respond(&token, PermissionDecision::Deny))
+            .await
+            .unwrap();
+        let err = svc
+            .respond_to_action(respond(&token, PermissionDecision::AllowOnce))
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), tonic::Code::FailedPrecondition);
+    }
+
+    #[tokio::test]
+    async fn failed_delivery_leaves_token_usable() {
+        let (svc, _router, _rx) = setup().await;
+        let token = svc
+            .jwt
+            .issue_action_token("u1", "m-off", "s1", "perm-1")
+            .unwrap();
+
+        let err = svc
+            .respond_to_action(respond(&token, PermissionDecision::AllowOnce))
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), tonic::Code::Unavailable);
+        let claims = svc.jwt.validate_action_token(&token).unwrap();
+        assert!(
+            svc.db
+                .consume_action_token(&claims.jti, claims.exp)
+                .await
+                .unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn rejects_access_tokens() {
+        let (svc, _router, _rx) = setup().await;
+        let (access, _) = svc.jwt.issue_access_token("u1", "alice").unwrap();
+
+        let err = svc
+            .respond_to_action(respond(&access, PermissionDecision::AllowOnce))
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), tonic::Code::Unauthenticated);
+    }
+
+    #[tokio::test]
+    async fn rejects_session_wide_grants() {
+        let (svc, router, rx) = setup().await;
+        spawn_responder(
+            &router,
+            "m1",
+            rx,
+            RespondToPermissionResponse { accepted: true },
+        );
+        let token = action(&svc, "u1");
+
+        let err = svc
+            .respond_to_action(respond(&token, PermissionDecision::AllowSession))
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), tonic::Code::InvalidArgument);
+        // A rejected request must not burn the token
+        svc.respond_to_action(respond(&token, PermissionDecision::Deny))
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn rejects_tokens_for_machines_the_user_does_not_own() {
+        let (svc, _router, _rx) = setup().await;
+        svc.db
+            .create_user("u2", "eve", "e@t.com", "hash")
+            .await
+            .unwrap();
+        let token = action(&svc, "u2");
+
+        let err = svc
+            .respond_to_action(respond(&token, PermissionDecision::AllowOnce))
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), tonic::Code::PermissionDenied);
+    }
+}
@@ -0,0 +1,224 @@
+//! Notification delivery backends.
+//!
+//! Each registered device names a [`DevicePlatform`], which picks the
+//! [`NotificationBackend`] that delivers to it: FCM for the mobile apps, Web
+//! Push for browsers, ntfy/Gotify topics and signed webhooks for self-hosted
+//! setups. The device token is whatever that backend needs to reach the
+//! device (an FCM token, a push subscription, a topic or webhook URL).
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+
+use serde::Serialize;
+use tracing::{debug, warn};
+
+use betcode_proto::v1::DevicePlatform;
+
+use super::NotificationError;
+
+/// A rendered notification, independent of how it is delivered.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct Notification {
+    pub title: String,
+    pub body: String,
+    /// Identifiers the client uses to open the right session.
+    pub data: HashMap<String, String>,
+}
+
+impl Notification {
+    /// Whether the user is being asked for something (permission, answer),
+    /// as opposed to being told something happened.
+    pub fn needs_response(&self) -> bool {
+        matches!(
+            self.data.get("kind").map(String::as_str),
+            Some("permission_request" | "user_question")
+        )
+    }
+}
+
+/// Supported delivery backends.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BackendKind {
+    Fcm,
+    WebPush,
+    Ntfy,
+    Gotify,
+    Webhook,
+}
+
+impl BackendKind {
+    /// Human-readable name.
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Fcm => "FCM",
+            Self::WebPush => "Web Push",
+            Self::Ntfy => "ntfy",
+            Self::Gotify => "Gotify",
+            Self::Webhook => "webhook",
+        }
+    }
+}
+
+impl fmt::Display for BackendKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Device platforms, their database names and the backend delivering to them.
+const PLATFORMS: [(DevicePlatform, &str, BackendKind); 6] = [
+    (DevicePlatform::Android, "android", BackendKind::Fcm),
+    (DevicePlatform::Ios, "ios", BackendKind::Fcm),
+    (DevicePlatform::WebPush, "web_push", BackendKind::WebPush),
+    (DevicePlatform::Ntfy, "ntfy", BackendKind::Ntfy),
+    (DevicePlatform::Gotify, "gotify", BackendKind::Gotify),
+    (DevicePlatform::Webhook, "webhook", BackendKind::Webhook),
+];
+
+/// Database name of a device platform.
+pub fn platform_name(platform: DevicePlatform) -> Option<&'static str> {
+    PLATFORMS
+        .iter()
+        .find(|(p, _, _)| *p == platform)
+        .map(|(_, name, _)| *name)
+}
+
+/// Backend delivering to devices stored with platform `name`.
+fn backend_for(name: &str) -> Option<BackendKind> {
+    PLATFORMS
+        .iter()
+        .find(|(_, n, _)| *n == name)
+        .map(|(_, _, kind)| *kind)
+}
+
+/// A way of delivering notifications to devices.
+#[tonic::async_trait]
+pub trait NotificationBackend: Send + Sync + fmt::Debug {
+    /// Which backend this is.
+    fn kind(&self) -> BackendKind;
+
+    /// Public key clients need to subscribe, if the backend has one (the VAPID
+    /// application server key for Web Push).
+    fn public_key(&self) -> Option<&str> {
+        None
+    }
+
+    /// Check a device token when it is registered.
+    fn validate_target(&self, target: &str) -> Result<(), NotificationError>;
+
+    /// Deliver `notification` to the device identified by `target`.
+    async fn send(
+        &self,
+        target: &str,
+        notification: &Notification,
+    ) -> Result<(), NotificationError>;
+}
+
+/// The backends enabled on this relay.
+#[derive(Debug, Default)]
+pub struct NotificationBackends {
+    backends: HashMap<BackendKind, Arc<dyn NotificationBackend>>,
+}
+
+impl NotificationBackends {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Enable `backend`, replacing any backend of the same kind.
+    #[must_use]
+    pub fn with_backend(mut self, backend: impl NotificationBackend + 'static) -> Self {
+        self.backends.insert(backend.kind(), Arc::new(backend));
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.backends.is_empty()
+    }
+
+    pub fn get(&self, kind: BackendKind) -> Option<&Arc<dyn NotificationBackend>> {
+        self.backends.get(&kind)
+    }
+
+    /// Device platforms some enabled backend can deliver to.
+    pub fn platforms(&self) -> Vec<DevicePlatform> {
+        PLATFORMS
+            .iter()
+            .filter(|(_, _, kind)| self.backends.contains_key(kind))
+            .map(|(platform, _, _)| *platform)
+            .collect()
+    }
+
+    /// The Web Push application server key, when Web Push is enabled.
+    pub fn vapid_public_key(&self) -> Option<&str> {
+        self.get(BackendKind::WebPush)?.public_key()
+    }
+
+    /// Backend delivering to devices stored with platform `platform`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`NotificationError::Unavailable`] if the platform is unknown or
+    /// its backend is not enabled on this relay.
+    pub fn for_platform(
+        &self,
+        platform: &str,
+    ) -> Result<&Arc<dyn NotificationBackend>, NotificationError> {
+        let kind = backend_for(platform).ok_or_else(|| {
+            NotificationError::Unavailable(format!("unknown platform {platform}"))
+        })?;
+        self.get(kind)
+            .ok_or_else(|| NotificationError::Unavailable(format!("{kind} is not enabled")))
+    }
+
+    /// Deliver `notification` to a device stored with `platform` and `target`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`NotificationError::Unavailable`] if no backend serves the
+    /// platform, or the backend's error if delivery fails.
+    pub async fn send(
+        &self,
+        platform: &str,
+        target: &str,
+        notification: &Notification,
+    ) -> Result<(), NotificationError> {
+        self.for_platform(platform)?
+            .send(target, notification)
+            .await
+    }
+}
+
+/// Parse a device token that must be an `http(s)` URL.
+pub(crate) fn parse_url(target: &str) -> Result<reqwest::Url, NotificationError> {
+    let url = reqwest::Url::parse(target)
+        .map_err(|e| NotificationError::InvalidTarget(format!("invalid URL: {e}")))?;
+    if !matches!(url.scheme(), "http" | "https") || url.host_str().is_none() {
+        return Err(NotificationError::InvalidTarget(format!(
+            "expected an http(s) URL, got {target}"
+        )));
+    }
+    Ok(url)
+}
+
+/// Turn a non-success HTTP response into [`NotificationError::ApiError`].
+pub(crate) async fn check_response(
+    response: reqwest::Response,
+    kind: BackendKind,
+) -> Result<(), NotificationError> {
+    let status = response.status();
+    if status.is_success() {
+        debug!(backend = %kind, "Notification delivered");
+        return Ok(());
+    }
+    let body = response
+        .text()
+        .await
+        .unwrap_or_else(|_| "<failed to read body>".to_string());
+    warn!(backend = %kind, status = status.as_u16(), body = %body, "Notification delivery failed");
+    Err(NotificationError::ApiError {
+        status: status.as_u16(),
+        body,
+    })
+}
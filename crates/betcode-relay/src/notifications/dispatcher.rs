@@ -0,0 +1,395 @@
+//! Turns notification intents from daemons into push notifications.
+//!
+//! A daemon raises an intent (a `NOTIFY` tunnel control frame) when a
+//! session needs attention. Intents carry identifiers only, so the
+//! notification text is generic and names the machine, never the
+//! end-to-end encrypted session content. The dispatcher applies the machine
+//! owner's preferences and a per-user rate limit before sending to each of
+//! the owner's registered devices through the backend for its platform.
+//!
+//! With actions enabled, permission notifications also carry an
+//! `action_token` that lets the device approve or deny the request through
+//! [`NotificationActionService`](super::actions::NotificationActionServiceImpl).
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use tokio::sync::Mutex;
+use tracing::{debug, warn};
+
+use betcode_core::db::unix_timestamp;
+use betcode_proto::v1::NotificationEventKind;
+
+use crate::auth::JwtManager;
+use crate::storage::RelayDatabase;
+
+use super::NotificationError;
+use super::backend::{Notification, NotificationBackends};
+use super::preferences::{Preferences, parse_kind, wire_name};
+
+/// Window the per-user rate limit applies to.
+const RATE_LIMIT_WINDOW: Duration = Duration::from_secs(60);
+
+/// A daemon's request to notify the owner of its machine.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NotificationIntent {
+    pub kind: NotificationEventKind,
+    pub session_id: String,
+    /// Permission request or question the notification is about, if any.
+    pub request_id: String,
+    /// Subagent the notification is about, if any.
+    pub subagent_id: String,
+}
+
+impl NotificationIntent {
+    /// Parse an intent from the params of a `NOTIFY` control frame.
+    pub fn from_params(params: &HashMap<String, String>) -> Option<Self> {
+        let kind = parse_kind(params.get("kind")?)?;
+        let field = |name: &str| params.get(name).cloned().unwrap_or_default();
+        Some(Self {
+            kind,
+            session_id: field("session_id"),
+            request_id: field("request_id"),
+            subagent_id: field("subagent_id"),
+        })
+    }
+
+    /// The notification shown to the user.
+    fn render(&self, machine_id: &str, machine_name: &str) -> Notification {
+        let (title, body) = self.message(machine_name);
+        Notification {
+            title: title.to_string(),
+            body,
+            data: self.data(machine_id),
+        }
+    }
+
+    /// Title and body shown to the user.
+    fn message(&self, machine_name: &str) -> (&'static str, String) {
+        match self.kind {
+            NotificationEventKind::PermissionRequest => (
+                "Permission needed",
+                format!("A tool call on {machine_name} is waiting for your approval"),
+            ),
+            NotificationEventKind::UserQuestion => (
+                "Question waiting",
+                format!("Claude on {machine_name} has a question for you"),
+            ),
+            NotificationEventKind::TurnComplete => (
+                "Turn complete",
+                format!("Claude finished working on {machine_name}"),
+            ),
+            NotificationEventKind::Error => (
+                "Session error",
+                format!("A session on {machine_name} stopped with an error"),
+            ),
+            NotificationEventKind::SubagentCompleted => (
+                "Subagent finished",
+                format!("A subagent on {machine_name} completed"),
+            ),
+            NotificationEventKind::SubagentFailed | NotificationEventKind::Unspecified => (
+                "Subagent failed",
+                format!("A subagent on {machine_name} failed"),
+            ),
+        }
+    }
+
+    /// Data payload the app uses to open the right session.
+    fn data(&self, machine_id: &str) -> HashMap<String, String> {
+        let mut data = HashMap::from([
+            ("kind".to_string(), wire_name(self.kind).to_string()),
+            ("machine_id".to_string(), machine_id.to_string()),
+            ("session_id".to_string(), self.session_id.clone()),
+        ]);
+        if !self.request_id.is_empty() {
+            data.insert("request_id".to_string(), self.request_id.clone());
+        }
+        if !self.subagent_id.is_empty() {
+            data.insert("subagent_id".to_string(), self.subagent_id.clone());
+        }
+        data
+    }
+}
+
+/// Sliding-window limit on notifications per user.
+struct RateLimiter {
+    max_per_window: usize,
+    sent: Mutex<HashMap<String, VecDeque<Instant>>>,
+}
+
+impl RateLimiter {
+    fn new(max_per_window: usize) -> Self {
+        Self {
+            max_per_window,
+            sent: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Record a notification for `user_id` unless the limit is reached.
+    async fn try_acquire(&self, user_id: &str) -> bool {
+        let now = Instant::now();
+        let mut sent = self.sent.lock().await;
+        let times = sent.entry(user_id.to_string()).or_default();
+        while times
+            .front()
+            .is_some_and(|t| now.duration_since(*t) >= RATE_LIMIT_WINDOW)
+        {
+            times.pop_front();
+        }
+        if times.len() >= self.max_per_window {
+            return false;
+        }
+        times.push_back(now);
+        true
+    }
+}
+
+/// Sends push notifications for daemon intents.
+pub struct NotificationDispatcher {
+    db: RelayDatabase,
+    backends: Arc<NotificationBackends>,
+    limiter: RateLimiter,
+    /// Issues action tokens for permission notifications, when enabled.
+    actions: Option<Arc<JwtManager>>,
+}
+
+impl NotificationDispatcher {
+    /// Create a dispatcher sending at most `max_per_minute` notifications to
+    /// each user.
+    pub fn new(
+        db: RelayDatabase,
+        backends: Arc<NotificationBackends>,
+        max_per_minute: usize,
+    ) -> Self {
+        Self {
+            db,
+            backends,
+            limiter: RateLimiter::new(max_per_minute),
+            actions: None,
+        }
+    }
+
+    /// Attach action tokens signed by `jwt` to permission notifications, so
+    /// they can be answered from the notification.
+    #[must_use]
+    pub fn with_actions(mut self, jwt: Arc<JwtManager>) -> Self {
+        self.actions = Some(jwt);
+        self
+    }
+
+    /// Single-use token answering the permission request `intent` is about,
+    /// if it is one and actions are enabled.
+    fn action_token(
+        &self,
+        owner_id: &str,
+        machine_id: &str,
+        intent: &NotificationIntent,
+    ) -> Option<String> {
+        let jwt = self.actions.as_ref()?;
+        if intent.kind != NotificationEventKind::PermissionRequest
+            || intent.session_id.is_empty()
+            || intent.request_id.is_empty()
+        {
+            return None;
+        }
+        jwt.issue_action_token(owner_id, machine_id, &intent.session_id, &intent.request_id)
+            .inspect_err(|e| warn!(owner_id, error = %e, "Failed to issue action token"))
+            .ok()
+    }
+
+    /// Notify `owner_id` about `intent` from machine `machine_id`.
+    ///
+    /// Returns how many devices were notified; zero when the owner's
+    /// preferences or the rate limit hold the notification back.
+    pub async fn dispatch(
+        &self,
+        owner_id: &str,
+        machine_id: &str,
+        intent: &NotificationIntent,
+    ) -> Result<usize, NotificationError> {
+        let prefs = self
+            .db
+            .get_notification_preferences(owner_id)
+            .await?
+            .map(|row| Preferences::from_row(&row))
+            .unwrap_or_default();
+        if !prefs.allows(intent.kind, unix_timestamp()) {
+            debug!(
+                owner_id,
+                kind = wire_name(intent.kind),
+                "Notification muted by preferences"
+            );
+            return Ok(0);
+        }
+
+        let devices = self.db.get_device_tokens_for_user(owner_id).await?;
+        if devices.is_empty() {
+            return Ok(0);
+        }
+        if !self.limiter.try_acquire(owner_id).await {
+            warn!(
+                owner_id,
+                kind = wire_name(intent.kind),
+                "Notification rate limit reached"
+            );
+            return Ok(0);
+        }
+
+        let machine_name = self
+            .db
+            .get_machine(machine_id)
+            .await
+            .map_or_else(|_| machine_id.to_string(), |m| m.name);
+        let mut notification = intent.render(machine_id, &machine_name);
+        if let Some(token) = self.action_token(owner_id, machine_id, intent) {
+            notification.data.insert("action_token".to_string(), token);
+        }
+
+        let mut notified = 0;
+        for device in devices {
+            match self
+                .backends
+                .send(&device.platform, &device.device_token, &notification)
+                .await
+            {
+                Ok(()) => notified += 1,
+                Err(e) => {
+                    warn!(owner_id, platform = %device.platform, error = %e, "Failed to send notification")
+                }
+            }
+        }
+        Ok(notified)
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::panic, clippy::expect_used, clippy::unwrap_used)]
+mod tests {
+    use super::*;
+    use crate::notifications::FcmClient;
+    use crate::notifications::fcm::ServiceAccountCredentials;
+
+    fn params(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs
+            .iter()
+            .map(|(k, v)| ((*k).to_string(), (*v).to_string()))
+            .collect()
+    }
+
+    async fn dispatcher(max_per_minute: usize) -> NotificationDispatcher {
+        let db = RelayDatabase::open_in_memory().await.unwrap();
+        db.create_user("u1", "alice", "a@t.com", "hash")
+            .await
+            .unwrap();
+        db.create_machine("m1", "laptop", "u1", "{}").await.unwrap();
+        let fcm = FcmClient::for_testing(ServiceAccountCredentials {
+            project_id: "test-project".to_string(),
+            client_email: String::new(),
+            private_key: String::new(),
+        });
+        let backends = NotificationBackends::new().with_backend(fcm);
+        NotificationDispatcher::new(db, Arc::new(backends), max_per_minute)
+    }
+
+    #[test]
+    fn intent_from_params() {
+        let intent = NotificationIntent::from_params(&params(&[
+            ("kind", "permission_request"),
+            ("session_id", "s1"),
+            ("request_id", "r1"),
+        ]))
+        .unwrap();
+        assert_eq!(intent.kind, NotificationEventKind::PermissionRequest);
+        assert_eq!(intent.request_id, "r1");
+        assert!(intent.subagent_id.is_empty());
+
+        let data = intent.data("m1");
+        assert_eq!(data["kind"], "permission_request");
+        assert_eq!(data["machine_id"], "m1");
+
+        assert!(NotificationIntent::from_params(&params(&[("kind", "bogus")])).is_none());
+        assert!(NotificationIntent::from_params(&params(&[("session_id", "s1")])).is_none());
+    }
+
+    #[test]
+    fn message_names_machine_only() {
+        let intent =
+            NotificationIntent::from_params(&params(&[("kind", "turn_complete")])).unwrap();
+        let (title, body) = intent.message("laptop");
+        assert_eq!(title, "Turn complete");
+        assert!(body.contains("laptop"));
+    }
+
+    #[tokio::test]
+    async fn rate_limiter_caps_per_user() {
+        let limiter = RateLimiter::new(2);
+        assert!(limiter.try_acquire("u1").await);
+        assert!(limiter.try_acquire("u1").await);
+        assert!(!limiter.try_acquire("u1").await);
+        assert!(limiter.try_acquire("u2").await);
+    }
+
+    #[tokio::test]
+    async fn dispatch_skips_users_without_devices() {
+        let dispatcher = dispatcher(10).await;
+        let intent = NotificationIntent::from_params(&params(&[("kind", "error")])).unwrap();
+        assert_eq!(dispatcher.dispatch("u1", "m1", &intent).await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn dispatch_respects_disabled_events() {
+        let dispatcher = dispatcher(10).await;
+        dispatcher
+            .db
+            .upsert_device_token("dt-1", "u1", "token-1", "android")
+            .await
+            .unwrap();
+        dispatcher
+            .db
+            .upsert_notification_preferences("u1", "error", None, 0)
+            .await
+            .unwrap();
+
+        let intent =
+            NotificationIntent::from_params(&params(&[("kind", "turn_complete")])).unwrap();
+        assert_eq!(dispatcher.dispatch("u1", "m1", &intent).await.unwrap(), 0);
+        // Muted notifications don't count against the rate limit
+        assert!(dispatcher.limiter.sent.lock().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn action_tokens_only_for_permission_requests() {
+        let intent = |kind| {
+            NotificationIntent::from_params(&params(&[
+                ("kind", kind),
+                ("session_id", "s1"),
+                ("request_id", "r1"),
+            ]))
+            .unwrap()
+        };
+        let jwt = Arc::new(JwtManager::new(b"test-secret-key-for-testing", 3600, 86400));
+
+        let plain = dispatcher(10).await;
+        assert!(
+            plain
+                .action_token("u1", "m1", &intent("permission_request"))
+                .is_none()
+        );
+
+        let dispatcher = dispatcher(10).await.with_actions(Arc::clone(&jwt));
+        assert!(
+            dispatcher
+                .action_token("u1", "m1", &intent("user_question"))
+                .is_none()
+        );
+        let token = dispatcher
+            .action_token("u1", "m1", &intent("permission_request"))
+            .unwrap();
+        let claims = jwt.validate_action_token(&token).unwrap();
+        assert_eq!(claims.sub, "u1");
+        assert_eq!(claims.machine_id, "m1");
+        assert_eq!(claims.session_id, "s1");
+        assert_eq!(claims.request_id, "r1");
+    }
+}
@@ -9,6 +9,7 @@ use serde::{Deserialize, Serialize};
 use tracing::{debug, warn};
 
 use super::NotificationError;
+use super::backend::{BackendKind, Notification, NotificationBackend};
 
 /// FCM HTTP v1 API endpoint template.
 /// The `{project_id}` placeholder is replaced with the actual project ID.
@@ -258,6 +259,33 @@ impl FcmClient {
     }
 }
 
+#[tonic::async_trait]
+impl NotificationBackend for FcmClient {
+    fn kind(&self) -> BackendKind {
+        BackendKind::Fcm
+    }
+
+    fn validate_target(&self, target: &str) -> Result<(), NotificationError> {
+        if target.is_empty() {
+            return Err(NotificationError::InvalidTarget(
+                "FCM device token is empty".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    async fn send(
+        &self,
+        target: &str,
+        notification: &Notification,
+    ) -> Result<(), NotificationError> {
+        let data = (!notification.data.is_empty()).then(|| notification.data.clone());
+        let msg = Self::build_message(target, &notification.title, &notification.body, data);
+        // Resolves to the inherent `FcmClient::send`
+        self.send(&msg).await
+    }
+}
+
 #[cfg(test)]
 #[allow(clippy::panic, clippy::expect_used, clippy::unwrap_used)]
 mod tests {
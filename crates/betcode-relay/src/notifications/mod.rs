@@ -1,16 +1,40 @@
-//! Push notification support (FCM).
+//! Push notification support.
 //!
 //! This module is only compiled when the `push-notifications` Cargo feature is
 //! enabled. It provides:
-//! - [`FcmClient`] for sending push notifications via the FCM HTTP v1 API
+//! - [`NotificationBackend`] implementations for FCM, Web Push, ntfy/Gotify
+//!   topics and signed webhooks, selected per device by its platform
 //! - [`NotificationServiceImpl`] gRPC service for device token registration
+//!   and notification preferences
+//! - [`NotificationDispatcher`] for turning daemon notification intents into
+//!   push notifications
+//! - [`NotificationActionServiceImpl`] gRPC service for answering permission
+//!   requests straight from a notification
 //! - Database queries for persisting device tokens
 
+pub mod actions;
+pub mod backend;
+pub mod dispatcher;
 pub mod fcm;
+pub mod preferences;
 pub mod service;
+pub mod topic;
+pub mod webhook;
+pub mod webpush;
 
+#[cfg(test)]
+#[allow(clippy::panic, clippy::expect_used, clippy::unwrap_used)]
+mod tests;
+
+pub use actions::NotificationActionServiceImpl;
+pub use backend::{BackendKind, Notification, NotificationBackend, NotificationBackends};
+pub use dispatcher::{NotificationDispatcher, NotificationIntent};
 pub use fcm::FcmClient;
+pub use preferences::Preferences;
 pub use service::NotificationServiceImpl;
+pub use topic::TopicBackend;
+pub use webhook::WebhookBackend;
+pub use webpush::WebPushBackend;
 
 /// Errors that can occur in the notification subsystem.
 #[derive(Debug, thiserror::Error)]
@@ -19,19 +43,32 @@ pub enum NotificationError {
     #[error("FCM credentials error: {0}")]
     Credentials(String),
 
-    /// HTTP request to FCM API failed.
-    #[error("FCM request error: {0}")]
+    /// A notification backend is misconfigured (bad VAPID key, weak webhook
+    /// secret, ...).
+    #[error("Notification backend configuration error: {0}")]
+    Config(String),
+
+    /// HTTP request to a notification backend failed.
+    #[error("Notification request error: {0}")]
     Request(String),
 
-    /// FCM API returned a non-success status code.
-    #[error("FCM API error (status {status}): {body}")]
+    /// A notification backend returned a non-success status code.
+    #[error("Notification API error (status {status}): {body}")]
     ApiError {
-        /// HTTP status code returned by FCM.
+        /// HTTP status code returned by the backend.
         status: u16,
-        /// Response body from FCM.
+        /// Response body from the backend.
         body: String,
     },
 
+    /// A device token is not usable by its platform's backend.
+    #[error("Invalid device token: {0}")]
+    InvalidTarget(String),
+
+    /// No enabled backend delivers to the device's platform.
+    #[error("Notification backend unavailable: {0}")]
+    Unavailable(String),
+
     /// Database operation failed.
     #[error("Database error: {0}")]
     Database(String),
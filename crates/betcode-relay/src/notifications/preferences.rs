@@ -0,0 +1,230 @@
+//! Per-user notification preferences: which events to notify about and
+//! when to stay quiet.
+
+use betcode_proto::v1::{NotificationEventKind, NotificationPreferences};
+
+use crate::storage::NotificationPreferencesRow;
+
+/// Every kind of event a notification can be sent for.
+pub const ALL_KINDS: [NotificationEventKind; 6] = [
+    NotificationEventKind::PermissionRequest,
+    NotificationEventKind::UserQuestion,
+    NotificationEventKind::TurnComplete,
+    NotificationEventKind::Error,
+    NotificationEventKind::SubagentCompleted,
+    NotificationEventKind::SubagentFailed,
+];
+
+const MINUTES_PER_DAY: u32 = 24 * 60;
+
+/// Largest UTC offset accepted, in minutes (UTC+14:00).
+const MAX_UTC_OFFSET_MINUTES: i32 = 14 * 60;
+
+/// Name of an event kind in tunnel frames and storage.
+pub const fn wire_name(kind: NotificationEventKind) -> &'static str {
+    match kind {
+        NotificationEventKind::Unspecified => "unspecified",
+        NotificationEventKind::PermissionRequest => "permission_request",
+        NotificationEventKind::UserQuestion => "user_question",
+        NotificationEventKind::TurnComplete => "turn_complete",
+        NotificationEventKind::Error => "error",
+        NotificationEventKind::SubagentCompleted => "subagent_completed",
+        NotificationEventKind::SubagentFailed => "subagent_failed",
+    }
+}
+
+/// Parse an event kind from its [`wire_name`].
+pub fn parse_kind(name: &str) -> Option<NotificationEventKind> {
+    ALL_KINDS.into_iter().find(|kind| wire_name(*kind) == name)
+}
+
+/// A user's notification preferences.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Preferences {
+    /// Event kinds to notify about.
+    pub events: Vec<NotificationEventKind>,
+    /// Start and end of the quiet period, in minutes after local midnight.
+    /// A start after the end wraps past midnight.
+    pub quiet_hours: Option<(u32, u32)>,
+    /// Offset of the user's local time from UTC.
+    pub utc_offset_minutes: i32,
+}
+
+impl Default for Preferences {
+    /// Every event kind, no quiet hours.
+    fn default() -> Self {
+        Self {
+            events: ALL_KINDS.to_vec(),
+            quiet_hours: None,
+            utc_offset_minutes: 0,
+        }
+    }
+}
+
+impl Preferences {
+    /// Preferences stored for a user. Unknown event kinds are skipped.
+    pub fn from_row(row: &NotificationPreferencesRow) -> Self {
+        let quiet_hours = match (row.quiet_hours_start, row.quiet_hours_end) {
+            (Some(start), Some(end)) => u32::try_from(start).ok().zip(u32::try_from(end).ok()),
+            _ => None,
+        };
+        Self {
+            events: row.events.split(',').filter_map(parse_kind).collect(),
+            quiet_hours,
+            utc_offset_minutes: i32::try_from(row.utc_offset_minutes).unwrap_or(0),
+        }
+    }
+
+    /// Validate preferences sent by a client.
+    pub fn from_proto(proto: &NotificationPreferences) -> Result<Self, String> {
+        let mut events = Vec::new();
+        for value in &proto.enabled_events {
+            match NotificationEventKind::try_from(*value) {
+                Ok(NotificationEventKind::Unspecified) | Err(_) => {
+                    return Err(format!("unknown notification event kind {value}"));
+                }
+                Ok(kind) if !events.contains(&kind) => events.push(kind),
+                Ok(_) => {}
+            }
+        }
+        let quiet_hours = if proto.quiet_hours_enabled {
+            if proto.quiet_hours_start >= MINUTES_PER_DAY
+                || proto.quiet_hours_end >= MINUTES_PER_DAY
+            {
+                return Err("quiet hours must be minutes after midnight (0-1439)".to_string());
+            }
+            Some((proto.quiet_hours_start, proto.quiet_hours_end))
+        } else {
+            None
+        };
+        if proto.utc_offset_minutes.abs() > MAX_UTC_OFFSET_MINUTES {
+            return Err("utc_offset_minutes must be within +/-14 hours".to_string());
+        }
+        Ok(Self {
+            events,
+            quiet_hours,
+            utc_offset_minutes: proto.utc_offset_minutes,
+        })
+    }
+
+    /// Preferences as returned to clients.
+    pub fn to_proto(&self) -> NotificationPreferences {
+        let (start, end) = self.quiet_hours.unwrap_or_default();
+        NotificationPreferences {
+            enabled_events: self.events.iter().map(|kind| *kind as i32).collect(),
+            quiet_hours_enabled: self.quiet_hours.is_some(),
+            quiet_hours_start: start,
+            quiet_hours_end: end,
+            utc_offset_minutes: self.utc_offset_minutes,
+        }
+    }
+
+    /// The `events` column value.
+    pub fn events_column(&self) -> String {
+        self.events
+            .iter()
+            .map(|kind| wire_name(*kind))
+            .collect::<Vec<_>>()
+            .join(",")
+    }
+
+    /// Whether to notify about `kind` at unix time `now`.
+    pub fn allows(&self, kind: NotificationEventKind, now: i64) -> bool {
+        self.events.contains(&kind) && !self.is_quiet_at(now)
+    }
+
+    /// Whether unix time `now` falls in the user's quiet hours.
+    pub fn is_quiet_at(&self, now: i64) -> bool {
+        let Some((start, end)) = self.quiet_hours else {
+            return false;
+        };
+        let local = (now.div_euclid(60) + i64::from(self.utc_offset_minutes))
+            .rem_euclid(i64::from(MINUTES_PER_DAY));
+        let (start, end) = (i64::from(start), i64::from(end));
+        if start <= end {
+            start <= local && local < end
+        } else {
+            local >= start || local < end
+        }
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::panic, clippy::expect_used, clippy::unwrap_used)]
+mod tests {
+    use super::*;
+
+    /// Unix time of 1970-01-01 at `hour:minute` UTC.
+    const fn at(hour: i64, minute: i64) -> i64 {
+        (hour * 60 + minute) * 60
+    }
+
+    #[test]
+    fn quiet_hours_wrap_past_midnight() {
+        let prefs = Preferences {
+            quiet_hours: Some((22 * 60, 7 * 60)),
+            ..Preferences::default()
+        };
+        assert!(prefs.is_quiet_at(at(23, 0)));
+        assert!(prefs.is_quiet_at(at(3, 30)));
+        assert!(!prefs.is_quiet_at(at(7, 0)));
+        assert!(!prefs.is_quiet_at(at(12, 0)));
+        assert!(!prefs.allows(NotificationEventKind::Error, at(23, 0)));
+        assert!(prefs.allows(NotificationEventKind::Error, at(12, 0)));
+    }
+
+    #[test]
+    fn quiet_hours_use_local_time() {
+        // 09:00-17:00 at UTC+2 is 07:00-15:00 UTC
+        let prefs = Preferences {
+            quiet_hours: Some((9 * 60, 17 * 60)),
+            utc_offset_minutes: 120,
+            ..Preferences::default()
+        };
+        assert!(prefs.is_quiet_at(at(7, 0)));
+        assert!(!prefs.is_quiet_at(at(15, 0)));
+    }
+
+    #[test]
+    fn proto_roundtrip_and_validation() {
+        let prefs = Preferences {
+            events: vec![
+                NotificationEventKind::PermissionRequest,
+                NotificationEventKind::Error,
+            ],
+            quiet_hours: Some((60, 120)),
+            utc_offset_minutes: -300,
+        };
+        assert_eq!(Preferences::from_proto(&prefs.to_proto()).unwrap(), prefs);
+        assert_eq!(prefs.events_column(), "permission_request,error");
+
+        let mut bad = prefs.to_proto();
+        bad.quiet_hours_end = MINUTES_PER_DAY;
+        assert!(Preferences::from_proto(&bad).is_err());
+
+        let mut bad = prefs.to_proto();
+        bad.enabled_events.push(0);
+        assert!(Preferences::from_proto(&bad).is_err());
+    }
+
+    #[test]
+    fn from_row_skips_unknown_kinds() {
+        let row = NotificationPreferencesRow {
+            user_id: "u1".into(),
+            events: "error,bogus,turn_complete".into(),
+            quiet_hours_start: Some(0),
+            quiet_hours_end: None,
+            utc_offset_minutes: 0,
+            updated_at: 0,
+        };
+        let prefs = Preferences::from_row(&row);
+        assert_eq!(
+            prefs.events,
+            vec![
+                NotificationEventKind::Error,
+                NotificationEventKind::TurnComplete
+            ]
+        );
+        assert_eq!(prefs.quiet_hours, None);
+    }
+}
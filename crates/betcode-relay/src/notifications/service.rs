@@ -1,66 +1,76 @@
 //! `NotificationService` gRPC implementation.
 //!
 //! Provides device token registration and unregistration for push
-//! notifications. Device tokens are stored in the relay database.
+//! notifications, per-user notification preferences, and discovery of the
+//! delivery backends this relay has enabled. Device tokens and preferences
+//! are stored in the relay database.
 
 use std::collections::HashMap;
+use std::sync::Arc;
 
 use tonic::{Request, Response, Status};
 use tracing::{info, instrument, warn};
 
 use betcode_proto::v1::notification_service_server::NotificationService;
 use betcode_proto::v1::{
-    DevicePlatform, RegisterDeviceRequest, RegisterDeviceResponse, UnregisterDeviceRequest,
-    UnregisterDeviceResponse,
+    DevicePlatform, GetNotificationBackendsRequest, GetNotificationBackendsResponse,
+    GetNotificationPreferencesRequest, NotificationPreferences, RegisterDeviceRequest,
+    RegisterDeviceResponse, UnregisterDeviceRequest, UnregisterDeviceResponse,
 };
 
+use crate::server::interceptor::extract_claims;
 use crate::storage::RelayDatabase;
 
-use super::{FcmClient, NotificationError};
+use super::backend::platform_name;
+use super::{Notification, NotificationBackends, NotificationError, Preferences};
 
 /// gRPC service for managing device token registrations.
 pub struct NotificationServiceImpl {
     db: RelayDatabase,
-    fcm: FcmClient,
+    backends: Arc<NotificationBackends>,
 }
 
 impl NotificationServiceImpl {
     /// Create a new `NotificationServiceImpl`.
-    pub const fn new(db: RelayDatabase, fcm: FcmClient) -> Self {
-        Self { db, fcm }
+    pub const fn new(db: RelayDatabase, backends: Arc<NotificationBackends>) -> Self {
+        Self { db, backends }
     }
 
     /// Send a push notification to a device.
     ///
-    /// Builds an FCM message from the given parameters and delegates to the
-    /// underlying [`FcmClient`].
+    /// Delegates to the backend serving `platform` (a database platform name
+    /// such as `android` or `web_push`).
     ///
     /// # Errors
     ///
-    /// Returns [`NotificationError`] if the FCM request fails or the API
-    /// returns a non-success status.
+    /// Returns [`NotificationError`] if no backend serves the platform, the
+    /// request fails or the backend returns a non-success status.
     #[instrument(skip(self, data), fields(device_token))]
     pub async fn send_notification(
         &self,
+        platform: &str,
         device_token: &str,
         title: &str,
         body: &str,
         data: Option<HashMap<String, String>>,
     ) -> Result<(), NotificationError> {
-        let msg = FcmClient::build_message(device_token, title, body, data);
-        self.fcm.send(&msg).await
+        let notification = Notification {
+            title: title.to_string(),
+            body: body.to_string(),
+            data: data.unwrap_or_default(),
+        };
+        self.backends
+            .send(platform, device_token, &notification)
+            .await
     }
 }
 
 /// Convert a `DevicePlatform` enum value to a database string.
 fn platform_to_str(platform: i32) -> Result<&'static str, Status> {
-    match DevicePlatform::try_from(platform) {
-        Ok(DevicePlatform::Android) => Ok("android"),
-        Ok(DevicePlatform::Ios) => Ok("ios"),
-        Ok(DevicePlatform::Unspecified) | Err(_) => {
-            Err(Status::invalid_argument("Platform must be ANDROID or IOS"))
-        }
-    }
+    DevicePlatform::try_from(platform)
+        .ok()
+        .and_then(platform_name)
+        .ok_or_else(|| Status::invalid_argument("Platform must be a known, specified platform"))
 }
 
 #[tonic::async_trait]
@@ -80,6 +90,13 @@ impl NotificationService for NotificationServiceImpl {
         }
 
         let platform = platform_to_str(req.platform)?;
+        let backend = self
+            .backends
+            .for_platform(platform)
+            .map_err(|e| Status::failed_precondition(e.to_string()))?;
+        backend
+            .validate_target(&req.device_token)
+            .map_err(|e| Status::invalid_argument(e.to_string()))?;
 
         let id = uuid::Uuid::new_v4().to_string();
 
@@ -133,6 +150,66 @@ impl NotificationService for NotificationServiceImpl {
             }
         }
     }
+
+    #[instrument(skip(self, _request), fields(rpc = "GetNotificationBackends"))]
+    async fn get_notification_backends(
+        &self,
+        _request: Request<GetNotificationBackendsRequest>,
+    ) -> Result<Response<GetNotificationBackendsResponse>, Status> {
+        Ok(Response::new(GetNotificationBackendsResponse {
+            platforms: self
+                .backends
+                .platforms()
+                .into_iter()
+                .map(|p| p as i32)
+                .collect(),
+            vapid_public_key: self
+                .backends
+                .vapid_public_key()
+                .unwrap_or_default()
+                .to_string(),
+        }))
+    }
+
+    #[instrument(skip(self, request), fields(rpc = "GetNotificationPreferences"))]
+    async fn get_notification_preferences(
+        &self,
+        request: Request<GetNotificationPreferencesRequest>,
+    ) -> Result<Response<NotificationPreferences>, Status> {
+        let user_id = extract_claims(&request)?.sub.clone();
+
+        let prefs = self
+            .db
+            .get_notification_preferences(&user_id)
+            .await
+            .map_err(|e| Status::internal(format!("Failed to load preferences: {e}")))?
+            .map(|row| Preferences::from_row(&row))
+            .unwrap_or_default();
+        Ok(Response::new(prefs.to_proto()))
+    }
+
+    #[instrument(skip(self, request), fields(rpc = "SetNotificationPreferences"))]
+    async fn set_notification_preferences(
+        &self,
+        request: Request<NotificationPreferences>,
+    ) -> Result<Response<NotificationPreferences>, Status> {
+        let user_id = extract_claims(&request)?.sub.clone();
+        let prefs = Preferences::from_proto(request.get_ref()).map_err(Status::invalid_argument)?;
+
+        self.db
+            .upsert_notification_preferences(
+                &user_id,
+                &prefs.events_column(),
+                prefs
+                    .quiet_hours
+                    .map(|(start, end)| (i64::from(start), i64::from(end))),
+                i64::from(prefs.utc_offset_minutes),
+            )
+            .await
+            .map_err(|e| Status::internal(format!("Failed to save preferences: {e}")))?;
+        info!(user_id = %user_id, "Notification preferences updated");
+        Ok(Response::new(prefs.to_proto()))
+    }
 }
 
 #[cfg(test)]
@@ -140,6 +217,8 @@ impl NotificationService for NotificationServiceImpl {
 mod tests {
     use super::*;
     use crate::notifications::fcm::ServiceAccountCredentials;
+    use crate::server::test_helpers::{test_claims, test_db_with_owner};
+    use betcode_proto::v1::NotificationEventKind;
 
     fn register_req(
         token: &str,
@@ -154,14 +233,21 @@ mod tests {
     }
 
     async fn test_service() -> NotificationServiceImpl {
-        let db = RelayDatabase::open_in_memory().await.unwrap();
+        let db = test_db_with_owner().await;
         let creds = ServiceAccountCredentials {
             project_id: "test-project".to_string(),
             client_email: "test@test.iam.gserviceaccount.com".to_string(),
             private_key: "test-key".to_string(),
         };
         let fcm = FcmClient::for_testing(creds);
-        NotificationServiceImpl::new(db, fcm)
+        let backends = NotificationBackends::new().with_backend(fcm);
+        NotificationServiceImpl::new(db, Arc::new(backends))
+    }
+
+    fn authed<T>(msg: T) -> Request<T> {
+        let mut req = Request::new(msg);
+        req.extensions_mut().insert(test_claims());
+        req
     }
 
     #[tokio::test]
@@ -283,7 +369,7 @@ mod tests {
         // (not a panic or compile error).
         let svc = test_service().await;
         let result = svc
-            .send_notification("device-tok", "Hello", "World", None)
+            .send_notification("android", "device-tok", "Hello", "World", None)
             .await;
 
         // We expect an error because no real FCM endpoint is reachable in tests.
@@ -297,7 +383,7 @@ mod tests {
         data.insert("key".to_string(), "value".to_string());
 
         let result = svc
-            .send_notification("device-tok", "Title", "Body", Some(data))
+            .send_notification("android", "device-tok", "Title", "Body", Some(data))
             .await;
 
         assert!(result.is_err(), "expected an error from unreachable FCM");
@@ -337,4 +423,91 @@ mod tests {
         let resp = svc.unregister_device(req).await.unwrap();
         assert!(!resp.into_inner().success);
     }
+
+    #[tokio::test]
+    async fn notification_preferences_default_to_everything() {
+        let svc = test_service().await;
+        let prefs = svc
+            .get_notification_preferences(authed(GetNotificationPreferencesRequest {}))
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(prefs.enabled_events.len(), 6);
+        assert!(!prefs.quiet_hours_enabled);
+    }
+
+    #[tokio::test]
+    async fn set_notification_preferences_persists() {
+        let svc = test_service().await;
+        let update = NotificationPreferences {
+            enabled_events: vec![NotificationEventKind::PermissionRequest as i32],
+            quiet_hours_enabled: true,
+            quiet_hours_start: 22 * 60,
+            quiet_hours_end: 7 * 60,
+            utc_offset_minutes: 60,
+        };
+        svc.set_notification_preferences(authed(update.clone()))
+            .await
+            .unwrap();
+
+        let prefs = svc
+            .get_notification_preferences(authed(GetNotificationPreferencesRequest {}))
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(prefs, update);
+    }
+
+    #[tokio::test]
+    async fn set_notification_preferences_rejects_bad_quiet_hours() {
+        let svc = test_service().await;
+        let err = svc
+            .set_notification_preferences(authed(NotificationPreferences {
+                quiet_hours_enabled: true,
+                quiet_hours_start: 24 * 60,
+                ..Default::default()
+            }))
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), tonic::Code::InvalidArgument);
+    }
+
+    #[tokio::test]
+    async fn notification_preferences_require_claims() {
+        let svc = test_service().await;
+        let err = svc
+            .get_notification_preferences(Request::new(GetNotificationPreferencesRequest {}))
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), tonic::Code::Internal);
+    }
+
+    #[tokio::test]
+    async fn register_device_for_disabled_backend_fails() {
+        let svc = test_service().await;
+        let err = svc
+            .register_device(register_req(
+                "https://ntfy.example/topic",
+                DevicePlatform::Ntfy,
+                "user-1",
+            ))
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), tonic::Code::FailedPrecondition);
+    }
+
+    #[tokio::test]
+    async fn get_notification_backends_lists_fcm_platforms() {
+        let svc = test_service().await;
+        let resp = svc
+            .get_notification_backends(Request::new(GetNotificationBackendsRequest {}))
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(
+            resp.platforms,
+            vec![DevicePlatform::Android as i32, DevicePlatform::Ios as i32]
+        );
+        assert!(resp.vapid_public_key.is_empty());
+    }
 }
@@ -0,0 +1,419 @@
+//! Delivery tests for the notification backends against a mock HTTP server.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use aes_gcm::aead::Aead;
+use aes_gcm::{Aes128Gcm, KeyInit, Nonce};
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use hkdf::Hkdf;
+use p256::ecdsa::signature::Verifier;
+use p256::ecdsa::{Signature, VerifyingKey};
+use p256::elliptic_curve::sec1::ToEncodedPoint;
+use p256::{PublicKey, SecretKey};
+use rand::RngCore;
+use rand::rngs::OsRng;
+use sha2::Sha256;
+use wiremock::matchers::{body_string, header, method, path, query_param};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+use super::webhook::{SIGNATURE_HEADER, TIMESTAMP_HEADER};
+use super::{
+    BackendKind, Notification, NotificationBackend, NotificationBackends, NotificationDispatcher,
+    NotificationError, NotificationIntent, TopicBackend, WebPushBackend, WebhookBackend,
+};
+use crate::storage::RelayDatabase;
+
+const WEBHOOK_SECRET: &str = "0123456789abcdef-secret";
+
+fn http() -> reqwest::Client {
+    let _ = rustls::crypto::ring::default_provider().install_default();
+    reqwest::Client::new()
+}
+
+fn notification(kind: &str) -> Notification {
+    Notification {
+        title: "Permission needed".into(),
+        body: "A tool call on laptop is waiting for your approval".into(),
+        data: HashMap::from([
+            ("kind".to_string(), kind.to_string()),
+            ("session_id".to_string(), "s1".to_string()),
+        ]),
+    }
+}
+
+/// A browser's side of a push subscription.
+struct UserAgent {
+    secret: SecretKey,
+    auth: [u8; 16],
+}
+
+impl UserAgent {
+    fn new() -> Self {
+        let mut auth = [0u8; 16];
+        OsRng.fill_bytes(&mut auth);
+        Self {
+            secret: SecretKey::random(&mut OsRng),
+            auth,
+        }
+    }
+
+    fn public_bytes(&self) -> Vec<u8> {
+        self.secret
+            .public_key()
+            .to_encoded_point(false)
+            .as_bytes()
+            .to_vec()
+    }
+
+    fn subscription(&self, endpoint: &str) -> String {
+        serde_json::json!({
+            "endpoint": endpoint,
+            "keys": {
+                "p256dh": URL_SAFE_NO_PAD.encode(self.public_bytes()),
+                "auth": URL_SAFE_NO_PAD.encode(self.auth),
+            },
+        })
+        .to_string()
+    }
+
+    /// Decrypt an `aes128gcm` body the way a browser would (RFC 8291).
+    fn decrypt(&self, body: &[u8]) -> Vec<u8> {
+        let salt = &body[..16];
+        let id_len = usize::from(body[20]);
+        let as_public = &body[21..21 + id_len];
+        let ciphertext = &body[21 + id_len..];
+
+        let shared = p256::ecdh::diffie_hellman(
+            self.secret.to_nonzero_scalar(),
+            PublicKey::from_sec1_bytes(as_public).unwrap().as_affine(),
+        );
+        let mut key_info = b"WebPush: info\0".to_vec();
+        key_info.extend_from_slice(&self.public_bytes());
+        key_info.extend_from_slice(as_public);
+        let mut ikm = [0u8; 32];
+        Hkdf::<Sha256>::new(Some(&self.auth), shared.raw_secret_bytes())
+            .expand(&key_info, &mut ikm)
+            .unwrap();
+
+        let hkdf = Hkdf::<Sha256>::new(Some(salt), &ikm);
+        let mut cek = [0u8; 16];
+        hkdf.expand(b"Content-Encoding: aes128gcm\0", &mut cek)
+            .unwrap();
+        let mut nonce = [0u8; 12];
+        hkdf.expand(b"Content-Encoding: nonce\0", &mut nonce)
+            .unwrap();
+
+        let mut record = Aes128Gcm::new_from_slice(&cek)
+            .unwrap()
+            .decrypt(Nonce::from_slice(&nonce), ciphertext)
+            .unwrap();
+        assert_eq!(record.pop(), Some(2), "last record delimiter");
+        record
+    }
+}
+
+// =============================================================================
+// ntfy / Gotify
+// =============================================================================
+
+#[tokio::test]
+async fn ntfy_publishes_body_with_title_and_priority() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/betcode-alice"))
+        .and(query_param("title", "Permission needed"))
+        .and(query_param("priority", "high"))
+        .and(query_param("tags", "permission_request"))
+        .and(body_string(
+            "A tool call on laptop is waiting for your approval",
+        ))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let backend = TopicBackend::ntfy(http());
+    let target = format!("{}/betcode-alice", server.uri());
+    backend.validate_target(&target).unwrap();
+    backend
+        .send(&target, &notification("permission_request"))
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn gotify_posts_json_message_with_extras() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/message"))
+        .and(query_param("token", "app-token"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let backend = TopicBackend::gotify(http());
+    let target = format!("{}/message?token=app-token", server.uri());
+    backend
+        .send(&target, &notification("turn_complete"))
+        .await
+        .unwrap();
+
+    let requests = server.received_requests().await.unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&requests[0].body).unwrap();
+    assert_eq!(json["title"], "Permission needed");
+    assert_eq!(json["priority"], 5);
+    assert_eq!(json["extras"]["betcode::event"]["session_id"], "s1");
+}
+
+#[tokio::test]
+async fn topic_rejects_non_http_targets() {
+    let backend = TopicBackend::ntfy(http());
+    let err = backend.validate_target("file:///etc/passwd").unwrap_err();
+    assert!(matches!(err, NotificationError::InvalidTarget(_)));
+    assert!(backend.validate_target("not a url").is_err());
+}
+
+#[tokio::test]
+async fn backend_error_status_is_reported() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .respond_with(ResponseTemplate::new(403).set_body_string("forbidden"))
+        .mount(&server)
+        .await;
+
+    let err = TopicBackend::ntfy(http())
+        .send(&format!("{}/topic", server.uri()), &notification("error"))
+        .await
+        .unwrap_err();
+    match err {
+        NotificationError::ApiError { status, body } => {
+            assert_eq!(status, 403);
+            assert_eq!(body, "forbidden");
+        }
+        other => panic!("expected ApiError, got {other}"),
+    }
+}
+
+// =============================================================================
+// Webhook
+// =============================================================================
+
+#[tokio::test]
+async fn webhook_signs_payload() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/hooks/betcode"))
+        .and(header("content-type", "application/json"))
+        .respond_with(ResponseTemplate::new(204))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let backend = WebhookBackend::new(http(), WEBHOOK_SECRET).unwrap();
+    backend
+        .send(
+            &format!("{}/hooks/betcode", server.uri()),
+            &notification("user_question"),
+        )
+        .await
+        .unwrap();
+
+    let request = &server.received_requests().await.unwrap()[0];
+    let timestamp: i64 = request.headers[TIMESTAMP_HEADER]
+        .to_str()
+        .unwrap()
+        .parse()
+        .unwrap();
+    let signature = request.headers[SIGNATURE_HEADER].to_str().unwrap();
+    assert_eq!(signature, backend.sign(timestamp, &request.body).unwrap());
+
+    let json: serde_json::Value = serde_json::from_slice(&request.body).unwrap();
+    assert_eq!(json["timestamp"], timestamp);
+    assert_eq!(json["data"]["kind"], "user_question");
+    assert!(
+        json["text"]
+            .as_str()
+            .unwrap()
+            .starts_with("Permission needed: ")
+    );
+}
+
+#[test]
+fn webhook_signature_depends_on_secret_and_timestamp() {
+    let a = WebhookBackend::new(http(), WEBHOOK_SECRET).unwrap();
+    let b = WebhookBackend::new(http(), "another-secret-of-length").unwrap();
+    let sig = a.sign(1, b"{}").unwrap();
+    assert!(sig.starts_with("sha256="));
+    assert_eq!(sig.len(), "sha256=".len() + 64);
+    assert_ne!(sig, b.sign(1, b"{}").unwrap());
+    assert_ne!(sig, a.sign(2, b"{}").unwrap());
+}
+
+#[test]
+fn webhook_rejects_short_secret() {
+    let err = WebhookBackend::new(http(), "short").unwrap_err();
+    assert!(matches!(err, NotificationError::Config(_)));
+}
+
+// =============================================================================
+// Web Push
+// =============================================================================
+
+fn vapid_key() -> String {
+    URL_SAFE_NO_PAD.encode(SecretKey::random(&mut OsRng).to_bytes())
+}
+
+#[tokio::test]
+async fn web_push_encrypts_payload_for_subscription() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/push/abc"))
+        .and(header("content-encoding", "aes128gcm"))
+        .and(header("urgency", "high"))
+        .respond_with(ResponseTemplate::new(201))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let backend = WebPushBackend::new(http(), &vapid_key(), "mailto:ops@example.com").unwrap();
+    let ua = UserAgent::new();
+    let subscription = ua.subscription(&format!("{}/push/abc", server.uri()));
+    backend.validate_target(&subscription).unwrap();
+    let sent = notification("permission_request");
+    backend.send(&subscription, &sent).await.unwrap();
+
+    let request = &server.received_requests().await.unwrap()[0];
+    let payload: serde_json::Value = serde_json::from_slice(&ua.decrypt(&request.body)).unwrap();
+    assert_eq!(payload["title"], sent.title);
+    assert_eq!(payload["data"]["kind"], "permission_request");
+}
+
+#[tokio::test]
+async fn web_push_sends_verifiable_vapid_token() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .respond_with(ResponseTemplate::new(201))
+        .mount(&server)
+        .await;
+
+    let backend = WebPushBackend::new(http(), &vapid_key(), "mailto:ops@example.com").unwrap();
+    let subscription = UserAgent::new().subscription(&format!("{}/push/abc", server.uri()));
+    backend
+        .send(&subscription, &notification("turn_complete"))
+        .await
+        .unwrap();
+
+    let request = &server.received_requests().await.unwrap()[0];
+    let auth = request.headers["authorization"].to_str().unwrap();
+    let (token, key) = auth
+        .strip_prefix("vapid t=")
+        .and_then(|rest| rest.split_once(", k="))
+        .unwrap();
+    assert_eq!(Some(key), backend.public_key());
+
+    let (signing_input, signature) = token.rsplit_once('.').unwrap();
+    let verifying_key =
+        VerifyingKey::from_sec1_bytes(&URL_SAFE_NO_PAD.decode(key).unwrap()).unwrap();
+    let signature = Signature::from_slice(&URL_SAFE_NO_PAD.decode(signature).unwrap()).unwrap();
+    verifying_key
+        .verify(signing_input.as_bytes(), &signature)
+        .unwrap();
+
+    let claims = signing_input.split('.').nth(1).unwrap();
+    let claims: serde_json::Value =
+        serde_json::from_slice(&URL_SAFE_NO_PAD.decode(claims).unwrap()).unwrap();
+    assert_eq!(claims["aud"], server.uri());
+    assert_eq!(claims["sub"], "mailto:ops@example.com");
+}
+
+#[test]
+fn web_push_rejects_bad_config_and_subscriptions() {
+    assert!(matches!(
+        WebPushBackend::new(http(), "not-a-key", "mailto:ops@example.com"),
+        Err(NotificationError::Config(_))
+    ));
+    assert!(matches!(
+        WebPushBackend::new(http(), &vapid_key(), "ops@example.com"),
+        Err(NotificationError::Config(_))
+    ));
+
+    let backend = WebPushBackend::new(http(), &vapid_key(), "mailto:ops@example.com").unwrap();
+    assert!(backend.validate_target("{}").is_err());
+    assert!(
+        backend
+            .validate_target(
+                r#"{"endpoint":"https://push.example/x","keys":{"p256dh":"AAAA","auth":"AAAA"}}"#
+            )
+            .is_err()
+    );
+}
+
+// =============================================================================
+// Routing
+// =============================================================================
+
+#[test]
+fn backends_route_by_platform() {
+    let backends = NotificationBackends::new().with_backend(TopicBackend::ntfy(http()));
+    assert_eq!(
+        backends.for_platform("ntfy").unwrap().kind(),
+        BackendKind::Ntfy
+    );
+    assert!(matches!(
+        backends.for_platform("gotify"),
+        Err(NotificationError::Unavailable(_))
+    ));
+    assert!(matches!(
+        backends.for_platform("pager"),
+        Err(NotificationError::Unavailable(_))
+    ));
+    assert!(backends.vapid_public_key().is_none());
+}
+
+#[tokio::test]
+async fn dispatcher_delivers_through_each_device_backend() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/topic"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/hook"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let db = RelayDatabase::open_in_memory().await.unwrap();
+    db.create_user("u1", "alice", "a@t.com", "hash")
+        .await
+        .unwrap();
+    db.create_machine("m1", "laptop", "u1", "{}").await.unwrap();
+    db.upsert_device_token("dt-1", "u1", &format!("{}/topic", server.uri()), "ntfy")
+        .await
+        .unwrap();
+    db.upsert_device_token("dt-2", "u1", &format!("{}/hook", server.uri()), "webhook")
+        .await
+        .unwrap();
+    // No Gotify backend is enabled, so this device is skipped
+    db.upsert_device_token("dt-3", "u1", &format!("{}/gotify", server.uri()), "gotify")
+        .await
+        .unwrap();
+
+    let backends = NotificationBackends::new()
+        .with_backend(TopicBackend::ntfy(http()))
+        .with_backend(WebhookBackend::new(http(), WEBHOOK_SECRET).unwrap());
+    let dispatcher = NotificationDispatcher::new(db, Arc::new(backends), 10);
+    let intent = NotificationIntent::from_params(&HashMap::from([
+        ("kind".to_string(), "turn_complete".to_string()),
+        ("session_id".to_string(), "s1".to_string()),
+    ]))
+    .unwrap();
+
+    assert_eq!(dispatcher.dispatch("u1", "m1", &intent).await.unwrap(), 2);
+}
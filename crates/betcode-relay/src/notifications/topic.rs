@@ -0,0 +1,114 @@
+//! ntfy and Gotify delivery.
+//!
+//! Both are self-hostable push servers with a plain HTTP publish API. The
+//! device token is the URL to publish to: an ntfy topic URL
+//! (`https://ntfy.example/betcode-alice`) or a Gotify message URL carrying the
+//! application token (`https://gotify.example/message?token=...`).
+
+use serde_json::json;
+
+use super::NotificationError;
+use super::backend::{BackendKind, Notification, NotificationBackend, check_response, parse_url};
+
+/// Gotify priority for notifications that need a response. Gotify clients
+/// alert audibly at 8 and above.
+const GOTIFY_PRIORITY_HIGH: u8 = 8;
+
+/// Gotify priority for everything else.
+const GOTIFY_PRIORITY_DEFAULT: u8 = 5;
+
+/// Publishes notifications to ntfy topics or Gotify applications.
+#[derive(Debug)]
+pub struct TopicBackend {
+    http: reqwest::Client,
+    kind: BackendKind,
+}
+
+impl TopicBackend {
+    /// Publish to ntfy topics.
+    pub const fn ntfy(http: reqwest::Client) -> Self {
+        Self {
+            http,
+            kind: BackendKind::Ntfy,
+        }
+    }
+
+    /// Publish to Gotify applications.
+    pub const fn gotify(http: reqwest::Client) -> Self {
+        Self {
+            http,
+            kind: BackendKind::Gotify,
+        }
+    }
+
+    /// ntfy: the body is the message, everything else goes in query
+    /// parameters (headers can't carry non-ASCII titles).
+    fn ntfy_request(
+        &self,
+        mut url: reqwest::Url,
+        notification: &Notification,
+    ) -> reqwest::RequestBuilder {
+        url.query_pairs_mut()
+            .append_pair("title", &notification.title)
+            .append_pair(
+                "priority",
+                if notification.needs_response() {
+                    "high"
+                } else {
+                    "default"
+                },
+            );
+        if let Some(kind) = notification.data.get("kind") {
+            url.query_pairs_mut().append_pair("tags", kind);
+        }
+        self.http.post(url).body(notification.body.clone())
+    }
+
+    /// Gotify: a JSON message, with the notification data under `extras`.
+    fn gotify_request(
+        &self,
+        url: reqwest::Url,
+        notification: &Notification,
+    ) -> reqwest::RequestBuilder {
+        let priority = if notification.needs_response() {
+            GOTIFY_PRIORITY_HIGH
+        } else {
+            GOTIFY_PRIORITY_DEFAULT
+        };
+        self.http.post(url).json(&json!({
+            "title": notification.title,
+            "message": notification.body,
+            "priority": priority,
+            "extras": { "betcode::event": notification.data },
+        }))
+    }
+}
+
+#[tonic::async_trait]
+impl NotificationBackend for TopicBackend {
+    fn kind(&self) -> BackendKind {
+        self.kind
+    }
+
+    fn validate_target(&self, target: &str) -> Result<(), NotificationError> {
+        parse_url(target).map(|_| ())
+    }
+
+    async fn send(
+        &self,
+        target: &str,
+        notification: &Notification,
+    ) -> Result<(), NotificationError> {
+        let url = parse_url(target)?;
+        let request = if self.kind == BackendKind::Gotify {
+            self.gotify_request(url, notification)
+        } else {
+            self.ntfy_request(url, notification)
+        };
+        let response = request
+            .send()
+            .await
+            .map_err(|e| NotificationError::Request(e.to_string()))?;
+        check_response(response, self.kind).await
+    }
+}
@@ -0,0 +1,116 @@
+//! Signed generic webhooks.
+//!
+//! The device token is a URL the relay POSTs JSON to, for chat bridges
+//! (Slack, Matrix, ...) or custom automation:
+//!
+//! ```json
+//! {"title": "...", "body": "...", "text": "title: body", "data": {...}, "timestamp": 1700000000}
+//! ```
+//!
+//! `text` makes the payload usable as-is by Slack-style incoming webhooks.
+//! Every request carries [`TIMESTAMP_HEADER`] and [`SIGNATURE_HEADER`]
+//! (`sha256=<hex>`, an HMAC-SHA256 of `"{timestamp}.{body}"` keyed with the
+//! relay's webhook secret) so receivers can reject forged or replayed calls.
+
+use std::fmt;
+
+use hmac::{Hmac, Mac};
+use serde_json::json;
+use sha2::Sha256;
+
+use betcode_core::db::unix_timestamp;
+
+use super::NotificationError;
+use super::backend::{BackendKind, Notification, NotificationBackend, check_response, parse_url};
+
+/// Header carrying the request signature.
+pub const SIGNATURE_HEADER: &str = "X-Betcode-Signature";
+
+/// Header carrying the unix time the signature covers.
+pub const TIMESTAMP_HEADER: &str = "X-Betcode-Timestamp";
+
+/// Shortest secret accepted, in bytes.
+const MIN_SECRET_LEN: usize = 16;
+
+/// POSTs signed JSON notifications to webhook URLs.
+pub struct WebhookBackend {
+    http: reqwest::Client,
+    secret: Vec<u8>,
+}
+
+impl fmt::Debug for WebhookBackend {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WebhookBackend").finish_non_exhaustive()
+    }
+}
+
+impl WebhookBackend {
+    /// Create a backend signing requests with `secret`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`NotificationError::Config`] if the secret is shorter than 16
+    /// bytes.
+    pub fn new(http: reqwest::Client, secret: &str) -> Result<Self, NotificationError> {
+        if secret.len() < MIN_SECRET_LEN {
+            return Err(NotificationError::Config(format!(
+                "webhook secret must be at least {MIN_SECRET_LEN} bytes"
+            )));
+        }
+        Ok(Self {
+            http,
+            secret: secret.as_bytes().to_vec(),
+        })
+    }
+
+    /// Signature header value for `body` sent at `timestamp`.
+    pub fn sign(&self, timestamp: i64, body: &[u8]) -> Result<String, NotificationError> {
+        let mut mac = Hmac::<Sha256>::new_from_slice(&self.secret)
+            .map_err(|e| NotificationError::Config(e.to_string()))?;
+        mac.update(timestamp.to_string().as_bytes());
+        mac.update(b".");
+        mac.update(body);
+        Ok(format!("sha256={:x}", mac.finalize().into_bytes()))
+    }
+}
+
+#[tonic::async_trait]
+impl NotificationBackend for WebhookBackend {
+    fn kind(&self) -> BackendKind {
+        BackendKind::Webhook
+    }
+
+    fn validate_target(&self, target: &str) -> Result<(), NotificationError> {
+        parse_url(target).map(|_| ())
+    }
+
+    async fn send(
+        &self,
+        target: &str,
+        notification: &Notification,
+    ) -> Result<(), NotificationError> {
+        let url = parse_url(target)?;
+        let timestamp = unix_timestamp();
+        let body = serde_json::to_vec(&json!({
+            "title": notification.title,
+            "body": notification.body,
+            "text": format!("{}: {}", notification.title, notification.body),
+            "data": notification.data,
+            "timestamp": timestamp,
+        }))
+        .map_err(|e| NotificationError::Request(e.to_string()))?;
+        let signature = self.sign(timestamp, &body)?;
+
+        let response = self
+            .http
+            .post(url)
+            .header("Content-Type", "application/json")
+            .header(TIMESTAMP_HEADER, timestamp.to_string())
+            .header(SIGNATURE_HEADER, signature)
+            .body(body)
+            .send()
+            .await
+            .map_err(|e| NotificationError::Request(e.to_string()))?;
+        check_response(response, BackendKind::Webhook).await
+    }
+}
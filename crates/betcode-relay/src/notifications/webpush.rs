@@ -0,0 +1,272 @@
+//! Web Push delivery for browsers and PWAs.
+//!
+//! Implements the push protocol (RFC 8030) with VAPID authentication
+//! (RFC 8292) and `aes128gcm` payload encryption (RFC 8291), so no
+//! third-party credentials are needed. The device token is the JSON push
+//! subscription the browser returns from `PushManager.subscribe()`, using the
+//! relay's VAPID public key as `applicationServerKey`.
+
+use std::fmt;
+
+use aes_gcm::aead::Aead;
+use aes_gcm::{Aes128Gcm, KeyInit, Nonce};
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use hkdf::Hkdf;
+use p256::PublicKey;
+use p256::ecdh::EphemeralSecret;
+use p256::ecdsa::signature::Signer;
+use p256::ecdsa::{Signature, SigningKey};
+use p256::elliptic_curve::sec1::ToEncodedPoint;
+use rand::RngCore;
+use rand::rngs::OsRng;
+use serde::Deserialize;
+use serde_json::json;
+use sha2::Sha256;
+
+use betcode_core::db::unix_timestamp;
+
+use super::NotificationError;
+use super::backend::{BackendKind, Notification, NotificationBackend, check_response, parse_url};
+
+/// Record size advertised in the `aes128gcm` header. The whole payload is
+/// sent as one record, so it must fit.
+const RECORD_SIZE: u32 = 4096;
+
+/// AES-GCM authentication tag length.
+const TAG_LEN: usize = 16;
+
+/// How long the push service should keep an undelivered message.
+const TTL_SECS: u32 = 24 * 60 * 60;
+
+/// Lifetime of VAPID tokens. RFC 8292 caps it at 24 hours.
+const VAPID_TOKEN_LIFETIME_SECS: i64 = 12 * 60 * 60;
+
+/// A browser push subscription (`PushSubscription.toJSON()`).
+#[derive(Debug, Clone, Deserialize)]
+pub struct Subscription {
+    pub endpoint: String,
+    pub keys: SubscriptionKeys,
+}
+
+/// Keys of a push subscription, base64url-encoded.
+#[derive(Debug, Clone, Deserialize)]
+pub struct SubscriptionKeys {
+    /// The user agent's P-256 public key, uncompressed.
+    pub p256dh: String,
+    /// The 16-byte authentication secret.
+    pub auth: String,
+}
+
+impl Subscription {
+    /// Parse and check a subscription stored as a device token.
+    ///
+    /// Returns the subscription with its decoded public key and auth secret.
+    fn parse(target: &str) -> Result<(Self, Vec<u8>, Vec<u8>), NotificationError> {
+        let sub: Self = serde_json::from_str(target).map_err(|e| {
+            NotificationError::InvalidTarget(format!("invalid push subscription: {e}"))
+        })?;
+        parse_url(&sub.endpoint)?;
+        let ua_public = decode(&sub.keys.p256dh, "p256dh")?;
+        PublicKey::from_sec1_bytes(&ua_public).map_err(|_| {
+            NotificationError::InvalidTarget("p256dh is not a P-256 public key".to_string())
+        })?;
+        let auth_secret = decode(&sub.keys.auth, "auth")?;
+        if auth_secret.len() != 16 {
+            return Err(NotificationError::InvalidTarget(
+                "auth secret must be 16 bytes".to_string(),
+            ));
+        }
+        Ok((sub, ua_public, auth_secret))
+    }
+}
+
+fn decode(value: &str, name: &str) -> Result<Vec<u8>, NotificationError> {
+    URL_SAFE_NO_PAD
+        .decode(value.trim_end_matches('='))
+        .map_err(|e| NotificationError::InvalidTarget(format!("{name} is not base64url: {e}")))
+}
+
+/// Sends notifications through browser push services.
+pub struct WebPushBackend {
+    http: reqwest::Client,
+    signing_key: SigningKey,
+    /// Uncompressed public key, base64url-encoded (the application server key).
+    public_key: String,
+    /// Contact for the push service operator (`mailto:` or `https:` URL).
+    subject: String,
+}
+
+impl fmt::Debug for WebPushBackend {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WebPushBackend")
+            .field("public_key", &self.public_key)
+            .field("subject", &self.subject)
+            .finish_non_exhaustive()
+    }
+}
+
+impl WebPushBackend {
+    /// Create a backend from a VAPID private key (the raw 32-byte P-256
+    /// scalar, base64url-encoded, as printed by common VAPID key generators).
+    ///
+    /// # Errors
+    ///
+    /// Returns [`NotificationError::Config`] if the key or subject is invalid.
+    pub fn new(
+        http: reqwest::Client,
+        private_key: &str,
+        subject: &str,
+    ) -> Result<Self, NotificationError> {
+        let bytes = URL_SAFE_NO_PAD
+            .decode(private_key.trim().trim_end_matches('='))
+            .map_err(|e| NotificationError::Config(format!("VAPID key is not base64url: {e}")))?;
+        let signing_key = SigningKey::from_slice(&bytes)
+            .map_err(|_| NotificationError::Config("VAPID key is not a P-256 key".to_string()))?;
+        if !(subject.starts_with("mailto:") || subject.starts_with("https://")) {
+            return Err(NotificationError::Config(
+                "VAPID subject must be a mailto: or https:// URL".to_string(),
+            ));
+        }
+        let public_key = URL_SAFE_NO_PAD.encode(
+            signing_key
+                .verifying_key()
+                .to_encoded_point(false)
+                .as_bytes(),
+        );
+        Ok(Self {
+            http,
+            signing_key,
+            public_key,
+            subject: subject.to_string(),
+        })
+    }
+
+    /// `Authorization` header value for a request to `endpoint`.
+    fn vapid_header(&self, endpoint: &reqwest::Url) -> Result<String, NotificationError> {
+        let header = URL_SAFE_NO_PAD.encode(br#"{"typ":"JWT","alg":"ES256"}"#);
+        let claims = serde_json::to_vec(&json!({
+            "aud": endpoint.origin().ascii_serialization(),
+            "exp": unix_timestamp() + VAPID_TOKEN_LIFETIME_SECS,
+            "sub": self.subject,
+        }))
+        .map_err(|e| NotificationError::Request(e.to_string()))?;
+        let signing_input = format!("{header}.{}", URL_SAFE_NO_PAD.encode(claims));
+        let signature: Signature = self.signing_key.sign(signing_input.as_bytes());
+        let token = format!(
+            "{signing_input}.{}",
+            URL_SAFE_NO_PAD.encode(signature.to_bytes())
+        );
+        Ok(format!("vapid t={token}, k={}", self.public_key))
+    }
+}
+
+/// Encrypt `plaintext` for a subscription as a single `aes128gcm` record
+/// (RFC 8291 section 3.4, RFC 8188 section 2).
+pub(crate) fn encrypt(
+    plaintext: &[u8],
+    ua_public: &[u8],
+    auth_secret: &[u8],
+) -> Result<Vec<u8>, NotificationError> {
+    // Content plus the 0x02 last-record delimiter plus the tag
+    if !u32::try_from(plaintext.len() + 1 + TAG_LEN).is_ok_and(|len| len <= RECORD_SIZE) {
+        return Err(NotificationError::Request(
+            "notification too large for Web Push".to_string(),
+        ));
+    }
+    let ua_key = PublicKey::from_sec1_bytes(ua_public).map_err(|_| {
+        NotificationError::InvalidTarget("p256dh is not a P-256 public key".to_string())
+    })?;
+
+    let as_secret = EphemeralSecret::random(&mut OsRng);
+    let as_public = as_secret.public_key().to_encoded_point(false);
+    let shared = as_secret.diffie_hellman(&ua_key);
+
+    // IKM = HKDF(auth_secret, ecdh_secret, "WebPush: info" || 0x00 || ua_public || as_public)
+    let mut key_info = b"WebPush: info\0".to_vec();
+    key_info.extend_from_slice(ua_public);
+    key_info.extend_from_slice(as_public.as_bytes());
+    let ikm: [u8; 32] = expand(
+        &Hkdf::<Sha256>::new(Some(auth_secret), shared.raw_secret_bytes()),
+        &key_info,
+    )?;
+
+    let mut salt = [0u8; 16];
+    OsRng.fill_bytes(&mut salt);
+    let hkdf = Hkdf::<Sha256>::new(Some(&salt), &ikm);
+    let cek: [u8; 16] = expand(&hkdf, b"Content-Encoding: aes128gcm\0")?;
+    let nonce: [u8; 12] = expand(&hkdf, b"Content-Encoding: nonce\0")?;
+
+    let mut record = plaintext.to_vec();
+    record.push(2);
+    let ciphertext = Aes128Gcm::new_from_slice(&cek)
+        .map_err(|e| NotificationError::Request(e.to_string()))?
+        .encrypt(Nonce::from_slice(&nonce), record.as_slice())
+        .map_err(|_| NotificationError::Request("payload encryption failed".to_string()))?;
+
+    // Header: salt || record size || key id length || key id (as_public)
+    let key_id = as_public.as_bytes();
+    let mut body = Vec::with_capacity(16 + 4 + 1 + key_id.len() + ciphertext.len());
+    body.extend_from_slice(&salt);
+    body.extend_from_slice(&RECORD_SIZE.to_be_bytes());
+    #[allow(clippy::cast_possible_truncation)] // 65 bytes
+    body.push(key_id.len() as u8);
+    body.extend_from_slice(key_id);
+    body.extend_from_slice(&ciphertext);
+    Ok(body)
+}
+
+fn expand<const N: usize>(hkdf: &Hkdf<Sha256>, info: &[u8]) -> Result<[u8; N], NotificationError> {
+    let mut out = [0u8; N];
+    hkdf.expand(info, &mut out)
+        .map_err(|e| NotificationError::Request(e.to_string()))?;
+    Ok(out)
+}
+
+#[tonic::async_trait]
+impl NotificationBackend for WebPushBackend {
+    fn kind(&self) -> BackendKind {
+        BackendKind::WebPush
+    }
+
+    fn public_key(&self) -> Option<&str> {
+        Some(&self.public_key)
+    }
+
+    fn validate_target(&self, target: &str) -> Result<(), NotificationError> {
+        Subscription::parse(target).map(|_| ())
+    }
+
+    async fn send(
+        &self,
+        target: &str,
+        notification: &Notification,
+    ) -> Result<(), NotificationError> {
+        let (sub, ua_public, auth_secret) = Subscription::parse(target)?;
+        let endpoint = parse_url(&sub.endpoint)?;
+        let payload = serde_json::to_vec(notification)
+            .map_err(|e| NotificationError::Request(e.to_string()))?;
+        let body = encrypt(&payload, &ua_public, &auth_secret)?;
+
+        let response = self
+            .http
+            .post(endpoint.clone())
+            .header("Authorization", self.vapid_header(&endpoint)?)
+            .header("TTL", TTL_SECS.to_string())
+            .header(
+                "Urgency",
+                if notification.needs_response() {
+                    "high"
+                } else {
+                    "normal"
+                },
+            )
+            .header("Content-Encoding", "aes128gcm")
+            .header("Content-Type", "application/octet-stream")
+            .body(body)
+            .send()
+            .await
+            .map_err(|e| NotificationError::Request(e.to_string()))?;
+        check_response(response, BackendKind::WebPush).await
+    }
+}
@@ -0,0 +1,187 @@
+//! Token buckets, alone and keyed by client.
+
+use std::time::Duration;
+
+use tokio::sync::Mutex;
+use tokio::time::Instant;
+
+use super::TrackedKeys;
+
+/// Sustained rate and burst size of a token bucket.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Rate {
+    per_second: f64,
+    burst: f64,
+}
+
+impl Rate {
+    /// `count` tokens per `period`, in bursts of up to `count`.
+    ///
+    /// Returns `None` for a zero count or period, which configuration uses
+    /// to mean "unlimited".
+    pub fn new(count: u32, period: Duration) -> Option<Self> {
+        if count == 0 || period.is_zero() {
+            return None;
+        }
+        let burst = f64::from(count);
+        Some(Self {
+            per_second: burst / period.as_secs_f64(),
+            burst,
+        })
+    }
+
+    /// `count` tokens per second, `None` if zero.
+    pub fn per_second(count: u32) -> Option<Self> {
+        Self::new(count, Duration::from_secs(1))
+    }
+
+    /// `count` tokens per minute, `None` if zero.
+    pub fn per_minute(count: u32) -> Option<Self> {
+        Self::new(count, Duration::from_mins(1))
+    }
+}
+
+/// A bucket refilled continuously at its [`Rate`], starting full.
+#[derive(Debug, Clone)]
+pub struct TokenBucket {
+    rate: Rate,
+    tokens: f64,
+    updated: Instant,
+}
+
+impl TokenBucket {
+    pub const fn new(rate: Rate, now: Instant) -> Self {
+        Self {
+            rate,
+            tokens: rate.burst,
+            updated: now,
+        }
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.tokens = elapsed
+            .mul_add(self.rate.per_second, self.tokens)
+            .min(self.rate.burst);
+        self.updated = now;
+    }
+
+    /// How long until `cost` tokens can be taken, or `None` if they can be
+    /// now.
+    ///
+    /// A cost above the burst size is allowed once the bucket is full; the
+    /// bucket then goes into debt, so the long-run rate still holds.
+    pub fn wait_time(&mut self, cost: u32, now: Instant) -> Option<Duration> {
+        self.refill(now);
+        let needed = f64::from(cost).min(self.rate.burst);
+        (self.tokens < needed)
+            .then(|| Duration::from_secs_f64((needed - self.tokens) / self.rate.per_second))
+    }
+
+    /// Take `cost` tokens, which [`wait_time`](Self::wait_time) has allowed.
+    pub fn take(&mut self, cost: u32) {
+        self.tokens -= f64::from(cost);
+    }
+
+    /// Take `cost` tokens, or return how long until that is possible.
+    pub fn try_take(&mut self, cost: u32, now: Instant) -> Result<(), Duration> {
+        self.wait_time(cost, now).map_or_else(
+            || {
+                self.take(cost);
+                Ok(())
+            },
+            Err,
+        )
+    }
+}
+
+/// One token bucket per key (client IP, username, ...).
+pub struct KeyedLimiter {
+    rate: Rate,
+    buckets: Mutex<TrackedKeys<TokenBucket>>,
+}
+
+impl KeyedLimiter {
+    pub fn new(rate: Rate) -> Self {
+        Self {
+            rate,
+            buckets: Mutex::new(TrackedKeys::new()),
+        }
+    }
+
+    /// Take one token from `key`'s bucket, or return how long until one is
+    /// available.
+    pub async fn check(&self, key: &str) -> Result<(), Duration> {
+        let now = Instant::now();
+        self.buckets
+            .lock()
+            .await
+            .get_or_insert_with(key, || TokenBucket::new(self.rate, now))
+            .try_take(1, now)
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::panic, clippy::expect_used, clippy::unwrap_used)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn zero_rate_is_unlimited() {
+        assert!(Rate::per_minute(0).is_none());
+        assert!(Rate::new(5, Duration::ZERO).is_none());
+    }
+
+    #[test]
+    fn bucket_allows_burst_then_refills() {
+        let start = Instant::now();
+        let mut bucket = TokenBucket::new(Rate::per_second(2).unwrap(), start);
+
+        assert!(bucket.try_take(1, start).is_ok());
+        assert!(bucket.try_take(1, start).is_ok());
+        let wait = bucket.try_take(1, start).unwrap_err();
+        assert_eq!(wait, Duration::from_millis(500));
+
+        assert!(bucket.try_take(1, start + wait).is_ok());
+    }
+
+    #[test]
+    fn oversized_cost_waits_for_full_bucket_then_goes_into_debt() {
+        let start = Instant::now();
+        let mut bucket = TokenBucket::new(Rate::per_second(100).unwrap(), start);
+
+        assert!(bucket.try_take(50, start).is_ok());
+        assert!(bucket.try_take(300, start).is_err());
+
+        let full = start + Duration::from_millis(500);
+        assert!(bucket.try_take(300, full).is_ok());
+        // 200 tokens of debt take 2s to repay before the next token
+        let wait = bucket.wait_time(1, full).unwrap();
+        assert_eq!(wait, Duration::from_millis(2010));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn keyed_limiter_tracks_keys_separately() {
+        let limiter = KeyedLimiter::new(Rate::per_minute(2).unwrap());
+
+        assert!(limiter.check("10.0.0.1").await.is_ok());
+        assert!(limiter.check("10.0.0.1").await.is_ok());
+        let wait = limiter.check("10.0.0.1").await.unwrap_err();
+        assert_eq!(wait, Duration::from_secs(30));
+        assert!(limiter.check("10.0.0.2").await.is_ok());
+
+        tokio::time::advance(wait).await;
+        assert!(limiter.check("10.0.0.1").await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn keyed_limiter_caps_tracked_keys() {
+        let limiter = KeyedLimiter::new(Rate::per_minute(1).unwrap());
+        for i in 0..=super::super::MAX_TRACKED_KEYS + 10 {
+            let _ = limiter
+                .check(&format!("10.0.{}.{}", i / 256, i % 256))
+                .await;
+        }
+        assert!(limiter.buckets.lock().await.len() <= super::super::MAX_TRACKED_KEYS);
+    }
+}
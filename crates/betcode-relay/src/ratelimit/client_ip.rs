@@ -0,0 +1,198 @@
+//! Client addresses behind trusted reverse proxies.
+//!
+//! Behind a load balancer every connection comes from the balancer, so the
+//! peer address says nothing about the client. The balancer records the
+//! client in a forwarding header; that header is only believed when the
+//! peer is a configured proxy, since clients can send it too.
+
+use std::net::{IpAddr, SocketAddr};
+
+use ipnet::IpNet;
+use tonic::metadata::MetadataMap;
+
+/// Header trusted proxies record the client address in.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum ForwardedHeader {
+    /// `X-Forwarded-For: client, proxy1, proxy2`.
+    #[default]
+    XForwardedFor,
+    /// RFC 7239 `Forwarded: for=client, for=proxy1`.
+    Forwarded,
+}
+
+/// Proxies whose forwarding header is believed.
+#[derive(Debug, Clone, Default)]
+pub struct TrustedProxies {
+    networks: Vec<IpNet>,
+    header: ForwardedHeader,
+}
+
+impl TrustedProxies {
+    pub const fn new(networks: Vec<IpNet>, header: ForwardedHeader) -> Self {
+        Self { networks, header }
+    }
+
+    fn trusts(&self, ip: IpAddr) -> bool {
+        self.networks.iter().any(|net| net.contains(&ip))
+    }
+
+    /// The client address of a request from `peer`.
+    ///
+    /// Walks the forwarding header from the nearest hop back while each hop
+    /// so far is a trusted proxy, and returns the first untrusted address.
+    /// Hops added before that are the client's own claims and are ignored.
+    /// An entry that is not an address stops the walk at the proxy that
+    /// added it.
+    pub fn client_ip(&self, peer: Option<SocketAddr>, metadata: &MetadataMap) -> Option<IpAddr> {
+        let mut ip = peer?.ip();
+        if !self.trusts(ip) {
+            return Some(ip);
+        }
+        let mut hops = self.hops(metadata);
+        while self.trusts(ip) {
+            match hops.pop() {
+                Some(Some(hop)) => ip = hop,
+                Some(None) | None => break,
+            }
+        }
+        Some(ip)
+    }
+
+    /// Addresses in the forwarding header, client first, `None` for entries
+    /// that are not addresses. Repeated headers are joined in order.
+    fn hops(&self, metadata: &MetadataMap) -> Vec<Option<IpAddr>> {
+        let name = match self.header {
+            ForwardedHeader::XForwardedFor => "x-forwarded-for",
+            ForwardedHeader::Forwarded => "forwarded",
+        };
+        let mut hops = Vec::new();
+        for value in metadata.get_all(name) {
+            let Ok(value) = value.to_str() else {
+                hops.push(None);
+                continue;
+            };
+            for element in value.split(',') {
+                hops.push(match self.header {
+                    ForwardedHeader::XForwardedFor => parse_node(element),
+                    ForwardedHeader::Forwarded => element
+                        .split(';')
+                        .filter_map(|pair| pair.split_once('='))
+                        .find(|(key, _)| key.trim().eq_ignore_ascii_case("for"))
+                        .and_then(|(_, node)| parse_node(node)),
+                });
+            }
+        }
+        hops
+    }
+}
+
+/// An address with an optional port, IPv6 optionally in brackets, and for
+/// `Forwarded` optionally quoted.
+fn parse_node(node: &str) -> Option<IpAddr> {
+    let node = node.trim().trim_matches('"');
+    if let Ok(ip) = node.parse() {
+        return Some(ip);
+    }
+    if let Ok(addr) = node.parse::<SocketAddr>() {
+        return Some(addr.ip());
+    }
+    node.strip_prefix('[')?.strip_suffix(']')?.parse().ok()
+}
+
+#[cfg(test)]
+#[allow(clippy::panic, clippy::expect_used, clippy::unwrap_used)]
+mod tests {
+    use super::*;
+
+    fn proxies(header: ForwardedHeader) -> TrustedProxies {
+        TrustedProxies::new(
+            vec![
+                "10.0.0.0/8".parse().unwrap(),
+                "fd00::1/128".parse().unwrap(),
+            ],
+            header,
+        )
+    }
+
+    fn peer(ip: &str) -> Option<SocketAddr> {
+        ip.parse().ok().map(|ip| SocketAddr::new(ip, 40000))
+    }
+
+    fn metadata(name: &'static str, values: &[&str]) -> MetadataMap {
+        let mut metadata = MetadataMap::new();
+        for value in values {
+            metadata.append(name, value.parse().unwrap());
+        }
+        metadata
+    }
+
+    fn ip(ip: &str) -> Option<IpAddr> {
+        ip.parse().ok()
+    }
+
+    #[test]
+    fn header_is_ignored_from_untrusted_peers() {
+        let proxies = proxies(ForwardedHeader::XForwardedFor);
+        let spoofed = metadata("x-forwarded-for", &["198.51.100.1"]);
+
+        assert_eq!(
+            proxies.client_ip(peer("203.0.113.7"), &spoofed),
+            ip("203.0.113.7")
+        );
+        assert_eq!(
+            TrustedProxies::default().client_ip(peer("10.0.0.2"), &spoofed),
+            ip("10.0.0.2")
+        );
+        assert_eq!(proxies.client_ip(None, &spoofed), None);
+    }
+
+    #[test]
+    fn x_forwarded_for_is_walked_back_through_trusted_hops() {
+        let proxies = proxies(ForwardedHeader::XForwardedFor);
+
+        // The client's own claim before its real address is skipped
+        let chain = metadata(
+            "x-forwarded-for",
+            &["192.0.2.99, 203.0.113.7", "10.1.2.3:8080"],
+        );
+        assert_eq!(
+            proxies.client_ip(peer("10.0.0.2"), &chain),
+            ip("203.0.113.7")
+        );
+
+        // Only proxies in the chain: the earliest one
+        let internal = metadata("x-forwarded-for", &["10.9.9.9"]);
+        assert_eq!(
+            proxies.client_ip(peer("10.0.0.2"), &internal),
+            ip("10.9.9.9")
+        );
+
+        // Garbage stops at the proxy that forwarded it
+        let garbage = metadata("x-forwarded-for", &["203.0.113.7, nonsense"]);
+        assert_eq!(
+            proxies.client_ip(peer("10.0.0.2"), &garbage),
+            ip("10.0.0.2")
+        );
+        assert_eq!(
+            proxies.client_ip(peer("10.0.0.2"), &MetadataMap::new()),
+            ip("10.0.0.2")
+        );
+    }
+
+    #[test]
+    fn forwarded_header_for_nodes_are_parsed() {
+        let proxies = proxies(ForwardedHeader::Forwarded);
+        let chain = metadata(
+            "forwarded",
+            &[r#"for="[2001:db8::7]:4711";proto=https, For="[fd00::1]""#],
+        );
+
+        assert_eq!(
+            proxies.client_ip(peer("10.0.0.2"), &chain),
+            ip("2001:db8::7")
+        );
+        // The other header is not the one configured
+        let xff = metadata("x-forwarded-for", &["203.0.113.7"]);
+        assert_eq!(proxies.client_ip(peer("10.0.0.2"), &xff), ip("10.0.0.2"));
+    }
+}
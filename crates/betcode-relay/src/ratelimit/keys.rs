@@ -0,0 +1,119 @@
+//! Per-key limiter state with a hard cap on the number of keys.
+
+use std::collections::{BTreeMap, HashMap};
+
+use super::MAX_TRACKED_KEYS;
+
+struct Entry<V> {
+    value: V,
+    /// When the key was last seen, as a [`TrackedKeys::tick`] value.
+    seen: u64,
+}
+
+/// State per key (client IP, username, machine).
+///
+/// Holds at most [`MAX_TRACKED_KEYS`] keys; a new key beyond that evicts the
+/// key seen least recently, so clients spraying keys cannot grow it without
+/// bound.
+pub struct TrackedKeys<V> {
+    entries: HashMap<String, Entry<V>>,
+    /// Keys by the tick they were last seen at, oldest first.
+    recency: BTreeMap<u64, String>,
+    tick: u64,
+    capacity: usize,
+}
+
+impl<V> TrackedKeys<V> {
+    pub fn new() -> Self {
+        Self::with_capacity(MAX_TRACKED_KEYS)
+    }
+
+    fn with_capacity(capacity: usize) -> Self {
+        Self {
+            entries: HashMap::new(),
+            recency: BTreeMap::new(),
+            tick: 0,
+            capacity: capacity.max(1),
+        }
+    }
+
+    /// Number of keys tracked.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Whether no key is tracked.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// `key`'s state, without counting as a use.
+    pub fn get(&self, key: &str) -> Option<&V> {
+        self.entries.get(key).map(|entry| &entry.value)
+    }
+
+    /// `key`'s state, created with `default` if the key is new.
+    pub fn get_or_insert_with(&mut self, key: &str, default: impl FnOnce() -> V) -> &mut V {
+        self.tick += 1;
+        let tick = self.tick;
+        if let Some(seen) = self.entries.get(key).map(|entry| entry.seen) {
+            self.recency.remove(&seen);
+        } else {
+            while self.entries.len() >= self.capacity {
+                let Some((_, oldest)) = self.recency.pop_first() else {
+                    break;
+                };
+                self.entries.remove(&oldest);
+            }
+        }
+        self.recency.insert(tick, key.to_string());
+        let entry = self
+            .entries
+            .entry(key.to_string())
+            .or_insert_with(|| Entry {
+                value: default(),
+                seen: tick,
+            });
+        entry.seen = tick;
+        &mut entry.value
+    }
+
+    /// Forget `key`.
+    pub fn remove(&mut self, key: &str) {
+        if let Some(entry) = self.entries.remove(key) {
+            self.recency.remove(&entry.seen);
+        }
+    }
+}
+
+impl<V> Default for TrackedKeys<V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::panic, clippy::expect_used, clippy::unwrap_used)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn evicts_least_recently_seen_key() {
+        let mut keys = TrackedKeys::with_capacity(2);
+        *keys.get_or_insert_with("a", || 0) += 1;
+        keys.get_or_insert_with("b", || 0);
+        // Seeing "a" again makes "b" the oldest
+        *keys.get_or_insert_with("a", || 0) += 1;
+        keys.get_or_insert_with("c", || 0);
+
+        assert_eq!(keys.len(), 2);
+        assert_eq!(keys.get("a"), Some(&2));
+        assert_eq!(keys.get("b"), None);
+        assert_eq!(keys.get("c"), Some(&0));
+
+        keys.remove("a");
+        keys.get_or_insert_with("d", || 0);
+        assert_eq!(keys.len(), 2);
+        assert_eq!(keys.get("c"), Some(&0));
+    }
+}
@@ -0,0 +1,174 @@
+//! Account lockout after repeated failed logins.
+
+use std::net::IpAddr;
+use std::time::Duration;
+
+use tokio::sync::Mutex;
+use tokio::time::Instant;
+
+use super::TrackedKeys;
+
+/// When and for how long failed logins lock an account.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LockoutPolicy {
+    /// Consecutive failures that lock the account.
+    pub threshold: u32,
+    /// Lock after reaching the threshold; each further failure doubles it.
+    pub base: Duration,
+    /// Longest lock. Failures are also forgotten once this long has passed
+    /// since the last one.
+    pub max: Duration,
+}
+
+impl LockoutPolicy {
+    /// Lock after the `failures`th consecutive failure, if any.
+    fn lock_duration(&self, failures: u32) -> Option<Duration> {
+        let beyond = failures.checked_sub(self.threshold)?;
+        Some(self.base.saturating_mul(1 << beyond.min(16)).min(self.max))
+    }
+}
+
+#[derive(Debug)]
+struct Failures {
+    count: u32,
+    last: Instant,
+    locked_until: Option<Instant>,
+}
+
+/// Tracks failed logins per username and client IP, whether or not the user
+/// exists, so a lock reveals nothing about which usernames are registered.
+///
+/// Failures from one address never lock the account for the others; a
+/// guesser spread over many addresses is left to the per-user rate limit.
+pub struct LoginLockout {
+    policy: LockoutPolicy,
+    accounts: Mutex<TrackedKeys<Failures>>,
+}
+
+impl LoginLockout {
+    pub fn new(policy: LockoutPolicy) -> Self {
+        Self {
+            policy,
+            accounts: Mutex::new(TrackedKeys::new()),
+        }
+    }
+
+    /// How much longer `username` is locked for `ip`, if it is.
+    pub async fn locked_for(&self, username: &str, ip: Option<IpAddr>) -> Option<Duration> {
+        let now = Instant::now();
+        let key = key(username, ip);
+        let until = self.accounts.lock().await.get(&key)?.locked_until?;
+        (until > now).then(|| until - now)
+    }
+
+    /// Record a failed login, returning the lock it triggered, if any.
+    pub async fn record_failure(&self, username: &str, ip: Option<IpAddr>) -> Option<Duration> {
+        let now = Instant::now();
+        let max = self.policy.max;
+        let key = key(username, ip);
+        let mut accounts = self.accounts.lock().await;
+        let failures = accounts.get_or_insert_with(&key, || Failures {
+            count: 0,
+            last: now,
+            locked_until: None,
+        });
+        if now.saturating_duration_since(failures.last) >= max {
+            failures.count = 0;
+        }
+        failures.count = failures.count.saturating_add(1);
+        failures.last = now;
+
+        let lock = self.policy.lock_duration(failures.count);
+        failures.locked_until = lock.map(|lock| now + lock);
+        drop(accounts);
+        lock
+    }
+
+    /// Forget `username`'s failures from `ip` after a successful login.
+    pub async fn record_success(&self, username: &str, ip: Option<IpAddr>) {
+        self.accounts.lock().await.remove(&key(username, ip));
+    }
+}
+
+/// Tracking key for `username` from `ip`; `-` (never an address) when the
+/// client IP is unknown.
+fn key(username: &str, ip: Option<IpAddr>) -> String {
+    ip.map_or_else(|| format!("- {username}"), |ip| format!("{ip} {username}"))
+}
+
+#[cfg(test)]
+#[allow(clippy::panic, clippy::expect_used, clippy::unwrap_used)]
+mod tests {
+    use super::*;
+
+    const POLICY: LockoutPolicy = LockoutPolicy {
+        threshold: 3,
+        base: Duration::from_secs(30),
+        max: Duration::from_mins(5),
+    };
+
+    const ALICE_IP: Option<IpAddr> = Some(IpAddr::V4(std::net::Ipv4Addr::new(203, 0, 113, 7)));
+
+    #[test]
+    fn lock_doubles_from_threshold_up_to_max() {
+        assert_eq!(POLICY.lock_duration(2), None);
+        assert_eq!(POLICY.lock_duration(3), Some(Duration::from_secs(30)));
+        assert_eq!(POLICY.lock_duration(4), Some(Duration::from_mins(1)));
+        assert_eq!(POLICY.lock_duration(7), Some(POLICY.max));
+        assert_eq!(POLICY.lock_duration(u32::MAX), Some(POLICY.max));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn failures_lock_until_backoff_expires() {
+        let lockout = LoginLockout::new(POLICY);
+
+        assert_eq!(lockout.record_failure("alice", ALICE_IP).await, None);
+        assert_eq!(lockout.record_failure("alice", ALICE_IP).await, None);
+        assert_eq!(lockout.locked_for("alice", ALICE_IP).await, None);
+        assert_eq!(
+            lockout.record_failure("alice", ALICE_IP).await,
+            Some(Duration::from_secs(30))
+        );
+        assert_eq!(
+            lockout.locked_for("alice", ALICE_IP).await,
+            Some(Duration::from_secs(30))
+        );
+        assert_eq!(lockout.locked_for("bob", ALICE_IP).await, None);
+
+        tokio::time::advance(Duration::from_secs(30)).await;
+        assert_eq!(lockout.locked_for("alice", ALICE_IP).await, None);
+        // The next failure locks for longer
+        assert_eq!(
+            lockout.record_failure("alice", ALICE_IP).await,
+            Some(Duration::from_mins(1))
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn success_and_quiet_period_reset_failures() {
+        let lockout = LoginLockout::new(POLICY);
+
+        lockout.record_failure("alice", ALICE_IP).await;
+        lockout.record_failure("alice", ALICE_IP).await;
+        lockout.record_success("alice", ALICE_IP).await;
+        assert_eq!(lockout.record_failure("alice", ALICE_IP).await, None);
+
+        lockout.record_failure("alice", ALICE_IP).await;
+        tokio::time::advance(POLICY.max).await;
+        assert_eq!(lockout.record_failure("alice", ALICE_IP).await, None);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn failures_only_lock_the_failing_client() {
+        let lockout = LoginLockout::new(POLICY);
+        let other = Some(IpAddr::V4(std::net::Ipv4Addr::new(198, 51, 100, 1)));
+
+        for _ in 0..3 {
+            lockout.record_failure("alice", other).await;
+        }
+        assert!(lockout.locked_for("alice", other).await.is_some());
+        assert_eq!(lockout.locked_for("alice", ALICE_IP).await, None);
+        assert_eq!(lockout.locked_for("alice", None).await, None);
+        assert_eq!(lockout.record_failure("alice", ALICE_IP).await, None);
+    }
+}
@@ -0,0 +1,24 @@
+//! Request throttling and abuse protection.
+//!
+//! Token buckets limit auth RPCs per client IP and per username, and frames
+//! and bytes sent down each machine's tunnel. Repeated failed logins lock the
+//! account, for the client that failed, with exponential backoff. Behind trusted proxies the client IP
+//! comes from their forwarding header. Throttled requests fail with
+//! `RESOURCE_EXHAUSTED` and a retry hint (see
+//! [`betcode_proto::rate_limit`]). All state is in memory and per instance.
+
+pub mod bucket;
+pub mod client_ip;
+pub mod keys;
+pub mod lockout;
+pub mod tunnel;
+
+pub use bucket::{KeyedLimiter, Rate, TokenBucket};
+pub use client_ip::{ForwardedHeader, TrustedProxies};
+pub use keys::TrackedKeys;
+pub use lockout::{LockoutPolicy, LoginLockout};
+pub use tunnel::TunnelLimiter;
+
+/// Keys a limiter tracks; beyond this the one seen least recently is
+/// forgotten.
+const MAX_TRACKED_KEYS: usize = 10_000;
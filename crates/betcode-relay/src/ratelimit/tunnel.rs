@@ -0,0 +1,110 @@
+//! Frame and byte rate limits on machine tunnels.
+
+use std::time::Duration;
+
+use tokio::sync::Mutex;
+use tokio::time::Instant;
+
+use super::{Rate, TokenBucket, TrackedKeys};
+
+struct MachineBuckets {
+    frames: Option<TokenBucket>,
+    bytes: Option<TokenBucket>,
+}
+
+/// Limits the frames, and the bytes they carry, that clients send down each
+/// machine's tunnel.
+pub struct TunnelLimiter {
+    frames: Option<Rate>,
+    bytes: Option<Rate>,
+    machines: Mutex<TrackedKeys<MachineBuckets>>,
+}
+
+impl TunnelLimiter {
+    /// A limiter allowing `frames` frames and `bytes` bytes per machine.
+    /// `None` leaves that dimension unlimited.
+    pub fn new(frames: Option<Rate>, bytes: Option<Rate>) -> Self {
+        Self {
+            frames,
+            bytes,
+            machines: Mutex::new(TrackedKeys::new()),
+        }
+    }
+
+    /// Whether either limit is set.
+    pub const fn is_enabled(&self) -> bool {
+        self.frames.is_some() || self.bytes.is_some()
+    }
+
+    /// Account a frame of `len` bytes for `machine_id`, or return how long
+    /// until it would be allowed. A rejected frame is not counted.
+    pub async fn check(&self, machine_id: &str, len: usize) -> Result<(), Duration> {
+        let now = Instant::now();
+        let cost = u32::try_from(len).unwrap_or(u32::MAX);
+        let mut machines = self.machines.lock().await;
+        let buckets = machines.get_or_insert_with(machine_id, || MachineBuckets {
+            frames: self.frames.map(|rate| TokenBucket::new(rate, now)),
+            bytes: self.bytes.map(|rate| TokenBucket::new(rate, now)),
+        });
+
+        let frame_wait = buckets.frames.as_mut().and_then(|b| b.wait_time(1, now));
+        let byte_wait = buckets.bytes.as_mut().and_then(|b| b.wait_time(cost, now));
+        let result = frame_wait.max(byte_wait).map_or_else(
+            || {
+                if let Some(bucket) = &mut buckets.frames {
+                    bucket.take(1);
+                }
+                if let Some(bucket) = &mut buckets.bytes {
+                    bucket.take(cost);
+                }
+                Ok(())
+            },
+            Err,
+        );
+        drop(machines);
+        result
+    }
+
+    /// Wait until a frame of `len` bytes is allowed for `machine_id`, then
+    /// account it.
+    pub async fn acquire(&self, machine_id: &str, len: usize) {
+        while let Err(wait) = self.check(machine_id, len).await {
+            tokio::time::sleep(wait).await;
+        }
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::panic, clippy::expect_used, clippy::unwrap_used)]
+mod tests {
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn frames_and_bytes_are_both_limited() {
+        let limiter = TunnelLimiter::new(Rate::per_second(2), Rate::per_second(1000));
+
+        assert!(limiter.check("m1", 600).await.is_ok());
+        // The byte budget is short, and the frame is not counted
+        assert_eq!(
+            limiter.check("m1", 600).await,
+            Err(Duration::from_millis(200))
+        );
+        assert!(limiter.check("m1", 10).await.is_ok());
+        // Now the frame budget is spent
+        assert_eq!(
+            limiter.check("m1", 10).await,
+            Err(Duration::from_millis(500))
+        );
+        assert!(limiter.check("m2", 10).await.is_ok());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn acquire_waits_for_budget() {
+        let limiter = TunnelLimiter::new(Rate::per_second(1), None);
+
+        limiter.acquire("m1", 0).await;
+        let start = Instant::now();
+        limiter.acquire("m1", 0).await;
+        assert_eq!(start.elapsed(), Duration::from_secs(1));
+    }
+}
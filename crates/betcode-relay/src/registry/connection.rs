@@ -3,10 +3,13 @@
 use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 
+use prost::Message;
 use tokio::sync::{RwLock, mpsc, oneshot};
 use tracing::{debug, info, warn};
 
-use betcode_proto::v1::TunnelFrame;
+use betcode_proto::v1::{FrameType, TunnelFrame};
+
+use crate::usage::{CONTROL_METHOD, Direction, UNKNOWN_METHOD, UsageMeter};
 
 /// Holds an active tunnel connection to a daemon.
 pub struct TunnelConnection {
@@ -23,6 +26,11 @@ pub struct TunnelConnection {
     /// Request IDs of streams whose receivers were dropped (client disconnected).
     /// Used to silently drop subsequent frames instead of warning.
     cancelled_streams: Arc<RwLock<HashSet<String>>>,
+    /// Meter accounting the traffic through this tunnel, if any.
+    usage: Option<Arc<UsageMeter>>,
+    /// Methods of requests in flight, so the frames that follow a request
+    /// are accounted to its method.
+    methods: Arc<RwLock<HashMap<String, String>>>,
 }
 
 impl TunnelConnection {
@@ -34,9 +42,18 @@ impl TunnelConnection {
             pending: Arc::new(RwLock::new(HashMap::new())),
             stream_pending: Arc::new(RwLock::new(HashMap::new())),
             cancelled_streams: Arc::new(RwLock::new(HashSet::new())),
+            usage: None,
+            methods: Arc::new(RwLock::new(HashMap::new())),
         }
     }
 
+    /// Account the frames crossing this tunnel in `meter`.
+    #[must_use]
+    pub fn with_usage(mut self, meter: Arc<UsageMeter>) -> Self {
+        self.usage = Some(meter);
+        self
+    }
+
     /// Send a frame to the daemon through the tunnel.
     pub async fn send_frame(
         &self,
@@ -118,6 +135,75 @@ impl TunnelConnection {
         self.cancelled_streams.write().await.remove(request_id);
     }
 
+    // --- Usage ---
+
+    /// Account a frame sent down the tunnel to the daemon. A request frame
+    /// names the method the rest of its request's frames are accounted to.
+    pub async fn record_sent(&self, frame: &TunnelFrame) {
+        let Some(usage) = &self.usage else {
+            return;
+        };
+        let request_method = match &frame.payload {
+            Some(betcode_proto::v1::tunnel_frame::Payload::StreamData(p))
+                if frame.frame_type == FrameType::Request as i32 && !p.method.is_empty() =>
+            {
+                Some(p.method.clone())
+            }
+            _ => None,
+        };
+        let method = if let Some(method) = request_method {
+            self.methods
+                .write()
+                .await
+                .insert(frame.request_id.clone(), method.clone());
+            method
+        } else {
+            self.method_of(frame).await
+        };
+        usage.record(
+            &self.machine_id,
+            &self.owner_id,
+            &method,
+            Direction::Sent,
+            frame.encoded_len(),
+        );
+    }
+
+    /// Account a frame the daemon sent up the tunnel. The request's method
+    /// is forgotten once its final frame arrives.
+    pub async fn record_received(&self, frame: &TunnelFrame) {
+        let Some(usage) = &self.usage else {
+            return;
+        };
+        let method = self.method_of(frame).await;
+        let is_final = [FrameType::Response, FrameType::StreamEnd, FrameType::Error]
+            .iter()
+            .any(|t| *t as i32 == frame.frame_type);
+        if is_final {
+            self.methods.write().await.remove(&frame.request_id);
+        }
+        usage.record(
+            &self.machine_id,
+            &self.owner_id,
+            &method,
+            Direction::Received,
+            frame.encoded_len(),
+        );
+    }
+
+    /// The method `frame` is accounted to.
+    async fn method_of(&self, frame: &TunnelFrame) -> String {
+        if frame.frame_type == FrameType::Control as i32 {
+            return CONTROL_METHOD.to_string();
+        }
+        self.methods
+            .read()
+            .await
+            .get(&frame.request_id)
+            .cloned()
+            .unwrap_or_else(|| UNKNOWN_METHOD.to_string())
+    }
+
     // --- Shared ---
 
     /// Cancel all pending requests (both unary and streaming).
@@ -125,6 +211,7 @@ impl TunnelConnection {
         self.pending.write().await.clear();
         self.stream_pending.write().await.clear();
         self.cancelled_streams.write().await.clear();
+        self.methods.write().await.clear();
         debug!(machine_id = %self.machine_id, "All pending requests cancelled");
     }
 
@@ -143,15 +230,25 @@ impl TunnelConnection {
 #[derive(Clone)]
 pub struct ConnectionRegistry {
     connections: Arc<RwLock<HashMap<String, Arc<TunnelConnection>>>>,
+    usage: Option<Arc<UsageMeter>>,
 }
 
 impl ConnectionRegistry {
     pub fn new() -> Self {
         Self {
             connections: Arc::new(RwLock::new(HashMap::new())),
+            usage: None,
         }
     }
 
+    /// Account the traffic of every tunnel registered from now on in
+    /// `meter`.
+    #[must_use]
+    pub fn with_usage(mut self, meter: Arc<UsageMeter>) -> Self {
+        self.usage = Some(meter);
+        self
+    }
+
     /// Register a tunnel connection for a machine.
     pub async fn register(
         &self,
@@ -159,11 +256,11 @@ impl ConnectionRegistry {
         owner_id: String,
         frame_tx: mpsc::Sender<TunnelFrame>,
     ) -> Arc<TunnelConnection> {
-        let conn = Arc::new(TunnelConnection::new(
-            machine_id.clone(),
-            owner_id,
-            frame_tx,
-        ));
+        let mut conn = TunnelConnection::new(machine_id.clone(), owner_id, frame_tx);
+        if let Some(meter) = &self.usage {
+            conn = conn.with_usage(Arc::clone(meter));
+        }
+        let conn = Arc::new(conn);
         self.connections
             .write()
             .await
@@ -214,7 +311,6 @@ impl Default for ConnectionRegistry {
 #[allow(clippy::panic, clippy::expect_used, clippy::unwrap_used)]
 mod tests {
     use super::*;
-    use betcode_proto::v1::FrameType;
 
     #[tokio::test]
     async fn register_and_get_connection() {
@@ -436,4 +532,52 @@ mod tests {
         conn.clear_cancelled_stream("drop").await;
         assert!(!conn.is_cancelled_stream("drop").await);
     }
+
+    #[tokio::test]
+    async fn usage_follows_request_method() {
+        let meter = Arc::new(UsageMeter::new());
+        let registry = ConnectionRegistry::new().with_usage(Arc::clone(&meter));
+        let (tx, _rx) = mpsc::channel(16);
+        let conn = registry.register("m1".into(), "u1".into(), tx).await;
+
+        let request = crate::router::forwarder::build_request_frame(
+            "req-1",
+            "Agent/Converse",
+            vec![0; 10],
+            HashMap::new(),
+        );
+        conn.record_sent(&request).await;
+        let client_frame = TunnelFrame {
+            request_id: "req-1".into(),
+            frame_type: FrameType::StreamData as i32,
+            ..Default::default()
+        };
+        conn.record_sent(&client_frame).await;
+        let end = TunnelFrame {
+            request_id: "req-1".into(),
+            frame_type: FrameType::StreamEnd as i32,
+            ..Default::default()
+        };
+        conn.record_received(&end).await;
+        // The request is over, so late frames are unattributed
+        conn.record_received(&end).await;
+        let heartbeat = TunnelFrame {
+            frame_type: FrameType::Control as i32,
+            ..Default::default()
+        };
+        conn.record_received(&heartbeat).await;
+
+        let mut rows = meter.take();
+        rows.sort_by(|a, b| a.method.cmp(&b.method));
+        let methods: Vec<_> = rows.iter().map(|r| r.method.as_str()).collect();
+        assert_eq!(
+            methods,
+            vec![CONTROL_METHOD, UNKNOWN_METHOD, "Agent/Converse"]
+        );
+        let converse = &rows[2];
+        assert_eq!(converse.owner_id, "u1");
+        assert_eq!(converse.frames_sent, 2);
+        assert_eq!(converse.frames_received, 1);
+        assert!(converse.bytes_sent > 10);
+    }
 }
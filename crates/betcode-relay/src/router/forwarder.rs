@@ -4,16 +4,22 @@ use std::collections::HashMap;
 use std::sync::Arc;
 use std::time::Duration;
 
+use prost::Message;
 use tokio::sync::mpsc;
 use tokio::time::timeout;
-use tracing::{info, warn};
+use tracing::{debug, info, warn};
 
 use betcode_proto::v1::{
-    EncryptedPayload, FrameType, StreamPayload, TunnelError, TunnelErrorCode, TunnelFrame,
+    EncryptedPayload, FrameType, PeerRequest, StreamPayload, TunnelError, TunnelErrorCode,
+    TunnelFrame,
 };
 
 use crate::buffer::BufferManager;
+use crate::cluster::Cluster;
+use crate::ratelimit::TunnelLimiter;
 use crate::registry::ConnectionRegistry;
+use crate::storage::RelayInstance;
+use crate::wake::{WakeError, WakeManager};
 
 /// Routes requests through tunnel connections to daemons.
 #[derive(Clone)]
@@ -21,8 +27,14 @@ pub struct RequestRouter {
     registry: Arc<ConnectionRegistry>,
     buffer: Arc<BufferManager>,
     request_timeout: Duration,
+    wake: Option<Arc<WakeManager>>,
+    cluster: Option<Arc<Cluster>>,
+    limiter: Option<Arc<TunnelLimiter>>,
 }
 
+/// Client frames a throttled bidi stream queues before it blocks the sender.
+const THROTTLED_STREAM_BUFFER: usize = 32;
+
 /// Build a `TunnelFrame` request envelope.
 ///
 /// This is the common frame structure used by `forward_request`,
@@ -52,6 +64,43 @@ pub(crate) fn build_request_frame(
     }
 }
 
+/// A sender whose frames reach `tunnel_tx` no faster than `limiter` allows
+/// for `machine_id`. Frames over the limit are delayed, not dropped.
+fn throttled_sender(
+    limiter: Arc<TunnelLimiter>,
+    machine_id: &str,
+    tunnel_tx: mpsc::Sender<TunnelFrame>,
+) -> mpsc::Sender<TunnelFrame> {
+    let (tx, mut rx) = mpsc::channel::<TunnelFrame>(THROTTLED_STREAM_BUFFER);
+    let machine_id = machine_id.to_string();
+    tokio::spawn(async move {
+        while let Some(frame) = rx.recv().await {
+            limiter.acquire(&machine_id, frame.encoded_len()).await;
+            if tunnel_tx.send(frame).await.is_err() {
+                break;
+            }
+        }
+    });
+    tx
+}
+
+/// Build the request handed to the peer relay holding a machine's tunnel.
+fn peer_request(
+    machine_id: &str,
+    request_id: &str,
+    method: &str,
+    data: Vec<u8>,
+    metadata: HashMap<String, String>,
+) -> PeerRequest {
+    PeerRequest {
+        machine_id: machine_id.to_string(),
+        request_id: request_id.to_string(),
+        method: method.to_string(),
+        data,
+        metadata,
+    }
+}
+
 impl RequestRouter {
     pub const fn new(
         registry: Arc<ConnectionRegistry>,
@@ -62,13 +111,96 @@ impl RequestRouter {
             registry,
             buffer,
             request_timeout,
+            wake: None,
+            cluster: None,
+            limiter: None,
+        }
+    }
+
+    /// Wake machines that have Wake-on-LAN configured when a request is
+    /// buffered for them.
+    #[must_use]
+    pub fn with_wake(mut self, wake: Arc<WakeManager>) -> Self {
+        self.wake = Some(wake);
+        self
+    }
+
+    /// Forward requests for machines connected to other relay instances to
+    /// those instances.
+    #[must_use]
+    pub fn with_cluster(mut self, cluster: Arc<Cluster>) -> Self {
+        self.cluster = Some(cluster);
+        self
+    }
+
+    /// Limit the frames and bytes clients send down each machine's tunnel.
+    #[must_use]
+    pub fn with_rate_limits(mut self, limiter: Arc<TunnelLimiter>) -> Self {
+        self.limiter = Some(limiter);
+        self
+    }
+
+    /// A copy of this router that only reaches machines connected to this
+    /// instance. Used to serve peer requests, so they are never forwarded on.
+    #[must_use]
+    pub fn local_only(&self) -> Self {
+        Self {
+            cluster: None,
+            ..self.clone()
+        }
+    }
+
+    /// The peer instance holding `machine_id`'s tunnel, if clustering is on.
+    async fn remote(&self, machine_id: &str) -> Option<(&Arc<Cluster>, RelayInstance)> {
+        let cluster = self.cluster.as_ref()?;
+        match cluster.directory.locate(machine_id).await {
+            Ok(instance) => instance.map(|i| (cluster, i)),
+            Err(e) => {
+                warn!(machine_id = %machine_id, error = %e, "Failed to look up machine in cluster directory");
+                None
+            }
+        }
+    }
+
+    /// Account `frame` against `machine_id`'s tunnel limits, rejecting it if
+    /// they are exceeded.
+    async fn check_rate(&self, machine_id: &str, frame: &TunnelFrame) -> Result<(), RouterError> {
+        let Some(limiter) = &self.limiter else {
+            return Ok(());
+        };
+        limiter
+            .check(machine_id, frame.encoded_len())
+            .await
+            .map_err(|retry_after| {
+                warn!(machine_id = %machine_id, request_id = %frame.request_id, "Tunnel rate limit exceeded");
+                RouterError::RateLimited {
+                    machine_id: machine_id.to_string(),
+                    retry_after,
+                }
+            })
+    }
+
+    /// Ask the machine's waker to wake it, if it has one.
+    async fn wake_for_buffered(&self, machine_id: &str) {
+        let Some(wake) = &self.wake else {
+            return;
+        };
+        match wake.wake(machine_id).await {
+            Ok(outcome) => {
+                debug!(machine_id = %machine_id, ?outcome, "Wake triggered by buffered request");
+            }
+            Err(WakeError::NotConfigured(_)) => {}
+            Err(e) => {
+                warn!(machine_id = %machine_id, error = %e, "Failed to wake offline machine");
+            }
         }
     }
 
     /// Forward a request to a machine and wait for the response.
     ///
-    /// If the machine is offline, the request is buffered for later delivery
-    /// and a `Buffered` error is returned.
+    /// If the machine is connected to another relay instance, the request is
+    /// forwarded there. If it is offline, the request is buffered for later
+    /// delivery and a `Buffered` error is returned.
     pub async fn forward_request(
         &self,
         machine_id: &str,
@@ -78,6 +210,21 @@ impl RequestRouter {
         metadata: std::collections::HashMap<String, String>,
     ) -> Result<TunnelFrame, RouterError> {
         let Some(conn) = self.registry.get(machine_id).await else {
+            if let Some((cluster, peer)) = self.remote(machine_id).await {
+                debug!(
+                    machine_id = %machine_id,
+                    request_id = %request_id,
+                    peer = %peer.id,
+                    "Forwarding request to peer relay"
+                );
+                let request = peer_request(machine_id, request_id, method, data, metadata);
+                return timeout(
+                    self.request_timeout,
+                    cluster.peers.forward_request(&peer.address, request),
+                )
+                .await
+                .unwrap_or_else(|_| Err(RouterError::Timeout(request_id.to_string())));
+            }
             // Buffer the request for when the machine reconnects
             let metadata_json =
                 serde_json::to_string(&metadata).unwrap_or_else(|_| "{}".to_string());
@@ -93,6 +240,7 @@ impl RequestRouter {
                         buffer_id = buf_id,
                         "Request buffered for offline machine"
                     );
+                    self.wake_for_buffered(machine_id).await;
                     return Err(RouterError::Buffered(machine_id.to_string()));
                 }
                 Err(e) => {
@@ -107,6 +255,7 @@ impl RequestRouter {
         };
 
         let frame = build_request_frame(request_id, method, data, metadata);
+        self.check_rate(machine_id, &frame).await?;
 
         // Register pending response before sending
         let response_rx = conn.register_pending(request_id.to_string()).await;
@@ -160,6 +309,7 @@ impl RequestRouter {
             .ok_or_else(|| RouterError::MachineOffline(machine_id.to_string()))?;
 
         let frame = build_request_frame(request_id, method, data, metadata);
+        self.check_rate(machine_id, &frame).await?;
         let stream_rx = conn.register_stream_pending(request_id.to_string()).await;
 
         conn.send_frame(frame)
@@ -182,6 +332,12 @@ impl RequestRouter {
         data: Vec<u8>,
         metadata: std::collections::HashMap<String, String>,
     ) -> Result<mpsc::Receiver<TunnelFrame>, RouterError> {
+        if !self.registry.is_connected(machine_id).await
+            && let Some((cluster, peer)) = self.remote(machine_id).await
+        {
+            let request = peer_request(machine_id, request_id, method, data, metadata);
+            return cluster.peers.forward_stream(&peer.address, request).await;
+        }
         let (_conn, stream_rx) = self
             .setup_stream_forward(machine_id, request_id, method, data, metadata)
             .await?;
@@ -200,10 +356,22 @@ impl RequestRouter {
         data: Vec<u8>,
         metadata: std::collections::HashMap<String, String>,
     ) -> Result<(mpsc::Sender<TunnelFrame>, mpsc::Receiver<TunnelFrame>), RouterError> {
+        if !self.registry.is_connected(machine_id).await
+            && let Some((cluster, peer)) = self.remote(machine_id).await
+        {
+            let request = peer_request(machine_id, request_id, method, data, metadata);
+            return cluster
+                .peers
+                .forward_bidi_stream(&peer.address, request)
+                .await;
+        }
         let (conn, stream_rx) = self
             .setup_stream_forward(machine_id, request_id, method, data, metadata)
             .await?;
-        let client_tx = conn.frame_tx.clone();
+        let client_tx = self.limiter.as_ref().map_or_else(
+            || conn.frame_tx.clone(),
+            |limiter| throttled_sender(Arc::clone(limiter), machine_id, conn.frame_tx.clone()),
+        );
         Ok((client_tx, stream_rx))
     }
 
@@ -228,9 +396,10 @@ impl RequestRouter {
         }
     }
 
-    /// Check if a machine is currently connected.
+    /// Check if a machine is currently connected, to this or (with
+    /// clustering) another relay instance.
     pub async fn is_machine_online(&self, machine_id: &str) -> bool {
-        self.registry.is_connected(machine_id).await
+        self.registry.is_connected(machine_id).await || self.remote(machine_id).await.is_some()
     }
 }
 
@@ -250,6 +419,18 @@ pub enum RouterError {
 
     #[error("Response channel dropped: {0}")]
     ResponseDropped(String),
+
+    #[error("Peer relay unreachable: {0}")]
+    PeerUnreachable(String),
+
+    #[error("Rate limit exceeded for machine {machine_id}, retry in {retry_after:?}")]
+    RateLimited {
+        machine_id: String,
+        retry_after: Duration,
+    },
+
+    #[error("Peer relay error: {0}")]
+    Peer(Box<tonic::Status>),
 }
 
 #[cfg(test)]
@@ -338,6 +519,47 @@ mod tests {
         assert!(matches!(result, Err(RouterError::Buffered(_))));
     }
 
+    #[tokio::test]
+    async fn buffering_wakes_the_machine() {
+        let registry = Arc::new(ConnectionRegistry::new());
+        let db = RelayDatabase::open_in_memory().await.unwrap();
+        db.create_user("u1", "alice", "alice@test.com", "hash")
+            .await
+            .unwrap();
+        db.create_machine(
+            "sleeper",
+            "sleeper",
+            "u1",
+            r#"{"wake_mac":"aa:bb:cc:dd:ee:ff","wake_via":"nas"}"#,
+        )
+        .await
+        .unwrap();
+        db.create_machine("nas", "nas", "u1", "{}").await.unwrap();
+        let (tx, mut nas_rx) = mpsc::channel(16);
+        registry.register("nas".into(), "u1".into(), tx).await;
+        let buffer = Arc::new(BufferManager::new(
+            db.clone(),
+            Arc::clone(&registry),
+            3600,
+            1000,
+        ));
+        let wake = Arc::new(WakeManager::new(
+            db,
+            Arc::clone(&registry),
+            Duration::from_mins(1),
+        ));
+        let router = RequestRouter::new(Arc::clone(&registry), buffer, Duration::from_secs(1))
+            .with_wake(wake);
+
+        let result = router
+            .forward_request("sleeper", "req-1", "Test", vec![], HashMap::default())
+            .await;
+
+        assert!(matches!(result, Err(RouterError::Buffered(_))));
+        let frame = nas_rx.recv().await.unwrap();
+        assert_eq!(frame.frame_type, FrameType::Control as i32);
+    }
+
     #[tokio::test]
     async fn forward_request_timeout() {
         // Very short timeout, no responder
@@ -354,6 +576,30 @@ mod tests {
         assert_eq!(conn.pending_count().await, 0);
     }
 
+    #[tokio::test]
+    async fn forward_request_over_tunnel_rate_is_rejected() {
+        let (_registry, router, _rx) = setup_online("m1", Duration::from_millis(20)).await;
+        let limiter = Arc::new(TunnelLimiter::new(
+            crate::ratelimit::Rate::per_minute(1),
+            None,
+        ));
+        let router = router.with_rate_limits(limiter);
+
+        // The first request is sent (and times out unanswered)
+        let result = router
+            .forward_request("m1", "req-1", "Test", vec![], HashMap::default())
+            .await;
+        assert!(matches!(result, Err(RouterError::Timeout(_))));
+
+        let result = router
+            .forward_request("m1", "req-2", "Test", vec![], HashMap::default())
+            .await;
+        let Err(RouterError::RateLimited { retry_after, .. }) = result else {
+            panic!("expected rate limit, got {result:?}");
+        };
+        assert!(retry_after > Duration::from_secs(50));
+    }
+
     #[tokio::test]
     async fn forward_stream_to_online_machine() {
         let (registry, router, mut tunnel_rx) = setup_online("m1", Duration::from_secs(5)).await;
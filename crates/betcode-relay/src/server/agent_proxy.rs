@@ -22,16 +22,18 @@ use betcode_proto::v1::{
     DeleteSessionRequest, DeleteSessionResponse, EncryptedPayload, FrameType, InputLockRequest,
     InputLockResponse, KeyExchangeRequest, KeyExchangeResponse, ListSessionGrantsRequest,
     ListSessionGrantsResponse, ListSessionsRequest, ListSessionsResponse, RenameSessionRequest,
-    RenameSessionResponse, ResumeSessionRequest, SetSessionGrantRequest, SetSessionGrantResponse,
-    StreamPayload, TunnelFrame,
+    RenameSessionResponse, RespondToPermissionRequest, RespondToPermissionResponse,
+    ResumeSessionRequest, SetSessionGrantRequest, SetSessionGrantResponse, StreamPayload,
+    TunnelFrame,
 };
 
 use betcode_proto::methods::{
     METHOD_CANCEL_TURN, METHOD_CLEAR_SESSION_GRANTS, METHOD_COMPACT_SESSION, METHOD_CONVERSE,
     METHOD_DELETE_SESSION, METHOD_EXCHANGE_KEYS, METHOD_LIST_SESSION_GRANTS, METHOD_LIST_SESSIONS,
-    METHOD_RENAME_SESSION, METHOD_REQUEST_INPUT_LOCK, METHOD_RESUME_SESSION,
-    METHOD_SET_SESSION_GRANT,
+    METHOD_RENAME_SESSION, METHOD_REQUEST_INPUT_LOCK, METHOD_RESPOND_TO_PERMISSION,
+    METHOD_RESUME_SESSION, METHOD_SET_SESSION_GRANT,
 };
+use betcode_proto::rate_limit;
 
 use crate::router::{RequestRouter, RouterError};
 use crate::server::interceptor::extract_claims;
@@ -59,6 +61,18 @@ pub fn router_error_to_status(err: RouterError) -> Status {
         RouterError::Timeout(r) => Status::deadline_exceeded(format!("Request timed out: {r}")),
         RouterError::SendFailed(m) => Status::internal(format!("Failed to send to machine: {m}")),
         RouterError::ResponseDropped(r) => Status::internal(format!("Response dropped: {r}")),
+        RouterError::PeerUnreachable(p) => {
+            Status::unavailable(format!("Peer relay unreachable: {p}"))
+        }
+        RouterError::RateLimited {
+            machine_id,
+            retry_after,
+        } => rate_limit::resource_exhausted(
+            format!("Rate limit exceeded for machine {machine_id}"),
+            retry_after,
+        ),
+        // The peer already mapped its own router error.
+        RouterError::Peer(status) => *status,
     }
 }
 
@@ -388,6 +402,20 @@ impl AgentService for AgentProxyService {
         super::grpc_util::forward_unary_rpc(&self.router, &self.db, request, METHOD_DELETE_SESSION)
             .await
     }
+
+    #[instrument(skip(self, request), fields(rpc = "RespondToPermission"))]
+    async fn respond_to_permission(
+        &self,
+        request: Request<RespondToPermissionRequest>,
+    ) -> Result<Response<RespondToPermissionResponse>, Status> {
+        super::grpc_util::forward_unary_rpc(
+            &self.router,
+            &self.db,
+            request,
+            METHOD_RESPOND_TO_PERMISSION,
+        )
+        .await
+    }
 }
 
 #[cfg(test)]
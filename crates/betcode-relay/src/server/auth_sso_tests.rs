@@ -0,0 +1,454 @@
+//! Tests for SSO login in `AuthService` against a mock `OpenID` provider.
+
+use std::sync::Arc;
+
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use jsonwebtoken::{Algorithm, EncodingKey, Header};
+use serde_json::{Value, json};
+use tonic::{Code, Request};
+use wiremock::matchers::{body_string_contains, method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+use betcode_crypto::PkcePair;
+use betcode_proto::v1::auth_service_server::AuthService;
+use betcode_proto::v1::{
+    CompleteSsoLoginRequest, LoginRequest, LoginResponse, PollSsoDeviceLoginRequest,
+    RegisterRequest, StartSsoDeviceLoginRequest, StartSsoLoginRequest, StartSsoLoginResponse,
+};
+
+use super::auth_svc::AuthServiceImpl;
+use crate::auth::jwt::JwtManager;
+use crate::auth::oidc::{OidcConfig, OidcProvider};
+use crate::storage::RelayDatabase;
+
+const CLIENT_ID: &str = "betcode-relay";
+const REDIRECT_URI: &str = "http://127.0.0.1:8400/callback";
+
+/// A mock identity provider signing ID tokens with an ES256 key.
+struct MockIdp {
+    server: MockServer,
+    key: EncodingKey,
+}
+
+impl MockIdp {
+    async fn start() -> Self {
+        let server = MockServer::start().await;
+        let key_pair = rcgen::KeyPair::generate_for(&rcgen::PKCS_ECDSA_P256_SHA256).unwrap();
+        // Uncompressed SEC1 point: 0x04 || x || y
+        let point = key_pair.public_key_raw();
+        let jwk = json!({
+            "kty": "EC",
+            "crv": "P-256",
+            "kid": "k1",
+            "use": "sig",
+            "alg": "ES256",
+            "x": URL_SAFE_NO_PAD.encode(&point[1..33]),
+            "y": URL_SAFE_NO_PAD.encode(&point[33..65]),
+        });
+
+        let issuer = server.uri();
+        Mock::given(method("GET"))
+            .and(path("/.well-known/openid-configuration"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "issuer": issuer,
+                "authorization_endpoint": format!("{issuer}/authorize"),
+                "token_endpoint": format!("{issuer}/token"),
+                "jwks_uri": format!("{issuer}/jwks"),
+                "device_authorization_endpoint": format!("{issuer}/device"),
+            })))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/jwks"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "keys": [jwk] })))
+            .mount(&server)
+            .await;
+
+        let key = EncodingKey::from_ec_pem(key_pair.serialize_pem().as_bytes()).unwrap();
+        Self { server, key }
+    }
+
+    /// A signed ID token for `sub` with `extra` claims merged in.
+    fn id_token(&self, sub: &str, extra: &Value) -> String {
+        let now = betcode_core::db::unix_timestamp();
+        let mut claims = json!({
+            "iss": self.server.uri(),
+            "aud": CLIENT_ID,
+            "sub": sub,
+            "iat": now,
+            "exp": now + 300,
+        });
+        if let (Some(claims), Some(extra)) = (claims.as_object_mut(), extra.as_object()) {
+            claims.extend(extra.clone());
+        }
+        let mut header = Header::new(Algorithm::ES256);
+        header.kid = Some("k1".into());
+        jsonwebtoken::encode(&header, &claims, &self.key).unwrap()
+    }
+
+    /// Answer token requests whose body contains `matching` with `id_token`.
+    async fn issue(&self, matching: &str, id_token: String) {
+        Mock::given(method("POST"))
+            .and(path("/token"))
+            .and(body_string_contains(matching))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "access_token": "idp-access",
+                "token_type": "Bearer",
+                "id_token": id_token,
+            })))
+            .mount(&self.server)
+            .await;
+    }
+
+    async fn provider(&self, allowed_domains: &[&str]) -> Arc<OidcProvider> {
+        Arc::new(
+            OidcProvider::discover(OidcConfig {
+                issuer: self.server.uri(),
+                client_id: CLIENT_ID.into(),
+                client_secret: Some("client-secret".into()),
+                scopes: "openid email profile".into(),
+                allowed_domains: allowed_domains.iter().map(ToString::to_string).collect(),
+            })
+            .await
+            .unwrap(),
+        )
+    }
+}
+
+async fn service(idp: &MockIdp) -> AuthServiceImpl {
+    let db = RelayDatabase::open_in_memory().await.unwrap();
+    let jwt = Arc::new(JwtManager::new(b"test-secret", 3600, 86400));
+    AuthServiceImpl::new(db, jwt, 30).with_sso(idp.provider(&["corp.test"]).await)
+}
+
+/// Start an authorization code login for `pkce`.
+async fn start_login(svc: &AuthServiceImpl, pkce: &PkcePair) -> StartSsoLoginResponse {
+    svc.start_sso_login(Request::new(StartSsoLoginRequest {
+        redirect_uri: REDIRECT_URI.into(),
+        code_challenge: pkce.challenge.clone(),
+    }))
+    .await
+    .unwrap()
+    .into_inner()
+}
+
+/// The nonce `start` asked the provider to put in the ID token.
+fn requested_nonce(start: &StartSsoLoginResponse) -> String {
+    let url = reqwest::Url::parse(&start.authorization_url).unwrap();
+    url.query_pairs()
+        .find(|(name, _)| name == "nonce")
+        .map(|(_, value)| value.into_owned())
+        .unwrap()
+}
+
+/// Run an authorization code login whose ID token carries `claims`.
+async fn code_login(
+    svc: &AuthServiceImpl,
+    idp: &MockIdp,
+    sub: &str,
+    claims: Value,
+) -> Result<LoginResponse, tonic::Status> {
+    let pkce = PkcePair::generate();
+    let start = start_login(svc, &pkce).await;
+
+    let mut claims = claims;
+    claims["nonce"] = json!(requested_nonce(&start));
+    let code = format!("code-{}", start.state);
+    idp.issue(&format!("code={code}"), idp.id_token(sub, &claims))
+        .await;
+
+    svc.complete_sso_login(Request::new(CompleteSsoLoginRequest {
+        code,
+        code_verifier: pkce.verifier,
+        redirect_uri: REDIRECT_URI.into(),
+        login_token: start.login_token,
+        state: start.state,
+    }))
+    .await
+    .map(tonic::Response::into_inner)
+}
+
+fn alice_claims() -> Value {
+    json!({
+        "email": "alice@corp.test",
+        "email_verified": true,
+        "preferred_username": "alice@corp.test",
+    })
+}
+
+#[tokio::test]
+async fn sso_rpcs_fail_when_not_configured() {
+    let db = RelayDatabase::open_in_memory().await.unwrap();
+    let jwt = Arc::new(JwtManager::new(b"test-secret", 3600, 86400));
+    let svc = AuthServiceImpl::new(db, jwt, 30);
+
+    let err = svc
+        .start_sso_device_login(Request::new(StartSsoDeviceLoginRequest {}))
+        .await
+        .unwrap_err();
+    assert_eq!(err.code(), Code::FailedPrecondition);
+}
+
+#[tokio::test]
+async fn authorization_url_carries_pkce_and_client() {
+    let idp = MockIdp::start().await;
+    let svc = service(&idp).await;
+    let pkce = PkcePair::generate();
+    let start = start_login(&svc, &pkce).await;
+
+    let url = reqwest::Url::parse(&start.authorization_url).unwrap();
+    assert_eq!(url.path(), "/authorize");
+    let query: std::collections::HashMap<_, _> = url.query_pairs().into_owned().collect();
+    assert_eq!(query["client_id"], CLIENT_ID);
+    assert_eq!(query["redirect_uri"], REDIRECT_URI);
+    assert_eq!(query["code_challenge"], pkce.challenge);
+    assert_eq!(query["code_challenge_method"], "S256");
+    assert_eq!(query["state"], start.state);
+    assert!(!query["nonce"].is_empty());
+    assert!(!start.login_token.is_empty());
+
+    let err = svc
+        .start_sso_login(Request::new(StartSsoLoginRequest {
+            redirect_uri: REDIRECT_URI.into(),
+            code_challenge: "plain".into(),
+        }))
+        .await
+        .unwrap_err();
+    assert_eq!(err.code(), Code::InvalidArgument);
+}
+
+#[tokio::test]
+async fn code_login_provisions_then_reuses_the_user() {
+    let idp = MockIdp::start().await;
+    let svc = service(&idp).await;
+
+    let first = code_login(&svc, &idp, "sub-alice", alice_claims())
+        .await
+        .unwrap();
+    assert_eq!(first.username, "alice");
+    assert!(!first.access_token.is_empty());
+    assert!(!first.refresh_token.is_empty());
+
+    let second = code_login(&svc, &idp, "sub-alice", alice_claims())
+        .await
+        .unwrap();
+    assert_eq!(second.user_id, first.user_id);
+}
+
+#[tokio::test]
+async fn taken_username_gets_a_numbered_one() {
+    let idp = MockIdp::start().await;
+    let svc = service(&idp).await;
+    svc.register(Request::new(RegisterRequest {
+        username: "alice".into(),
+        password: "password123".into(),
+        email: "alice@home.test".into(),
+    }))
+    .await
+    .unwrap();
+
+    let login = code_login(&svc, &idp, "sub-alice", alice_claims())
+        .await
+        .unwrap();
+    assert_eq!(login.username, "alice2");
+}
+
+#[tokio::test]
+async fn identities_outside_allowed_domains_are_refused() {
+    let idp = MockIdp::start().await;
+    let svc = service(&idp).await;
+
+    let err = code_login(
+        &svc,
+        &idp,
+        "sub-mallory",
+        json!({ "email": "mallory@evil.test", "email_verified": true }),
+    )
+    .await
+    .unwrap_err();
+    assert_eq!(err.code(), Code::PermissionDenied);
+
+    let err = code_login(
+        &svc,
+        &idp,
+        "sub-bob",
+        json!({ "email": "bob@corp.test", "email_verified": false }),
+    )
+    .await
+    .unwrap_err();
+    assert_eq!(err.code(), Code::PermissionDenied);
+
+    // A provider that does not say the email is verified is not trusted
+    let err = code_login(
+        &svc,
+        &idp,
+        "sub-carol",
+        json!({ "email": "carol@corp.test" }),
+    )
+    .await
+    .unwrap_err();
+    assert_eq!(err.code(), Code::PermissionDenied);
+}
+
+#[tokio::test]
+async fn id_token_with_foreign_nonce_is_rejected() {
+    let idp = MockIdp::start().await;
+    let svc = service(&idp).await;
+    let pkce = PkcePair::generate();
+    let start = start_login(&svc, &pkce).await;
+
+    let mut claims = alice_claims();
+    claims["nonce"] = json!("someone-elses-nonce");
+    idp.issue("code=stolen", idp.id_token("sub-alice", &claims))
+        .await;
+
+    let err = svc
+        .complete_sso_login(Request::new(CompleteSsoLoginRequest {
+            code: "stolen".into(),
+            code_verifier: pkce.verifier,
+            redirect_uri: REDIRECT_URI.into(),
+            login_token: start.login_token,
+            state: start.state,
+        }))
+        .await
+        .unwrap_err();
+    assert_eq!(err.code(), Code::Unauthenticated);
+}
+
+#[tokio::test]
+async fn completion_must_match_the_started_login() {
+    let idp = MockIdp::start().await;
+    let svc = service(&idp).await;
+    let pkce = PkcePair::generate();
+    let start = start_login(&svc, &pkce).await;
+
+    let mut claims = alice_claims();
+    claims["nonce"] = json!(requested_nonce(&start));
+    idp.issue("code=c1", idp.id_token("sub-alice", &claims))
+        .await;
+
+    let complete = |change: fn(&mut CompleteSsoLoginRequest)| {
+        let mut request = CompleteSsoLoginRequest {
+            code: "c1".into(),
+            code_verifier: pkce.verifier.clone(),
+            redirect_uri: REDIRECT_URI.into(),
+            login_token: start.login_token.clone(),
+            state: start.state.clone(),
+        };
+        change(&mut request);
+        svc.complete_sso_login(Request::new(request))
+    };
+
+    let err = complete(|r| r.state = "other-state".into())
+        .await
+        .unwrap_err();
+    assert_eq!(err.code(), Code::Unauthenticated);
+    let err = complete(|r| r.code_verifier = PkcePair::generate().verifier)
+        .await
+        .unwrap_err();
+    assert_eq!(err.code(), Code::Unauthenticated);
+    let err = complete(|r| r.redirect_uri = "http://127.0.0.1:9999/callback".into())
+        .await
+        .unwrap_err();
+    assert_eq!(err.code(), Code::Unauthenticated);
+
+    // A login token signed by another relay
+    let forged = JwtManager::new(b"other-secret", 3600, 86400)
+        .issue_sso_login_token(
+            &start.state,
+            &requested_nonce(&start),
+            REDIRECT_URI,
+            &pkce.challenge,
+        )
+        .unwrap();
+    let err = svc
+        .complete_sso_login(Request::new(CompleteSsoLoginRequest {
+            code: "c1".into(),
+            code_verifier: pkce.verifier.clone(),
+            redirect_uri: REDIRECT_URI.into(),
+            login_token: forged,
+            state: start.state.clone(),
+        }))
+        .await
+        .unwrap_err();
+    assert_eq!(err.code(), Code::Unauthenticated);
+
+    let login = complete(|_| {}).await.unwrap().into_inner();
+    assert_eq!(login.username, "alice");
+}
+
+#[tokio::test]
+async fn device_login_polls_until_approved() {
+    let idp = MockIdp::start().await;
+    let svc = service(&idp).await;
+
+    Mock::given(method("POST"))
+        .and(path("/device"))
+        .and(body_string_contains("client_id=betcode-relay"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "device_code": "dev-1",
+            "user_code": "ABCD-EFGH",
+            "verification_uri": format!("{}/activate", idp.server.uri()),
+            "expires_in": 600,
+        })))
+        .mount(&idp.server)
+        .await;
+
+    let start = svc
+        .start_sso_device_login(Request::new(StartSsoDeviceLoginRequest {}))
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(start.device_code, "dev-1");
+    assert_eq!(start.user_code, "ABCD-EFGH");
+    assert_eq!(start.interval_secs, 5);
+
+    // Not approved yet
+    Mock::given(method("POST"))
+        .and(path("/token"))
+        .and(body_string_contains("device_code=dev-1"))
+        .respond_with(
+            ResponseTemplate::new(400).set_body_json(json!({ "error": "authorization_pending" })),
+        )
+        .up_to_n_times(1)
+        .with_priority(1)
+        .mount(&idp.server)
+        .await;
+    idp.issue(
+        "device_code=dev-1",
+        idp.id_token("sub-alice", &alice_claims()),
+    )
+    .await;
+
+    let poll = |device_code: &str| {
+        svc.poll_sso_device_login(Request::new(PollSsoDeviceLoginRequest {
+            device_code: device_code.into(),
+        }))
+    };
+    let pending = poll("dev-1").await.unwrap().into_inner();
+    assert!(pending.pending);
+    assert!(pending.login.is_none());
+
+    let approved = poll("dev-1").await.unwrap().into_inner();
+    assert!(!approved.pending);
+    assert_eq!(approved.login.unwrap().username, "alice");
+}
+
+#[tokio::test]
+async fn sso_users_cannot_log_in_with_a_password() {
+    let idp = MockIdp::start().await;
+    let svc = service(&idp).await;
+    code_login(&svc, &idp, "sub-alice", alice_claims())
+        .await
+        .unwrap();
+
+    let err = svc
+        .login(Request::new(LoginRequest {
+            username: "alice".into(),
+            password: String::new(),
+        }))
+        .await
+        .unwrap_err();
+    assert_eq!(err.code(), Code::Unauthenticated);
+}
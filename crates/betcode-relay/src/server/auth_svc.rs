@@ -1,24 +1,41 @@
 //! `AuthService` gRPC implementation.
 
+use std::net::IpAddr;
 use std::sync::Arc;
 
 use tonic::{Request, Response, Status};
 use tracing::{info, instrument, warn};
 
+use betcode_crypto::pkce;
+use betcode_proto::rate_limit;
 use betcode_proto::v1::auth_service_server::AuthService;
 use betcode_proto::v1::{
-    LoginRequest, LoginResponse, RefreshTokenRequest, RefreshTokenResponse, RegisterRequest,
-    RegisterResponse, RevokeTokenRequest, RevokeTokenResponse,
+    CompleteSsoLoginRequest, LoginRequest, LoginResponse, PollSsoDeviceLoginRequest,
+    PollSsoDeviceLoginResponse, RefreshTokenRequest, RefreshTokenResponse, RegisterRequest,
+    RegisterResponse, RevokeTokenRequest, RevokeTokenResponse, StartSsoDeviceLoginRequest,
+    StartSsoDeviceLoginResponse, StartSsoLoginRequest, StartSsoLoginResponse,
 };
 
 use crate::auth::jwt::JwtManager;
+use crate::auth::oidc::{self, IdTokenClaims, OidcError, OidcProvider};
 use crate::auth::password;
-use crate::storage::RelayDatabase;
+use crate::ratelimit::{
+    ForwardedHeader, KeyedLimiter, LockoutPolicy, LoginLockout, Rate, TrustedProxies,
+};
+use crate::storage::{RelayDatabase, User};
+
+/// Numbered usernames tried when an SSO user's preferred one is taken.
+const MAX_USERNAME_SUFFIX: u32 = 100;
 
 pub struct AuthServiceImpl {
     db: RelayDatabase,
     jwt: Arc<JwtManager>,
     grace_period_secs: i64,
+    per_ip: Option<KeyedLimiter>,
+    per_user: Option<KeyedLimiter>,
+    lockout: Option<LoginLockout>,
+    proxies: TrustedProxies,
+    sso: Option<Arc<OidcProvider>>,
 }
 
 /// Issued token pair returned by [`AuthServiceImpl::issue_token_pair`].
@@ -35,6 +52,94 @@ impl AuthServiceImpl {
             db,
             jwt,
             grace_period_secs,
+            per_ip: None,
+            per_user: None,
+            lockout: None,
+            proxies: TrustedProxies::new(Vec::new(), ForwardedHeader::XForwardedFor),
+            sso: None,
+        }
+    }
+
+    /// Limit every auth RPC per client IP.
+    #[must_use]
+    pub fn with_ip_rate_limit(mut self, rate: Rate) -> Self {
+        self.per_ip = Some(KeyedLimiter::new(rate));
+        self
+    }
+
+    /// Limit login, registration and token refresh per username.
+    #[must_use]
+    pub fn with_user_rate_limit(mut self, rate: Rate) -> Self {
+        self.per_user = Some(KeyedLimiter::new(rate));
+        self
+    }
+
+    /// Lock accounts after repeated failed logins.
+    #[must_use]
+    pub fn with_lockout(mut self, policy: LockoutPolicy) -> Self {
+        self.lockout = Some(LoginLockout::new(policy));
+        self
+    }
+
+    /// Take client IPs from the forwarding header of requests from `proxies`.
+    #[must_use]
+    pub fn with_trusted_proxies(mut self, proxies: TrustedProxies) -> Self {
+        self.proxies = proxies;
+        self
+    }
+
+    /// Offer `OpenID` Connect login through `provider`.
+    #[must_use]
+    pub fn with_sso(mut self, provider: Arc<OidcProvider>) -> Self {
+        self.sso = Some(provider);
+        self
+    }
+
+    fn sso(&self) -> Result<&OidcProvider, Status> {
+        self.sso
+            .as_deref()
+            .ok_or_else(|| Status::failed_precondition("SSO login is not configured on this relay"))
+    }
+
+    /// The address of the client that sent `request`.
+    fn client_ip<T>(&self, request: &Request<T>) -> Option<IpAddr> {
+        self.proxies
+            .client_ip(request.remote_addr(), request.metadata())
+    }
+
+    /// Reject a request from `ip` if it is over the rate limit.
+    async fn throttle_ip(&self, ip: Option<IpAddr>) -> Result<(), Status> {
+        let (Some(limiter), Some(ip)) = (&self.per_ip, ip) else {
+            return Ok(());
+        };
+        limiter.check(&ip.to_string()).await.map_err(|wait| {
+            warn!(ip = %ip, "Auth rate limit exceeded for client IP");
+            rate_limit::resource_exhausted("Too many requests from this address", wait)
+        })
+    }
+
+    /// Reject the request if `username` is over the rate limit.
+    async fn throttle_user(&self, username: &str) -> Result<(), Status> {
+        let Some(limiter) = &self.per_user else {
+            return Ok(());
+        };
+        limiter.check(username).await.map_err(|wait| {
+            warn!(username = %username, "Auth rate limit exceeded for user");
+            rate_limit::resource_exhausted("Too many requests for this account", wait)
+        })
+    }
+
+    /// Count a failed login from `ip` towards `username`'s lockout.
+    async fn record_login_failure(&self, username: &str, ip: Option<IpAddr>) {
+        if let Some(lockout) = &self.lockout
+            && let Some(lock) = lockout.record_failure(username, ip).await
+        {
+            warn!(
+                username = %username,
+                ip = ?ip,
+                lock_secs = lock.as_secs(),
+                "Account locked after repeated failed logins"
+            );
         }
     }
 
@@ -64,6 +169,102 @@ impl AuthServiceImpl {
             expires_in,
         })
     }
+
+    /// The relay user linked to an ID token's subject, creating one when the
+    /// identity may be provisioned.
+    async fn sso_user(
+        &self,
+        provider: &OidcProvider,
+        claims: &IdTokenClaims,
+    ) -> Result<User, Status> {
+        let issuer = provider.issuer();
+        let lookup = |e| Status::internal(format!("User lookup failed: {e}"));
+        if let Some(user) = self
+            .db
+            .get_user_by_sso_identity(issuer, &claims.sub)
+            .await
+            .map_err(lookup)?
+        {
+            return Ok(user);
+        }
+        if !provider.may_provision(claims) {
+            warn!(subject = %claims.sub, "SSO login for an identity outside the allowed domains");
+            return Err(Status::permission_denied(
+                "No relay account for this identity",
+            ));
+        }
+
+        let base = oidc::username_candidate(claims);
+        let mut username = base.clone();
+        let mut suffix = 1;
+        while self.db.get_user_by_username(&username).await.is_ok() {
+            suffix += 1;
+            if suffix > MAX_USERNAME_SUFFIX {
+                return Err(Status::already_exists("No free username for this identity"));
+            }
+            username = format!("{base}{suffix}");
+        }
+
+        let user_id = uuid::Uuid::new_v4().to_string();
+        let email = claims.email.as_deref().unwrap_or_default();
+        match self
+            .db
+            .create_sso_user(&user_id, &username, email, issuer, &claims.sub)
+            .await
+        {
+            Ok(user) => {
+                info!(user_id = %user.id, username = %user.username, "Provisioned SSO user");
+                Ok(user)
+            }
+            // A concurrent login may have linked the subject first
+            Err(e) => self
+                .db
+                .get_user_by_sso_identity(issuer, &claims.sub)
+                .await
+                .map_err(lookup)?
+                .ok_or_else(|| Status::internal(format!("User creation failed: {e}"))),
+        }
+    }
+
+    /// Issue tokens for the relay user behind a validated ID token.
+    async fn finish_sso_login(
+        &self,
+        provider: &OidcProvider,
+        claims: &IdTokenClaims,
+    ) -> Result<LoginResponse, Status> {
+        let user = self.sso_user(provider, claims).await?;
+        let tokens = self.issue_token_pair(&user.id, &user.username).await?;
+
+        info!(user_id = %user.id, username = %user.username, "User logged in via SSO");
+
+        Ok(LoginResponse {
+            access_token: tokens.access_token,
+            refresh_token: tokens.refresh_token,
+            expires_in_secs: tokens.expires_in,
+            user_id: user.id,
+            username: user.username,
+        })
+    }
+}
+
+/// Map an identity provider failure to a gRPC status.
+fn oidc_status(e: &OidcError) -> Status {
+    match e {
+        OidcError::Http(_) | OidcError::Discovery(_) => {
+            warn!(error = %e, "Identity provider unavailable");
+            Status::unavailable("Identity provider unavailable")
+        }
+        OidcError::DeviceFlowUnsupported => Status::failed_precondition(e.to_string()),
+        OidcError::ExpiredToken => Status::deadline_exceeded(e.to_string()),
+        OidcError::AccessDenied => Status::permission_denied(e.to_string()),
+        OidcError::AuthorizationPending
+        | OidcError::SlowDown
+        | OidcError::TokenRequest(_)
+        | OidcError::InvalidIdToken(_) => {
+            warn!(error = %e, "SSO login failed");
+            Status::unauthenticated(e.to_string())
+        }
+    }
 }
 
 #[tonic::async_trait]
@@ -73,21 +274,39 @@ impl AuthService for AuthServiceImpl {
         &self,
         request: Request<LoginRequest>,
     ) -> Result<Response<LoginResponse>, Status> {
+        let ip = self.client_ip(&request);
+        self.throttle_ip(ip).await?;
         let req = request.into_inner();
+        self.throttle_user(&req.username).await?;
+
+        if let Some(lockout) = &self.lockout
+            && let Some(wait) = lockout.locked_for(&req.username, ip).await
+        {
+            warn!(username = %req.username, ip = ?ip, "Login attempt on locked account");
+            return Err(rate_limit::resource_exhausted(
+                "Too many failed login attempts; account temporarily locked",
+                wait,
+            ));
+        }
 
-        let user = self
-            .db
-            .get_user_by_username(&req.username)
-            .await
-            .map_err(|_| Status::unauthenticated("Invalid credentials"))?;
+        let Ok(user) = self.db.get_user_by_username(&req.username).await else {
+            self.record_login_failure(&req.username, ip).await;
+            return Err(Status::unauthenticated("Invalid credentials"));
+        };
 
-        let valid = password::verify_password(&req.password, &user.password_hash)
-            .map_err(|_| Status::internal("Password verification failed"))?;
+        // SSO-only accounts have no password to log in with
+        let valid = !user.password_hash.is_empty()
+            && password::verify_password(&req.password, &user.password_hash)
+                .map_err(|_| Status::internal("Password verification failed"))?;
 
         if !valid {
             warn!(username = %req.username, "Failed login attempt");
+            self.record_login_failure(&req.username, ip).await;
             return Err(Status::unauthenticated("Invalid credentials"));
         }
+        if let Some(lockout) = &self.lockout {
+            lockout.record_success(&req.username, ip).await;
+        }
 
         let tokens = self.issue_token_pair(&user.id, &user.username).await?;
 
@@ -98,6 +317,7 @@ impl AuthService for AuthServiceImpl {
             refresh_token: tokens.refresh_token,
             expires_in_secs: tokens.expires_in,
             user_id: user.id,
+            username: user.username,
         }))
     }
 
@@ -106,7 +326,9 @@ impl AuthService for AuthServiceImpl {
         &self,
         request: Request<RegisterRequest>,
     ) -> Result<Response<RegisterResponse>, Status> {
+        self.throttle_ip(self.client_ip(&request)).await?;
         let req = request.into_inner();
+        self.throttle_user(&req.username).await?;
 
         if req.username.len() < 3 {
             return Err(Status::invalid_argument(
@@ -149,6 +371,7 @@ impl AuthService for AuthServiceImpl {
         &self,
         request: Request<RefreshTokenRequest>,
     ) -> Result<Response<RefreshTokenResponse>, Status> {
+        self.throttle_ip(self.client_ip(&request)).await?;
         let req = request.into_inner();
 
         let claims = self
@@ -159,6 +382,7 @@ impl AuthService for AuthServiceImpl {
         if !claims.is_refresh() {
             return Err(Status::invalid_argument("Not a refresh token"));
         }
+        self.throttle_user(&claims.username).await?;
 
         let token_hash = JwtManager::hash_token(&req.refresh_token);
         let stored = self
@@ -203,6 +427,7 @@ impl AuthService for AuthServiceImpl {
         &self,
         request: Request<RevokeTokenRequest>,
     ) -> Result<Response<RevokeTokenResponse>, Status> {
+        self.throttle_ip(self.client_ip(&request)).await?;
         let req = request.into_inner();
 
         let token_hash = JwtManager::hash_token(&req.refresh_token);
@@ -223,4 +448,139 @@ impl AuthService for AuthServiceImpl {
 
         Ok(Response::new(RevokeTokenResponse { revoked }))
     }
+
+    #[instrument(skip(self, request), fields(rpc = "StartSsoLogin"))]
+    async fn start_sso_login(
+        &self,
+        request: Request<StartSsoLoginRequest>,
+    ) -> Result<Response<StartSsoLoginResponse>, Status> {
+        self.throttle_ip(self.client_ip(&request)).await?;
+        let provider = self.sso()?;
+        let req = request.into_inner();
+
+        if req.redirect_uri.is_empty() {
+            return Err(Status::invalid_argument("redirect_uri is required"));
+        }
+        // RFC 7636: an S256 challenge is 43 base64url characters
+        if req.code_challenge.len() != 43 {
+            return Err(Status::invalid_argument(
+                "code_challenge must be an S256 PKCE challenge",
+            ));
+        }
+
+        let state = pkce::random_token();
+        let nonce = pkce::random_token();
+        let authorization_url = provider
+            .authorization_url(&req.redirect_uri, &req.code_challenge, &state, &nonce)
+            .map_err(|e| oidc_status(&e))?;
+        let login_token = self
+            .jwt
+            .issue_sso_login_token(&state, &nonce, &req.redirect_uri, &req.code_challenge)
+            .map_err(|e| Status::internal(format!("Token issuance failed: {e}")))?;
+
+        Ok(Response::new(StartSsoLoginResponse {
+            authorization_url,
+            state,
+            login_token,
+        }))
+    }
+
+    #[instrument(skip(self, request), fields(rpc = "CompleteSsoLogin"))]
+    async fn complete_sso_login(
+        &self,
+        request: Request<CompleteSsoLoginRequest>,
+    ) -> Result<Response<LoginResponse>, Status> {
+        self.throttle_ip(self.client_ip(&request)).await?;
+        let provider = self.sso()?;
+        let req = request.into_inner();
+
+        if req.code.is_empty() || req.code_verifier.is_empty() || req.login_token.is_empty() {
+            return Err(Status::invalid_argument(
+                "code, code_verifier and login_token are required",
+            ));
+        }
+
+        // The code must come back to the login this relay started: same
+        // state, redirect URI and PKCE verifier.
+        let login = self
+            .jwt
+            .validate_sso_login_token(&req.login_token)
+            .map_err(|_| Status::unauthenticated("Invalid or expired SSO login"))?;
+        if req.state != login.state
+            || req.redirect_uri != login.redirect_uri
+            || pkce::s256_challenge(&req.code_verifier) != login.code_challenge
+        {
+            return Err(Status::unauthenticated(
+                "SSO login does not match the one started",
+            ));
+        }
+
+        let claims = provider
+            .exchange_code(
+                &req.code,
+                &req.code_verifier,
+                &req.redirect_uri,
+                &login.nonce,
+            )
+            .await
+            .map_err(|e| oidc_status(&e))?;
+        Ok(Response::new(
+            self.finish_sso_login(provider, &claims).await?,
+        ))
+    }
+
+    #[instrument(skip(self, request), fields(rpc = "StartSsoDeviceLogin"))]
+    async fn start_sso_device_login(
+        &self,
+        request: Request<StartSsoDeviceLoginRequest>,
+    ) -> Result<Response<StartSsoDeviceLoginResponse>, Status> {
+        self.throttle_ip(self.client_ip(&request)).await?;
+        let provider = self.sso()?;
+
+        let device = provider
+            .start_device_authorization()
+            .await
+            .map_err(|e| oidc_status(&e))?;
+
+        Ok(Response::new(StartSsoDeviceLoginResponse {
+            device_code: device.device_code,
+            user_code: device.user_code,
+            verification_uri: device.verification_uri,
+            verification_uri_complete: device.verification_uri_complete.unwrap_or_default(),
+            interval_secs: device.interval,
+            expires_in_secs: device.expires_in,
+        }))
+    }
+
+    #[instrument(skip(self, request), fields(rpc = "PollSsoDeviceLogin"))]
+    async fn poll_sso_device_login(
+        &self,
+        request: Request<PollSsoDeviceLoginRequest>,
+    ) -> Result<Response<PollSsoDeviceLoginResponse>, Status> {
+        self.throttle_ip(self.client_ip(&request)).await?;
+        let provider = self.sso()?;
+        let req = request.into_inner();
+
+        if req.device_code.is_empty() {
+            return Err(Status::invalid_argument("device_code is required"));
+        }
+
+        let response = match provider.poll_device_token(&req.device_code).await {
+            Ok(claims) => PollSsoDeviceLoginResponse {
+                login: Some(self.finish_sso_login(provider, &claims).await?),
+                ..Default::default()
+            },
+            Err(OidcError::AuthorizationPending) => PollSsoDeviceLoginResponse {
+                pending: true,
+                ..Default::default()
+            },
+            Err(OidcError::SlowDown) => PollSsoDeviceLoginResponse {
+                pending: true,
+                slow_down: true,
+                ..Default::default()
+            },
+            Err(e) => return Err(oidc_status(&e)),
+        };
+        Ok(Response::new(response))
+    }
 }
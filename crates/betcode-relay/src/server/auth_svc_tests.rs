@@ -1,14 +1,19 @@
 //! Tests for `AuthService` gRPC implementation.
 
+use std::net::SocketAddr;
 use std::sync::Arc;
+use std::time::Duration;
 
 use tonic::Request;
+use tonic::transport::server::TcpConnectInfo;
 
+use betcode_proto::rate_limit::retry_after;
 use betcode_proto::v1::auth_service_server::AuthService;
 use betcode_proto::v1::{LoginRequest, RefreshTokenRequest, RegisterRequest, RevokeTokenRequest};
 
 use super::auth_svc::AuthServiceImpl;
 use crate::auth::jwt::JwtManager;
+use crate::ratelimit::{ForwardedHeader, LockoutPolicy, Rate, TrustedProxies};
 use crate::storage::RelayDatabase;
 
 /// Default grace period used in tests (30 seconds).
@@ -302,3 +307,158 @@ async fn register_duplicate_username() {
 
     assert_eq!(err.code(), tonic::Code::AlreadyExists);
 }
+
+fn login_request(username: &str, password: &str) -> Request<LoginRequest> {
+    Request::new(LoginRequest {
+        username: username.into(),
+        password: password.into(),
+    })
+}
+
+/// A request arriving from `ip`, as the server's connection info reports it.
+fn request_from<T>(ip: &str, message: T) -> Request<T> {
+    let mut request = Request::new(message);
+    request.extensions_mut().insert(TcpConnectInfo {
+        local_addr: None,
+        remote_addr: Some(SocketAddr::new(ip.parse().unwrap(), 40000)),
+    });
+    request
+}
+
+#[tokio::test]
+async fn repeated_failed_logins_lock_the_account() {
+    let (svc, _jwt) = setup().await;
+    let svc = svc.with_lockout(LockoutPolicy {
+        threshold: 2,
+        base: Duration::from_mins(1),
+        max: Duration::from_hours(1),
+    });
+    register_alice(&svc).await;
+
+    for _ in 0..2 {
+        let err = svc
+            .login(login_request("alice", "wrong"))
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), tonic::Code::Unauthenticated);
+    }
+
+    // Locked: even the right password is refused, with a retry hint
+    let err = svc
+        .login(login_request("alice", "password123"))
+        .await
+        .unwrap_err();
+    assert_eq!(err.code(), tonic::Code::ResourceExhausted);
+    assert_eq!(retry_after(&err), Some(Duration::from_mins(1)));
+
+    // Unknown usernames lock the same way, revealing nothing
+    for _ in 0..2 {
+        svc.login(login_request("mallory", "x")).await.unwrap_err();
+    }
+    let err = svc.login(login_request("mallory", "x")).await.unwrap_err();
+    assert_eq!(err.code(), tonic::Code::ResourceExhausted);
+}
+
+#[tokio::test]
+async fn auth_rpcs_are_rate_limited_per_ip() {
+    let (svc, _jwt) = setup().await;
+    let svc = svc.with_ip_rate_limit(Rate::per_minute(2).unwrap());
+
+    for _ in 0..2 {
+        let err = svc
+            .login(request_from("203.0.113.7", LoginRequest::default()))
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), tonic::Code::Unauthenticated);
+    }
+    let err = svc
+        .revoke_token(request_from(
+            "203.0.113.7",
+            RevokeTokenRequest {
+                refresh_token: "t".into(),
+            },
+        ))
+        .await
+        .unwrap_err();
+    assert_eq!(err.code(), tonic::Code::ResourceExhausted);
+    assert!(retry_after(&err).is_some_and(|wait| wait <= Duration::from_secs(30)));
+
+    // Other addresses are unaffected
+    let err = svc
+        .login(request_from("198.51.100.1", LoginRequest::default()))
+        .await
+        .unwrap_err();
+    assert_eq!(err.code(), tonic::Code::Unauthenticated);
+}
+
+/// A request forwarded by the proxy at `proxy` on behalf of `client`.
+fn request_via<T>(proxy: &str, client: &str, message: T) -> Request<T> {
+    let mut request = request_from(proxy, message);
+    request
+        .metadata_mut()
+        .insert("x-forwarded-for", client.parse().unwrap());
+    request
+}
+
+#[tokio::test]
+async fn clients_behind_trusted_proxies_are_told_apart() {
+    let (svc, _jwt) = setup().await;
+    let svc = svc
+        .with_trusted_proxies(TrustedProxies::new(
+            vec!["10.0.0.0/8".parse().unwrap()],
+            ForwardedHeader::XForwardedFor,
+        ))
+        .with_ip_rate_limit(Rate::per_minute(2).unwrap())
+        .with_lockout(LockoutPolicy {
+            threshold: 1,
+            base: Duration::from_mins(1),
+            max: Duration::from_hours(1),
+        });
+    register_alice(&svc).await;
+
+    let attempt = |client: &str, password: &str| {
+        request_via(
+            "10.0.0.2",
+            client,
+            LoginRequest {
+                username: "alice".into(),
+                password: password.into(),
+            },
+        )
+    };
+    let err = svc
+        .login(attempt("203.0.113.7", "wrong"))
+        .await
+        .unwrap_err();
+    assert_eq!(err.code(), tonic::Code::Unauthenticated);
+
+    // The guesser is locked out and throttled; alice behind the same
+    // balancer is neither
+    let err = svc
+        .login(attempt("203.0.113.7", "password123"))
+        .await
+        .unwrap_err();
+    assert_eq!(err.code(), tonic::Code::ResourceExhausted);
+    svc.login(attempt("198.51.100.1", "password123"))
+        .await
+        .unwrap();
+    let err = svc
+        .login(attempt("203.0.113.7", "password123"))
+        .await
+        .unwrap_err();
+    assert_eq!(err.code(), tonic::Code::ResourceExhausted);
+    svc.login(attempt("198.51.100.1", "password123"))
+        .await
+        .unwrap();
+
+    // Untrusted peers cannot pick their address
+    let err = svc
+        .login(request_via(
+            "192.0.2.1",
+            "198.51.100.1",
+            LoginRequest::default(),
+        ))
+        .await
+        .unwrap_err();
+    assert_eq!(err.code(), tonic::Code::Unauthenticated);
+}
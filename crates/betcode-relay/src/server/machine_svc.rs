@@ -1,25 +1,42 @@
 //! `MachineService` gRPC implementation.
 
+use std::collections::HashMap;
+use std::sync::Arc;
+
 use tonic::{Request, Response, Status};
 use tracing::{info, instrument};
 
+use betcode_core::wake::WakeTarget;
+
 use betcode_proto::v1::machine_service_server::MachineService;
 use betcode_proto::v1::{
-    GetMachineRequest, GetMachineResponse, ListMachinesRequest, ListMachinesResponse, MachineInfo,
+    BufferedRequest, CancelBufferedRequestRequest, CancelBufferedRequestResponse,
+    GetMachineRequest, GetMachineResponse, ListBufferedRequestsRequest,
+    ListBufferedRequestsResponse, ListMachinesRequest, ListMachinesResponse, MachineInfo,
     MachineStatus, RegisterMachineRequest, RegisterMachineResponse, RemoveMachineRequest,
-    RemoveMachineResponse,
+    RemoveMachineResponse, SetMachineWakeRequest, SetMachineWakeResponse, WakeMachineRequest,
+    WakeMachineResponse,
 };
 
 use crate::server::interceptor::extract_claims;
 use crate::storage::RelayDatabase;
+use crate::wake::{WakeConfig, WakeError, WakeManager, WakeOutcome};
 
 pub struct MachineServiceImpl {
     db: RelayDatabase,
+    wake: Option<Arc<WakeManager>>,
 }
 
 impl MachineServiceImpl {
     pub const fn new(db: RelayDatabase) -> Self {
-        Self { db }
+        Self { db, wake: None }
+    }
+
+    /// Enable the `WakeMachine` RPC.
+    #[must_use]
+    pub fn with_wake(mut self, wake: Arc<WakeManager>) -> Self {
+        self.wake = Some(wake);
+        self
     }
 }
 
@@ -71,6 +88,39 @@ fn machine_to_proto(m: &crate::storage::Machine) -> MachineInfo {
     }
 }
 
+const fn timestamp(seconds: i64) -> prost_types::Timestamp {
+    prost_types::Timestamp { seconds, nanos: 0 }
+}
+
+fn buffered_to_proto(m: &crate::storage::BufferedMessage) -> BufferedRequest {
+    BufferedRequest {
+        request_id: m.request_id.clone(),
+        method: m.method.clone(),
+        priority: i32::try_from(m.priority).unwrap_or_default(),
+        created_at: Some(timestamp(m.created_at)),
+        expires_at: Some(timestamp(m.expires_at)),
+        delivered_at: m.delivered_at.map(timestamp),
+    }
+}
+
+const fn wake_outcome_to_proto(outcome: WakeOutcome) -> betcode_proto::v1::WakeOutcome {
+    match outcome {
+        WakeOutcome::Sent => betcode_proto::v1::WakeOutcome::Sent,
+        WakeOutcome::InProgress => betcode_proto::v1::WakeOutcome::InProgress,
+        WakeOutcome::AlreadyOnline => betcode_proto::v1::WakeOutcome::AlreadyOnline,
+    }
+}
+
+fn wake_error_to_status(e: &WakeError) -> Status {
+    match e {
+        WakeError::NotConfigured(_)
+        | WakeError::InvalidConfig { .. }
+        | WakeError::WakerNotOwned { .. } => Status::failed_precondition(e.to_string()),
+        WakeError::WakerOffline(_) | WakeError::SendFailed(_) => Status::unavailable(e.to_string()),
+        WakeError::Storage(_) => Status::internal(e.to_string()),
+    }
+}
+
 #[tonic::async_trait]
 impl MachineService for MachineServiceImpl {
     #[instrument(skip(self, request), fields(rpc = "RegisterMachine"))]
@@ -166,4 +216,300 @@ impl MachineService for MachineServiceImpl {
             machine: Some(machine_to_proto(&machine)),
         }))
     }
+    #[instrument(skip(self, request), fields(rpc = "ListBufferedRequests"))]
+    async fn list_buffered_requests(
+        &self,
+        request: Request<ListBufferedRequestsRequest>,
+    ) -> Result<Response<ListBufferedRequestsResponse>, Status> {
+        let user_id = extract_user_id(&request)?;
+        let req = request.into_inner();
+
+        verify_machine_ownership(&self.db, &req.machine_id, &user_id).await?;
+
+        let messages = self
+            .db
+            .list_buffered_messages(&req.machine_id, req.include_delivered)
+            .await
+            .map_err(|e| Status::internal(format!("Failed to list buffered requests: {e}")))?;
+
+        Ok(Response::new(ListBufferedRequestsResponse {
+            requests: messages.iter().map(buffered_to_proto).collect(),
+        }))
+    }
+
+    #[instrument(skip(self, request), fields(rpc = "CancelBufferedRequest"))]
+    async fn cancel_buffered_request(
+        &self,
+        request: Request<CancelBufferedRequestRequest>,
+    ) -> Result<Response<CancelBufferedRequestResponse>, Status> {
+        let user_id = extract_user_id(&request)?;
+        let req = request.into_inner();
+
+        verify_machine_ownership(&self.db, &req.machine_id, &user_id).await?;
+
+        let cancelled = self
+            .db
+            .cancel_buffered_message(&req.machine_id, &req.request_id)
+            .await
+            .map_err(|e| Status::internal(format!("Failed to cancel buffered request: {e}")))?;
+
+        if cancelled {
+            info!(machine_id = %req.machine_id, request_id = %req.request_id, "Buffered request cancelled");
+        }
+
+        Ok(Response::new(CancelBufferedRequestResponse { cancelled }))
+    }
+
+    #[instrument(skip(self, request), fields(rpc = "SetMachineWake"))]
+    async fn set_machine_wake(
+        &self,
+        request: Request<SetMachineWakeRequest>,
+    ) -> Result<Response<SetMachineWakeResponse>, Status> {
+        let user_id = extract_user_id(&request)?;
+        let req = request.into_inner();
+
+        let machine = verify_machine_ownership(&self.db, &req.machine_id, &user_id).await?;
+        let mut metadata: HashMap<String, String> =
+            serde_json::from_str(&machine.metadata).unwrap_or_default();
+
+        if req.mac.is_empty() {
+            WakeConfig::clear(&mut metadata);
+        } else {
+            if req.waker_machine_id.is_empty() {
+                return Err(Status::invalid_argument("waker_machine_id is required"));
+            }
+            if req.waker_machine_id == req.machine_id {
+                return Err(Status::invalid_argument("A machine cannot wake itself"));
+            }
+            verify_machine_ownership(&self.db, &req.waker_machine_id, &user_id).await?;
+            let port = if req.port == 0 {
+                String::new()
+            } else {
+                req.port.to_string()
+            };
+            let target = WakeTarget::parse(&req.mac, &req.broadcast, &port)
+                .map_err(|e| Status::invalid_argument(e.to_string()))?;
+            WakeConfig {
+                target,
+                waker_machine_id: req.waker_machine_id.clone(),
+            }
+            .apply(&mut metadata);
+        }
+
+        let metadata_json = serde_json::to_string(&metadata).unwrap_or_else(|_| "{}".to_string());
+        self.db
+            .update_machine_metadata(&req.machine_id, &metadata_json)
+            .await
+            .map_err(|e| Status::internal(format!("Failed to update machine: {e}")))?;
+        let machine = self
+            .db
+            .get_machine(&req.machine_id)
+            .await
+            .map_err(|e| Status::internal(format!("Failed to load machine: {e}")))?;
+
+        info!(
+            machine_id = %req.machine_id,
+            waker = %req.waker_machine_id,
+            enabled = !req.mac.is_empty(),
+            "Machine wake configuration updated"
+        );
+
+        Ok(Response::new(SetMachineWakeResponse {
+            machine: Some(machine_to_proto(&machine)),
+        }))
+    }
+
+    #[instrument(skip(self, request), fields(rpc = "WakeMachine"))]
+    async fn wake_machine(
+        &self,
+        request: Request<WakeMachineRequest>,
+    ) -> Result<Response<WakeMachineResponse>, Status> {
+        let user_id = extract_user_id(&request)?;
+        let req = request.into_inner();
+
+        verify_machine_ownership(&self.db, &req.machine_id, &user_id).await?;
+        let Some(wake) = &self.wake else {
+            return Err(Status::unimplemented(
+                "Wake-on-LAN is disabled on this relay",
+            ));
+        };
+
+        let outcome = wake
+            .wake(&req.machine_id)
+            .await
+            .map_err(|e| wake_error_to_status(&e))?;
+
+        Ok(Response::new(WakeMachineResponse {
+            outcome: wake_outcome_to_proto(outcome) as i32,
+        }))
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::panic, clippy::expect_used, clippy::unwrap_used)]
+mod tests {
+    use super::*;
+    use crate::server::test_helpers::{test_claims, test_claims_u2, test_db_with_two_users};
+    use crate::storage::BufferMessageParams;
+
+    fn authed<T>(inner: T, claims: crate::auth::Claims) -> Request<T> {
+        let mut req = Request::new(inner);
+        req.extensions_mut().insert(claims);
+        req
+    }
+
+    async fn svc_with_buffered(request_ids: &[&str]) -> MachineServiceImpl {
+        let db = test_db_with_two_users().await;
+        for request_id in request_ids {
+            db.buffer_message(&BufferMessageParams {
+                machine_id: "m1",
+                request_id,
+                method: "AgentService/CancelTurn",
+                payload: b"data",
+                metadata: "{}",
+                priority: 10,
+                ttl_secs: 3600,
+            })
+            .await
+            .unwrap();
+        }
+        MachineServiceImpl::new(db)
+    }
+
+    #[tokio::test]
+    async fn list_and_cancel_buffered_requests() {
+        let svc = svc_with_buffered(&["r1", "r2"]).await;
+
+        let resp = svc
+            .cancel_buffered_request(authed(
+                CancelBufferedRequestRequest {
+                    machine_id: "m1".into(),
+                    request_id: "r1".into(),
+                },
+                test_claims(),
+            ))
+            .await
+            .unwrap();
+        assert!(resp.into_inner().cancelled);
+
+        let requests = svc
+            .list_buffered_requests(authed(
+                ListBufferedRequestsRequest {
+                    machine_id: "m1".into(),
+                    include_delivered: false,
+                },
+                test_claims(),
+            ))
+            .await
+            .unwrap()
+            .into_inner()
+            .requests;
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].request_id, "r2");
+        assert_eq!(requests[0].method, "AgentService/CancelTurn");
+        assert_eq!(requests[0].priority, 10);
+        assert!(requests[0].delivered_at.is_none());
+    }
+
+    #[tokio::test]
+    async fn buffered_requests_are_private_to_the_owner() {
+        let svc = svc_with_buffered(&["r1"]).await;
+
+        let err = svc
+            .list_buffered_requests(authed(
+                ListBufferedRequestsRequest {
+                    machine_id: "m1".into(),
+                    include_delivered: true,
+                },
+                test_claims_u2(),
+            ))
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), tonic::Code::PermissionDenied);
+
+        let err = svc
+            .cancel_buffered_request(authed(
+                CancelBufferedRequestRequest {
+                    machine_id: "m1".into(),
+                    request_id: "r1".into(),
+                },
+                test_claims_u2(),
+            ))
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), tonic::Code::PermissionDenied);
+        assert_eq!(svc.db.count_buffered_messages("m1").await.unwrap(), 1);
+    }
+
+    fn set_wake(mac: &str, waker: &str) -> SetMachineWakeRequest {
+        SetMachineWakeRequest {
+            machine_id: "m1".into(),
+            mac: mac.into(),
+            waker_machine_id: waker.into(),
+            broadcast: String::new(),
+            port: 0,
+        }
+    }
+
+    #[tokio::test]
+    async fn set_and_clear_machine_wake() {
+        let db = test_db_with_two_users().await;
+        db.create_machine("nas", "nas", "u1", "{}").await.unwrap();
+        db.create_machine("eve-box", "eve-box", "u2", "{}")
+            .await
+            .unwrap();
+        let svc = MachineServiceImpl::new(db);
+
+        let machine = svc
+            .set_machine_wake(authed(set_wake("AA-BB-CC-DD-EE-FF", "nas"), test_claims()))
+            .await
+            .unwrap()
+            .into_inner()
+            .machine
+            .unwrap();
+        assert_eq!(machine.metadata["wake_mac"], "aa:bb:cc:dd:ee:ff");
+        assert_eq!(machine.metadata["wake_via"], "nas");
+
+        // The waker must be one of the caller's machines
+        let err = svc
+            .set_machine_wake(authed(
+                set_wake("aa:bb:cc:dd:ee:ff", "eve-box"),
+                test_claims(),
+            ))
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), tonic::Code::PermissionDenied);
+
+        let machine = svc
+            .set_machine_wake(authed(set_wake("", ""), test_claims()))
+            .await
+            .unwrap()
+            .into_inner()
+            .machine
+            .unwrap();
+        assert!(!machine.metadata.contains_key("wake_mac"));
+    }
+
+    #[tokio::test]
+    async fn wake_machine_requires_configuration() {
+        let db = test_db_with_two_users().await;
+        let registry = Arc::new(crate::registry::ConnectionRegistry::new());
+        let wake = Arc::new(WakeManager::new(
+            db.clone(),
+            registry,
+            std::time::Duration::from_mins(1),
+        ));
+        let svc = MachineServiceImpl::new(db).with_wake(wake);
+
+        let err = svc
+            .wake_machine(authed(
+                WakeMachineRequest {
+                    machine_id: "m1".into(),
+                },
+                test_claims(),
+            ))
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), tonic::Code::FailedPrecondition);
+    }
 }
@@ -14,6 +14,9 @@ pub mod subagent_proxy;
 pub mod tunnel_svc;
 pub mod worktree_proxy;
 
+#[cfg(test)]
+#[allow(clippy::panic, clippy::expect_used, clippy::unwrap_used)]
+mod auth_sso_tests;
 #[cfg(test)]
 #[allow(clippy::panic, clippy::expect_used, clippy::unwrap_used)]
 mod auth_svc_tests;
@@ -12,13 +12,14 @@ use betcode_proto::v1::{
     CreateOrchestrationResponse, ListSubagentsRequest, ListSubagentsResponse, OrchestrationEvent,
     RevokeAutoApproveRequest, RevokeAutoApproveResponse, SendToSubagentRequest,
     SendToSubagentResponse, SpawnSubagentRequest, SpawnSubagentResponse, SubagentEvent,
-    WatchOrchestrationRequest, WatchSubagentRequest,
+    SubagentPermissionResponse, SubagentPermissionResult, WatchOrchestrationRequest,
+    WatchSubagentRequest,
 };
 
 use betcode_proto::methods::{
     METHOD_CANCEL_SUBAGENT, METHOD_CREATE_ORCHESTRATION, METHOD_LIST_SUBAGENTS,
-    METHOD_REVOKE_AUTO_APPROVE, METHOD_SEND_TO_SUBAGENT, METHOD_SPAWN_SUBAGENT,
-    METHOD_WATCH_ORCHESTRATION, METHOD_WATCH_SUBAGENT,
+    METHOD_RESPOND_TO_SUBAGENT_PERMISSION, METHOD_REVOKE_AUTO_APPROVE, METHOD_SEND_TO_SUBAGENT,
+    METHOD_SPAWN_SUBAGENT, METHOD_WATCH_ORCHESTRATION, METHOD_WATCH_SUBAGENT,
 };
 
 use crate::router::RequestRouter;
@@ -142,4 +143,18 @@ impl SubagentService for SubagentProxyService {
         )
         .await
     }
+
+    #[instrument(skip(self, request), fields(rpc = "RespondToSubagentPermission"))]
+    async fn respond_to_subagent_permission(
+        &self,
+        request: Request<SubagentPermissionResponse>,
+    ) -> Result<Response<SubagentPermissionResult>, Status> {
+        super::grpc_util::forward_unary_rpc(
+            &self.router,
+            &self.db,
+            request,
+            METHOD_RESPOND_TO_SUBAGENT_PERMISSION,
+        )
+        .await
+    }
 }
@@ -13,7 +13,11 @@ use betcode_proto::v1::{
     FrameType, TunnelFrame, TunnelHeartbeat, TunnelRegisterRequest, TunnelRegisterResponse,
 };
 
+#[cfg(feature = "push-notifications")]
+use crate::notifications::{NotificationDispatcher, NotificationIntent};
+
 use crate::buffer::BufferManager;
+use crate::cluster::Cluster;
 use crate::registry::ConnectionRegistry;
 use crate::server::interceptor::extract_claims;
 use crate::storage::RelayDatabase;
@@ -25,6 +29,9 @@ pub struct TunnelServiceImpl {
     db: RelayDatabase,
     buffer: Arc<BufferManager>,
     mtls_enabled: bool,
+    cluster: Option<Arc<Cluster>>,
+    #[cfg(feature = "push-notifications")]
+    notifier: Option<Arc<NotificationDispatcher>>,
 }
 
 impl TunnelServiceImpl {
@@ -39,8 +46,61 @@ impl TunnelServiceImpl {
             db,
             buffer,
             mtls_enabled,
+            cluster: None,
+            #[cfg(feature = "push-notifications")]
+            notifier: None,
         }
     }
+
+    /// Record the tunnels this instance holds in the cluster directory.
+    #[must_use]
+    pub fn with_cluster(mut self, cluster: Arc<Cluster>) -> Self {
+        self.cluster = Some(cluster);
+        self
+    }
+
+    /// Send push notifications for the intents daemons raise over their
+    /// tunnels.
+    #[cfg(feature = "push-notifications")]
+    #[must_use]
+    pub fn with_notifications(mut self, notifier: Arc<NotificationDispatcher>) -> Self {
+        self.notifier = Some(notifier);
+        self
+    }
+}
+
+/// Dispatch the notification intent carried by a `NOTIFY` control frame.
+///
+/// The intent is attributed to the tunnel's own machine and owner, whatever
+/// machine the frame claims to come from.
+#[cfg(feature = "push-notifications")]
+fn notify(
+    notifier: &Arc<NotificationDispatcher>,
+    owner_id: &str,
+    machine_id: &str,
+    frame: &TunnelFrame,
+) {
+    use betcode_proto::v1::TunnelControlType;
+    use betcode_proto::v1::tunnel_frame::Payload;
+
+    let Some(Payload::Control(ctrl)) = &frame.payload else {
+        return;
+    };
+    if ctrl.control_type != TunnelControlType::Notify as i32 {
+        return;
+    }
+    let Some(intent) = NotificationIntent::from_params(&ctrl.params) else {
+        warn!(machine_id = %machine_id, "Ignoring malformed notification intent");
+        return;
+    };
+    let notifier = Arc::clone(notifier);
+    let owner_id = owner_id.to_string();
+    let machine_id = machine_id.to_string();
+    tokio::spawn(async move {
+        if let Err(e) = notifier.dispatch(&owner_id, &machine_id, &intent).await {
+            warn!(machine_id = %machine_id, error = %e, "Failed to dispatch notification");
+        }
+    });
 }
 
 #[tonic::async_trait]
@@ -94,6 +154,9 @@ impl TunnelService for TunnelServiceImpl {
         let registry = Arc::clone(&self.registry);
         let db = self.db.clone();
         let buffer = Arc::clone(&self.buffer);
+        let cluster = self.cluster.clone();
+        #[cfg(feature = "push-notifications")]
+        let notifier = self.notifier.clone();
 
         tokio::spawn(async move {
             // Wait for first frame to identify the machine
@@ -144,9 +207,16 @@ impl TunnelService for TunnelServiceImpl {
 
             // Register the connection
             let conn = registry
-                .register(machine_id.clone(), owner_id, relay_tx)
+                .register(machine_id.clone(), owner_id.clone(), relay_tx)
                 .await;
 
+            // Let the other relay instances route this machine's requests here
+            if let Some(cluster) = &cluster
+                && let Err(e) = cluster.directory.claim(&machine_id).await
+            {
+                warn!(machine_id = %machine_id, error = %e, "Failed to claim machine in cluster directory");
+            }
+
             // Update machine status to online
             if let Err(e) = db.update_machine_status(&machine_id, "online").await {
                 warn!(machine_id = %machine_id, error = %e, "Failed to update machine status");
@@ -165,9 +235,11 @@ impl TunnelService for TunnelServiceImpl {
 
             // Forward frames from relay_rx to daemon (out_tx)
             let out_tx_fwd = out_tx.clone();
+            let conn_fwd = Arc::clone(&conn);
             let relay_rx_handle = tokio::spawn(async move {
                 let mut relay_rx = relay_rx;
                 while let Some(frame) = relay_rx.recv().await {
+                    conn_fwd.record_sent(&frame).await;
                     if out_tx_fwd.send(Ok(frame)).await.is_err() {
                         break;
                     }
@@ -181,6 +253,7 @@ impl TunnelService for TunnelServiceImpl {
                 daemon_frame_count += 1;
                 match result {
                     Ok(frame) => {
+                        conn_ref.record_received(&frame).await;
                         let frame_type = frame.frame_type;
                         let rid = frame.request_id.clone();
 
@@ -237,6 +310,11 @@ impl TunnelService for TunnelServiceImpl {
                             if !conn_ref.complete_pending(&rid, frame).await {
                                 warn!(request_id = %rid, "No pending waiter for Response frame");
                             }
+                        } else if frame_type == FrameType::Control as i32 {
+                            #[cfg(feature = "push-notifications")]
+                            if let Some(notifier) = &notifier {
+                                notify(notifier, &owner_id, &machine_id, &frame);
+                            }
                         }
                     }
                     Err(e) => {
@@ -256,7 +334,20 @@ impl TunnelService for TunnelServiceImpl {
             registry.unregister(&machine_id).await;
             relay_rx_handle.abort();
 
-            if let Err(e) = db.update_machine_status(&machine_id, "offline").await {
+            // If the machine already reconnected through another instance,
+            // that instance owns its location and status now.
+            let still_ours = match &cluster {
+                Some(cluster) => cluster
+                    .directory
+                    .release(&machine_id)
+                    .await
+                    .unwrap_or_else(|e| {
+                        warn!(machine_id = %machine_id, error = %e, "Failed to release machine in cluster directory");
+                        true
+                    }),
+                None => true,
+            };
+            if still_ours && let Err(e) = db.update_machine_status(&machine_id, "offline").await {
                 warn!(machine_id = %machine_id, error = %e, "Failed to update machine status");
             }
         });
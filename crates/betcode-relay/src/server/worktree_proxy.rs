@@ -1,5 +1,6 @@
 //! `WorktreeService` proxy that forwards calls through the tunnel to daemons.
 
+use std::pin::Pin;
 use std::sync::Arc;
 
 use tonic::{Request, Response, Status};
@@ -7,12 +8,15 @@ use tracing::instrument;
 
 use betcode_proto::v1::worktree_service_server::WorktreeService;
 use betcode_proto::v1::{
-    CreateWorktreeRequest, GetWorktreeRequest, ListWorktreesRequest, ListWorktreesResponse,
-    RemoveWorktreeRequest, RemoveWorktreeResponse, WorktreeDetail,
+    CreateWorktreeRequest, GcWorktreesRequest, GcWorktreesResponse, GetWorktreeRequest,
+    GetWorktreeStatusRequest, ListWorktreesRequest, ListWorktreesResponse, RemoveWorktreeRequest,
+    RemoveWorktreeResponse, RunWorktreeSetupRequest, SyncWorktreeRequest, SyncWorktreeResponse,
+    WorktreeDetail, WorktreeSetupEvent, WorktreeStatus,
 };
 
 use betcode_proto::methods::{
-    METHOD_CREATE_WORKTREE, METHOD_GET_WORKTREE, METHOD_LIST_WORKTREES, METHOD_REMOVE_WORKTREE,
+    METHOD_CREATE_WORKTREE, METHOD_GC_WORKTREES, METHOD_GET_WORKTREE, METHOD_GET_WORKTREE_STATUS,
+    METHOD_LIST_WORKTREES, METHOD_REMOVE_WORKTREE, METHOD_RUN_WORKTREE_SETUP, METHOD_SYNC_WORKTREE,
 };
 
 use crate::router::RequestRouter;
@@ -67,6 +71,56 @@ impl WorktreeService for WorktreeProxyService {
         super::grpc_util::forward_unary_rpc(&self.router, &self.db, request, METHOD_GET_WORKTREE)
             .await
     }
+
+    #[instrument(skip(self, request), fields(rpc = "GetWorktreeStatus"))]
+    async fn get_worktree_status(
+        &self,
+        request: Request<GetWorktreeStatusRequest>,
+    ) -> Result<Response<WorktreeStatus>, Status> {
+        super::grpc_util::forward_unary_rpc(
+            &self.router,
+            &self.db,
+            request,
+            METHOD_GET_WORKTREE_STATUS,
+        )
+        .await
+    }
+
+    #[instrument(skip(self, request), fields(rpc = "SyncWorktree"))]
+    async fn sync_worktree(
+        &self,
+        request: Request<SyncWorktreeRequest>,
+    ) -> Result<Response<SyncWorktreeResponse>, Status> {
+        super::grpc_util::forward_unary_rpc(&self.router, &self.db, request, METHOD_SYNC_WORKTREE)
+            .await
+    }
+
+    #[instrument(skip(self, request), fields(rpc = "GcWorktrees"))]
+    async fn gc_worktrees(
+        &self,
+        request: Request<GcWorktreesRequest>,
+    ) -> Result<Response<GcWorktreesResponse>, Status> {
+        super::grpc_util::forward_unary_rpc(&self.router, &self.db, request, METHOD_GC_WORKTREES)
+            .await
+    }
+
+    type RunWorktreeSetupStream =
+        Pin<Box<dyn tokio_stream::Stream<Item = Result<WorktreeSetupEvent, Status>> + Send>>;
+
+    #[instrument(skip(self, request), fields(rpc = "RunWorktreeSetup"))]
+    async fn run_worktree_setup(
+        &self,
+        request: Request<RunWorktreeSetupRequest>,
+    ) -> Result<Response<Self::RunWorktreeSetupStream>, Status> {
+        super::grpc_util::forward_stream_rpc(
+            &self.router,
+            &self.db,
+            request,
+            METHOD_RUN_WORKTREE_SETUP,
+            64,
+        )
+        .await
+    }
 }
 
 #[cfg(test)]
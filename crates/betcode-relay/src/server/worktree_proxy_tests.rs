@@ -2,14 +2,19 @@
 
 use betcode_proto::v1::worktree_service_server::WorktreeService;
 use betcode_proto::v1::{
-    CreateWorktreeRequest, GetWorktreeRequest, ListWorktreesRequest, ListWorktreesResponse,
-    RemoveWorktreeRequest, RemoveWorktreeResponse, WorktreeDetail,
+    CreateWorktreeRequest, GcWorktreeEntry, GcWorktreesRequest, GcWorktreesResponse,
+    GetWorktreeRequest, GetWorktreeStatusRequest, ListWorktreesRequest, ListWorktreesResponse,
+    RemoveWorktreeRequest, RemoveWorktreeResponse, RunWorktreeSetupRequest, SetupFinished,
+    SetupStepStarted, SyncWorktreeRequest, SyncWorktreeResponse, WorktreeDetail,
+    WorktreeSetupEvent, WorktreeStatus, worktree_setup_event,
 };
+use tokio_stream::StreamExt;
 
 use super::WorktreeProxyService;
 use crate::server::test_helpers::{
     assert_daemon_error, assert_no_claims_error, assert_no_machine_error, assert_offline_error,
     assert_wrong_owner_error, make_request, proxy_test_setup, spawn_responder,
+    spawn_stream_responder,
 };
 
 proxy_test_setup!(WorktreeProxyService);
@@ -63,6 +68,7 @@ async fn create_worktree_routes_to_machine() {
             repo_id: "/repo".into(),
             branch: "feature-branch".into(),
             setup_script: String::new(),
+            defer_setup: false,
         },
         "m1",
     );
@@ -96,6 +102,118 @@ async fn remove_worktree_routes_to_machine() {
     assert!(resp.removed);
 }
 
+#[tokio::test]
+async fn get_worktree_status_routes_to_machine() {
+    let (svc, router, rx) = setup_with_machine("m1").await;
+    let status = WorktreeStatus {
+        id: "wt-3".into(),
+        branch: "feat".into(),
+        ahead: 2,
+        behind: 1,
+        exists_on_disk: true,
+        ..Default::default()
+    };
+    spawn_responder(&router, "m1", rx, status);
+    let req = make_request(GetWorktreeStatusRequest { id: "wt-3".into() }, "m1");
+    let resp = svc.get_worktree_status(req).await.unwrap().into_inner();
+    assert_eq!(resp.id, "wt-3");
+    assert_eq!((resp.ahead, resp.behind), (2, 1));
+}
+
+#[tokio::test]
+async fn sync_worktree_routes_to_machine() {
+    let (svc, router, rx) = setup_with_machine("m1").await;
+    spawn_responder(
+        &router,
+        "m1",
+        rx,
+        SyncWorktreeResponse {
+            success: false,
+            base_ref: "origin/main".into(),
+            conflicts: vec!["src/lib.rs".into()],
+            ..Default::default()
+        },
+    );
+    let req = make_request(
+        SyncWorktreeRequest {
+            id: "wt-3".into(),
+            ..Default::default()
+        },
+        "m1",
+    );
+    let resp = svc.sync_worktree(req).await.unwrap().into_inner();
+    assert!(!resp.success);
+    assert_eq!(resp.conflicts, vec!["src/lib.rs".to_string()]);
+}
+
+#[tokio::test]
+async fn gc_worktrees_routes_to_machine() {
+    let (svc, router, rx) = setup_with_machine("m1").await;
+    spawn_responder(
+        &router,
+        "m1",
+        rx,
+        GcWorktreesResponse {
+            entries: vec![GcWorktreeEntry {
+                id: "wt-4".into(),
+                reasons: vec!["merged".into()],
+                ..Default::default()
+            }],
+            dry_run: true,
+        },
+    );
+    let req = make_request(
+        GcWorktreesRequest {
+            dry_run: true,
+            ..Default::default()
+        },
+        "m1",
+    );
+    let resp = svc.gc_worktrees(req).await.unwrap().into_inner();
+    assert!(resp.dry_run);
+    assert_eq!(resp.entries[0].id, "wt-4");
+}
+
+// --- Streaming RPC routing ---
+
+#[tokio::test]
+async fn run_worktree_setup_routes_to_machine() {
+    let (svc, router, rx) = setup_with_machine("m1").await;
+    let events = vec![
+        WorktreeSetupEvent {
+            event: Some(worktree_setup_event::Event::StepStarted(SetupStepStarted {
+                index: 1,
+                total: 1,
+                name: "install".into(),
+            })),
+        },
+        WorktreeSetupEvent {
+            event: Some(worktree_setup_event::Event::Finished(SetupFinished {
+                status: "succeeded".into(),
+            })),
+        },
+    ];
+    spawn_stream_responder(&router, "m1", rx, events);
+
+    let req = make_request(
+        RunWorktreeSetupRequest {
+            id: "wt-1".into(),
+            ..Default::default()
+        },
+        "m1",
+    );
+    let mut stream = svc.run_worktree_setup(req).await.unwrap().into_inner();
+    let mut received = vec![];
+    while let Some(result) = stream.next().await {
+        received.push(result.unwrap());
+    }
+    assert_eq!(received.len(), 2);
+    assert!(matches!(
+        received[1].event,
+        Some(worktree_setup_event::Event::Finished(ref f)) if f.status == "succeeded"
+    ));
+}
+
 // --- Error handling ---
 
 #[tokio::test]
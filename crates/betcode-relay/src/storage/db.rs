@@ -1,5 +1,108 @@
-//! `SQLite` database for `BetCode` relay server.
+//! Relay database connection: `SQLite` or `PostgreSQL`, chosen by URL scheme.
+//!
+//! Queries are written once against sqlx's `Any` driver, so they must stay
+//! within the SQL both engines accept: `$N` placeholders, `ON CONFLICT`
+//! upserts, `RETURNING`, and only `i64`, `bool`, text and blob bindings.
+
+use std::path::Path;
+
+use sqlx::any::AnyPoolOptions;
+use sqlx::{Any, Pool};
+use tracing::info;
 
 pub use betcode_core::db::DatabaseError;
 
-betcode_core::define_database!(RelayDatabase, "Relay database migrations complete");
+/// Storage engine behind a [`RelayDatabase`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Backend {
+    Sqlite,
+    Postgres,
+}
+
+impl Backend {
+    /// The backend serving a database URL, by its scheme.
+    pub fn from_url(url: &str) -> Result<Self, DatabaseError> {
+        let scheme = url.split_once(':').map_or("", |(scheme, _)| scheme);
+        match scheme {
+            "sqlite" => Ok(Self::Sqlite),
+            "postgres" | "postgresql" => Ok(Self::Postgres),
+            _ => Err(DatabaseError::Connection(format!(
+                "Unsupported database URL scheme '{scheme}': expected sqlite: or postgres://"
+            ))),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct RelayDatabase {
+    pool: Pool<Any>,
+    backend: Backend,
+}
+
+impl RelayDatabase {
+    /// Open or create a `SQLite` database at the given path.
+    pub async fn open(path: &Path) -> Result<Self, DatabaseError> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).map_err(|e| DatabaseError::Io(e.to_string()))?;
+        }
+        let db = Self::connect_with(
+            &format!("sqlite:{}?mode=rwc", path.display()),
+            AnyPoolOptions::new().max_connections(5),
+        )
+        .await?;
+        sqlx::query("PRAGMA journal_mode = WAL")
+            .execute(db.pool())
+            .await?;
+        info!(path = %path.display(), "Database opened");
+        Ok(db)
+    }
+
+    /// Connect to the database at `url` (`sqlite:...` or `postgres://...`).
+    pub async fn connect(url: &str) -> Result<Self, DatabaseError> {
+        let db = Self::connect_with(url, AnyPoolOptions::new().max_connections(10)).await?;
+        info!(backend = ?db.backend, "Database connected");
+        Ok(db)
+    }
+
+    /// Open an in-memory `SQLite` database (for testing).
+    pub async fn open_in_memory() -> Result<Self, DatabaseError> {
+        Self::connect_with("sqlite::memory:", AnyPoolOptions::new().max_connections(1)).await
+    }
+
+    async fn connect_with(url: &str, options: AnyPoolOptions) -> Result<Self, DatabaseError> {
+        let backend = Backend::from_url(url)?;
+        sqlx::any::install_default_drivers();
+        let pool = options
+            .connect(url)
+            .await
+            .map_err(|e| DatabaseError::Connection(e.to_string()))?;
+        let db = Self { pool, backend };
+        db.run_migrations().await?;
+        Ok(db)
+    }
+
+    /// Run the migrations for this database's backend.
+    async fn run_migrations(&self) -> Result<(), DatabaseError> {
+        let migrator = match self.backend {
+            Backend::Sqlite => sqlx::migrate!("./migrations/sqlite"),
+            Backend::Postgres => sqlx::migrate!("./migrations/postgres"),
+        };
+        migrator
+            .run(&self.pool)
+            .await
+            .map_err(|e| DatabaseError::Migration(e.to_string()))?;
+
+        info!("Relay database migrations complete");
+        Ok(())
+    }
+
+    /// Get a reference to the connection pool.
+    pub const fn pool(&self) -> &Pool<Any> {
+        &self.pool
+    }
+
+    /// The storage engine behind this database.
+    pub const fn backend(&self) -> Backend {
+        self.backend
+    }
+}
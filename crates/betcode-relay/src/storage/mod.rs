@@ -1,18 +1,26 @@
-//! `SQLite` storage for `BetCode` relay server.
+//! `SQLite` or `PostgreSQL` storage for `BetCode` relay server.
 //!
-//! Provides persistence for users, tokens, machines, message buffer, and certificates.
+//! Provides persistence for users, SSO identities, tokens, machines, message
+//! buffer, certificates, the cluster directory, and usage rollups.
 
 mod db;
 mod models;
 mod queries;
 mod queries_buffer;
 mod queries_certs;
+mod queries_cluster;
 mod queries_notifications;
+mod queries_sso;
+mod queries_usage;
 
+#[cfg(test)]
+#[allow(clippy::panic, clippy::expect_used, clippy::unwrap_used)]
+pub(crate) mod test_backends;
 #[cfg(test)]
 #[allow(clippy::panic, clippy::expect_used, clippy::unwrap_used)]
 mod tests;
 
-pub use db::{DatabaseError, RelayDatabase};
+pub use db::{Backend, DatabaseError, RelayDatabase};
 pub use models::*;
-pub use queries_buffer::{BufferMessageParams, CertificateParams};
+pub use queries_buffer::{BufferCaps, BufferMessageParams, CappedBuffer, CertificateParams};
+pub use queries_usage::{UsageGroupBy, UsageQuery};
@@ -48,6 +48,8 @@ pub struct BufferedMessage {
     pub priority: i64,
     pub expires_at: i64,
     pub created_at: i64,
+    /// When the request was sent to the machine; `None` while still queued.
+    pub delivered_at: Option<i64>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
@@ -72,3 +74,40 @@ pub struct DeviceToken {
     pub platform: String,
     pub created_at: i64,
 }
+
+/// A user's push notification preferences.
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct NotificationPreferencesRow {
+    pub user_id: String,
+    /// Comma-separated event kinds.
+    pub events: String,
+    pub quiet_hours_start: Option<i64>,
+    pub quiet_hours_end: Option<i64>,
+    pub utc_offset_minutes: i64,
+    pub updated_at: i64,
+}
+
+/// A relay instance in a cluster sharing this database.
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct RelayInstance {
+    pub id: String,
+    /// URL peers use to reach the instance's `RelayPeerService`.
+    pub address: String,
+    pub heartbeat_at: i64,
+}
+
+/// Tunnel traffic summed over one hour, per machine and method.
+///
+/// "Sent" frames went down the tunnel to the machine, "received" frames came
+/// back up it. Grouped reports leave the columns outside the grouping empty.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, sqlx::FromRow)]
+pub struct UsageRow {
+    pub hour_start: i64,
+    pub machine_id: String,
+    pub owner_id: String,
+    pub method: String,
+    pub frames_sent: i64,
+    pub bytes_sent: i64,
+    pub frames_received: i64,
+    pub bytes_received: i64,
+}
@@ -21,7 +21,7 @@ impl RelayDatabase {
         let now = unix_timestamp();
 
         sqlx::query(
-            "INSERT INTO users (id, username, email, password_hash, created_at, updated_at) VALUES (?, ?, ?, ?, ?, ?)",
+            "INSERT INTO users (id, username, email, password_hash, created_at, updated_at) VALUES ($1, $2, $3, $4, $5, $6)",
         )
         .bind(id)
         .bind(username)
@@ -37,7 +37,7 @@ impl RelayDatabase {
 
     /// Get a user by ID.
     pub async fn get_user(&self, id: &str) -> Result<User, DatabaseError> {
-        sqlx::query_as::<_, User>("SELECT * FROM users WHERE id = ?")
+        sqlx::query_as::<_, User>("SELECT * FROM users WHERE id = $1")
             .bind(id)
             .fetch_optional(self.pool())
             .await?
@@ -46,7 +46,7 @@ impl RelayDatabase {
 
     /// Get a user by username.
     pub async fn get_user_by_username(&self, username: &str) -> Result<User, DatabaseError> {
-        sqlx::query_as::<_, User>("SELECT * FROM users WHERE username = ?")
+        sqlx::query_as::<_, User>("SELECT * FROM users WHERE username = $1")
             .bind(username)
             .fetch_optional(self.pool())
             .await?
@@ -68,7 +68,7 @@ impl RelayDatabase {
         let now = unix_timestamp();
 
         sqlx::query(
-            "INSERT INTO tokens (id, user_id, token_hash, expires_at, created_at) VALUES (?, ?, ?, ?, ?)",
+            "INSERT INTO tokens (id, user_id, token_hash, expires_at, created_at) VALUES ($1, $2, $3, $4, $5)",
         )
         .bind(id)
         .bind(user_id)
@@ -83,7 +83,7 @@ impl RelayDatabase {
 
     /// Get a token by ID.
     pub async fn get_token(&self, id: &str) -> Result<Token, DatabaseError> {
-        sqlx::query_as::<_, Token>("SELECT * FROM tokens WHERE id = ?")
+        sqlx::query_as::<_, Token>("SELECT * FROM tokens WHERE id = $1")
             .bind(id)
             .fetch_optional(self.pool())
             .await?
@@ -103,8 +103,8 @@ impl RelayDatabase {
         let grace_cutoff = now - grace_window_secs;
 
         let token = sqlx::query_as::<_, Token>(
-            "SELECT * FROM tokens WHERE token_hash = ? AND expires_at > ? AND revoked = 0 \
-             AND (rotated_at IS NULL OR rotated_at > ?)",
+            "SELECT * FROM tokens WHERE token_hash = $1 AND expires_at > $2 AND revoked = 0 \
+             AND (rotated_at IS NULL OR rotated_at > $3)",
         )
         .bind(token_hash)
         .bind(now)
@@ -126,8 +126,8 @@ impl RelayDatabase {
         let now = unix_timestamp();
 
         let result = sqlx::query(
-            "UPDATE tokens SET rotated_at = COALESCE(rotated_at, ?), successor_id = ? \
-             WHERE id = ? AND revoked = 0",
+            "UPDATE tokens SET rotated_at = COALESCE(rotated_at, $1), successor_id = $2 \
+             WHERE id = $3 AND revoked = 0",
         )
         .bind(now)
         .bind(successor_id)
@@ -140,7 +140,7 @@ impl RelayDatabase {
 
     /// Revoke a token by ID.
     pub async fn revoke_token(&self, id: &str) -> Result<bool, DatabaseError> {
-        let result = sqlx::query("UPDATE tokens SET revoked = 1 WHERE id = ?")
+        let result = sqlx::query("UPDATE tokens SET revoked = 1 WHERE id = $1")
             .bind(id)
             .execute(self.pool())
             .await?;
@@ -150,7 +150,7 @@ impl RelayDatabase {
 
     /// Revoke all tokens for a user.
     pub async fn revoke_user_tokens(&self, user_id: &str) -> Result<u64, DatabaseError> {
-        let result = sqlx::query("UPDATE tokens SET revoked = 1 WHERE user_id = ?")
+        let result = sqlx::query("UPDATE tokens SET revoked = 1 WHERE user_id = $1")
             .bind(user_id)
             .execute(self.pool())
             .await?;
@@ -173,7 +173,7 @@ impl RelayDatabase {
         let now = unix_timestamp();
 
         sqlx::query(
-            "INSERT INTO machines (id, name, owner_id, registered_at, last_seen, metadata) VALUES (?, ?, ?, ?, ?, ?)",
+            "INSERT INTO machines (id, name, owner_id, registered_at, last_seen, metadata) VALUES ($1, $2, $3, $4, $5, $6)",
         )
         .bind(id)
         .bind(name)
@@ -189,7 +189,7 @@ impl RelayDatabase {
 
     /// Get a machine by ID.
     pub async fn get_machine(&self, id: &str) -> Result<Machine, DatabaseError> {
-        sqlx::query_as::<_, Machine>("SELECT * FROM machines WHERE id = ?")
+        sqlx::query_as::<_, Machine>("SELECT * FROM machines WHERE id = $1")
             .bind(id)
             .fetch_optional(self.pool())
             .await?
@@ -206,21 +206,21 @@ impl RelayDatabase {
     ) -> Result<Vec<Machine>, DatabaseError> {
         let machines = if let Some(status) = status_filter {
             sqlx::query_as::<_, Machine>(
-                "SELECT * FROM machines WHERE owner_id = ? AND status = ? ORDER BY last_seen DESC LIMIT ? OFFSET ?",
+                "SELECT * FROM machines WHERE owner_id = $1 AND status = $2 ORDER BY last_seen DESC LIMIT $3 OFFSET $4",
             )
             .bind(owner_id)
             .bind(status)
-            .bind(limit)
-            .bind(offset)
+            .bind(i64::from(limit))
+            .bind(i64::from(offset))
             .fetch_all(self.pool())
             .await?
         } else {
             sqlx::query_as::<_, Machine>(
-                "SELECT * FROM machines WHERE owner_id = ? ORDER BY last_seen DESC LIMIT ? OFFSET ?",
+                "SELECT * FROM machines WHERE owner_id = $1 ORDER BY last_seen DESC LIMIT $2 OFFSET $3",
             )
             .bind(owner_id)
-            .bind(limit)
-            .bind(offset)
+            .bind(i64::from(limit))
+            .bind(i64::from(offset))
             .fetch_all(self.pool())
             .await?
         };
@@ -232,7 +232,7 @@ impl RelayDatabase {
     pub async fn update_machine_status(&self, id: &str, status: &str) -> Result<(), DatabaseError> {
         let now = unix_timestamp();
 
-        sqlx::query("UPDATE machines SET status = ?, last_seen = ? WHERE id = ?")
+        sqlx::query("UPDATE machines SET status = $1, last_seen = $2 WHERE id = $3")
             .bind(status)
             .bind(now)
             .bind(id)
@@ -246,7 +246,7 @@ impl RelayDatabase {
     pub async fn touch_machine(&self, id: &str) -> Result<(), DatabaseError> {
         let now = unix_timestamp();
 
-        sqlx::query("UPDATE machines SET last_seen = ? WHERE id = ?")
+        sqlx::query("UPDATE machines SET last_seen = $1 WHERE id = $2")
             .bind(now)
             .bind(id)
             .execute(self.pool())
@@ -257,7 +257,7 @@ impl RelayDatabase {
 
     /// Remove a machine.
     pub async fn remove_machine(&self, id: &str) -> Result<bool, DatabaseError> {
-        let result = sqlx::query("DELETE FROM machines WHERE id = ?")
+        let result = sqlx::query("DELETE FROM machines WHERE id = $1")
             .bind(id)
             .execute(self.pool())
             .await?;
@@ -265,13 +265,27 @@ impl RelayDatabase {
         Ok(result.rows_affected() > 0)
     }
 
+    /// Replace a machine's metadata JSON. Returns whether the machine exists.
+    pub async fn update_machine_metadata(
+        &self,
+        id: &str,
+        metadata: &str,
+    ) -> Result<bool, DatabaseError> {
+        let result = sqlx::query("UPDATE machines SET metadata = $1 WHERE id = $2")
+            .bind(metadata)
+            .bind(id)
+            .execute(self.pool())
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
     /// Update a machine's identity public key.
     pub async fn update_machine_identity_pubkey(
         &self,
         id: &str,
         pubkey: &[u8],
     ) -> Result<(), DatabaseError> {
-        sqlx::query("UPDATE machines SET identity_pubkey = ? WHERE id = ?")
+        sqlx::query("UPDATE machines SET identity_pubkey = $1 WHERE id = $2")
             .bind(pubkey)
             .bind(id)
             .execute(self.pool())
@@ -295,13 +309,13 @@ impl RelayDatabase {
         status_filter: Option<&str>,
     ) -> Result<i64, DatabaseError> {
         let row: (i64,) = if let Some(status) = status_filter {
-            sqlx::query_as("SELECT COUNT(*) FROM machines WHERE owner_id = ? AND status = ?")
+            sqlx::query_as("SELECT COUNT(*) FROM machines WHERE owner_id = $1 AND status = $2")
                 .bind(owner_id)
                 .bind(status)
                 .fetch_one(self.pool())
                 .await?
         } else {
-            sqlx::query_as("SELECT COUNT(*) FROM machines WHERE owner_id = ?")
+            sqlx::query_as("SELECT COUNT(*) FROM machines WHERE owner_id = $1")
                 .bind(owner_id)
                 .fetch_one(self.pool())
                 .await?
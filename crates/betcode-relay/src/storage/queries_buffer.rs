@@ -15,6 +15,26 @@ pub struct BufferMessageParams<'a> {
     pub ttl_secs: i64,
 }
 
+/// Limits on undelivered buffered messages.
+#[derive(Debug, Clone, Copy)]
+pub struct BufferCaps {
+    /// Per machine.
+    pub per_machine: i64,
+    /// Across all machines of one owner, if capped.
+    pub per_user: Option<i64>,
+}
+
+/// Outcome of [`RelayDatabase::buffer_message_within_caps`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CappedBuffer {
+    /// The message was buffered with this ID.
+    Buffered(i64),
+    /// The machine already had `current` messages buffered.
+    MachineFull { current: i64 },
+    /// The machine's owner already had `current` messages buffered.
+    UserFull { owner_id: String, current: i64 },
+}
+
 /// Parameters for creating a certificate.
 pub struct CertificateParams<'a> {
     pub id: &'a str,
@@ -39,8 +59,8 @@ impl RelayDatabase {
         let now = unix_timestamp();
         let expires_at = now + params.ttl_secs;
 
-        let result = sqlx::query(
-            "INSERT INTO message_buffer (machine_id, request_id, method, payload, metadata, priority, expires_at, created_at) VALUES (?, ?, ?, ?, ?, ?, ?, ?)",
+        let id: i64 = sqlx::query_scalar(
+            "INSERT INTO message_buffer (machine_id, request_id, method, payload, metadata, priority, expires_at, created_at) VALUES ($1, $2, $3, $4, $5, $6, $7, $8) RETURNING id",
         )
         .bind(params.machine_id)
         .bind(params.request_id)
@@ -50,22 +70,23 @@ impl RelayDatabase {
         .bind(params.priority)
         .bind(expires_at)
         .bind(now)
-        .execute(self.pool())
+        .fetch_one(self.pool())
         .await?;
 
-        Ok(result.last_insert_rowid())
+        Ok(id)
     }
 
-    /// Fetch buffered messages for a machine (priority DESC, `created_at` ASC).
+    /// Fetch undelivered buffered messages for a machine (priority DESC,
+    /// `created_at` ASC).
     ///
-    /// Messages are NOT deleted by this call. Use `delete_buffered_message` to
-    /// remove each message after it has been successfully delivered.
+    /// Messages are NOT removed by this call. Use
+    /// `mark_buffered_message_delivered` after each has been sent.
     pub async fn drain_buffer(
         &self,
         machine_id: &str,
     ) -> Result<Vec<BufferedMessage>, DatabaseError> {
         let messages = sqlx::query_as::<_, BufferedMessage>(
-            "SELECT * FROM message_buffer WHERE machine_id = ? AND expires_at > ? ORDER BY priority DESC, created_at ASC",
+            "SELECT * FROM message_buffer WHERE machine_id = $1 AND delivered_at IS NULL AND expires_at > $2 ORDER BY priority DESC, created_at ASC, id ASC",
         )
         .bind(machine_id)
         .bind(unix_timestamp())
@@ -77,7 +98,7 @@ impl RelayDatabase {
 
     /// Delete a single buffered message by ID after successful delivery.
     pub async fn delete_buffered_message(&self, id: i64) -> Result<bool, DatabaseError> {
-        let result = sqlx::query("DELETE FROM message_buffer WHERE id = ?")
+        let result = sqlx::query("DELETE FROM message_buffer WHERE id = $1")
             .bind(id)
             .execute(self.pool())
             .await?;
@@ -85,11 +106,69 @@ impl RelayDatabase {
         Ok(result.rows_affected() > 0)
     }
 
+    /// Record that a buffered message was sent to its machine, dropping its
+    /// payload. The row stays until it expires as a delivery receipt.
+    ///
+    /// Returns `false` if the message is gone (cancelled or expired) or was
+    /// already delivered.
+    pub async fn mark_buffered_message_delivered(&self, id: i64) -> Result<bool, DatabaseError> {
+        let result = sqlx::query(
+            "UPDATE message_buffer SET delivered_at = $1, payload = $2 WHERE id = $3 AND delivered_at IS NULL",
+        )
+        .bind(unix_timestamp())
+        .bind(Vec::<u8>::new())
+        .bind(id)
+        .execute(self.pool())
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// List a machine's queued messages in delivery order, optionally
+    /// preceded by the ones already delivered (in the order they went out).
+    pub async fn list_buffered_messages(
+        &self,
+        machine_id: &str,
+        include_delivered: bool,
+    ) -> Result<Vec<BufferedMessage>, DatabaseError> {
+        let messages = sqlx::query_as::<_, BufferedMessage>(
+            "SELECT * FROM message_buffer WHERE machine_id = $1 AND expires_at > $2 \
+             AND ($3 OR delivered_at IS NULL) \
+             ORDER BY delivered_at IS NULL, delivered_at ASC, priority DESC, created_at ASC, id ASC",
+        )
+        .bind(machine_id)
+        .bind(unix_timestamp())
+        .bind(include_delivered)
+        .fetch_all(self.pool())
+        .await?;
+
+        Ok(messages)
+    }
+
+    /// Remove a buffered message that has not been delivered yet.
+    ///
+    /// Returns `false` if there is no such queued message.
+    pub async fn cancel_buffered_message(
+        &self,
+        machine_id: &str,
+        request_id: &str,
+    ) -> Result<bool, DatabaseError> {
+        let result = sqlx::query(
+            "DELETE FROM message_buffer WHERE machine_id = $1 AND request_id = $2 AND delivered_at IS NULL",
+        )
+        .bind(machine_id)
+        .bind(request_id)
+        .execute(self.pool())
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
     /// Remove expired buffered messages.
     pub async fn cleanup_expired_buffer(&self) -> Result<u64, DatabaseError> {
         let now = unix_timestamp();
 
-        let result = sqlx::query("DELETE FROM message_buffer WHERE expires_at <= ?")
+        let result = sqlx::query("DELETE FROM message_buffer WHERE expires_at <= $1")
             .bind(now)
             .execute(self.pool())
             .await?;
@@ -97,17 +176,107 @@ impl RelayDatabase {
         Ok(result.rows_affected())
     }
 
-    /// Count buffered messages for a machine.
+    /// Count undelivered buffered messages for a machine.
     pub async fn count_buffered_messages(&self, machine_id: &str) -> Result<i64, DatabaseError> {
-        let row: (i64,) =
-            sqlx::query_as("SELECT COUNT(*) FROM message_buffer WHERE machine_id = ?")
-                .bind(machine_id)
-                .fetch_one(self.pool())
-                .await?;
+        let row: (i64,) = sqlx::query_as(
+            "SELECT COUNT(*) FROM message_buffer WHERE machine_id = $1 AND delivered_at IS NULL",
+        )
+        .bind(machine_id)
+        .fetch_one(self.pool())
+        .await?;
+
+        Ok(row.0)
+    }
+
+    /// Count undelivered buffered messages across all of a user's machines.
+    pub async fn count_buffered_messages_for_user(
+        &self,
+        user_id: &str,
+    ) -> Result<i64, DatabaseError> {
+        let row: (i64,) = sqlx::query_as(
+            "SELECT COUNT(*) FROM message_buffer b JOIN machines m ON m.id = b.machine_id \
+             WHERE m.owner_id = $1 AND b.delivered_at IS NULL",
+        )
+        .bind(user_id)
+        .fetch_one(self.pool())
+        .await?;
 
         Ok(row.0)
     }
 
+    /// Buffer a message unless its machine or the machine's owner is at a cap.
+    ///
+    /// The counts and the insert run in one transaction that first locks the
+    /// owner's row, so concurrent requests for the same owner are checked one
+    /// after another and cannot overshoot the caps together.
+    pub async fn buffer_message_within_caps(
+        &self,
+        params: &BufferMessageParams<'_>,
+        caps: BufferCaps,
+    ) -> Result<CappedBuffer, DatabaseError> {
+        let mut tx = self.pool().begin().await?;
+        // A no-op write: row lock on PostgreSQL, write lock on SQLite
+        let locked = sqlx::query(
+            "UPDATE users SET updated_at = updated_at \
+             WHERE id = (SELECT owner_id FROM machines WHERE id = $1)",
+        )
+        .bind(params.machine_id)
+        .execute(&mut *tx)
+        .await?;
+        if locked.rows_affected() == 0 {
+            return Err(DatabaseError::NotFound(format!(
+                "Machine {}",
+                params.machine_id
+            )));
+        }
+
+        let (current,): (i64,) = sqlx::query_as(
+            "SELECT COUNT(*) FROM message_buffer WHERE machine_id = $1 AND delivered_at IS NULL",
+        )
+        .bind(params.machine_id)
+        .fetch_one(&mut *tx)
+        .await?;
+        if current >= caps.per_machine {
+            return Ok(CappedBuffer::MachineFull { current });
+        }
+
+        if let Some(cap) = caps.per_user {
+            let owner_id: String =
+                sqlx::query_scalar("SELECT owner_id FROM machines WHERE id = $1")
+                    .bind(params.machine_id)
+                    .fetch_one(&mut *tx)
+                    .await?;
+            let (current,): (i64,) = sqlx::query_as(
+                "SELECT COUNT(*) FROM message_buffer b JOIN machines m ON m.id = b.machine_id \
+                 WHERE m.owner_id = $1 AND b.delivered_at IS NULL",
+            )
+            .bind(&owner_id)
+            .fetch_one(&mut *tx)
+            .await?;
+            if current >= cap {
+                return Ok(CappedBuffer::UserFull { owner_id, current });
+            }
+        }
+
+        let now = unix_timestamp();
+        let id: i64 = sqlx::query_scalar(
+            "INSERT INTO message_buffer (machine_id, request_id, method, payload, metadata, priority, expires_at, created_at) VALUES ($1, $2, $3, $4, $5, $6, $7, $8) RETURNING id",
+        )
+        .bind(params.machine_id)
+        .bind(params.request_id)
+        .bind(params.method)
+        .bind(params.payload)
+        .bind(params.metadata)
+        .bind(params.priority)
+        .bind(now + params.ttl_secs)
+        .bind(now)
+        .fetch_one(&mut *tx)
+        .await?;
+        tx.commit().await?;
+
+        Ok(CappedBuffer::Buffered(id))
+    }
+
     // =========================================================================
     // Certificate queries
     // =========================================================================
@@ -120,7 +289,7 @@ impl RelayDatabase {
         let now = unix_timestamp();
 
         sqlx::query(
-            "INSERT INTO certificates (id, machine_id, subject_cn, serial_number, not_before, not_after, pem_cert, created_at) VALUES (?, ?, ?, ?, ?, ?, ?, ?)",
+            "INSERT INTO certificates (id, machine_id, subject_cn, serial_number, not_before, not_after, pem_cert, created_at) VALUES ($1, $2, $3, $4, $5, $6, $7, $8)",
         )
         .bind(params.id)
         .bind(params.machine_id)
@@ -138,7 +307,7 @@ impl RelayDatabase {
 
     /// Get a certificate by ID.
     pub async fn get_certificate(&self, id: &str) -> Result<Certificate, DatabaseError> {
-        sqlx::query_as::<_, Certificate>("SELECT * FROM certificates WHERE id = ?")
+        sqlx::query_as::<_, Certificate>("SELECT * FROM certificates WHERE id = $1")
             .bind(id)
             .fetch_optional(self.pool())
             .await?
@@ -151,7 +320,7 @@ impl RelayDatabase {
         machine_id: &str,
     ) -> Result<Vec<Certificate>, DatabaseError> {
         let certs = sqlx::query_as::<_, Certificate>(
-            "SELECT * FROM certificates WHERE machine_id = ? AND revoked = 0 ORDER BY created_at DESC",
+            "SELECT * FROM certificates WHERE machine_id = $1 AND revoked = 0 ORDER BY created_at DESC",
         )
         .bind(machine_id)
         .fetch_all(self.pool())
@@ -162,7 +331,7 @@ impl RelayDatabase {
 
     /// Revoke a certificate.
     pub async fn revoke_certificate(&self, id: &str) -> Result<bool, DatabaseError> {
-        let result = sqlx::query("UPDATE certificates SET revoked = 1 WHERE id = ?")
+        let result = sqlx::query("UPDATE certificates SET revoked = 1 WHERE id = $1")
             .bind(id)
             .execute(self.pool())
             .await?;
@@ -13,7 +13,7 @@ impl RelayDatabase {
         serial_number: &str,
     ) -> Result<Option<Certificate>, DatabaseError> {
         let cert =
-            sqlx::query_as::<_, Certificate>("SELECT * FROM certificates WHERE serial_number = ?")
+            sqlx::query_as::<_, Certificate>("SELECT * FROM certificates WHERE serial_number = $1")
                 .bind(serial_number)
                 .fetch_optional(self.pool())
                 .await?;
@@ -27,7 +27,7 @@ impl RelayDatabase {
     /// considered revoked -- chain validation is handled by the TLS layer).
     pub async fn is_certificate_revoked(&self, serial_number: &str) -> Result<bool, DatabaseError> {
         let row: Option<(i64,)> =
-            sqlx::query_as("SELECT revoked FROM certificates WHERE serial_number = ?")
+            sqlx::query_as("SELECT revoked FROM certificates WHERE serial_number = $1")
                 .bind(serial_number)
                 .fetch_optional(self.pool())
                 .await?;
@@ -40,7 +40,7 @@ impl RelayDatabase {
         &self,
         serial_number: &str,
     ) -> Result<bool, DatabaseError> {
-        let result = sqlx::query("UPDATE certificates SET revoked = 1 WHERE serial_number = ?")
+        let result = sqlx::query("UPDATE certificates SET revoked = 1 WHERE serial_number = $1")
             .bind(serial_number)
             .execute(self.pool())
             .await?;
@@ -57,10 +57,10 @@ impl RelayDatabase {
     clippy::needless_pass_by_value
 )]
 mod tests {
+    use crate::storage::test_backends::backend_tests;
     use crate::storage::{CertificateParams, RelayDatabase};
 
-    async fn test_db() -> RelayDatabase {
-        let db = RelayDatabase::open_in_memory().await.unwrap();
+    async fn setup_machines(db: &RelayDatabase) {
         // Set up user + machine so FK constraints pass
         db.create_user("u1", "alice", "alice@test.com", "hash")
             .await
@@ -74,7 +74,6 @@ mod tests {
         db.create_machine("machine-3", "server", "u1", "{}")
             .await
             .unwrap();
-        db
     }
 
     fn sample_cert_params<'a>(
@@ -93,9 +92,8 @@ mod tests {
         }
     }
 
-    #[tokio::test]
-    async fn get_certificate_by_serial_found() {
-        let db = test_db().await;
+    async fn get_certificate_by_serial_found(db: RelayDatabase) {
+        setup_machines(&db).await;
         let params = sample_cert_params("cert-1", "AABB01", "machine-1");
         db.create_certificate(&params).await.unwrap();
 
@@ -104,25 +102,22 @@ mod tests {
         assert_eq!(found.unwrap().id, "cert-1");
     }
 
-    #[tokio::test]
-    async fn get_certificate_by_serial_not_found() {
-        let db = test_db().await;
+    async fn get_certificate_by_serial_not_found(db: RelayDatabase) {
+        setup_machines(&db).await;
         let missing = db.get_certificate_by_serial("NONEXISTENT").await.unwrap();
         assert!(missing.is_none());
     }
 
-    #[tokio::test]
-    async fn is_certificate_revoked_false_for_active() {
-        let db = test_db().await;
+    async fn is_certificate_revoked_false_for_active(db: RelayDatabase) {
+        setup_machines(&db).await;
         let params = sample_cert_params("cert-2", "CC0022", "machine-2");
         db.create_certificate(&params).await.unwrap();
 
         assert!(!db.is_certificate_revoked("CC0022").await.unwrap());
     }
 
-    #[tokio::test]
-    async fn is_certificate_revoked_true_after_revocation() {
-        let db = test_db().await;
+    async fn is_certificate_revoked_true_after_revocation(db: RelayDatabase) {
+        setup_machines(&db).await;
         let params = sample_cert_params("cert-3", "DD0033", "machine-3");
         db.create_certificate(&params).await.unwrap();
 
@@ -131,15 +126,22 @@ mod tests {
         assert!(db.is_certificate_revoked("DD0033").await.unwrap());
     }
 
-    #[tokio::test]
-    async fn is_certificate_revoked_false_for_unknown_serial() {
-        let db = test_db().await;
+    async fn is_certificate_revoked_false_for_unknown_serial(db: RelayDatabase) {
+        setup_machines(&db).await;
         assert!(!db.is_certificate_revoked("UNKNOWN").await.unwrap());
     }
 
-    #[tokio::test]
-    async fn revoke_by_serial_returns_false_for_unknown() {
-        let db = test_db().await;
+    async fn revoke_by_serial_returns_false_for_unknown(db: RelayDatabase) {
+        setup_machines(&db).await;
         assert!(!db.revoke_certificate_by_serial("UNKNOWN").await.unwrap());
     }
+
+    backend_tests!(
+        get_certificate_by_serial_found,
+        get_certificate_by_serial_not_found,
+        is_certificate_revoked_false_for_active,
+        is_certificate_revoked_true_after_revocation,
+        is_certificate_revoked_false_for_unknown_serial,
+        revoke_by_serial_returns_false_for_unknown,
+    );
 }
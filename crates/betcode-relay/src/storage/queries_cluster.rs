@@ -0,0 +1,99 @@
+//! Cluster directory queries: relay instances and which instance holds each
+//! machine's tunnel.
+
+use betcode_core::db::unix_timestamp;
+
+use super::db::{DatabaseError, RelayDatabase};
+use super::models::RelayInstance;
+
+impl RelayDatabase {
+    /// Register a relay instance or refresh its heartbeat and address.
+    pub async fn upsert_relay_instance(
+        &self,
+        id: &str,
+        address: &str,
+    ) -> Result<(), DatabaseError> {
+        sqlx::query(
+            "INSERT INTO relay_instances (id, address, heartbeat_at) VALUES ($1, $2, $3) \
+             ON CONFLICT(id) DO UPDATE SET address = excluded.address, heartbeat_at = excluded.heartbeat_at",
+        )
+        .bind(id)
+        .bind(address)
+        .bind(unix_timestamp())
+        .execute(self.pool())
+        .await?;
+        Ok(())
+    }
+
+    /// Remove a relay instance and the machine locations it held.
+    pub async fn remove_relay_instance(&self, id: &str) -> Result<bool, DatabaseError> {
+        let result = sqlx::query("DELETE FROM relay_instances WHERE id = $1")
+            .bind(id)
+            .execute(self.pool())
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Remove instances whose last heartbeat is older than `before`.
+    pub async fn purge_stale_relay_instances(&self, before: i64) -> Result<u64, DatabaseError> {
+        let result = sqlx::query("DELETE FROM relay_instances WHERE heartbeat_at < $1")
+            .bind(before)
+            .execute(self.pool())
+            .await?;
+        Ok(result.rows_affected())
+    }
+
+    /// Record that `instance_id` now holds `machine_id`'s tunnel.
+    pub async fn claim_machine_location(
+        &self,
+        machine_id: &str,
+        instance_id: &str,
+    ) -> Result<(), DatabaseError> {
+        sqlx::query(
+            "INSERT INTO machine_locations (machine_id, instance_id, connected_at) VALUES ($1, $2, $3) \
+             ON CONFLICT(machine_id) DO UPDATE SET instance_id = excluded.instance_id, connected_at = excluded.connected_at",
+        )
+        .bind(machine_id)
+        .bind(instance_id)
+        .bind(unix_timestamp())
+        .execute(self.pool())
+        .await?;
+        Ok(())
+    }
+
+    /// Forget `machine_id`'s location if `instance_id` still holds it.
+    ///
+    /// A machine that reconnected to another instance keeps that claim.
+    pub async fn release_machine_location(
+        &self,
+        machine_id: &str,
+        instance_id: &str,
+    ) -> Result<bool, DatabaseError> {
+        let result =
+            sqlx::query("DELETE FROM machine_locations WHERE machine_id = $1 AND instance_id = $2")
+                .bind(machine_id)
+                .bind(instance_id)
+                .execute(self.pool())
+                .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// The instance holding `machine_id`'s tunnel, if it has heartbeated at
+    /// or after `live_since`.
+    pub async fn locate_machine(
+        &self,
+        machine_id: &str,
+        live_since: i64,
+    ) -> Result<Option<RelayInstance>, DatabaseError> {
+        let instance = sqlx::query_as::<_, RelayInstance>(
+            "SELECT i.id, i.address, i.heartbeat_at FROM machine_locations l \
+             JOIN relay_instances i ON i.id = l.instance_id \
+             WHERE l.machine_id = $1 AND i.heartbeat_at >= $2",
+        )
+        .bind(machine_id)
+        .bind(live_since)
+        .fetch_optional(self.pool())
+        .await?;
+        Ok(instance)
+    }
+}
@@ -1,4 +1,5 @@
-//! Device token queries for push notification support.
+//! Device token, notification preference and action token queries for push
+//! notification support.
 //!
 //! These queries are only used when the `push-notifications` feature is enabled,
 //! but the table is always created by the migration so the queries themselves
@@ -7,7 +8,7 @@
 use betcode_core::db::unix_timestamp;
 
 use super::db::{DatabaseError, RelayDatabase};
-use super::models::DeviceToken;
+use super::models::{DeviceToken, NotificationPreferencesRow};
 
 impl RelayDatabase {
     // =========================================================================
@@ -29,8 +30,8 @@ impl RelayDatabase {
 
         sqlx::query(
             "INSERT INTO device_tokens (id, user_id, device_token, platform, created_at) \
-             VALUES (?, ?, ?, ?, ?) \
-             ON CONFLICT(device_token) DO UPDATE SET user_id = ?, platform = ?, created_at = ?",
+             VALUES ($1, $2, $3, $4, $5) \
+             ON CONFLICT(device_token) DO UPDATE SET user_id = $6, platform = $7, created_at = $8",
         )
         .bind(id)
         .bind(user_id)
@@ -50,7 +51,7 @@ impl RelayDatabase {
     ///
     /// Returns `true` if a row was deleted, `false` if the token was not found.
     pub async fn delete_device_token(&self, device_token: &str) -> Result<bool, DatabaseError> {
-        let result = sqlx::query("DELETE FROM device_tokens WHERE device_token = ?")
+        let result = sqlx::query("DELETE FROM device_tokens WHERE device_token = $1")
             .bind(device_token)
             .execute(self.pool())
             .await?;
@@ -64,7 +65,7 @@ impl RelayDatabase {
         user_id: &str,
     ) -> Result<Vec<DeviceToken>, DatabaseError> {
         let tokens = sqlx::query_as::<_, DeviceToken>(
-            "SELECT * FROM device_tokens WHERE user_id = ? ORDER BY created_at DESC",
+            "SELECT * FROM device_tokens WHERE user_id = $1 ORDER BY created_at DESC",
         )
         .bind(user_id)
         .fetch_all(self.pool())
@@ -79,28 +80,113 @@ impl RelayDatabase {
         device_token: &str,
     ) -> Result<Option<DeviceToken>, DatabaseError> {
         let token =
-            sqlx::query_as::<_, DeviceToken>("SELECT * FROM device_tokens WHERE device_token = ?")
+            sqlx::query_as::<_, DeviceToken>("SELECT * FROM device_tokens WHERE device_token = $1")
                 .bind(device_token)
                 .fetch_optional(self.pool())
                 .await?;
 
         Ok(token)
     }
+
+    // =========================================================================
+    // Notification preference queries
+    // =========================================================================
+
+    /// Get a user's notification preferences, if they ever set any.
+    pub async fn get_notification_preferences(
+        &self,
+        user_id: &str,
+    ) -> Result<Option<NotificationPreferencesRow>, DatabaseError> {
+        let row = sqlx::query_as::<_, NotificationPreferencesRow>(
+            "SELECT * FROM notification_preferences WHERE user_id = $1",
+        )
+        .bind(user_id)
+        .fetch_optional(self.pool())
+        .await?;
+
+        Ok(row)
+    }
+
+    /// Create or replace a user's notification preferences.
+    pub async fn upsert_notification_preferences(
+        &self,
+        user_id: &str,
+        events: &str,
+        quiet_hours: Option<(i64, i64)>,
+        utc_offset_minutes: i64,
+    ) -> Result<(), DatabaseError> {
+        let (start, end) = quiet_hours.unzip();
+
+        sqlx::query(
+            "INSERT INTO notification_preferences \
+             (user_id, events, quiet_hours_start, quiet_hours_end, utc_offset_minutes, updated_at) \
+             VALUES ($1, $2, $3, $4, $5, $6) \
+             ON CONFLICT(user_id) DO UPDATE SET events = excluded.events, \
+             quiet_hours_start = excluded.quiet_hours_start, \
+             quiet_hours_end = excluded.quiet_hours_end, \
+             utc_offset_minutes = excluded.utc_offset_minutes, \
+             updated_at = excluded.updated_at",
+        )
+        .bind(user_id)
+        .bind(events)
+        .bind(start)
+        .bind(end)
+        .bind(utc_offset_minutes)
+        .bind(unix_timestamp())
+        .execute(self.pool())
+        .await?;
+
+        Ok(())
+    }
+
+    // =========================================================================
+    // Action token queries
+    // =========================================================================
+
+    /// Record that the action token `jti` has been used.
+    ///
+    /// Returns `false` if it was already used. Tokens past their expiry are
+    /// forgotten on the way, since they no longer validate anyway.
+    pub async fn consume_action_token(
+        &self,
+        jti: &str,
+        expires_at: i64,
+    ) -> Result<bool, DatabaseError> {
+        sqlx::query("DELETE FROM used_action_tokens WHERE expires_at < $1")
+            .bind(unix_timestamp())
+            .execute(self.pool())
+            .await?;
+
+        let result = sqlx::query(
+            "INSERT INTO used_action_tokens (jti, expires_at) VALUES ($1, $2) \
+             ON CONFLICT(jti) DO NOTHING",
+        )
+        .bind(jti)
+        .bind(expires_at)
+        .execute(self.pool())
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Make a consumed action token usable again, after the answer it
+    /// carried could not be delivered.
+    pub async fn release_action_token(&self, jti: &str) -> Result<(), DatabaseError> {
+        sqlx::query("DELETE FROM used_action_tokens WHERE jti = $1")
+            .bind(jti)
+            .execute(self.pool())
+            .await?;
+        Ok(())
+    }
 }
 
 #[cfg(test)]
 #[allow(clippy::panic, clippy::expect_used, clippy::unwrap_used)]
 mod tests {
     use super::*;
+    use crate::storage::test_backends::backend_tests;
 
-    async fn test_db() -> RelayDatabase {
-        RelayDatabase::open_in_memory().await.unwrap()
-    }
-
-    #[tokio::test]
-    async fn upsert_and_get_device_token() {
-        let db = test_db().await;
-
+    async fn upsert_and_get_device_token(db: RelayDatabase) {
         db.upsert_device_token("dt-1", "user-1", "token-abc", "android")
             .await
             .unwrap();
@@ -111,10 +197,7 @@ mod tests {
         assert_eq!(token.platform, "android");
     }
 
-    #[tokio::test]
-    async fn upsert_updates_existing_token() {
-        let db = test_db().await;
-
+    async fn upsert_updates_existing_token(db: RelayDatabase) {
         db.upsert_device_token("dt-1", "user-1", "token-abc", "android")
             .await
             .unwrap();
@@ -129,10 +212,7 @@ mod tests {
         assert_eq!(token.platform, "ios");
     }
 
-    #[tokio::test]
-    async fn delete_device_token_existing() {
-        let db = test_db().await;
-
+    async fn delete_device_token_existing(db: RelayDatabase) {
         db.upsert_device_token("dt-1", "user-1", "token-abc", "android")
             .await
             .unwrap();
@@ -144,18 +224,12 @@ mod tests {
         assert!(token.is_none());
     }
 
-    #[tokio::test]
-    async fn delete_device_token_nonexistent() {
-        let db = test_db().await;
-
+    async fn delete_device_token_nonexistent(db: RelayDatabase) {
         let removed = db.delete_device_token("nonexistent").await.unwrap();
         assert!(!removed);
     }
 
-    #[tokio::test]
-    async fn get_device_tokens_for_user() {
-        let db = test_db().await;
-
+    async fn get_device_tokens_for_user(db: RelayDatabase) {
         db.upsert_device_token("dt-1", "user-1", "token-a", "android")
             .await
             .unwrap();
@@ -174,19 +248,64 @@ mod tests {
         assert_eq!(tokens_u2.len(), 1);
     }
 
-    #[tokio::test]
-    async fn get_device_tokens_for_user_empty() {
-        let db = test_db().await;
-
+    async fn get_device_tokens_for_user_empty(db: RelayDatabase) {
         let tokens = db.get_device_tokens_for_user("nobody").await.unwrap();
         assert!(tokens.is_empty());
     }
 
-    #[tokio::test]
-    async fn get_device_token_not_found() {
-        let db = test_db().await;
-
+    async fn get_device_token_not_found(db: RelayDatabase) {
         let token = db.get_device_token("missing").await.unwrap();
         assert!(token.is_none());
     }
+
+    async fn notification_preferences_roundtrip(db: RelayDatabase) {
+        db.create_user("user-1", "alice", "a@t.com", "hash")
+            .await
+            .unwrap();
+        assert!(
+            db.get_notification_preferences("user-1")
+                .await
+                .unwrap()
+                .is_none()
+        );
+
+        db.upsert_notification_preferences("user-1", "error", Some((1320, 420)), 60)
+            .await
+            .unwrap();
+        db.upsert_notification_preferences("user-1", "permission_request,error", None, 120)
+            .await
+            .unwrap();
+
+        let prefs = db
+            .get_notification_preferences("user-1")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(prefs.events, "permission_request,error");
+        assert_eq!(prefs.quiet_hours_start, None);
+        assert_eq!(prefs.utc_offset_minutes, 120);
+    }
+
+    async fn action_token_can_be_consumed_once(db: RelayDatabase) {
+        let expires_at = unix_timestamp() + 600;
+
+        assert!(db.consume_action_token("jti-1", expires_at).await.unwrap());
+        assert!(!db.consume_action_token("jti-1", expires_at).await.unwrap());
+        assert!(db.consume_action_token("jti-2", expires_at).await.unwrap());
+
+        db.release_action_token("jti-1").await.unwrap();
+        assert!(db.consume_action_token("jti-1", expires_at).await.unwrap());
+    }
+
+    backend_tests!(
+        upsert_and_get_device_token,
+        upsert_updates_existing_token,
+        delete_device_token_existing,
+        delete_device_token_nonexistent,
+        get_device_tokens_for_user,
+        get_device_tokens_for_user_empty,
+        get_device_token_not_found,
+        notification_preferences_roundtrip,
+        action_token_can_be_consumed_once,
+    );
 }
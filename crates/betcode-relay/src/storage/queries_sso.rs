@@ -0,0 +1,62 @@
+//! SSO identity queries: identity provider accounts linked to relay users.
+
+use betcode_core::db::unix_timestamp;
+
+use super::db::{DatabaseError, RelayDatabase};
+use super::models::User;
+
+impl RelayDatabase {
+    /// The user an identity provider subject is linked to, if any.
+    pub async fn get_user_by_sso_identity(
+        &self,
+        issuer: &str,
+        subject: &str,
+    ) -> Result<Option<User>, DatabaseError> {
+        Ok(sqlx::query_as::<_, User>(
+            "SELECT u.* FROM users u JOIN sso_identities s ON s.user_id = u.id \
+             WHERE s.issuer = $1 AND s.subject = $2",
+        )
+        .bind(issuer)
+        .bind(subject)
+        .fetch_optional(self.pool())
+        .await?)
+    }
+
+    /// Create a user without a password, linked to an identity provider
+    /// subject. Fails without creating the user if the subject is already
+    /// linked.
+    pub async fn create_sso_user(
+        &self,
+        id: &str,
+        username: &str,
+        email: &str,
+        issuer: &str,
+        subject: &str,
+    ) -> Result<User, DatabaseError> {
+        let now = unix_timestamp();
+        let mut tx = self.pool().begin().await?;
+        sqlx::query(
+            "INSERT INTO users (id, username, email, password_hash, created_at, updated_at) VALUES ($1, $2, $3, '', $4, $5)",
+        )
+        .bind(id)
+        .bind(username)
+        .bind(email)
+        .bind(now)
+        .bind(now)
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query(
+            "INSERT INTO sso_identities (issuer, subject, user_id, email, created_at) VALUES ($1, $2, $3, $4, $5)",
+        )
+        .bind(issuer)
+        .bind(subject)
+        .bind(id)
+        .bind(email)
+        .bind(now)
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+
+        self.get_user(id).await
+    }
+}
@@ -0,0 +1,167 @@
+//! Usage rollup queries: hourly tunnel traffic per machine and method.
+
+use super::db::{DatabaseError, RelayDatabase};
+use super::models::UsageRow;
+
+/// How [`RelayDatabase::usage_report`] groups the hourly rows.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum UsageGroupBy {
+    /// One row per hour, machine and method.
+    #[default]
+    None,
+    Machine,
+    Owner,
+    Method,
+    Hour,
+}
+
+impl UsageGroupBy {
+    /// Select list and `GROUP BY` columns for this grouping. Columns outside
+    /// the grouping are selected as empty values.
+    const fn columns(self) -> (&'static str, &'static str) {
+        match self {
+            Self::None => (
+                "hour_start, machine_id, owner_id, method",
+                "hour_start, machine_id, owner_id, method",
+            ),
+            Self::Machine => (
+                "CAST(0 AS BIGINT) AS hour_start, machine_id, MAX(owner_id) AS owner_id, '' AS method",
+                "machine_id",
+            ),
+            Self::Owner => (
+                "CAST(0 AS BIGINT) AS hour_start, '' AS machine_id, owner_id, '' AS method",
+                "owner_id",
+            ),
+            Self::Method => (
+                "CAST(0 AS BIGINT) AS hour_start, '' AS machine_id, '' AS owner_id, method",
+                "method",
+            ),
+            Self::Hour => (
+                "hour_start, '' AS machine_id, '' AS owner_id, '' AS method",
+                "hour_start",
+            ),
+        }
+    }
+
+    /// Hourly rows read newest first; groups by total traffic.
+    const fn order(self) -> &'static str {
+        match self {
+            Self::None => "hour_start DESC, machine_id, method",
+            Self::Hour => "hour_start DESC",
+            Self::Machine | Self::Owner | Self::Method => {
+                "SUM(bytes_sent) + SUM(bytes_received) DESC"
+            }
+        }
+    }
+}
+
+/// Filters for a usage report. `since` and `until` bound `hour_start`
+/// (inclusive and exclusive); `None` leaves a filter open.
+#[derive(Debug, Clone, Default)]
+pub struct UsageQuery<'a> {
+    pub since: Option<i64>,
+    pub until: Option<i64>,
+    pub machine_id: Option<&'a str>,
+    pub owner_id: Option<&'a str>,
+    pub method: Option<&'a str>,
+    pub group_by: UsageGroupBy,
+    pub limit: i64,
+}
+
+impl RelayDatabase {
+    /// Add traffic to the hourly rollups, summing with what each hour,
+    /// machine and method already recorded.
+    pub async fn add_usage(&self, rows: &[UsageRow]) -> Result<(), DatabaseError> {
+        let mut tx = self.pool().begin().await?;
+        for row in rows {
+            sqlx::query(
+                "INSERT INTO usage_hourly (hour_start, machine_id, owner_id, method, frames_sent, bytes_sent, frames_received, bytes_received) \
+                 VALUES ($1, $2, $3, $4, $5, $6, $7, $8) \
+                 ON CONFLICT(hour_start, machine_id, method) DO UPDATE SET \
+                 owner_id = excluded.owner_id, \
+                 frames_sent = usage_hourly.frames_sent + excluded.frames_sent, \
+                 bytes_sent = usage_hourly.bytes_sent + excluded.bytes_sent, \
+                 frames_received = usage_hourly.frames_received + excluded.frames_received, \
+                 bytes_received = usage_hourly.bytes_received + excluded.bytes_received",
+            )
+            .bind(row.hour_start)
+            .bind(&row.machine_id)
+            .bind(&row.owner_id)
+            .bind(&row.method)
+            .bind(row.frames_sent)
+            .bind(row.bytes_sent)
+            .bind(row.frames_received)
+            .bind(row.bytes_received)
+            .execute(&mut *tx)
+            .await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Summed traffic matching `query`, grouped as it asks.
+    pub async fn usage_report(
+        &self,
+        query: &UsageQuery<'_>,
+    ) -> Result<Vec<UsageRow>, DatabaseError> {
+        let mut clauses = Vec::new();
+        let mut ints = Vec::new();
+        let mut texts = Vec::new();
+        for (clause, value) in [
+            ("hour_start >=", query.since),
+            ("hour_start <", query.until),
+        ] {
+            if let Some(value) = value {
+                clauses.push(clause);
+                ints.push(value);
+            }
+        }
+        for (clause, value) in [
+            ("machine_id =", query.machine_id),
+            ("owner_id =", query.owner_id),
+            ("method =", query.method),
+        ] {
+            if let Some(value) = value {
+                clauses.push(clause);
+                texts.push(value);
+            }
+        }
+        let filter = if clauses.is_empty() {
+            String::new()
+        } else {
+            let conditions: Vec<String> = clauses
+                .iter()
+                .enumerate()
+                .map(|(i, clause)| format!("{clause} ${}", i + 1))
+                .collect();
+            format!(" WHERE {}", conditions.join(" AND "))
+        };
+        let (select, group) = query.group_by.columns();
+        let sql = format!(
+            "SELECT {select}, \
+             CAST(SUM(frames_sent) AS BIGINT) AS frames_sent, CAST(SUM(bytes_sent) AS BIGINT) AS bytes_sent, \
+             CAST(SUM(frames_received) AS BIGINT) AS frames_received, CAST(SUM(bytes_received) AS BIGINT) AS bytes_received \
+             FROM usage_hourly{filter} GROUP BY {group} ORDER BY {} LIMIT ${}",
+            query.group_by.order(),
+            clauses.len() + 1,
+        );
+
+        let mut q = sqlx::query_as::<_, UsageRow>(&sql);
+        for value in ints {
+            q = q.bind(value);
+        }
+        for value in texts {
+            q = q.bind(value);
+        }
+        Ok(q.bind(query.limit).fetch_all(self.pool()).await?)
+    }
+
+    /// Delete rollups for hours starting before `before`.
+    pub async fn prune_usage(&self, before: i64) -> Result<u64, DatabaseError> {
+        let result = sqlx::query("DELETE FROM usage_hourly WHERE hour_start < $1")
+            .bind(before)
+            .execute(self.pool())
+            .await?;
+        Ok(result.rows_affected())
+    }
+}
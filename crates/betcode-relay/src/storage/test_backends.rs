@@ -0,0 +1,168 @@
+//! Run storage tests against every backend.
+//!
+//! `backend_tests!(a, b)` turns `async fn a(db: RelayDatabase)` test bodies
+//! into `sqlite::a` and `postgres::a` tests. The `PostgreSQL` tests are
+//! ignored unless `BETCODE_TEST_PG_URL` (e.g. `postgres://user:pw@host:5432`)
+//! was set at build time, and then fail if that server cannot be reached.
+//! Run with `--ignored` and no URL, they start an ephemeral local server with
+//! `initdb` and `postgres` from `PATH`, shared by the whole test binary, and
+//! fail if it does not start. Each test gets a fresh database.
+
+use std::net::{Ipv4Addr, TcpListener, TcpStream};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+use sqlx::AnyConnection;
+use sqlx::Connection;
+
+use super::db::RelayDatabase;
+
+/// How long to wait for the ephemeral server to accept connections.
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// Runs `postgres` in the background and stops it, removing its data, when
+/// the test binary exits and closes the script's stdin. Background jobs read
+/// `/dev/null` unless given another descriptor, hence fd 3.
+const SUPERVISOR_SCRIPT: &str = r#"
+exec 3<&0
+postgres -D "$1/data" -p "$2" -k "$1" -c listen_addresses=127.0.0.1 -c fsync=off >"$1/log" 2>&1 &
+pid=$!
+(cat <&3 >/dev/null; kill "$pid") &
+wait "$pid"
+rm -rf "$1"
+"#;
+
+/// An ephemeral server, kept alive for the life of the test binary.
+struct EphemeralServer {
+    url: String,
+    _supervisor: Child,
+    _stdin: ChildStdin,
+}
+
+enum Server {
+    External(String),
+    Ephemeral(Mutex<EphemeralServer>),
+}
+
+static SERVER: OnceLock<Result<Server, String>> = OnceLock::new();
+
+fn free_port() -> Option<u16> {
+    let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).ok()?;
+    Some(listener.local_addr().ok()?.port())
+}
+
+fn start_ephemeral() -> Result<EphemeralServer, String> {
+    let dir = std::env::temp_dir().join(format!("betcode-relay-pg-{}", uuid::Uuid::new_v4()));
+    let initdb = Command::new("initdb")
+        .arg("-D")
+        .arg(dir.join("data"))
+        .args(["-U", "postgres", "--auth=trust", "--no-sync", "-E", "UTF8"])
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .map_err(|e| format!("initdb not available: {e}"))?;
+    if !initdb.success() {
+        return Err(format!("initdb failed: {initdb}"));
+    }
+
+    let port = free_port().ok_or("no free port")?;
+    let mut supervisor = Command::new("sh")
+        .args(["-c", SUPERVISOR_SCRIPT, "sh"])
+        .arg(&dir)
+        .arg(port.to_string())
+        .stdin(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("failed to start postgres: {e}"))?;
+    let stdin = supervisor.stdin.take().ok_or("no supervisor stdin")?;
+
+    let started = Instant::now();
+    while TcpStream::connect((Ipv4Addr::LOCALHOST, port)).is_err() {
+        if started.elapsed() > STARTUP_TIMEOUT || supervisor.try_wait().ok().flatten().is_some() {
+            return Err(format!(
+                "postgres did not start (log: {})",
+                dir.join("log").display()
+            ));
+        }
+        std::thread::sleep(Duration::from_millis(100));
+    }
+    Ok(EphemeralServer {
+        url: format!("postgres://postgres@127.0.0.1:{port}"),
+        _supervisor: supervisor,
+        _stdin: stdin,
+    })
+}
+
+fn server() -> &'static Server {
+    SERVER
+        .get_or_init(|| {
+            if let Ok(url) = std::env::var("BETCODE_TEST_PG_URL") {
+                return Ok(Server::External(url.trim_end_matches('/').to_string()));
+            }
+            start_ephemeral().map(|server| Server::Ephemeral(Mutex::new(server)))
+        })
+        .as_ref()
+        .unwrap_or_else(|e| panic!("no PostgreSQL server for the storage tests: {e}"))
+}
+
+/// A migrated, empty database on the test `PostgreSQL` server.
+///
+/// # Panics
+///
+/// If there is no server or it cannot be reached.
+pub async fn postgres_db() -> RelayDatabase {
+    let base = match server() {
+        Server::External(url) => url.clone(),
+        Server::Ephemeral(server) => server.lock().unwrap().url.clone(),
+    };
+    sqlx::any::install_default_drivers();
+
+    // The server may still be finishing startup after its port opened
+    let started = Instant::now();
+    let mut admin = loop {
+        match AnyConnection::connect(&format!("{base}/postgres")).await {
+            Ok(conn) => break conn,
+            Err(e) if started.elapsed() > STARTUP_TIMEOUT => {
+                panic!("cannot connect to the test PostgreSQL server at {base}: {e}")
+            }
+            Err(_) => tokio::time::sleep(Duration::from_millis(100)).await,
+        }
+    };
+    let name = format!("relay_test_{}", uuid::Uuid::new_v4().simple());
+    sqlx::query(&format!("CREATE DATABASE {name}"))
+        .execute(&mut admin)
+        .await
+        .unwrap();
+    RelayDatabase::connect(&format!("{base}/{name}"))
+        .await
+        .unwrap()
+}
+
+/// Generate a `sqlite` and a `postgres` test for each listed test body.
+macro_rules! backend_tests {
+    ($($name:ident),* $(,)?) => {
+        mod sqlite {
+            $(
+                #[tokio::test]
+                async fn $name() {
+                    super::$name(super::RelayDatabase::open_in_memory().await.unwrap()).await;
+                }
+            )*
+        }
+
+        mod postgres {
+            $(
+                #[tokio::test]
+                #[cfg_attr(
+                    not(betcode_test_pg),
+                    ignore = "set BETCODE_TEST_PG_URL to test against PostgreSQL"
+                )]
+                async fn $name() {
+                    super::$name($crate::storage::test_backends::postgres_db().await).await;
+                }
+            )*
+        }
+    };
+}
+
+pub(crate) use backend_tests;
@@ -1,13 +1,12 @@
 //! Storage layer tests for `BetCode` relay.
 
 use super::db::RelayDatabase;
-use super::queries_buffer::{BufferMessageParams, CertificateParams};
+use super::models::UsageRow;
+use super::queries_buffer::{BufferCaps, BufferMessageParams, CappedBuffer, CertificateParams};
+use super::queries_usage::{UsageGroupBy, UsageQuery};
+use super::test_backends::backend_tests;
 use betcode_core::db::unix_timestamp;
 
-async fn test_db() -> RelayDatabase {
-    RelayDatabase::open_in_memory().await.unwrap()
-}
-
 /// Create the default test user ("u1", "alice") in the database.
 async fn create_test_user(db: &RelayDatabase) {
     db.create_user("u1", "alice", "alice@example.com", "hash123")
@@ -28,21 +27,19 @@ async fn setup_user_and_machine(db: &RelayDatabase) {
     create_test_machine(db, "m1", "laptop").await;
 }
 
-/// Create test DB with user "u1" and two tokens "t1", "t2" expiring in 1 hour.
-async fn setup_two_tokens() -> (RelayDatabase, i64) {
-    let db = test_db().await;
-    create_test_user(&db).await;
+/// Create user "u1" and two tokens "t1", "t2" expiring in 1 hour, returning
+/// their expiry.
+async fn setup_two_tokens(db: &RelayDatabase) -> i64 {
+    create_test_user(db).await;
     let future = unix_timestamp() + 3600;
     db.create_token("t1", "u1", "hash1", future).await.unwrap();
     db.create_token("t2", "u1", "hash2", future).await.unwrap();
-    (db, future)
+    future
 }
 
 // === User tests ===
 
-#[tokio::test]
-async fn create_and_get_user() {
-    let db = test_db().await;
+async fn create_and_get_user(db: RelayDatabase) {
     let user = db
         .create_user("u1", "alice", "alice@example.com", "hash123")
         .await
@@ -53,9 +50,7 @@ async fn create_and_get_user() {
     assert_eq!(user.email, "alice@example.com");
 }
 
-#[tokio::test]
-async fn get_user_by_username() {
-    let db = test_db().await;
+async fn get_user_by_username(db: RelayDatabase) {
     create_test_user(&db).await;
 
     let user = db.get_user_by_username("alice").await.unwrap();
@@ -64,11 +59,65 @@ async fn get_user_by_username() {
     assert!(db.get_user_by_username("bob").await.is_err());
 }
 
+// === SSO identity tests ===
+
+async fn sso_user_is_found_by_identity(db: RelayDatabase) {
+    let user = db
+        .create_sso_user(
+            "u1",
+            "alice",
+            "alice@corp.test",
+            "https://idp.test",
+            "sub-1",
+        )
+        .await
+        .unwrap();
+    assert_eq!(user.username, "alice");
+    assert!(user.password_hash.is_empty());
+
+    let found = db
+        .get_user_by_sso_identity("https://idp.test", "sub-1")
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(found.id, "u1");
+    assert!(
+        db.get_user_by_sso_identity("https://other.test", "sub-1")
+            .await
+            .unwrap()
+            .is_none()
+    );
+}
+
+async fn sso_identity_links_only_once(db: RelayDatabase) {
+    db.create_sso_user(
+        "u1",
+        "alice",
+        "alice@corp.test",
+        "https://idp.test",
+        "sub-1",
+    )
+    .await
+    .unwrap();
+
+    assert!(
+        db.create_sso_user(
+            "u2",
+            "alice2",
+            "alice@corp.test",
+            "https://idp.test",
+            "sub-1"
+        )
+        .await
+        .is_err()
+    );
+    // The second user was rolled back with its identity
+    assert!(db.get_user("u2").await.is_err());
+}
+
 // === Token tests ===
 
-#[tokio::test]
-async fn create_and_get_token() {
-    let db = test_db().await;
+async fn create_and_get_token(db: RelayDatabase) {
     create_test_user(&db).await;
 
     let future = unix_timestamp() + 3600;
@@ -82,9 +131,7 @@ async fn create_and_get_token() {
     assert_eq!(token.revoked, 0);
 }
 
-#[tokio::test]
-async fn find_token_by_hash() {
-    let db = test_db().await;
+async fn find_token_by_hash(db: RelayDatabase) {
     create_test_user(&db).await;
 
     let future = unix_timestamp() + 3600;
@@ -102,9 +149,7 @@ async fn find_token_by_hash() {
     assert!(not_found.is_none());
 }
 
-#[tokio::test]
-async fn revoke_token() {
-    let db = test_db().await;
+async fn revoke_token(db: RelayDatabase) {
     create_test_user(&db).await;
 
     let future = unix_timestamp() + 3600;
@@ -118,11 +163,18 @@ async fn revoke_token() {
     assert!(found.is_none());
 }
 
+async fn revoke_user_tokens_revokes_every_token(db: RelayDatabase) {
+    setup_two_tokens(&db).await;
+
+    assert_eq!(db.revoke_user_tokens("u1").await.unwrap(), 2);
+    assert!(db.get_token_by_hash("hash1", 0).await.unwrap().is_none());
+    assert!(db.get_token_by_hash("hash2", 0).await.unwrap().is_none());
+}
+
 // === Token rotation tests ===
 
-#[tokio::test]
-async fn rotate_token_sets_rotated_at_and_successor() {
-    let (db, _future) = setup_two_tokens().await;
+async fn rotate_token_sets_rotated_at_and_successor(db: RelayDatabase) {
+    let _future = setup_two_tokens(&db).await;
 
     let now = unix_timestamp();
     assert!(db.rotate_token("t1", "t2").await.unwrap());
@@ -135,9 +187,8 @@ async fn rotate_token_sets_rotated_at_and_successor() {
     assert_eq!(token.revoked, 0);
 }
 
-#[tokio::test]
-async fn rotate_token_preserves_original_rotated_at() {
-    let (db, future) = setup_two_tokens().await;
+async fn rotate_token_preserves_original_rotated_at(db: RelayDatabase) {
+    let future = setup_two_tokens(&db).await;
     db.create_token("t3", "u1", "hash3", future).await.unwrap();
 
     // First rotation
@@ -155,9 +206,8 @@ async fn rotate_token_preserves_original_rotated_at() {
     assert_eq!(token.successor_id.as_deref(), Some("t3"));
 }
 
-#[tokio::test]
-async fn rotate_token_noop_on_revoked_token() {
-    let (db, _future) = setup_two_tokens().await;
+async fn rotate_token_noop_on_revoked_token(db: RelayDatabase) {
+    let _future = setup_two_tokens(&db).await;
 
     // Hard-revoke first
     assert!(db.revoke_token("t1").await.unwrap());
@@ -171,9 +221,7 @@ async fn rotate_token_noop_on_revoked_token() {
     assert!(token.successor_id.is_none());
 }
 
-#[tokio::test]
-async fn get_token_by_hash_returns_active_token() {
-    let db = test_db().await;
+async fn get_token_by_hash_returns_active_token(db: RelayDatabase) {
     create_test_user(&db).await;
 
     let future = unix_timestamp() + 3600;
@@ -186,9 +234,8 @@ async fn get_token_by_hash_returns_active_token() {
     assert!(token.rotated_at.is_none());
 }
 
-#[tokio::test]
-async fn get_token_by_hash_returns_recently_rotated() {
-    let (db, _future) = setup_two_tokens().await;
+async fn get_token_by_hash_returns_recently_rotated(db: RelayDatabase) {
+    let _future = setup_two_tokens(&db).await;
 
     // Rotate t1 → t2 (rotated_at = now)
     db.rotate_token("t1", "t2").await.unwrap();
@@ -199,13 +246,12 @@ async fn get_token_by_hash_returns_recently_rotated() {
     assert_eq!(found.unwrap().id, "t1");
 }
 
-#[tokio::test]
-async fn get_token_by_hash_excludes_old_rotated() {
-    let (db, _future) = setup_two_tokens().await;
+async fn get_token_by_hash_excludes_old_rotated(db: RelayDatabase) {
+    let _future = setup_two_tokens(&db).await;
 
     // Rotate t1, then manually backdate rotated_at to simulate expired grace
     db.rotate_token("t1", "t2").await.unwrap();
-    sqlx::query("UPDATE tokens SET rotated_at = ? WHERE id = ?")
+    sqlx::query("UPDATE tokens SET rotated_at = $1 WHERE id = $2")
         .bind(unix_timestamp() - 60) // 60s ago — past any reasonable grace window
         .bind("t1")
         .execute(db.pool())
@@ -217,9 +263,7 @@ async fn get_token_by_hash_excludes_old_rotated() {
     assert!(found.is_none());
 }
 
-#[tokio::test]
-async fn get_token_by_hash_excludes_hard_revoked() {
-    let db = test_db().await;
+async fn get_token_by_hash_excludes_hard_revoked(db: RelayDatabase) {
     create_test_user(&db).await;
 
     let future = unix_timestamp() + 3600;
@@ -232,9 +276,7 @@ async fn get_token_by_hash_excludes_hard_revoked() {
     assert!(found.is_none());
 }
 
-#[tokio::test]
-async fn get_token_by_hash_excludes_expired() {
-    let db = test_db().await;
+async fn get_token_by_hash_excludes_expired(db: RelayDatabase) {
     create_test_user(&db).await;
 
     // Token expired 1 second ago
@@ -247,9 +289,7 @@ async fn get_token_by_hash_excludes_expired() {
 
 // === Machine tests ===
 
-#[tokio::test]
-async fn create_and_get_machine() {
-    let db = test_db().await;
+async fn create_and_get_machine(db: RelayDatabase) {
     create_test_user(&db).await;
 
     let machine = db
@@ -263,9 +303,7 @@ async fn create_and_get_machine() {
     assert_eq!(machine.status, "offline");
 }
 
-#[tokio::test]
-async fn list_machines_with_filter() {
-    let db = test_db().await;
+async fn list_machines_with_filter(db: RelayDatabase) {
     create_test_user(&db).await;
     create_test_machine(&db, "m1", "laptop").await;
     create_test_machine(&db, "m2", "desktop").await;
@@ -287,9 +325,7 @@ async fn list_machines_with_filter() {
     assert_eq!(offline.len(), 1);
 }
 
-#[tokio::test]
-async fn remove_machine() {
-    let db = test_db().await;
+async fn remove_machine(db: RelayDatabase) {
     setup_user_and_machine(&db).await;
 
     assert!(db.remove_machine("m1").await.unwrap());
@@ -297,11 +333,34 @@ async fn remove_machine() {
     assert!(db.get_machine("m1").await.is_err());
 }
 
+async fn touch_and_update_machine_metadata(db: RelayDatabase) {
+    setup_user_and_machine(&db).await;
+    create_test_machine(&db, "m2", "desktop").await;
+    db.update_machine_status("m2", "online").await.unwrap();
+
+    let before = unix_timestamp();
+    db.touch_machine("m1").await.unwrap();
+    assert!(db.get_machine("m1").await.unwrap().last_seen >= before);
+
+    assert!(
+        db.update_machine_metadata("m1", r#"{"os":"linux"}"#)
+            .await
+            .unwrap()
+    );
+    assert!(!db.update_machine_metadata("missing", "{}").await.unwrap());
+    assert_eq!(
+        db.get_machine("m1").await.unwrap().metadata,
+        r#"{"os":"linux"}"#
+    );
+
+    assert_eq!(db.count_machines("u1", None).await.unwrap(), 2);
+    assert_eq!(db.count_machines("u1", Some("online")).await.unwrap(), 1);
+    assert_eq!(db.count_machines("nobody", None).await.unwrap(), 0);
+}
+
 // === Machine identity pubkey tests ===
 
-#[tokio::test]
-async fn update_and_get_machine_identity_pubkey() {
-    let db = test_db().await;
+async fn update_and_get_machine_identity_pubkey(db: RelayDatabase) {
     setup_user_and_machine(&db).await;
 
     // Initially no pubkey
@@ -329,9 +388,7 @@ async fn update_and_get_machine_identity_pubkey() {
 
 // === Buffer tests ===
 
-#[tokio::test]
-async fn buffer_and_drain_messages() {
-    let db = test_db().await;
+async fn buffer_and_drain_messages(db: RelayDatabase) {
     setup_user_and_machine(&db).await;
 
     db.buffer_message(&BufferMessageParams {
@@ -372,9 +429,7 @@ async fn buffer_and_drain_messages() {
     assert_eq!(db.count_buffered_messages("m1").await.unwrap(), 0);
 }
 
-#[tokio::test]
-async fn cleanup_expired_buffer() {
-    let db = test_db().await;
+async fn cleanup_expired_buffer(db: RelayDatabase) {
     setup_user_and_machine(&db).await;
 
     db.buffer_message(&BufferMessageParams {
@@ -405,11 +460,161 @@ async fn cleanup_expired_buffer() {
     assert_eq!(db.count_buffered_messages("m1").await.unwrap(), 1);
 }
 
+async fn delivered_messages_leave_receipts(db: RelayDatabase) {
+    setup_user_and_machine(&db).await;
+
+    let id = db
+        .buffer_message(&BufferMessageParams {
+            machine_id: "m1",
+            request_id: "r1",
+            method: "AgentService/CancelTurn",
+            payload: b"data",
+            metadata: "{}",
+            priority: 0,
+            ttl_secs: 3600,
+        })
+        .await
+        .unwrap();
+
+    assert!(db.mark_buffered_message_delivered(id).await.unwrap());
+    assert!(!db.mark_buffered_message_delivered(id).await.unwrap());
+
+    // Delivered messages are no longer queued...
+    assert!(db.drain_buffer("m1").await.unwrap().is_empty());
+    assert_eq!(db.count_buffered_messages("m1").await.unwrap(), 0);
+    assert!(
+        db.list_buffered_messages("m1", false)
+            .await
+            .unwrap()
+            .is_empty()
+    );
+    // ...but remain visible, without their payload
+    let receipts = db.list_buffered_messages("m1", true).await.unwrap();
+    assert_eq!(receipts.len(), 1);
+    assert!(receipts[0].delivered_at.is_some());
+    assert!(receipts[0].payload.is_empty());
+    // ...and can no longer be cancelled
+    assert!(!db.cancel_buffered_message("m1", "r1").await.unwrap());
+}
+
+async fn cancel_buffered_message_and_count_per_user(db: RelayDatabase) {
+    setup_user_and_machine(&db).await;
+    create_test_machine(&db, "m2", "desktop").await;
+
+    for (machine_id, request_id) in [("m1", "r1"), ("m1", "r2"), ("m2", "r3")] {
+        db.buffer_message(&BufferMessageParams {
+            machine_id,
+            request_id,
+            method: "AgentService/CancelTurn",
+            payload: b"data",
+            metadata: "{}",
+            priority: 0,
+            ttl_secs: 3600,
+        })
+        .await
+        .unwrap();
+    }
+    assert_eq!(db.count_buffered_messages_for_user("u1").await.unwrap(), 3);
+
+    // Cancelling is scoped to the machine
+    assert!(!db.cancel_buffered_message("m2", "r1").await.unwrap());
+    assert!(db.cancel_buffered_message("m1", "r1").await.unwrap());
+    assert!(!db.cancel_buffered_message("m1", "r1").await.unwrap());
+
+    assert_eq!(db.count_buffered_messages("m1").await.unwrap(), 1);
+    assert_eq!(db.count_buffered_messages_for_user("u1").await.unwrap(), 2);
+}
+
+fn capped_params<'a>(machine_id: &'a str, request_id: &'a str) -> BufferMessageParams<'a> {
+    BufferMessageParams {
+        machine_id,
+        request_id,
+        method: "AgentService/Converse",
+        payload: b"data",
+        metadata: "{}",
+        priority: 0,
+        ttl_secs: 3600,
+    }
+}
+
+async fn buffer_within_caps_rejects_at_either_cap(db: RelayDatabase) {
+    setup_user_and_machine(&db).await;
+    create_test_machine(&db, "m2", "desktop").await;
+    let caps = BufferCaps {
+        per_machine: 2,
+        per_user: Some(3),
+    };
+
+    for request_id in ["r1", "r2"] {
+        let outcome = db
+            .buffer_message_within_caps(&capped_params("m1", request_id), caps)
+            .await
+            .unwrap();
+        assert!(matches!(outcome, CappedBuffer::Buffered(_)));
+    }
+    assert_eq!(
+        db.buffer_message_within_caps(&capped_params("m1", "r3"), caps)
+            .await
+            .unwrap(),
+        CappedBuffer::MachineFull { current: 2 }
+    );
+
+    let outcome = db
+        .buffer_message_within_caps(&capped_params("m2", "r4"), caps)
+        .await
+        .unwrap();
+    assert!(matches!(outcome, CappedBuffer::Buffered(_)));
+    assert_eq!(
+        db.buffer_message_within_caps(&capped_params("m2", "r5"), caps)
+            .await
+            .unwrap(),
+        CappedBuffer::UserFull {
+            owner_id: "u1".to_string(),
+            current: 3
+        }
+    );
+
+    assert!(
+        db.buffer_message_within_caps(&capped_params("missing", "r6"), caps)
+            .await
+            .is_err()
+    );
+    assert_eq!(db.count_buffered_messages_for_user("u1").await.unwrap(), 3);
+}
+
+async fn buffer_caps_hold_under_concurrent_requests(db: RelayDatabase) {
+    setup_user_and_machine(&db).await;
+    create_test_machine(&db, "m2", "desktop").await;
+    let caps = BufferCaps {
+        per_machine: 100,
+        per_user: Some(5),
+    };
+
+    let mut attempts = tokio::task::JoinSet::new();
+    for i in 0..20 {
+        let db = db.clone();
+        attempts.spawn(async move {
+            let machine_id = if i % 2 == 0 { "m1" } else { "m2" };
+            let request_id = format!("r{i}");
+            db.buffer_message_within_caps(&capped_params(machine_id, &request_id), caps)
+                .await
+                .unwrap()
+        });
+    }
+    let mut buffered = 0;
+    while let Some(outcome) = attempts.join_next().await {
+        if matches!(outcome.unwrap(), CappedBuffer::Buffered(_)) {
+            buffered += 1;
+        }
+    }
+
+    assert_eq!(buffered, 5);
+    assert_eq!(db.count_buffered_messages_for_user("u1").await.unwrap(), 5);
+}
+
 // === Certificate tests ===
 
-#[tokio::test]
-async fn create_and_get_certificate() {
-    let db = test_db().await;
+async fn create_and_get_certificate(db: RelayDatabase) {
     setup_user_and_machine(&db).await;
 
     let now = unix_timestamp();
@@ -429,11 +634,13 @@ async fn create_and_get_certificate() {
     assert_eq!(cert.id, "c1");
     assert_eq!(cert.subject_cn, "m1.betcode.dev");
     assert_eq!(cert.revoked, 0);
+
+    let fetched = db.get_certificate("c1").await.unwrap();
+    assert_eq!(fetched.serial_number, "SN001");
+    assert!(db.get_certificate("missing").await.is_err());
 }
 
-#[tokio::test]
-async fn revoke_certificate_hides_from_machine_certs() {
-    let db = test_db().await;
+async fn revoke_certificate_hides_from_machine_certs(db: RelayDatabase) {
     setup_user_and_machine(&db).await;
 
     let now = unix_timestamp();
@@ -454,3 +661,194 @@ async fn revoke_certificate_hides_from_machine_certs() {
     let certs = db.get_machine_certificates("m1").await.unwrap();
     assert!(certs.is_empty());
 }
+
+// =========================================================================
+// Cluster directory
+// =========================================================================
+
+async fn machine_location_follows_latest_claim(db: RelayDatabase) {
+    setup_user_and_machine(&db).await;
+    db.upsert_relay_instance("relay-a", "http://a:443")
+        .await
+        .unwrap();
+    db.upsert_relay_instance("relay-b", "http://b:443")
+        .await
+        .unwrap();
+
+    db.claim_machine_location("m1", "relay-a").await.unwrap();
+    db.claim_machine_location("m1", "relay-b").await.unwrap();
+    let instance = db.locate_machine("m1", 0).await.unwrap().unwrap();
+    assert_eq!(instance.id, "relay-b");
+    assert_eq!(instance.address, "http://b:443");
+
+    // A stale release from the old instance keeps the new claim
+    assert!(!db.release_machine_location("m1", "relay-a").await.unwrap());
+    assert!(db.release_machine_location("m1", "relay-b").await.unwrap());
+    assert!(db.locate_machine("m1", 0).await.unwrap().is_none());
+}
+
+async fn stale_instances_are_not_located(db: RelayDatabase) {
+    setup_user_and_machine(&db).await;
+    db.upsert_relay_instance("relay-a", "http://a:443")
+        .await
+        .unwrap();
+    db.claim_machine_location("m1", "relay-a").await.unwrap();
+
+    let future = unix_timestamp() + 60;
+    assert!(db.locate_machine("m1", future).await.unwrap().is_none());
+
+    // Purging the instance drops its claims
+    assert_eq!(db.purge_stale_relay_instances(future).await.unwrap(), 1);
+    assert!(db.locate_machine("m1", 0).await.unwrap().is_none());
+}
+
+async fn removed_instances_drop_their_claims(db: RelayDatabase) {
+    setup_user_and_machine(&db).await;
+    db.upsert_relay_instance("relay-a", "http://a:443")
+        .await
+        .unwrap();
+    db.claim_machine_location("m1", "relay-a").await.unwrap();
+
+    assert!(db.remove_relay_instance("relay-a").await.unwrap());
+    assert!(!db.remove_relay_instance("relay-a").await.unwrap());
+    assert!(db.locate_machine("m1", 0).await.unwrap().is_none());
+}
+
+// =========================================================================
+// Usage rollups
+// =========================================================================
+
+fn usage(hour_start: i64, machine_id: &str, owner_id: &str, method: &str, bytes: i64) -> UsageRow {
+    UsageRow {
+        hour_start,
+        machine_id: machine_id.into(),
+        owner_id: owner_id.into(),
+        method: method.into(),
+        frames_sent: 1,
+        bytes_sent: bytes,
+        frames_received: 1,
+        bytes_received: bytes,
+    }
+}
+
+async fn usage_rollups_sum_and_group(db: RelayDatabase) {
+    db.add_usage(&[
+        usage(3600, "m1", "u1", "Converse", 100),
+        usage(7200, "m1", "u1", "Converse", 10),
+        usage(7200, "m2", "u2", "Converse", 500),
+        usage(7200, "m2", "u2", "ListCommands", 5),
+    ])
+    .await
+    .unwrap();
+    // Flushing the same hour again adds to it
+    db.add_usage(&[usage(3600, "m1", "u1", "Converse", 100)])
+        .await
+        .unwrap();
+
+    let all = UsageQuery {
+        limit: 100,
+        ..UsageQuery::default()
+    };
+    let rows = db.usage_report(&all).await.unwrap();
+    assert_eq!(rows.len(), 4);
+    // Newest hour first
+    assert_eq!(rows[0].hour_start, 7200);
+    let first_hour = rows.iter().find(|r| r.hour_start == 3600).unwrap();
+    assert_eq!(first_hour.frames_sent, 2);
+    assert_eq!(first_hour.bytes_sent, 200);
+
+    let by_machine = db
+        .usage_report(&UsageQuery {
+            group_by: UsageGroupBy::Machine,
+            ..all.clone()
+        })
+        .await
+        .unwrap();
+    assert_eq!(by_machine.len(), 2);
+    assert_eq!(by_machine[0].machine_id, "m2");
+    assert_eq!(by_machine[0].owner_id, "u2");
+    assert_eq!(by_machine[0].bytes_sent, 505);
+    assert_eq!(by_machine[0].method, "");
+    assert_eq!(by_machine[1].bytes_received, 210);
+
+    let by_method = db
+        .usage_report(&UsageQuery {
+            group_by: UsageGroupBy::Method,
+            owner_id: Some("u2"),
+            ..all.clone()
+        })
+        .await
+        .unwrap();
+    let methods: Vec<_> = by_method.iter().map(|r| r.method.as_str()).collect();
+    assert_eq!(methods, vec!["Converse", "ListCommands"]);
+
+    let by_hour = db
+        .usage_report(&UsageQuery {
+            group_by: UsageGroupBy::Hour,
+            since: Some(3600),
+            until: Some(7200),
+            ..all
+        })
+        .await
+        .unwrap();
+    assert_eq!(by_hour.len(), 1);
+    assert_eq!(by_hour[0].hour_start, 3600);
+    assert_eq!(by_hour[0].machine_id, "");
+}
+
+async fn prune_usage_drops_old_hours(db: RelayDatabase) {
+    db.add_usage(&[
+        usage(3600, "m1", "u1", "Converse", 1),
+        usage(7200, "m1", "u1", "Converse", 1),
+    ])
+    .await
+    .unwrap();
+
+    assert_eq!(db.prune_usage(7200).await.unwrap(), 1);
+    let rows = db
+        .usage_report(&UsageQuery {
+            limit: 1,
+            ..UsageQuery::default()
+        })
+        .await
+        .unwrap();
+    assert_eq!(rows.len(), 1);
+    assert_eq!(rows[0].hour_start, 7200);
+}
+
+backend_tests!(
+    create_and_get_user,
+    get_user_by_username,
+    sso_user_is_found_by_identity,
+    sso_identity_links_only_once,
+    create_and_get_token,
+    find_token_by_hash,
+    revoke_token,
+    revoke_user_tokens_revokes_every_token,
+    rotate_token_sets_rotated_at_and_successor,
+    rotate_token_preserves_original_rotated_at,
+    rotate_token_noop_on_revoked_token,
+    get_token_by_hash_returns_active_token,
+    get_token_by_hash_returns_recently_rotated,
+    get_token_by_hash_excludes_old_rotated,
+    get_token_by_hash_excludes_hard_revoked,
+    get_token_by_hash_excludes_expired,
+    create_and_get_machine,
+    list_machines_with_filter,
+    remove_machine,
+    touch_and_update_machine_metadata,
+    update_and_get_machine_identity_pubkey,
+    buffer_and_drain_messages,
+    cleanup_expired_buffer,
+    delivered_messages_leave_receipts,
+    cancel_buffered_message_and_count_per_user,
+    buffer_within_caps_rejects_at_either_cap,
+    buffer_caps_hold_under_concurrent_requests,
+    create_and_get_certificate,
+    revoke_certificate_hides_from_machine_certs,
+    machine_location_follows_latest_claim,
+    stale_instances_are_not_located,
+    removed_instances_drop_their_claims,
+    usage_rollups_sum_and_group,
+    prune_usage_drops_old_hours,
+);
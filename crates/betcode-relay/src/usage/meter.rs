@@ -0,0 +1,289 @@
+//! In-memory tunnel traffic counters, flushed to the hourly rollups.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::Duration;
+
+use tracing::{debug, warn};
+
+use betcode_core::db::unix_timestamp;
+
+use crate::storage::{DatabaseError, RelayDatabase, UsageRow};
+
+/// Seconds in one rollup bucket.
+const HOUR_SECS: i64 = 3600;
+
+/// Which way a frame crossed the tunnel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    /// Down the tunnel, from the relay to the machine.
+    Sent,
+    /// Up the tunnel, from the machine to the relay.
+    Received,
+}
+
+impl Direction {
+    /// Label used in metric attributes.
+    #[cfg(feature = "metrics")]
+    const fn as_str(self) -> &'static str {
+        match self {
+            Self::Sent => "sent",
+            Self::Received => "received",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct UsageKey {
+    hour_start: i64,
+    machine_id: String,
+    method: String,
+}
+
+#[derive(Debug, Clone, Default)]
+struct UsageCounts {
+    owner_id: String,
+    frames_sent: i64,
+    bytes_sent: i64,
+    frames_received: i64,
+    bytes_received: i64,
+}
+
+/// `OpenTelemetry` counters mirroring the recorded traffic.
+#[cfg(feature = "metrics")]
+struct Instruments {
+    frames: opentelemetry::metrics::Counter<u64>,
+    bytes: opentelemetry::metrics::Counter<u64>,
+}
+
+#[cfg(feature = "metrics")]
+impl Instruments {
+    /// Instruments from the global meter provider, so the OTLP pipeline must
+    /// be initialised first.
+    fn new() -> Self {
+        let meter = opentelemetry::global::meter("betcode-relay");
+        Self {
+            frames: meter
+                .u64_counter("betcode_relay_tunnel_frames_total")
+                .with_description("Frames carried through machine tunnels")
+                .build(),
+            bytes: meter
+                .u64_counter("betcode_relay_tunnel_bytes_total")
+                .with_description("Encoded frame bytes carried through machine tunnels")
+                .with_unit("By")
+                .build(),
+        }
+    }
+}
+
+/// Counts frames and bytes per machine, owner and method for each hour,
+/// until they are flushed to the database.
+pub struct UsageMeter {
+    pending: Mutex<HashMap<UsageKey, UsageCounts>>,
+    #[cfg(feature = "metrics")]
+    instruments: Instruments,
+}
+
+impl UsageMeter {
+    pub fn new() -> Self {
+        Self {
+            pending: Mutex::new(HashMap::new()),
+            #[cfg(feature = "metrics")]
+            instruments: Instruments::new(),
+        }
+    }
+
+    /// Account one frame of `bytes` encoded bytes.
+    pub fn record(
+        &self,
+        machine_id: &str,
+        owner_id: &str,
+        method: &str,
+        direction: Direction,
+        bytes: usize,
+    ) {
+        let bytes = i64::try_from(bytes).unwrap_or(i64::MAX);
+        let now = unix_timestamp();
+        let key = UsageKey {
+            hour_start: now - now.rem_euclid(HOUR_SECS),
+            machine_id: machine_id.to_string(),
+            method: method.to_string(),
+        };
+        let mut pending = self.pending.lock().unwrap_or_else(PoisonError::into_inner);
+        let counts = pending.entry(key).or_default();
+        owner_id.clone_into(&mut counts.owner_id);
+        match direction {
+            Direction::Sent => {
+                counts.frames_sent += 1;
+                counts.bytes_sent = counts.bytes_sent.saturating_add(bytes);
+            }
+            Direction::Received => {
+                counts.frames_received += 1;
+                counts.bytes_received = counts.bytes_received.saturating_add(bytes);
+            }
+        }
+        drop(pending);
+
+        #[cfg(feature = "metrics")]
+        {
+            use opentelemetry::KeyValue;
+            let attributes = [
+                KeyValue::new("machine_id", machine_id.to_string()),
+                KeyValue::new("owner_id", owner_id.to_string()),
+                KeyValue::new("method", method.to_string()),
+                KeyValue::new("direction", direction.as_str()),
+            ];
+            self.instruments.frames.add(1, &attributes);
+            self.instruments
+                .bytes
+                .add(u64::try_from(bytes).unwrap_or_default(), &attributes);
+        }
+    }
+
+    /// Remove and return everything recorded since the last take.
+    pub fn take(&self) -> Vec<UsageRow> {
+        let pending =
+            std::mem::take(&mut *self.pending.lock().unwrap_or_else(PoisonError::into_inner));
+        pending
+            .into_iter()
+            .map(|(key, counts)| UsageRow {
+                hour_start: key.hour_start,
+                machine_id: key.machine_id,
+                owner_id: counts.owner_id,
+                method: key.method,
+                frames_sent: counts.frames_sent,
+                bytes_sent: counts.bytes_sent,
+                frames_received: counts.frames_received,
+                bytes_received: counts.bytes_received,
+            })
+            .collect()
+    }
+
+    /// Put rows back after a failed flush, summing with anything recorded
+    /// since.
+    fn restore(&self, rows: Vec<UsageRow>) {
+        let mut pending = self.pending.lock().unwrap_or_else(PoisonError::into_inner);
+        for row in rows {
+            let key = UsageKey {
+                hour_start: row.hour_start,
+                machine_id: row.machine_id,
+                method: row.method,
+            };
+            let counts = pending.entry(key).or_default();
+            if counts.owner_id.is_empty() {
+                counts.owner_id = row.owner_id;
+            }
+            counts.frames_sent += row.frames_sent;
+            counts.bytes_sent = counts.bytes_sent.saturating_add(row.bytes_sent);
+            counts.frames_received += row.frames_received;
+            counts.bytes_received = counts.bytes_received.saturating_add(row.bytes_received);
+        }
+        drop(pending);
+    }
+
+    /// Add everything recorded so far to the database rollups. On failure
+    /// the traffic is kept for the next flush.
+    pub async fn flush(&self, db: &RelayDatabase) -> Result<usize, DatabaseError> {
+        let rows = self.take();
+        if rows.is_empty() {
+            return Ok(0);
+        }
+        match db.add_usage(&rows).await {
+            Ok(()) => Ok(rows.len()),
+            Err(e) => {
+                self.restore(rows);
+                Err(e)
+            }
+        }
+    }
+
+    /// Flush to `db` every `interval` in a background task.
+    pub fn spawn_flush(self: &Arc<Self>, db: RelayDatabase, interval: Duration) {
+        let meter = Arc::clone(self);
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            ticker.tick().await; // Skip first immediate tick
+            loop {
+                ticker.tick().await;
+                match meter.flush(&db).await {
+                    Ok(rows) if rows > 0 => debug!(rows, "Flushed tunnel usage"),
+                    Err(e) => warn!(error = %e, "Failed to flush tunnel usage"),
+                    _ => {}
+                }
+            }
+        });
+    }
+}
+
+impl Default for UsageMeter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::panic, clippy::expect_used, clippy::unwrap_used)]
+mod tests {
+    use super::*;
+    use crate::storage::UsageQuery;
+
+    #[test]
+    fn record_sums_per_machine_and_method() {
+        let meter = UsageMeter::new();
+        meter.record("m1", "u1", "Agent/Converse", Direction::Sent, 100);
+        meter.record("m1", "u1", "Agent/Converse", Direction::Sent, 50);
+        meter.record("m1", "u1", "Agent/Converse", Direction::Received, 10);
+        meter.record("m2", "u1", "Agent/Converse", Direction::Sent, 7);
+
+        let mut rows = meter.take();
+        rows.sort_by(|a, b| a.machine_id.cmp(&b.machine_id));
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0].machine_id, "m1");
+        assert_eq!(rows[0].owner_id, "u1");
+        assert_eq!(rows[0].frames_sent, 2);
+        assert_eq!(rows[0].bytes_sent, 150);
+        assert_eq!(rows[0].frames_received, 1);
+        assert_eq!(rows[0].bytes_received, 10);
+        assert_eq!(rows[0].hour_start % HOUR_SECS, 0);
+        assert_eq!(rows[1].bytes_sent, 7);
+
+        assert!(meter.take().is_empty());
+    }
+
+    #[tokio::test]
+    async fn flush_accumulates_in_rollups() {
+        let db = RelayDatabase::open_in_memory().await.unwrap();
+        let meter = UsageMeter::new();
+
+        meter.record("m1", "u1", "Agent/Converse", Direction::Sent, 100);
+        assert_eq!(meter.flush(&db).await.unwrap(), 1);
+        meter.record("m1", "u1", "Agent/Converse", Direction::Sent, 20);
+        assert_eq!(meter.flush(&db).await.unwrap(), 1);
+        assert_eq!(meter.flush(&db).await.unwrap(), 0);
+
+        let rows = db
+            .usage_report(&UsageQuery {
+                limit: 10,
+                ..UsageQuery::default()
+            })
+            .await
+            .unwrap();
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].frames_sent, 2);
+        assert_eq!(rows[0].bytes_sent, 120);
+    }
+
+    #[tokio::test]
+    async fn failed_flush_keeps_traffic() {
+        let db = RelayDatabase::open_in_memory().await.unwrap();
+        let meter = UsageMeter::new();
+        meter.record("m1", "u1", "Agent/Converse", Direction::Received, 5);
+
+        db.pool().close().await;
+        assert!(meter.flush(&db).await.is_err());
+
+        let rows = meter.take();
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].bytes_received, 5);
+    }
+}
@@ -0,0 +1,43 @@
+//! Tunnel bandwidth accounting.
+//!
+//! Every frame crossing a machine's tunnel on this instance is counted per
+//! machine, owner and method. The counts are flushed to hourly rollups in
+//! the database, exported as `OpenTelemetry` counters when the `metrics`
+//! feature is on, and reported to operators by `RelayAdminService`.
+
+pub mod meter;
+pub mod service;
+
+#[cfg(test)]
+#[allow(clippy::panic, clippy::expect_used, clippy::unwrap_used)]
+mod tests;
+
+pub use meter::{Direction, UsageMeter};
+pub use service::RelayAdminServiceImpl;
+
+/// Metadata key carrying the admin token on `RelayAdminService` requests.
+pub const ADMIN_TOKEN_HEADER: &str = "x-relay-admin-token";
+
+/// Minimum length of the admin token in bytes.
+pub const MIN_ADMIN_TOKEN_LEN: usize = 16;
+
+/// Method recorded for control frames, which belong to no request.
+pub const CONTROL_METHOD: &str = "(control)";
+
+/// Method recorded for frames of requests the relay has no method for.
+pub const UNKNOWN_METHOD: &str = "(unknown)";
+
+/// Error returned by [`validate_admin_token`].
+#[derive(Debug, thiserror::Error)]
+pub enum AdminTokenError {
+    #[error("Admin token must be at least {MIN_ADMIN_TOKEN_LEN} bytes, got {0}")]
+    TooShort(usize),
+}
+
+/// Reject admin tokens too short to resist guessing.
+pub const fn validate_admin_token(token: &str) -> Result<(), AdminTokenError> {
+    if token.len() < MIN_ADMIN_TOKEN_LEN {
+        return Err(AdminTokenError::TooShort(token.len()));
+    }
+    Ok(())
+}
@@ -0,0 +1,122 @@
+//! `RelayAdminService`: usage reports for relay operators.
+
+use std::sync::Arc;
+
+use tonic::{Request, Response, Status};
+use tracing::{instrument, warn};
+
+use betcode_proto::v1::relay_admin_service_server::RelayAdminService;
+use betcode_proto::v1::{GetUsageRequest, GetUsageResponse, UsageGrouping, UsageRecord};
+
+use super::{ADMIN_TOKEN_HEADER, UsageMeter};
+use crate::cluster::service::secret_digest;
+use crate::storage::{RelayDatabase, UsageGroupBy, UsageQuery, UsageRow};
+
+/// Records returned when the request sets no limit.
+const DEFAULT_LIMIT: i64 = 100;
+
+/// Most records one request may return.
+const MAX_LIMIT: i64 = 10_000;
+
+pub struct RelayAdminServiceImpl {
+    db: RelayDatabase,
+    meter: Arc<UsageMeter>,
+    token_digest: String,
+}
+
+impl RelayAdminServiceImpl {
+    pub fn new(db: RelayDatabase, meter: Arc<UsageMeter>, token: &str) -> Self {
+        Self {
+            db,
+            meter,
+            token_digest: secret_digest(token),
+        }
+    }
+
+    /// Reject requests that don't carry the admin token.
+    fn authenticate<T>(&self, request: &Request<T>) -> Result<(), Status> {
+        let provided = request
+            .metadata()
+            .get(ADMIN_TOKEN_HEADER)
+            .and_then(|v| v.to_str().ok())
+            .ok_or_else(|| Status::unauthenticated("Missing admin token"))?;
+        if betcode_crypto::constant_time_str_eq(&secret_digest(provided), &self.token_digest) {
+            Ok(())
+        } else {
+            Err(Status::unauthenticated("Invalid admin token"))
+        }
+    }
+}
+
+const fn group_by(grouping: UsageGrouping) -> UsageGroupBy {
+    match grouping {
+        UsageGrouping::Unspecified => UsageGroupBy::None,
+        UsageGrouping::Machine => UsageGroupBy::Machine,
+        UsageGrouping::Owner => UsageGroupBy::Owner,
+        UsageGrouping::Method => UsageGroupBy::Method,
+        UsageGrouping::Hour => UsageGroupBy::Hour,
+    }
+}
+
+/// An empty string filter matches everything.
+fn filter(value: &str) -> Option<&str> {
+    (!value.is_empty()).then_some(value)
+}
+
+fn to_record(row: UsageRow) -> UsageRecord {
+    let count = |n: i64| u64::try_from(n).unwrap_or_default();
+    UsageRecord {
+        hour_start: (row.hour_start > 0).then_some(prost_types::Timestamp {
+            seconds: row.hour_start,
+            nanos: 0,
+        }),
+        machine_id: row.machine_id,
+        owner_id: row.owner_id,
+        method: row.method,
+        frames_sent: count(row.frames_sent),
+        bytes_sent: count(row.bytes_sent),
+        frames_received: count(row.frames_received),
+        bytes_received: count(row.bytes_received),
+    }
+}
+
+#[tonic::async_trait]
+impl RelayAdminService for RelayAdminServiceImpl {
+    #[instrument(skip(self, request), fields(rpc = "GetUsage"))]
+    async fn get_usage(
+        &self,
+        request: Request<GetUsageRequest>,
+    ) -> Result<Response<GetUsageResponse>, Status> {
+        self.authenticate(&request)?;
+        let req = request.into_inner();
+        let grouping = UsageGrouping::try_from(req.group_by)
+            .map_err(|_| Status::invalid_argument("Unknown usage grouping"))?;
+
+        // Include this instance's traffic since the last periodic flush
+        if let Err(e) = self.meter.flush(&self.db).await {
+            warn!(error = %e, "Failed to flush tunnel usage before report");
+        }
+
+        let limit = match i64::from(req.limit) {
+            0 => DEFAULT_LIMIT,
+            n => n.min(MAX_LIMIT),
+        };
+        let query = UsageQuery {
+            since: req.since.map(|t| t.seconds),
+            until: req.until.map(|t| t.seconds),
+            machine_id: filter(&req.machine_id),
+            owner_id: filter(&req.owner_id),
+            method: filter(&req.method),
+            group_by: group_by(grouping),
+            limit,
+        };
+        let rows = self
+            .db
+            .usage_report(&query)
+            .await
+            .map_err(|e| Status::internal(format!("Failed to read usage: {e}")))?;
+        Ok(Response::new(GetUsageResponse {
+            records: rows.into_iter().map(to_record).collect(),
+        }))
+    }
+}
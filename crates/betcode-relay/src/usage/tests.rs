@@ -0,0 +1,120 @@
+//! `RelayAdminService` usage reports over a meter and an in-memory database.
+
+use std::sync::Arc;
+
+use tonic::{Code, Request};
+
+use betcode_proto::v1::relay_admin_service_server::RelayAdminService;
+use betcode_proto::v1::{GetUsageRequest, UsageGrouping};
+
+use super::*;
+use crate::storage::RelayDatabase;
+
+const TOKEN: &str = "admin-token-for-tests";
+
+async fn service() -> (RelayAdminServiceImpl, Arc<UsageMeter>) {
+    let db = RelayDatabase::open_in_memory().await.unwrap();
+    let meter = Arc::new(UsageMeter::new());
+    (
+        RelayAdminServiceImpl::new(db, Arc::clone(&meter), TOKEN),
+        meter,
+    )
+}
+
+fn request(token: &str, req: GetUsageRequest) -> Request<GetUsageRequest> {
+    let mut request = Request::new(req);
+    request
+        .metadata_mut()
+        .insert(ADMIN_TOKEN_HEADER, token.parse().unwrap());
+    request
+}
+
+#[tokio::test]
+async fn get_usage_requires_admin_token() {
+    let (svc, _meter) = service().await;
+
+    let err = svc
+        .get_usage(Request::new(GetUsageRequest::default()))
+        .await
+        .unwrap_err();
+    assert_eq!(err.code(), Code::Unauthenticated);
+
+    let err = svc
+        .get_usage(request("not-the-admin-token", GetUsageRequest::default()))
+        .await
+        .unwrap_err();
+    assert_eq!(err.code(), Code::Unauthenticated);
+}
+
+#[tokio::test]
+async fn get_usage_reports_unflushed_traffic_by_machine() {
+    let (svc, meter) = service().await;
+    meter.record("m1", "u1", "Agent/Converse", Direction::Sent, 1000);
+    meter.record("m1", "u1", "Command/List", Direction::Received, 500);
+    meter.record("m2", "u2", "Agent/Converse", Direction::Sent, 10);
+
+    let records = svc
+        .get_usage(request(
+            TOKEN,
+            GetUsageRequest {
+                group_by: UsageGrouping::Machine as i32,
+                ..Default::default()
+            },
+        ))
+        .await
+        .unwrap()
+        .into_inner()
+        .records;
+
+    assert_eq!(records.len(), 2);
+    // Heaviest machine first
+    assert_eq!(records[0].machine_id, "m1");
+    assert_eq!(records[0].owner_id, "u1");
+    assert_eq!(records[0].method, "");
+    assert_eq!(records[0].frames_sent, 1);
+    assert_eq!(records[0].bytes_sent, 1000);
+    assert_eq!(records[0].bytes_received, 500);
+    assert!(records[0].hour_start.is_none());
+    assert_eq!(records[1].machine_id, "m2");
+}
+
+#[tokio::test]
+async fn get_usage_filters_hourly_rows() {
+    let (svc, meter) = service().await;
+    meter.record("m1", "u1", "Agent/Converse", Direction::Sent, 1000);
+    meter.record("m2", "u2", "Agent/Converse", Direction::Sent, 10);
+
+    let records = svc
+        .get_usage(request(
+            TOKEN,
+            GetUsageRequest {
+                owner_id: "u2".into(),
+                ..Default::default()
+            },
+        ))
+        .await
+        .unwrap()
+        .into_inner()
+        .records;
+
+    assert_eq!(records.len(), 1);
+    assert_eq!(records[0].machine_id, "m2");
+    assert_eq!(records[0].method, "Agent/Converse");
+    assert!(records[0].hour_start.is_some());
+}
+
+#[tokio::test]
+async fn get_usage_rejects_unknown_grouping() {
+    let (svc, _meter) = service().await;
+    let err = svc
+        .get_usage(request(
+            TOKEN,
+            GetUsageRequest {
+                group_by: 99,
+                ..Default::default()
+            },
+        ))
+        .await
+        .unwrap_err();
+    assert_eq!(err.code(), Code::InvalidArgument);
+}
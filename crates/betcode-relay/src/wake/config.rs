@@ -0,0 +1,97 @@
+//! Wake configuration stored in machine metadata.
+
+use std::collections::HashMap;
+
+use betcode_core::wake::{WakeError, WakeTarget};
+
+/// Metadata key holding the machine's MAC address.
+pub const META_MAC: &str = "wake_mac";
+
+/// Metadata key holding the ID of the machine that sends the magic packet.
+pub const META_VIA: &str = "wake_via";
+
+/// Metadata key holding the LAN broadcast address (optional).
+pub const META_BROADCAST: &str = "wake_broadcast";
+
+/// Metadata key holding the UDP port (optional).
+pub const META_PORT: &str = "wake_port";
+
+/// How to wake a machine.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WakeConfig {
+    pub target: WakeTarget,
+    /// Machine whose daemon broadcasts the magic packet.
+    pub waker_machine_id: String,
+}
+
+impl WakeConfig {
+    /// Read the wake configuration from a machine's metadata JSON.
+    ///
+    /// Returns `Ok(None)` when the machine has no MAC address configured.
+    pub fn from_metadata(metadata_json: &str) -> Result<Option<Self>, WakeError> {
+        let metadata: HashMap<String, String> =
+            serde_json::from_str(metadata_json).unwrap_or_default();
+        let get = |key: &str| metadata.get(key).map_or("", String::as_str);
+        if get(META_MAC).is_empty() {
+            return Ok(None);
+        }
+        let waker_machine_id = get(META_VIA);
+        if waker_machine_id.is_empty() {
+            return Err(WakeError::Missing(META_VIA));
+        }
+        Ok(Some(Self {
+            target: WakeTarget::parse(get(META_MAC), get(META_BROADCAST), get(META_PORT))?,
+            waker_machine_id: waker_machine_id.to_string(),
+        }))
+    }
+
+    /// Write this configuration into `metadata`, replacing any previous one.
+    pub fn apply(&self, metadata: &mut HashMap<String, String>) {
+        metadata.insert(META_MAC.to_string(), self.target.mac.to_string());
+        metadata.insert(META_VIA.to_string(), self.waker_machine_id.clone());
+        metadata.insert(
+            META_BROADCAST.to_string(),
+            self.target.broadcast.to_string(),
+        );
+        metadata.insert(META_PORT.to_string(), self.target.port.to_string());
+    }
+
+    /// Remove any wake configuration from `metadata`.
+    pub fn clear(metadata: &mut HashMap<String, String>) {
+        for key in [META_MAC, META_VIA, META_BROADCAST, META_PORT] {
+            metadata.remove(key);
+        }
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::panic, clippy::expect_used, clippy::unwrap_used)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips_through_metadata() {
+        let config = WakeConfig {
+            target: WakeTarget::parse("aa:bb:cc:dd:ee:ff", "10.0.0.255", "7").unwrap(),
+            waker_machine_id: "nas".into(),
+        };
+        let mut metadata = HashMap::from([("os".to_string(), "linux".to_string())]);
+        config.apply(&mut metadata);
+        let json = serde_json::to_string(&metadata).unwrap();
+        assert_eq!(WakeConfig::from_metadata(&json).unwrap(), Some(config));
+
+        WakeConfig::clear(&mut metadata);
+        assert_eq!(metadata.len(), 1);
+        let json = serde_json::to_string(&metadata).unwrap();
+        assert_eq!(WakeConfig::from_metadata(&json).unwrap(), None);
+    }
+
+    #[test]
+    fn mac_without_waker_is_an_error() {
+        let json = r#"{"wake_mac":"aa:bb:cc:dd:ee:ff"}"#;
+        assert_eq!(
+            WakeConfig::from_metadata(json),
+            Err(WakeError::Missing(META_VIA))
+        );
+    }
+}
@@ -0,0 +1,322 @@
+//! Wake manager: asks a waker daemon to wake an offline machine.
+
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::sync::Mutex;
+use tokio::time::Instant;
+use tracing::{info, warn};
+
+use betcode_proto::v1::{FrameType, TunnelControl, TunnelControlType, TunnelFrame};
+
+use super::WakeConfig;
+use crate::registry::ConnectionRegistry;
+use crate::storage::RelayDatabase;
+
+/// How often to check whether a woken machine has connected.
+const WATCH_INTERVAL: Duration = Duration::from_secs(1);
+
+/// What a wake request did.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WakeOutcome {
+    /// The magic packet was handed to the waker.
+    Sent,
+    /// An earlier wake is still waiting for the machine to connect.
+    InProgress,
+    /// The machine is already connected.
+    AlreadyOnline,
+}
+
+/// Wake operation errors.
+#[derive(Debug, thiserror::Error)]
+pub enum WakeError {
+    #[error("Wake-on-LAN not configured for machine {0}")]
+    NotConfigured(String),
+
+    #[error("Invalid wake configuration for machine {machine_id}: {reason}")]
+    InvalidConfig { machine_id: String, reason: String },
+
+    #[error("Waker {waker} is not a machine of the owner of {machine_id}")]
+    WakerNotOwned { machine_id: String, waker: String },
+
+    #[error("Waker machine offline: {0}")]
+    WakerOffline(String),
+
+    #[error("Failed to send wake request to {0}")]
+    SendFailed(String),
+
+    #[error("Storage error: {0}")]
+    Storage(String),
+}
+
+/// Wakes offline machines through waker daemons on their LAN.
+pub struct WakeManager {
+    db: RelayDatabase,
+    registry: Arc<ConnectionRegistry>,
+    /// How long to wait for a woken machine's tunnel before giving up.
+    wait_timeout: Duration,
+    /// Machines with a wake in flight.
+    waking: Arc<Mutex<HashSet<String>>>,
+}
+
+impl WakeManager {
+    pub fn new(
+        db: RelayDatabase,
+        registry: Arc<ConnectionRegistry>,
+        wait_timeout: Duration,
+    ) -> Self {
+        Self {
+            db,
+            registry,
+            wait_timeout,
+            waking: Arc::new(Mutex::new(HashSet::new())),
+        }
+    }
+
+    /// Wake `machine_id` if it is offline and has a wake configuration.
+    ///
+    /// At most one wake per machine is in flight; until the machine connects
+    /// or the wait times out, further calls return `WakeOutcome::InProgress`.
+    pub async fn wake(&self, machine_id: &str) -> Result<WakeOutcome, WakeError> {
+        if self.registry.is_connected(machine_id).await {
+            return Ok(WakeOutcome::AlreadyOnline);
+        }
+        if self.waking.lock().await.contains(machine_id) {
+            return Ok(WakeOutcome::InProgress);
+        }
+
+        let machine = self
+            .db
+            .get_machine(machine_id)
+            .await
+            .map_err(|e| WakeError::Storage(e.to_string()))?;
+        let config = WakeConfig::from_metadata(&machine.metadata)
+            .map_err(|e| WakeError::InvalidConfig {
+                machine_id: machine_id.to_string(),
+                reason: e.to_string(),
+            })?
+            .ok_or_else(|| WakeError::NotConfigured(machine_id.to_string()))?;
+
+        // The waker must belong to the same owner; its tunnel was
+        // authenticated as them, so it can be trusted with the MAC address.
+        let not_owned = || WakeError::WakerNotOwned {
+            machine_id: machine_id.to_string(),
+            waker: config.waker_machine_id.clone(),
+        };
+        let waker = self
+            .db
+            .get_machine(&config.waker_machine_id)
+            .await
+            .map_err(|_| not_owned())?;
+        if waker.owner_id != machine.owner_id || waker.id == machine.id {
+            return Err(not_owned());
+        }
+        let Some(conn) = self.registry.get(&waker.id).await else {
+            return Err(WakeError::WakerOffline(waker.id));
+        };
+
+        if !self.waking.lock().await.insert(machine_id.to_string()) {
+            return Ok(WakeOutcome::InProgress);
+        }
+        if conn
+            .send_frame(wake_frame(machine_id, &config))
+            .await
+            .is_err()
+        {
+            self.waking.lock().await.remove(machine_id);
+            return Err(WakeError::SendFailed(waker.id));
+        }
+        info!(
+            machine_id = %machine_id,
+            waker = %waker.id,
+            mac = %config.target.mac,
+            "Wake request sent"
+        );
+
+        tokio::spawn(watch(
+            machine_id.to_string(),
+            Arc::clone(&self.registry),
+            Arc::clone(&self.waking),
+            self.wait_timeout,
+        ));
+        Ok(WakeOutcome::Sent)
+    }
+}
+
+/// Wait for a woken machine's tunnel, then clear its in-flight marker.
+///
+/// Buffered requests are drained by the tunnel service when it registers.
+async fn watch(
+    machine_id: String,
+    registry: Arc<ConnectionRegistry>,
+    waking: Arc<Mutex<HashSet<String>>>,
+    wait_timeout: Duration,
+) {
+    let started = Instant::now();
+    let deadline = started + wait_timeout;
+    let mut connected = registry.is_connected(&machine_id).await;
+    while !connected && Instant::now() < deadline {
+        tokio::time::sleep(WATCH_INTERVAL).await;
+        connected = registry.is_connected(&machine_id).await;
+    }
+    waking.lock().await.remove(&machine_id);
+    if connected {
+        info!(
+            machine_id = %machine_id,
+            elapsed_ms = u64::try_from(started.elapsed().as_millis()).unwrap_or(u64::MAX),
+            "Woken machine connected"
+        );
+    } else {
+        warn!(
+            machine_id = %machine_id,
+            timeout_secs = wait_timeout.as_secs(),
+            "Woken machine did not connect in time"
+        );
+    }
+}
+
+/// `WAKE` control frame asking a waker to wake `machine_id`.
+fn wake_frame(machine_id: &str, config: &WakeConfig) -> TunnelFrame {
+    let mut params = config.target.to_params();
+    params.insert("machine_id".to_string(), machine_id.to_string());
+    TunnelFrame {
+        request_id: String::new(),
+        frame_type: FrameType::Control as i32,
+        timestamp: Some(prost_types::Timestamp::from(std::time::SystemTime::now())),
+        payload: Some(betcode_proto::v1::tunnel_frame::Payload::Control(
+            TunnelControl {
+                control_type: TunnelControlType::Wake as i32,
+                params,
+            },
+        )),
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::panic, clippy::expect_used, clippy::unwrap_used)]
+mod tests {
+    use super::*;
+    use crate::server::test_helpers::test_db_with_two_users;
+    use betcode_proto::v1::tunnel_frame::Payload;
+    use tokio::sync::mpsc;
+
+    const SLEEPER_METADATA: &str =
+        r#"{"wake_mac":"aa:bb:cc:dd:ee:ff","wake_via":"nas","wake_broadcast":"10.0.0.255"}"#;
+
+    /// "m1" (u1) sleeps and is woken through "nas" (u1), which is online.
+    async fn setup(
+        wait_timeout: Duration,
+    ) -> (
+        WakeManager,
+        Arc<ConnectionRegistry>,
+        mpsc::Receiver<TunnelFrame>,
+    ) {
+        let db = test_db_with_two_users().await;
+        db.update_machine_metadata("m1", SLEEPER_METADATA)
+            .await
+            .unwrap();
+        db.create_machine("nas", "nas", "u1", "{}").await.unwrap();
+        let registry = Arc::new(ConnectionRegistry::new());
+        let (tx, rx) = mpsc::channel(8);
+        registry.register("nas".into(), "u1".into(), tx).await;
+        let manager = WakeManager::new(db, Arc::clone(&registry), wait_timeout);
+        (manager, registry, rx)
+    }
+
+    #[tokio::test]
+    async fn sends_wake_frame_to_waker() {
+        let (manager, _registry, mut rx) = setup(Duration::from_mins(1)).await;
+
+        assert_eq!(manager.wake("m1").await.unwrap(), WakeOutcome::Sent);
+        let frame = rx.recv().await.unwrap();
+        let Some(Payload::Control(ctrl)) = frame.payload else {
+            panic!("expected control frame");
+        };
+        assert_eq!(ctrl.control_type, TunnelControlType::Wake as i32);
+        assert_eq!(ctrl.params["machine_id"], "m1");
+        assert_eq!(ctrl.params["mac"], "aa:bb:cc:dd:ee:ff");
+        assert_eq!(ctrl.params["broadcast"], "10.0.0.255");
+
+        // Only one wake in flight
+        assert_eq!(manager.wake("m1").await.unwrap(), WakeOutcome::InProgress);
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn wake_clears_after_machine_connects() {
+        let (manager, registry, _rx) = setup(Duration::from_mins(1)).await;
+        assert_eq!(manager.wake("m1").await.unwrap(), WakeOutcome::Sent);
+
+        let (tx, _m1_rx) = mpsc::channel(8);
+        registry.register("m1".into(), "u1".into(), tx).await;
+        assert_eq!(
+            manager.wake("m1").await.unwrap(),
+            WakeOutcome::AlreadyOnline
+        );
+        tokio::time::sleep(WATCH_INTERVAL * 2).await;
+        assert!(manager.waking.lock().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn wake_can_retry_after_timeout() {
+        let (manager, _registry, mut rx) = setup(Duration::ZERO).await;
+        assert_eq!(manager.wake("m1").await.unwrap(), WakeOutcome::Sent);
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        assert_eq!(manager.wake("m1").await.unwrap(), WakeOutcome::Sent);
+        assert!(rx.recv().await.is_some());
+        assert!(rx.recv().await.is_some());
+    }
+
+    #[tokio::test]
+    async fn rejects_unconfigured_and_offline_wakers() {
+        let (manager, registry, _rx) = setup(Duration::from_mins(1)).await;
+
+        manager
+            .db
+            .update_machine_metadata("m1", "{}")
+            .await
+            .unwrap();
+        assert!(matches!(
+            manager.wake("m1").await,
+            Err(WakeError::NotConfigured(_))
+        ));
+
+        manager
+            .db
+            .update_machine_metadata("m1", SLEEPER_METADATA)
+            .await
+            .unwrap();
+        registry.unregister("nas").await;
+        assert!(matches!(
+            manager.wake("m1").await,
+            Err(WakeError::WakerOffline(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn rejects_wakers_of_another_owner() {
+        let (manager, registry, _rx) = setup(Duration::from_mins(1)).await;
+        manager
+            .db
+            .create_machine("eve-box", "eve-box", "u2", "{}")
+            .await
+            .unwrap();
+        let (tx, _eve_rx) = mpsc::channel(8);
+        registry.register("eve-box".into(), "u2".into(), tx).await;
+        manager
+            .db
+            .update_machine_metadata(
+                "m1",
+                r#"{"wake_mac":"aa:bb:cc:dd:ee:ff","wake_via":"eve-box"}"#,
+            )
+            .await
+            .unwrap();
+
+        assert!(matches!(
+            manager.wake("m1").await,
+            Err(WakeError::WakerNotOwned { .. })
+        ));
+    }
+}
@@ -0,0 +1,12 @@
+//! Wake-on-LAN for offline machines.
+//!
+//! A machine that sleeps can name an always-on "waker" machine of the same
+//! owner on its LAN. When a request is buffered for the sleeping machine, the
+//! relay asks the waker (over its tunnel) to broadcast a magic packet, then
+//! watches for the sleeper's tunnel; the buffer drains when it registers.
+
+pub mod config;
+pub mod manager;
+
+pub use config::WakeConfig;
+pub use manager::{WakeError, WakeManager, WakeOutcome};